@@ -0,0 +1,788 @@
+//! Streaming batch computation of the fingerprints, composing with the shell pipelines
+//!
+//! Input is JSON lines of the items: `{"item_id": "...", "transaction": {...}, "salt": "..."}`, the transaction is
+//! in the format of the conformance vectors. Output is JSON lines of the results in the input order:
+//! `{"line": 1, "item_id": "...", "fingerprint": "..."}` or `{"line": 1, "item_id": "...", "error": "..."}`.
+//! Lines are read lazily and at most `concurrency` items are in flight, so the memory does not grow with the input.
+//!
+//! Long batches write the checkpoint: the keys of the computed items, `item_id` or the input line if the item has
+//! no id. The batch resumed after the crash skips the checkpointed items, the results written after the last
+//! checkpoint flush are computed (and written) once more.
+//!
+//! The batch could be sharded across several instances of the fingerprint service, see `Endpoints`.
+//!
+//! Results could be validated against the output of the previous batch, see `ExpectedFingerprints`, e.g. to check
+//! the parity of the upgraded environment.
+//!
+//! Failed items could be written to the dead-letter output along with their structured error, see `write_dead_letter`,
+//! the `input` of every dead letter is the item as it was read, so the dead letters are reprocessed as the batch.
+
+use crate::conformance::VectorTransaction;
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use fingerprinting_grpc::derive_item_id;
+use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+    Fingerprint as FingerprintDto, TransactionFingerprintData,
+};
+use futures::future::{ready, Either};
+use futures::StreamExt;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How often the progress is reported and the checkpoint is flushed
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BatchItem {
+    /// Identifier of the item in the caller's system, passed through to the result
+    pub item_id: Option<String>,
+    pub transaction: VectorTransaction,
+    pub salt: Option<String>,
+    /// Data-residency tag, e.g. "EU", routing the item to the agents allowed for it
+    pub residency: Option<String>,
+}
+
+impl BatchItem {
+    pub fn transaction_data(&self) -> Result<TransactionFingerprintData, Error> {
+        let transaction_data: TransactionFingerprintData =
+            self.transaction.raw_transaction()?.try_into()?;
+
+        Ok(TransactionFingerprintData {
+            residency: self.residency.clone().unwrap_or_default().into(),
+            ..transaction_data
+        })
+    }
+
+    pub fn salt(&self) -> Bytes {
+        self.salt.clone().map(Bytes::from).unwrap_or_default()
+    }
+
+    /// Item with the id derived from its transaction and salt when it has none, the same id the service derives,
+    /// see `fingerprinting_grpc::derive_item_id`. The item which transaction could not be read is left without the id
+    pub fn with_derived_item_id(self) -> Self {
+        if self.item_id.is_some() {
+            return self;
+        }
+        let item_id = self
+            .transaction
+            .raw_transaction()
+            .ok()
+            .map(|tx| derive_item_id(&tx, &self.salt()));
+
+        Self { item_id, ..self }
+    }
+}
+
+/// Stage the item failed at
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// Line is not the JSON of the item
+    InvalidItem,
+    /// Transaction of the item could not be converted, e.g. the unknown currency
+    InvalidTransaction,
+    /// Service refused or failed to compute the fingerprint
+    Service,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BatchError {
+    pub kind: FailureKind,
+    /// gRPC status code of the service failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<i32>,
+    pub message: String,
+}
+
+impl BatchError {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            code: None,
+            message: message.into(),
+        }
+    }
+
+    /// Failure of the service call, with the status code if the service returned one
+    pub fn service(e: &Error) -> Self {
+        Self {
+            kind: FailureKind::Service,
+            code: e
+                .downcast_ref::<volo_grpc::Status>()
+                .map(|status| status.code() as i32),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    /// Line of the item in the input, starting from 1
+    pub line: usize,
+    pub item_id: Option<String>,
+    pub fingerprint: Result<FingerprintDto, BatchError>,
+    /// Input line of the failed item, kept for the dead-letter output
+    pub input: Option<String>,
+}
+
+impl BatchResult {
+    /// Key of the item in the checkpoint
+    pub fn key(&self) -> String {
+        item_key(self.item_id.as_deref(), self.line)
+    }
+}
+
+fn item_key(item_id: Option<&str>, line: usize) -> String {
+    match item_id {
+        Some(item_id) => item_id.to_string(),
+        None => format!("line:{}", line),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub computed: usize,
+    pub failed: usize,
+    /// Items computed before the resumed batch
+    pub skipped: usize,
+}
+
+#[derive(Serialize)]
+struct JsonResult<'a> {
+    line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<&'a str>,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    namespace: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+/// Computes the fingerprints of the `input` items via `send`, passing the results to `output` in the input order.
+/// Items with the keys in `completed` are skipped, the items with no id get the derived one with `derive_item_ids`.
+/// Invalid item fails only the item, failure to read the input or to write the result ends the batch
+pub async fn run_batch<R, S, F, O>(
+    input: R,
+    concurrency: usize,
+    completed: &HashSet<String>,
+    derive_item_ids: bool,
+    send: S,
+    mut output: O,
+) -> Result<BatchSummary, Error>
+where
+    R: BufRead,
+    S: Fn(TransactionFingerprintData, Bytes) -> F,
+    F: Future<Output = Result<FingerprintDto, Error>>,
+    O: FnMut(BatchResult, &BatchSummary) -> Result<(), Error>,
+{
+    let mut results = futures::stream::iter(input.lines().enumerate())
+        .filter(|(_, line)| ready(!matches!(line, Ok(line) if line.trim().is_empty())))
+        .map(|(index, line)| {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    return Either::Left(ready(Err(anyhow!(
+                        "Failed to read line {}: {}",
+                        index + 1,
+                        e
+                    ))))
+                }
+            };
+            let item = serde_json::from_str::<BatchItem>(&line).map(|item| match derive_item_ids {
+                true => item.with_derived_item_id(),
+                false => item,
+            });
+            let (item_id, sent) = match item {
+                Ok(item) if completed.contains(&item_key(item.item_id.as_deref(), index + 1)) => {
+                    return Either::Left(ready(Ok(None)))
+                }
+                Ok(item) => {
+                    let sent = item
+                        .transaction_data()
+                        .map(|transaction_data| send(transaction_data, item.salt()))
+                        .map_err(|e| {
+                            BatchError::new(FailureKind::InvalidTransaction, e.to_string())
+                        });
+                    (item.item_id, sent)
+                }
+                Err(e) => (
+                    None,
+                    Err(BatchError::new(
+                        FailureKind::InvalidItem,
+                        format!("Invalid item: {}", e),
+                    )),
+                ),
+            };
+
+            Either::Right(async move {
+                let fingerprint = match sent {
+                    Ok(sent) => sent.await.map_err(|e| BatchError::service(&e)),
+                    Err(e) => Err(e),
+                };
+                let input = fingerprint.is_err().then_some(line);
+
+                Ok(Some(BatchResult {
+                    line: index + 1,
+                    item_id,
+                    fingerprint,
+                    input,
+                }))
+            })
+        })
+        .buffered(concurrency);
+
+    let mut summary = BatchSummary::default();
+    while let Some(result) = results.next().await {
+        let Some(result) = result? else {
+            summary.skipped += 1;
+            continue;
+        };
+        match result.fingerprint {
+            Ok(_) => summary.computed += 1,
+            Err(_) => summary.failed += 1,
+        }
+        output(result, &summary)?;
+    }
+
+    Ok(summary)
+}
+
+/// Writes the result as the JSON line with the compact fingerprint
+pub fn write_json<W: Write>(result: &BatchResult, output: &mut W) -> Result<(), Error> {
+    let (fingerprint, namespace, error) = match &result.fingerprint {
+        Ok(fingerprint) => (
+            Some(fingerprint.compact_fingerprint.as_str()),
+            fingerprint.namespace.as_str(),
+            None,
+        ),
+        Err(e) => (None, "", Some(e.message.as_str())),
+    };
+
+    serde_json::to_writer(
+        &mut *output,
+        &JsonResult {
+            line: result.line,
+            item_id: result.item_id.as_deref(),
+            fingerprint,
+            namespace,
+            error,
+        },
+    )?;
+    writeln!(output)?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct DeadLetter<'a> {
+    line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item_id: Option<&'a str>,
+    /// Item as it was read, the line itself if it is not JSON
+    input: serde_json::Value,
+    error: &'a BatchError,
+}
+
+/// Writes the failed item as the JSON line of its input and the structured error, computed items are not written.
+/// `jq -c .input` turns the dead letters back into the items of the batch
+pub fn write_dead_letter<W: Write>(result: &BatchResult, output: &mut W) -> Result<(), Error> {
+    let Err(error) = &result.fingerprint else {
+        return Ok(());
+    };
+    let input = result.input.as_deref().unwrap_or_default();
+
+    serde_json::to_writer(
+        &mut *output,
+        &DeadLetter {
+            line: result.line,
+            item_id: result.item_id.as_deref(),
+            input: serde_json::from_str(input)
+                .unwrap_or_else(|_| serde_json::Value::String(input.to_string())),
+            error,
+        },
+    )?;
+    writeln!(output)?;
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ExpectedResult {
+    line: usize,
+    item_id: Option<String>,
+    fingerprint: Option<String>,
+}
+
+/// Fingerprints of the previous batch (JSON lines output) the results are expected to match, by the item key
+pub struct ExpectedFingerprints {
+    fingerprints: HashMap<String, String>,
+    checked: HashSet<String>,
+}
+
+impl ExpectedFingerprints {
+    /// Reads the output of the previous batch, failed items of it are not expected
+    pub fn read<R: BufRead>(input: R) -> Result<Self, Error> {
+        let mut fingerprints = HashMap::new();
+        for (number, line) in input.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let expected: ExpectedResult = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("Invalid expected result at line {}: {}", number + 1, e))?;
+            if let Some(fingerprint) = expected.fingerprint {
+                fingerprints.insert(
+                    item_key(expected.item_id.as_deref(), expected.line),
+                    fingerprint,
+                );
+            }
+        }
+
+        Ok(Self {
+            fingerprints,
+            checked: HashSet::new(),
+        })
+    }
+
+    /// Difference of the result from the expected fingerprint, `None` if the fingerprint is the expected one
+    pub fn check(&mut self, result: &BatchResult) -> Option<String> {
+        let key = result.key();
+        let Some(expected) = self.fingerprints.get(&key) else {
+            return Some("No fingerprint is expected".to_string());
+        };
+        self.checked.insert(key);
+
+        match &result.fingerprint {
+            Ok(fingerprint) if fingerprint.compact_fingerprint == expected.as_str() => None,
+            Ok(fingerprint) => Some(format!(
+                "Expected {}, computed {}",
+                expected, fingerprint.compact_fingerprint
+            )),
+            Err(e) => Some(format!("Expected {}, failed: {}", expected, e)),
+        }
+    }
+
+    /// Keys of the expected items missing in the batch, `completed` items of the resumed batch are not missing
+    pub fn missing(&self, completed: &HashSet<String>) -> Vec<&str> {
+        let mut missing = self
+            .fingerprints
+            .keys()
+            .filter(|key| !self.checked.contains(*key) && !completed.contains(*key))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing
+    }
+}
+
+/// Clients of the fingerprint service instances sharing the batch, every instance is called within its rate limit
+pub struct Endpoints<C> {
+    clients: Vec<C>,
+    /// Earliest time of the next call of every instance
+    slots: Mutex<Vec<Instant>>,
+    /// Time between the calls of the instance, zero without the rate limit
+    interval: Duration,
+    next: AtomicUsize,
+}
+
+impl<C: Clone> Endpoints<C> {
+    /// Endpoints of the `clients`, `rate_limit` is the calls per second of every instance
+    pub fn new(clients: Vec<C>, rate_limit: Option<NonZeroU32>) -> Result<Self, Error> {
+        if clients.is_empty() {
+            return Err(anyhow!("At least one endpoint is required"));
+        }
+        let now = Instant::now();
+
+        Ok(Self {
+            slots: Mutex::new(vec![now; clients.len()]),
+            clients,
+            interval: rate_limit
+                .map(|rate_limit| Duration::from_secs(1) / rate_limit.get())
+                .unwrap_or_default(),
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Waits for the instance available first, instances equally available are taken in turns
+    pub async fn acquire(&self) -> C {
+        let (index, slot) = {
+            let mut slots = self.slots.lock().unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            let index = (0..slots.len())
+                .map(|i| (start + i) % slots.len())
+                .min_by_key(|i| slots[*i].max(now))
+                .unwrap_or_default();
+            let slot = slots[index].max(now);
+            slots[index] = slot + self.interval;
+            (index, slot)
+        };
+        tokio::time::sleep_until(slot.into()).await;
+
+        self.clients[index].clone()
+    }
+}
+
+/// Reads the keys of the checkpointed items
+pub fn read_checkpoint(path: &Path) -> Result<HashSet<String>, Error> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+        .map(|line| Ok(line?))
+        .collect()
+}
+
+/// Appends the keys of the computed items to the checkpoint
+pub struct CheckpointWriter {
+    file: BufWriter<File>,
+}
+
+impl CheckpointWriter {
+    /// Checkpoint at `path`, the existing one is kept only if the batch is resumed
+    pub fn open(path: &Path, resume: bool) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(path)?;
+
+        Ok(Self {
+            file: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, result: &BatchResult) -> Result<(), Error> {
+        Ok(writeln!(self.file, "{}", result.key())?)
+    }
+
+    /// Should be called after the results are flushed, so the checkpointed items are never lost
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.file.flush()?)
+    }
+}
+
+/// Reader counting the bytes read, the progress of the input of the known size
+pub struct CountingReader<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            read: Arc::default(),
+        }
+    }
+
+    /// Counter of the bytes read, shared with the progress
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        self.read.clone()
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+/// Progress of the batch, the bar and the ETA are shown only for the input of the known size
+pub struct Progress {
+    started: Instant,
+    reported: Instant,
+    read: Arc<AtomicU64>,
+    total: Option<u64>,
+}
+
+impl Progress {
+    pub fn new(read: Arc<AtomicU64>, total: Option<u64>) -> Self {
+        let now = Instant::now();
+
+        Self {
+            started: now,
+            reported: now,
+            read,
+            total: total.filter(|total| *total > 0),
+        }
+    }
+
+    /// The report is due every `PROGRESS_INTERVAL`
+    pub fn due(&mut self) -> bool {
+        if self.reported.elapsed() < PROGRESS_INTERVAL {
+            return false;
+        }
+        self.reported = Instant::now();
+        true
+    }
+
+    pub fn describe(&self, summary: &BatchSummary) -> String {
+        let elapsed = self.started.elapsed();
+        let processed = summary.computed + summary.failed;
+        let mut description = format!(
+            "{} items ({} failed, {} skipped), {:.0} items/s",
+            processed,
+            summary.failed,
+            summary.skipped,
+            processed as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+        );
+
+        if let Some(total) = self.total {
+            let fraction = (self.read.load(Ordering::Relaxed) as f64 / total as f64).min(1.0);
+            let filled = (fraction * PROGRESS_BAR_WIDTH as f64) as usize;
+            let bar = format!(
+                "[{}{}] {:.1}%",
+                "#".repeat(filled),
+                ".".repeat(PROGRESS_BAR_WIDTH - filled),
+                fraction * 100.0
+            );
+            description = format!("{} {}", bar, description);
+
+            if fraction > 0.0 {
+                let remaining = elapsed.as_secs_f64() * (1.0 - fraction) / fraction;
+                let remaining = remaining as u64;
+                description.push_str(&format!(
+                    ", ETA {:02}:{:02}:{:02}",
+                    remaining / 3600,
+                    remaining / 60 % 60,
+                    remaining % 60
+                ));
+            }
+        }
+
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fingerprinting_core::NaiveProtocol;
+    use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+        ComputeSingleFingerprintRequest, FingerprintService as _,
+    };
+    use fingerprinting_grpc::FingerprintService;
+    use halo2_axiom::halo2curves::bn256::Fr;
+    use std::sync::Arc;
+    use volo_grpc::Request;
+
+    const TRANSACTION: &str = r#"{"bic": "BCEELU21", "amount": "1000.55", "currency": "EUR", "date_time": "2025-09-16T12:30:15Z"}"#;
+
+    fn json_lines(output: Vec<u8>) -> Result<Vec<serde_json::Value>, Error> {
+        Ok(String::from_utf8(output)?
+            .lines()
+            .map(serde_json::from_str::<serde_json::Value>)
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn batch(
+        input: &str,
+        completed: &HashSet<String>,
+        checkpoint: &mut CheckpointWriter,
+    ) -> Result<(BatchSummary, Vec<serde_json::Value>, Vec<serde_json::Value>), Error> {
+        let service = Arc::new(FingerprintService::new(NaiveProtocol::new(Fr::from(42))));
+
+        let mut output = vec![];
+        let mut dead_letters = vec![];
+        let summary = run_batch(
+            input.as_bytes(),
+            2,
+            completed,
+            false,
+            |transaction_data, salt| {
+                let service = service.clone();
+                async move {
+                    let response = service
+                        .compute_single_fingerprint(Request::new(ComputeSingleFingerprintRequest {
+                            transaction_data: Some(transaction_data),
+                            salt,
+                            with_commitments: false,
+                            encodings: Default::default(),
+                            validate_only: false,
+                            _unknown_fields: Default::default(),
+                        }))
+                        .await?;
+
+                    Ok(response.into_inner().fingerprint.unwrap_or_default())
+                }
+            },
+            |result, _| {
+                write_json(&result, &mut output)?;
+                write_dead_letter(&result, &mut dead_letters)?;
+                match result.fingerprint {
+                    Ok(_) => checkpoint.record(&result),
+                    Err(_) => Ok(()),
+                }
+            },
+        )
+        .await?;
+        checkpoint.flush()?;
+
+        Ok((summary, json_lines(output)?, json_lines(dead_letters)?))
+    }
+
+    #[test]
+    fn test_expected_fingerprints() -> Result<(), Error> {
+        let expected = r#"{"line": 1, "item_id": "a", "fingerprint": "A"}
+{"line": 2, "fingerprint": "B"}
+{"line": 3, "item_id": "c", "error": "Invalid item"}
+{"line": 4, "item_id": "d", "fingerprint": "D"}"#;
+        let mut expected = ExpectedFingerprints::read(expected.as_bytes())?;
+
+        let result = |line, item_id: Option<&str>, fingerprint: Result<&str, &str>| BatchResult {
+            line,
+            item_id: item_id.map(str::to_string),
+            fingerprint: fingerprint
+                .map(|fingerprint| FingerprintDto {
+                    compact_fingerprint: fingerprint.to_string().into(),
+                    ..Default::default()
+                })
+                .map_err(|e| BatchError::new(FailureKind::InvalidItem, e)),
+            input: None,
+        };
+        assert_eq!(expected.check(&result(1, Some("a"), Ok("A"))), None);
+        assert_eq!(
+            expected.check(&result(2, None, Ok("X"))),
+            Some("Expected B, computed X".to_string())
+        );
+        assert!(expected
+            .check(&result(3, Some("c"), Err("Invalid item")))
+            .is_some());
+        assert_eq!(expected.missing(&HashSet::new()), vec!["d"]);
+        assert!(expected
+            .missing(&HashSet::from(["d".to_string()]))
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_derived_item_id() -> Result<(), Error> {
+        let item = |item: &str| serde_json::from_str::<BatchItem>(item);
+        let derived = item(&format!(
+            r#"{{"transaction": {}, "salt": "salt"}}"#,
+            TRANSACTION
+        ))?
+        .with_derived_item_id();
+        let raw_tx = derived.transaction.raw_transaction()?;
+        assert_eq!(derived.item_id, Some(derive_item_id(&raw_tx, b"salt")));
+
+        // Ids of the caller are kept, the unreadable transaction is left without one
+        let kept = item(&format!(
+            r#"{{"item_id": "a", "transaction": {}}}"#,
+            TRANSACTION
+        ))?;
+        assert_eq!(kept.with_derived_item_id().item_id.as_deref(), Some("a"));
+        let invalid = item(&format!(
+            r#"{{"transaction": {}}}"#,
+            TRANSACTION.replace("1000.55", "many")
+        ))?;
+        assert!(invalid.with_derived_item_id().item_id.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_endpoints() -> Result<(), Error> {
+        assert!(Endpoints::<usize>::new(vec![], None).is_err());
+
+        let endpoints = Endpoints::new(vec![0, 1], NonZeroU32::new(10))?;
+        let started = Instant::now();
+        let mut calls = [0; 2];
+        for _ in 0..6 {
+            calls[endpoints.acquire().await] += 1;
+        }
+        assert_eq!(calls, [3, 3]);
+        // Third call of every instance waits for 2 intervals of 100 ms
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_batch() -> Result<(), Error> {
+        let input = [
+            format!(r#"{{"item_id": "a", "transaction": {}}}"#, TRANSACTION),
+            String::new(),
+            format!(
+                r#"{{"item_id": "b", "transaction": {}, "salt": "salt"}}"#,
+                TRANSACTION
+            ),
+            r#"{"item_id": "c", "transaction": {}}"#.to_string(),
+            format!(
+                r#"{{"transaction": {}}}"#,
+                TRANSACTION.replace("EUR", "XXY")
+            ),
+        ]
+        .join("\n");
+        let path = std::env::temp_dir().join(format!("batch-{}.checkpoint", std::process::id()));
+
+        let mut checkpoint = CheckpointWriter::open(&path, false)?;
+        let (summary, results, dead_letters) =
+            batch(&input, &HashSet::new(), &mut checkpoint).await?;
+        assert_eq!(
+            summary,
+            BatchSummary {
+                computed: 2,
+                failed: 2,
+                skipped: 0
+            }
+        );
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0]["item_id"], "a");
+        assert_eq!(results[1]["line"], 3);
+        assert_ne!(results[0]["fingerprint"], results[1]["fingerprint"]);
+        assert!(results[2]["error"].is_string());
+        assert!(results[3]["item_id"].is_null());
+        assert!(results[3]["error"].as_str().unwrap().contains("ISO 4217"));
+
+        // Dead letters carry the failed items as they were read along with the stage they failed at
+        assert_eq!(dead_letters.len(), 2);
+        assert_eq!(dead_letters[0]["line"], 4);
+        assert!(dead_letters[0]["item_id"].is_null());
+        assert_eq!(dead_letters[0]["input"]["item_id"], "c");
+        assert_eq!(dead_letters[0]["error"]["kind"], "invalid_item");
+        assert_eq!(dead_letters[1]["error"]["kind"], "invalid_transaction");
+        assert_eq!(dead_letters[1]["input"]["transaction"]["currency"], "XXY");
+        let reprocessed = serde_json::from_value::<BatchItem>(dead_letters[1]["input"].clone())?;
+        assert!(reprocessed.transaction_data().is_err());
+
+        // Computed items are skipped by the resumed batch, failed ones are retried
+        let completed = read_checkpoint(&path)?;
+        assert_eq!(completed, HashSet::from(["a".to_string(), "b".to_string()]));
+        let mut checkpoint = CheckpointWriter::open(&path, true)?;
+        let (summary, results, _) = batch(&input, &completed, &mut checkpoint).await?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["line"], 4);
+
+        Ok(())
+    }
+}
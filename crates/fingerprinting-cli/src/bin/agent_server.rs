@@ -1,13 +1,57 @@
+use anyhow::anyhow;
+use bytes::Bytes;
 use clap::Parser;
-use fingerprinting_cli::config::{FingerprintServiceConfig, GrpcConfig};
-use fingerprinting_core::{CollaborativeProtocol, Compact, NaiveProtocol};
-use fingerprinting_grpc::{net as fp, FingerprintService};
-use fingerprinting_grpc_agent::{net as fp_agent, CooperationAgentService, GrpcAgentsTopology};
+use fingerprinting_cli::capacity::{CapacityLog, CapacitySample};
+use fingerprinting_cli::config::{
+    self, AgentTlsConfig, ArchiveConfig, EntropyConfig, FingerprintServiceConfig,
+    FingerprintingServiceConfig, GrpcConfig, StoreConfig,
+};
+use fingerprinting_cli::snapshot::CoordinatorState;
+use fingerprinting_cli::{downgrade, rest};
+use fingerprinting_core::breaker::CircuitBreakers;
+use fingerprinting_core::clock::{self, Clock, Epoch, SimulatedClock};
+use fingerprinting_core::entropy::{self, EntropySource};
+use fingerprinting_core::events::{self, BroadcastEventBus, EventBus};
+use fingerprinting_core::namespace::Namespace;
+use fingerprinting_core::pii::{self, PiiPolicy};
+use fingerprinting_core::pseudonym::BicPseudonymizer;
+use fingerprinting_core::schema::FingerprintSchema;
+use fingerprinting_core::warmup;
+use fingerprinting_core::wire::WireVersion;
+use fingerprinting_core::{
+    CollaborativeProtocol, Compact, FingerprintProtocol, HierarchicalProtocol,
+    InProcessAgentsTopology, Members, NaiveProtocol, PhaseMetrics, TimeBucket,
+};
+use fingerprinting_grpc::{
+    net as fp, FingerprintRecorder, FingerprintSampling, FingerprintService, FingerprintStatusHub,
+    IdempotencyCache, LoadShedding, ResidencyRouting, SchedulerLimits, ServiceCounters,
+    ShadowFingerprinting, TopologyStatus, UsageAccounting,
+};
+use fingerprinting_grpc_agent::signature::FileTranscript;
+use fingerprinting_grpc_agent::{
+    grpc as fp_health, net as fp_agent, AgentAdmin, AgentHealth, CooperationAgentService,
+    EvaluationLimits, GrpcAgentsTopology, Maintenance, RegionalEvaluation, SpiffeSource,
+};
+use fingerprinting_offline_agent::{OfflineAgentsTopology, SigningKey};
+use fingerprinting_p2p_agent::P2pAgentsTopology;
+use fingerprinting_store::archive::{ArchivedFingerprintStore, FingerprintArchive};
+use fingerprinting_store::merkle::{EpochAccumulator, EpochProofs};
+use fingerprinting_store::tally::{MemoryTallyLedger, TallyLedger};
+use fingerprinting_store::usage::{MemoryUsageLedger, UsageLedger};
+use fingerprinting_store::{
+    DuplicateWindow, FingerprintStore, MemoryFingerprintStore, PostgresFingerprintStore,
+    SqliteFingerprintStore, StoredFingerprint,
+};
+use fingerprinting_types::InputLimits;
 use halo2_axiom::halo2curves::bn256::Fr;
 use hocon::HoconLoader;
-use serde_derive::Deserialize;
 use std::net::SocketAddr;
-use volo_grpc::codegen::futures;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use volo_grpc::codegen::futures::{self, FutureExt, TryFutureExt};
 use volo_grpc::server::{Server, ServiceBuilder};
 
 #[derive(Parser, Debug)]
@@ -17,16 +61,12 @@ struct Args {
     /// Config file location
     #[arg(long)]
     config: String,
-}
 
-#[derive(Deserialize)]
-struct FingerprintingServiceConfig {
-    grpc: GrpcConfig,
-    #[serde(rename = "agent-grpc")]
-    agent_grpc: GrpcConfig,
-    #[serde(rename = "fingerprint-service")]
-    fingerprint_service: FingerprintServiceConfig,
+    /// Acknowledges the weaker protocol configuration than the one recorded in the `state-file`
+    #[arg(long)]
+    allow_downgrade: bool,
 }
+
 #[volo::main]
 async fn main() -> Result<(), anyhow::Error> {
     env_logger::builder()
@@ -43,52 +83,647 @@ async fn main() -> Result<(), anyhow::Error> {
         .load_str(reference_config)?
         .load_file(args.config)?
         .resolve()?;
+    conf.validate()?;
 
-    let (fingerprint_server, agent_server): (Server, Option<Server>) = match conf
-        .fingerprint_service
-    {
-        FingerprintServiceConfig::Cooperative(topology_config) => {
-            log::info!("== Starting CRA Fingerprint agent in Cooperative mode with {} agents and {} threshold", topology_config.agents, topology_config.threshold);
-            let topology = GrpcAgentsTopology::new(
-                topology_config.agents,
-                topology_config.threshold,
-                topology_config
-                    .members
-                    .iter()
-                    .map(|agent| (agent.agent_id, agent.address.to_string()))
-                    .collect(),
+    let clock: Arc<dyn Clock> = match conf.simulated_time {
+        Some(start) => {
+            log::warn!(
+                "== Agent runs at the simulated time starting from {}",
+                start
             );
+            Arc::new(SimulatedClock::starting_at(start))
+        }
+        None => clock::system_clock(),
+    };
+
+    if let Some(state_file) = &conf.state_file {
+        downgrade::check_downgrade(
+            Path::new(&state_file.path),
+            &conf.protocol_state(),
+            &fingerprinting_offline_agent::signing_key(&state_file.signing_key)?,
+            args.allow_downgrade,
+            clock.as_ref(),
+        )?;
+    }
+
+    if let Some(pii) = &conf.pii {
+        let policy = pii.policy();
+        if policy != PiiPolicy::DEFAULT {
+            log::warn!(
+                "== Transaction values are scrubbed by the policy {:?}",
+                policy
+            );
+        }
+        pii::set_policy(policy);
+    }
+
+    let pseudonymizer = match &conf.pseudonymization {
+        Some(pseudonymization) => {
+            log::info!("== BIC pseudonymization is enabled");
+            Some(BicPseudonymizer::new(Compact::unwrap(
+                &pseudonymization.key,
+            )?))
+        }
+        None => None,
+    };
+    let commitment_key = match &conf.commitment_key {
+        Some(key) => {
+            log::info!("== Commitments to the components are signed");
+            Some(fingerprinting_offline_agent::signing_key(key)?)
+        }
+        None => None,
+    };
+
+    // Usage of the API keys and the statistics of the epochs are accounted in the same database as the fingerprints
+    let mut ledger: Option<Arc<dyn UsageLedger>> = None;
+    let mut tallies: Option<Arc<dyn TallyLedger>> = None;
+    let store: Option<Arc<dyn FingerprintStore>> = match &conf.store {
+        Some(StoreConfig::Memory) => {
+            log::warn!("== Fingerprints are stored in memory and will be lost on restart");
+            ledger = Some(Arc::new(MemoryUsageLedger::new()));
+            tallies = Some(Arc::new(MemoryTallyLedger::new()));
+            Some(Arc::new(MemoryFingerprintStore::new()))
+        }
+        Some(StoreConfig::Postgres(postgres)) => {
+            log::info!("== Connecting to Postgres fingerprint store");
+            let store =
+                PostgresFingerprintStore::connect(&postgres.url, postgres.max_connections).await?;
+            ledger = Some(Arc::new(store.usage_ledger()));
+            tallies = Some(Arc::new(store.tally_ledger()));
+            Some(Arc::new(store))
+        }
+        Some(StoreConfig::Sqlite(sqlite)) => {
+            log::info!("== Opening SQLite fingerprint store {}", sqlite.path);
+            let store = SqliteFingerprintStore::connect(&sqlite.path).await?;
+            ledger = Some(Arc::new(store.usage_ledger()));
+            tallies = Some(Arc::new(store.tally_ledger()));
+            Some(Arc::new(store))
+        }
+        None => None,
+    };
+
+    // Archiver publishes the anchored fingerprints to the subscribers of the service
+    let status_hub = Arc::new(FingerprintStatusHub::default());
+    let (store, archive) = match (store, &conf.archive) {
+        (Some(hot), Some(archive_config)) => {
+            let archive = start_archival(
+                hot.clone(),
+                tallies.clone(),
+                status_hub.clone(),
+                archive_config,
+                clock.clone(),
+            )
+            .await?;
+            let store: Arc<dyn FingerprintStore> =
+                Arc::new(ArchivedFingerprintStore::new(hot, archive.clone()));
+            (Some(store), Some(archive as Arc<dyn EpochProofs>))
+        }
+        (None, Some(_)) => return Err(anyhow!("Archive requires the fingerprint store")),
+        (store, None) => (store, None),
+    };
 
+    let duplicate_window = match conf.duplicate_window_days {
+        Some(days) => {
             log::info!(
-                "== Built topology with members: {:?}",
-                topology_config.members
+                "== Repeated fingerprints are duplicates within {} days",
+                days
             );
+            DuplicateWindow::Within(chrono::Duration::days(days as i64))
+        }
+        None => DuplicateWindow::Unbounded,
+    };
 
-            let current_agent_secret = Compact::unwrap(&topology_config.secret_shard)?;
-            let cooperation_service = CooperationAgentService::new(current_agent_secret);
+    let namespace = match &conf.namespace {
+        Some(namespace) => {
+            log::info!(
+                "== Fingerprints are computed in the {} namespace",
+                namespace
+            );
+            Some(Namespace::new(namespace)?)
+        }
+        None => None,
+    };
 
-            let protocol = CollaborativeProtocol::new(
-                (topology_config.agent_id, current_agent_secret),
-                topology,
+    let schema = config::fingerprint_schema(&conf.schemas, conf.schema_id)?;
+    if let Some(schema) = &schema {
+        log::info!(
+            "== Fingerprints are computed by the schema {} binding [{}]",
+            schema.id(),
+            schema
+                .components()
+                .iter()
+                .map(|component| component.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    let wire_version = config::wire_version(conf.wire_version)?;
+    if wire_version != WireVersion::CURRENT {
+        log::info!(
+            "== Preimages are laid out by the wire version {}",
+            wire_version.as_u8()
+        );
+    }
+    let epoch = config::epoch(conf.epoch.as_deref())?;
+    if epoch != Epoch::default() {
+        log::info!("== Date components are offset from the epoch {}", epoch);
+    }
+    let fuzzy_bucket = config::fuzzy_bucket(conf.fuzzy_bucket.as_deref())?;
+    if let Some(bucket) = fuzzy_bucket {
+        log::info!("== Fuzzy fingerprints are computed by the {}", bucket);
+    }
+
+    let recorder = match &conf.recording {
+        Some(recording) => {
+            log::warn!("== Unsalted exchanges are recorded to {}", recording.path);
+            Some(Arc::new(FingerprintRecorder::open(Path::new(
+                &recording.path,
+            ))?))
+        }
+        None => None,
+    };
+
+    let capacity_log = match &conf.capacity_log {
+        Some(capacity_log) => {
+            log::info!(
+                "== Capacity samples are appended to {} every {} seconds",
+                capacity_log.path,
+                capacity_log.interval_secs
             );
+            Some((
+                CapacityLog::open(Path::new(&capacity_log.path))?,
+                Duration::from_secs(capacity_log.interval_secs),
+            ))
+        }
+        None => None,
+    };
+
+    let events: Arc<dyn EventBus> = Arc::new(BroadcastEventBus::default());
+    if conf.event_log {
+        log::info!("== Lifecycle events are logged with the events target");
+        events::spawn_sink(
+            events.as_ref(),
+            "log",
+            |event| log::info!(target: "events", "{}", event),
+        );
+    }
 
-            let fingerprint_server = Server::new().add_service(
-                ServiceBuilder::new(fp::outbe::fingerprint::v1::FingerprintServiceServer::new(
-                    FingerprintService::new(protocol),
-                ))
-                .build(),
+    let shadow = match &conf.shadow {
+        Some(shadow) => {
+            let candidate_namespace = match &shadow.namespace {
+                Some(candidate) => Some(Namespace::new(candidate)?),
+                None => namespace.clone(),
+            };
+            log::info!(
+                "== Fingerprints are shadowed with the candidate secret in the {} namespace",
+                candidate_namespace
+                    .as_ref()
+                    .map(|namespace| namespace.name())
+                    .unwrap_or("default")
             );
+            let candidate_schema = match shadow.schema_id {
+                Some(schema_id) => config::fingerprint_schema(&conf.schemas, Some(schema_id))?,
+                None => schema.clone(),
+            };
+            let candidate_wire_version = match shadow.wire_version {
+                Some(version) => config::wire_version(Some(version))?,
+                None => wire_version,
+            };
+            let candidate_epoch = match &shadow.epoch {
+                Some(candidate) => config::epoch(Some(candidate))?,
+                None => epoch,
+            };
+            let secret: Fr = Compact::unwrap(&shadow.secret)?;
+            Some(
+                ShadowFingerprinting::new(NaiveProtocol::new(secret))
+                    .with_namespace(candidate_namespace)
+                    .with_schema(candidate_schema)
+                    .with_wire_version(candidate_wire_version)
+                    .with_epoch(candidate_epoch),
+            )
+        }
+        None => None,
+    };
 
-            let agent_server = Server::new().add_service(
-                ServiceBuilder::new(
-                    fp_agent::outbe::fingerprint::agent::v1::CooperationServiceServer::new(
-                        cooperation_service,
-                    ),
-                )
-                .build(),
+    let sampling = match &conf.sampling {
+        Some(sampling) => {
+            log::warn!(
+                "== {}% of fingerprints are recomputed with the reference secret",
+                sampling.rate * 100.0
             );
+            let secret: Fr = Compact::unwrap(&sampling.secret)?;
+            Some(
+                FingerprintSampling::new(NaiveProtocol::new(secret), sampling.rate)?
+                    .with_namespace(namespace.clone())
+                    .with_schema(schema.clone())
+                    .with_wire_version(wire_version)
+                    .with_epoch(epoch),
+            )
+        }
+        None => None,
+    };
+
+    let shedding = conf.load_shedding.as_ref().map(|shedding| {
+        LoadShedding::new(shedding.concurrency, shedding.max_queue)
+            .with_retry_after(Duration::from_secs(shedding.retry_after_secs))
+    });
+    let scheduler = conf
+        .scheduler
+        .as_ref()
+        .map(|scheduler| SchedulerLimits::new(scheduler.workers, scheduler.round_size))
+        .unwrap_or_default();
+    let residency = conf
+        .residency
+        .iter()
+        .fold(ResidencyRouting::default(), |routing, route| {
+            routing.with_route(route.tag.clone(), route.agents.clone())
+        });
+    let usage = conf.usage.as_ref().map(|usage| {
+        log::info!("== Usage of {} API keys is accounted", usage.api_keys.len());
+        let ledger = ledger.unwrap_or_else(|| {
+            log::warn!("== Usage of the API keys is kept in memory and will be lost on restart");
+            Arc::new(MemoryUsageLedger::new())
+        });
+        usage.accounting(ledger)
+    });
+    // State of the coordinator lost with its restart unless snapshotted
+    let coordinator = CoordinatorState {
+        idempotency: conf.idempotency.as_ref().map(|idempotency| {
+            Arc::new(IdempotencyCache::new(
+                idempotency.capacity.get(),
+                chrono::Duration::seconds(idempotency.ttl_secs as i64),
+            ))
+        }),
+        breakers: match &conf.fingerprint_service {
+            FingerprintServiceConfig::Cooperative(topology_config) => {
+                topology_config.circuit_breakers.as_ref().map(|breakers| {
+                    Arc::new(
+                        CircuitBreakers::new(
+                            breakers.failures.get(),
+                            chrono::Duration::seconds(breakers.cooldown_secs as i64),
+                        )
+                        .with_clock(clock.clone()),
+                    )
+                })
+            }
+            _ => None,
+        },
+        // Only the new fingerprints are accumulated, which are told from the duplicates by the store
+        accumulator: store
+            .as_ref()
+            .map(|_| Arc::new(Mutex::new(EpochAccumulator::new(clock.today())))),
+    };
+    let idempotency = coordinator.idempotency.clone();
+    let accumulator = coordinator.accumulator.clone();
+    let breakers = coordinator.breakers.clone();
+    if let Some(snapshot) = &conf.snapshot {
+        let path = Path::new(&snapshot.path);
+        if coordinator.restore(path)? {
+            log::info!("== Restored the coordinator state from {}", snapshot.path);
+        }
+        start_snapshots(
+            coordinator,
+            path,
+            Duration::from_secs(snapshot.interval_secs),
+            clock.clone(),
+        );
+    }
+    let rest = conf
+        .rest
+        .as_ref()
+        .map(|rest| format!("{}:{}", rest.host, rest.port).parse::<SocketAddr>())
+        .transpose()?;
+
+    let options = ServiceOptions {
+        pseudonymizer,
+        commitment_key,
+        entropy: entropy::default_source(),
+        store,
+        archive,
+        tallies,
+        duplicate_window,
+        namespace,
+        schema,
+        wire_version,
+        epoch,
+        fuzzy_bucket,
+        input_limits: conf.input_limits.limits(),
+        recorder,
+        shadow,
+        sampling,
+        shedding,
+        residency,
+        scheduler,
+        usage,
+        idempotency,
+        accumulator,
+        events: events.clone(),
+        status_hub,
+        topology: None,
+        capacity_log,
+        rest,
+        clock: clock.clone(),
+        warm_up: true,
+    };
+
+    let (fingerprint_server, agent_server): (Server, Option<AgentServer>) = match conf
+        .fingerprint_service
+    {
+        FingerprintServiceConfig::Cooperative(topology_config) => {
+            log::info!("== Starting CRA Fingerprint agent in Cooperative mode with {} agents and {} threshold", topology_config.agents, topology_config.threshold);
+            let current_agent_secret = Compact::unwrap(&topology_config.secret_shard)?;
+            let agent_info = (topology_config.agent_id, current_agent_secret);
+            // Agent does not start with the entropy source failing the health check
+            let entropy = match &topology_config.entropy {
+                Some(entropy) => entropy.source()?,
+                None => EntropyConfig::default().source()?,
+            };
+            let options = ServiceOptions {
+                entropy: entropy.clone(),
+                ..options
+            };
+            let diversity = topology_config.diversity_policy();
+            let metadata = topology_config.members();
+            // Members reported by the service info with the evaluations counted by the protocol
+            let topology_status = |metrics| Some(TopologyStatus::new(metadata.clone(), metrics));
+
+            if let Some(libp2p) = &topology_config.libp2p {
+                let key: Bytes = Compact::unwrap(&libp2p.key)?;
+                let members = topology_config
+                    .members
+                    .iter()
+                    .map(|agent| Ok((agent.agent_id, agent.address.parse()?)))
+                    .collect::<Result<Vec<_>, anyhow::Error>>()?;
+                let topology = P2pAgentsTopology::start(
+                    topology_config.agents,
+                    topology_config.threshold,
+                    fingerprinting_p2p_agent::ed25519_keypair(&key)?,
+                    libp2p.listen.parse()?,
+                    current_agent_secret,
+                    members,
+                )?;
+
+                log::info!(
+                    "== Built libp2p topology with members: {:?}",
+                    topology_config.members
+                );
+
+                let protocol = CollaborativeProtocol::new(agent_info, topology)
+                    .with_entropy(entropy)
+                    .with_diversity(diversity)
+                    .with_members(metadata.clone());
+                let options = ServiceOptions {
+                    topology: topology_status(protocol.phase_metrics()),
+                    ..options
+                };
+
+                // libp2p node serves the other agents itself
+                (fingerprint_server(protocol, options).await?, None)
+            } else {
+                let spiffe = match &topology_config.spiffe {
+                    Some(spiffe) => {
+                        log::info!(
+                            "== Fetching SVID from Workload API at {}, trusted domains: {:?}",
+                            spiffe.workload_api_socket,
+                            spiffe.trust_domains
+                        );
+                        let source =
+                            SpiffeSource::from_workload_api(&spiffe.workload_api_socket).await?;
+                        Some((source, spiffe.trust_domains.clone()))
+                    }
+                    None => None,
+                };
 
-            (fingerprint_server, Some(agent_server))
+                let client_tls = |tls: Option<&AgentTlsConfig>| {
+                    let mut tls = tls.map(|tls| tls.client_tls()).transpose()?;
+                    if let Some((source, trust_domains)) = &spiffe {
+                        tls = Some(
+                            tls.unwrap_or_default()
+                                .with_spiffe(source.clone(), trust_domains.clone()),
+                        );
+                    }
+                    Ok::<_, anyhow::Error>(tls)
+                };
+                let members = topology_config
+                    .members
+                    .iter()
+                    .map(|agent| {
+                        Ok((
+                            agent.agent_id,
+                            agent.address.to_string(),
+                            client_tls(agent.tls.as_ref())?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, anyhow::Error>>()?;
+                let verifying_keys = topology_config
+                    .members
+                    .iter()
+                    .filter_map(|agent| {
+                        agent.verifying_key.as_ref().map(|key| {
+                            Ok((
+                                agent.agent_id,
+                                fingerprinting_offline_agent::verifying_key(key)?,
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, anyhow::Error>>()?;
+                let mut topology = GrpcAgentsTopology::with_client_tls(
+                    topology_config.agents,
+                    topology_config.threshold,
+                    members,
+                )?
+                .with_verifying_keys(verifying_keys)
+                .with_events(events.clone());
+                if let Some(breakers) = &breakers {
+                    topology = topology.with_breakers(breakers.clone());
+                }
+                let signatures = topology_config.signatures.as_ref();
+                if let Some(transcript) = signatures.and_then(|s| s.transcript.as_ref()) {
+                    log::info!(
+                        "== Signed partial evaluations are recorded to {}",
+                        transcript
+                    );
+                    topology = topology
+                        .with_transcript(Arc::new(FileTranscript::open(Path::new(transcript))?));
+                }
+                let topology = topology
+                    .check_versions(topology_config.incompatible_agents.into())
+                    .await?;
+
+                log::info!(
+                    "== Built topology with members: {:?}",
+                    topology_config.members
+                );
+
+                let cooperation_service = CooperationAgentService::new(current_agent_secret);
+                let cooperation_service = match topology_config.exponent_cache {
+                    Some(capacity) => cooperation_service.with_cache(capacity),
+                    None => cooperation_service,
+                };
+                let cooperation_service = match &topology_config.evaluation_limits {
+                    Some(limits) => {
+                        log::info!(
+                            "== Serving {} partial evaluations at once, refusing beyond {} queued",
+                            limits.concurrency,
+                            limits.max_queue
+                        );
+                        cooperation_service.with_limits(EvaluationLimits::new(
+                            limits.concurrency,
+                            limits.max_queue,
+                        ))
+                    }
+                    None => cooperation_service,
+                };
+                let cooperation_service = match signatures.and_then(|s| s.signing_key.as_ref()) {
+                    Some(key) => cooperation_service
+                        .with_signing_key(fingerprinting_offline_agent::signing_key(key)?),
+                    None => cooperation_service,
+                };
+                // Health is reported NOT_SERVING and the new evaluations are refused once the admin enters
+                // the maintenance
+                let maintenance = Arc::new(Maintenance::default());
+                let cooperation_service = cooperation_service.with_maintenance(maintenance.clone());
+
+                let (coordination, cooperation_service) = match &topology_config.region {
+                    Some(region) => {
+                        let coordinators = region
+                            .coordinators
+                            .iter()
+                            .map(|coordinator| {
+                                Ok((
+                                    coordinator.region_id,
+                                    coordinator.address.to_string(),
+                                    client_tls(coordinator.tls.as_ref())?,
+                                ))
+                            })
+                            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+                        let regions = GrpcAgentsTopology::with_client_tls(
+                            region.regions,
+                            region.threshold,
+                            coordinators,
+                        )?
+                        .with_regions()
+                        .with_events(events.clone())
+                        .check_versions(topology_config.incompatible_agents.into())
+                        .await?;
+
+                        log::info!(
+                            "== Coordinating region {} of {} regions with {} threshold, regional coordinators: {:?}",
+                            region.region_id,
+                            region.regions,
+                            region.threshold,
+                            region.coordinators
+                        );
+
+                        let regional = Arc::new(
+                            CollaborativeProtocol::new(agent_info, topology)
+                                .with_diversity(diversity.clone())
+                                .with_members(metadata.clone()),
+                        );
+                        let metrics = regional.phase_metrics();
+                        let evaluation: RegionalEvaluation = {
+                            let regional = regional.clone();
+                            Arc::new(move |blinded_value| {
+                                let regional = regional.clone();
+                                async move { regional.evaluate(blinded_value).await }.boxed()
+                            })
+                        };
+
+                        (
+                            Coordination::Regions(
+                                HierarchicalProtocol::new(region.region_id, regional, regions)
+                                    .with_entropy(entropy.clone()),
+                                metrics,
+                            ),
+                            cooperation_service.with_region(evaluation),
+                        )
+                    }
+                    None => (Coordination::Agents(topology), cooperation_service),
+                };
+                let agent_server = Server::new()
+                    .add_service(
+                        ServiceBuilder::new(
+                            fp_agent::outbe::fingerprint::agent::v1::CooperationServiceServer::new(
+                                cooperation_service,
+                            ),
+                        )
+                        .build(),
+                    )
+                    .add_service(
+                        ServiceBuilder::new(fp_health::health::v1::HealthServer::new(
+                            AgentHealth::new(maintenance.clone()),
+                        ))
+                        .build(),
+                    );
+                let agent_server = match &spiffe {
+                    Some((source, trust_domains)) => {
+                        agent_server.tls_config(source.server_tls_config(trust_domains)?)
+                    }
+                    None => agent_server,
+                };
+                // Other agents cooperate in the warm-up of this one and vice versa, so they are served right away
+                let agent_server = start_agent_server(agent_server, &conf.agent_grpc)?;
+                if let Some(admin) = &conf.agent_admin {
+                    start_admin_server(maintenance, admin)?;
+                }
+
+                let fingerprint_server = match (coordination, &topology_config.offline) {
+                    (Coordination::Regions(protocol, metrics), _) => {
+                        let options = ServiceOptions {
+                            topology: topology_status(metrics),
+                            ..options
+                        };
+                        fingerprint_server(protocol, options).await?
+                    }
+                    (Coordination::Agents(topology), Some(offline)) => {
+                        let mut topology = OfflineAgentsTopology::new(
+                            topology,
+                            fingerprinting_offline_agent::signing_key(&offline.signing_key)?,
+                            &offline.outbox,
+                            &offline.inbox,
+                            Duration::from_secs(offline.deadline_secs),
+                        )
+                        .with_clock(clock.clone());
+                        for agent in &offline.agents {
+                            topology = topology.with_offline_agent(
+                                agent.agent_id,
+                                fingerprinting_offline_agent::verifying_key(&agent.verifying_key)?,
+                            );
+                        }
+                        log::info!(
+                            "== Offline agents {:?} are reached via request files in {}, waiting up to {} seconds",
+                            offline.agents.iter().map(|agent| agent.agent_id).collect::<Vec<_>>(),
+                            offline.outbox,
+                            offline.deadline_secs
+                        );
+
+                        let protocol = CollaborativeProtocol::new(agent_info, topology)
+                            .with_entropy(entropy)
+                            .with_diversity(diversity)
+                            .with_members(metadata.clone());
+                        // The dummy fingerprint would leave the request files for the offline agents
+                        let options = ServiceOptions {
+                            warm_up: false,
+                            topology: topology_status(protocol.phase_metrics()),
+                            ..options
+                        };
+                        fingerprint_server(protocol, options).await?
+                    }
+                    (Coordination::Agents(topology), None) => {
+                        let protocol = CollaborativeProtocol::new(agent_info, topology)
+                            .with_entropy(entropy)
+                            .with_diversity(diversity)
+                            .with_members(metadata.clone());
+                        let options = ServiceOptions {
+                            topology: topology_status(protocol.phase_metrics()),
+                            ..options
+                        };
+                        fingerprint_server(protocol, options).await?
+                    }
+                };
+
+                (fingerprint_server, Some(agent_server))
+            }
         }
         FingerprintServiceConfig::Naive(naive) => {
             log::warn!(
@@ -99,15 +734,28 @@ async fn main() -> Result<(), anyhow::Error> {
 
             let protocol = NaiveProtocol::new(secret);
 
-            (
-                Server::new().add_service(
-                    ServiceBuilder::new(fp::outbe::fingerprint::v1::FingerprintServiceServer::new(
-                        FingerprintService::new(protocol),
-                    ))
-                    .build(),
-                ),
-                None,
-            )
+            (fingerprint_server(protocol, options).await?, None)
+        }
+        FingerprintServiceConfig::InProcess(in_process) => {
+            log::warn!(
+                "== Starting CRA Fingerprint agent in InProcess mode with {} agents and {} threshold, the shares are held by this process",
+                in_process.agents,
+                in_process.threshold
+            );
+            let secret: Fr = Compact::unwrap(&in_process.secret)?;
+            let (agent_info, topology) =
+                InProcessAgentsTopology::share(secret, in_process.threshold, in_process.agents);
+
+            let protocol = CollaborativeProtocol::new(agent_info, topology);
+            let options = ServiceOptions {
+                topology: Some(TopologyStatus::new(
+                    Members::default(),
+                    protocol.phase_metrics(),
+                )),
+                ..options
+            };
+
+            (fingerprint_server(protocol, options).await?, None)
         }
     };
 
@@ -121,35 +769,324 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let fingerprint_grpc_address = volo::net::Address::from(addr);
 
+    let fingerprint_server = fingerprint_server
+        .http2_adaptive_window(true)
+        .accept_http1(true)
+        .run(fingerprint_grpc_address)
+        .map_err(|e| anyhow::anyhow!(e));
+
     match agent_server {
-        None => fingerprint_server
-            .http2_adaptive_window(true)
-            .accept_http1(true)
-            .run(fingerprint_grpc_address)
-            .await
-            .map_err(|e| anyhow::anyhow!(e)),
+        None => fingerprint_server.await,
         Some(agent_server) => {
-            let agent_grpc_address = format!("{}:{}", conf.agent_grpc.host, conf.agent_grpc.port);
+            let agent_server = agent_server.map(|served| -> Result<(), anyhow::Error> { served? });
 
-            log::info!("== starting Agent GRPC server on {}", agent_grpc_address);
-            let addr: SocketAddr = agent_grpc_address.parse()?;
+            futures::future::try_join(agent_server, fingerprint_server)
+                .await
+                .map(|_| ())
+        }
+    }
+}
 
-            let agent_grpc_address = volo::net::Address::from(addr);
+/// How the fingerprints of this agent are coordinated
+enum Coordination {
+    /// Threshold of the agents
+    Agents(GrpcAgentsTopology),
+    /// Threshold of the regions, the agents of the own region evaluate its share with the metrics of
+    /// the regional protocol
+    Regions(
+        HierarchicalProtocol<GrpcAgentsTopology, GrpcAgentsTopology>,
+        Arc<PhaseMetrics>,
+    ),
+}
 
-            let agent_server = agent_server
-                .http2_adaptive_window(true)
-                .accept_http1(true)
-                .run(agent_grpc_address);
+/// Agent gRPC server running in the background
+type AgentServer = JoinHandle<Result<(), anyhow::Error>>;
 
-            let fingerprint_server = fingerprint_server
-                .http2_adaptive_window(true)
-                .accept_http1(true)
-                .run(fingerprint_grpc_address);
+/// Starts serving the other agents
+fn start_agent_server(
+    agent_server: Server,
+    config: &GrpcConfig,
+) -> Result<AgentServer, anyhow::Error> {
+    let agent_grpc_address = format!("{}:{}", config.host, config.port);
 
-            futures::future::try_join(agent_server, fingerprint_server)
-                .await
-                .map(|_| ())
-                .map_err(|e| anyhow::anyhow!(e))
+    log::info!("== starting Agent GRPC server on {}", agent_grpc_address);
+    let addr: SocketAddr = agent_grpc_address.parse()?;
+
+    let agent_grpc_address = volo::net::Address::from(addr);
+
+    let agent_server = agent_server
+        .http2_adaptive_window(true)
+        .accept_http1(true)
+        .run(agent_grpc_address)
+        .map_err(|e| anyhow::anyhow!(e));
+
+    Ok(tokio::spawn(agent_server))
+}
+
+/// Starts serving the admin calls of the agent, its failure leaves the agent serving without them
+fn start_admin_server(
+    maintenance: Arc<Maintenance>,
+    config: &GrpcConfig,
+) -> Result<(), anyhow::Error> {
+    let admin_address = format!("{}:{}", config.host, config.port);
+
+    log::info!("== starting Agent admin GRPC server on {}", admin_address);
+    let addr: SocketAddr = admin_address.parse()?;
+
+    let admin_server = Server::new().add_service(
+        ServiceBuilder::new(
+            fp_agent::outbe::fingerprint::agent::v1::AgentAdminServiceServer::new(AgentAdmin::new(
+                maintenance,
+            )),
+        )
+        .build(),
+    );
+    tokio::spawn(async move {
+        if let Err(e) = admin_server.run(volo::net::Address::from(addr)).await {
+            log::error!("Agent admin GRPC server failed: {}", e);
         }
+    });
+
+    Ok(())
+}
+
+/// Configuration of the public fingerprint service independent of the protocol
+struct ServiceOptions {
+    pseudonymizer: Option<BicPseudonymizer>,
+    /// Key the commitments to the components are signed with
+    commitment_key: Option<SigningKey>,
+    /// Source of the openings of the commitments, shared with the protocol of the cooperative agent
+    entropy: Arc<dyn EntropySource>,
+    store: Option<Arc<dyn FingerprintStore>>,
+    /// Archived epochs the inclusion proofs are served from
+    archive: Option<Arc<dyn EpochProofs>>,
+    /// Statistics of the epochs the new fingerprints are counted to
+    tallies: Option<Arc<dyn TallyLedger>>,
+    duplicate_window: DuplicateWindow,
+    namespace: Option<Namespace>,
+    schema: Option<Arc<FingerprintSchema>>,
+    wire_version: WireVersion,
+    epoch: Epoch,
+    /// Time bucket of the fuzzy fingerprints returned alongside the exact ones
+    fuzzy_bucket: Option<TimeBucket>,
+    input_limits: InputLimits,
+    recorder: Option<Arc<FingerprintRecorder>>,
+    shadow: Option<ShadowFingerprinting>,
+    sampling: Option<FingerprintSampling>,
+    shedding: Option<LoadShedding>,
+    residency: ResidencyRouting,
+    scheduler: SchedulerLimits,
+    usage: Option<UsageAccounting>,
+    /// Responses of the completed calls the retried ones are answered by
+    idempotency: Option<Arc<IdempotencyCache>>,
+    /// Partial tree of the new fingerprints of the current epoch
+    accumulator: Option<Arc<Mutex<EpochAccumulator>>>,
+    /// Bus the lifecycle events are published to, shared with the topology
+    events: Arc<dyn EventBus>,
+    /// Hub the status updates are published to, shared with the archiver
+    status_hub: Arc<FingerprintStatusHub>,
+    /// Members of the cooperative topology reported by the service info
+    topology: Option<TopologyStatus>,
+    /// Log the counters of the service are sampled into with the interval of the samples
+    capacity_log: Option<(CapacityLog, Duration)>,
+    /// Address of the JSON over HTTP endpoint served along the gRPC one
+    rest: Option<SocketAddr>,
+    clock: Arc<dyn Clock>,
+    /// Whether the dummy fingerprint is computed before the service is ready
+    warm_up: bool,
+}
+
+/// Public fingerprint service computing fingerprints via the `protocol`, built once the protocol is warmed up
+async fn fingerprint_server<P: FingerprintProtocol<Fr> + Send + Sync + 'static>(
+    protocol: P,
+    options: ServiceOptions,
+) -> Result<Server, anyhow::Error> {
+    if options.warm_up {
+        warm_up(&protocol).await;
+    } else {
+        warmup::init_specs();
+    }
+
+    let counters = Arc::new(ServiceCounters::default());
+    if let Some((capacity_log, interval)) = options.capacity_log {
+        let metrics = options.topology.as_ref().map(TopologyStatus::metrics);
+        start_capacity_log(
+            capacity_log,
+            interval,
+            counters.clone(),
+            metrics,
+            options.clock.clone(),
+        );
+    }
+
+    let service = Arc::new(
+        FingerprintService::new(protocol)
+            .with_pseudonymizer(options.pseudonymizer)
+            .with_commitment_key(options.commitment_key)
+            .with_entropy(options.entropy)
+            .with_store(options.store)
+            .with_archive(options.archive)
+            .with_tallies(options.tallies)
+            .with_duplicate_window(options.duplicate_window)
+            .with_namespace(options.namespace)
+            .with_schema(options.schema)
+            .with_wire_version(options.wire_version)
+            .with_epoch(options.epoch)
+            .with_fuzzy_bucket(options.fuzzy_bucket)
+            .with_input_limits(options.input_limits)
+            .with_recorder(options.recorder)
+            .with_shadow(options.shadow)
+            .with_sampling(options.sampling)
+            .with_load_shedding(options.shedding)
+            .with_residency(options.residency)
+            .with_scheduler(options.scheduler)
+            .with_usage(options.usage)
+            .with_idempotency(options.idempotency)
+            .with_accumulator(options.accumulator)
+            .with_events(options.events)
+            .with_status_hub(options.status_hub)
+            .with_topology(options.topology)
+            .with_counters(counters)
+            .with_clock(options.clock),
+    );
+
+    if let Some(addr) = options.rest {
+        log::info!("== starting Fingerprint REST server on {}", addr);
+        // Bound before the gRPC server starts, so the taken port fails the start
+        let listener = TcpListener::bind(addr).await?;
+        let service = service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = rest::serve(listener, service).await {
+                log::error!("Fingerprint REST server failed: {}", e);
+            }
+        });
+    }
+
+    Ok(Server::new().add_service(
+        ServiceBuilder::new(
+            fp::outbe::fingerprint::v1::FingerprintServiceServer::from_arc(service),
+        )
+        .build(),
+    ))
+}
+
+/// Time the dummy fingerprint of the warm-up is computed within
+const WARM_UP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Pause between the failed warm-ups, e.g. while the other agents are starting
+const WARM_UP_RETRY: Duration = Duration::from_secs(5);
+
+/// Computes the dummy fingerprint until it succeeds, so the first requests find the specs generated and
+/// the agents connected
+async fn warm_up<P: FingerprintProtocol<Fr> + Sync>(protocol: &P) {
+    loop {
+        let started = Instant::now();
+        match tokio::time::timeout(WARM_UP_TIMEOUT, warmup::warm_up(protocol)).await {
+            Ok(Ok(_)) => {
+                log::info!("== Warmed up in {} ms", started.elapsed().as_millis());
+                return;
+            }
+            Ok(Err(e)) => log::warn!("Warm-up fingerprint failed, retrying: {}", e),
+            Err(_) => log::warn!(
+                "Warm-up fingerprint is not computed within {} seconds, retrying",
+                WARM_UP_TIMEOUT.as_secs()
+            ),
+        }
+        tokio::time::sleep(WARM_UP_RETRY).await;
     }
 }
+
+/// Periodically appends the counters of the service and the evaluations of the agents to the capacity log
+fn start_capacity_log(
+    mut capacity_log: CapacityLog,
+    interval: Duration,
+    counters: Arc<ServiceCounters>,
+    metrics: Option<Arc<PhaseMetrics>>,
+    clock: Arc<dyn Clock>,
+) {
+    let started_at = clock.now();
+    let mut interval = tokio::time::interval(interval);
+    // The first tick completes right away, nothing is counted yet
+    interval.reset();
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+
+            let sample =
+                CapacitySample::new(clock.now(), started_at, &counters, metrics.as_deref());
+            if let Err(e) = capacity_log.append(&sample) {
+                log::error!("Failed to append the capacity sample: {}", e);
+            }
+        }
+    });
+}
+
+/// Periodically saves the coordinator state, so it is restored on the restart
+fn start_snapshots(
+    state: CoordinatorState,
+    path: &Path,
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+) {
+    let path = path.to_path_buf();
+    let mut interval = tokio::time::interval(interval);
+    // The first tick completes right away, the state is just restored
+    interval.reset();
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = state.save(&path, clock.now()) {
+                log::error!("Failed to save the coordinator state: {}", e);
+            }
+        }
+    });
+}
+
+/// Periodically rolls completed epochs from the hot store to the archive
+async fn start_archival(
+    hot: Arc<dyn FingerprintStore>,
+    tallies: Option<Arc<dyn TallyLedger>>,
+    status_hub: Arc<FingerprintStatusHub>,
+    config: &ArchiveConfig,
+    clock: Arc<dyn Clock>,
+) -> Result<Arc<FingerprintArchive>, anyhow::Error> {
+    let anchored = move |root: [u8; 32], fingerprints: &[StoredFingerprint]| {
+        status_hub.publish_anchored(root, fingerprints.iter().map(|stored| stored.fingerprint));
+    };
+    let archive = Arc::new(
+        FingerprintArchive::s3(&config.bucket, config.endpoint.as_deref(), &config.prefix)?
+            .with_tallies(tallies)
+            .with_listener(Some(Arc::new(anchored)))
+            .with_clock(clock.clone()),
+    );
+
+    let archived = archive.load_manifests().await?;
+    log::info!(
+        "== Archiving fingerprints to s3://{}/{}, {} epochs archived so far",
+        config.bucket,
+        config.prefix,
+        archived
+    );
+
+    let retention = chrono::Duration::days(config.hot_retention_days as i64);
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+
+    let archival = archive.clone();
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = archival
+                .archive_completed(hot.as_ref(), clock.now(), retention)
+                .await
+            {
+                log::error!("Failed to archive fingerprints: {}", e);
+            }
+        }
+    });
+
+    Ok(archive)
+}
@@ -1,12 +1,19 @@
 use clap::Parser;
-use fingerprinting_grpc_agent::{net, CooperationAgentService};
+use fingerprinting_grpc_agent::{
+    grpc, net, AgentAdmin, AgentHealth, CooperationAgentService, EvaluationLimits, Maintenance,
+    PipeSigner,
+};
 use halo2_axiom::halo2curves::bn256::Fr;
 use hocon::HoconLoader;
-use serde_derive::Deserialize;
 use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use volo::net::MakeIncoming;
 use volo_grpc::server::{Server, ServiceBuilder};
 
-use fingerprinting_cli::config::{AgentConfig, GrpcConfig};
+use fingerprinting_cli::config::LightAgentConfig;
+use fingerprinting_cli::hardening;
 use fingerprinting_core::Compact;
 
 #[derive(Parser, Debug)]
@@ -18,12 +25,6 @@ struct Args {
     config: String,
 }
 
-#[derive(Deserialize)]
-struct LightAgentConfig {
-    grpc: GrpcConfig,
-    agent: AgentConfig,
-}
-
 #[volo::main]
 async fn main() -> Result<(), anyhow::Error> {
     env_logger::builder()
@@ -37,19 +38,93 @@ async fn main() -> Result<(), anyhow::Error> {
     log::info!("== loading configuration from {}", args.config);
     let conf: LightAgentConfig = HoconLoader::new()
         .load_str(reference_config)?
-        .load_file(args.config)?
+        .load_file(&args.config)?
         .resolve()?;
+    conf.validate()?;
+
+    // Shard is read and the signer is started before the privileges are dropped and the process is confined,
+    // so the file could be readable by root only
+    let service = match &conf.agent.relay {
+        Some(relay) => {
+            log::info!(
+                "== relaying partial evaluations to the signer {}",
+                relay.command[0]
+            );
+            let signer = PipeSigner::spawn(&relay.command)?
+                .with_timeout(Duration::from_secs(relay.timeout_secs));
+            CooperationAgentService::relay(Arc::new(signer))
+        }
+        None => {
+            let secret_shard = match &conf.agent.secret_shard_file {
+                Some(path) => hardening::read_secret_file(Path::new(path))?,
+                None => {
+                    if let Err(e) = hardening::check_secret_permissions(Path::new(&args.config)) {
+                        log::warn!("Configuration holds the secret shard: {}", e);
+                    }
+                    conf.agent.secret_shard.clone()
+                }
+            };
+            let secret_shard: Fr = Compact::unwrap(&secret_shard)
+                .map_err(|e| anyhow::anyhow!("Cannot parse secret shard: {}", e))?;
+            CooperationAgentService::new(secret_shard)
+        }
+    };
 
     let address = format!("{}:{}", conf.grpc.host, conf.grpc.port);
 
     log::info!("== starting GRPC server on {}", address);
     let addr: SocketAddr = address.parse()?;
 
-    let addr = volo::net::Address::from(addr);
-    let secret_shard: Fr =
-        Compact::unwrap(&conf.agent.secret_shard).expect("Cannot parse secret shard");
+    let incoming = volo::net::Address::from(addr).make_incoming().await?;
+    let admin_incoming = match &conf.admin {
+        Some(admin) => {
+            let admin_address = format!("{}:{}", admin.host, admin.port);
+            log::info!("== starting admin GRPC server on {}", admin_address);
+            let addr: SocketAddr = admin_address.parse()?;
+            Some(volo::net::Address::from(addr).make_incoming().await?)
+        }
+        None => None,
+    };
 
-    let service = CooperationAgentService::new(secret_shard);
+    match &conf.hardening.user {
+        Some(user) => {
+            hardening::drop_privileges(user, conf.hardening.group.as_deref())?;
+            log::info!("== dropped privileges to user {}", user);
+        }
+        None => log::warn!("Privileges are not dropped, hardening.user is not configured"),
+    }
+    hardening::apply_seccomp(conf.hardening.seccomp, conf.agent.relay.is_some())?;
+
+    let service = match &conf.evaluation_limits {
+        Some(limits) => {
+            log::info!(
+                "== Serving {} partial evaluations at once, refusing beyond {} queued",
+                limits.concurrency,
+                limits.max_queue
+            );
+            service.with_limits(EvaluationLimits::new(limits.concurrency, limits.max_queue))
+        }
+        None => service,
+    };
+
+    // Health is reported NOT_SERVING and the new evaluations are refused once the admin enters the maintenance
+    let maintenance = Arc::new(Maintenance::default());
+    let service = service.with_maintenance(maintenance.clone());
+    if let Some(admin_incoming) = admin_incoming {
+        let admin_server = Server::new().add_service(
+            ServiceBuilder::new(
+                net::outbe::fingerprint::agent::v1::AgentAdminServiceServer::new(AgentAdmin::new(
+                    maintenance.clone(),
+                )),
+            )
+            .build(),
+        );
+        tokio::spawn(async move {
+            if let Err(e) = admin_server.run(admin_incoming).await {
+                log::error!("Admin GRPC server failed: {}", e);
+            }
+        });
+    }
 
     Server::new()
         .http2_adaptive_window(true)
@@ -60,7 +135,13 @@ async fn main() -> Result<(), anyhow::Error> {
             )
             .build(),
         )
-        .run(addr)
+        .add_service(
+            ServiceBuilder::new(grpc::health::v1::HealthServer::new(AgentHealth::new(
+                maintenance,
+            )))
+            .build(),
+        )
+        .run(incoming)
         .await
         .map_err(|e| anyhow::anyhow!(e))
 }
@@ -0,0 +1,215 @@
+use anyhow::{anyhow, Error};
+use clap::Parser;
+use fingerprinting_cli::batch::Endpoints;
+use fingerprinting_cli::loadgen::{
+    self, LoadObjectives, LoadProfile, LoadReport, LoadStats, TransactionGenerator,
+};
+use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+    ComputeSingleFingerprintRequest, FingerprintServiceClientBuilder, GetServiceInfoRequest,
+};
+use fingerprinting_grpc::API_KEY;
+use rand_core::{OsRng, RngCore};
+use std::net::SocketAddr;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::pin::pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::{Instant, MissedTickBehavior};
+
+#[derive(Parser, Debug)]
+#[command(name = "fingerprinting-loadgen")]
+#[command(
+    about = "Drive the fingerprint service with the synthetic transactions at the fixed rate and report the latencies",
+    long_about = None
+)]
+struct Args {
+    /// Address of the fingerprint service, e.g. [::1]:9000, repeated for the instances sharing the load
+    #[arg(long = "endpoint", required = true)]
+    endpoints: Vec<SocketAddr>,
+
+    /// Transactions sent per second to all the instances
+    #[arg(long)]
+    tps: NonZeroU32,
+
+    /// Length of the run
+    #[arg(long, default_value_t = 60)]
+    duration_secs: u64,
+
+    /// Share of the transactions resent as the duplicates of the recent ones, from 0 to 1
+    #[arg(long, default_value_t = 0.0)]
+    duplicate_ratio: f64,
+
+    /// Currencies with their weights as `<currency>:<weight>,...`
+    #[arg(long, default_value = loadgen::DEFAULT_CURRENCY_MIX)]
+    currency_mix: String,
+
+    /// Seed of the transaction stream, the random one by default
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Requests awaiting the response at most, the sending is held back beyond it
+    #[arg(long, default_value_t = NonZeroUsize::new(1024).unwrap())]
+    max_in_flight: NonZeroUsize,
+
+    /// API key presented to the service, see `usage` of the agent configuration
+    #[arg(long)]
+    api_key: Option<String>,
+
+    /// How often the latencies of the last interval are reported
+    #[arg(long, default_value_t = 60)]
+    report_interval_secs: u64,
+
+    /// Failed requests allowed of all the requests, from 0 to 1
+    #[arg(long, default_value_t = 0.001)]
+    error_budget: f64,
+
+    /// 99th percentile of the latency the run should stay within
+    #[arg(long)]
+    p99_target_ms: Option<u64>,
+}
+
+fn keyed<T>(message: T, api_key: Option<&str>) -> Result<volo_grpc::Request<T>, Error> {
+    let mut request = volo_grpc::Request::new(message);
+    if let Some(api_key) = api_key {
+        request.metadata_mut().insert(API_KEY, api_key.parse()?);
+    }
+
+    Ok(request)
+}
+
+fn take(window: &Mutex<LoadStats>) -> LoadStats {
+    std::mem::take(&mut *window.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+#[volo::main]
+async fn main() -> Result<(), Error> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .init();
+
+    let args = Args::parse();
+    let seed = args.seed.unwrap_or_else(|| OsRng.next_u64());
+    let mut generator = TransactionGenerator::new(LoadProfile {
+        duplicate_ratio: args.duplicate_ratio,
+        currencies: loadgen::parse_currency_mix(&args.currency_mix)?,
+        seed,
+    })?;
+    let objectives = LoadObjectives {
+        error_budget: args.error_budget,
+        p99_target: args.p99_target_ms.map(Duration::from_millis),
+    };
+    if !(0.0..=1.0).contains(&objectives.error_budget) {
+        return Err(anyhow!(
+            "Error budget should be from 0 to 1, given {}",
+            objectives.error_budget
+        ));
+    }
+
+    let mut clients = vec![];
+    for endpoint in &args.endpoints {
+        let client = FingerprintServiceClientBuilder::new("fingerprinting-loadgen")
+            .address(*endpoint)
+            .build();
+        let info = client
+            .get_service_info(GetServiceInfoRequest::default())
+            .await?;
+        fingerprinting_grpc::check_service_parameters(info.get_ref())
+            .map_err(|e| anyhow!("Endpoint {}: {}", endpoint, e))?;
+        clients.push(client);
+    }
+    let endpoints = Endpoints::new(clients, None)?;
+
+    log::info!(
+        "Sending {} transactions per second to {} endpoints for {} s, seed {}",
+        args.tps,
+        args.endpoints.len(),
+        args.duration_secs,
+        seed
+    );
+
+    let in_flight = Arc::new(Semaphore::new(args.max_in_flight.get()));
+    let window = Arc::new(Mutex::new(LoadStats::default()));
+    let mut total = LoadStats::default();
+    let mut tasks = JoinSet::new();
+
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration_secs);
+    let mut ticks = tokio::time::interval(Duration::from_secs(1) / args.tps.get());
+    // Sends missed while held back are caught up, so the rate is kept over the run
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let report_interval = Duration::from_secs(args.report_interval_secs.max(1));
+    let mut reports = tokio::time::interval_at(started + report_interval, report_interval);
+    let mut interrupted = pin!(tokio::signal::ctrl_c());
+
+    loop {
+        tokio::select! {
+            scheduled = ticks.tick() => {
+                if scheduled >= deadline {
+                    break;
+                }
+                let permit = in_flight.clone().acquire_owned().await?;
+                let (transaction_data, duplicate) = generator.next_transaction()?;
+                let request = keyed(
+                    ComputeSingleFingerprintRequest {
+                        transaction_data: Some(transaction_data),
+                        salt: Default::default(),
+                        with_commitments: false,
+                        encodings: Default::default(),
+                        validate_only: false,
+                        _unknown_fields: Default::default(),
+                    },
+                    args.api_key.as_deref(),
+                )?;
+                let client = endpoints.acquire().await;
+                let window = window.clone();
+
+                tasks.spawn(async move {
+                    let result = client.compute_single_fingerprint(request).await;
+                    // Measured from the scheduled send, so the time held back by the saturated service is counted
+                    let latency = scheduled.elapsed();
+                    let mut window = window.lock().unwrap_or_else(PoisonError::into_inner);
+                    match result {
+                        Ok(_) => window.record_success(latency, duplicate),
+                        Err(e) => window.record_error(&e.into(), duplicate),
+                    }
+                    drop(permit);
+                });
+                while tasks.try_join_next().is_some() {}
+            }
+            _ = reports.tick() => {
+                let stats = take(&window);
+                let report = LoadReport::new(&stats, report_interval, &objectives);
+                total.merge(&stats);
+                log::info!(
+                    "{} s: {:.1} tps, p50 {:.1} ms, p99 {:.1} ms, {} failed, {:.0} errors of the budget left",
+                    started.elapsed().as_secs(),
+                    report.achieved_tps,
+                    report.p50_ms,
+                    report.p99_ms,
+                    report.failed,
+                    LoadReport::new(&total, started.elapsed(), &objectives).error_budget_remaining
+                );
+            }
+            _ = &mut interrupted => {
+                log::warn!("Interrupted, waiting for the requests in flight");
+                break;
+            }
+        }
+    }
+
+    while tasks.join_next().await.is_some() {}
+    total.merge(&take(&window));
+
+    let report = LoadReport::new(&total, started.elapsed(), &objectives);
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if !report.violations.is_empty() {
+        return Err(anyhow!(
+            "Objectives are missed: {}",
+            report.violations.join(", ")
+        ));
+    }
+
+    Ok(())
+}
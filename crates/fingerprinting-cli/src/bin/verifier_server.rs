@@ -0,0 +1,123 @@
+use clap::Parser;
+use fingerprinting_cli::config::{self, GrpcConfig, SchemaConfig, StoreConfig};
+use fingerprinting_core::namespace::Namespace;
+use fingerprinting_grpc::{net, FingerprintVerifierService};
+use fingerprinting_store::{
+    FingerprintStore, MemoryFingerprintStore, PostgresFingerprintStore, SqliteFingerprintStore,
+};
+use hocon::HoconLoader;
+use serde_derive::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use volo::net::MakeIncoming;
+use volo_grpc::server::{Server, ServiceBuilder};
+
+#[derive(Parser, Debug)]
+#[command(name = "fingerprinting-verifier")]
+#[command(about = "Read-only Fingerprint Verifier, holds no secret", long_about = None)]
+struct Args {
+    /// Config file location
+    #[arg(long)]
+    config: String,
+}
+
+#[derive(Deserialize)]
+struct VerifierConfig {
+    grpc: GrpcConfig,
+    /// Store (replica) the fingerprints are looked up in, lookups are disabled without it
+    store: Option<StoreConfig>,
+    /// Compacted verifying key of the fingerprint service the commitments are bound by,
+    /// disclosures are not verified without it
+    #[serde(rename = "commitment-key")]
+    commitment_key: Option<String>,
+    /// Namespace of the environment the verified fingerprints are computed in
+    namespace: Option<String>,
+    /// Fingerprint schemas of the environment and the one the verified fingerprints are computed by
+    #[serde(default)]
+    schemas: Vec<SchemaConfig>,
+    #[serde(rename = "schema-id")]
+    schema_id: Option<u32>,
+    /// Wire version the verified fingerprints are computed with, the current one when absent
+    #[serde(rename = "wire-version")]
+    wire_version: Option<u8>,
+    /// Epoch the verified fingerprints are computed with, the default epoch when absent
+    epoch: Option<String>,
+}
+
+#[volo::main]
+async fn main() -> Result<(), anyhow::Error> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .init();
+
+    log::info!("Starting fingerprinting verifier...");
+
+    let args = Args::parse();
+    let reference_config = include_str!("../../config/verifier-reference.conf");
+    log::info!("== loading configuration from {}", args.config);
+    let conf: VerifierConfig = HoconLoader::new()
+        .load_str(reference_config)?
+        .load_file(&args.config)?
+        .resolve()?;
+
+    let store: Option<Arc<dyn FingerprintStore>> = match &conf.store {
+        Some(StoreConfig::Memory) => {
+            log::warn!("== Fingerprints are looked up in the empty memory store");
+            Some(Arc::new(MemoryFingerprintStore::new()))
+        }
+        Some(StoreConfig::Postgres(postgres)) => {
+            log::info!("== Connecting to Postgres fingerprint store");
+            Some(Arc::new(
+                PostgresFingerprintStore::connect(&postgres.url, postgres.max_connections).await?,
+            ))
+        }
+        Some(StoreConfig::Sqlite(sqlite)) => {
+            log::info!("== Opening SQLite fingerprint store {}", sqlite.path);
+            Some(Arc::new(
+                SqliteFingerprintStore::connect(&sqlite.path).await?,
+            ))
+        }
+        None => {
+            log::info!("== Fingerprint lookups are disabled, store is not configured");
+            None
+        }
+    };
+
+    let namespace = match &conf.namespace {
+        Some(namespace) => Some(Namespace::new(namespace)?),
+        None => None,
+    };
+
+    let commitment_key = conf
+        .commitment_key
+        .as_deref()
+        .map(fingerprinting_offline_agent::verifying_key)
+        .transpose()?;
+
+    let address = format!("{}:{}", conf.grpc.host, conf.grpc.port);
+
+    log::info!("== starting GRPC server on {}", address);
+    let addr: SocketAddr = address.parse()?;
+    let incoming = volo::net::Address::from(addr).make_incoming().await?;
+
+    let service = FingerprintVerifierService::new()
+        .with_store(store)
+        .with_commitment_key(commitment_key)
+        .with_namespace(namespace)
+        .with_schema(config::fingerprint_schema(&conf.schemas, conf.schema_id)?)
+        .with_wire_version(config::wire_version(conf.wire_version)?)
+        .with_epoch(config::epoch(conf.epoch.as_deref())?);
+
+    Server::new()
+        .http2_adaptive_window(true)
+        .accept_http1(true)
+        .add_service(
+            ServiceBuilder::new(
+                net::outbe::fingerprint::v1::FingerprintVerifierServiceServer::new(service),
+            )
+            .build(),
+        )
+        .run(incoming)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))
+}
@@ -0,0 +1,313 @@
+//! Capacity log of the agent and the daily capacity report built from it
+//!
+//! The agent appends a sample of its counters to the JSON lines log every `capacity-log.interval-secs`:
+//! `{"at": "...", "started_at": "...", "computed": 10, "duplicates": 1, "failed": 0, "agents": [...]}`.
+//! Counters are cumulative since `started_at`, the report takes the differences between the consecutive samples
+//! and sums them by the day of the later sample. The sample of the restarted agent counts from zero again.
+
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, NaiveDate, Utc};
+use fingerprinting_core::PhaseMetrics;
+use fingerprinting_grpc::ServiceCounters;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+/// Partial evaluations of the other agent since the start
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AgentSample {
+    pub agent: usize,
+    pub evaluated: u64,
+    pub failed: u64,
+    /// Time the agent took to respond in total
+    pub total_micros: u64,
+}
+
+/// Counters of the agent at the time of the sample
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CapacitySample {
+    pub at: DateTime<Utc>,
+    /// Start of the agent, the counters are cumulative since then
+    pub started_at: DateTime<Utc>,
+    pub computed: u64,
+    pub duplicates: u64,
+    pub failed: u64,
+    /// Agents of the cooperative topology, none in the naive mode
+    #[serde(default)]
+    pub agents: Vec<AgentSample>,
+}
+
+impl CapacitySample {
+    pub fn new(
+        at: DateTime<Utc>,
+        started_at: DateTime<Utc>,
+        counters: &ServiceCounters,
+        metrics: Option<&PhaseMetrics>,
+    ) -> Self {
+        let stats = counters.stats();
+        let agents = metrics
+            .map(|metrics| metrics.evaluation_stats())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(agent, stats)| AgentSample {
+                agent,
+                evaluated: stats.evaluated,
+                failed: stats.failed,
+                total_micros: stats.total_time.as_micros() as u64,
+            })
+            .collect();
+
+        Self {
+            at,
+            started_at,
+            computed: stats.computed,
+            duplicates: stats.duplicates,
+            failed: stats.failed,
+            agents,
+        }
+    }
+}
+
+/// Capacity log the samples are appended to
+pub struct CapacityLog {
+    file: BufWriter<File>,
+}
+
+impl CapacityLog {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: BufWriter::new(file),
+        })
+    }
+
+    pub fn append(&mut self, sample: &CapacitySample) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.file, sample)?;
+        writeln!(self.file)?;
+        self.file.flush()?;
+
+        Ok(())
+    }
+}
+
+pub fn read_samples<R: BufRead>(input: R) -> Result<Vec<CapacitySample>, Error> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(number, line)| {
+            serde_json::from_str(&line?)
+                .map_err(|e| anyhow!("Invalid sample at line {}: {}", number + 1, e))
+        })
+        .collect()
+}
+
+/// Partial evaluations of the agent within the day
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct AgentDay {
+    pub agent: usize,
+    pub evaluated: u64,
+    pub failed: u64,
+    /// Mean time the agent took to respond
+    pub mean_latency_ms: f64,
+    pub error_rate: f64,
+}
+
+/// Fingerprints served within the day
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CapacityDay {
+    pub day: NaiveDate,
+    pub fingerprints: u64,
+    pub duplicates: u64,
+    pub failed: u64,
+    /// Failed of all the fingerprints asked for
+    pub error_rate: f64,
+    pub agents: Vec<AgentDay>,
+}
+
+/// Counters of the sample gained since the previous one, all of them when the agent restarted in between
+fn increment(previous: Option<&CapacitySample>, sample: &CapacitySample) -> CapacitySample {
+    let previous = match previous {
+        Some(previous) if previous.started_at == sample.started_at => previous,
+        _ => return sample.clone(),
+    };
+    let agents = sample
+        .agents
+        .iter()
+        .map(|agent| {
+            let before = previous
+                .agents
+                .iter()
+                .find(|before| before.agent == agent.agent)
+                .copied()
+                .unwrap_or_default();
+
+            AgentSample {
+                agent: agent.agent,
+                evaluated: agent.evaluated.saturating_sub(before.evaluated),
+                failed: agent.failed.saturating_sub(before.failed),
+                total_micros: agent.total_micros.saturating_sub(before.total_micros),
+            }
+        })
+        .collect();
+
+    CapacitySample {
+        computed: sample.computed.saturating_sub(previous.computed),
+        duplicates: sample.duplicates.saturating_sub(previous.duplicates),
+        failed: sample.failed.saturating_sub(previous.failed),
+        agents,
+        ..sample.clone()
+    }
+}
+
+fn ratio(part: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        total => part as f64 / total as f64,
+    }
+}
+
+/// Builds the report ordered by day, the samples may come in any order
+pub fn capacity_report(samples: &[CapacitySample]) -> Vec<CapacityDay> {
+    let mut samples = samples.iter().collect::<Vec<_>>();
+    samples.sort_by_key(|sample| sample.at);
+
+    let mut days = BTreeMap::<NaiveDate, (CapacityDay, BTreeMap<usize, AgentSample>)>::new();
+    let mut previous = None;
+    for sample in samples {
+        let gained = increment(previous, sample);
+        previous = Some(sample);
+
+        let (day, agents) = days.entry(sample.at.date_naive()).or_default();
+        day.fingerprints += gained.computed;
+        day.duplicates += gained.duplicates;
+        day.failed += gained.failed;
+        for gained in gained.agents {
+            let agent = agents.entry(gained.agent).or_default();
+            agent.evaluated += gained.evaluated;
+            agent.failed += gained.failed;
+            agent.total_micros += gained.total_micros;
+        }
+    }
+
+    days.into_iter()
+        .map(|(date, (day, agents))| CapacityDay {
+            day: date,
+            error_rate: ratio(day.failed, day.fingerprints + day.failed),
+            agents: agents
+                .into_iter()
+                .map(|(agent, sample)| {
+                    let responses = sample.evaluated + sample.failed;
+                    AgentDay {
+                        agent,
+                        evaluated: sample.evaluated,
+                        failed: sample.failed,
+                        mean_latency_ms: match responses {
+                            0 => 0.0,
+                            responses => sample.total_micros as f64 / responses as f64 / 1000.0,
+                        },
+                        error_rate: ratio(sample.failed, responses),
+                    }
+                })
+                .collect(),
+            ..day
+        })
+        .collect()
+}
+
+/// One row per day, the agents seen by the report get the latency and the error rate columns
+pub fn write_csv<W: Write>(days: &[CapacityDay], output: &mut W) -> Result<(), Error> {
+    let agents = days
+        .iter()
+        .flat_map(|day| day.agents.iter().map(|agent| agent.agent))
+        .collect::<BTreeSet<_>>();
+
+    write!(output, "day,fingerprints,duplicates,failed,error_rate")?;
+    for agent in agents.iter() {
+        write!(
+            output,
+            ",agent_{}_latency_ms,agent_{}_error_rate",
+            agent, agent
+        )?;
+    }
+    writeln!(output)?;
+
+    for day in days {
+        write!(
+            output,
+            "{},{},{},{},{:.4}",
+            day.day, day.fingerprints, day.duplicates, day.failed, day.error_rate
+        )?;
+        for agent in agents.iter() {
+            match day.agents.iter().find(|known| known.agent == *agent) {
+                Some(known) => write!(
+                    output,
+                    ",{:.1},{:.4}",
+                    known.mean_latency_ms, known.error_rate
+                )?,
+                None => write!(output, ",,")?,
+            }
+        }
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacity_report() -> Result<(), Error> {
+        // Agent restarted at 23:00 of the first day, the second day starts with the samples of the new start
+        let input = r#"
+{"at": "2025-09-16T12:00:00Z", "started_at": "2025-09-16T00:00:00Z", "computed": 100, "duplicates": 5, "failed": 0, "agents": [{"agent": 2, "evaluated": 100, "failed": 0, "total_micros": 200000}]}
+{"at": "2025-09-16T18:00:00Z", "started_at": "2025-09-16T00:00:00Z", "computed": 160, "duplicates": 8, "failed": 4, "agents": [{"agent": 2, "evaluated": 160, "failed": 4, "total_micros": 380000}]}
+
+{"at": "2025-09-17T06:00:00Z", "started_at": "2025-09-16T23:00:00Z", "computed": 30, "duplicates": 0, "failed": 0, "agents": [{"agent": 2, "evaluated": 20, "failed": 0, "total_micros": 60000}, {"agent": 3, "evaluated": 10, "failed": 0, "total_micros": 50000}]}
+"#;
+
+        let samples = read_samples(input.as_bytes())?;
+        assert_eq!(samples.len(), 3);
+        let report = capacity_report(&samples);
+
+        assert_eq!(report.len(), 2);
+        let first = &report[0];
+        assert_eq!(first.day, NaiveDate::from_ymd_opt(2025, 9, 16).unwrap());
+        assert_eq!(
+            (first.fingerprints, first.duplicates, first.failed),
+            (160, 8, 4)
+        );
+        assert_eq!(first.error_rate, 4.0 / 164.0);
+        assert_eq!(first.agents.len(), 1);
+        assert_eq!(first.agents[0].mean_latency_ms, 380.0 / 164.0);
+
+        // Counters of the restarted agent are not subtracted from the ones before the restart
+        let second = &report[1];
+        assert_eq!((second.fingerprints, second.failed), (30, 0));
+        assert_eq!(
+            second
+                .agents
+                .iter()
+                .map(|agent| (agent.agent, agent.mean_latency_ms))
+                .collect::<Vec<_>>(),
+            vec![(2, 3.0), (3, 5.0)]
+        );
+
+        let mut csv = vec![];
+        write_csv(&report, &mut csv)?;
+        let csv = String::from_utf8(csv)?;
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "day,fingerprints,duplicates,failed,error_rate,agent_2_latency_ms,agent_2_error_rate,agent_3_latency_ms,agent_3_error_rate"
+        );
+        assert_eq!(lines[1], "2025-09-16,160,8,4,0.0244,2.3,0.0244,,");
+
+        Ok(())
+    }
+}
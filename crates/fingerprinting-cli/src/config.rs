@@ -1,14 +1,221 @@
+use crate::downgrade::{ProtocolMode, ProtocolState};
+use anyhow::anyhow;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use fingerprinting_core::clock::Epoch;
+use fingerprinting_core::entropy::{
+    DeviceEntropy, EntropySource, HealthTested, HmacDrbg, OsEntropy,
+};
+use fingerprinting_core::namespace::Namespace;
+use fingerprinting_core::pii::{PiiPolicy, Scrub};
+use fingerprinting_core::schema::{FingerprintSchema, FingerprintSchemaRegistry, SchemaComponent};
+use fingerprinting_core::version::IncompatibleAgentPolicy;
+use fingerprinting_core::wire::WireVersion;
+use fingerprinting_core::{Compact, DiversityPolicy, Member, Members, TimeBucket};
+use fingerprinting_grpc::{ApiKey, UsageAccounting};
+use fingerprinting_grpc_agent::AgentClientTls;
+use fingerprinting_p2p_agent::Multiaddr;
+use fingerprinting_store::usage::UsageLedger;
+use fingerprinting_types::InputLimits;
+use halo2_axiom::halo2curves::bn256::Fr;
 use serde_derive::Deserialize;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::sync::Arc;
+
+pub mod schema;
+
+use schema::ConfigSchema;
+
+/// Violations of the resolved configuration, reported all together rather than failing on the first one
+#[derive(Debug, Default)]
+pub struct ConfigViolations {
+    violations: Vec<String>,
+}
+
+impl ConfigViolations {
+    pub fn push(&mut self, violation: impl Into<String>) {
+        self.violations.push(violation.into());
+    }
+
+    /// Records the violation when the `value` is refused
+    pub fn check<T, E: Display>(&mut self, key: &str, value: Result<T, E>) -> Option<T> {
+        value.map_err(|e| self.push(format!("{}: {}", key, e))).ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<(), anyhow::Error> {
+        if self.violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "Invalid configuration, {} violation(s):\n  - {}",
+            self.violations.len(),
+            self.violations.join("\n  - ")
+        ))
+    }
+}
+
+/// Compacted secret (shard) decoding to the field element
+fn check_secret(key: &str, compacted: &str, violations: &mut ConfigViolations) {
+    violations.check(
+        key,
+        Compact::unwrap(compacted).map(|_: Fr| ()).map_err(|_| {
+            "should be the compacted (base58) 32 bytes representation of the field element"
+        }),
+    );
+}
+
+/// `host:port` address, the host is resolved only when connecting
+fn check_address(key: &str, address: &str, violations: &mut ConfigViolations) {
+    let port = address
+        .rsplit_once(':')
+        .filter(|(host, _)| !host.is_empty())
+        .map(|(_, port)| port.parse::<u16>());
+    if !matches!(port, Some(Ok(_))) {
+        violations.push(format!("{}: {} should be host:port", key, address));
+    }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct AgentConfig {
     pub agent_id: usize,
     pub secret_shard: String,
+    /// File holding the compacted secret shard instead of `secret_shard`,
+    /// it should not be accessible by group and others
+    #[serde(rename = "secret-shard-file")]
+    pub secret_shard_file: Option<String>,
+    /// Downstream signer holding the shard instead of this agent, `secret_shard` is ignored then
+    pub relay: Option<RelayConfig>,
+}
+
+/// Signer process the partial evaluations are relayed to, see `fingerprinting_grpc_agent::relay`
+#[derive(Deserialize, Debug)]
+pub struct RelayConfig {
+    /// Program of the signer and its arguments, started once with the agent
+    pub command: Vec<String>,
+    #[serde(rename = "timeout-secs", default = "RelayConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl RelayConfig {
+    fn default_timeout_secs() -> u64 {
+        10
+    }
+}
+
+/// Hardening of the agent process, see `hardening` module
+#[derive(Deserialize, Debug, Default)]
+pub struct HardeningConfig {
+    /// User to switch to after the port is bound
+    pub user: Option<String>,
+    /// Group to switch to, the primary group of the user by default
+    pub group: Option<String>,
+    #[serde(default)]
+    pub seccomp: SeccompMode,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SeccompMode {
+    #[default]
+    Disabled,
+    /// System calls outside of the profile are logged by the kernel, useful to verify the profile
+    Log,
+    /// Process is killed on the system call outside of the profile
+    Enforce,
 }
 #[derive(Deserialize, Debug)]
 pub struct AgentReferenceConfig {
     pub agent_id: usize,
     pub address: String,
+    pub tls: Option<AgentTlsConfig>,
+    /// Compacted Ed25519 key the partial evaluations of the agent are signed with, unsigned ones are refused then
+    #[serde(rename = "verifying-key")]
+    pub verifying_key: Option<String>,
+    /// Id of the institution the agent belongs to, see `DiversityConfig`
+    pub institution: Option<String>,
+    /// Name of the operator running the agent, e.g. `BankCorp`
+    pub operator: Option<String>,
+    /// Region the agent is deployed in, e.g. `eu-west`
+    pub region: Option<String>,
+}
+
+impl AgentReferenceConfig {
+    fn member(&self) -> Member {
+        Member {
+            operator: self.operator.clone(),
+            institution: self.institution.clone(),
+            region: self.region.clone(),
+            verifying_key: self.verifying_key.clone(),
+        }
+    }
+}
+
+/// TLS of the connection to the agent, all the files are PEM encoded
+#[derive(Deserialize, Debug)]
+pub struct AgentTlsConfig {
+    #[serde(rename = "ca-bundle")]
+    pub ca_bundle: Option<String>,
+    #[serde(rename = "server-name")]
+    pub server_name: Option<String>,
+    #[serde(rename = "client-certificate")]
+    pub client_certificate: Option<String>,
+    #[serde(rename = "client-key")]
+    pub client_key: Option<String>,
+}
+
+impl AgentConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        match &self.relay {
+            Some(relay) => {
+                if self.secret_shard_file.is_some() {
+                    violations.push("agent.secret-shard-file: the relay holds no shard");
+                }
+                if relay.command.is_empty() {
+                    violations.push("agent.relay.command: should name the signer program");
+                }
+                if relay.timeout_secs == 0 {
+                    violations.push("agent.relay.timeout-secs: 0 should be above 0");
+                }
+            }
+            None if self.secret_shard_file.is_none() => {
+                check_secret("agent.secret_shard", &self.secret_shard, violations)
+            }
+            None => {}
+        }
+    }
+}
+
+impl AgentTlsConfig {
+    pub fn client_tls(&self) -> Result<AgentClientTls, anyhow::Error> {
+        let mut tls = AgentClientTls::new();
+        if let Some(ca_bundle) = &self.ca_bundle {
+            tls = tls.with_ca_bundle_file(ca_bundle)?;
+        }
+        if let Some(server_name) = &self.server_name {
+            tls = tls.with_server_name(server_name);
+        }
+        match (&self.client_certificate, &self.client_key) {
+            (Some(certificate), Some(key)) => {
+                tls = tls.with_client_identity_files(certificate, key)?;
+            }
+            (None, None) => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Both client-certificate and client-key are required for mutual TLS"
+                ))
+            }
+        }
+
+        Ok(tls)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -16,13 +223,937 @@ pub struct GrpcConfig {
     pub host: String,
     pub port: u16,
 }
+
+impl GrpcConfig {
+    pub fn validate(&self, key: &str, violations: &mut ConfigViolations) {
+        violations.check(
+            key,
+            format!("{}:{}", self.host, self.port)
+                .parse::<SocketAddr>()
+                .map_err(|_| format!("host {} should be the IP address to listen on", self.host)),
+        );
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct CooperativeTopologyConfig {
     pub agent_id: usize,
     pub secret_shard: String,
+    /// Id of the institution this agent belongs to, see `DiversityConfig`
+    pub institution: Option<String>,
+    /// Name of the operator running this agent
+    pub operator: Option<String>,
+    /// Region this agent is deployed in, not to be confused with the `region` of the two-level topology
+    #[serde(rename = "deployment-region")]
+    pub deployment_region: Option<String>,
     pub agents: usize,
     pub threshold: usize,
     pub members: Vec<AgentReferenceConfig>,
+    pub spiffe: Option<SpiffeConfig>,
+    pub libp2p: Option<Libp2pConfig>,
+    pub offline: Option<OfflineTopologyConfig>,
+    /// What is done with the agents of the incompatible versions when the topology is built
+    #[serde(rename = "incompatible-agents", default)]
+    pub incompatible_agents: IncompatibleAgentsMode,
+    /// Number of the recent blinded points whose partial evaluations are kept for the coordinator retries
+    #[serde(rename = "exponent-cache")]
+    pub exponent_cache: Option<NonZeroUsize>,
+    /// Partial evaluations served to the coordinators at once, see `EvaluationLimitsConfig`
+    #[serde(rename = "evaluation-limits")]
+    pub evaluation_limits: Option<EvaluationLimitsConfig>,
+    /// Quarantine of the agents failing the evaluations in a row, the agents are always asked when absent
+    #[serde(rename = "circuit-breakers")]
+    pub circuit_breakers: Option<CircuitBreakersConfig>,
+    /// Region of the two-level topology, `agents`, `threshold` and `members` are the ones within the region then
+    pub region: Option<RegionConfig>,
+    /// Source of the blinding factors, the OS RNG by default
+    pub entropy: Option<EntropyConfig>,
+    /// Signatures of the partial evaluations, see `PartialSignaturesConfig`
+    pub signatures: Option<PartialSignaturesConfig>,
+    /// Minimum number of the institutions among the agents of every fingerprint
+    pub diversity: Option<DiversityConfig>,
+}
+
+impl CooperativeTopologyConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        let key = "fingerprint-service";
+        check_secret(
+            &format!("{}.secret_shard", key),
+            &self.secret_shard,
+            violations,
+        );
+
+        if self.threshold == 0 || self.threshold > self.agents {
+            violations.push(format!(
+                "{}.threshold: {} should be from 1 up to the {} agents",
+                key, self.threshold, self.agents
+            ));
+        }
+        let agent_ids = 1..=self.agents;
+        if !agent_ids.contains(&self.agent_id) {
+            violations.push(format!(
+                "{}.agent_id: {} should be from 1 up to the {} agents",
+                key, self.agent_id, self.agents
+            ));
+        }
+
+        let mut members = HashSet::new();
+        for member in &self.members {
+            let member_key = format!("{}.members[agent_id = {}]", key, member.agent_id);
+            if member.agent_id == self.agent_id {
+                violations.push(format!(
+                    "{}: agent itself should not be the member",
+                    member_key
+                ));
+            } else if !agent_ids.contains(&member.agent_id) {
+                violations.push(format!(
+                    "{}: agent_id should be from 1 up to the {} agents",
+                    member_key, self.agents
+                ));
+            } else if !members.insert(member.agent_id) {
+                violations.push(format!("{}: agent is listed more than once", member_key));
+            }
+            if let Some(verifying_key) = &member.verifying_key {
+                violations.check(
+                    &format!("{}.verifying-key", member_key),
+                    fingerprinting_offline_agent::verifying_key(verifying_key),
+                );
+            }
+
+            match &self.libp2p {
+                Some(_) => {
+                    violations.check(&member_key, member.address.parse::<Multiaddr>());
+                }
+                None => check_address(&member_key, &member.address, violations),
+            }
+        }
+        // The agent itself is one of the cooperating agents
+        if members.len() + 1 < self.threshold {
+            violations.push(format!(
+                "{}.members: threshold {} is not reachable with {} other agents",
+                key,
+                self.threshold,
+                members.len()
+            ));
+        }
+
+        if let Some(libp2p) = &self.libp2p {
+            violations.check(
+                &format!("{}.libp2p.listen", key),
+                libp2p.listen.parse::<Multiaddr>(),
+            );
+            violations.check(
+                &format!("{}.libp2p.key", key),
+                Compact::unwrap(&libp2p.key)
+                    .and_then(|key: Bytes| fingerprinting_p2p_agent::ed25519_keypair(&key)),
+            );
+        }
+        if let Some(region) = &self.region {
+            region.validate(violations);
+            if self.libp2p.is_some() || self.offline.is_some() {
+                violations.push(format!(
+                    "{}.region: regions are reached via gRPC only, libp2p and offline agents are not supported",
+                    key
+                ));
+            }
+        }
+        if let Some(entropy) = &self.entropy {
+            entropy.validate(violations);
+        }
+        if let Some(diversity) = &self.diversity {
+            self.validate_diversity(diversity, violations);
+        }
+        if let Some(signatures) = &self.signatures {
+            if let Some(signing_key) = &signatures.signing_key {
+                violations.check(
+                    &format!("{}.signatures.signing-key", key),
+                    fingerprinting_offline_agent::signing_key(signing_key),
+                );
+            }
+        }
+        if let Some(offline) = &self.offline {
+            violations.check(
+                &format!("{}.offline.signing-key", key),
+                fingerprinting_offline_agent::signing_key(&offline.signing_key),
+            );
+            for agent in &offline.agents {
+                violations.check(
+                    &format!("{}.offline.agents[agent_id = {}]", key, agent.agent_id),
+                    fingerprinting_offline_agent::verifying_key(&agent.verifying_key),
+                );
+            }
+        }
+    }
+}
+
+impl CooperativeTopologyConfig {
+    fn validate_diversity(&self, diversity: &DiversityConfig, violations: &mut ConfigViolations) {
+        let key = "fingerprint-service";
+        let required = diversity.min_institutions;
+        if required > self.threshold {
+            violations.push(format!(
+                "{}.diversity.min-institutions: {} institutions are never reached by the threshold {}",
+                key, required, self.threshold
+            ));
+        }
+        if self.institution.is_none() {
+            violations.push(format!(
+                "{}.institution: required by the diversity policy",
+                key
+            ));
+        }
+        for member in self
+            .members
+            .iter()
+            .filter(|member| member.institution.is_none())
+        {
+            violations.push(format!(
+                "{}.members[agent_id = {}].institution: required by the diversity policy",
+                key, member.agent_id
+            ));
+        }
+
+        let institutions = self
+            .members
+            .iter()
+            .filter_map(|member| member.institution.as_ref())
+            .chain(&self.institution)
+            .collect::<HashSet<_>>();
+        if institutions.len() < required {
+            violations.push(format!(
+                "{}.diversity.min-institutions: {} institutions are required, the agents are run by {}",
+                key,
+                required,
+                institutions.len()
+            ));
+        }
+    }
+
+    /// Metadata of this agent and of the members
+    pub fn members(&self) -> Members {
+        let own = Member {
+            operator: self.operator.clone(),
+            institution: self.institution.clone(),
+            region: self.deployment_region.clone(),
+            verifying_key: None,
+        };
+
+        self.members.iter().fold(
+            Members::default().with_member(self.agent_id, own),
+            |members, member| members.with_member(member.agent_id, member.member()),
+        )
+    }
+
+    /// Diversity policy of the agents, none is required when not configured
+    pub fn diversity_policy(&self) -> DiversityPolicy {
+        match &self.diversity {
+            Some(diversity) => self.members().diversity(diversity.min_institutions),
+            None => DiversityPolicy::default(),
+        }
+    }
+}
+
+/// Agents of every fingerprint span at least `min-institutions` distinct institutions, so the threshold is not
+/// reached by the agents of the same operator. The coordinator refuses to compute otherwise.
+#[derive(Deserialize, Debug)]
+pub struct DiversityConfig {
+    #[serde(rename = "min-institutions")]
+    pub min_institutions: usize,
+}
+
+/// Regional group of agents, the secret is shared between the regions and the share of the region between its agents
+#[derive(Deserialize, Debug)]
+pub struct RegionConfig {
+    pub region_id: usize,
+    /// Number of the regions
+    pub regions: usize,
+    /// Number of the regions cooperating in every fingerprint
+    pub threshold: usize,
+    /// Regional coordinators of the other regions, serving the aggregate evaluations of their regions
+    pub coordinators: Vec<RegionReferenceConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RegionReferenceConfig {
+    pub region_id: usize,
+    pub address: String,
+    pub tls: Option<AgentTlsConfig>,
+}
+
+impl RegionConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        let key = "fingerprint-service.region";
+        if self.threshold == 0 || self.threshold > self.regions {
+            violations.push(format!(
+                "{}.threshold: {} should be from 1 up to the {} regions",
+                key, self.threshold, self.regions
+            ));
+        }
+        let region_ids = 1..=self.regions;
+        if !region_ids.contains(&self.region_id) {
+            violations.push(format!(
+                "{}.region_id: {} should be from 1 up to the {} regions",
+                key, self.region_id, self.regions
+            ));
+        }
+
+        let mut coordinators = HashSet::new();
+        for coordinator in &self.coordinators {
+            let coordinator_key = format!(
+                "{}.coordinators[region_id = {}]",
+                key, coordinator.region_id
+            );
+            if coordinator.region_id == self.region_id {
+                violations.push(format!(
+                    "{}: region itself should not be the coordinator",
+                    coordinator_key
+                ));
+            } else if !region_ids.contains(&coordinator.region_id) {
+                violations.push(format!(
+                    "{}: region_id should be from 1 up to the {} regions",
+                    coordinator_key, self.regions
+                ));
+            } else if !coordinators.insert(coordinator.region_id) {
+                violations.push(format!(
+                    "{}: region is listed more than once",
+                    coordinator_key
+                ));
+            }
+            check_address(&coordinator_key, &coordinator.address, violations);
+        }
+        // The region itself is one of the cooperating regions
+        if coordinators.len() + 1 < self.threshold {
+            violations.push(format!(
+                "{}.coordinators: threshold {} is not reachable with {} other regions",
+                key,
+                self.threshold,
+                coordinators.len()
+            ));
+        }
+    }
+}
+
+/// Randomness of the blinding factors, every draw passes the continuous health tests
+#[derive(Deserialize, Debug, Default)]
+pub struct EntropyConfig {
+    #[serde(default)]
+    pub source: EntropySourceKind,
+    /// Hardware RNG device of the `device` source, e.g. "/dev/hwrng"
+    pub device: Option<String>,
+    /// HMAC-DRBG of SP 800-90A seeded from the source, rather than the source directly
+    #[serde(default)]
+    pub drbg: bool,
+    /// Requests the DRBG serves before reseeding from the source
+    #[serde(rename = "reseed-interval")]
+    pub reseed_interval: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntropySourceKind {
+    /// The operating system RNG
+    #[default]
+    Os,
+    /// Character device of the HSM or TPM
+    Device,
+}
+
+impl EntropyConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        let key = "fingerprint-service.entropy";
+        match (self.source, &self.device) {
+            (EntropySourceKind::Device, None) => {
+                violations.push(format!("{}.device: required by the device source", key))
+            }
+            (EntropySourceKind::Os, Some(_)) => {
+                violations.push(format!("{}.device: only used by the device source", key))
+            }
+            _ => {}
+        }
+        if self.reseed_interval.is_some() && !self.drbg {
+            violations.push(format!("{}.reseed-interval: only used by the DRBG", key));
+        }
+        if self.reseed_interval == Some(0) {
+            violations.push(format!("{}.reseed-interval: should be above 0", key));
+        }
+    }
+
+    /// Opens the configured source and runs its start-up health check
+    pub fn source(&self) -> Result<Arc<dyn EntropySource>, anyhow::Error> {
+        let source: Arc<dyn EntropySource> = match (self.source, &self.device) {
+            (EntropySourceKind::Device, Some(device)) => {
+                Arc::new(HealthTested::new(DeviceEntropy::open(device)?))
+            }
+            (EntropySourceKind::Device, None) => {
+                return Err(anyhow!("Entropy device is not configured"))
+            }
+            (EntropySourceKind::Os, _) => Arc::new(HealthTested::new(OsEntropy)),
+        };
+        let source: Arc<dyn EntropySource> = if self.drbg {
+            let mut drbg = HmacDrbg::new(source)?;
+            if let Some(reseed_interval) = self.reseed_interval {
+                drbg = drbg.with_reseed_interval(reseed_interval);
+            }
+            Arc::new(HealthTested::new(drbg))
+        } else {
+            source
+        };
+
+        source
+            .health_check()
+            .map_err(|e| anyhow!("Entropy source failed the health check: {}", e))?;
+
+        Ok(source)
+    }
+}
+
+/// Ed25519 signatures of the partial evaluations, so a wrong partial evaluation is attributed to the agent
+#[derive(Deserialize, Debug)]
+pub struct PartialSignaturesConfig {
+    /// Compacted Ed25519 key the partial evaluations of this agent are signed with
+    #[serde(rename = "signing-key")]
+    pub signing_key: Option<String>,
+    /// File the signed partial evaluations of the members are appended to
+    pub transcript: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IncompatibleAgentsMode {
+    /// Agent does not start
+    #[default]
+    Refuse,
+    /// Agents are excluded with the warning while the threshold is reachable
+    Exclude,
+}
+
+impl From<IncompatibleAgentsMode> for IncompatibleAgentPolicy {
+    fn from(mode: IncompatibleAgentsMode) -> Self {
+        match mode {
+            IncompatibleAgentsMode::Refuse => IncompatibleAgentPolicy::Refuse,
+            IncompatibleAgentsMode::Exclude => IncompatibleAgentPolicy::Exclude,
+        }
+    }
+}
+
+/// Agents in the air-gapped environments, reached via signed request and response files
+#[derive(Deserialize, Debug)]
+pub struct OfflineTopologyConfig {
+    /// Directory the request files are written to
+    pub outbox: String,
+    /// Directory the response files are picked up from
+    pub inbox: String,
+    /// Compacted Ed25519 key the requests are signed with
+    #[serde(rename = "signing-key")]
+    pub signing_key: String,
+    #[serde(
+        rename = "deadline-secs",
+        default = "OfflineTopologyConfig::default_deadline_secs"
+    )]
+    pub deadline_secs: u64,
+    pub agents: Vec<OfflineAgentReferenceConfig>,
+}
+
+impl OfflineTopologyConfig {
+    fn default_deadline_secs() -> u64 {
+        3600
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OfflineAgentReferenceConfig {
+    pub agent_id: usize,
+    /// Compacted Ed25519 key the responses of the agent are signed with
+    #[serde(rename = "verifying-key")]
+    pub verifying_key: String,
+}
+
+/// libp2p transport between the agents, member addresses are multiaddrs ending with `/p2p/<peer id>` then
+#[derive(Deserialize, Debug)]
+pub struct Libp2pConfig {
+    /// Multiaddr to listen on, e.g. `/ip4/0.0.0.0/tcp/9101`
+    pub listen: String,
+    /// Compacted Ed25519 secret key of the node
+    pub key: String,
+}
+
+/// SPIFFE identity of the agent, used for the mutual TLS with the other agents in both directions
+#[derive(Deserialize, Debug)]
+pub struct SpiffeConfig {
+    #[serde(rename = "workload-api-socket")]
+    pub workload_api_socket: String,
+    /// Trust domains of the other agents and coordinators
+    #[serde(rename = "trust-domains")]
+    pub trust_domains: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PseudonymizationConfig {
+    pub key: String,
+}
+
+impl PseudonymizationConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        check_secret("pseudonymization.key", &self.key, violations);
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostgresStoreConfig {
+    pub url: String,
+    #[serde(default = "PostgresStoreConfig::default_max_connections")]
+    pub max_connections: u32,
+}
+
+impl PostgresStoreConfig {
+    fn default_max_connections() -> u32 {
+        10
+    }
+}
+
+/// Store of the single node deployments, kept in the single file
+#[derive(Deserialize, Debug)]
+pub struct SqliteStoreConfig {
+    /// Database file, created if missing
+    pub path: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum StoreConfig {
+    Memory,
+    Postgres(PostgresStoreConfig),
+    Sqlite(SqliteStoreConfig),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RecordingConfig {
+    /// File the exchanges are appended to
+    pub path: String,
+}
+
+/// Signed record of the protocol state the agent runs with, see `downgrade`
+#[derive(Deserialize, Debug)]
+pub struct StateFileConfig {
+    pub path: String,
+    /// Compacted Ed25519 key the state is signed with, see `fingerprinting-cli generate-signing-key`
+    #[serde(rename = "signing-key")]
+    pub signing_key: String,
+}
+
+/// Samples of the service counters for the capacity report, see `capacity::CapacitySample`
+#[derive(Deserialize, Debug)]
+pub struct CapacityLogConfig {
+    /// JSON lines file the samples are appended to
+    pub path: String,
+    #[serde(
+        rename = "interval-secs",
+        default = "CapacityLogConfig::default_interval_secs"
+    )]
+    pub interval_secs: u64,
+}
+
+impl CapacityLogConfig {
+    fn default_interval_secs() -> u64 {
+        300
+    }
+}
+
+/// Snapshots of the coordinator state restored on start, see `snapshot`
+#[derive(Deserialize, Debug)]
+pub struct SnapshotConfig {
+    pub path: String,
+    #[serde(
+        rename = "interval-secs",
+        default = "SnapshotConfig::default_interval_secs"
+    )]
+    pub interval_secs: u64,
+}
+
+impl SnapshotConfig {
+    fn default_interval_secs() -> u64 {
+        60
+    }
+}
+
+/// Fingerprint schema known to the deployment, see `FingerprintSchema`
+#[derive(Deserialize, Debug, Clone)]
+pub struct SchemaConfig {
+    /// Id serialized into the preimage, above 0 and never reused for another layout
+    pub id: u32,
+    /// Optional components bound in the order, e.g. "merchant", "country"
+    pub components: Vec<String>,
+    /// Transfer fingerprints the same from both sides, the components list the "counterparty"
+    #[serde(rename = "direction-agnostic", default)]
+    pub direction_agnostic: bool,
+}
+
+impl SchemaConfig {
+    pub fn schema(&self) -> Result<FingerprintSchema, anyhow::Error> {
+        let components = self
+            .components
+            .iter()
+            .map(|component| component.parse::<SchemaComponent>())
+            .collect::<Result<_, _>>()?;
+
+        if self.direction_agnostic {
+            FingerprintSchema::direction_agnostic(self.id, components)
+        } else {
+            FingerprintSchema::new(self.id, components)
+        }
+    }
+}
+
+/// Registry of the configured `schemas`
+pub fn schema_registry(
+    schemas: &[SchemaConfig],
+) -> Result<FingerprintSchemaRegistry, anyhow::Error> {
+    let mut registry = FingerprintSchemaRegistry::new();
+    for schema in schemas {
+        registry.register(schema.schema()?)?;
+    }
+
+    Ok(registry)
+}
+
+/// Schema of the `schema_id` from the configured `schemas`, the built-in layout when absent
+pub fn fingerprint_schema(
+    schemas: &[SchemaConfig],
+    schema_id: Option<u32>,
+) -> Result<Option<Arc<FingerprintSchema>>, anyhow::Error> {
+    let registry = schema_registry(schemas)?;
+
+    schema_id
+        .map(|schema_id| registry.get(schema_id))
+        .transpose()
+}
+
+/// Wire version of the configured `version`, the current one when absent
+pub fn wire_version(version: Option<u8>) -> Result<WireVersion, anyhow::Error> {
+    version.map_or(Ok(WireVersion::CURRENT), WireVersion::try_from)
+}
+
+/// Epoch of the configured date, e.g. "2023-01-01", the default epoch when absent
+pub fn epoch(epoch: Option<&str>) -> Result<Epoch, anyhow::Error> {
+    epoch.map_or(Ok(Epoch::default()), str::parse)
+}
+
+/// Time bucket of the fuzzy fingerprints, minute or hour, none when absent
+pub fn fuzzy_bucket(bucket: Option<&str>) -> Result<Option<TimeBucket>, anyhow::Error> {
+    bucket.map(str::parse).transpose()
+}
+
+/// Maximum sizes of the string fields in bytes, the absent ones are the sizes the components encode, see
+/// `InputLimits::MAX`
+#[derive(Deserialize, Debug, Default)]
+pub struct InputLimitsConfig {
+    pub bic: Option<usize>,
+    pub currency: Option<usize>,
+    #[serde(rename = "series-id")]
+    pub series_id: Option<usize>,
+    #[serde(rename = "merchant-id")]
+    pub merchant_id: Option<usize>,
+    #[serde(rename = "country-code")]
+    pub country_code: Option<usize>,
+    #[serde(rename = "transaction-reference")]
+    pub transaction_reference: Option<usize>,
+    pub mcc: Option<usize>,
+    /// Limit of the payer and of the payee identifier each
+    #[serde(rename = "counterparty-id")]
+    pub counterparty_id: Option<usize>,
+}
+
+impl InputLimitsConfig {
+    pub fn limits(&self) -> InputLimits {
+        let max = InputLimits::MAX;
+
+        InputLimits {
+            bic: self.bic.unwrap_or(max.bic),
+            currency: self.currency.unwrap_or(max.currency),
+            series_id: self.series_id.unwrap_or(max.series_id),
+            merchant_id: self.merchant_id.unwrap_or(max.merchant_id),
+            country_code: self.country_code.unwrap_or(max.country_code),
+            transaction_reference: self
+                .transaction_reference
+                .unwrap_or(max.transaction_reference),
+            mcc: self.mcc.unwrap_or(max.mcc),
+            counterparty_id: self.counterparty_id.unwrap_or(max.counterparty_id),
+        }
+    }
+}
+
+/// Scrubbing of the values written into the logs and the errors, see `PiiPolicy`
+#[derive(Deserialize, Debug)]
+pub struct PiiConfig {
+    /// BICs, amounts, date times and merchants, truncated by default
+    #[serde(default = "PiiConfig::default_confidential")]
+    pub confidential: ScrubConfig,
+    /// Counterparties, references, series and salts, redacted by default
+    #[serde(default = "PiiConfig::default_personal")]
+    pub personal: ScrubConfig,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrubConfig {
+    Reveal,
+    Truncate,
+    Redact,
+}
+
+impl From<ScrubConfig> for Scrub {
+    fn from(value: ScrubConfig) -> Self {
+        match value {
+            ScrubConfig::Reveal => Scrub::Reveal,
+            ScrubConfig::Truncate => Scrub::Truncate,
+            ScrubConfig::Redact => Scrub::Redact,
+        }
+    }
+}
+
+impl PiiConfig {
+    fn default_confidential() -> ScrubConfig {
+        ScrubConfig::Truncate
+    }
+
+    fn default_personal() -> ScrubConfig {
+        ScrubConfig::Redact
+    }
+
+    pub fn policy(&self) -> PiiPolicy {
+        PiiPolicy {
+            confidential: self.confidential.into(),
+            personal: self.personal.into(),
+        }
+    }
+}
+
+/// Candidate configuration the fingerprints are computed with in the background, see `ShadowFingerprinting`
+#[derive(Deserialize, Debug)]
+pub struct ShadowConfig {
+    /// Compacted secret of the candidate (e.g. of the next key epoch), computed in the naive mode
+    pub secret: String,
+    /// Namespace of the candidate, the current namespace when absent
+    pub namespace: Option<String>,
+    /// Fingerprint schema of the candidate, one of the configured `schemas`, the current schema when absent
+    #[serde(rename = "schema-id")]
+    pub schema_id: Option<u32>,
+    /// Wire version of the candidate, the current wire version when absent
+    #[serde(rename = "wire-version")]
+    pub wire_version: Option<u8>,
+    /// Epoch of the candidate, the current epoch when absent
+    pub epoch: Option<String>,
+}
+
+/// Reference the sampled fingerprints are recomputed with, see `FingerprintSampling`
+#[derive(Deserialize, Debug)]
+pub struct SamplingConfig {
+    /// Compacted secret reconstructed from the shares, computed in the naive mode
+    pub secret: String,
+    /// Fraction of the fingerprints recomputed, e.g. 0.01
+    pub rate: f64,
+}
+
+/// Limits of the fingerprint computations, see `LoadShedding`
+#[derive(Deserialize, Debug)]
+pub struct LoadSheddingConfig {
+    /// Computations run at once
+    pub concurrency: NonZeroUsize,
+    /// Computations waiting for the slot before the batch work is shed
+    #[serde(rename = "max-queue")]
+    pub max_queue: usize,
+    #[serde(
+        rename = "retry-after-secs",
+        default = "LoadSheddingConfig::default_retry_after_secs"
+    )]
+    pub retry_after_secs: u64,
+}
+
+/// Limits of the partial evaluations served by the agent, see `EvaluationLimits`
+#[derive(Deserialize, Debug)]
+pub struct EvaluationLimitsConfig {
+    /// Evaluations run at once
+    pub concurrency: NonZeroUsize,
+    /// Evaluations waiting for the slot before the rest are refused
+    #[serde(rename = "max-queue")]
+    pub max_queue: usize,
+}
+
+/// Quarantine of the failing agents, see `CircuitBreakers`
+#[derive(Deserialize, Debug)]
+pub struct CircuitBreakersConfig {
+    /// Evaluations failed in a row the agent is quarantined after
+    #[serde(default = "CircuitBreakersConfig::default_failures")]
+    pub failures: NonZeroU32,
+    /// Time the quarantined agent is not asked for
+    #[serde(
+        rename = "cooldown-secs",
+        default = "CircuitBreakersConfig::default_cooldown_secs"
+    )]
+    pub cooldown_secs: u64,
+}
+
+impl CircuitBreakersConfig {
+    fn default_failures() -> NonZeroU32 {
+        NonZeroU32::new(5).unwrap()
+    }
+
+    fn default_cooldown_secs() -> u64 {
+        60
+    }
+}
+
+/// Responses kept for the retried calls, see `IdempotencyCache`
+#[derive(Deserialize, Debug)]
+pub struct IdempotencyConfig {
+    #[serde(default = "IdempotencyConfig::default_capacity")]
+    pub capacity: NonZeroUsize,
+    #[serde(rename = "ttl-secs", default = "IdempotencyConfig::default_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl IdempotencyConfig {
+    fn default_capacity() -> NonZeroUsize {
+        NonZeroUsize::new(100_000).unwrap()
+    }
+
+    fn default_ttl_secs() -> u64 {
+        86_400
+    }
+}
+
+/// API keys of the callers sharing the coordinator, see `UsageAccounting`
+#[derive(Deserialize, Debug)]
+pub struct UsageConfig {
+    #[serde(rename = "api-keys")]
+    pub api_keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiKeyConfig {
+    /// Name the usage is recorded and reported by
+    pub name: String,
+    /// Key presented in the `x-api-key` metadata
+    pub key: String,
+    /// Fingerprints the key may compute within the calendar month, unlimited when absent
+    #[serde(rename = "monthly-quota")]
+    pub monthly_quota: Option<u64>,
+    /// Gets the usage of all the keys
+    #[serde(default)]
+    pub admin: bool,
+}
+
+/// Shortest API key accepted, shorter ones are guessable
+const MIN_API_KEY_LEN: usize = 16;
+
+impl UsageConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        if self.api_keys.is_empty() {
+            violations.push("usage.api-keys: should list at least one key");
+        }
+
+        let mut names = HashSet::new();
+        let mut keys = HashSet::new();
+        for api_key in &self.api_keys {
+            let key = format!("usage.api-keys[name = {}]", api_key.name);
+            if api_key.name.is_empty() {
+                violations.push("usage.api-keys: name should not be empty");
+            } else if !names.insert(&api_key.name) {
+                violations.push(format!("{}: name is listed more than once", key));
+            }
+            if api_key.key.len() < MIN_API_KEY_LEN {
+                violations.push(format!(
+                    "{}: key should be at least {} characters long",
+                    key, MIN_API_KEY_LEN
+                ));
+            } else if !keys.insert(&api_key.key) {
+                violations.push(format!("{}: key is shared with another name", key));
+            }
+        }
+    }
+
+    /// Accounting of the configured keys in the `ledger`
+    pub fn accounting(&self, ledger: Arc<dyn UsageLedger>) -> UsageAccounting {
+        self.api_keys
+            .iter()
+            .fold(UsageAccounting::new(ledger), |accounting, api_key| {
+                accounting.with_key(
+                    api_key.key.clone(),
+                    ApiKey {
+                        name: api_key.name.clone(),
+                        monthly_quota: api_key.monthly_quota,
+                        admin: api_key.admin,
+                    },
+                )
+            })
+    }
+}
+
+/// Work queue of the protocol rounds shared by all the requests
+#[derive(Deserialize, Debug)]
+pub struct SchedulerConfig {
+    /// Workers dispatching the rounds to the agents
+    pub workers: NonZeroUsize,
+    /// Rounds of the different requests a worker dispatches at once
+    #[serde(rename = "round-size")]
+    pub round_size: NonZeroUsize,
+}
+
+impl LoadSheddingConfig {
+    fn default_retry_after_secs() -> u64 {
+        1
+    }
+}
+
+impl ShadowConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        check_secret("shadow.secret", &self.secret, violations);
+        if let Some(namespace) = &self.namespace {
+            violations.check("shadow.namespace", Namespace::new(namespace));
+        }
+        violations.check("shadow.wire-version", wire_version(self.wire_version));
+        violations.check("shadow.epoch", epoch(self.epoch.as_deref()));
+    }
+}
+
+impl SamplingConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        check_secret("sampling.secret", &self.secret, violations);
+        if !(self.rate > 0.0 && self.rate <= 1.0) {
+            violations.push(format!(
+                "sampling.rate: {} should be above 0 and up to 1",
+                self.rate
+            ));
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ArchiveConfig {
+    pub bucket: String,
+    /// Endpoint of the S3 compatible storage, AWS S3 is used when absent
+    pub endpoint: Option<String>,
+    #[serde(default = "ArchiveConfig::default_prefix")]
+    pub prefix: String,
+    /// How long archived fingerprints are kept in the hot store
+    #[serde(default = "ArchiveConfig::default_hot_retention_days")]
+    pub hot_retention_days: u32,
+    #[serde(default = "ArchiveConfig::default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl ArchiveConfig {
+    fn default_prefix() -> String {
+        "fingerprints".to_string()
+    }
+
+    fn default_hot_retention_days() -> u32 {
+        90
+    }
+
+    fn default_interval_secs() -> u64 {
+        3600
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -30,9 +1161,868 @@ pub struct NaiveTopologyConfig {
     pub secret: String,
 }
 
+impl NaiveTopologyConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        check_secret("fingerprint-service.secret", &self.secret, violations);
+    }
+}
+
+/// Threshold protocol evaluated by the agents within the single process, the `secret` is shared on start
+#[derive(Deserialize, Debug)]
+pub struct InProcessTopologyConfig {
+    pub secret: String,
+    pub agents: usize,
+    pub threshold: usize,
+}
+
+impl InProcessTopologyConfig {
+    pub fn validate(&self, violations: &mut ConfigViolations) {
+        check_secret("fingerprint-service.secret", &self.secret, violations);
+        if self.threshold == 0 || self.threshold > self.agents {
+            violations.push(format!(
+                "fingerprint-service.threshold: {} should be from 1 up to the {} agents",
+                self.threshold, self.agents
+            ));
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum FingerprintServiceConfig {
-    Cooperative(CooperativeTopologyConfig),
+    Cooperative(Box<CooperativeTopologyConfig>),
     Naive(NaiveTopologyConfig),
+    InProcess(InProcessTopologyConfig),
+}
+
+/// Configuration of the full agent
+#[derive(Deserialize, Debug)]
+pub struct FingerprintingServiceConfig {
+    pub grpc: GrpcConfig,
+    #[serde(rename = "agent-grpc")]
+    pub agent_grpc: GrpcConfig,
+    /// Admin calls of the agent (maintenance before the restart), not served when absent.
+    /// Listen on the address the coordinators can not reach, e.g. the loopback one
+    #[serde(rename = "agent-admin")]
+    pub agent_admin: Option<GrpcConfig>,
+    #[serde(rename = "fingerprint-service")]
+    pub fingerprint_service: FingerprintServiceConfig,
+    /// JSON over HTTP endpoint of the fingerprint service, see `rest`
+    pub rest: Option<GrpcConfig>,
+    pub pseudonymization: Option<PseudonymizationConfig>,
+    /// Compacted Ed25519 key the commitments to the components are bound to the fingerprints with,
+    /// see `fingerprinting-cli generate-signing-key`. Commitments are refused when absent
+    #[serde(rename = "commitment-key")]
+    pub commitment_key: Option<String>,
+    pub store: Option<StoreConfig>,
+    pub archive: Option<ArchiveConfig>,
+    #[serde(rename = "duplicate-window-days")]
+    pub duplicate_window_days: Option<u32>,
+    /// Namespace of the environment (prod, staging, partner-x) the fingerprints are separated into
+    pub namespace: Option<String>,
+    /// Fingerprint schemas known to the deployment
+    #[serde(default)]
+    pub schemas: Vec<SchemaConfig>,
+    /// Schema the optional components are bound by, one of the `schemas`, the built-in layout by default
+    #[serde(rename = "schema-id")]
+    pub schema_id: Option<u32>,
+    /// Wire version the preimages are laid out by, `WireVersion::CURRENT` by default
+    #[serde(rename = "wire-version")]
+    pub wire_version: Option<u8>,
+    /// Date the date components are offset from, e.g. "2023-01-01", moving it changes all the fingerprints.
+    /// `Epoch::default()` when absent
+    pub epoch: Option<String>,
+    /// Returns the fuzzy fingerprint of the date time truncated to the bucket, minute or hour, alongside every
+    /// computed one, for the near-duplicate detection. Fuzzy fingerprints are not returned when absent
+    #[serde(rename = "fuzzy-bucket")]
+    pub fuzzy_bucket: Option<String>,
+    /// Maximum sizes of the string fields of the transactions, the larger ones are refused
+    #[serde(rename = "input-limits", default)]
+    pub input_limits: InputLimitsConfig,
+    /// Scrubbing of the transaction values in the logs and the errors, confidential values are truncated and
+    /// personal ones redacted by default
+    pub pii: Option<PiiConfig>,
+    /// Debug recording of the exchanges, replayed by `fingerprinting-cli replay`
+    pub recording: Option<RecordingConfig>,
+    /// Counters of the service sampled for `fingerprinting-cli report`
+    #[serde(rename = "capacity-log")]
+    pub capacity_log: Option<CapacityLogConfig>,
+    /// Logs the lifecycle events (fingerprints computed, duplicates detected, agents excluded) with the `events`
+    /// target, e.g. for the audit log
+    #[serde(rename = "event-log", default)]
+    pub event_log: bool,
+    /// Candidate configuration of the migration, compared with the current one on the live traffic
+    pub shadow: Option<ShadowConfig>,
+    /// Reference recomputation of the sampled fingerprints, test environments only
+    pub sampling: Option<SamplingConfig>,
+    /// Fast failure of the computations when the service is saturated
+    #[serde(rename = "load-shedding")]
+    pub load_shedding: Option<LoadSheddingConfig>,
+    /// Workers of the protocol rounds, 16 workers dispatching up to 4 rounds each by default
+    pub scheduler: Option<SchedulerConfig>,
+    /// Agents (regions for the regional topologies) the transactions of every residency tag are computed via,
+    /// the tagged transactions are refused without the route
+    #[serde(default)]
+    pub residency: Vec<ResidencyRouteConfig>,
+    /// Accounting of the calls by the API keys of the callers with the optional monthly quotas
+    pub usage: Option<UsageConfig>,
+    /// Answers the calls retried with the `x-idempotency-key` metadata by the completed responses
+    pub idempotency: Option<IdempotencyConfig>,
+    /// Saves the idempotency cache, the circuit breakers and the epoch accumulator periodically and restores them
+    /// on start
+    pub snapshot: Option<SnapshotConfig>,
+    /// Start of the simulated time the agent runs at, e.g. "2030-01-01T00:00:00Z", test environments only
+    #[serde(rename = "simulated-time")]
+    pub simulated_time: Option<DateTime<Utc>>,
+    /// Generation of the secret (shards), increased on every rotation, 0 when absent
+    #[serde(rename = "key-epoch")]
+    pub key_epoch: Option<u64>,
+    /// Refuses to start in the weaker protocol configuration than the recorded one, see `downgrade`
+    #[serde(rename = "state-file")]
+    pub state_file: Option<StateFileConfig>,
+}
+
+impl FingerprintingServiceConfig {
+    /// Checks the resolved configuration up front, all the violations are reported together
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let mut violations = ConfigViolations::default();
+
+        self.grpc.validate("grpc", &mut violations);
+        match &self.fingerprint_service {
+            FingerprintServiceConfig::Cooperative(topology) => {
+                if topology.libp2p.is_none() {
+                    self.agent_grpc.validate("agent-grpc", &mut violations);
+                    if self.agent_grpc.port == self.grpc.port {
+                        violations.push(format!(
+                            "agent-grpc.port: {} should be distinct from grpc.port",
+                            self.agent_grpc.port
+                        ));
+                    }
+                    if let Some(admin) = &self.agent_admin {
+                        validate_admin(
+                            "agent-admin",
+                            admin,
+                            &[&self.grpc, &self.agent_grpc],
+                            &mut violations,
+                        );
+                    }
+                }
+                topology.validate(&mut violations);
+            }
+            FingerprintServiceConfig::Naive(naive) => naive.validate(&mut violations),
+            FingerprintServiceConfig::InProcess(in_process) => in_process.validate(&mut violations),
+        }
+        if let Some(rest) = &self.rest {
+            rest.validate("rest", &mut violations);
+            if rest.port == self.grpc.port {
+                violations.push(format!(
+                    "rest.port: {} should be distinct from grpc.port",
+                    rest.port
+                ));
+            }
+        }
+
+        if let Some(namespace) = &self.namespace {
+            violations.check("namespace", Namespace::new(namespace));
+        }
+        if let Some(registry) = violations.check("schemas", schema_registry(&self.schemas)) {
+            let schema_ids = [
+                ("schema-id", self.schema_id),
+                (
+                    "shadow.schema-id",
+                    self.shadow.as_ref().and_then(|shadow| shadow.schema_id),
+                ),
+            ];
+            for (key, schema_id) in schema_ids {
+                if let Some(schema_id) = schema_id {
+                    violations.check(key, registry.get(schema_id));
+                }
+            }
+        }
+        violations.check("wire-version", wire_version(self.wire_version));
+        violations.check("epoch", epoch(self.epoch.as_deref()));
+        violations.check("fuzzy-bucket", fuzzy_bucket(self.fuzzy_bucket.as_deref()));
+        violations.check("input-limits", self.input_limits.limits().validate());
+        self.validate_residency(&mut violations);
+        if let Some(pseudonymization) = &self.pseudonymization {
+            pseudonymization.validate(&mut violations);
+        }
+        if let Some(commitment_key) = &self.commitment_key {
+            violations.check(
+                "commitment-key",
+                fingerprinting_offline_agent::signing_key(commitment_key),
+            );
+        }
+        if let Some(shadow) = &self.shadow {
+            shadow.validate(&mut violations);
+        }
+        if let Some(sampling) = &self.sampling {
+            sampling.validate(&mut violations);
+        }
+        if let Some(usage) = &self.usage {
+            usage.validate(&mut violations);
+        }
+        if let Some(capacity_log) = &self.capacity_log {
+            if capacity_log.interval_secs == 0 {
+                violations.push("capacity-log.interval-secs: 0 should be above 0");
+            }
+        }
+        if let Some(snapshot) = &self.snapshot {
+            if snapshot.interval_secs == 0 {
+                violations.push("snapshot.interval-secs: 0 should be above 0");
+            }
+        }
+        if let Some(state_file) = &self.state_file {
+            violations.check(
+                "state-file.signing-key",
+                fingerprinting_offline_agent::signing_key(&state_file.signing_key),
+            );
+        }
+
+        violations.into_result()
+    }
+
+    /// Protocol state of the configuration, recorded by the `state-file`
+    pub fn protocol_state(&self) -> ProtocolState {
+        let (mode, threshold) = match &self.fingerprint_service {
+            FingerprintServiceConfig::Cooperative(topology) => {
+                (ProtocolMode::Cooperative, topology.threshold)
+            }
+            FingerprintServiceConfig::InProcess(in_process) => {
+                (ProtocolMode::Embedded, in_process.threshold)
+            }
+            FingerprintServiceConfig::Naive(_) => (ProtocolMode::Naive, 1),
+        };
+
+        ProtocolState {
+            mode,
+            threshold,
+            key_epoch: self.key_epoch.unwrap_or_default(),
+        }
+    }
+
+    fn validate_residency(&self, violations: &mut ConfigViolations) {
+        // Agents or regions the coordinator computes via, the naive mode computes locally
+        let (own, count, threshold) = match &self.fingerprint_service {
+            FingerprintServiceConfig::Cooperative(topology) => match &topology.region {
+                Some(region) => (region.region_id, region.regions, region.threshold),
+                None => (topology.agent_id, topology.agents, topology.threshold),
+            },
+            FingerprintServiceConfig::InProcess(in_process) => {
+                (1, in_process.agents, in_process.threshold)
+            }
+            FingerprintServiceConfig::Naive(_) => return,
+        };
+
+        let mut tags = HashSet::new();
+        for route in &self.residency {
+            let key = format!("residency[tag = {}]", route.tag);
+            if route.tag.is_empty() {
+                violations.push("residency: tag should not be empty");
+            } else if !tags.insert(&route.tag) {
+                violations.push(format!("{}: tag is listed more than once", key));
+            }
+
+            let agents = route.agents.iter().collect::<HashSet<_>>();
+            if !agents.contains(&own) {
+                violations.push(format!(
+                    "{}: coordinating agent {} should be one of the agents",
+                    key, own
+                ));
+            }
+            if let Some(agent) = agents.iter().find(|agent| !(1..=count).contains(**agent)) {
+                violations.push(format!(
+                    "{}: agent {} should be from 1 up to the {} agents",
+                    key, agent, count
+                ));
+            }
+            if agents.len() < threshold {
+                violations.push(format!(
+                    "{}: threshold {} is not reachable with {} agents",
+                    key,
+                    threshold,
+                    agents.len()
+                ));
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResidencyRouteConfig {
+    pub tag: String,
+    pub agents: Vec<usize>,
+}
+
+/// Configuration of the light agent, serving the cooperation requests only
+#[derive(Deserialize, Debug)]
+pub struct LightAgentConfig {
+    pub grpc: GrpcConfig,
+    pub agent: AgentConfig,
+    #[serde(rename = "evaluation-limits")]
+    pub evaluation_limits: Option<EvaluationLimitsConfig>,
+    /// Admin calls of the agent (maintenance before the restart), not served when absent
+    pub admin: Option<GrpcConfig>,
+    #[serde(default)]
+    pub hardening: HardeningConfig,
+}
+
+impl LightAgentConfig {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let mut violations = ConfigViolations::default();
+        self.grpc.validate("grpc", &mut violations);
+        if let Some(admin) = &self.admin {
+            validate_admin("admin", admin, &[&self.grpc], &mut violations);
+        }
+        self.agent.validate(&mut violations);
+
+        violations.into_result()
+    }
+}
+
+/// Admin address of the agent, distinct from the addresses served to the callers
+fn validate_admin(
+    key: &str,
+    admin: &GrpcConfig,
+    served: &[&GrpcConfig],
+    violations: &mut ConfigViolations,
+) {
+    admin.validate(key, violations);
+    if served.iter().any(|grpc| grpc.port == admin.port) {
+        violations.push(format!(
+            "{}.port: {} should be distinct from the ports served to the callers",
+            key, admin.port
+        ));
+    }
+}
+
+/// Configuration of the agent in the air-gapped environment
+#[derive(Deserialize, Debug)]
+pub struct OfflineAgentConfig {
+    pub agent_id: usize,
+    pub secret_shard: String,
+    /// Compacted Ed25519 key the responses are signed with
+    #[serde(rename = "signing-key")]
+    pub signing_key: String,
+    /// Compacted Ed25519 keys of the coordinators allowed to send requests
+    pub coordinators: Vec<String>,
+    /// Directory of the request files carried in
+    pub requests: String,
+    /// Directory of the response files to carry out
+    pub responses: String,
+}
+
+/// Commented template of the full agent computing the fingerprints cooperatively
+pub const COOPERATIVE_TEMPLATE: &str = include_str!("../config/templates/cooperative.conf");
+/// Commented template of the full agent holding the whole secret
+pub const NAIVE_TEMPLATE: &str = include_str!("../config/templates/naive.conf");
+/// Commented template of the single node agent with the SQLite store and the REST endpoint
+pub const EMBEDDED_TEMPLATE: &str = include_str!("../config/templates/embedded.conf");
+/// Commented template of the light agent
+pub const LIGHT_AGENT_TEMPLATE: &str = include_str!("../config/templates/light.conf");
+
+/// Accepted structure of the full agent configuration
+pub fn agent_schema() -> Result<Vec<schema::Key>, anyhow::Error> {
+    let schema = ConfigSchema::default()
+        .with_tagged(
+            "fingerprint-service",
+            "type",
+            vec![
+                (
+                    "Cooperative",
+                    ConfigSchema::default().trace::<CooperativeTopologyConfig>()?,
+                ),
+                (
+                    "Naive",
+                    ConfigSchema::default().trace::<NaiveTopologyConfig>()?,
+                ),
+                (
+                    "InProcess",
+                    ConfigSchema::default().trace::<InProcessTopologyConfig>()?,
+                ),
+            ],
+        )
+        .with_tagged(
+            "store",
+            "type",
+            vec![
+                ("Memory", vec![]),
+                (
+                    "Postgres",
+                    ConfigSchema::default().trace::<PostgresStoreConfig>()?,
+                ),
+                (
+                    "Sqlite",
+                    ConfigSchema::default().trace::<SqliteStoreConfig>()?,
+                ),
+            ],
+        );
+
+    Ok(schema.trace::<FingerprintingServiceConfig>()?)
+}
+
+/// Accepted structure of the light agent configuration
+pub fn light_agent_schema() -> Result<Vec<schema::Key>, anyhow::Error> {
+    Ok(ConfigSchema::default().trace::<LightAgentConfig>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hocon::HoconLoader;
+
+    #[test]
+    fn test_config_violations() -> Result<(), anyhow::Error> {
+        let topology: CooperativeTopologyConfig = HoconLoader::new()
+            .load_str(
+                r#"{
+                    agent_id: 1
+                    secret_shard: "not a shard"
+                    agents: 3
+                    threshold: 4
+                    members: [
+                      {agent_id: 1, address: "localhost:9001"}
+                      {agent_id: 2, address: "localhost"}
+                      {agent_id: 2, address: "localhost:9002"}
+                    ]
+                    signatures: {signing-key: "not a key"}
+                    diversity: {min-institutions: 2}
+                }"#,
+            )?
+            .resolve()?;
+
+        let mut violations = ConfigViolations::default();
+        topology.validate(&mut violations);
+        let report = violations.into_result().unwrap_err().to_string();
+
+        for violation in [
+            "fingerprint-service.secret_shard",
+            "threshold: 4 should be from 1 up to the 3 agents",
+            "members[agent_id = 1]: agent itself should not be the member",
+            "members[agent_id = 2]: localhost should be host:port",
+            "members[agent_id = 2]: agent is listed more than once",
+            "threshold 4 is not reachable with 1 other agents",
+            "fingerprint-service.signatures.signing-key",
+            "fingerprint-service.institution: required by the diversity policy",
+            "members[agent_id = 2].institution: required by the diversity policy",
+            "min-institutions: 2 institutions are required, the agents are run by 0",
+        ] {
+            assert!(
+                report.contains(violation),
+                "{} is not reported in {}",
+                violation,
+                report
+            );
+        }
+        assert!(report.starts_with("Invalid configuration, 12 violation(s)"));
+
+        let grpc = GrpcConfig {
+            host: "[::]".to_string(),
+            port: 9000,
+        };
+        let mut violations = ConfigViolations::default();
+        grpc.validate("grpc", &mut violations);
+        assert!(violations.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_topology_members() -> Result<(), anyhow::Error> {
+        let topology: CooperativeTopologyConfig = HoconLoader::new()
+            .load_str(
+                r#"{
+                    agent_id: 1
+                    secret_shard: "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD"
+                    institution: bank-a
+                    operator: BankA
+                    deployment-region: eu-central
+                    agents: 3
+                    threshold: 2
+                    members: [
+                      {agent_id: 2, address: "localhost:9002", institution: bank-a}
+                      {agent_id: 3, address: "localhost:9003", institution: bank-b, operator: BankCorp, region: eu-west}
+                    ]
+                    diversity: {min-institutions: 2}
+                }"#,
+            )?
+            .resolve()?;
+        let mut violations = ConfigViolations::default();
+        topology.validate(&mut violations);
+        assert!(violations.is_empty());
+
+        let members = topology.members();
+        assert_eq!(members.describe(1), "agent 1 (BankA, eu-central)");
+        assert_eq!(members.describe(3), "agent 3 (BankCorp, eu-west)");
+        assert_eq!(topology.diversity_policy().institutions(&[1, 2]), 1);
+        assert_eq!(topology.diversity_policy().institutions(&[2, 3]), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entropy_config() -> Result<(), anyhow::Error> {
+        let entropy: EntropyConfig = HoconLoader::new()
+            .load_str(r#"{source: device, reseed-interval: 0}"#)?
+            .resolve()?;
+        let mut violations = ConfigViolations::default();
+        entropy.validate(&mut violations);
+        let report = violations.into_result().unwrap_err().to_string();
+        assert!(report.contains("entropy.device: required by the device source"));
+        assert!(report.contains("reseed-interval: only used by the DRBG"));
+        assert!(report.contains("reseed-interval: should be above 0"));
+
+        let entropy: EntropyConfig = HoconLoader::new()
+            .load_str(
+                r#"{source: device, device: "/dev/urandom", drbg: true, reseed-interval: 2}"#,
+            )?
+            .resolve()?;
+        let mut violations = ConfigViolations::default();
+        entropy.validate(&mut violations);
+        assert!(violations.is_empty());
+        let source = entropy.source()?;
+        let (mut first, mut second) = ([0u8; 32], [0u8; 32]);
+        source.fill_bytes(&mut first)?;
+        source.fill_bytes(&mut second)?;
+        assert_ne!(first, second);
+
+        assert!(EntropyConfig::default().source().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_templates() -> Result<(), anyhow::Error> {
+        let shard = "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD";
+        let fill = |template: &str| {
+            [
+                "<secret shard of agent 1>",
+                "<secret shard of agent 2>",
+                "<secret>",
+            ]
+            .iter()
+            .fold(template.to_string(), |template, placeholder| {
+                template.replace(placeholder, shard)
+            })
+        };
+
+        for template in [COOPERATIVE_TEMPLATE, NAIVE_TEMPLATE, EMBEDDED_TEMPLATE] {
+            let config: FingerprintingServiceConfig =
+                HoconLoader::new().load_str(&fill(template))?.resolve()?;
+            config.validate()?;
+        }
+
+        let state_file = |state_file: &str| -> Result<FingerprintingServiceConfig, anyhow::Error> {
+            Ok(HoconLoader::new()
+                .load_str(&fill(COOPERATIVE_TEMPLATE))?
+                .load_str(state_file)?
+                .resolve()?)
+        };
+        // Any 32 bytes are the Ed25519 key
+        let config = state_file(&format!(
+            r#"{{key-epoch: 4, state-file: {{path: "/var/lib/fingerprinting/state.json", signing-key: "{}"}}}}"#,
+            shard
+        ))?;
+        config.validate()?;
+        assert_eq!(
+            config.protocol_state(),
+            ProtocolState {
+                mode: ProtocolMode::Cooperative,
+                threshold: 2,
+                key_epoch: 4,
+            }
+        );
+        let refused =
+            state_file(r#"{state-file: {path: "state.json", signing-key: "not a key"}}"#)?
+                .validate()
+                .err()
+                .unwrap()
+                .to_string();
+        assert!(refused.contains("state-file.signing-key"));
+
+        let config: LightAgentConfig = HoconLoader::new()
+            .load_str(&fill(LIGHT_AGENT_TEMPLATE))?
+            .resolve()?;
+        config.validate()?;
+
+        // Relay holds no shard, the placeholder is left as it is
+        let relay = |relay: &str| -> Result<LightAgentConfig, anyhow::Error> {
+            Ok(HoconLoader::new()
+                .load_str(LIGHT_AGENT_TEMPLATE)?
+                .load_str(relay)?
+                .resolve()?)
+        };
+        relay(r#"{agent: {relay: {command: ["hsm-bridge"]}}}"#)?.validate()?;
+        let refused = relay(r#"{agent: {relay: {command: []}, secret-shard-file: "/etc/shard"}}"#)?
+            .validate()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(refused.contains("agent.relay.command"));
+        assert!(refused.contains("agent.secret-shard-file"));
+
+        let admin = |admin: &str| -> Result<LightAgentConfig, anyhow::Error> {
+            Ok(HoconLoader::new()
+                .load_str(&fill(LIGHT_AGENT_TEMPLATE))?
+                .load_str(admin)?
+                .resolve()?)
+        };
+        admin(r#"{admin: {host: "127.0.0.1", port: 9101}}"#)?.validate()?;
+        let refused = admin(r#"{admin: {host: "127.0.0.1", port: 9001}}"#)?
+            .validate()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(refused.contains("admin.port: 9001 should be distinct"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_config() -> Result<(), anyhow::Error> {
+        let naive =
+            NAIVE_TEMPLATE.replace("<secret>", "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD");
+        let config = |schemas: &str| -> Result<FingerprintingServiceConfig, anyhow::Error> {
+            Ok(HoconLoader::new()
+                .load_str(&naive)?
+                .load_str(schemas)?
+                .resolve()?)
+        };
+
+        let valid = config(
+            r#"{
+                schemas: [{id: 1, components: [merchant, country]}, {id: 2, components: [country]}]
+                schema-id: 1
+            }"#,
+        )?;
+        valid.validate()?;
+        let schema = fingerprint_schema(&valid.schemas, valid.schema_id)?.unwrap();
+        assert_eq!(
+            schema.components(),
+            [SchemaComponent::Merchant, SchemaComponent::Country]
+        );
+
+        let report = config(
+            r#"{
+                schemas: [{id: 1, components: [merchant, colour]}]
+                schema-id: 3
+            }"#,
+        )?
+        .validate()
+        .unwrap_err()
+        .to_string();
+        assert!(
+            report.contains("schemas: Unknown component colour"),
+            "{}",
+            report
+        );
+
+        let agnostic = config(
+            r#"{
+                schemas: [{id: 1, components: [counterparty], direction-agnostic: true}]
+                schema-id: 1
+            }"#,
+        )?;
+        agnostic.validate()?;
+        let schema = fingerprint_schema(&agnostic.schemas, agnostic.schema_id)?.unwrap();
+        assert!(schema.is_direction_agnostic());
+
+        let report = config(
+            r#"{schemas: [{id: 1, components: [mcc], direction-agnostic: true}], schema-id: 1}"#,
+        )?
+        .validate()
+        .unwrap_err()
+        .to_string();
+        assert!(
+            report.contains("should list the counterparty component"),
+            "{}",
+            report
+        );
+
+        let report = config(r#"{schemas: [{id: 1, components: [mcc]}], schema-id: 3}"#)?
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            report.contains("schema-id: Schema 3 is not registered"),
+            "{}",
+            report
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_input_limits_config() -> Result<(), anyhow::Error> {
+        let naive =
+            NAIVE_TEMPLATE.replace("<secret>", "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD");
+        let config = |limits: &str| -> Result<FingerprintingServiceConfig, anyhow::Error> {
+            Ok(HoconLoader::new()
+                .load_str(&naive)?
+                .load_str(limits)?
+                .resolve()?)
+        };
+
+        let defaults = config("{}")?;
+        defaults.validate()?;
+        assert_eq!(defaults.input_limits.limits(), InputLimits::MAX);
+
+        let limited = config(r#"{input-limits: {merchant-id: 16, transaction-reference: 20}}"#)?;
+        limited.validate()?;
+        let limits = limited.input_limits.limits();
+        assert_eq!((limits.merchant_id, limits.transaction_reference), (16, 20));
+        assert_eq!(limits.bic, InputLimits::MAX.bic);
+
+        // Limits above the sizes the components encode are refused
+        let report = config(r#"{input-limits: {merchant-id: 1024}}"#)?
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            report.contains("input-limits: Field merchant_id should be at most 64 bytes long"),
+            "{}",
+            report
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wire_version_config() -> Result<(), anyhow::Error> {
+        let naive =
+            NAIVE_TEMPLATE.replace("<secret>", "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD");
+        let config = |version: &str| -> Result<FingerprintingServiceConfig, anyhow::Error> {
+            Ok(HoconLoader::new()
+                .load_str(&naive)?
+                .load_str(version)?
+                .resolve()?)
+        };
+
+        let defaults = config("{}")?;
+        defaults.validate()?;
+        assert_eq!(wire_version(defaults.wire_version)?, WireVersion::CURRENT);
+
+        let migrated = config(r#"{wire-version: 2}"#)?;
+        migrated.validate()?;
+        assert_eq!(wire_version(migrated.wire_version)?, WireVersion::V2);
+
+        let report = config(r#"{wire-version: 7}"#)?
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(report.contains("wire-version:"), "{}", report);
+
+        assert_eq!(epoch(defaults.epoch.as_deref())?, Epoch::default());
+        let moved = config(r#"{epoch: "2023-01-01"}"#)?;
+        moved.validate()?;
+        assert_eq!(epoch(moved.epoch.as_deref())?.to_string(), "2023-01-01");
+
+        let report = config(r#"{epoch: "2023-13-01", shadow: {secret: "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD", epoch: "01/01/2023"}}"#)?
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(report.contains("- epoch:"), "{}", report);
+        assert!(report.contains("- shadow.epoch:"), "{}", report);
+
+        assert_eq!(fuzzy_bucket(defaults.fuzzy_bucket.as_deref())?, None);
+        let fuzzy = config(r#"{fuzzy-bucket: minute}"#)?;
+        fuzzy.validate()?;
+        assert_eq!(
+            fuzzy_bucket(fuzzy.fuzzy_bucket.as_deref())?,
+            Some(TimeBucket::Minute)
+        );
+        let report = config(r#"{fuzzy-bucket: second}"#)?
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(report.contains("- fuzzy-bucket:"), "{}", report);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_usage_config() -> Result<(), anyhow::Error> {
+        let naive =
+            NAIVE_TEMPLATE.replace("<secret>", "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD");
+        let config = |usage: &str| -> Result<FingerprintingServiceConfig, anyhow::Error> {
+            Ok(HoconLoader::new()
+                .load_str(&naive)?
+                .load_str(usage)?
+                .resolve()?)
+        };
+
+        let accounted = config(
+            r#"{usage: {api-keys: [
+                {name: bank-a, key: "key-of-the-bank-a", monthly-quota: 1000}
+                {name: ops, key: "key-of-the-operator", admin: true}
+            ]}}"#,
+        )?;
+        accounted.validate()?;
+        let usage = accounted.usage.unwrap();
+        assert_eq!(usage.api_keys[0].monthly_quota, Some(1000));
+        assert!(!usage.api_keys[0].admin && usage.api_keys[1].admin);
+
+        let report = config(
+            r#"{usage: {api-keys: [
+                {name: bank-a, key: "short"}
+                {name: bank-a, key: "key-of-the-bank-a"}
+                {name: bank-b, key: "key-of-the-bank-a"}
+            ]}}"#,
+        )?
+        .validate()
+        .unwrap_err()
+        .to_string();
+        assert!(report.contains("key should be at least 16"), "{}", report);
+        assert!(
+            report.contains("name is listed more than once"),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains("key is shared with another name"),
+            "{}",
+            report
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_schema() -> Result<(), anyhow::Error> {
+        let keys = agent_schema()?;
+        let key = |keys: &[schema::Key], name: &str| {
+            keys.iter().find(|key| key.name == name).cloned().unwrap()
+        };
+
+        let grpc = key(&keys, "grpc");
+        assert!(grpc.required);
+        let schema::Value::Struct(grpc) = grpc.value else {
+            panic!("grpc is not the struct");
+        };
+        assert_eq!(key(&grpc, "port").value, schema::Value::Scalar("integer"));
+        assert!(!key(&keys, "namespace").required);
+
+        let schema::Value::Tagged { tag, variants } = key(&keys, "fingerprint-service").value
+        else {
+            panic!("fingerprint-service is not the tagged enum");
+        };
+        assert_eq!(tag, "type");
+        let (_, cooperative) = variants
+            .iter()
+            .find(|(name, _)| *name == "Cooperative")
+            .unwrap();
+        assert!(key(cooperative, "threshold").required);
+        let incompatible = key(cooperative, "incompatible-agents");
+        assert!(!incompatible.required);
+        assert_eq!(
+            incompatible.value,
+            schema::Value::OneOf(vec!["refuse", "exclude"])
+        );
+
+        let keys = light_agent_schema()?;
+        assert!(key(&keys, "agent").required);
+        assert!(!key(&keys, "hardening").required);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,567 @@
+//! Accepted structure of the configuration, derived from the serde types rather than maintained by hand
+//!
+//! The configuration type is deserialized from the tracer, which records the keys serde asks for and feeds it
+//! the sample values. A key is required when the deserialization fails without it. serde does not expose
+//! the variants of the internally tagged enums, their paths and variants are registered with `with_tagged`.
+
+use serde::de::value::{Error, StrDeserializer};
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, Error as _, IntoDeserializer,
+    MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use std::fmt::Write;
+
+/// Sample of the strings, valid for the date times as well
+const SAMPLE_STRING: &str = "2025-01-01T00:00:00Z";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `string`, `integer`, `number` or `boolean`
+    Scalar(&'static str),
+    /// One of the names
+    OneOf(Vec<&'static str>),
+    List(Box<Value>),
+    Struct(Vec<Key>),
+    /// Internally tagged enum, the `tag` key selects the keys of the variant
+    Tagged {
+        tag: &'static str,
+        variants: Vec<(&'static str, Vec<Key>)>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    pub name: &'static str,
+    pub required: bool,
+    pub value: Value,
+}
+
+#[derive(Debug, Default)]
+pub struct ConfigSchema {
+    tagged: Vec<(String, Value)>,
+}
+
+impl ConfigSchema {
+    /// Internally tagged enum at the `path` (keys joined by dots), the keys of the variants are traced separately
+    pub fn with_tagged(
+        mut self,
+        path: &str,
+        tag: &'static str,
+        variants: Vec<(&'static str, Vec<Key>)>,
+    ) -> Self {
+        self.tagged
+            .push((path.to_string(), Value::Tagged { tag, variants }));
+        self
+    }
+
+    /// Keys of the configuration struct `T`
+    pub fn trace<T: DeserializeOwned>(&self) -> Result<Vec<Key>, Error> {
+        let mut shape = None;
+        T::deserialize(self.tracer(String::new(), None, &mut shape))?;
+
+        let Some(Value::Struct(mut keys)) = shape else {
+            return Err(Error::custom("Configuration should be a struct"));
+        };
+        self.mark_required::<T>(&mut keys, "");
+
+        Ok(keys)
+    }
+
+    fn mark_required<T: DeserializeOwned>(&self, keys: &mut [Key], prefix: &str) {
+        for key in keys {
+            let path = join(prefix, key.name);
+            key.required =
+                T::deserialize(self.tracer(String::new(), Some(&path), &mut None)).is_err();
+
+            match &mut key.value {
+                Value::Struct(keys) => self.mark_required::<T>(keys, &path),
+                Value::List(element) => {
+                    if let Value::Struct(keys) = element.as_mut() {
+                        self.mark_required::<T>(keys, &format!("{}[]", path));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn tracer<'a>(
+        &'a self,
+        path: String,
+        omit: Option<&'a str>,
+        shape: &'a mut Option<Value>,
+    ) -> Tracer<'a> {
+        Tracer {
+            schema: self,
+            path,
+            omit,
+            shape,
+        }
+    }
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    match prefix {
+        "" => name.to_string(),
+        prefix => format!("{}.{}", prefix, name),
+    }
+}
+
+/// Renders the keys in the HOCON like layout
+pub fn render(keys: &[Key]) -> String {
+    let mut rendered = String::new();
+    render_keys(&mut rendered, keys, 0);
+    rendered
+}
+
+fn render_keys(out: &mut String, keys: &[Key], indent: usize) {
+    for key in keys {
+        let optional = if key.required { "" } else { "  # optional" };
+        let pad = "  ".repeat(indent);
+        match &key.value {
+            Value::Struct(keys) => {
+                let _ = writeln!(out, "{}{}: {{{}", pad, key.name, optional);
+                render_keys(out, keys, indent + 1);
+                let _ = writeln!(out, "{}}}", pad);
+            }
+            Value::List(element) => match element.as_ref() {
+                Value::Struct(keys) => {
+                    let _ = writeln!(out, "{}{}: [{{{}", pad, key.name, optional);
+                    render_keys(out, keys, indent + 1);
+                    let _ = writeln!(out, "{}}}]", pad);
+                }
+                element => {
+                    let _ = writeln!(
+                        out,
+                        "{}{}: [{}]{}",
+                        pad,
+                        key.name,
+                        describe(element),
+                        optional
+                    );
+                }
+            },
+            Value::Tagged { tag, variants } => {
+                let _ = writeln!(out, "{}{}: {{{}", pad, key.name, optional);
+                let names = variants.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                let _ = writeln!(out, "{}  {}: {}", pad, tag, names.join(" | "));
+                for (name, keys) in variants.iter().filter(|(_, keys)| !keys.is_empty()) {
+                    let _ = writeln!(out, "{}  # {}: {}", pad, tag, name);
+                    render_keys(out, keys, indent + 1);
+                }
+                let _ = writeln!(out, "{}}}", pad);
+            }
+            value => {
+                let _ = writeln!(out, "{}{}: {}{}", pad, key.name, describe(value), optional);
+            }
+        }
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Scalar(scalar) => scalar.to_string(),
+        Value::OneOf(names) => names.join(" | "),
+        Value::List(element) => format!("[{}]", describe(element)),
+        Value::Struct(_) | Value::Tagged { .. } => "{...}".to_string(),
+    }
+}
+
+/// Deserializer recording the shape of the value asked for at the `path`
+struct Tracer<'a> {
+    schema: &'a ConfigSchema,
+    path: String,
+    /// Key left out of the struct, when the requirement of the key is checked
+    omit: Option<&'a str>,
+    shape: &'a mut Option<Value>,
+}
+
+impl<'de> Deserializer<'de> for Tracer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let tagged = self
+            .schema
+            .tagged
+            .iter()
+            .find(|(path, _)| *path == self.path)
+            .map(|(_, tagged)| tagged)
+            .ok_or_else(|| Error::custom(format!("{}: type is not traceable", self.path)))?;
+
+        *self.shape = Some(tagged.clone());
+        Sample(tagged).deserialize_any(visitor)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.shape = Some(Value::Scalar("boolean"));
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.shape = Some(Value::Scalar("integer"));
+        visitor.visit_i64(1)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.shape = Some(Value::Scalar("integer"));
+        visitor.visit_u64(1)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.shape = Some(Value::Scalar("number"));
+        visitor.visit_f64(0.5)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.shape = Some(Value::Scalar("string"));
+        visitor.visit_str(SAMPLE_STRING)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut element = None;
+        let value = visitor.visit_seq(TracedElement {
+            tracer: Some(
+                self.schema
+                    .tracer(format!("{}[]", self.path), self.omit, &mut element),
+            ),
+        })?;
+
+        *self.shape = Some(Value::List(Box::new(
+            element.unwrap_or(Value::Scalar("unknown")),
+        )));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+        Err(Error::custom(format!(
+            "{}: maps are not traceable",
+            self.path
+        )))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut fields = TracedFields {
+            schema: self.schema,
+            fields: fields
+                .iter()
+                .filter(|field| Some(join(&self.path, field).as_str()) != self.omit)
+                .copied()
+                .collect::<Vec<_>>()
+                .into_iter(),
+            current: None,
+            path: self.path,
+            omit: self.omit,
+            keys: vec![],
+        };
+        let value = visitor.visit_map(&mut fields)?;
+
+        *self.shape = Some(Value::Struct(fields.keys));
+        Ok(value)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        *self.shape = Some(Value::OneOf(variants.to_vec()));
+        visitor.visit_enum(UnitVariant(variants.first().copied().unwrap_or_default()))
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Keys of the traced struct
+struct TracedFields<'a> {
+    schema: &'a ConfigSchema,
+    fields: std::vec::IntoIter<&'static str>,
+    current: Option<&'static str>,
+    path: String,
+    omit: Option<&'a str>,
+    keys: Vec<Key>,
+}
+
+impl<'de> MapAccess<'de> for TracedFields<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        self.current = self.fields.next();
+        self.current
+            .map(|field| seed.deserialize(IntoDeserializer::<Error>::into_deserializer(field)))
+            .transpose()
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let name = self
+            .current
+            .ok_or_else(|| Error::custom("Value is asked before the key"))?;
+        let mut shape = None;
+        let value = seed.deserialize(self.schema.tracer(
+            join(&self.path, name),
+            self.omit,
+            &mut shape,
+        ))?;
+
+        self.keys.push(Key {
+            name,
+            required: true,
+            value: shape.unwrap_or(Value::Scalar("unknown")),
+        });
+        Ok(value)
+    }
+}
+
+/// The only element of the traced list
+struct TracedElement<'a> {
+    tracer: Option<Tracer<'a>>,
+}
+
+impl<'de> SeqAccess<'de> for TracedElement<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.tracer
+            .take()
+            .map(|tracer| seed.deserialize(tracer))
+            .transpose()
+    }
+}
+
+/// First variant of the unit enum
+struct UnitVariant(&'static str);
+
+impl<'de> EnumAccess<'de> for UnitVariant {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant: StrDeserializer<Error> = self.0.into_deserializer();
+        Ok((seed.deserialize(variant)?, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for UnitVariant {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, _: T) -> Result<T::Value, Error> {
+        Err(Error::custom("Only unit variants are traceable"))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, _: V) -> Result<V::Value, Error> {
+        Err(Error::custom("Only unit variants are traceable"))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Error> {
+        Err(Error::custom("Only unit variants are traceable"))
+    }
+}
+
+/// Self-describing sample of the traced value, fed to the internally tagged enums
+struct Sample<'a>(&'a Value);
+
+impl<'de> Deserializer<'de> for Sample<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Scalar("boolean") => visitor.visit_bool(false),
+            Value::Scalar("integer") => visitor.visit_u64(1),
+            Value::Scalar("number") => visitor.visit_f64(0.5),
+            Value::Scalar(_) => visitor.visit_str(SAMPLE_STRING),
+            Value::OneOf(names) => visitor.visit_str(names.first().copied().unwrap_or_default()),
+            Value::List(element) => visitor.visit_seq(SampleElement(Some(element))),
+            Value::Struct(keys) => visitor.visit_map(SampleKeys {
+                tag: None,
+                keys: keys.iter(),
+                current: None,
+            }),
+            Value::Tagged { tag, variants } => {
+                let (name, keys) = variants
+                    .first()
+                    .ok_or_else(|| Error::custom("Tagged enum has no variants"))?;
+                visitor.visit_map(SampleKeys {
+                    tag: Some((*tag, *name)),
+                    keys: keys.iter(),
+                    current: None,
+                })
+            }
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct SampleElement<'a>(Option<&'a Value>);
+
+impl<'de> SeqAccess<'de> for SampleElement<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0
+            .take()
+            .map(|value| seed.deserialize(Sample(value)))
+            .transpose()
+    }
+}
+
+struct SampleKeys<'a> {
+    /// Tag and the name of the variant, the first key of the tagged enum
+    tag: Option<(&'static str, &'static str)>,
+    keys: std::slice::Iter<'a, Key>,
+    current: Option<SampleValue<'a>>,
+}
+
+enum SampleValue<'a> {
+    Variant(&'static str),
+    Value(&'a Value),
+}
+
+impl<'de> MapAccess<'de> for SampleKeys<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let name = match self.tag.take() {
+            Some((tag, variant)) => {
+                self.current = Some(SampleValue::Variant(variant));
+                tag
+            }
+            None => match self.keys.next() {
+                Some(key) => {
+                    self.current = Some(SampleValue::Value(&key.value));
+                    key.name
+                }
+                None => return Ok(None),
+            },
+        };
+
+        seed.deserialize(IntoDeserializer::<Error>::into_deserializer(name))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.current.take() {
+            Some(SampleValue::Variant(variant)) => {
+                seed.deserialize(IntoDeserializer::<Error>::into_deserializer(variant))
+            }
+            Some(SampleValue::Value(value)) => seed.deserialize(Sample(value)),
+            None => Err(Error::custom("Value is asked before the key")),
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Conformance of partner implementations: vendor-supplied vectors validated against the local implementation
+//!
+//! Every `.json` file of the directory is a test suite, evaluated with the test secret of the file:
+//! `{"secret": "...", "vectors": [{"name": "...", "transaction": {...}, "salt": "...", "fingerprint": "..."}]}`.
+//! Fingerprints and the secret are compacted, the salt is UTF-8 string.
+
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
+use fingerprinting_core::explain::ExplainSecret;
+use fingerprinting_core::pii::{self, PiiField};
+use fingerprinting_core::{Compact, TransactionFingerprintData};
+use fingerprinting_types::{Counterparty, Money, RawTransaction};
+use halo2_axiom::halo2curves::bn256::Fr;
+use serde_derive::Deserialize;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VectorFile {
+    pub secret: String,
+    pub vectors: Vec<Vector>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Vector {
+    pub name: String,
+    pub transaction: VectorTransaction,
+    pub salt: Option<String>,
+    /// Fingerprint claimed by the vendor
+    pub fingerprint: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VectorTransaction {
+    pub bic: String,
+    /// Decimal amount, e.g. "1000.55"
+    pub amount: String,
+    pub currency: String,
+    pub date_time: DateTime<Utc>,
+    /// The date of the transaction by default
+    pub wwd: Option<NaiveDate>,
+    /// Decimal amount of the bought leg of the FX transaction
+    pub counter_amount: Option<String>,
+    pub counter_currency: Option<String>,
+    pub series_id: Option<String>,
+    pub merchant_id: Option<String>,
+    /// ISO 3166-1 alpha-2 or numeric code
+    pub country_code: Option<String>,
+    /// ISO 20022 end-to-end reference or remittance id
+    pub transaction_reference: Option<String>,
+    /// ISO 18245 merchant category code, 4 decimal digits
+    pub mcc: Option<String>,
+    /// Identifiers of the payer and the payee, given together
+    pub payer: Option<String>,
+    pub payee: Option<String>,
+}
+
+impl VectorTransaction {
+    pub fn raw_transaction(&self) -> Result<RawTransaction, Error> {
+        let (amount_base, amount_atto) = parse_amount(&self.amount)?;
+        let counter_amount = match (&self.counter_amount, &self.counter_currency) {
+            (Some(amount), Some(currency)) => {
+                let (amount_base, amount_atto) = parse_amount(amount)?;
+                Some(Money {
+                    amount_base,
+                    amount_atto,
+                    currency: currency.clone(),
+                })
+            }
+            (None, None) => None,
+            _ => {
+                return Err(anyhow!(
+                    "Counter amount and counter currency should be given together"
+                ))
+            }
+        };
+        let counterparty = match (&self.payer, &self.payee) {
+            (Some(payer), Some(payee)) => Some(Counterparty {
+                payer: payer.clone(),
+                payee: payee.clone(),
+            }),
+            (None, None) => None,
+            _ => return Err(anyhow!("Payer and payee should be given together")),
+        };
+
+        Ok(RawTransaction {
+            bic: self.bic.clone(),
+            amount: Money {
+                amount_base,
+                amount_atto,
+                currency: self.currency.clone(),
+            },
+            counter_amount,
+            series_id: self.series_id.clone(),
+            merchant_id: self.merchant_id.clone(),
+            country_code: self.country_code.clone(),
+            transaction_reference: self.transaction_reference.clone(),
+            mcc: self.mcc.clone(),
+            counterparty,
+            date_time: self.date_time,
+            wwd: self.wwd.unwrap_or(self.date_time.date_naive()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// Claimed fingerprint differs from the local one
+    Failed(String),
+    /// Vector could not be evaluated
+    Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub name: String,
+    pub outcome: Outcome,
+    pub time: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct SuiteResult {
+    pub name: String,
+    pub cases: Vec<CaseResult>,
+}
+
+impl SuiteResult {
+    pub fn failures(&self) -> usize {
+        self.cases
+            .iter()
+            .filter(|case| matches!(case.outcome, Outcome::Failed(_)))
+            .count()
+    }
+
+    pub fn errors(&self) -> usize {
+        self.cases
+            .iter()
+            .filter(|case| matches!(case.outcome, Outcome::Error(_)))
+            .count()
+    }
+}
+
+/// Runs the vector files of the `directory` in the name order, unreadable file is reported as the erroneous suite
+pub fn run_directory(directory: &Path) -> Result<Vec<SuiteResult>, Error> {
+    let mut files = std::fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "json")
+    });
+    files.sort();
+
+    Ok(files
+        .iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let vectors = std::fs::read(path)
+                .map_err(Error::from)
+                .and_then(|content| Ok(serde_json::from_slice::<VectorFile>(&content)?));
+
+            match vectors {
+                Ok(vectors) => run_suite(name, &vectors),
+                Err(e) => SuiteResult {
+                    cases: vec![CaseResult {
+                        name: name.clone(),
+                        outcome: Outcome::Error(format!("Invalid vector file: {}", e)),
+                        time: Duration::ZERO,
+                    }],
+                    name,
+                },
+            }
+        })
+        .collect())
+}
+
+pub fn run_suite(name: String, file: &VectorFile) -> SuiteResult {
+    let secret = Compact::unwrap(&file.secret).map(ExplainSecret::Naive);
+
+    let cases =
+        file.vectors
+            .iter()
+            .map(|vector| {
+                let started = Instant::now();
+                let outcome = match &secret {
+                    Ok(secret) => check_vector(vector, secret)
+                        .unwrap_or_else(|e| Outcome::Error(e.to_string())),
+                    Err(e) => Outcome::Error(format!("Invalid secret: {}", e)),
+                };
+
+                CaseResult {
+                    name: vector.name.clone(),
+                    outcome,
+                    time: started.elapsed(),
+                }
+            })
+            .collect();
+
+    SuiteResult { name, cases }
+}
+
+fn check_vector(vector: &Vector, secret: &ExplainSecret) -> Result<Outcome, Error> {
+    let mut tx_data: TransactionFingerprintData<Fr> =
+        vector.transaction.raw_transaction()?.try_into()?;
+    if let Some(salt) = &vector.salt {
+        tx_data = tx_data.with_salt(Bytes::from(salt.clone()))?;
+    }
+    let claimed: Fr = Compact::unwrap(&vector.fingerprint)
+        .map_err(|e| anyhow!("Invalid claimed fingerprint: {}", e))?;
+
+    let explanation = tx_data.explain(secret)?;
+    if explanation.fingerprint == claimed {
+        Ok(Outcome::Passed)
+    } else {
+        // Preimage points to the diverging component, see `explain` command for the rest of the values
+        Ok(Outcome::Failed(format!(
+            "Expected {}, local implementation computed {}, preimage {}",
+            vector.fingerprint,
+            explanation.fingerprint.compact(),
+            hex::encode(&explanation.preimage)
+        )))
+    }
+}
+
+/// Decimal amount to the units and atto (10^-18) parts
+pub fn parse_amount(amount: &str) -> Result<(u64, u64), Error> {
+    let (units, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if fraction.len() > 18 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return Err(anyhow!(
+            "Invalid amount {}",
+            pii::scrubbed(PiiField::Amount, amount)
+        ));
+    }
+
+    Ok((units.parse()?, format!("{:0<18}", fraction).parse()?))
+}
+
+/// Writes the JUnit XML report, understood by the CI systems
+pub fn write_junit<W: Write>(suites: &[SuiteResult], output: &mut W) -> Result<(), Error> {
+    let tests: usize = suites.iter().map(|suite| suite.cases.len()).sum();
+    let failures: usize = suites.iter().map(SuiteResult::failures).sum();
+    let errors: usize = suites.iter().map(SuiteResult::errors).sum();
+
+    writeln!(output, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        output,
+        r#"<testsuites name="fingerprint-conformance" tests="{}" failures="{}" errors="{}">"#,
+        tests, failures, errors
+    )?;
+    for suite in suites {
+        let time: Duration = suite.cases.iter().map(|case| case.time).sum();
+        writeln!(
+            output,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" time="{:.3}">"#,
+            escape(&suite.name),
+            suite.cases.len(),
+            suite.failures(),
+            suite.errors(),
+            time.as_secs_f64()
+        )?;
+        for case in &suite.cases {
+            let open = format!(
+                r#"    <testcase classname="{}" name="{}" time="{:.3}""#,
+                escape(&suite.name),
+                escape(&case.name),
+                case.time.as_secs_f64()
+            );
+            match &case.outcome {
+                Outcome::Passed => writeln!(output, "{}/>", open)?,
+                Outcome::Failed(message) => writeln!(
+                    output,
+                    "{}>\n      <failure message=\"{}\"/>\n    </testcase>",
+                    open,
+                    escape(message)
+                )?,
+                Outcome::Error(message) => writeln!(
+                    output,
+                    "{}>\n      <error message=\"{}\"/>\n    </testcase>",
+                    open,
+                    escape(message)
+                )?,
+            }
+        }
+        writeln!(output, "  </testsuite>")?;
+    }
+    writeln!(output, "</testsuites>")?;
+
+    Ok(())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fingerprinting_core::{NaiveProtocol, ProtocolFingerprint};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_conformance_report() -> Result<(), Error> {
+        let secret = Fr::from(42);
+        let transaction = VectorTransaction {
+            bic: "BCEELU21".to_string(),
+            amount: "1000.55".to_string(),
+            currency: "EUR".to_string(),
+            date_time: "2025-09-16T12:30:15Z".parse()?,
+            wwd: None,
+            counter_amount: None,
+            counter_currency: None,
+            series_id: None,
+            merchant_id: None,
+            country_code: None,
+            transaction_reference: None,
+            mcc: None,
+            payer: None,
+            payee: None,
+        };
+        let tx: TransactionFingerprintData<Fr> = RawTransaction {
+            bic: transaction.bic.clone(),
+            amount: Money {
+                amount_base: 1000,
+                amount_atto: 550_000_000_000_000_000,
+                currency: "EUR".to_string(),
+            },
+            counter_amount: None,
+            series_id: None,
+            merchant_id: None,
+            country_code: None,
+            transaction_reference: None,
+            mcc: None,
+            counterparty: None,
+            date_time: transaction.date_time,
+            wwd: transaction.date_time.date_naive(),
+        }
+        .try_into()?;
+        let fingerprint = tx.complete_fingerprint(&NaiveProtocol::new(secret)).await?;
+
+        let vector = |name: &str, fingerprint: String, currency: &str| Vector {
+            name: name.to_string(),
+            transaction: VectorTransaction {
+                currency: currency.to_string(),
+                ..transaction.clone()
+            },
+            salt: None,
+            fingerprint,
+        };
+        let file = VectorFile {
+            secret: secret.compact(),
+            vectors: vec![
+                vector("matching", fingerprint.to_string(), "EUR"),
+                vector("different", Fr::from(7).compact(), "EUR"),
+                vector("invalid <currency>", fingerprint.to_string(), "XXY"),
+            ],
+        };
+
+        let suite = run_suite("vendor".to_string(), &file);
+        assert_eq!(suite.cases[0].outcome, Outcome::Passed);
+        assert!(matches!(suite.cases[1].outcome, Outcome::Failed(_)));
+        assert!(matches!(suite.cases[2].outcome, Outcome::Error(_)));
+        assert_eq!((suite.failures(), suite.errors()), (1, 1));
+
+        let mut report = Vec::new();
+        write_junit(&[suite], &mut report)?;
+        let report = String::from_utf8(report)?;
+        assert!(report.contains(
+            r#"<testsuites name="fingerprint-conformance" tests="3" failures="1" errors="1">"#
+        ));
+        assert!(report.contains(r#"name="invalid &lt;currency&gt;""#));
+
+        assert_eq!(parse_amount("1000.55")?, (1000, 550_000_000_000_000_000));
+        assert_eq!(parse_amount("7")?, (7, 0));
+        assert!(parse_amount("1.-5").is_err());
+
+        Ok(())
+    }
+}
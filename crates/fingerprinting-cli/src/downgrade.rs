@@ -0,0 +1,250 @@
+//! Downgrade protection of the full agent
+//!
+//! The agent records the protocol mode, the threshold and the key epoch it runs with in the local state file,
+//! signed by the Ed25519 key of `state-file.signing-key`. On start the configuration is compared with the recorded
+//! state: the weaker one (cooperative → embedded → naive, the lower threshold of the same mode or the lower key epoch)
+//! or the state file failing the signature check refuses to start unless acknowledged by `--allow-downgrade`, so the
+//! accidental change of the configuration does not silently collapse the security model. The accepted configuration
+//! is recorded as the new state.
+
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use fingerprinting_core::clock::Clock;
+use fingerprinting_core::Compact;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+const STATE_DOMAIN: &[u8] = b"outbe/fingerprint/protocol-state/1";
+
+/// Protocol modes ordered by the strength of the security model
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtocolMode {
+    /// Whole secret is held by the agent
+    Naive,
+    /// Secret is shared between the agents of the single process
+    Embedded,
+    /// Agents hold the shards of the secret
+    Cooperative,
+}
+
+impl fmt::Display for ProtocolMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProtocolMode::Naive => "naive",
+            ProtocolMode::Embedded => "embedded",
+            ProtocolMode::Cooperative => "cooperative",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolState {
+    pub mode: ProtocolMode,
+    /// Agents cooperating in every fingerprint, 1 in the naive mode
+    pub threshold: usize,
+    /// Generation of the secret, increased on every rotation
+    pub key_epoch: u64,
+}
+
+impl ProtocolState {
+    /// Ways this state is weaker than the `recorded` one, none when it is not
+    pub fn downgrades(&self, recorded: &ProtocolState) -> Vec<String> {
+        let mut downgrades = vec![];
+        if self.mode < recorded.mode {
+            downgrades.push(format!("mode {} → {}", recorded.mode, self.mode));
+        } else if self.mode == recorded.mode && self.threshold < recorded.threshold {
+            downgrades.push(format!(
+                "threshold {} → {}",
+                recorded.threshold, self.threshold
+            ));
+        }
+        if self.key_epoch < recorded.key_epoch {
+            downgrades.push(format!(
+                "key epoch {} → {}",
+                recorded.key_epoch, self.key_epoch
+            ));
+        }
+
+        downgrades
+    }
+
+    fn payload(&self, recorded_at: &DateTime<Utc>) -> Vec<u8> {
+        [
+            STATE_DOMAIN,
+            self.mode.to_string().as_bytes(),
+            &(self.threshold as u64).to_be_bytes(),
+            &self.key_epoch.to_be_bytes(),
+            &recorded_at.timestamp().to_be_bytes(),
+        ]
+        .join(&0u8)
+    }
+}
+
+/// Content of the state file, the signature is compacted
+#[derive(Serialize, Deserialize, Debug)]
+struct SignedState {
+    #[serde(flatten)]
+    state: ProtocolState,
+    recorded_at: DateTime<Utc>,
+    signature: String,
+}
+
+/// Recorded state, none when the file does not exist yet
+pub fn read_state(path: &Path, key: &VerifyingKey) -> Result<Option<ProtocolState>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let signed: SignedState = serde_json::from_slice(&std::fs::read(path)?)?;
+
+    let signature: bytes::Bytes = Compact::unwrap(&signed.signature)?;
+    let signature = Signature::from_slice(&signature)?;
+    key.verify_strict(&signed.state.payload(&signed.recorded_at), &signature)
+        .map_err(|_| anyhow!("State is not signed by the configured signing key"))?;
+
+    Ok(Some(signed.state))
+}
+
+/// Records the state, replacing the file at once
+pub fn write_state(
+    path: &Path,
+    state: &ProtocolState,
+    key: &SigningKey,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    let signature = key.sign(&state.payload(&now));
+    let signed = SignedState {
+        state: *state,
+        recorded_at: now,
+        signature: bytes::Bytes::copy_from_slice(&signature.to_bytes()).compact(),
+    };
+
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, serde_json::to_vec_pretty(&signed)?)?;
+    std::fs::rename(&temporary, path)?;
+
+    Ok(())
+}
+
+/// Checks the configured `state` against the recorded one and records it at the `clock` time, the downgrade is refused
+/// unless allowed
+pub fn check_downgrade(
+    path: &Path,
+    state: &ProtocolState,
+    key: &SigningKey,
+    allow_downgrade: bool,
+    clock: &dyn Clock,
+) -> Result<(), Error> {
+    let acknowledge = "start with --allow-downgrade to acknowledge";
+    match read_state(path, &key.verifying_key()) {
+        Ok(None) => log::info!("== Recording the protocol state to {}", path.display()),
+        Ok(Some(recorded)) => {
+            let downgrades = state.downgrades(&recorded);
+            match (downgrades.is_empty(), allow_downgrade) {
+                (true, _) => {}
+                (false, true) => log::warn!(
+                    "== Protocol downgrade is acknowledged: {}",
+                    downgrades.join(", ")
+                ),
+                (false, false) => {
+                    return Err(anyhow!(
+                        "Configuration is weaker than the one recorded in {}: {}, {}",
+                        path.display(),
+                        downgrades.join(", "),
+                        acknowledge
+                    ))
+                }
+            }
+        }
+        Err(e) if allow_downgrade => log::warn!(
+            "== Protocol state {} is recorded anew, it failed the check: {}",
+            path.display(),
+            e
+        ),
+        Err(e) => {
+            return Err(anyhow!(
+                "Protocol state {} failed the check: {}, {}",
+                path.display(),
+                e,
+                acknowledge
+            ))
+        }
+    }
+
+    write_state(path, state, key, clock.now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use fingerprinting_core::clock::FixedClock;
+
+    fn recorded_at(path: &Path) -> Result<DateTime<Utc>, Error> {
+        let signed: SignedState = serde_json::from_slice(&std::fs::read(path)?)?;
+        Ok(signed.recorded_at)
+    }
+
+    #[test]
+    fn test_check_downgrade() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("protocol-state-{}.json", std::process::id()));
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap());
+        let cooperative = ProtocolState {
+            mode: ProtocolMode::Cooperative,
+            threshold: 3,
+            key_epoch: 2,
+        };
+
+        check_downgrade(&path, &cooperative, &key, false, &clock)?;
+        assert_eq!(read_state(&path, &key.verifying_key())?, Some(cooperative));
+        assert_eq!(recorded_at(&path)?, clock.now());
+        // Stronger and the same configurations start
+        check_downgrade(&path, &cooperative, &key, false, &clock)?;
+        let rotated = ProtocolState {
+            key_epoch: 3,
+            ..cooperative
+        };
+        check_downgrade(&path, &rotated, &key, false, &clock)?;
+
+        let naive = ProtocolState {
+            mode: ProtocolMode::Naive,
+            threshold: 1,
+            key_epoch: 3,
+        };
+        let lower_threshold = ProtocolState {
+            threshold: 2,
+            ..rotated
+        };
+        assert_eq!(naive.downgrades(&rotated), vec!["mode cooperative → naive"]);
+        assert_eq!(cooperative.downgrades(&rotated), vec!["key epoch 3 → 2"]);
+        assert!(check_downgrade(&path, &naive, &key, false, &clock).is_err());
+        assert!(check_downgrade(&path, &lower_threshold, &key, false, &clock).is_err());
+        assert_eq!(read_state(&path, &key.verifying_key())?, Some(rotated));
+
+        // Acknowledged downgrade is recorded as the new state at its time
+        clock.advance(chrono::Duration::days(1));
+        check_downgrade(&path, &lower_threshold, &key, true, &clock)?;
+        assert_eq!(
+            recorded_at(&path)?,
+            Utc.with_ymd_and_hms(2025, 9, 17, 12, 0, 0).unwrap()
+        );
+        check_downgrade(&path, &lower_threshold, &key, false, &clock)?;
+
+        // State signed by another key (or edited) is refused
+        let other = SigningKey::from_bytes(&[8; 32]);
+        assert!(read_state(&path, &other.verifying_key()).is_err());
+        assert!(check_downgrade(&path, &rotated, &other, false, &clock).is_err());
+        let edited =
+            std::fs::read_to_string(&path)?.replace("\"threshold\": 2", "\"threshold\": 1");
+        std::fs::write(&path, edited)?;
+        assert!(read_state(&path, &key.verifying_key()).is_err());
+        check_downgrade(&path, &rotated, &key, true, &clock)?;
+        assert_eq!(read_state(&path, &key.verifying_key())?, Some(rotated));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
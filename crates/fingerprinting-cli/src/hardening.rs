@@ -0,0 +1,244 @@
+//! Hardening of the agent process holding the secret shard
+//!
+//! The shard file should be readable by the owner only, privileges are dropped once the port is bound
+//! and the optional seccomp profile restricts the process to the system calls of the network server.
+
+use crate::config::SeccompMode;
+use anyhow::{anyhow, Error};
+use std::path::Path;
+
+/// Reads the compacted secret shard from `path`, refusing the file accessible by group or others
+pub fn read_secret_file(path: &Path) -> Result<String, Error> {
+    check_secret_permissions(path)?;
+
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+
+    Ok(content.trim().to_string())
+}
+
+#[cfg(unix)]
+pub fn check_secret_permissions(path: &Path) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        return Err(anyhow!(
+            "Permissions {:o} of {} are too open, it should not be accessible by group and others",
+            mode & 0o777,
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn check_secret_permissions(_: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Switches the process to the `user` and the `group` (primary group of the user by default)
+/// It fails when the privileges could be regained afterwards
+#[cfg(unix)]
+pub fn drop_privileges(user: &str, group: Option<&str>) -> Result<(), Error> {
+    use nix::unistd::{setgid, setgroups, setuid, Group, Uid, User};
+
+    let user = User::from_name(user)?.ok_or(anyhow!("Unknown user {}", user))?;
+    let gid = match group {
+        Some(group) => {
+            Group::from_name(group)?
+                .ok_or(anyhow!("Unknown group {}", group))?
+                .gid
+        }
+        None => user.gid,
+    };
+
+    // Supplementary groups are dropped first, it requires the privileges as well as the group change
+    setgroups(&[gid]).map_err(|e| anyhow!("Failed to drop supplementary groups: {}", e))?;
+    setgid(gid).map_err(|e| anyhow!("Failed to switch to group {}: {}", gid, e))?;
+    setuid(user.uid).map_err(|e| anyhow!("Failed to switch to user {}: {}", user.name, e))?;
+
+    if !user.uid.is_root() && setuid(Uid::from_raw(0)).is_ok() {
+        return Err(anyhow!("Privileges are regained after dropping them"));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn drop_privileges(_: &str, _: Option<&str>) -> Result<(), Error> {
+    Err(anyhow!("Privilege drop is supported on Unix only"))
+}
+
+/// Applies the seccomp profile to all threads of the process, it can't be removed afterwards
+/// The `relay` profile lets the agent reap and kill the signer process it has started
+#[cfg(target_os = "linux")]
+pub fn apply_seccomp(mode: SeccompMode, relay: bool) -> Result<(), Error> {
+    if let Some(program) = seccomp_program(mode, relay)? {
+        seccompiler::apply_filter_all_threads(&program)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply_seccomp(mode: SeccompMode, _: bool) -> Result<(), Error> {
+    match mode {
+        SeccompMode::Disabled => Ok(()),
+        _ => Err(anyhow!("Seccomp is supported on Linux only")),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn seccomp_program(
+    mode: SeccompMode,
+    relay: bool,
+) -> Result<Option<seccompiler::BpfProgram>, Error> {
+    use seccompiler::{SeccompAction, SeccompFilter};
+
+    let mismatch_action = match mode {
+        SeccompMode::Disabled => return Ok(None),
+        SeccompMode::Log => SeccompAction::Log,
+        SeccompMode::Enforce => SeccompAction::KillProcess,
+    };
+
+    // Empty rules allow the system call with any arguments
+    let rules = allowed_syscalls(relay)
+        .map(|syscall| (*syscall, vec![]))
+        .collect();
+    let filter = SeccompFilter::new(
+        rules,
+        mismatch_action,
+        SeccompAction::Allow,
+        std::env::consts::ARCH.try_into()?,
+    )?;
+
+    Ok(Some(filter.try_into()?))
+}
+
+#[cfg(target_os = "linux")]
+fn allowed_syscalls(relay: bool) -> impl Iterator<Item = &'static libc::c_long> {
+    let relay_syscalls: &[libc::c_long] = if relay { RELAY_SYSCALLS } else { &[] };
+    ALLOWED_SYSCALLS.iter().chain(relay_syscalls)
+}
+
+// Signer child is reaped by the Tokio runtime once it exits and killed when the relay is dropped
+#[cfg(target_os = "linux")]
+const RELAY_SYSCALLS: &[libc::c_long] = &[libc::SYS_kill, libc::SYS_wait4];
+
+// System calls of the Tokio runtime serving the gRPC connections and writing the logs,
+// files are opened for the runtime introspection only
+#[cfg(target_os = "linux")]
+const ALLOWED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_accept4,
+    libc::SYS_brk,
+    libc::SYS_clock_gettime,
+    libc::SYS_clock_nanosleep,
+    libc::SYS_clone,
+    libc::SYS_clone3,
+    libc::SYS_close,
+    libc::SYS_epoll_ctl,
+    libc::SYS_epoll_pwait,
+    libc::SYS_eventfd2,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+    libc::SYS_fcntl,
+    libc::SYS_fstat,
+    libc::SYS_futex,
+    libc::SYS_getpeername,
+    libc::SYS_getpid,
+    libc::SYS_getrandom,
+    libc::SYS_getsockname,
+    libc::SYS_getsockopt,
+    libc::SYS_gettid,
+    libc::SYS_ioctl,
+    libc::SYS_lseek,
+    libc::SYS_madvise,
+    libc::SYS_mmap,
+    libc::SYS_mprotect,
+    libc::SYS_mremap,
+    libc::SYS_munmap,
+    libc::SYS_nanosleep,
+    libc::SYS_newfstatat,
+    libc::SYS_openat,
+    libc::SYS_ppoll,
+    libc::SYS_prctl,
+    libc::SYS_read,
+    libc::SYS_readv,
+    libc::SYS_recvfrom,
+    libc::SYS_recvmsg,
+    libc::SYS_restart_syscall,
+    libc::SYS_rseq,
+    libc::SYS_rt_sigaction,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_sched_getaffinity,
+    libc::SYS_sched_yield,
+    libc::SYS_sendmsg,
+    libc::SYS_sendto,
+    libc::SYS_set_robust_list,
+    libc::SYS_setsockopt,
+    libc::SYS_shutdown,
+    libc::SYS_sigaltstack,
+    libc::SYS_statx,
+    libc::SYS_tgkill,
+    libc::SYS_write,
+    libc::SYS_writev,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_accept,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_epoll_wait,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_open,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_poll,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secret_file() -> Result<(), Error> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("secret-shard-{}", std::process::id()));
+        std::fs::write(&path, "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD\n")?;
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+        assert!(read_secret_file(&path).is_err());
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o400))?;
+        assert_eq!(
+            read_secret_file(&path)?,
+            "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD"
+        );
+
+        std::fs::remove_file(&path)?;
+
+        #[cfg(target_os = "linux")]
+        {
+            assert!(seccomp_program(SeccompMode::Disabled, false)?.is_none());
+            assert!(seccomp_program(SeccompMode::Enforce, false)?.is_some());
+            assert!(seccomp_program(SeccompMode::Enforce, true)?.is_some());
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_relay_syscalls() {
+        let reaping = [libc::SYS_wait4, libc::SYS_kill];
+        let agent: Vec<_> = allowed_syscalls(false).collect();
+        let relay: Vec<_> = allowed_syscalls(true).collect();
+        assert!(reaping.iter().all(|syscall| !agent.contains(&syscall)));
+        assert!(reaping.iter().all(|syscall| relay.contains(&syscall)));
+        assert_eq!(relay.len(), agent.len() + reaping.len());
+    }
+}
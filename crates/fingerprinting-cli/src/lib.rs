@@ -1,2 +1,13 @@
+pub mod batch;
+pub mod capacity;
 pub mod config;
-
+pub mod conformance;
+pub mod downgrade;
+pub mod hardening;
+pub mod loadgen;
+pub mod migration;
+pub mod near_miss;
+pub mod replay;
+pub mod rest;
+pub mod selftest;
+pub mod snapshot;
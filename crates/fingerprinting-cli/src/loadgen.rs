@@ -0,0 +1,511 @@
+//! Synthetic transaction streams and the latency report of the soak and load runs, see the `fingerprinting-loadgen`
+//! binary
+//!
+//! Transactions are drawn from the fixed set of banks and merchant categories with the log-uniform amounts and the
+//! currencies weighted by the mix, the duplicate is the resend of one of the recent transactions. The stream is
+//! reproducible by its seed. Latencies are kept in the log-linear histogram of ~3% precision, so the runs of hours
+//! take constant memory.
+
+use anyhow::{anyhow, Error};
+use chrono::{Duration as ChronoDuration, Utc};
+use fingerprinting_grpc::net::outbe::common::v1::Money as MoneyDto;
+use fingerprinting_grpc::net::outbe::fingerprint::v1::TransactionFingerprintData;
+use fingerprinting_types::{Money, RawTransactionBuilder};
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+/// Mix of the currencies when none is given
+pub const DEFAULT_CURRENCY_MIX: &str = "EUR:60,USD:25,GBP:10,CHF:5";
+
+/// Recent transactions the duplicates are drawn from
+const RECENT_TRANSACTIONS: usize = 1024;
+
+const BICS: [&str; 8] = [
+    "BCEELU21", "DEUTDEFF", "BNPAFRPP", "COBADEFF", "INGBNL2A", "UBSWCHZH", "BARCGB22", "CHASUS33",
+];
+
+/// Groceries, restaurants, fuel, transit, online retail, utilities
+const MCCS: [&str; 6] = ["5411", "5812", "5541", "4111", "5999", "4900"];
+
+/// Largest amount in the base units
+const MAX_AMOUNT_BASE: f64 = 100_000.0;
+
+/// Currency of the mix with its weight
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrencyWeight {
+    pub currency: String,
+    pub weight: u32,
+}
+
+/// Parses the mix as `<currency>:<weight>,...`, e.g. `EUR:60,USD:40`
+pub fn parse_currency_mix(mix: &str) -> Result<Vec<CurrencyWeight>, Error> {
+    let weights = mix
+        .split(',')
+        .map(|entry| {
+            let (currency, weight) = entry.trim().split_once(':').ok_or(anyhow!(
+                "Currency mix entry should be <currency>:<weight>, given {}",
+                entry
+            ))?;
+            // Currencies the service does not accept would fail every request of theirs
+            let _: MoneyDto = Money {
+                amount_base: 0,
+                amount_atto: 0,
+                currency: currency.to_string(),
+            }
+            .try_into()
+            .map_err(|e| anyhow!("Currency {} is not supported: {}", currency, e))?;
+
+            Ok(CurrencyWeight {
+                currency: currency.to_string(),
+                weight: weight
+                    .parse()
+                    .map_err(|e| anyhow!("Weight of {} should be the integer: {}", currency, e))?,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    if weights.iter().all(|weight| weight.weight == 0) {
+        return Err(anyhow!("Currency mix should have the positive weight"));
+    }
+
+    Ok(weights)
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadProfile {
+    /// Share of the resent transactions, from 0 to 1
+    pub duplicate_ratio: f64,
+    pub currencies: Vec<CurrencyWeight>,
+    pub seed: u64,
+}
+
+/// SplitMix64, the small generator reproducing the stream from the seed
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform from 0 to 1 exclusive
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+pub struct TransactionGenerator {
+    profile: LoadProfile,
+    rng: SplitMix64,
+    total_weight: u64,
+    recent: VecDeque<TransactionFingerprintData>,
+}
+
+impl TransactionGenerator {
+    pub fn new(profile: LoadProfile) -> Result<Self, Error> {
+        if !(0.0..=1.0).contains(&profile.duplicate_ratio) {
+            return Err(anyhow!(
+                "Duplicate ratio should be from 0 to 1, given {}",
+                profile.duplicate_ratio
+            ));
+        }
+        let total_weight = profile
+            .currencies
+            .iter()
+            .map(|weight| weight.weight as u64)
+            .sum();
+        if total_weight == 0 {
+            return Err(anyhow!("Currency mix should have the positive weight"));
+        }
+
+        Ok(Self {
+            rng: SplitMix64(profile.seed),
+            profile,
+            total_weight,
+            recent: VecDeque::with_capacity(RECENT_TRANSACTIONS),
+        })
+    }
+
+    /// Next transaction of the stream, whether it is the duplicate of the recent one
+    pub fn next_transaction(&mut self) -> Result<(TransactionFingerprintData, bool), Error> {
+        if !self.recent.is_empty() && self.rng.next_f64() < self.profile.duplicate_ratio {
+            let index = self.rng.below(self.recent.len() as u64) as usize;
+            return Ok((self.recent[index].clone(), true));
+        }
+
+        let transaction = self.fresh_transaction()?;
+        if self.recent.len() == RECENT_TRANSACTIONS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(transaction.clone());
+
+        Ok((transaction, false))
+    }
+
+    fn fresh_transaction(&mut self) -> Result<TransactionFingerprintData, Error> {
+        let mut pick = self.rng.below(self.total_weight);
+        let currency = self
+            .profile
+            .currencies
+            .iter()
+            .find(|weight| {
+                if pick < weight.weight as u64 {
+                    return true;
+                }
+                pick -= weight.weight as u64;
+                false
+            })
+            .map(|weight| weight.currency.clone())
+            .unwrap_or_default();
+
+        // Log-uniform amount with the cents, the small payments are the most frequent
+        let amount_base = MAX_AMOUNT_BASE.powf(self.rng.next_f64()) as u64;
+        let cents = self.rng.below(100);
+        // Recorded within the last minute, as the live traffic
+        let date_time = Utc::now() - ChronoDuration::milliseconds(self.rng.below(60_000) as i64);
+
+        let tx = RawTransactionBuilder::default()
+            .bic(BICS[self.rng.below(BICS.len() as u64) as usize])
+            .amount(Money {
+                amount_base,
+                amount_atto: cents * 10_000_000_000_000_000,
+                currency,
+            })
+            .mcc(Some(
+                MCCS[self.rng.below(MCCS.len() as u64) as usize].to_string(),
+            ))
+            .date_time(date_time)
+            .wwd(date_time.date_naive())
+            .build()?;
+
+        tx.try_into()
+    }
+}
+
+/// Exact values below it, the 32 sub-buckets of every power of two above it
+const LINEAR_BUCKETS: u64 = 64;
+
+const SUB_BUCKETS: u64 = 32;
+
+fn bucket(micros: u64) -> usize {
+    if micros < LINEAR_BUCKETS {
+        return micros as usize;
+    }
+    let shift = 63 - micros.leading_zeros() as u64 - 5;
+    let mantissa = micros >> shift;
+
+    (LINEAR_BUCKETS + (shift - 1) * SUB_BUCKETS + mantissa - SUB_BUCKETS) as usize
+}
+
+/// Largest value of the bucket
+fn bucket_limit(index: usize) -> u64 {
+    let index = index as u64;
+    if index < LINEAR_BUCKETS {
+        return index;
+    }
+    let shift = (index - LINEAR_BUCKETS) / SUB_BUCKETS + 1;
+    let mantissa = (index - LINEAR_BUCKETS) % SUB_BUCKETS + SUB_BUCKETS;
+
+    ((mantissa + 1) << shift) - 1
+}
+
+/// Log-linear histogram of the latencies in microseconds
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    total_micros: u64,
+    max_micros: u64,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        let index = bucket(micros);
+        if self.buckets.len() <= index {
+            self.buckets.resize(index + 1, 0);
+        }
+        self.buckets[index] += 1;
+        self.count += 1;
+        self.total_micros = self.total_micros.saturating_add(micros);
+        self.max_micros = self.max_micros.max(micros);
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        if self.buckets.len() < other.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (bucket, count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += count;
+        }
+        self.count += other.count;
+        self.total_micros = self.total_micros.saturating_add(other.total_micros);
+        self.max_micros = self.max_micros.max(other.max_micros);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Latency the `quantile` (from 0 to 1) of the recorded ones is within, zero when none is recorded
+    pub fn percentile(&self, quantile: f64) -> Duration {
+        let rank = ((self.count as f64 * quantile).ceil() as u64).clamp(1, self.count.max(1));
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(bucket_limit(index).min(self.max_micros));
+            }
+        }
+
+        Duration::ZERO
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_micros(self.total_micros / count),
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max_micros)
+    }
+}
+
+/// Outcomes of the requests sent within the window (or the whole run)
+#[derive(Debug, Clone, Default)]
+pub struct LoadStats {
+    /// Latencies of the succeeded requests
+    pub latencies: LatencyHistogram,
+    pub duplicates: u64,
+    /// Failed requests by the gRPC status code, `transport` when the service returned no status
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl LoadStats {
+    pub fn record_success(&mut self, latency: Duration, duplicate: bool) {
+        self.latencies.record(latency);
+        self.duplicates += duplicate as u64;
+    }
+
+    pub fn record_error(&mut self, e: &Error, duplicate: bool) {
+        let code = e
+            .downcast_ref::<volo_grpc::Status>()
+            .map(|status| format!("{:?}", status.code()))
+            .unwrap_or_else(|| "transport".to_string());
+        *self.errors.entry(code).or_default() += 1;
+        self.duplicates += duplicate as u64;
+    }
+
+    pub fn merge(&mut self, other: &LoadStats) {
+        self.latencies.merge(&other.latencies);
+        self.duplicates += other.duplicates;
+        for (code, count) in &other.errors {
+            *self.errors.entry(code.clone()).or_default() += count;
+        }
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.latencies.count() + self.failed()
+    }
+}
+
+/// Objectives the run is checked against
+#[derive(Debug, Clone, Copy)]
+pub struct LoadObjectives {
+    /// Failed requests allowed of all the requests, from 0 to 1
+    pub error_budget: f64,
+    /// 99th percentile of the latency, unchecked when absent
+    pub p99_target: Option<Duration>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LoadReport {
+    pub elapsed_secs: f64,
+    pub requests: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub duplicates: u64,
+    /// Requests completed per second
+    pub achieved_tps: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+    pub errors: BTreeMap<String, u64>,
+    pub error_rate: f64,
+    pub error_budget: f64,
+    /// Failed requests the budget still allows, negative once the budget is exhausted
+    pub error_budget_remaining: f64,
+    /// Objectives missed by the run, empty when all are met
+    pub violations: Vec<String>,
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl LoadReport {
+    pub fn new(stats: &LoadStats, elapsed: Duration, objectives: &LoadObjectives) -> Self {
+        let requests = stats.requests();
+        let failed = stats.failed();
+        let error_rate = match requests {
+            0 => 0.0,
+            requests => failed as f64 / requests as f64,
+        };
+        let latencies = &stats.latencies;
+
+        let mut violations = vec![];
+        if error_rate > objectives.error_budget {
+            violations.push(format!(
+                "Error rate {:.5} exceeds the budget {:.5}",
+                error_rate, objectives.error_budget
+            ));
+        }
+        if let Some(target) = objectives.p99_target {
+            if latencies.percentile(0.99) > target {
+                violations.push(format!(
+                    "p99 latency {:.1} ms exceeds the target {:.1} ms",
+                    millis(latencies.percentile(0.99)),
+                    millis(target)
+                ));
+            }
+        }
+
+        Self {
+            elapsed_secs: elapsed.as_secs_f64(),
+            requests,
+            succeeded: latencies.count(),
+            failed,
+            duplicates: stats.duplicates,
+            achieved_tps: match elapsed.as_secs_f64() {
+                secs if secs > 0.0 => requests as f64 / secs,
+                _ => 0.0,
+            },
+            p50_ms: millis(latencies.percentile(0.5)),
+            p90_ms: millis(latencies.percentile(0.9)),
+            p99_ms: millis(latencies.percentile(0.99)),
+            p999_ms: millis(latencies.percentile(0.999)),
+            mean_ms: millis(latencies.mean()),
+            max_ms: millis(latencies.max()),
+            errors: stats.errors.clone(),
+            error_rate,
+            error_budget: objectives.error_budget,
+            error_budget_remaining: objectives.error_budget * requests as f64 - failed as f64,
+            violations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fingerprinting_grpc::net::outbe::common::v1::Currency;
+    use volo_grpc::{Code, Status};
+
+    #[test]
+    fn test_transaction_generator() -> Result<(), Error> {
+        let profile = LoadProfile {
+            duplicate_ratio: 0.25,
+            currencies: parse_currency_mix("EUR:3, USD:1")?,
+            seed: 7,
+        };
+        let stream = |profile: &LoadProfile| -> Result<Vec<_>, Error> {
+            let mut generator = TransactionGenerator::new(profile.clone())?;
+            (0..2000).map(|_| generator.next_transaction()).collect()
+        };
+
+        let transactions = stream(&profile)?;
+        let duplicates = transactions
+            .iter()
+            .filter(|(_, duplicate)| *duplicate)
+            .count();
+        assert!(
+            (400..600).contains(&duplicates),
+            "{} duplicates",
+            duplicates
+        );
+        let euros = transactions
+            .iter()
+            .filter(|(tx, _)| tx.amount.as_ref().unwrap().currency == Currency::CURRENCY_EUR)
+            .count();
+        assert!((1350..1650).contains(&euros), "{} in EUR", euros);
+
+        // Same seed, same amounts and banks
+        let again = stream(&profile)?;
+        assert!(transactions
+            .iter()
+            .zip(&again)
+            .all(|((tx, _), (again, _))| tx.amount == again.amount && tx.bic == again.bic));
+
+        assert!(parse_currency_mix("EUR:0").is_err());
+        assert!(parse_currency_mix("EUR").is_err());
+        assert!(parse_currency_mix("XYZ:1").is_err());
+        assert!(TransactionGenerator::new(LoadProfile {
+            duplicate_ratio: 1.5,
+            ..profile
+        })
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_report() {
+        for micros in [0, 63, 64, 65, 1000, 123_456, 60_000_000] {
+            let limit = bucket_limit(bucket(micros));
+            assert!(
+                limit >= micros && limit as f64 <= micros as f64 * 1.07 + 1.0,
+                "{}",
+                micros
+            );
+        }
+
+        let mut stats = LoadStats::default();
+        for millis in 1..=1000 {
+            stats.record_success(Duration::from_millis(millis), millis % 10 == 0);
+        }
+        let mut window = LoadStats::default();
+        window.record_error(&anyhow!("connection refused"), false);
+        window.record_error(&Status::new(Code::Unavailable, "draining").into(), false);
+        stats.merge(&window);
+
+        let objectives = LoadObjectives {
+            error_budget: 0.001,
+            p99_target: Some(Duration::from_millis(500)),
+        };
+        let report = LoadReport::new(&stats, Duration::from_secs(10), &objectives);
+        assert_eq!(report.requests, 1002);
+        assert_eq!(report.failed, 2);
+        assert_eq!(report.duplicates, 100);
+        assert_eq!(report.errors.len(), 2);
+        assert!((report.p50_ms - 500.0).abs() < 500.0 * 0.04);
+        assert!((report.p99_ms - 990.0).abs() < 990.0 * 0.04);
+        assert_eq!(report.max_ms, 1000.0);
+        assert!(report.error_budget_remaining < 0.0);
+        assert_eq!(report.violations.len(), 2);
+
+        let relaxed = LoadObjectives {
+            error_budget: 0.01,
+            p99_target: None,
+        };
+        assert!(LoadReport::new(&stats, Duration::from_secs(10), &relaxed)
+            .violations
+            .is_empty());
+    }
+}
@@ -1,32 +1,491 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use fingerprinting_cli::config::{
+    self, schema, EntropyConfig, EntropySourceKind, OfflineAgentConfig,
+};
+use fingerprinting_cli::conformance::{self, parse_amount, VectorTransaction};
+use fingerprinting_cli::migration::{self, SchemaVersion};
+use fingerprinting_cli::replay::{self, ReplayOutcome};
+use fingerprinting_cli::{batch, capacity, hardening, near_miss, selftest};
+use fingerprinting_core::entropy::{self, EntropyRng, EntropySource};
+use fingerprinting_core::explain::ExplainSecret;
+use fingerprinting_core::namespace::Namespace;
 use fingerprinting_core::secret_sharing::SecretSharing;
-use fingerprinting_core::Compact;
-use halo2_axiom::arithmetic::Field;
-use halo2_axiom::halo2curves::bn256::Fr;
+use fingerprinting_core::{wire, Compact, TransactionFingerprintData};
+use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+    ComputeSingleFingerprintRequest, FingerprintServiceClientBuilder, GetServiceInfoRequest,
+    GetUsageRequest,
+};
+use fingerprinting_grpc::{FingerprintRecordWriter, API_KEY};
+use fingerprinting_grpc_agent::relay;
+use fingerprinting_offline_agent::{OfflineAgent, SigningKey};
+use fingerprinting_p2p_agent::Keypair;
+use fingerprinting_store::archive::archive_root;
+use fingerprinting_store::StoredFingerprint;
+use fingerprinting_types::{Money, RawTransaction, RawTransactionBuilder};
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use hocon::HoconLoader;
 use rand_core::OsRng;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::Path;
+use std::sync::Arc;
 
-/// Generate a transaction fingerprint
+/// Fingerprint CLI utility
 #[derive(Parser, Debug)]
 #[command(name = "fingerprinting-cli")]
 #[command(about = "Fingerprint CLI utility", long_about = None)]
 struct Args {
-    /// Threshold for cooperative computation
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate a random secret and its shares for threshold secret sharing
+    GenerateShares {
+        /// Threshold for cooperative computation
+        #[arg(long)]
+        threshold: usize,
+
+        /// Total number of cooperative agents network size
+        #[arg(long)]
+        agents: usize,
+
+        /// Hardware RNG device (HSM, TPM) the secret is drawn from via the DRBG, the OS RNG by default
+        #[arg(long)]
+        entropy_device: Option<String>,
+    },
+
+    /// Generate a random secret shared between the regions and the shares of every region for its agents
+    GenerateRegionalShares {
+        /// Threshold of the regions cooperating in every fingerprint
+        #[arg(long)]
+        threshold: usize,
+
+        /// Threshold and number of the agents of the region as `<threshold>:<agents>`, repeated for every region
+        #[arg(long = "region", required = true)]
+        regions: Vec<String>,
+
+        /// Hardware RNG device (HSM, TPM) the secret is drawn from via the DRBG, the OS RNG by default
+        #[arg(long)]
+        entropy_device: Option<String>,
+    },
+
+    /// Generate an Ed25519 key of the libp2p agent node and print its peer id
+    GeneratePeerKey,
+
+    /// Generate an Ed25519 key signing the offline request or response files and print its verifying key
+    GenerateSigningKey,
+
+    /// Evaluate the request files in the air-gapped environment and write the signed response files
+    ProcessOfflineRequests {
+        /// Offline agent configuration file
+        #[arg(long)]
+        config: String,
+    },
+
+    /// Evaluate the points relayed by the light agent over the standard input and output with the shard in memory,
+    /// the reference signer of the relay protocol
+    RelaySigner {
+        /// File holding the compacted secret shard, not accessible by group and others
+        #[arg(long)]
+        secret_shard_file: String,
+    },
+
+    /// Compute the fingerprint printing every intermediate value, for debugging mismatches with other implementations
+    Explain {
+        #[arg(long)]
+        bic: String,
+
+        /// Decimal amount, e.g. 1000.55
+        #[arg(long)]
+        amount: String,
+
+        /// ISO 4217 currency code
+        #[arg(long)]
+        currency: String,
+
+        /// Decimal amount of the bought leg of the FX transaction, `amount` is the sold leg then
+        #[arg(long, requires = "counter_currency")]
+        counter_amount: Option<String>,
+
+        /// ISO 4217 currency code of the bought leg
+        #[arg(long, requires = "counter_amount")]
+        counter_currency: Option<String>,
+
+        /// RFC 3339 date time of the transaction
+        #[arg(long)]
+        date_time: DateTime<Utc>,
+
+        /// World wide date, the date of the transaction by default
+        #[arg(long)]
+        wwd: Option<NaiveDate>,
+
+        /// Salt as UTF-8 string
+        #[arg(long)]
+        salt: Option<String>,
+
+        /// Namespace of the environment the fingerprint is computed in
+        #[arg(long)]
+        namespace: Option<String>,
+
+        /// Secret of the naive mode
+        #[arg(long, conflicts_with = "share", required_unless_present = "share")]
+        secret: Option<String>,
+
+        /// Agent share as `<agent>:<share>`, repeated for the threshold of agents
+        #[arg(long)]
+        share: Vec<String>,
+    },
+
+    /// Validate the vendor-supplied vector files of the directory against the local implementation
+    Conformance {
+        /// Directory of the vector files
+        #[arg(long)]
+        vectors: String,
+
+        /// JUnit XML report file, the report is printed when not provided
+        #[arg(long)]
+        report: Option<String>,
+    },
+
+    /// Fingerprint the transaction by two schema versions and report whether and why the fingerprints differ
+    Diff {
+        /// JSON file of the transaction in the format of the conformance vectors
+        #[arg(long)]
+        transaction: String,
+
+        /// JSON file of the current schema version
+        #[arg(long)]
+        from: String,
+
+        /// JSON file of the schema version migrated to
+        #[arg(long)]
+        to: String,
+    },
+
+    /// Re-send the recorded exchanges to another instance and diff the fingerprints
+    Replay {
+        /// Recording written by the instance with `recording` configured
+        #[arg(long)]
+        recording: String,
+
+        /// Address of the fingerprint service replayed against, e.g. [::1]:9000
+        #[arg(long)]
+        endpoint: SocketAddr,
+    },
+
+    /// Compute the fingerprints of the JSON lines items via the fingerprint service, streaming the results
+    Batch(BatchArgs),
+
+    /// Report near-duplicates (same fuzzy, different exact fingerprints) by BIC and day
+    NearMiss {
+        /// JSON lines export of fingerprints with the accompanying fuzzy fingerprints
+        #[arg(long)]
+        input: String,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Csv)]
+        format: ReportFormat,
+    },
+
+    /// Report the fingerprints and the agent rounds of the API key in the month, of all the keys for the admin key
+    Usage {
+        /// Address of the fingerprint service, e.g. [::1]:9000
+        #[arg(long)]
+        endpoint: SocketAddr,
+
+        /// API key presented to the service, see `usage` of the agent configuration
+        #[arg(long)]
+        api_key: String,
+
+        /// Calendar month as YYYY-MM, the current one by default
+        #[arg(long)]
+        month: Option<String>,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Csv)]
+        format: ReportFormat,
+    },
+
+    /// Report the fingerprints, duplicates, failures and agent latencies by day from the capacity log of the agent
+    Report {
+        /// Capacity log of the agent, see `capacity-log` of the agent configuration
+        #[arg(long)]
+        capacity_log: String,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Csv)]
+        format: ReportFormat,
+    },
+
+    /// Check the fingerprint records and their Merkle root against the archived epoch
+    VerifyRecords {
+        /// File of the length-delimited fingerprint records
+        #[arg(long)]
+        records: String,
+
+        /// Hex encoded Merkle root of the archived epoch, see the archive manifest
+        #[arg(long)]
+        root: Option<String>,
+    },
+
+    /// Smoke test the binary against the ephemeral in-process topology and print pass or fail of every check,
+    /// exits with the failure if any check fails
+    Selftest {
+        /// Items of the batch check
+        #[arg(long, default_value_t = selftest::BATCH_SIZE)]
+        batch_size: usize,
+    },
+
+    /// Configuration templates and the accepted structure of the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the fully commented configuration template of the mode
+    Init {
+        #[arg(long, value_enum)]
+        mode: ConfigMode,
+    },
+
+    /// Print the keys the configuration accepts, derived from the configuration types
+    Schema {
+        #[arg(long, value_enum, default_value_t = ConfigKind::Agent)]
+        kind: ConfigKind,
+    },
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum ConfigMode {
+    /// Full agent computing the fingerprints with the threshold of agents
+    Cooperative,
+    /// Full agent holding the whole secret, for the development
+    Naive,
+    /// Single node agent with the SQLite store and the REST endpoint, for the pilots
+    Embedded,
+    /// Light agent serving the cooperation requests only
+    Light,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum ConfigKind {
+    /// Configuration of `fingerprinting-agent`
+    Agent,
+    /// Configuration of `fingerprinting-light-agent`
+    LightAgent,
+}
+
+#[derive(clap::Args, Debug)]
+struct BatchArgs {
+    /// JSON lines of the items, "-" reads the standard input
     #[arg(long)]
-    threshold: usize,
+    input: String,
 
-    /// Total number of cooperative agents network size
+    /// Results file, the standard output by default
     #[arg(long)]
-    agents: usize,
+    output: Option<String>,
+
+    /// Address of the fingerprint service, e.g. [::1]:9000, repeated to shard the items across the instances
+    #[arg(long = "endpoint", required = true)]
+    endpoints: Vec<SocketAddr>,
+
+    /// Calls per second of every instance, unlimited by default
+    #[arg(long)]
+    rate_limit: Option<NonZeroU32>,
+
+    /// API key presented to the service, see `usage` of the agent configuration
+    #[arg(long)]
+    api_key: Option<String>,
+
+    /// Items computed at the same time
+    #[arg(long, default_value_t = NonZeroUsize::new(16).unwrap())]
+    concurrency: NonZeroUsize,
+
+    #[arg(long, value_enum, default_value_t = BatchFormat::Json)]
+    format: BatchFormat,
+
+    /// File of the computed item ids, flushed periodically
+    #[arg(long)]
+    checkpoint: Option<String>,
+
+    /// Skip the items of the checkpoint and append to the output, continuing the crashed batch
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// JSON lines output of the previous batch, the differences from its fingerprints are reported as the failure
+    #[arg(long)]
+    expect: Option<String>,
+
+    /// JSON lines file of the failed items with their errors, for the later reprocessing
+    #[arg(long)]
+    dead_letter: Option<String>,
+
+    /// Items with no id get the one derived from their transaction and salt (BLAKE3 of the canonical JSON)
+    #[arg(long)]
+    derive_item_ids: bool,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum BatchFormat {
+    /// JSON lines of the compact fingerprints and the errors
+    Json,
+    /// Length-delimited fingerprint records, failed items are reported to the standard error
+    Records,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum ReportFormat {
+    Csv,
+    Json,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    let mut rng = OsRng;
 
-    let random_secret = Fr::random(&mut rng);
+    match args.command {
+        Command::GenerateShares {
+            threshold,
+            agents,
+            entropy_device,
+        } => generate_shares(threshold, agents, entropy_device),
+        Command::GenerateRegionalShares {
+            threshold,
+            regions,
+            entropy_device,
+        } => generate_regional_shares(threshold, &regions, entropy_device),
+        Command::GeneratePeerKey => generate_peer_key(),
+        Command::GenerateSigningKey => generate_signing_key(),
+        Command::ProcessOfflineRequests { config } => process_offline_requests(&config),
+        Command::RelaySigner { secret_shard_file } => relay_signer(&secret_shard_file),
+        Command::Explain {
+            bic,
+            amount,
+            currency,
+            counter_amount,
+            counter_currency,
+            date_time,
+            wwd,
+            salt,
+            namespace,
+            secret,
+            share,
+        } => {
+            let (amount_base, amount_atto) = parse_amount(&amount)?;
+            let counter_amount = match (counter_amount, counter_currency) {
+                (Some(amount), Some(currency)) => {
+                    let (amount_base, amount_atto) = parse_amount(&amount)?;
+                    Some(Money {
+                        amount_base,
+                        amount_atto,
+                        currency,
+                    })
+                }
+                _ => None,
+            };
+            let tx = RawTransactionBuilder::default()
+                .bic(bic)
+                .amount(Money {
+                    amount_base,
+                    amount_atto,
+                    currency,
+                })
+                .counter_amount(counter_amount)
+                .date_time(date_time)
+                .wwd(wwd.unwrap_or(date_time.date_naive()))
+                .build()?;
+            let secret = match secret {
+                Some(secret) => ExplainSecret::Naive(Compact::unwrap(&secret)?),
+                None => ExplainSecret::Shares(
+                    share
+                        .iter()
+                        .map(|share| wire::decode_share(share))
+                        .collect::<Result<_>>()?,
+                ),
+            };
+
+            let namespace = namespace.as_deref().map(Namespace::new).transpose()?;
+
+            explain(tx, salt, namespace, &secret)
+        }
+        Command::Conformance { vectors, report } => conformance(&vectors, report.as_deref()),
+        Command::Diff {
+            transaction,
+            from,
+            to,
+        } => diff(&transaction, &from, &to),
+        Command::Replay {
+            recording,
+            endpoint,
+        } => replay(&recording, endpoint),
+        Command::Batch(args) => batch(args),
+        Command::NearMiss { input, format } => near_miss(&input, format),
+        Command::Usage {
+            endpoint,
+            api_key,
+            month,
+            format,
+        } => usage(endpoint, &api_key, month, format),
+        Command::Report {
+            capacity_log,
+            format,
+        } => report(&capacity_log, format),
+        Command::VerifyRecords { records, root } => verify_records(&records, root.as_deref()),
+        Command::Selftest { batch_size } => selftest(batch_size),
+        Command::Config(ConfigCommand::Init { mode }) => {
+            print!(
+                "{}",
+                match mode {
+                    ConfigMode::Cooperative => config::COOPERATIVE_TEMPLATE,
+                    ConfigMode::Naive => config::NAIVE_TEMPLATE,
+                    ConfigMode::Embedded => config::EMBEDDED_TEMPLATE,
+                    ConfigMode::Light => config::LIGHT_AGENT_TEMPLATE,
+                }
+            );
+            Ok(())
+        }
+        Command::Config(ConfigCommand::Schema { kind }) => {
+            let keys = match kind {
+                ConfigKind::Agent => config::agent_schema()?,
+                ConfigKind::LightAgent => config::light_agent_schema()?,
+            };
+            print!("{}", schema::render(&keys));
+            Ok(())
+        }
+    }
+}
+
+/// Health checked OS RNG, or the DRBG seeded from the hardware RNG `device`
+fn entropy_source(device: Option<String>) -> Result<Arc<dyn EntropySource>> {
+    let config = match device {
+        Some(device) => EntropyConfig {
+            source: EntropySourceKind::Device,
+            device: Some(device),
+            drbg: true,
+            reseed_interval: None,
+        },
+        None => EntropyConfig::default(),
+    };
+
+    config.source()
+}
+
+fn generate_shares(threshold: usize, agents: usize, entropy_device: Option<String>) -> Result<()> {
+    let entropy = entropy_source(entropy_device)?;
+    let mut rng = EntropyRng::new(entropy.as_ref());
+
+    let random_secret = entropy::random_scalar(entropy.as_ref())?;
 
-    let secret_sharing = SecretSharing::generate(random_secret, args.threshold, args.agents);
+    let secret_sharing =
+        SecretSharing::generate_with_rng(random_secret, threshold, agents, &mut rng);
+    if let Some(e) = rng.failure() {
+        return Err(e);
+    }
 
     let shares_set = secret_sharing.get_shares();
 
@@ -38,3 +497,619 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+fn generate_regional_shares(
+    threshold: usize,
+    regions: &[String],
+    entropy_device: Option<String>,
+) -> Result<()> {
+    let regions = regions
+        .iter()
+        .map(|region| {
+            let (threshold, agents) = region
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Region {} should be <threshold>:<agents>", region))?;
+            let (threshold, agents) = (threshold.parse::<usize>()?, agents.parse::<usize>()?);
+            if threshold == 0 || threshold > agents {
+                return Err(anyhow!(
+                    "Region {}: threshold should be from 1 up to the agents",
+                    region
+                ));
+            }
+            Ok((threshold, agents))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if threshold == 0 || threshold > regions.len() {
+        return Err(anyhow!(
+            "Threshold {} should be from 1 up to the {} regions",
+            threshold,
+            regions.len()
+        ));
+    }
+
+    let entropy = entropy_source(entropy_device)?;
+    let mut rng = EntropyRng::new(entropy.as_ref());
+    let random_secret = entropy::random_scalar(entropy.as_ref())?;
+    let secret_sharing =
+        SecretSharing::generate_with_rng(random_secret, threshold, regions.len(), &mut rng);
+    let regionals = (1..)
+        .zip(regions)
+        .map(|(region, (threshold, agents))| {
+            secret_sharing
+                .reshare_with_rng(region, threshold, agents, &mut rng)
+                .ok_or_else(|| anyhow!("No share of region {}", region))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(e) = rng.failure() {
+        return Err(e);
+    }
+
+    println!("Random secret: {}", random_secret.compact());
+    println!("Shares:");
+    for (region, regional) in (1..).zip(regionals) {
+        let mut shares = regional.get_shares().iter().collect::<Vec<_>>();
+        shares.sort_by_key(|(agent, _)| **agent);
+        for (agent, secret) in shares {
+            println!("== region {} share {}: {}", region, agent, secret.compact());
+        }
+    }
+
+    Ok(())
+}
+
+fn generate_peer_key() -> Result<()> {
+    let keypair = Keypair::generate_ed25519();
+    let secret = Bytes::copy_from_slice(keypair.clone().try_into_ed25519()?.secret().as_ref());
+
+    println!("Key: {}", secret.compact());
+    println!("Peer id: {}", keypair.public().to_peer_id());
+
+    Ok(())
+}
+
+fn generate_signing_key() -> Result<()> {
+    let signing_key = SigningKey::generate(&mut OsRng);
+
+    println!(
+        "Key: {}",
+        Bytes::copy_from_slice(signing_key.as_bytes()).compact()
+    );
+    println!(
+        "Verifying key: {}",
+        Bytes::copy_from_slice(signing_key.verifying_key().as_bytes()).compact()
+    );
+
+    Ok(())
+}
+
+fn process_offline_requests(config: &str) -> Result<()> {
+    let conf: OfflineAgentConfig = HoconLoader::new().load_file(config)?.resolve()?;
+
+    let coordinators = conf
+        .coordinators
+        .iter()
+        .map(|key| fingerprinting_offline_agent::verifying_key(key))
+        .collect::<Result<Vec<_>>>()?;
+    let agent = OfflineAgent::new(
+        conf.agent_id,
+        Compact::unwrap(&conf.secret_shard)?,
+        fingerprinting_offline_agent::signing_key(&conf.signing_key)?,
+        coordinators,
+    );
+
+    let processed = agent.process_directory(
+        Path::new(&conf.requests),
+        Path::new(&conf.responses),
+        Utc::now(),
+    )?;
+    println!("Processed {} requests", processed);
+
+    Ok(())
+}
+
+fn relay_signer(secret_shard_file: &str) -> Result<()> {
+    let shard: Fr = Compact::unwrap(&hardening::read_secret_file(Path::new(secret_shard_file))?)?;
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(relay::serve_signer(
+        shard,
+        tokio::io::stdin(),
+        tokio::io::stdout(),
+    ))
+}
+
+fn explain(
+    tx: RawTransaction,
+    salt: Option<String>,
+    namespace: Option<Namespace>,
+    secret: &ExplainSecret,
+) -> Result<()> {
+    println!("Transaction: {:?}", tx);
+
+    let tx_data: TransactionFingerprintData<Fr> = tx.try_into()?;
+    let mut tx_data = tx_data.with_namespace(namespace);
+    if let Some(salt) = salt {
+        tx_data = tx_data.with_salt(Bytes::from(salt))?;
+    }
+    let explanation = tx_data.explain(secret)?;
+
+    println!("Components:");
+    println!("  bic:      {}", hex::encode(&explanation.bic));
+    println!("  amount:   {}", hex::encode(&explanation.amount));
+    println!("  currency: {}", hex::encode(&explanation.currency));
+    if let Some(counter_amount) = &explanation.counter_amount {
+        println!("  counter amount: {}", hex::encode(counter_amount));
+    }
+
+    let [seconds, days, nonce] = explanation.date_time_inputs;
+    println!("Date time:");
+    println!("  seconds since epoch: {}", scalar(&seconds));
+    println!("  days since epoch:    {}", scalar(&days));
+    println!("  nonce:               {}", scalar(&nonce));
+    println!(
+        "  scalar:              {}",
+        scalar(&explanation.date_time_scalar)
+    );
+    println!("  curve point:         {}", point(&explanation.curve_point));
+
+    for (agent, partial) in explanation.partial_shares.iter() {
+        println!("  partial share {}:     {}", agent, point(partial));
+    }
+    println!(
+        "  evaluated point:     {}",
+        point(&explanation.evaluated_point)
+    );
+    println!(
+        "  fingerprint:         {}",
+        scalar(&explanation.date_time_fingerprint)
+    );
+
+    println!(
+        "Preimage (wire version {}): {}",
+        explanation.wire_version.as_u8(),
+        hex::encode(&explanation.preimage)
+    );
+    println!(
+        "Unsalted fingerprint: {}",
+        scalar(&explanation.unsalted_fingerprint)
+    );
+    println!("Fingerprint: {}", scalar(&explanation.fingerprint));
+
+    Ok(())
+}
+
+// Compacted value along with the big-endian hex
+fn scalar(value: &Fr) -> String {
+    format!("{} ({:?})", value.compact(), value)
+}
+
+// Compressed point hex
+fn point(value: &G1) -> String {
+    hex::encode(wire::encode_point(value))
+}
+
+fn conformance(vectors: &str, report: Option<&str>) -> Result<()> {
+    let suites = conformance::run_directory(Path::new(vectors))?;
+
+    match report {
+        Some(report) => conformance::write_junit(&suites, &mut File::create(report)?)?,
+        None => conformance::write_junit(&suites, &mut std::io::stdout().lock())?,
+    }
+
+    let tests: usize = suites.iter().map(|suite| suite.cases.len()).sum();
+    let failed: usize = suites
+        .iter()
+        .map(|suite| suite.failures() + suite.errors())
+        .sum();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} vectors failed", failed, tests));
+    }
+    eprintln!("All {} vectors passed", tests);
+
+    Ok(())
+}
+
+fn diff(transaction: &str, from: &str, to: &str) -> Result<()> {
+    let transaction: VectorTransaction = serde_json::from_reader(File::open(transaction)?)?;
+    let from: SchemaVersion = serde_json::from_reader(File::open(from)?)?;
+    let to: SchemaVersion = serde_json::from_reader(File::open(to)?)?;
+
+    let diff = migration::diff_versions(&transaction, &from, &to)?;
+    migration::write_report(&diff, &from, &to, &mut std::io::stdout().lock())
+}
+
+fn replay(recording: &str, endpoint: SocketAddr) -> Result<()> {
+    let exchanges = fingerprinting_grpc::read_recording(Path::new(recording))?;
+    let client = FingerprintServiceClientBuilder::new("fingerprinting-cli-replay")
+        .address(endpoint)
+        .build();
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    let info = runtime.block_on(client.get_service_info(GetServiceInfoRequest::default()))?;
+    fingerprinting_grpc::check_service_parameters(info.get_ref())?;
+
+    let results = runtime.block_on(replay::replay(exchanges, |transaction_data| {
+        let client = client.clone();
+        async move {
+            let response = client
+                .compute_single_fingerprint(ComputeSingleFingerprintRequest {
+                    transaction_data: Some(transaction_data),
+                    salt: Default::default(),
+                    with_commitments: false,
+                    encodings: Default::default(),
+                    validate_only: false,
+                    _unknown_fields: Default::default(),
+                })
+                .await?;
+
+            Ok(response.into_inner().fingerprint.unwrap_or_default())
+        }
+    }));
+
+    let mut failed = 0;
+    for result in &results {
+        match &result.outcome {
+            ReplayOutcome::Matched => {}
+            ReplayOutcome::Mismatched { recorded, replayed } => {
+                failed += 1;
+                println!(
+                    "#{}: recorded {}, replayed {}",
+                    result.index, recorded, replayed
+                );
+            }
+            ReplayOutcome::Error(e) => {
+                failed += 1;
+                println!("#{}: {}", result.index, e);
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("{} of {} exchanges differ", failed, results.len()));
+    }
+    eprintln!("All {} exchanges matched", results.len());
+
+    Ok(())
+}
+
+fn batch(args: BatchArgs) -> Result<()> {
+    let resume = args.resume;
+    // Progress of the file is known from its size, the standard input is of the unknown size
+    let (input, total): (Box<dyn Read>, _) = match args.input.as_str() {
+        "-" => (Box::new(std::io::stdin().lock()), None),
+        path => {
+            let file = File::open(path)?;
+            let size = file.metadata()?.len();
+            (Box::new(file), Some(size))
+        }
+    };
+    let input = batch::CountingReader::new(input);
+    let mut progress = batch::Progress::new(input.counter(), total);
+    let output: Box<dyn Write> = match args.output.as_deref() {
+        None | Some("-") => Box::new(std::io::stdout().lock()),
+        Some(path) => Box::new(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resume)
+                .truncate(!resume)
+                .open(path)?,
+        ),
+    };
+    let mut output = BufWriter::new(output);
+    // Failed items are never checkpointed, the resumed batch retries all of them and writes the dead letters anew
+    let mut dead_letter = args
+        .dead_letter
+        .as_ref()
+        .map(|path| File::create(path).map(BufWriter::new))
+        .transpose()?;
+
+    let completed = match (&args.checkpoint, resume) {
+        (Some(checkpoint), true) => batch::read_checkpoint(Path::new(checkpoint))?,
+        _ => HashSet::new(),
+    };
+    let mut checkpoint = args
+        .checkpoint
+        .as_ref()
+        .map(|checkpoint| batch::CheckpointWriter::open(Path::new(checkpoint), resume))
+        .transpose()?;
+
+    let mut expected = args
+        .expect
+        .as_ref()
+        .map(|expect| batch::ExpectedFingerprints::read(BufReader::new(File::open(expect)?)))
+        .transpose()?;
+    let mut differences = 0;
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let mut clients = vec![];
+    for endpoint in &args.endpoints {
+        let client = FingerprintServiceClientBuilder::new("fingerprinting-cli-batch")
+            .address(*endpoint)
+            .build();
+        // Every instance should compute the same fingerprints
+        let info = runtime.block_on(client.get_service_info(GetServiceInfoRequest::default()))?;
+        fingerprinting_grpc::check_service_parameters(info.get_ref())
+            .map_err(|e| anyhow!("Endpoint {}: {}", endpoint, e))?;
+        clients.push(client);
+    }
+    let endpoints = batch::Endpoints::new(clients, args.rate_limit)?;
+
+    let summary = runtime.block_on(batch::run_batch(
+        BufReader::new(input),
+        args.concurrency.get(),
+        &completed,
+        args.derive_item_ids,
+        |transaction_data, salt| {
+            let endpoints = &endpoints;
+            let api_key = args.api_key.as_deref();
+            async move {
+                let request = keyed(
+                    ComputeSingleFingerprintRequest {
+                        transaction_data: Some(transaction_data),
+                        salt,
+                        with_commitments: false,
+                        encodings: Default::default(),
+                        validate_only: false,
+                        _unknown_fields: Default::default(),
+                    },
+                    api_key,
+                )?;
+                let response = endpoints
+                    .acquire()
+                    .await
+                    .compute_single_fingerprint(request)
+                    .await?;
+
+                Ok(response.into_inner().fingerprint.unwrap_or_default())
+            }
+        },
+        |result, summary| {
+            match (&args.format, &result.fingerprint) {
+                (BatchFormat::Json, _) => batch::write_json(&result, &mut output)?,
+                (BatchFormat::Records, Ok(fingerprint)) => {
+                    FingerprintRecordWriter::new(&mut output).write(
+                        &fingerprinting_grpc::fingerprint_record(fingerprint, Utc::now())?,
+                    )?
+                }
+                (BatchFormat::Records, Err(e)) => eprintln!("#{}: {}", result.line, e),
+            }
+            if let Some(dead_letter) = dead_letter.as_mut() {
+                batch::write_dead_letter(&result, dead_letter)?;
+            }
+            if let Some(difference) = expected
+                .as_mut()
+                .and_then(|expected| expected.check(&result))
+            {
+                differences += 1;
+                eprintln!("#{} {}: {}", result.line, result.key(), difference);
+            }
+            // Failed items are retried by the resumed batch
+            if let (Some(checkpoint), Ok(_)) = (checkpoint.as_mut(), &result.fingerprint) {
+                checkpoint.record(&result)?;
+            }
+
+            if progress.due() {
+                output.flush()?;
+                if let Some(dead_letter) = dead_letter.as_mut() {
+                    dead_letter.flush()?;
+                }
+                if let Some(checkpoint) = checkpoint.as_mut() {
+                    checkpoint.flush()?;
+                }
+                eprintln!("{}", progress.describe(summary));
+            }
+            Ok(())
+        },
+    ))?;
+    output.flush()?;
+    if let Some(dead_letter) = dead_letter.as_mut() {
+        dead_letter.flush()?;
+    }
+    if let Some(checkpoint) = checkpoint.as_mut() {
+        checkpoint.flush()?;
+    }
+
+    if let Some(expected) = &expected {
+        let missing = expected.missing(&completed);
+        for key in &missing {
+            eprintln!("{}: Expected item is not in the batch", key);
+        }
+        if differences + missing.len() > 0 {
+            return Err(anyhow!(
+                "{} of {} items differ from the expected fingerprints, {} expected items are missing",
+                differences,
+                summary.computed + summary.failed,
+                missing.len()
+            ));
+        }
+        eprintln!("All items match the expected fingerprints");
+    }
+    if summary.failed > 0 {
+        return Err(anyhow!(
+            "{} of {} items failed",
+            summary.failed,
+            summary.computed + summary.failed
+        ));
+    }
+    eprintln!(
+        "All {} items computed, {} skipped",
+        summary.computed, summary.skipped
+    );
+
+    Ok(())
+}
+
+/// Request presenting the API key when given, see `UsageAccounting`
+fn keyed<T>(message: T, api_key: Option<&str>) -> Result<volo_grpc::Request<T>> {
+    let mut request = volo_grpc::Request::new(message);
+    if let Some(api_key) = api_key {
+        request.metadata_mut().insert(API_KEY, api_key.parse()?);
+    }
+
+    Ok(request)
+}
+
+fn usage(
+    endpoint: SocketAddr,
+    api_key: &str,
+    month: Option<String>,
+    format: ReportFormat,
+) -> Result<()> {
+    let client = FingerprintServiceClientBuilder::new("fingerprinting-cli-usage")
+        .address(endpoint)
+        .build();
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    let request = GetUsageRequest {
+        month: month.unwrap_or_default().into(),
+        ..Default::default()
+    };
+    let usage = runtime
+        .block_on(client.get_usage(keyed(request, Some(api_key))?))?
+        .into_inner();
+
+    match format {
+        ReportFormat::Csv => {
+            println!("name,month,fingerprints,agent_rounds,monthly_quota");
+            for key in &usage.keys {
+                println!(
+                    "{},{},{},{},{}",
+                    key.name, usage.month, key.fingerprints, key.agent_rounds, key.monthly_quota
+                );
+            }
+        }
+        ReportFormat::Json => {
+            let keys: Vec<_> = usage
+                .keys
+                .iter()
+                .map(|key| {
+                    serde_json::json!({
+                        "name": key.name.as_str(),
+                        "month": usage.month.as_str(),
+                        "fingerprints": key.fingerprints,
+                        "agent_rounds": key.agent_rounds,
+                        "monthly_quota": key.monthly_quota,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&keys)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn near_miss(input: &str, format: ReportFormat) -> Result<()> {
+    let records = near_miss::read_records(BufReader::new(File::open(input)?))?;
+    let report = near_miss::near_miss_report(&records);
+
+    match format {
+        ReportFormat::Csv => near_miss::write_csv(&report, &mut std::io::stdout().lock())?,
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+
+    Ok(())
+}
+
+fn report(capacity_log: &str, format: ReportFormat) -> Result<()> {
+    let samples = capacity::read_samples(BufReader::new(File::open(capacity_log)?))?;
+    let report = capacity::capacity_report(&samples);
+
+    match format {
+        ReportFormat::Csv => capacity::write_csv(&report, &mut std::io::stdout().lock())?,
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+
+    Ok(())
+}
+
+fn selftest(batch_size: usize) -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let checks = runtime.block_on(selftest::run_selftest(batch_size))?;
+
+    for check in &checks {
+        match &check.result {
+            Ok(details) => println!("PASS {} ({})", check.name, details),
+            Err(e) => println!("FAIL {}: {}", check.name, e),
+        }
+    }
+
+    let failed = checks.iter().filter(|check| !check.passed()).count();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} checks failed", failed, checks.len()));
+    }
+
+    Ok(())
+}
+
+fn verify_records(records: &str, root: Option<&str>) -> Result<()> {
+    let records = fingerprinting_grpc::read_records(Path::new(records))?;
+
+    let mut invalid = 0;
+    let mut fingerprints = BTreeMap::new();
+    let mut namespaces = BTreeMap::<(u64, String), usize>::new();
+    for (index, record) in records.iter().enumerate() {
+        let fingerprint = match fingerprinting_grpc::check_record(record) {
+            Ok(fingerprint) => fingerprint,
+            Err(e) => {
+                invalid += 1;
+                println!("#{}: {}", index, e);
+                continue;
+            }
+        };
+        let computed_at = record
+            .computed_at
+            .as_ref()
+            .and_then(|timestamp| {
+                DateTime::from_timestamp(timestamp.seconds as i64, timestamp.nanos)
+            })
+            .unwrap_or_default();
+
+        // Archive commits to the distinct fingerprints of every key epoch
+        fingerprints
+            .entry((record.key_epoch, wire::encode_scalar(&fingerprint)))
+            .or_insert(StoredFingerprint {
+                fingerprint,
+                key_epoch: record.key_epoch,
+                first_seen: computed_at,
+                last_seen: computed_at,
+                occurrences: 1,
+                window_start: computed_at,
+            });
+        *namespaces
+            .entry((record.key_epoch, record.namespace.to_string()))
+            .or_default() += 1;
+    }
+
+    for ((key_epoch, namespace), count) in &namespaces {
+        eprintln!(
+            "Key epoch {}, namespace {:?}: {} records",
+            key_epoch, namespace, count
+        );
+    }
+    if invalid > 0 {
+        return Err(anyhow!(
+            "{} of {} records are invalid",
+            invalid,
+            records.len()
+        ));
+    }
+
+    if let Some(root) = root {
+        let fingerprints = fingerprints.into_values().collect::<Vec<_>>();
+        let computed = hex::encode(archive_root(&fingerprints));
+        if computed != root.to_lowercase() {
+            return Err(anyhow!(
+                "Merkle root {} of {} distinct fingerprints differs from the archived {}",
+                computed,
+                fingerprints.len(),
+                root
+            ));
+        }
+        eprintln!("Merkle root matches the archived epoch");
+    }
+    eprintln!("All {} records are valid", records.len());
+
+    Ok(())
+}
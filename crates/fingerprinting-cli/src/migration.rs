@@ -0,0 +1,218 @@
+//! Fingerprint diff for the schema migrations: the same transaction fingerprinted by two schema versions
+//!
+//! The version file declares the optional components of the transaction the version fingerprints and its
+//! parameters: `{"name": "v2", "components": ["merchant", "country"], "namespace": "prod", "secret": "..."}`.
+//! Optional components the transaction carries but the version does not list are dropped. The version with the
+//! `schema_id` binds the components by the fingerprint schema of the id, in the listed order, the one with the
+//! `wire_version` lays the preimage out by the version, the one with the `epoch` offsets the date components from it.
+//! The secret is compacted, the test secret of the environment rather than the production one.
+
+use crate::conformance::VectorTransaction;
+use anyhow::Error;
+use fingerprinting_core::diff::{self, FingerprintDiff};
+use fingerprinting_core::explain::{ExplainSecret, Explanation};
+use fingerprinting_core::namespace::Namespace;
+use fingerprinting_core::schema::{FingerprintSchema, SchemaComponent};
+use fingerprinting_core::wire::WireVersion;
+use fingerprinting_core::{Compact, TransactionFingerprintData};
+use fingerprinting_types::RawTransaction;
+use halo2_axiom::halo2curves::bn256::Fr;
+use serde_derive::Deserialize;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Optional component of the transaction
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OptionalComponent {
+    CounterAmount,
+    Series,
+    Merchant,
+    Country,
+    Reference,
+    Mcc,
+    Counterparty,
+}
+
+impl From<OptionalComponent> for SchemaComponent {
+    fn from(component: OptionalComponent) -> Self {
+        match component {
+            OptionalComponent::CounterAmount => SchemaComponent::CounterAmount,
+            OptionalComponent::Series => SchemaComponent::Series,
+            OptionalComponent::Merchant => SchemaComponent::Merchant,
+            OptionalComponent::Country => SchemaComponent::Country,
+            OptionalComponent::Reference => SchemaComponent::Reference,
+            OptionalComponent::Mcc => SchemaComponent::Mcc,
+            OptionalComponent::Counterparty => SchemaComponent::Counterparty,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SchemaVersion {
+    pub name: String,
+    /// Optional components fingerprinted by the version, none by default
+    #[serde(default)]
+    pub components: Vec<OptionalComponent>,
+    /// Fingerprint schema binding the `components`, the built-in layout when absent
+    pub schema_id: Option<u32>,
+    /// Layout of the preimage, `WireVersion::CURRENT` when absent
+    pub wire_version: Option<u8>,
+    /// Date the date components are offset from, e.g. "2023-01-01", `Epoch::default()` when absent
+    pub epoch: Option<String>,
+    pub namespace: Option<String>,
+    /// UTF-8 salt
+    pub salt: Option<String>,
+    pub secret: String,
+}
+
+impl SchemaVersion {
+    /// Transaction as fingerprinted by the version
+    pub fn transaction(&self, tx: &RawTransaction) -> RawTransaction {
+        let included = |component| self.components.contains(&component);
+
+        RawTransaction {
+            counter_amount: tx
+                .counter_amount
+                .clone()
+                .filter(|_| included(OptionalComponent::CounterAmount)),
+            series_id: tx
+                .series_id
+                .clone()
+                .filter(|_| included(OptionalComponent::Series)),
+            merchant_id: tx
+                .merchant_id
+                .clone()
+                .filter(|_| included(OptionalComponent::Merchant)),
+            country_code: tx
+                .country_code
+                .clone()
+                .filter(|_| included(OptionalComponent::Country)),
+            transaction_reference: tx
+                .transaction_reference
+                .clone()
+                .filter(|_| included(OptionalComponent::Reference)),
+            mcc: tx.mcc.clone().filter(|_| included(OptionalComponent::Mcc)),
+            counterparty: tx
+                .counterparty
+                .clone()
+                .filter(|_| included(OptionalComponent::Counterparty)),
+            ..tx.clone()
+        }
+    }
+
+    pub fn explain(&self, tx: &RawTransaction) -> Result<Explanation, Error> {
+        let tx_data: TransactionFingerprintData<Fr> = self.transaction(tx).try_into()?;
+        let schema = self
+            .schema_id
+            .map(|schema_id| {
+                let components = self.components.iter().map(|c| (*c).into()).collect();
+                FingerprintSchema::new(schema_id, components)
+            })
+            .transpose()?;
+        let mut tx_data = tx_data
+            .with_namespace(self.namespace.as_deref().map(Namespace::new).transpose()?)
+            .with_schema(schema.map(Arc::new));
+        if let Some(version) = self.wire_version {
+            tx_data = tx_data.with_wire_version(WireVersion::try_from(version)?);
+        }
+        if let Some(epoch) = &self.epoch {
+            tx_data = tx_data.with_epoch(epoch.parse()?);
+        }
+        if let Some(salt) = &self.salt {
+            tx_data = tx_data.with_salt(salt.clone().into())?;
+        }
+
+        tx_data.explain(&ExplainSecret::Naive(Compact::unwrap(&self.secret)?))
+    }
+}
+
+/// Fingerprints of the transaction by both versions and the steps they differ in
+pub fn diff_versions(
+    tx: &VectorTransaction,
+    from: &SchemaVersion,
+    to: &SchemaVersion,
+) -> Result<FingerprintDiff, Error> {
+    let tx = tx.raw_transaction()?;
+
+    Ok(diff::diff(&from.explain(&tx)?, &to.explain(&tx)?))
+}
+
+pub fn write_report<W: Write>(
+    diff: &FingerprintDiff,
+    from: &SchemaVersion,
+    to: &SchemaVersion,
+    out: &mut W,
+) -> Result<(), Error> {
+    writeln!(out, "{}: {}", from.name, diff.from)?;
+    writeln!(out, "{}: {}", to.name, diff.to)?;
+    if diff.is_same() {
+        writeln!(out, "Fingerprints are the same")?;
+        return Ok(());
+    }
+
+    writeln!(out, "Fingerprints differ:")?;
+    for difference in diff.differences.iter() {
+        writeln!(out, "  - {}", difference)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fingerprinting_core::diff::Difference;
+    use fingerprinting_core::{COUNTRY_DOMAIN_PREFIX, MERCHANT_DOMAIN_PREFIX};
+
+    #[test]
+    fn test_schema_migration() -> Result<(), Error> {
+        let tx: VectorTransaction = serde_json::from_str(
+            r#"{"bic": "BCEELU21", "amount": "1000.55", "currency": "EUR", "date_time": "2025-09-16T12:30:15Z",
+                "merchant_id": "MID-1", "country_code": "LU"}"#,
+        )?;
+        let version = |json: &str| serde_json::from_str::<SchemaVersion>(json);
+        let v1 =
+            version(r#"{"name": "v1", "secret": "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD"}"#)?;
+        let v2 = version(
+            r#"{"name": "v2", "components": ["merchant", "country"], "secret": "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD"}"#,
+        )?;
+
+        assert!(diff_versions(&tx, &v1, &v1)?.is_same());
+
+        let diff = diff_versions(&tx, &v1, &v2)?;
+        assert_eq!(
+            diff.differences,
+            vec![
+                Difference::Added(MERCHANT_DOMAIN_PREFIX.into()),
+                Difference::Added(COUNTRY_DOMAIN_PREFIX.into())
+            ]
+        );
+
+        let mut report = vec![];
+        write_report(&diff, &v1, &v2, &mut report)?;
+        let report = String::from_utf8(report)?;
+        assert!(report.starts_with("v1: CR4ibCtpXnxyzv2fXCBy4EGWdgDVSU8KpFQTXm43mXNn\n"));
+        assert!(report.contains("component CRA_FP_MERCHANT is added"));
+
+        // Same components bound by the schema
+        let v3 = version(
+            r#"{"name": "v3", "components": ["country", "merchant"], "schema_id": 3, "secret": "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD"}"#,
+        )?;
+        assert_eq!(
+            diff_versions(&tx, &v2, &v3)?.differences,
+            vec![Difference::Schema(0, 3), Difference::Order]
+        );
+
+        // Versioned preimage of the same components
+        let v4 = version(
+            r#"{"name": "v4", "components": ["country", "merchant"], "schema_id": 3, "wire_version": 2, "secret": "9tWY1NNFFLyx18YJ9wiyPc1fjW4Vu3CtnmXrsFmcHVVD"}"#,
+        )?;
+        assert_eq!(
+            diff_versions(&tx, &v3, &v4)?.differences,
+            vec![Difference::WireVersion(1, 2)]
+        );
+
+        Ok(())
+    }
+}
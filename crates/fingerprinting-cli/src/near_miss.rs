@@ -0,0 +1,134 @@
+//! Near-miss analytics: transactions sharing the fuzzy fingerprint while having different exact fingerprints
+//!
+//! Input is the JSON lines export of stored fingerprints with the accompanying fuzzy fingerprints:
+//! `{"fingerprint": "...", "fuzzy_fingerprint": "...", "bic": "...", "wwd": "2025-09-16"}`.
+//! Fingerprints are treated as opaque values, BIC could be either the original or pseudonymized one.
+
+use anyhow::{anyhow, Error};
+use chrono::NaiveDate;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{BufRead, Write};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NearMissRecord {
+    pub fingerprint: String,
+    pub fuzzy_fingerprint: String,
+    pub bic: String,
+    pub wwd: NaiveDate,
+}
+
+/// Near-duplicates of the single BIC and day
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct NearMissRow {
+    pub bic: String,
+    pub day: NaiveDate,
+
+    /// Fuzzy fingerprints with more than one exact fingerprint touching the BIC and day
+    pub groups: u64,
+
+    /// Distinct exact fingerprints of the BIC and day which have near-duplicates
+    pub transactions: u64,
+}
+
+pub fn read_records<R: BufRead>(input: R) -> Result<Vec<NearMissRecord>, Error> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(number, line)| {
+            serde_json::from_str(&line?)
+                .map_err(|e| anyhow!("Invalid record at line {}: {}", number + 1, e))
+        })
+        .collect()
+}
+
+/// Builds the report ordered by day and BIC, only BICs and days having near-duplicates are reported
+pub fn near_miss_report(records: &[NearMissRecord]) -> Vec<NearMissRow> {
+    let mut exact_by_fuzzy: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for record in records {
+        exact_by_fuzzy
+            .entry(&record.fuzzy_fingerprint)
+            .or_default()
+            .insert(&record.fingerprint);
+    }
+
+    // (fuzzy fingerprints, exact fingerprints) by day and BIC
+    type Row<'a> = (BTreeSet<&'a str>, BTreeSet<&'a str>);
+    let mut rows: BTreeMap<(NaiveDate, &str), Row> = BTreeMap::new();
+    for record in records {
+        if exact_by_fuzzy[record.fuzzy_fingerprint.as_str()].len() < 2 {
+            continue;
+        }
+
+        let (groups, transactions) = rows.entry((record.wwd, &record.bic)).or_default();
+        groups.insert(&record.fuzzy_fingerprint);
+        transactions.insert(&record.fingerprint);
+    }
+
+    rows.into_iter()
+        .map(|((day, bic), (groups, transactions))| NearMissRow {
+            bic: bic.to_string(),
+            day,
+            groups: groups.len() as u64,
+            transactions: transactions.len() as u64,
+        })
+        .collect()
+}
+
+pub fn write_csv<W: Write>(rows: &[NearMissRow], output: &mut W) -> Result<(), Error> {
+    writeln!(output, "day,bic,groups,transactions")?;
+    for row in rows {
+        writeln!(
+            output,
+            "{},{},{},{}",
+            row.day, row.bic, row.groups, row.transactions
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_near_miss_report() -> Result<(), Error> {
+        let input = r#"
+{"fingerprint": "a", "fuzzy_fingerprint": "x", "bic": "BCEELU21", "wwd": "2025-09-16"}
+{"fingerprint": "b", "fuzzy_fingerprint": "x", "bic": "BCEELU21", "wwd": "2025-09-16"}
+{"fingerprint": "b", "fuzzy_fingerprint": "x", "bic": "BCEELU21", "wwd": "2025-09-16"}
+{"fingerprint": "c", "fuzzy_fingerprint": "x", "bic": "BCEELU21", "wwd": "2025-09-17"}
+{"fingerprint": "d", "fuzzy_fingerprint": "y", "bic": "BCEELU21", "wwd": "2025-09-16"}
+{"fingerprint": "d", "fuzzy_fingerprint": "y", "bic": "BCEELU21", "wwd": "2025-09-16"}
+{"fingerprint": "e", "fuzzy_fingerprint": "z", "bic": "DEUTDEFF", "wwd": "2025-09-16"}
+"#;
+
+        let records = read_records(input.as_bytes())?;
+        let report = near_miss_report(&records);
+
+        // Exact duplicates `d` and unique `e` are not near-misses
+        assert_eq!(
+            report,
+            vec![
+                NearMissRow {
+                    bic: "BCEELU21".to_string(),
+                    day: NaiveDate::from_ymd_opt(2025, 9, 16).unwrap(),
+                    groups: 1,
+                    transactions: 2,
+                },
+                NearMissRow {
+                    bic: "BCEELU21".to_string(),
+                    day: NaiveDate::from_ymd_opt(2025, 9, 17).unwrap(),
+                    groups: 1,
+                    transactions: 1,
+                },
+            ]
+        );
+
+        assert!(read_records(r#"{"fingerprint": "a"}"#.as_bytes()).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+//! Replay of the recorded exchanges against another instance, diffing the fingerprints
+//!
+//! Recordings are written by the instances with `recording` configured, see `FingerprintRecorder`.
+//! Upgraded instance (or the instance with the reshared secret) is expected to match every recorded fingerprint,
+//! the instance with the rotated secret is expected to mismatch all of them.
+
+use anyhow::Error;
+use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+    Fingerprint as FingerprintDto, RecordedExchange, TransactionFingerprintData,
+};
+use futures::StreamExt;
+use std::future::Future;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayOutcome {
+    Matched,
+    /// Replayed fingerprint differs from the recorded one
+    Mismatched {
+        recorded: String,
+        replayed: String,
+    },
+    /// Exchange could not be replayed
+    Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayResult {
+    /// Position of the exchange in the recording
+    pub index: usize,
+    pub outcome: ReplayOutcome,
+}
+
+/// Re-sends the recorded transactions via `send` with the bounded concurrency, results keep the recording order
+pub async fn replay<S, F>(exchanges: Vec<RecordedExchange>, send: S) -> Vec<ReplayResult>
+where
+    S: Fn(TransactionFingerprintData) -> F,
+    F: Future<Output = Result<FingerprintDto, Error>>,
+{
+    futures::stream::iter(exchanges.into_iter().enumerate())
+        .map(|(index, exchange)| {
+            let replayed = exchange.transaction_data.clone().map(&send);
+            async move {
+                let outcome = match replayed {
+                    Some(replayed) => compare(exchange.fingerprint.as_ref(), replayed.await),
+                    None => ReplayOutcome::Error("Transaction data is not recorded".to_string()),
+                };
+
+                ReplayResult { index, outcome }
+            }
+        })
+        .buffered(16)
+        .collect()
+        .await
+}
+
+fn compare(
+    recorded: Option<&FingerprintDto>,
+    replayed: Result<FingerprintDto, Error>,
+) -> ReplayOutcome {
+    let (recorded, replayed) = match (recorded, replayed) {
+        (Some(recorded), Ok(replayed)) => (recorded, replayed),
+        (None, _) => return ReplayOutcome::Error("Fingerprint is not recorded".to_string()),
+        (_, Err(e)) => return ReplayOutcome::Error(e.to_string()),
+    };
+
+    if recorded.fingerprint == replayed.fingerprint {
+        return ReplayOutcome::Matched;
+    }
+
+    // Namespace is the usual reason of the mismatch between the environments
+    let describe = |fingerprint: &FingerprintDto| match fingerprint.namespace.is_empty() {
+        true => fingerprint.compact_fingerprint.to_string(),
+        false => format!(
+            "{} (namespace {})",
+            fingerprint.compact_fingerprint, fingerprint.namespace
+        ),
+    };
+
+    ReplayOutcome::Mismatched {
+        recorded: describe(recorded),
+        replayed: describe(&replayed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Utc};
+    use fingerprinting_core::NaiveProtocol;
+    use fingerprinting_grpc::net::outbe::common::v1::{Currency, Date, Money, Timestamp};
+    use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+        ComputeSingleFingerprintRequest, FingerprintService as _,
+    };
+    use fingerprinting_grpc::{read_recording, FingerprintRecorder, FingerprintService};
+    use halo2_axiom::halo2curves::bn256::Fr;
+    use std::sync::Arc;
+    use volo_grpc::Request;
+
+    fn transaction_data(units: u64) -> TransactionFingerprintData {
+        let tx_date = Utc::now();
+
+        TransactionFingerprintData {
+            bic: "BCEELU21".into(),
+            amount: Some(Money {
+                currency: Currency::CURRENCY_EUR,
+                units,
+                atto: 0,
+                _unknown_fields: Default::default(),
+            }),
+            counter_amount: None,
+            date_time: Some(Timestamp {
+                seconds: tx_date.timestamp() as u64,
+                nanos: 0,
+                _unknown_fields: Default::default(),
+            }),
+            wwd: Some(Date {
+                year: tx_date.year() as u32,
+                month: tx_date.month(),
+                day: tx_date.day(),
+                _unknown_fields: Default::default(),
+            }),
+            series_id: Default::default(),
+            merchant_id: Default::default(),
+            country_code: Default::default(),
+            transaction_reference: Default::default(),
+            mcc: Default::default(),
+            counterparty: None,
+            residency: Default::default(),
+            _unknown_fields: Default::default(),
+        }
+    }
+
+    fn request(units: u64, salt: &'static str) -> Request<ComputeSingleFingerprintRequest> {
+        Request::new(ComputeSingleFingerprintRequest {
+            transaction_data: Some(transaction_data(units)),
+            salt: salt.into(),
+            with_commitments: false,
+            encodings: Default::default(),
+            validate_only: false,
+            _unknown_fields: Default::default(),
+        })
+    }
+
+    async fn replay_against(
+        exchanges: Vec<RecordedExchange>,
+        secret: u64,
+    ) -> Result<Vec<ReplayResult>, Error> {
+        let service = Arc::new(FingerprintService::new(NaiveProtocol::new(Fr::from(
+            secret,
+        ))));
+
+        Ok(replay(exchanges, |transaction_data| {
+            let service = service.clone();
+            async move {
+                let response = service
+                    .compute_single_fingerprint(Request::new(ComputeSingleFingerprintRequest {
+                        transaction_data: Some(transaction_data),
+                        salt: Default::default(),
+                        with_commitments: false,
+                        encodings: Default::default(),
+                        validate_only: false,
+                        _unknown_fields: Default::default(),
+                    }))
+                    .await?;
+
+                Ok(response.into_inner().fingerprint.unwrap_or_default())
+            }
+        })
+        .await)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_record_and_replay() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("fingerprints-{}.rec", std::process::id()));
+        let recorder = Arc::new(FingerprintRecorder::open(&path)?);
+        let service =
+            FingerprintService::new(NaiveProtocol::new(Fr::from(42))).with_recorder(Some(recorder));
+
+        service
+            .compute_single_fingerprint(request(1000, ""))
+            .await?;
+        service
+            .compute_single_fingerprint(request(2000, ""))
+            .await?;
+        // Salted exchange is not recorded
+        service
+            .compute_single_fingerprint(request(3000, "salt"))
+            .await?;
+
+        let exchanges = read_recording(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(exchanges.len(), 2);
+
+        let upgraded = replay_against(exchanges.clone(), 42).await?;
+        assert!(upgraded
+            .iter()
+            .all(|result| result.outcome == ReplayOutcome::Matched));
+        assert_eq!(upgraded[1].index, 1);
+
+        let rotated = replay_against(exchanges, 7).await?;
+        assert!(rotated
+            .iter()
+            .all(|result| matches!(result.outcome, ReplayOutcome::Mismatched { .. })));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! JSON over HTTP endpoint of the fingerprint service, for the callers without the gRPC stack, e.g. the
+//! institutions piloting the single node agent
+//!
+//! `POST /v1/fingerprints` takes the batch item `{"item_id": "...", "transaction": {...}, "salt": "..."}`, see
+//! `batch::BatchItem`, and returns `{"item_id": "...", "fingerprint": "...", "namespace": "...", "duplicate": "new"}`.
+//! The duplicate status is present with the fingerprint store only. Failures are `{"error": "..."}` with the HTTP
+//! status of the gRPC code. The `x-api-key` header is passed to the service as the metadata of the same name, see
+//! `UsageAccounting`.
+
+use crate::batch::BatchItem;
+use anyhow::Error;
+use bytes::Bytes;
+use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+    ComputeSingleFingerprintRequest, DuplicateStatus, FingerprintService,
+};
+use fingerprinting_grpc::API_KEY;
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_derive::Serialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use volo_grpc::{Code, Status};
+
+/// Path of the single fingerprint computation
+pub const FINGERPRINTS_PATH: &str = "/v1/fingerprints";
+
+/// Largest request body accepted, the transaction is well below it
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Serialize)]
+struct JsonFingerprint<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item_id: Option<&'a str>,
+    fingerprint: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    namespace: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    error: &'a str,
+}
+
+/// Serves the endpoint on the bound `listener` until it fails
+pub async fn serve<S>(listener: TcpListener, service: Arc<S>) -> Result<(), Error>
+where
+    S: FingerprintService + Send + Sync + 'static,
+{
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let service = service.clone();
+
+        tokio::spawn(async move {
+            let handler = service_fn(move |request| {
+                let service = service.clone();
+                async move { Ok::<_, Infallible>(handle(service.as_ref(), request).await) }
+            });
+
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), handler)
+                .await
+            {
+                log::debug!("REST connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle<S: FingerprintService>(
+    service: &S,
+    request: hyper::Request<Incoming>,
+) -> hyper::Response<Full<Bytes>> {
+    let (parts, body) = request.into_parts();
+    let api_key = parts
+        .headers
+        .get(API_KEY)
+        .and_then(|api_key| api_key.to_str().ok());
+    let (status, body) = match Limited::new(body, MAX_BODY_SIZE).collect().await {
+        Ok(body) => {
+            respond(
+                service,
+                &parts.method,
+                parts.uri.path(),
+                api_key,
+                &body.to_bytes(),
+            )
+            .await
+        }
+        Err(_) => error(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large"),
+    };
+
+    hyper::Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .expect("Response parts are valid")
+}
+
+/// Status and JSON body of the response to the request of the caller presenting the `api_key`
+pub async fn respond<S: FingerprintService>(
+    service: &S,
+    method: &Method,
+    path: &str,
+    api_key: Option<&str>,
+    body: &[u8],
+) -> (StatusCode, String) {
+    if path != FINGERPRINTS_PATH {
+        return error(StatusCode::NOT_FOUND, &format!("{} is not found", path));
+    }
+    if method != Method::POST {
+        return error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            &format!("{} accepts POST only", path),
+        );
+    }
+
+    let item = match serde_json::from_slice::<BatchItem>(body) {
+        Ok(item) => item,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let transaction_data = match item.transaction_data() {
+        Ok(transaction_data) => transaction_data,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+
+    let mut request = volo_grpc::Request::new(ComputeSingleFingerprintRequest {
+        transaction_data: Some(transaction_data),
+        salt: item.salt(),
+        with_commitments: false,
+        encodings: Default::default(),
+        validate_only: false,
+        _unknown_fields: Default::default(),
+    });
+    if let Some(api_key) = api_key.and_then(|api_key| api_key.parse().ok()) {
+        request.metadata_mut().insert(API_KEY, api_key);
+    }
+    let response = match service.compute_single_fingerprint(request).await {
+        Ok(response) => response.into_inner(),
+        Err(status) => return error(http_status(&status), status.message()),
+    };
+
+    let fingerprint = response.fingerprint.unwrap_or_default();
+    let duplicate = response
+        .duplicate
+        .and_then(|duplicate| match duplicate.status {
+            DuplicateStatus::DUPLICATE_STATUS_NEW => Some("new"),
+            DuplicateStatus::DUPLICATE_STATUS_DUPLICATE => Some("duplicate"),
+            DuplicateStatus::DUPLICATE_STATUS_RECURRING => Some("recurring"),
+            _ => None,
+        });
+
+    json(
+        StatusCode::OK,
+        &JsonFingerprint {
+            item_id: item.item_id.as_deref(),
+            fingerprint: &fingerprint.compact_fingerprint,
+            namespace: &fingerprint.namespace,
+            duplicate,
+        },
+    )
+}
+
+/// HTTP status of the failed computation
+fn http_status(status: &Status) -> StatusCode {
+    match status.code() {
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> (StatusCode, String) {
+    json(status, &JsonError { error: message })
+}
+
+fn json<T: serde::Serialize>(status: StatusCode, body: &T) -> (StatusCode, String) {
+    match serde_json::to_string(body) {
+        Ok(body) => (status, body),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!(r#"{{"error": "{}"}}"#, e),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fingerprinting_core::NaiveProtocol;
+    use fingerprinting_grpc::FingerprintService as Service;
+    use fingerprinting_store::{FingerprintStore, MemoryFingerprintStore};
+    use halo2_axiom::halo2curves::bn256::Fr;
+    use serde_json::Value;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rest_fingerprints() -> Result<(), Error> {
+        let store: Arc<dyn FingerprintStore> = Arc::new(MemoryFingerprintStore::new());
+        let service = Service::new(NaiveProtocol::new(Fr::from(42))).with_store(Some(store));
+        let item = r#"{"item_id": "a", "transaction": {"bic": "BCEELU21", "amount": "1000.55",
+            "currency": "EUR", "date_time": "2025-09-16T12:30:15Z"}}"#;
+        let post = |body: &'static str| {
+            respond(
+                &service,
+                &Method::POST,
+                FINGERPRINTS_PATH,
+                None,
+                body.as_bytes(),
+            )
+        };
+
+        let (status, first) = post(item).await;
+        assert_eq!(status, StatusCode::OK);
+        let first: Value = serde_json::from_str(&first)?;
+        assert_eq!(first["item_id"], "a");
+        assert_eq!(first["duplicate"], "new");
+
+        let (_, second) = post(item).await;
+        let second: Value = serde_json::from_str(&second)?;
+        assert_eq!(second["fingerprint"], first["fingerprint"]);
+        assert_eq!(second["duplicate"], "duplicate");
+
+        // Malformed items and the invalid transactions are the caller's errors
+        assert_eq!(post("{").await.0, StatusCode::BAD_REQUEST);
+        let (status, invalid) = post(
+            r#"{"transaction": {"bic": "BCEELU21", "amount": "1",
+                "currency": "XXXX", "date_time": "2025-09-16T12:30:15Z"}}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", invalid);
+
+        assert_eq!(
+            respond(&service, &Method::GET, FINGERPRINTS_PATH, None, &[])
+                .await
+                .0,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            respond(&service, &Method::POST, "/v1/other", None, &[])
+                .await
+                .0,
+            StatusCode::NOT_FOUND
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+//! Smoke test of the deployed binary, the health gate of the container after the deployment
+//!
+//! Spins up the ephemeral in-process topology of the test secret and checks it end to end: the golden vectors,
+//! the batch of the distinct transactions, the failed agent tolerated and the duplicate detected by the store.
+//! Nothing leaves the process, the production secret and the agent fleet are not involved.
+
+use crate::batch::{self, BatchItem};
+use crate::conformance::{self, Outcome, Vector, VectorFile, VectorTransaction};
+use anyhow::{anyhow, Error};
+use fingerprinting_core::{CollaborativeProtocol, Compact, InProcessAgentsTopology};
+use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+    ComputeSingleFingerprintRequest, ComputeSingleFingerprintResponse, DuplicateStatus,
+    FingerprintService as _,
+};
+use fingerprinting_grpc::FingerprintService;
+use fingerprinting_store::{FingerprintStore, MemoryFingerprintStore};
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// Golden vectors of the test secret, computed by the reference implementation
+pub const GOLDEN_VECTORS: &str = include_str!("../vectors/golden.json");
+
+/// Items of the batch check
+pub const BATCH_SIZE: usize = 1000;
+
+const BATCH_CONCURRENCY: usize = 64;
+const AGENTS: usize = 5;
+const THRESHOLD: usize = 3;
+
+type InProcessService = FingerprintService<CollaborativeProtocol<Fr, G1, InProcessAgentsTopology>>;
+
+/// Outcome of the single check, the details of the passed one or the reason of the failure
+pub struct Check {
+    pub name: &'static str,
+    pub result: Result<String, Error>,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Runs all the checks with the `batch_size` items of the batch, the failed check does not stop the others
+pub async fn run_selftest(batch_size: usize) -> Result<Vec<Check>, Error> {
+    let golden: VectorFile = serde_json::from_str(GOLDEN_VECTORS)?;
+    let secret: Fr = Compact::unwrap(&golden.secret)?;
+
+    Ok(vec![
+        Check {
+            name: "golden vectors",
+            result: check_golden_vectors(&golden),
+        },
+        Check {
+            name: "in-process topology",
+            result: check_topology(secret, &golden.vectors).await,
+        },
+        Check {
+            name: "batch",
+            result: check_batch(secret, batch_size).await,
+        },
+        Check {
+            name: "agent failure",
+            result: check_agent_failure(secret, &golden.vectors[0]).await,
+        },
+        Check {
+            name: "duplicate detection",
+            result: check_duplicates(secret, &golden.vectors[0]).await,
+        },
+    ])
+}
+
+/// Service over the in-process topology of the `secret` without the `unavailable` agents
+fn service(secret: Fr, unavailable: &[usize]) -> InProcessService {
+    let (agent_info, topology) = InProcessAgentsTopology::share(secret, THRESHOLD, AGENTS);
+    let topology = unavailable.iter().fold(topology, |topology, agent| {
+        topology.with_unavailable_agent(*agent)
+    });
+
+    FingerprintService::new(CollaborativeProtocol::new(agent_info, topology))
+}
+
+async fn compute(
+    service: &InProcessService,
+    transaction: &VectorTransaction,
+    salt: Option<&str>,
+) -> Result<ComputeSingleFingerprintResponse, Error> {
+    let item = BatchItem {
+        item_id: None,
+        transaction: transaction.clone(),
+        salt: salt.map(str::to_string),
+        residency: None,
+    };
+    let request = volo_grpc::Request::new(ComputeSingleFingerprintRequest {
+        transaction_data: Some(item.transaction_data()?),
+        salt: item.salt(),
+        with_commitments: false,
+        encodings: Default::default(),
+        validate_only: false,
+        _unknown_fields: Default::default(),
+    });
+
+    Ok(service
+        .compute_single_fingerprint(request)
+        .await?
+        .into_inner())
+}
+
+/// Fingerprint of the vector computed by the `service` matches the golden one
+async fn check_vector(service: &InProcessService, vector: &Vector) -> Result<(), Error> {
+    let response = compute(service, &vector.transaction, vector.salt.as_deref()).await?;
+    let fingerprint = response
+        .fingerprint
+        .unwrap_or_default()
+        .compact_fingerprint
+        .to_string();
+    if fingerprint != vector.fingerprint {
+        return Err(anyhow!(
+            "{}: computed {}, golden {}",
+            vector.name,
+            fingerprint,
+            vector.fingerprint
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_golden_vectors(golden: &VectorFile) -> Result<String, Error> {
+    let suite = conformance::run_suite("golden".to_string(), golden);
+    let failed: Vec<_> = suite
+        .cases
+        .iter()
+        .filter_map(|case| match &case.outcome {
+            Outcome::Passed => None,
+            Outcome::Failed(message) | Outcome::Error(message) => {
+                Some(format!("{}: {}", case.name, message))
+            }
+        })
+        .collect();
+    if !failed.is_empty() {
+        return Err(anyhow!(failed.join("; ")));
+    }
+
+    Ok(format!("{} vectors", suite.cases.len()))
+}
+
+async fn check_topology(secret: Fr, vectors: &[Vector]) -> Result<String, Error> {
+    let service = service(secret, &[]);
+    for vector in vectors {
+        check_vector(&service, vector).await?;
+    }
+
+    Ok(format!(
+        "{} vectors, {} of {} agents",
+        vectors.len(),
+        THRESHOLD,
+        AGENTS
+    ))
+}
+
+async fn check_batch(secret: Fr, batch_size: usize) -> Result<String, Error> {
+    let service = service(secret, &[]);
+    let input: String = (0..batch_size)
+        .map(|i| {
+            format!(
+                r#"{{"item_id": "{i}", "transaction": {{"bic": "BCEELU21", "amount": "{i}.01",
+                    "currency": "EUR", "date_time": "2025-09-16T12:30:15Z"}}}}"#
+            )
+            .replace('\n', "")
+                + "\n"
+        })
+        .collect();
+
+    let mut fingerprints = HashSet::new();
+    let mut failures = vec![];
+    let summary = batch::run_batch(
+        Cursor::new(input),
+        BATCH_CONCURRENCY,
+        &HashSet::new(),
+        false,
+        |transaction_data, salt| {
+            let service = &service;
+            async move {
+                let request = volo_grpc::Request::new(ComputeSingleFingerprintRequest {
+                    transaction_data: Some(transaction_data),
+                    salt,
+                    with_commitments: false,
+                    encodings: Default::default(),
+                    validate_only: false,
+                    _unknown_fields: Default::default(),
+                });
+                let response = service.compute_single_fingerprint(request).await?;
+
+                Ok(response.into_inner().fingerprint.unwrap_or_default())
+            }
+        },
+        |result, _| {
+            match result.fingerprint {
+                Ok(fingerprint) => {
+                    fingerprints.insert(fingerprint.compact_fingerprint.to_string());
+                }
+                Err(e) => failures.push(format!("#{}: {}", result.line, e)),
+            }
+            Ok(())
+        },
+    )
+    .await?;
+
+    if let Some(failure) = failures.first() {
+        return Err(anyhow!("{} items failed, {}", summary.failed, failure));
+    }
+    if summary.computed != batch_size || fingerprints.len() != batch_size {
+        return Err(anyhow!(
+            "{} items computed, {} distinct fingerprints of {} distinct transactions",
+            summary.computed,
+            fingerprints.len(),
+            batch_size
+        ));
+    }
+
+    Ok(format!("{} items", summary.computed))
+}
+
+async fn check_agent_failure(secret: Fr, vector: &Vector) -> Result<String, Error> {
+    // Agent 1 coordinates, agent 2 does not respond and the others reach the threshold
+    check_vector(&service(secret, &[2]), vector).await?;
+
+    // Threshold is not reachable without three of the agents
+    let response = compute(&service(secret, &[2, 3, 4]), &vector.transaction, None).await;
+    if response.is_ok() {
+        return Err(anyhow!(
+            "Fingerprint computed below the threshold of {} agents",
+            THRESHOLD
+        ));
+    }
+
+    Ok("agent 2 of 5 failed".to_string())
+}
+
+async fn check_duplicates(secret: Fr, vector: &Vector) -> Result<String, Error> {
+    let store: Arc<dyn FingerprintStore> = Arc::new(MemoryFingerprintStore::new());
+    let service = service(secret, &[]).with_store(Some(store));
+
+    let mut statuses = vec![];
+    for _ in 0..2 {
+        let response = compute(&service, &vector.transaction, None).await?;
+        statuses.push(response.duplicate.map(|duplicate| duplicate.status));
+    }
+    if statuses
+        != [
+            Some(DuplicateStatus::DUPLICATE_STATUS_NEW),
+            Some(DuplicateStatus::DUPLICATE_STATUS_DUPLICATE),
+        ]
+    {
+        return Err(anyhow!(
+            "Statuses of the repeated transaction are {:?}",
+            statuses
+        ));
+    }
+
+    Ok("repeated transaction is the duplicate".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_selftest() -> Result<(), Error> {
+        let checks = run_selftest(50).await?;
+
+        for check in &checks {
+            assert!(check.passed(), "{}: {:?}", check.name, check.result);
+        }
+        assert_eq!(checks.len(), 5);
+
+        Ok(())
+    }
+}
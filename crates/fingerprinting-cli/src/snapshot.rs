@@ -0,0 +1,297 @@
+//! Snapshots of the coordinator state kept in memory
+//!
+//! The responses of the idempotency cache, the circuit breakers of the agents and the partial tree of the epoch
+//! accumulator are saved to the JSON file every `snapshot.interval-secs` and restored on start, so the coordinator
+//! bounced mid-epoch neither loses the accumulated fingerprints nor re-admits the quarantined agent, and the calls
+//! retried across the restart still get their responses. The file is replaced at once, the changes since the last
+//! snapshot are lost with the crash.
+
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
+use fingerprinting_core::breaker::{BreakerState, CircuitBreakers};
+use fingerprinting_core::Compact;
+use fingerprinting_grpc::{CachedResponse, IdempotencyCache};
+use fingerprinting_store::merkle::EpochAccumulator;
+use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// State of the coordinator, the parts not configured are neither saved nor restored
+#[derive(Default)]
+pub struct CoordinatorState {
+    pub idempotency: Option<Arc<IdempotencyCache>>,
+    pub breakers: Option<Arc<CircuitBreakers>>,
+    pub accumulator: Option<Arc<Mutex<EpochAccumulator>>>,
+}
+
+/// Content of the snapshot file, the bytes are compacted
+#[derive(Serialize, Deserialize, Debug)]
+struct Snapshot {
+    taken_at: DateTime<Utc>,
+    #[serde(default)]
+    responses: Vec<ResponseSnapshot>,
+    #[serde(default)]
+    breakers: Vec<BreakerSnapshot>,
+    accumulator: Option<AccumulatorSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ResponseSnapshot {
+    key: String,
+    at: DateTime<Utc>,
+    response: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct BreakerSnapshot {
+    agent: usize,
+    failures: u32,
+    open_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct AccumulatorSnapshot {
+    epoch: NaiveDate,
+    leaves: u64,
+    /// Subtree roots by the level, null for the levels without one
+    frontier: Vec<Option<String>>,
+}
+
+impl CoordinatorState {
+    /// Saves the state at `now`, replacing the snapshot file at once
+    pub fn save(&self, path: &Path, now: DateTime<Utc>) -> Result<(), Error> {
+        let responses = self
+            .idempotency
+            .as_ref()
+            .map(|idempotency| idempotency.responses(now))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|cached| ResponseSnapshot {
+                key: cached.key,
+                at: cached.at,
+                response: Bytes::from(cached.response).compact(),
+            })
+            .collect();
+        let breakers = self
+            .breakers
+            .as_ref()
+            .map(|breakers| breakers.states())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(agent, state)| BreakerSnapshot {
+                agent,
+                failures: state.failures,
+                open_until: state.open_until,
+            })
+            .collect();
+        let accumulator = self.accumulator.as_ref().map(|accumulator| {
+            let accumulator = accumulator.lock().unwrap_or_else(PoisonError::into_inner);
+            AccumulatorSnapshot {
+                epoch: accumulator.epoch(),
+                leaves: accumulator.leaves(),
+                frontier: accumulator
+                    .frontier()
+                    .iter()
+                    .map(|node| node.map(|node| Bytes::copy_from_slice(&node).compact()))
+                    .collect(),
+            }
+        });
+        let snapshot = Snapshot {
+            taken_at: now,
+            responses,
+            breakers,
+            accumulator,
+        };
+
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, serde_json::to_vec_pretty(&snapshot)?)?;
+        std::fs::rename(&temporary, path)?;
+
+        Ok(())
+    }
+
+    /// Restores the saved state, false when no snapshot is saved yet
+    pub fn restore(&self, path: &Path) -> Result<bool, Error> {
+        if !path.exists() {
+            return Ok(false);
+        }
+        let snapshot: Snapshot = serde_json::from_slice(&std::fs::read(path)?)?;
+
+        if let Some(idempotency) = &self.idempotency {
+            let responses = snapshot
+                .responses
+                .into_iter()
+                .map(|cached| {
+                    let response: Bytes = Compact::unwrap(&cached.response)?;
+                    Ok(CachedResponse {
+                        key: cached.key,
+                        at: cached.at,
+                        response: response.to_vec(),
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            idempotency.restore(responses)?;
+        }
+        if let Some(breakers) = &self.breakers {
+            breakers.restore(
+                snapshot
+                    .breakers
+                    .into_iter()
+                    .map(|breaker| {
+                        let state = BreakerState {
+                            failures: breaker.failures,
+                            open_until: breaker.open_until,
+                        };
+                        (breaker.agent, state)
+                    })
+                    .collect(),
+            );
+        }
+        if let (Some(accumulator), Some(saved)) = (&self.accumulator, snapshot.accumulator) {
+            let frontier = saved
+                .frontier
+                .iter()
+                .map(|node| node.as_deref().map(subtree_root).transpose())
+                .collect::<Result<Vec<_>, Error>>()?;
+            *accumulator.lock().unwrap_or_else(PoisonError::into_inner) =
+                EpochAccumulator::from_frontier(saved.epoch, saved.leaves, frontier)?;
+        }
+
+        Ok(true)
+    }
+}
+
+fn subtree_root(compacted: &str) -> Result<[u8; 32], Error> {
+    let node: Bytes = Compact::unwrap(compacted)?;
+    <[u8; 32]>::try_from(node.as_ref()).map_err(|_| anyhow!("Subtree root should be 32 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone};
+    use fingerprinting_core::clock::{Clock, FixedClock};
+    use fingerprinting_core::NaiveProtocol;
+    use fingerprinting_grpc::net::outbe::common::v1::{Currency, Date, Money, Timestamp};
+    use fingerprinting_grpc::net::outbe::fingerprint::v1::{
+        ComputeSingleFingerprintRequest, FingerprintService as _, TransactionFingerprintData,
+    };
+    use fingerprinting_grpc::{FingerprintService, IDEMPOTENCY_KEY};
+    use fingerprinting_store::{FingerprintStore, MemoryFingerprintStore};
+    use halo2_axiom::halo2curves::bn256::Fr;
+    use volo_grpc::Request;
+
+    fn transaction_data(units: u64, tx_date: DateTime<Utc>) -> TransactionFingerprintData {
+        TransactionFingerprintData {
+            bic: "BCEELU21".into(),
+            amount: Some(Money {
+                currency: Currency::CURRENCY_EUR,
+                units,
+                atto: 0,
+                _unknown_fields: Default::default(),
+            }),
+            date_time: Some(Timestamp {
+                seconds: tx_date.timestamp() as u64,
+                nanos: 0,
+                _unknown_fields: Default::default(),
+            }),
+            wwd: Some(Date {
+                year: tx_date.year() as u32,
+                month: tx_date.month(),
+                day: tx_date.day(),
+                _unknown_fields: Default::default(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// State of the coordinator started anew, sharing the store outliving it
+    fn start(
+        clock: Arc<FixedClock>,
+        store: Arc<dyn FingerprintStore>,
+    ) -> (CoordinatorState, FingerprintService<NaiveProtocol>) {
+        let state = CoordinatorState {
+            idempotency: Some(Arc::new(IdempotencyCache::new(
+                16,
+                chrono::Duration::hours(1),
+            ))),
+            breakers: Some(Arc::new(
+                CircuitBreakers::new(2, chrono::Duration::minutes(5)).with_clock(clock.clone()),
+            )),
+            accumulator: Some(Arc::new(Mutex::new(EpochAccumulator::new(clock.today())))),
+        };
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .with_store(Some(store))
+            .with_idempotency(state.idempotency.clone())
+            .with_accumulator(state.accumulator.clone())
+            .with_clock(clock);
+
+        (state, service)
+    }
+
+    #[tokio::test]
+    async fn test_coordinator_restart() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("coordinator-{}.json", std::process::id()));
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let clock = Arc::new(FixedClock::new(now));
+        let store: Arc<dyn FingerprintStore> = Arc::new(MemoryFingerprintStore::new());
+        let request = |units: u64, idempotency_key: &str| {
+            let mut request = Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data(units, now)),
+                ..Default::default()
+            });
+            request
+                .metadata_mut()
+                .insert(IDEMPOTENCY_KEY, idempotency_key.parse().unwrap());
+            request
+        };
+
+        let (state, service) = start(clock.clone(), store.clone());
+        assert!(!state.restore(&path)?);
+        let first = service
+            .compute_single_fingerprint(request(1, "first"))
+            .await?
+            .into_inner();
+        let breakers = state.breakers.as_ref().unwrap();
+        breakers.record(3, false);
+        breakers.record(3, false);
+        breakers.record(4, false);
+        state.save(&path, clock.now())?;
+        let mut uninterrupted = state.accumulator.as_ref().unwrap().lock().unwrap().clone();
+
+        // Restarted coordinator
+        clock.advance(chrono::Duration::minutes(1));
+        let (state, service) = start(clock.clone(), store);
+        assert!(state.restore(&path)?);
+
+        // Quarantined agent is not re-admitted, the failures of the other one are still counted
+        let breakers = state.breakers.as_ref().unwrap();
+        assert!(breakers.admit(3).is_err());
+        breakers.record(4, false);
+        assert!(breakers.admit(4).is_err());
+
+        // Retried call gets the response of the call completed before the restart rather than the duplicate
+        let retried = service
+            .compute_single_fingerprint(request(1, "first"))
+            .await?
+            .into_inner();
+        assert_eq!(retried, first);
+        assert_eq!(service.counters().stats().computed, 0);
+
+        // Accumulation of the epoch continues from the restored partial tree
+        let second = service
+            .compute_single_fingerprint(request(2, "second"))
+            .await?
+            .into_inner();
+        let fingerprint: Fr =
+            fingerprinting_core::wire::decode_scalar(&second.fingerprint.unwrap().fingerprint)?;
+        uninterrupted.append(0, &fingerprint);
+        let accumulator = state.accumulator.as_ref().unwrap().lock().unwrap().clone();
+        assert_eq!(accumulator.leaves(), 2);
+        assert_eq!(accumulator.root(), uninterrupted.root());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
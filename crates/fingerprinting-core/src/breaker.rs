@@ -0,0 +1,152 @@
+//! Circuit breakers of the agents of the topology
+//!
+//! Agent failing `failures` evaluations in a row is quarantined for the `cooldown`: its evaluations fail right away
+//! rather than waiting for the timeouts, the rounds are completed with the other agents. The first evaluation after
+//! the cooldown is the trial, the failed trial quarantines the agent again and the successful one closes the breaker.
+
+use crate::clock::{self, Clock};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Failures of the agent since its last successful evaluation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BreakerState {
+    /// Evaluations failed in a row
+    pub failures: u32,
+    /// End of the quarantine, absent while the agent is admitted
+    pub open_until: Option<DateTime<Utc>>,
+}
+
+/// Evaluation refused without asking the agent, it is quarantined by its breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgentQuarantined {
+    pub agent: usize,
+    pub until: DateTime<Utc>,
+}
+
+impl Display for AgentQuarantined {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Agent {} is quarantined until {}",
+            self.agent, self.until
+        )
+    }
+}
+
+impl std::error::Error for AgentQuarantined {}
+
+pub struct CircuitBreakers {
+    failures: u32,
+    cooldown: Duration,
+    states: Mutex<BTreeMap<usize, BreakerState>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl CircuitBreakers {
+    pub fn new(failures: u32, cooldown: Duration) -> Self {
+        Self {
+            failures: failures.max(1),
+            cooldown,
+            states: Mutex::default(),
+            clock: clock::system_clock(),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Refuses the evaluation of the quarantined agent
+    pub fn admit(&self, agent: usize) -> Result<(), AgentQuarantined> {
+        let states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        match states.get(&agent).and_then(|state| state.open_until) {
+            Some(until) if until > self.clock.now() => Err(AgentQuarantined { agent, until }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts the outcome of the evaluation of the agent
+    pub fn record(&self, agent: usize, succeeded: bool) {
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        if succeeded {
+            states.remove(&agent);
+            return;
+        }
+
+        let state = states.entry(agent).or_default();
+        state.failures += 1;
+        if state.failures >= self.failures {
+            let until = self.clock.now() + self.cooldown;
+            log::warn!(
+                "Agent {} failed {} evaluations in a row, quarantined until {}",
+                agent,
+                state.failures,
+                until
+            );
+            state.open_until = Some(until);
+        }
+    }
+
+    /// States of the agents failed since their last success, in the order of the agents
+    pub fn states(&self) -> Vec<(usize, BreakerState)> {
+        let states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        states
+            .iter()
+            .map(|(agent, state)| (*agent, *state))
+            .collect()
+    }
+
+    /// Replaces the states, e.g. by the ones saved before the restart
+    pub fn restore(&self, states: Vec<(usize, BreakerState)>) {
+        *self.states.lock().unwrap_or_else(PoisonError::into_inner) = states.into_iter().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_circuit_breakers() {
+        let clock = Arc::new(FixedClock::new(
+            Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap(),
+        ));
+        let breakers = CircuitBreakers::new(2, Duration::minutes(5)).with_clock(clock.clone());
+
+        breakers.record(3, false);
+        assert!(breakers.admit(3).is_ok());
+        breakers.record(3, false);
+        let quarantined = breakers.admit(3).unwrap_err();
+        assert_eq!(quarantined.until, clock.now() + Duration::minutes(5));
+        assert!(breakers.admit(2).is_ok());
+
+        // Failed trial after the cooldown quarantines the agent again, the successful one closes the breaker
+        clock.advance(Duration::minutes(5));
+        assert!(breakers.admit(3).is_ok());
+        breakers.record(3, false);
+        assert!(breakers.admit(3).is_err());
+        clock.advance(Duration::minutes(5));
+        breakers.record(3, true);
+        assert!(breakers.states().is_empty());
+
+        breakers.record(4, false);
+        let restored = CircuitBreakers::new(2, Duration::minutes(5)).with_clock(clock);
+        restored.restore(breakers.states());
+        assert_eq!(
+            restored.states(),
+            vec![(
+                4,
+                BreakerState {
+                    failures: 1,
+                    open_until: None
+                }
+            )]
+        );
+    }
+}
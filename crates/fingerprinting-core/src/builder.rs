@@ -0,0 +1,131 @@
+//! Fingerprints of the deployment record types, laid out of any ordered components rather than the fixed ones of
+//! `TransactionFingerprintData`
+//!
+//! The builder serializes the components into the preimage in the order they are added and squeezes it as
+//! Poseidon(DOMAIN_TAG | len | limbs), 31 bytes per limb. The domain tag of the record type keeps its fingerprints
+//! apart from the transaction fingerprints and from the other record types, it takes the prefix of the deployment
+//! like the tags of `BoundComponent`. The length goes before the limbs, so the preimages with the trailing zero bytes
+//! do not collide. The squeezed value is evaluated by the protocol round like the date time of the transaction.
+
+use crate::components::{validate_domain_tag, FingerprintComponent};
+use crate::fingerprint_poseidon;
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+
+// Size of the preimage limb, 31 bytes always fit into Fr
+const LIMB_SIZE: usize = 31;
+
+/// Preimage of the record type built of the ordered components
+#[derive(Debug, Clone)]
+pub struct FingerprintBuilder {
+    domain_tag: String,
+    preimage: Vec<u8>,
+    components: usize,
+}
+
+impl FingerprintBuilder {
+    /// Builder of the record type separated by the `domain_tag`, see `components::validate_domain_tag`
+    pub fn new(domain_tag: &str) -> Result<Self, Error> {
+        validate_domain_tag(domain_tag)?;
+
+        Ok(Self {
+            domain_tag: domain_tag.to_string(),
+            preimage: vec![],
+            components: 0,
+        })
+    }
+
+    /// Appends the serialized `component` to the preimage, fails when it writes other than its `size()` bytes
+    pub fn with_component<O, const S: usize, C: FingerprintComponent<O, S>>(
+        mut self,
+        component: &C,
+    ) -> Result<Self, Error> {
+        let offset = self.preimage.len();
+        component.serialize(&mut self.preimage)?;
+
+        let written = self.preimage.len() - offset;
+        if written != C::size() {
+            return Err(anyhow!(
+                "Component {} of the preimage wrote {} bytes, its size is {} bytes",
+                self.components,
+                written,
+                C::size()
+            ));
+        }
+        self.components += 1;
+
+        Ok(self)
+    }
+
+    pub fn domain_tag(&self) -> &str {
+        &self.domain_tag
+    }
+
+    /// Serialized components in the order they were added
+    pub fn preimage(&self) -> &[u8] {
+        &self.preimage
+    }
+
+    /// Poseidon(DOMAIN_TAG | len | limbs) of the preimage, fails when no component is added
+    pub fn squeeze(&self) -> Result<Fr, Error> {
+        if self.components == 0 {
+            return Err(anyhow!("Preimage of {} has no components", self.domain_tag));
+        }
+
+        let mut domain = [0u8; 32];
+        domain[0..self.domain_tag.len()].copy_from_slice(self.domain_tag.as_bytes());
+        let domain = Fr::from_bytes(&domain).unwrap_or(Fr::zero());
+
+        let mut limbs = vec![domain, Fr::from(self.preimage.len() as u64)];
+        for chunk in self.preimage.chunks(LIMB_SIZE) {
+            let mut buffer_32 = [0u8; 32];
+            buffer_32[0..chunk.len()].copy_from_slice(chunk);
+
+            limbs.push(Fr::from_bytes(&buffer_32).unwrap_or(Fr::zero()));
+        }
+
+        let mut poseidon = fingerprint_poseidon::<5, 4>();
+        poseidon.update(limbs.as_slice());
+
+        Ok(poseidon.squeeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{AmountComponent, BankIdentifierComponent, CurrencyComponent};
+
+    #[test]
+    fn test_fingerprint_builder() -> Result<(), Error> {
+        let invoice = |amount: (u64, u64)| {
+            FingerprintBuilder::new("ACME_INVOICE")?
+                .with_component(&BankIdentifierComponent::new("BCEELU21".to_string()))?
+                .with_component(&AmountComponent::new(amount))?
+                .with_component(&CurrencyComponent::new(978))
+        };
+
+        let builder = invoice((1000, 0))?;
+        assert_eq!(builder.preimage().len(), 6 + 32 + 2);
+        let squeezed = builder.squeeze()?;
+        assert_eq!(invoice((1000, 0))?.squeeze()?, squeezed);
+        assert_ne!(invoice((1001, 0))?.squeeze()?, squeezed);
+
+        // Order of the components and the record type are in the fingerprint
+        let reordered = FingerprintBuilder::new("ACME_INVOICE")?
+            .with_component(&AmountComponent::new((1000, 0)))?
+            .with_component(&BankIdentifierComponent::new("BCEELU21".to_string()))?
+            .with_component(&CurrencyComponent::new(978))?;
+        assert_ne!(reordered.squeeze()?, squeezed);
+        let receipt = FingerprintBuilder::new("ACME_RECEIPT")?
+            .with_component(&BankIdentifierComponent::new("BCEELU21".to_string()))?
+            .with_component(&AmountComponent::new((1000, 0)))?
+            .with_component(&CurrencyComponent::new(978))?;
+        assert_ne!(receipt.squeeze()?, squeezed);
+
+        assert!(FingerprintBuilder::new("CRA_FP_INVOICE").is_err());
+        assert!(FingerprintBuilder::new("ACME_EMPTY")?.squeeze().is_err());
+
+        Ok(())
+    }
+}
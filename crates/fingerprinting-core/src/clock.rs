@@ -0,0 +1,189 @@
+//! Time source of the services and the epoch arithmetic of the date components
+//!
+//! Services never read the system time directly, the `Clock` is injected instead: `SystemClock` in production,
+//! `FixedClock` in the tests of the date boundaries and `SimulatedClock` for the simulation runs at arbitrary dates.
+
+use crate::EPOCH;
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use std::fmt::{self, Debug};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// UTC calendar day of the current time
+    fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+}
+
+/// Clock of the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Shared system clock, the default of the services
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// Clock standing still until it is moved explicitly
+#[derive(Debug)]
+pub struct FixedClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl FixedClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += duration;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Clock running at the real pace from the `start` date, for the simulation runs
+#[derive(Debug, Clone, Copy)]
+pub struct SimulatedClock {
+    start: DateTime<Utc>,
+    started: Instant,
+}
+
+impl SimulatedClock {
+    pub fn starting_at(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        let elapsed = Duration::from_std(self.started.elapsed()).unwrap_or(Duration::MAX);
+        self.start
+            .checked_add_signed(elapsed)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+/// Origin the date components of the fingerprints are offset from, 01.01.2025 by default.
+/// Deployments back-filling the historical transactions either move the epoch before them or compute the dates
+/// before the epoch with the negative offsets, see `DateTimeComponent`. Moving the epoch changes all the fingerprints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Epoch(NaiveDate);
+
+impl Epoch {
+    pub fn new(date: NaiveDate) -> Self {
+        Self(date)
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        self.0
+    }
+
+    /// Seconds of the date time since the epoch, negative before it
+    pub fn seconds_since(&self, date_time: &DateTime<Utc>) -> i64 {
+        date_time
+            .naive_utc()
+            .signed_duration_since(self.0.and_time(NaiveTime::MIN))
+            .num_seconds()
+    }
+
+    /// Days of the date (e.g. World Wide Date) since the epoch, negative before it
+    pub fn days_since(&self, date: NaiveDate) -> i64 {
+        date.signed_duration_since(self.0).num_days()
+    }
+}
+
+impl Default for Epoch {
+    fn default() -> Self {
+        Self(EPOCH.date())
+    }
+}
+
+impl fmt::Display for Epoch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Epoch {
+    type Err = Error;
+
+    /// Epoch of the date, e.g. "2023-01-01"
+    fn from_str(date: &str) -> Result<Self, Self::Err> {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(Epoch)
+            .map_err(|e| anyhow!("Epoch {} should be the date YYYY-MM-DD: {}", date, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+
+    #[test]
+    fn test_epoch_boundaries() -> Result<(), Error> {
+        let epoch = Epoch::default();
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2025, 12, 31, 23, 59, 59).unwrap());
+        assert_eq!(epoch.days_since(clock.today()), 364);
+
+        // Year rollover
+        clock.advance(Duration::seconds(1));
+        assert_eq!(clock.today(), NaiveDate::from_ymd_opt(2026, 1, 1).unwrap());
+        assert_eq!(epoch.days_since(clock.today()), 365);
+        assert_eq!(epoch.seconds_since(&clock.now()), 365 * 24 * 3600);
+
+        // Epoch day, the dates before it are offset backwards
+        clock.set(EPOCH.and_utc());
+        assert_eq!(epoch.seconds_since(&clock.now()), 0);
+        assert_eq!(epoch.days_since(clock.today()), 0);
+        clock.advance(Duration::seconds(-1));
+        assert_eq!(epoch.seconds_since(&clock.now()), -1);
+        assert_eq!(epoch.days_since(clock.today()), -1);
+
+        // Moved epoch of the back-fills
+        let backfill: Epoch = "2023-01-01".parse()?;
+        assert_eq!(backfill.days_since(epoch.date()), 365 + 366);
+        assert_eq!(backfill.to_string(), "2023-01-01");
+        assert!("01.01.2023".parse::<Epoch>().is_err());
+
+        // Days are UTC ones, the local offset (e.g. of the DST switch night in CET) never shifts them
+        let cest = FixedOffset::east_opt(2 * 3600).unwrap();
+        let local = cest.with_ymd_and_hms(2025, 3, 30, 1, 30, 0).unwrap();
+        clock.set(local.with_timezone(&Utc));
+        assert_eq!(clock.today(), NaiveDate::from_ymd_opt(2025, 3, 29).unwrap());
+
+        let simulated =
+            SimulatedClock::starting_at(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(
+            simulated.today(),
+            NaiveDate::from_ymd_opt(2030, 1, 1).unwrap()
+        );
+
+        Ok(())
+    }
+}
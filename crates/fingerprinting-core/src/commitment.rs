@@ -0,0 +1,415 @@
+use crate::clock::Epoch;
+use crate::components::{
+    AmountComponent, BankIdentifierComponent, CurrencyComponent, DateTimeComponent, DateTimeRaw,
+    FingerprintComponent,
+};
+use crate::entropy::{self, EntropySource};
+use crate::wire::WireVersion;
+use crate::{
+    fingerprint_poseidon, wire, FingerprintProtocol, ProtocolFingerprint,
+    TransactionFingerprintData, COMMITMENT_DOMAIN_PREFIX,
+};
+use anyhow::Error;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use halo2_axiom::halo2curves::bn256::Fr;
+
+/// Component of the fingerprint which could be selectively disclosed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommittedComponent {
+    Bic,
+    Amount,
+    Currency,
+    DateTime,
+}
+
+impl CommittedComponent {
+    // Domain tag of the component commitment
+    fn tag(&self) -> u64 {
+        match self {
+            CommittedComponent::Bic => 1,
+            CommittedComponent::Amount => 2,
+            CommittedComponent::Currency => 3,
+            CommittedComponent::DateTime => 4,
+        }
+    }
+}
+
+/// Revealed value of the single component
+#[derive(Debug, Clone)]
+pub enum ComponentValue {
+    Bic(String),
+    Amount((u64, u64)),
+    Currency(u16),
+    DateTime(DateTime<Utc>, NaiveDate, (u64, u64)),
+}
+
+impl ComponentValue {
+    pub fn component(&self) -> CommittedComponent {
+        match self {
+            ComponentValue::Bic(_) => CommittedComponent::Bic,
+            ComponentValue::Amount(_) => CommittedComponent::Amount,
+            ComponentValue::Currency(_) => CommittedComponent::Currency,
+            ComponentValue::DateTime(..) => CommittedComponent::DateTime,
+        }
+    }
+
+    // Same normalization and serialization as used for the fingerprint of the `wire_version` and `epoch`
+    fn serialize(&self, wire_version: WireVersion, epoch: Epoch) -> Result<Vec<u8>, Error> {
+        let mut buffer = Vec::with_capacity(32);
+
+        match self {
+            ComponentValue::Bic(bic) => {
+                BankIdentifierComponent::new(bic.clone()).serialize(&mut buffer)?
+            }
+            ComponentValue::Amount(amount) => AmountComponent::new(*amount)
+                .with_wire_version(wire_version)
+                .serialize(&mut buffer)?,
+            ComponentValue::Currency(code) => {
+                CurrencyComponent::new(*code).serialize(&mut buffer)?
+            }
+            ComponentValue::DateTime(date_time, wwd, amount) => {
+                DateTimeComponent::new(DateTimeRaw::new(*date_time, *wwd, *amount))
+                    .with_wire_version(wire_version)
+                    .with_epoch(epoch)
+                    .serialize(&mut buffer)?
+            }
+        }
+
+        Ok(buffer)
+    }
+}
+
+/// Poseidon commitment to the single component with the opening (blinding factor)
+/// The opening should be kept by the holder in order to reveal the component later
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentCommitment {
+    pub component: CommittedComponent,
+    pub commitment: Fr,
+    pub opening: Fr,
+}
+
+impl ComponentCommitment {
+    fn commit(component: CommittedComponent, serialized: &[u8], opening: Fr) -> Fr {
+        // Components are serialized to at most 32 bytes, split them to 16 bytes limbs to fit into Fr
+        let mut inputs = vec![Fr::from(component.tag()), opening];
+        for chunk in serialized.chunks(16) {
+            let mut buffer_32 = [0u8; 32];
+            buffer_32[0..chunk.len()].copy_from_slice(chunk);
+
+            inputs.push(Fr::from_bytes(&buffer_32).unwrap_or(Fr::zero()));
+        }
+
+        let mut poseidon = fingerprint_poseidon::<5, 4>();
+        poseidon.update(inputs.as_slice());
+        poseidon.squeeze()
+    }
+
+    /// Checks that the revealed value is the committed one, serialized by the `wire_version` and `epoch`
+    /// the fingerprint is computed with
+    pub fn verify(
+        &self,
+        revealed: &ComponentValue,
+        wire_version: WireVersion,
+        epoch: Epoch,
+    ) -> Result<bool, Error> {
+        if revealed.component() != self.component {
+            return Ok(false);
+        }
+
+        let serialized = revealed.serialize(wire_version, epoch)?;
+
+        Ok(Self::commit(self.component, &serialized, self.opening) == self.commitment)
+    }
+}
+
+/// Commitments to all the components bound to the computed fingerprint by the signature of the service.
+/// The commitments and the fingerprint are public, so the binding anyone could recompute would not prove anything
+#[derive(Debug, Clone, PartialEq)]
+pub struct FingerprintCommitments {
+    pub fingerprint: Fr,
+    pub components: Vec<ComponentCommitment>,
+    /// Wire version and epoch the components are serialized with, the revealed values are serialized the same way
+    pub wire_version: WireVersion,
+    pub epoch: Epoch,
+    /// Ed25519 signature of the service over the `binding_message`
+    pub binding: Signature,
+}
+
+impl FingerprintCommitments {
+    /// Message the service signs to bind the commitments to the fingerprint
+    /// COMMITMENT_DOMAIN | fingerprint | wire version | epoch days | tag_bic | C_bic | tag_amount | C_amount | ...
+    pub fn binding_message(
+        fingerprint: Fr,
+        wire_version: WireVersion,
+        epoch: Epoch,
+        commitments: &[(CommittedComponent, Fr)],
+    ) -> Vec<u8> {
+        let mut message = Vec::with_capacity(
+            COMMITMENT_DOMAIN_PREFIX.len()
+                + wire::SCALAR_SIZE
+                + 1
+                + 4
+                + commitments.len() * (8 + wire::SCALAR_SIZE),
+        );
+        message.extend_from_slice(COMMITMENT_DOMAIN_PREFIX.as_bytes());
+        message.extend_from_slice(&wire::encode_scalar(&fingerprint));
+        message.push(wire_version.as_u8());
+        message.extend_from_slice(&epoch.date().num_days_from_ce().to_be_bytes());
+        for (component, commitment) in commitments {
+            message.extend_from_slice(&component.tag().to_be_bytes());
+            message.extend_from_slice(&wire::encode_scalar(commitment));
+        }
+
+        message
+    }
+
+    /// Checks that the commitments are bound to the fingerprint by the service of the verifying `key`
+    pub fn verify_binding(&self, key: &VerifyingKey) -> bool {
+        let commitments = self
+            .components
+            .iter()
+            .map(|c| (c.component, c.commitment))
+            .collect::<Vec<_>>();
+
+        let message = Self::binding_message(
+            self.fingerprint,
+            self.wire_version,
+            self.epoch,
+            &commitments,
+        );
+
+        key.verify_strict(&message, &self.binding).is_ok()
+    }
+
+    pub fn component(&self, component: CommittedComponent) -> Option<&ComponentCommitment> {
+        self.components.iter().find(|c| c.component == component)
+    }
+
+    /// Checks that the fingerprint is computed over the `revealed` value: the commitments are bound to
+    /// the fingerprint by the service of the verifying `key` and the commitment to the component opens to the value.
+    /// The other components stay hidden
+    pub fn verify_disclosure(
+        &self,
+        key: &VerifyingKey,
+        revealed: &ComponentValue,
+    ) -> Result<bool, Error> {
+        if !self.verify_binding(key) {
+            return Ok(false);
+        }
+
+        match self.component(revealed.component()) {
+            Some(commitment) => commitment.verify(revealed, self.wire_version, self.epoch),
+            None => Ok(false),
+        }
+    }
+}
+
+impl TransactionFingerprintData<Fr> {
+    /// Computes hiding commitments to each of the components alongside the computed `fingerprint`,
+    /// bound to it by the signature of the service `key`. Openings are drawn from the `entropy` source
+    pub fn commit_components(
+        &self,
+        fingerprint: Fr,
+        key: &SigningKey,
+        entropy: &dyn EntropySource,
+    ) -> Result<FingerprintCommitments, Error> {
+        let values = [
+            (CommittedComponent::Bic, serialize(&self.bic)?),
+            (CommittedComponent::Amount, serialize(&self.amount)?),
+            (CommittedComponent::Currency, serialize(&self.currency)?),
+            (CommittedComponent::DateTime, serialize(&self.date_time)?),
+        ];
+
+        let components = values
+            .into_iter()
+            .map(|(component, serialized)| {
+                let opening = entropy::random_scalar(entropy)?;
+
+                Ok(ComponentCommitment {
+                    component,
+                    commitment: ComponentCommitment::commit(component, &serialized, opening),
+                    opening,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let commitments = components
+            .iter()
+            .map(|c| (c.component, c.commitment))
+            .collect::<Vec<_>>();
+
+        Ok(FingerprintCommitments {
+            fingerprint,
+            binding: key.sign(&FingerprintCommitments::binding_message(
+                fingerprint,
+                self.wire_version(),
+                self.epoch(),
+                &commitments,
+            )),
+            components,
+            wire_version: self.wire_version(),
+            epoch: self.epoch(),
+        })
+    }
+
+    /// Computes the fingerprint via the protocol round together with the commitments to its components signed
+    /// by the service `key`, the holder keeps the openings for the disputes,
+    /// see `FingerprintCommitments::verify_disclosure`
+    pub async fn complete_fingerprint_with_commitments<P: FingerprintProtocol<Fr> + Sync>(
+        &self,
+        via_protocol: &P,
+        key: &SigningKey,
+        entropy: &dyn EntropySource,
+    ) -> Result<FingerprintCommitments, Error> {
+        let fingerprint = self.complete_fingerprint(via_protocol).await?;
+
+        self.commit_components(fingerprint.into_inner(), key, entropy)
+    }
+}
+
+fn serialize<O, const S: usize, C: FingerprintComponent<O, S>>(
+    component: &C,
+) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::with_capacity(S);
+    component.serialize(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entropy::OsEntropy;
+    use crate::NaiveProtocol;
+    use chrono::TimeZone;
+    use fingerprinting_types::RawTransactionBuilder;
+
+    #[test]
+    fn test_selective_disclosure() -> Result<(), Error> {
+        let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        let tx: TransactionFingerprintData<Fr> = RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount((1000u64, "EUR"))
+            .date_time(tx_date)
+            .wwd(tx_date.date_naive())
+            .build()?
+            .try_into()?;
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let commitments = tx.commit_components(Fr::from(42), &key, &OsEntropy)?;
+        assert!(commitments.verify_binding(&key.verifying_key()));
+        let (version, epoch) = (commitments.wire_version, commitments.epoch);
+
+        let amount = commitments.component(CommittedComponent::Amount).unwrap();
+        assert!(amount.verify(&ComponentValue::Amount((1000, 0)), version, epoch)?);
+        assert!(!amount.verify(&ComponentValue::Amount((1001, 0)), version, epoch)?);
+        assert!(!amount.verify(&ComponentValue::Currency(978), version, epoch)?);
+
+        // BIC is committed in the same normalized form as used for the fingerprint
+        let bic = commitments.component(CommittedComponent::Bic).unwrap();
+        assert!(bic.verify(
+            &ComponentValue::Bic("BCEELU21XXX".to_string()),
+            version,
+            epoch
+        )?);
+
+        let date_time = commitments.component(CommittedComponent::DateTime).unwrap();
+        assert!(date_time.verify(
+            &ComponentValue::DateTime(tx_date, tx_date.date_naive(), (1000, 0)),
+            version,
+            epoch
+        )?);
+
+        // Fresh openings make commitments of the same transaction unlinkable
+        let other = tx.commit_components(Fr::from(42), &key, &OsEntropy)?;
+        assert_ne!(commitments.binding, other.binding);
+
+        let mut tampered = commitments.clone();
+        tampered.fingerprint = Fr::from(43);
+        assert!(!tampered.verify_binding(&key.verifying_key()));
+
+        // Binding of the same commitments by anyone but the service is refused
+        let forged =
+            tx.commit_components(Fr::from(42), &SigningKey::from_bytes(&[8; 32]), &OsEntropy)?;
+        assert!(!forged.verify_binding(&key.verifying_key()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_disclosure_of_wire_version_and_epoch() -> Result<(), Error> {
+        let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        let tx: TransactionFingerprintData<Fr> = RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount((1000u64, "EUR"))
+            .date_time(tx_date)
+            .wwd(tx_date.date_naive())
+            .build()?
+            .try_into()?;
+        let backfill: Epoch = "2023-01-01".parse()?;
+        let tx = tx.with_wire_version(WireVersion::V4).with_epoch(backfill);
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let verifying_key = key.verifying_key();
+
+        let commitments = tx.commit_components(Fr::from(42), &key, &OsEntropy)?;
+        assert_eq!(commitments.wire_version, WireVersion::V4);
+        assert_eq!(commitments.epoch, backfill);
+        let amount = ComponentValue::Amount((1000, 0));
+        let date_time = ComponentValue::DateTime(tx_date, tx_date.date_naive(), (1000, 0));
+        assert!(commitments.verify_disclosure(&verifying_key, &amount)?);
+        assert!(commitments.verify_disclosure(&verifying_key, &date_time)?);
+
+        // Values are not opened by the layout of the other wire version or epoch
+        let committed = commitments.component(CommittedComponent::Amount).unwrap();
+        assert!(!committed.verify(&amount, WireVersion::V1, backfill)?);
+        let committed = commitments.component(CommittedComponent::DateTime).unwrap();
+        assert!(!committed.verify(&date_time, WireVersion::V4, Epoch::default())?);
+        assert!(!committed.verify(&date_time, WireVersion::V1, backfill)?);
+
+        // Nor is the binding kept once the recorded parameters are changed
+        let mut tampered = commitments.clone();
+        tampered.epoch = Epoch::default();
+        assert!(!tampered.verify_binding(&verifying_key));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_with_commitments() -> Result<(), Error> {
+        let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        let tx: TransactionFingerprintData<Fr> = RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount((1000u64, "EUR"))
+            .date_time(tx_date)
+            .wwd(tx_date.date_naive())
+            .build()?
+            .try_into()?;
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let verifying_key = key.verifying_key();
+
+        let commitments = tx
+            .complete_fingerprint_with_commitments(&protocol, &key, &OsEntropy)
+            .await?;
+        assert_eq!(
+            commitments.fingerprint,
+            tx.complete_fingerprint(&protocol).await?.into_inner()
+        );
+
+        // Amount is disclosed without the other components
+        let amount = ComponentValue::Amount((1000, 0));
+        assert!(commitments.verify_disclosure(&verifying_key, &amount)?);
+        let other_amount = ComponentValue::Amount((1000, 1));
+        assert!(!commitments.verify_disclosure(&verifying_key, &other_amount)?);
+        let currency = ComponentValue::Currency(978);
+        assert!(commitments.verify_disclosure(&verifying_key, &currency)?);
+
+        // Commitments of another fingerprint do not disclose anything
+        let mut tampered = commitments.clone();
+        tampered.fingerprint = Fr::from(43);
+        assert!(!tampered.verify_disclosure(&verifying_key, &amount)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use crate::components::salt::squeeze_bytes;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::wire;
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::io::Write;
+
+/// Maximum size of the on-chain address in bytes, e.g. 20 bytes of the EVM chains, 32 bytes of the ed25519 keys
+pub const MAX_ADDRESS_SIZE: usize = 32;
+
+// Address of the on-chain account, in the raw bytes of the chain rather than its text encoding,
+// so the checksummed and the lowercase hex of the same EVM address share the fingerprint
+#[derive(Debug)]
+pub struct AddressComponent {
+    address: Bytes,
+}
+
+impl AddressComponent {
+    pub(crate) fn validate(address: &[u8]) -> Result<(), Error> {
+        if address.is_empty() || address.len() > MAX_ADDRESS_SIZE {
+            return Err(anyhow!(
+                "Address should be from 1 to {} bytes long, given {} bytes",
+                MAX_ADDRESS_SIZE,
+                address.len()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl FingerprintComponent<Bytes, 32> for AddressComponent {
+    fn new(original: Bytes) -> Self {
+        Self { address: original }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let written = buffer.write(&wire::encode_scalar(&self.squeeze()?))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &Bytes {
+        &self.address
+    }
+}
+
+impl SqueezeComponent<Fr> for AddressComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        AddressComponent::validate(&self.address)?;
+
+        Ok(squeeze_bytes(&self.address))
+    }
+}
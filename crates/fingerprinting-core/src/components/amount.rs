@@ -1,12 +1,26 @@
 use crate::components::FingerprintComponent;
-use bigint::U256;
+use crate::pii::{self, PiiField};
+use crate::wire::{self, WireVersion};
+use std::fmt;
 use std::io::Write;
 
-#[derive(Debug)]
 pub struct AmountComponent {
     base: u64,
     atto: u64,
     original: (u64, u64),
+    /// Scale of the amount, see `wire::encode_versioned_amount`
+    wire_version: WireVersion,
+}
+
+impl fmt::Debug for AmountComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AmountComponent")
+            .field(
+                "amount",
+                &pii::scrubbed(PiiField::Amount, format!("{}.{:018}", self.base, self.atto)),
+            )
+            .finish()
+    }
 }
 
 impl FingerprintComponent<(u64, u64), 32> for AmountComponent {
@@ -15,6 +29,7 @@ impl FingerprintComponent<(u64, u64), 32> for AmountComponent {
             base: original.0,
             atto: original.1,
             original,
+            wire_version: WireVersion::CURRENT,
         }
     }
 
@@ -22,12 +37,10 @@ impl FingerprintComponent<(u64, u64), 32> for AmountComponent {
         // 256-bit unsigned integer, big-endian
         // All amounts converted to smallest unit (atto) to eliminate decimal formatting differences
 
-        // build uniform u256 with atto
-        let full_amount = U256::from(self.base) * U256::from(10 ^ 18) + U256::from(self.atto);
-        let mut full_amount_buffer = [0u8; 32];
-        full_amount.to_big_endian(&mut full_amount_buffer);
-
-        let written = buffer.write(&full_amount_buffer)?;
+        let written = buffer.write(&wire::encode_versioned_amount(
+            self.wire_version,
+            (self.base, self.atto),
+        )?)?;
 
         debug_assert_eq!(written, Self::size());
         Ok(())
@@ -37,3 +50,11 @@ impl FingerprintComponent<(u64, u64), 32> for AmountComponent {
         &self.original
     }
 }
+
+impl AmountComponent {
+    /// Scales the amount as the wire `version` does, `WireVersion::CURRENT` by default
+    pub fn with_wire_version(mut self, version: WireVersion) -> Self {
+        self.wire_version = version;
+        self
+    }
+}
@@ -0,0 +1,104 @@
+use crate::components::extension::bind_scalar;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::{fingerprint_poseidon, wire, AMOUNT_TIER_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::io::Write;
+
+/// Most tiers the amounts are mapped into
+pub const MAX_AMOUNT_TIERS: usize = 16;
+
+/// Coarse tiers of the amounts, e.g. below 1k, from 1k to 10k and from 10k on: `AmountTiers::new(vec![1_000, 10_000])`
+/// The bounds are the whole units of the currency the tier starts from, the first tier starts from zero
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmountTiers {
+    bounds: Vec<u64>,
+}
+
+impl AmountTiers {
+    /// Bounds are above zero and strictly ascending, so every tier is non-empty
+    pub fn new(bounds: Vec<u64>) -> Result<Self, Error> {
+        if bounds.is_empty() || bounds.len() >= MAX_AMOUNT_TIERS {
+            return Err(anyhow!(
+                "Amount tiers should have from 1 to {} bounds, given {}",
+                MAX_AMOUNT_TIERS - 1,
+                bounds.len()
+            ));
+        }
+        if bounds[0] == 0 || bounds.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(anyhow!(
+                "Amount tier bounds should be above zero and strictly ascending, given {:?}",
+                bounds
+            ));
+        }
+
+        Ok(Self { bounds })
+    }
+
+    pub fn bounds(&self) -> &[u64] {
+        &self.bounds
+    }
+
+    /// Range of the tier holding the `amount`, from its bound inclusive to the next one exclusive,
+    /// the last tier is open up to `u64::MAX`
+    pub fn tier(&self, amount: (u64, u64)) -> (u64, u64) {
+        let (base, _) = amount;
+        let index = self.bounds.partition_point(|bound| *bound <= base);
+
+        let lower = index
+            .checked_sub(1)
+            .map_or(0, |previous| self.bounds[previous]);
+        let upper = self.bounds.get(index).copied().unwrap_or(u64::MAX);
+
+        (lower, upper)
+    }
+}
+
+// Tier of the amount (range of the tier and currency) rather than the amount itself
+// Bound on top of the preimage hash, so the fingerprints of the schemas binding only the tier match across the tier,
+// e.g. for the tier-level duplicate heuristics, while the secondary indices never hold the exact amount
+#[derive(Debug)]
+pub struct AmountTierComponent {
+    original: ((u64, u64), u16),
+}
+
+impl AmountTierComponent {
+    /// Poseidon(AMOUNT_TIER_DOMAIN | fingerprint | Poseidon(lower | upper | currency))
+    pub fn bind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let tier = self.squeeze()?;
+
+        Ok(bind_scalar(AMOUNT_TIER_DOMAIN_PREFIX, fingerprint, tier))
+    }
+}
+
+impl FingerprintComponent<((u64, u64), u16), 18> for AmountTierComponent {
+    fn new(original: ((u64, u64), u16)) -> Self {
+        Self { original }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let ((lower, upper), currency) = self.original;
+
+        let mut written = buffer.write(&lower.to_be_bytes())?;
+        written += buffer.write(&upper.to_be_bytes())?;
+        written += buffer.write(&wire::encode_currency(currency))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &((u64, u64), u16) {
+        &self.original
+    }
+}
+
+impl SqueezeComponent<Fr> for AmountTierComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        let ((lower, upper), currency) = self.original;
+
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+        poseidon.update(&[Fr::from(lower), Fr::from(upper), Fr::from(currency as u64)]);
+
+        Ok(poseidon.squeeze())
+    }
+}
@@ -1,14 +1,22 @@
-use anyhow::anyhow;
-use regex::Regex;
+use crate::pii::{self, PiiField};
 use std::io::Write;
 
 use crate::components::FingerprintComponent;
+use crate::wire;
+use std::fmt;
 
-#[derive(Debug)]
 pub struct BankIdentifierComponent {
     bic: String,
 }
 
+impl fmt::Debug for BankIdentifierComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BankIdentifierComponent")
+            .field("bic", &pii::scrubbed(PiiField::Bic, &self.bic))
+            .finish()
+    }
+}
+
 impl FingerprintComponent<String, 6> for BankIdentifierComponent {
     fn new(original: String) -> Self {
         Self { bic: original }
@@ -28,24 +36,7 @@ impl FingerprintComponent<String, 6> for BankIdentifierComponent {
         // Firstly check the BIC is valid BIC
         // ([A-Z]{4})([A-Z]{2})([A-Z0-9]{2})([A-Z0-9]{3})?$
 
-        let bic_validation = Regex::new(
-            r"(?x)
-(?P<bank_code>[A-Z]{4})  # 4-letter bank code
-(?P<country_code>[A-Z]{2}) # 2-letter country code
-(?P<location_code>[A-Z0-9]{2}) # 2-character location code
-(?P<branch_code>[A-Z0-9]{3})? # optional 3-character branch code
-$",
-        )?;
-
-        let bic = bic_validation
-            .captures(&self.bic)
-            .ok_or(anyhow!("BIC is invalid format, should be BBBBCCLLBRN"))?;
-
-        let bank_code = &bic["bank_code"];
-        let country_code = &bic["country_code"];
-
-        let written = buffer.write(bank_code.as_bytes())?;
-        let written = written + buffer.write(country_code.as_bytes())?;
+        let written = buffer.write(&wire::encode_bic(&self.bic)?)?;
 
         debug_assert_eq!(written, Self::size());
         Ok(())
@@ -0,0 +1,35 @@
+use crate::components::salt::squeeze_bytes;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use anyhow::Error;
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::io::Write;
+
+// Fixed 32 bytes value of the on-chain transaction, e.g. the transaction hash or the big-endian uint256 amount
+// The value does not always fit into Fr, so it is squeezed like the other byte strings
+#[derive(Debug)]
+pub struct Bytes32Component {
+    original: [u8; 32],
+}
+
+impl FingerprintComponent<[u8; 32], 32> for Bytes32Component {
+    fn new(original: [u8; 32]) -> Self {
+        Self { original }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let written = buffer.write(&self.original)?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &[u8; 32] {
+        &self.original
+    }
+}
+
+impl SqueezeComponent<Fr> for Bytes32Component {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        Ok(squeeze_bytes(&self.original))
+    }
+}
@@ -0,0 +1,123 @@
+use crate::components::extension::bind_scalar;
+use crate::components::salt::squeeze_bytes;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::pii::{self, PiiField};
+use crate::{fingerprint_poseidon, wire, COUNTERPARTY_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
+use std::io::Write;
+
+/// Maximum size of the payer or the payee identifier in bytes
+pub const MAX_COUNTERPARTY_ID_SIZE: usize = 64;
+
+// Ordered (payer, payee) identifiers of the transfer, e.g. the IBANs or the account numbers of both sides
+// Every identifier is squeezed into the 32 bytes scalar like the other identifiers, the pair is ordered,
+// so the transfer back from the payee to the payer never shares the fingerprint
+pub struct CounterpartyComponent {
+    original: (String, String),
+}
+
+impl fmt::Debug for CounterpartyComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CounterpartyComponent")
+            .field(
+                "payer",
+                &pii::scrubbed(PiiField::Counterparty, &self.original.0),
+            )
+            .field(
+                "payee",
+                &pii::scrubbed(PiiField::Counterparty, &self.original.1),
+            )
+            .finish()
+    }
+}
+
+impl CounterpartyComponent {
+    pub(crate) fn validate(payer: &str, payee: &str) -> Result<(), Error> {
+        for (side, id) in [("Payer", payer), ("Payee", payee)] {
+            if id.is_empty() || id.len() > MAX_COUNTERPARTY_ID_SIZE {
+                return Err(anyhow!(
+                    "{} identifier should be from 1 to {} bytes long, given {} bytes",
+                    side,
+                    MAX_COUNTERPARTY_ID_SIZE,
+                    id.len()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Poseidon(COUNTERPARTY_DOMAIN | fingerprint | Poseidon(squeezed payer | squeezed payee))
+    pub fn bind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let counterparty = self.squeeze()?;
+
+        Ok(bind_scalar(
+            COUNTERPARTY_DOMAIN_PREFIX,
+            fingerprint,
+            counterparty,
+        ))
+    }
+
+    /// Squeezed pair sorted by the identifiers, so the transfer back from the payee to the payer squeezes the same,
+    /// see `FingerprintSchema::direction_agnostic`
+    pub fn squeeze_unordered(&self) -> Result<Fr, Error> {
+        let (payer, payee) = &self.original;
+        CounterpartyComponent::validate(payer, payee)?;
+
+        let mut sides = self.sides();
+        if payer > payee {
+            sides.reverse();
+        }
+
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+        poseidon.update(&sides);
+
+        Ok(poseidon.squeeze())
+    }
+
+    fn sides(&self) -> [Fr; 2] {
+        let (payer, payee) = &self.original;
+
+        [
+            squeeze_bytes(payer.as_bytes()),
+            squeeze_bytes(payee.as_bytes()),
+        ]
+    }
+}
+
+impl FingerprintComponent<(String, String), 64> for CounterpartyComponent {
+    fn new(original: (String, String)) -> Self {
+        Self { original }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let (payer, payee) = &self.original;
+        CounterpartyComponent::validate(payer, payee)?;
+
+        let mut written = 0;
+        for side in self.sides() {
+            written += buffer.write(&wire::encode_scalar(&side))?;
+        }
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &(String, String) {
+        &self.original
+    }
+}
+
+impl SqueezeComponent<Fr> for CounterpartyComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        let (payer, payee) = &self.original;
+        CounterpartyComponent::validate(payer, payee)?;
+
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+        poseidon.update(&self.sides());
+
+        Ok(poseidon.squeeze())
+    }
+}
@@ -0,0 +1,101 @@
+use crate::components::extension::bind_scalar;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::pii::{self, PiiField};
+use crate::{wire, COUNTRY_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::LazyLock;
+
+/// ISO 3166-1 numeric codes by the alpha-2 codes of the countries
+static COUNTRY_CODES: LazyLock<HashMap<&'static str, u16>> = LazyLock::new(|| {
+    iso_country::data::all()
+        .into_iter()
+        .filter_map(|country| Some((country.alpha2, country.num.parse().ok()?)))
+        .collect()
+});
+
+/// ISO 3166-1 numeric code of the country given by its alpha-2 (e.g. `LU`) or numeric (e.g. `442`) code
+pub fn numeric_country_code(code: &str) -> Result<u16, Error> {
+    let numeric = match code.len() {
+        2 => COUNTRY_CODES.get(code).copied(),
+        3 if code.bytes().all(|b| b.is_ascii_digit()) => code
+            .parse()
+            .ok()
+            .filter(|numeric| COUNTRY_CODES.values().any(|known| known == numeric)),
+        _ => None,
+    };
+
+    numeric.ok_or(anyhow!(
+        "Country {} is not in the ISO 3166-1 countries",
+        pii::scrubbed(PiiField::CountryCode, code)
+    ))
+}
+
+/// ISO 3166-1 alpha-2 code of the country given by its numeric code, the inverse of `numeric_country_code`
+pub fn alpha2_country_code(numeric: u16) -> Result<&'static str, Error> {
+    COUNTRY_CODES
+        .iter()
+        .find(|(_, known)| **known == numeric)
+        .map(|(alpha2, _)| *alpha2)
+        .ok_or(anyhow!(
+            "Country {} is not in the ISO 3166-1 countries",
+            pii::scrubbed(PiiField::CountryCode, numeric)
+        ))
+}
+
+// Jurisdiction the transaction took place in, as the ISO 3166-1 numeric code
+// Bound on top of the preimage hash, so identical transactions of the different jurisdictions never share the fingerprint
+#[derive(Debug)]
+pub struct CountryCodeComponent {
+    country_code: u16,
+}
+
+impl CountryCodeComponent {
+    pub(crate) fn validate(country_code: u16) -> Result<(), Error> {
+        if !COUNTRY_CODES.values().any(|known| *known == country_code) {
+            return Err(anyhow!(
+                "Country code {:03} is not in the ISO 3166-1 countries",
+                country_code
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Poseidon(COUNTRY_DOMAIN | fingerprint | country code)
+    pub fn bind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let country = self.squeeze()?;
+
+        Ok(bind_scalar(COUNTRY_DOMAIN_PREFIX, fingerprint, country))
+    }
+}
+
+impl FingerprintComponent<u16, 2> for CountryCodeComponent {
+    fn new(original: u16) -> Self {
+        Self {
+            country_code: original,
+        }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        // Same two bytes big-endian layout as the numeric currency code
+        let written = buffer.write(&wire::encode_currency(self.country_code))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &u16 {
+        &self.country_code
+    }
+}
+
+impl SqueezeComponent<Fr> for CountryCodeComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        CountryCodeComponent::validate(self.country_code)?;
+
+        Ok(Fr::from(self.country_code as u64))
+    }
+}
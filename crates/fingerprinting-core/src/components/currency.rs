@@ -1,4 +1,5 @@
 use crate::components::FingerprintComponent;
+use crate::wire;
 use std::io::Write;
 
 #[derive(Debug)]
@@ -14,7 +15,7 @@ impl FingerprintComponent<u16, 2> for CurrencyComponent {
     }
 
     fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), anyhow::Error> {
-        let written = buffer.write(&self.currency_code.to_be_bytes())?;
+        let written = buffer.write(&wire::encode_currency(self.currency_code))?;
 
         debug_assert_eq!(written, Self::size());
         Ok(())
@@ -1,21 +1,44 @@
+use crate::clock::Epoch;
 use crate::components::{FingerprintComponent, SqueezeComponent};
-use crate::{EPOCH, SPEC_DC};
+use crate::fuzzy::TimeBucket;
+use crate::pii::{self, PiiField};
+use crate::wire::WireVersion;
+use crate::{fingerprint_poseidon, fixed_point, wire};
 use anyhow::{anyhow, Error};
 use bigint::U256;
 use chrono::{DateTime, NaiveDate, Utc};
-use fingerprinting_poseidon::Poseidon;
 use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
 use std::io::Write;
 
 pub type Amount = (u64, u64);
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Eq, PartialEq, Copy, Clone)]
 pub struct DateTimeRaw {
     date_time: DateTime<Utc>,
     wwd: NaiveDate,
     amount: Amount,
 }
 
+impl fmt::Debug for DateTimeRaw {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DateTimeRaw")
+            .field(
+                "date_time",
+                &pii::scrubbed(PiiField::DateTime, self.date_time),
+            )
+            .field("wwd", &pii::scrubbed(PiiField::DateTime, self.wwd))
+            .field(
+                "amount",
+                &pii::scrubbed(
+                    PiiField::Amount,
+                    format!("{}.{:018}", self.amount.0, self.amount.1),
+                ),
+            )
+            .finish()
+    }
+}
+
 impl DateTimeRaw {
     pub fn new(date_time: DateTime<Utc>, wwd: NaiveDate, amount: Amount) -> Self {
         DateTimeRaw {
@@ -24,11 +47,37 @@ impl DateTimeRaw {
             amount,
         }
     }
+
+    pub fn date_time(&self) -> &DateTime<Utc> {
+        &self.date_time
+    }
+
+    /// Working day the transaction is settled on
+    pub fn wwd(&self) -> NaiveDate {
+        self.wwd
+    }
+
+    pub fn amount(&self) -> Amount {
+        self.amount
+    }
 }
 
 #[derive(Debug)]
 pub struct DateTimeComponent {
     raw: DateTimeRaw,
+    /// Derivation of the nonce, see `DateTimeComponent::nonce`
+    wire_version: WireVersion,
+    epoch: Epoch,
+}
+
+/// Scalar of the offset, the negative offsets are negated
+fn signed_scalar(offset: i64) -> Fr {
+    let scalar = Fr::from(offset.unsigned_abs());
+    if offset < 0 {
+        -scalar
+    } else {
+        scalar
+    }
 }
 
 #[inline]
@@ -40,14 +89,15 @@ fn cantor_pair_function(x: U256, y: U256) -> U256 {
 
 impl FingerprintComponent<DateTimeRaw, 32> for DateTimeComponent {
     fn new(original: DateTimeRaw) -> Self {
-        Self { raw: original }
+        Self {
+            raw: original,
+            wire_version: WireVersion::CURRENT,
+            epoch: Epoch::default(),
+        }
     }
 
     fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), anyhow::Error> {
-        let squeezed = self.squeeze()?;
-        let bytes = squeezed.to_bytes();
-
-        let written = buffer.write(&bytes)?;
+        let written = buffer.write(&wire::encode_scalar(&self.squeeze()?))?;
 
         debug_assert_eq!(written, Self::size());
         Ok(())
@@ -58,49 +108,206 @@ impl FingerprintComponent<DateTimeRaw, 32> for DateTimeComponent {
     }
 }
 
-impl SqueezeComponent<Fr> for DateTimeComponent {
-    fn squeeze(&self) -> Result<Fr, Error> {
-        let amount_base = U256::from(self.raw.amount.0);
-        let amount_atto = U256::from(self.raw.amount.1);
-        let full_amount = amount_base * U256::from(10 ^ 18) + amount_atto;
-
-        let date_time = self.raw.date_time;
-        let seconds_since_epoch = date_time
-            .naive_local()
-            .signed_duration_since(EPOCH)
-            .num_seconds();
-
-        if seconds_since_epoch < 0 {
-            return Err(anyhow!("Date cannot be earlier than Epoch: 01.01.2025"));
-        }
-
-        let seconds_since_epoch = U256::from(seconds_since_epoch as u64);
-        let days_since_epoch = self.raw.wwd.signed_duration_since(EPOCH.date()).num_days();
+impl DateTimeComponent {
+    /// Derives the nonce as the wire `version` does, `WireVersion::CURRENT` by default
+    pub fn with_wire_version(mut self, version: WireVersion) -> Self {
+        self.wire_version = version;
+        self
+    }
 
-        if days_since_epoch < 0 || days_since_epoch > u32::MAX as i64 {
-            return Err(anyhow!(
-                "World Wide Date cannot be earlier than Epoch: 01.01.2025"
-            ));
-        }
+    /// Offsets the dates from the `epoch`, `Epoch::default()` by default
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.epoch = epoch;
+        self
+    }
 
-        let days_since_epoch = U256::from(days_since_epoch as u32);
+    pub fn epoch(&self) -> Epoch {
+        self.epoch
+    }
 
-        // Calculating pair function
-        let paired_data = cantor_pair_function(seconds_since_epoch, full_amount / days_since_epoch);
+    /// Component of the date time truncated to the start of its `bucket`, of the same wire version and epoch
+    pub fn bucketed(&self, bucket: TimeBucket) -> Self {
+        Self {
+            raw: DateTimeRaw {
+                date_time: bucket.truncate(&self.raw.date_time),
+                ..self.raw
+            },
+            wire_version: self.wire_version,
+            epoch: self.epoch,
+        }
+    }
 
-        // Specs for 3 Fr input
-        let mut poseidon = Poseidon::new_with_spec(SPEC_DC.clone());
+    /// Poseidon inputs of the component: seconds since epoch, days since epoch and the nonce.
+    /// Offsets before the epoch are the negated scalars, so the later dates keep their inputs
+    pub(crate) fn inputs(&self) -> Result<[Fr; 3], Error> {
+        let seconds_since_epoch = self.epoch.seconds_since(&self.raw.date_time);
+        let days_since_epoch = self.epoch.days_since(self.raw.wwd);
 
         // According to the docs
         // - seconds since epoch
         // - days since epoch
         // - nonce as pairing function from amount days and seconds
-        let seconds_since_epoch = Fr::from(seconds_since_epoch.as_u64());
-        let days_since_epoch = Fr::from(days_since_epoch.as_u64());
-        let nonce = Fr::from_raw(paired_data.0);
+        let nonce = self.nonce(seconds_since_epoch, days_since_epoch)?;
+
+        Ok([
+            signed_scalar(seconds_since_epoch),
+            signed_scalar(days_since_epoch),
+            nonce,
+        ])
+    }
 
-        poseidon.update(&[seconds_since_epoch, days_since_epoch, nonce]);
+    /// Nonce binding the amount to the seconds since epoch.
+    ///
+    /// Until `WireVersion::V3` it is the Cantor pairing of the seconds and the full amount divided by the days,
+    /// which loses the remainder of the division, so the different amounts share the nonce. It is undefined on the
+    /// World Wide Date of the epoch itself and before the epoch, it fails there.
+    ///
+    /// Since `WireVersion::V3` it is the seconds, the amount base and the amount atto packed into the 64-bit limbs,
+    /// seconds * 2^128 + base * 2^64 + atto, the negative seconds are the negated scalar. The packed value stays
+    /// within ±2^192 and so far below the modulus of Fr, the nonce is distinct for every distinct triple and defined
+    /// for all of them.
+    fn nonce(&self, seconds_since_epoch: i64, days_since_epoch: i64) -> Result<Fr, Error> {
+        let (amount_base, amount_atto) = self.raw.amount;
+
+        match self.wire_version {
+            WireVersion::V1 | WireVersion::V2 => {
+                let (Ok(seconds), Ok(days @ 1..)) = (
+                    u64::try_from(seconds_since_epoch),
+                    u64::try_from(days_since_epoch),
+                ) else {
+                    return Err(anyhow!(
+                        "World Wide Date and the date time should be after the epoch {} until wire version {}",
+                        self.epoch,
+                        WireVersion::V3.as_u8()
+                    ));
+                };
+                // Inputs are at most 64 bits wide, so the pairing stays below 2^140 and never overflows U256
+                let full_amount = fixed_point::legacy_units(self.raw.amount)?;
+                let paired =
+                    cantor_pair_function(U256::from(seconds), full_amount / U256::from(days));
+
+                Ok(Fr::from_raw(paired.0))
+            }
+            WireVersion::V3 | WireVersion::V4 => Ok(signed_scalar(seconds_since_epoch)
+                * Fr::from_raw([0, 0, 1, 0])
+                + Fr::from_raw([amount_atto, amount_base, 0, 0])),
+        }
+    }
+}
+
+impl SqueezeComponent<Fr> for DateTimeComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        // Specs for 3 Fr input
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+
+        poseidon.update(&self.inputs()?);
 
         Ok(poseidon.squeeze())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn component(
+        date_time: DateTime<Utc>,
+        wwd: NaiveDate,
+        amount: Amount,
+        version: WireVersion,
+    ) -> DateTimeComponent {
+        DateTimeComponent::new(DateTimeRaw::new(date_time, wwd, amount)).with_wire_version(version)
+    }
+
+    #[test]
+    fn test_nonce() -> Result<(), Error> {
+        let date_time = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        let wwd = NaiveDate::from_ymd_opt(2025, 9, 16).unwrap();
+        let epoch = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let amount = (1000, 550_000_000_000_000_000);
+
+        // Regression vectors, changing any of them breaks the fingerprints of the version
+        let legacy = component(date_time, wwd, amount, WireVersion::V1).inputs()?;
+        assert_eq!(legacy[0], Fr::from(22_336_215));
+        assert_eq!(legacy[1], Fr::from(258));
+        assert_eq!(
+            format!("{:?}", legacy[2]),
+            "0x000000000000000000000000000000000000001cae0893cce6d9cb2702559ebc"
+        );
+        let packed = component(date_time, wwd, amount, WireVersion::V3).inputs()?;
+        assert_eq!(packed[..2], legacy[..2]);
+        assert_eq!(
+            packed[2],
+            Fr::from_raw([550_000_000_000_000_000, 1000, 22_336_215, 0])
+        );
+
+        // Legacy nonce fails on the epoch and collides on the remainder of the division
+        assert!(component(date_time, epoch, amount, WireVersion::V2)
+            .squeeze()
+            .is_err());
+        assert!(component(date_time, epoch, amount, WireVersion::V3)
+            .squeeze()
+            .is_ok());
+        let nearby = (1000, 550_000_000_000_000_001);
+        assert_eq!(
+            component(date_time, wwd, nearby, WireVersion::V1).squeeze()?,
+            component(date_time, wwd, amount, WireVersion::V1).squeeze()?
+        );
+        assert_ne!(
+            component(date_time, wwd, nearby, WireVersion::V3).squeeze()?,
+            component(date_time, wwd, amount, WireVersion::V3).squeeze()?
+        );
+
+        // Packing keeps the limbs apart, the extremes are defined
+        let max = (u64::MAX, u64::MAX);
+        assert_ne!(
+            component(date_time, wwd, (0, 1), WireVersion::V3).squeeze()?,
+            component(date_time, wwd, (1, 0), WireVersion::V3).squeeze()?
+        );
+        assert!(component(date_time, epoch, max, WireVersion::V3)
+            .squeeze()
+            .is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dates_before_epoch() -> Result<(), Error> {
+        let date_time = Utc.with_ymd_and_hms(2024, 3, 15, 9, 0, 0).unwrap();
+        let wwd = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let amount = (1000, 0);
+
+        // Negative offsets of the default epoch since the packed nonce only
+        assert!(component(date_time, wwd, amount, WireVersion::V2)
+            .squeeze()
+            .is_err());
+        let inputs = component(date_time, wwd, amount, WireVersion::V3).inputs()?;
+        assert_eq!(inputs[0], -Fr::from(25_196_400));
+        assert_eq!(inputs[1], -Fr::from(292));
+        assert_eq!(
+            inputs[2],
+            Fr::from_raw([0, 1000, 0, 0]) - Fr::from_raw([0, 0, 25_196_400, 0])
+        );
+        let earlier = Utc.with_ymd_and_hms(2023, 3, 15, 9, 0, 0).unwrap();
+        assert_ne!(
+            component(earlier, wwd, amount, WireVersion::V3).squeeze()?,
+            component(date_time, wwd, amount, WireVersion::V3).squeeze()?
+        );
+
+        // Moved epoch keeps the offsets positive for every version, the fingerprints are the other ones
+        let backfill: Epoch = "2023-01-01".parse()?;
+        let moved = component(date_time, wwd, amount, WireVersion::V1).with_epoch(backfill);
+        assert_eq!(moved.inputs()?[1], Fr::from(439));
+        let later = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let later_wwd = NaiveDate::from_ymd_opt(2025, 9, 16).unwrap();
+        assert_ne!(
+            component(later, later_wwd, amount, WireVersion::V1)
+                .with_epoch(backfill)
+                .squeeze()?,
+            component(later, later_wwd, amount, WireVersion::V1).squeeze()?
+        );
+
+        Ok(())
+    }
+}
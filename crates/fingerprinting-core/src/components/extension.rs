@@ -0,0 +1,71 @@
+//! Components of the deployments, bound on top of the fingerprint the same way as the built-in optional components
+//!
+//! Every bound component is separated by its domain tag: Poseidon(DOMAIN_TAG | fingerprint | squeezed value).
+//! Tags starting with `CRA_` are allocated by this crate, see the `*_DOMAIN_PREFIX` constants, a new built-in
+//! component takes a new `CRA_FP_` tag. Deployment components take the tags of their own prefix, e.g.
+//! `ACME_LOYALTY_ID`, so they never collide with the built-in ones or with the components of the other
+//! deployments. The tag is at most 31 bytes, so it fits into Fr.
+//!
+//! The value is squeezed with the public Poseidon specs, e.g. `squeeze_bytes` for the identifiers, and the same
+//! transaction with the same components in the same order produces the same fingerprint in every deployment.
+
+use crate::components::SqueezeComponent;
+use crate::fingerprint_poseidon;
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt::Debug;
+
+/// Maximum size of the domain tag in bytes, so it fits into Fr
+pub const MAX_DOMAIN_TAG_SIZE: usize = 31;
+
+/// Prefix of the domain tags allocated by this crate
+pub const RESERVED_DOMAIN_PREFIX: &str = "CRA_";
+
+/// Component bound on top of the fingerprint, implemented downstream for the deployment specific fields
+pub trait BoundComponent: SqueezeComponent<Fr> + Debug + Send + Sync {
+    /// Domain tag of the component, see the module docs on the allocation
+    fn domain_tag(&self) -> &str;
+}
+
+/// Checks the domain tag of the deployment component
+pub fn validate_domain_tag(tag: &str) -> Result<(), Error> {
+    if tag.is_empty() || tag.len() > MAX_DOMAIN_TAG_SIZE {
+        return Err(anyhow!(
+            "Domain tag should be from 1 to {} bytes long, given {} bytes",
+            MAX_DOMAIN_TAG_SIZE,
+            tag.len()
+        ));
+    }
+    if tag.starts_with(RESERVED_DOMAIN_PREFIX) {
+        return Err(anyhow!(
+            "Domain tag {} is reserved, tags starting with {} are allocated by the fingerprinting crate",
+            tag,
+            RESERVED_DOMAIN_PREFIX
+        ));
+    }
+
+    Ok(())
+}
+
+/// Poseidon(tag | fingerprint | value), the binding of all the optional components
+pub fn bind_scalar(tag: &str, fingerprint: Fr, value: Fr) -> Fr {
+    debug_assert!(tag.len() <= MAX_DOMAIN_TAG_SIZE);
+
+    let mut domain = [0u8; 32];
+    domain[0..tag.len()].copy_from_slice(tag.as_bytes());
+    let domain = Fr::from_bytes(&domain).unwrap_or(Fr::zero());
+
+    let mut poseidon = fingerprint_poseidon::<4, 3>();
+    poseidon.update(&[domain, fingerprint, value]);
+
+    poseidon.squeeze()
+}
+
+/// Binds the deployment `component` on top of the `fingerprint`
+pub fn bind_component(component: &dyn BoundComponent, fingerprint: Fr) -> Result<Fr, Error> {
+    Ok(bind_scalar(
+        component.domain_tag(),
+        fingerprint,
+        component.squeeze()?,
+    ))
+}
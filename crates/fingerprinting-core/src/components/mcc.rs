@@ -0,0 +1,76 @@
+use crate::components::extension::bind_scalar;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::pii::{self, PiiField};
+use crate::{wire, MCC_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::io::Write;
+
+/// Largest ISO 18245 merchant category code, the codes are 4 decimal digits
+pub const MAX_MCC: u16 = 9999;
+
+/// ISO 18245 merchant category code given as 4 decimal digits, e.g. `5411`
+pub fn numeric_mcc(code: &str) -> Result<u16, Error> {
+    if code.len() != 4 || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(anyhow!(
+            "Merchant category code should be 4 decimal digits, given {}",
+            pii::scrubbed(PiiField::Mcc, code)
+        ));
+    }
+
+    Ok(code.parse()?)
+}
+
+// Merchant category of the card payment, as the ISO 18245 numeric code
+// Bound on top of the preimage hash, so identical payments of the different merchant categories never share the fingerprint
+#[derive(Debug)]
+pub struct MccComponent {
+    mcc: u16,
+}
+
+impl MccComponent {
+    pub(crate) fn validate(mcc: u16) -> Result<(), Error> {
+        if mcc > MAX_MCC {
+            return Err(anyhow!(
+                "Merchant category code should be at most {}, given {}",
+                MAX_MCC,
+                mcc
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Poseidon(MCC_DOMAIN | fingerprint | mcc)
+    pub fn bind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let mcc = self.squeeze()?;
+
+        Ok(bind_scalar(MCC_DOMAIN_PREFIX, fingerprint, mcc))
+    }
+}
+
+impl FingerprintComponent<u16, 2> for MccComponent {
+    fn new(original: u16) -> Self {
+        Self { mcc: original }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        // Same two bytes big-endian layout as the numeric currency code
+        let written = buffer.write(&wire::encode_currency(self.mcc))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &u16 {
+        &self.mcc
+    }
+}
+
+impl SqueezeComponent<Fr> for MccComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        MccComponent::validate(self.mcc)?;
+
+        Ok(Fr::from(self.mcc as u64))
+    }
+}
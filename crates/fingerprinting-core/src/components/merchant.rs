@@ -0,0 +1,77 @@
+use crate::components::extension::bind_scalar;
+use crate::components::salt::squeeze_bytes;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::pii::{self, PiiField};
+use crate::{wire, MERCHANT_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
+use std::io::Write;
+
+/// Maximum size of the merchant identifier in bytes
+pub const MAX_MERCHANT_ID_SIZE: usize = 64;
+
+// Identifier of the merchant (or the acquirer) the transaction is paid to
+// Bound on top of the preimage hash, so the same payment to the different merchants never shares the fingerprint
+pub struct MerchantIdComponent {
+    merchant_id: String,
+}
+
+impl fmt::Debug for MerchantIdComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MerchantIdComponent")
+            .field(
+                "merchant_id",
+                &pii::scrubbed(PiiField::MerchantId, &self.merchant_id),
+            )
+            .finish()
+    }
+}
+
+impl MerchantIdComponent {
+    pub(crate) fn validate(merchant_id: &str) -> Result<(), Error> {
+        if merchant_id.is_empty() || merchant_id.len() > MAX_MERCHANT_ID_SIZE {
+            return Err(anyhow!(
+                "Merchant identifier should be from 1 to {} bytes long, given {} bytes",
+                MAX_MERCHANT_ID_SIZE,
+                merchant_id.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Poseidon(MERCHANT_DOMAIN | fingerprint | Poseidon(len | merchant id limbs))
+    pub fn bind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let merchant = self.squeeze()?;
+
+        Ok(bind_scalar(MERCHANT_DOMAIN_PREFIX, fingerprint, merchant))
+    }
+}
+
+impl FingerprintComponent<String, 32> for MerchantIdComponent {
+    fn new(original: String) -> Self {
+        Self {
+            merchant_id: original,
+        }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let written = buffer.write(&wire::encode_scalar(&self.squeeze()?))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &String {
+        &self.merchant_id
+    }
+}
+
+impl SqueezeComponent<Fr> for MerchantIdComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        MerchantIdComponent::validate(&self.merchant_id)?;
+
+        Ok(squeeze_bytes(self.merchant_id.as_bytes()))
+    }
+}
@@ -1,11 +1,22 @@
 use halo2_axiom::halo2curves::ff::PrimeField;
 use std::io::Write;
 
+mod address;
 mod amount;
+mod amount_tier;
 mod bank_identifier;
+mod bytes32;
+mod counterparty;
+mod country;
 mod currency;
 mod date_time_raw;
-mod scalar;
+mod extension;
+mod mcc;
+mod merchant;
+mod paired_amount;
+mod reference;
+mod salt;
+mod series;
 
 pub trait SqueezeComponent<F: PrimeField> {
     /// Squeeze original data into prime field
@@ -27,9 +38,25 @@ pub trait FingerprintComponent<O, const S: usize> {
     }
 }
 
+pub use address::{AddressComponent, MAX_ADDRESS_SIZE};
 pub use amount::AmountComponent;
+pub use amount_tier::{AmountTierComponent, AmountTiers, MAX_AMOUNT_TIERS};
 pub use bank_identifier::BankIdentifierComponent;
+pub use bytes32::Bytes32Component;
+pub use counterparty::{CounterpartyComponent, MAX_COUNTERPARTY_ID_SIZE};
+pub use country::{alpha2_country_code, numeric_country_code, CountryCodeComponent};
 pub use currency::CurrencyComponent;
 pub use date_time_raw::DateTimeComponent;
 pub use date_time_raw::DateTimeRaw;
-pub use scalar::ScalarComponent;
+pub use extension::{
+    bind_component, bind_scalar, validate_domain_tag, BoundComponent, MAX_DOMAIN_TAG_SIZE,
+    RESERVED_DOMAIN_PREFIX,
+};
+pub use mcc::{numeric_mcc, MccComponent, MAX_MCC};
+pub use merchant::{MerchantIdComponent, MAX_MERCHANT_ID_SIZE};
+pub use paired_amount::PairedAmountComponent;
+pub use reference::{
+    TransactionReferenceComponent, MAX_TRANSACTION_REFERENCE_SIZE, NOT_PROVIDED_REFERENCE,
+};
+pub use salt::{squeeze_bytes, SaltComponent, MAX_SALT_SIZE};
+pub use series::{SeriesComponent, MAX_SERIES_ID_SIZE};
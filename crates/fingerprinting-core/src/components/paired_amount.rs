@@ -0,0 +1,84 @@
+use crate::components::extension::bind_scalar;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::pii::{self, PiiField};
+use crate::{fingerprint_poseidon, wire, PAIRED_AMOUNT_DOMAIN_PREFIX};
+use anyhow::Error;
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
+use std::io::Write;
+
+// Bought leg of the FX transaction (amount and currency), the sold leg is the regular amount and currency
+// The leg is bound on top of the preimage fingerprint, so fingerprints of single currency transactions stay the same
+pub struct PairedAmountComponent {
+    original: ((u64, u64), u16),
+}
+
+impl fmt::Debug for PairedAmountComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PairedAmountComponent")
+            .field(
+                "amount",
+                &pii::scrubbed(
+                    PiiField::CounterAmount,
+                    format!("{}.{:018}", self.original.0 .0, self.original.0 .1),
+                ),
+            )
+            .field(
+                "currency",
+                &pii::scrubbed(PiiField::Currency, self.original.1),
+            )
+            .finish()
+    }
+}
+
+impl PairedAmountComponent {
+    /// Poseidon(PAIRED_AMOUNT_DOMAIN | fingerprint | Poseidon(amount limbs | currency))
+    pub fn bind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let leg = self.squeeze()?;
+
+        Ok(bind_scalar(PAIRED_AMOUNT_DOMAIN_PREFIX, fingerprint, leg))
+    }
+}
+
+impl FingerprintComponent<((u64, u64), u16), 34> for PairedAmountComponent {
+    fn new(original: ((u64, u64), u16)) -> Self {
+        Self { original }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let (amount, currency) = self.original;
+
+        let mut written = buffer.write(&wire::encode_amount(amount)?)?;
+        written += buffer.write(&wire::encode_currency(currency))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &((u64, u64), u16) {
+        &self.original
+    }
+}
+
+impl SqueezeComponent<Fr> for PairedAmountComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        let (amount, currency) = self.original;
+
+        // 32 bytes of the amount are split to the 2 limbs, so each of them fits into Fr
+        let limbs = wire::encode_amount(amount)?
+            .chunks(16)
+            .map(|chunk| {
+                let mut buffer_32 = [0u8; 32];
+                buffer_32[0..16].copy_from_slice(chunk);
+
+                Fr::from_bytes(&buffer_32).unwrap_or(Fr::zero())
+            })
+            .chain([Fr::from(currency as u64)])
+            .collect::<Vec<_>>();
+
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+        poseidon.update(limbs.as_slice());
+
+        Ok(poseidon.squeeze())
+    }
+}
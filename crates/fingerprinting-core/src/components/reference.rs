@@ -0,0 +1,87 @@
+use crate::components::extension::bind_scalar;
+use crate::components::salt::squeeze_bytes;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::pii::{self, PiiField};
+use crate::{wire, REFERENCE_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
+use std::io::Write;
+
+/// Maximum size of the end-to-end reference in bytes, ISO 20022 `Max35Text`
+pub const MAX_TRANSACTION_REFERENCE_SIZE: usize = 35;
+
+/// End-to-end identification ISO 20022 sets when the originator did not provide any
+pub const NOT_PROVIDED_REFERENCE: &str = "NOTPROVIDED";
+
+// End-to-end reference of the payment (ISO 20022 `EndToEndId`) or the remittance id set by the originator
+// Hashed into a single limb and bound on top of the preimage hash, so the recurring payments of the same amount
+// carrying the different references never share the fingerprint, while the resubmitted duplicate still does
+pub struct TransactionReferenceComponent {
+    reference: String,
+}
+
+impl fmt::Debug for TransactionReferenceComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransactionReferenceComponent")
+            .field(
+                "reference",
+                &pii::scrubbed(PiiField::TransactionReference, &self.reference),
+            )
+            .finish()
+    }
+}
+
+impl TransactionReferenceComponent {
+    pub(crate) fn validate(reference: &str) -> Result<(), Error> {
+        if reference.is_empty() || reference.len() > MAX_TRANSACTION_REFERENCE_SIZE {
+            return Err(anyhow!(
+                "Transaction reference should be from 1 to {} bytes long, given {} bytes",
+                MAX_TRANSACTION_REFERENCE_SIZE,
+                reference.len()
+            ));
+        }
+        if reference == NOT_PROVIDED_REFERENCE {
+            return Err(anyhow!(
+                "Transaction reference {} is a placeholder and does not distinguish the payments",
+                NOT_PROVIDED_REFERENCE
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Poseidon(REFERENCE_DOMAIN | fingerprint | Poseidon(len | reference limbs))
+    pub fn bind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let reference = self.squeeze()?;
+
+        Ok(bind_scalar(REFERENCE_DOMAIN_PREFIX, fingerprint, reference))
+    }
+}
+
+impl FingerprintComponent<String, 32> for TransactionReferenceComponent {
+    fn new(original: String) -> Self {
+        Self {
+            reference: original,
+        }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let written = buffer.write(&wire::encode_scalar(&self.squeeze()?))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &String {
+        &self.reference
+    }
+}
+
+impl SqueezeComponent<Fr> for TransactionReferenceComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        TransactionReferenceComponent::validate(&self.reference)?;
+
+        Ok(squeeze_bytes(self.reference.as_bytes()))
+    }
+}
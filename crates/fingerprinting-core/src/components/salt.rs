@@ -0,0 +1,99 @@
+use crate::components::extension::bind_scalar;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::pii::{self, PiiField};
+use crate::{fingerprint_poseidon, wire, SALT_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
+use std::io::Write;
+
+/// Maximum size of the caller supplied salt in bytes
+pub const MAX_SALT_SIZE: usize = 64;
+
+// Size of the salt limb, 31 bytes always fit into Fr
+const SALT_LIMB_SIZE: usize = 31;
+
+// Caller supplied salt, which is domain separated into the fingerprint
+// Fingerprints of the same transaction with different salts are unlinkable,
+// while anyone holding the salt can derive the salted fingerprint from the original one
+pub struct SaltComponent {
+    salt: Bytes,
+}
+
+impl fmt::Debug for SaltComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SaltComponent")
+            .field(
+                "salt",
+                &pii::scrubbed(PiiField::Salt, format!("{:?}", self.salt)),
+            )
+            .finish()
+    }
+}
+
+impl SaltComponent {
+    pub(crate) fn validate(salt: &[u8]) -> Result<(), Error> {
+        if salt.is_empty() || salt.len() > MAX_SALT_SIZE {
+            return Err(anyhow!(
+                "Salt should be from 1 to {} bytes long, given {} bytes",
+                MAX_SALT_SIZE,
+                salt.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Domain separated blinding of the fingerprint with the salt
+    /// Poseidon(SALT_DOMAIN | fingerprint | Poseidon(len | salt limbs))
+    pub fn blind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let salt = self.squeeze()?;
+
+        Ok(bind_scalar(SALT_DOMAIN_PREFIX, fingerprint, salt))
+    }
+}
+
+impl FingerprintComponent<Bytes, 32> for SaltComponent {
+    fn new(original: Bytes) -> Self {
+        Self { salt: original }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let written = buffer.write(&wire::encode_scalar(&self.squeeze()?))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &Bytes {
+        &self.salt
+    }
+}
+
+impl SqueezeComponent<Fr> for SaltComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        SaltComponent::validate(&self.salt)?;
+
+        Ok(squeeze_bytes(&self.salt))
+    }
+}
+
+/// Poseidon(len | limbs) of up to `MAX_SALT_SIZE` bytes
+/// Length goes first, so values with trailing zero bytes do not collide
+pub fn squeeze_bytes(bytes: &[u8]) -> Fr {
+    debug_assert!(bytes.len() <= MAX_SALT_SIZE);
+
+    let mut limbs = vec![Fr::from(bytes.len() as u64)];
+    for chunk in bytes.chunks(SALT_LIMB_SIZE) {
+        let mut buffer_32 = [0u8; 32];
+        buffer_32[0..chunk.len()].copy_from_slice(chunk);
+
+        limbs.push(Fr::from_bytes(&buffer_32).unwrap_or(Fr::zero()));
+    }
+
+    let mut poseidon = fingerprint_poseidon::<5, 4>();
+    poseidon.update(limbs.as_slice());
+
+    poseidon.squeeze()
+}
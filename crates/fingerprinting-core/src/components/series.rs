@@ -0,0 +1,77 @@
+use crate::components::extension::bind_scalar;
+use crate::components::salt::squeeze_bytes;
+use crate::components::{FingerprintComponent, SqueezeComponent};
+use crate::pii::{self, PiiField};
+use crate::{wire, SERIES_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
+use std::io::Write;
+
+/// Maximum size of the series identifier in bytes
+pub const MAX_SERIES_ID_SIZE: usize = 64;
+
+// Identifier of the standing order or the installment plan the transaction belongs to
+// Bound on top of the preimage hash, so installments of different series never share the fingerprint
+pub struct SeriesComponent {
+    series_id: String,
+}
+
+impl fmt::Debug for SeriesComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeriesComponent")
+            .field(
+                "series_id",
+                &pii::scrubbed(PiiField::SeriesId, &self.series_id),
+            )
+            .finish()
+    }
+}
+
+impl SeriesComponent {
+    pub(crate) fn validate(series_id: &str) -> Result<(), Error> {
+        if series_id.is_empty() || series_id.len() > MAX_SERIES_ID_SIZE {
+            return Err(anyhow!(
+                "Series identifier should be from 1 to {} bytes long, given {} bytes",
+                MAX_SERIES_ID_SIZE,
+                series_id.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Poseidon(SERIES_DOMAIN | fingerprint | Poseidon(len | series id limbs))
+    pub fn bind(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        let series = self.squeeze()?;
+
+        Ok(bind_scalar(SERIES_DOMAIN_PREFIX, fingerprint, series))
+    }
+}
+
+impl FingerprintComponent<String, 32> for SeriesComponent {
+    fn new(original: String) -> Self {
+        Self {
+            series_id: original,
+        }
+    }
+
+    fn serialize<W: Write>(&self, buffer: &mut W) -> Result<(), Error> {
+        let written = buffer.write(&wire::encode_scalar(&self.squeeze()?))?;
+
+        debug_assert_eq!(written, Self::size());
+        Ok(())
+    }
+
+    fn raw(&self) -> &String {
+        &self.series_id
+    }
+}
+
+impl SqueezeComponent<Fr> for SeriesComponent {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        SeriesComponent::validate(&self.series_id)?;
+
+        Ok(squeeze_bytes(self.series_id.as_bytes()))
+    }
+}
@@ -0,0 +1,188 @@
+//! Fingerprints of the on-chain transactions, computed via the same protocols as the fiat ones
+//!
+//! The protocol round evaluates the identity of the transaction, Poseidon(CRYPTO_DOMAIN | chain id | tx hash), in
+//! place of the date time of the fiat transaction, the evaluated scalar is hashed together with the addresses and the
+//! amount: Poseidon(evaluated | from | to | amount). The domain tag keeps the on-chain fingerprints apart from the fiat
+//! ones, the namespace and the salt are applied on top the same way.
+
+use crate::components::{
+    AddressComponent, Bytes32Component, FingerprintComponent, SaltComponent, SqueezeComponent,
+};
+use crate::namespace::Namespace;
+use crate::{fingerprint_poseidon, Compact, LocalFingerprint, CRYPTO_DOMAIN_PREFIX};
+use anyhow::Error;
+use bytes::Bytes;
+use halo2_axiom::halo2curves::bn256::Fr;
+use halo2_axiom::halo2curves::ff::PrimeField as PF;
+use std::marker::PhantomData;
+
+#[derive(Debug)]
+pub struct CryptoTransactionFingerprintData<F> {
+    chain_id: u64,
+    tx_hash: Bytes32Component,
+    from: AddressComponent,
+    to: AddressComponent,
+    /// Big-endian uint256 of the smallest units, e.g. wei
+    amount: Bytes32Component,
+    salt: Option<SaltComponent>,
+    namespace: Option<Namespace>,
+
+    _p: PhantomData<F>,
+}
+
+impl<F: PF> CryptoTransactionFingerprintData<F> {
+    pub fn new(
+        chain_id: u64,
+        tx_hash: Bytes32Component,
+        from: AddressComponent,
+        to: AddressComponent,
+        amount: Bytes32Component,
+    ) -> Self {
+        Self {
+            chain_id,
+            tx_hash,
+            from,
+            to,
+            amount,
+            salt: None,
+            namespace: None,
+            _p: PhantomData,
+        }
+    }
+
+    /// Domain separates the caller supplied salt into the fingerprint
+    pub fn with_salt(mut self, salt: Bytes) -> Result<Self, Error> {
+        SaltComponent::validate(&salt)?;
+
+        self.salt = Some(SaltComponent::new(salt));
+        Ok(self)
+    }
+
+    /// Separates the fingerprint into the namespace of the environment
+    pub fn with_namespace(mut self, namespace: Option<Namespace>) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    pub fn tx_hash(&self) -> &[u8; 32] {
+        self.tx_hash.raw()
+    }
+
+    pub fn from(&self) -> &Bytes {
+        self.from.raw()
+    }
+
+    pub fn to(&self) -> &Bytes {
+        self.to.raw()
+    }
+
+    pub fn amount(&self) -> &[u8; 32] {
+        self.amount.raw()
+    }
+}
+
+impl LocalFingerprint<Fr> for CryptoTransactionFingerprintData<Fr> {
+    /// Identity of the transaction on its chain, evaluated in place of the date time
+    fn unblinded_datetime(&self) -> Result<Fr, Error> {
+        let mut domain = [0u8; 32];
+        domain[0..CRYPTO_DOMAIN_PREFIX.len()].copy_from_slice(CRYPTO_DOMAIN_PREFIX.as_bytes());
+        let domain = Fr::from_bytes(&domain).unwrap_or(Fr::zero());
+
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+        poseidon.update(&[domain, Fr::from(self.chain_id), self.tx_hash.squeeze()?]);
+
+        Ok(poseidon.squeeze())
+    }
+
+    fn fingerprint(&self, evaluated: Fr) -> Result<Fr, Error> {
+        let mut poseidon = fingerprint_poseidon::<5, 4>();
+        poseidon.update(&[
+            evaluated,
+            self.from.squeeze()?,
+            self.to.squeeze()?,
+            self.amount.squeeze()?,
+        ]);
+        let fingerprint = poseidon.squeeze();
+
+        let fingerprint = match &self.namespace {
+            Some(namespace) => namespace.separate(fingerprint),
+            None => fingerprint,
+        };
+        let fingerprint = match &self.salt {
+            Some(salt) => salt.blind(fingerprint)?,
+            None => fingerprint,
+        };
+
+        log::info!(
+            "Crypto transaction fingerprint generated successfully: {}",
+            fingerprint.compact()
+        );
+
+        Ok(fingerprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CollaborativeProtocol, InProcessAgentsTopology, NaiveProtocol, ProtocolFingerprint,
+    };
+
+    fn transaction(chain_id: u64, from: u8, to: u8) -> CryptoTransactionFingerprintData<Fr> {
+        let mut amount = [0u8; 32];
+        amount[24..].copy_from_slice(&1_500_000_000_000_000_000u64.to_be_bytes());
+
+        CryptoTransactionFingerprintData::new(
+            chain_id,
+            Bytes32Component::new([7; 32]),
+            AddressComponent::new(Bytes::from(vec![from; 20])),
+            AddressComponent::new(Bytes::from(vec![to; 20])),
+            Bytes32Component::new(amount),
+        )
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_crypto_transaction_fingerprint() -> Result<(), Error> {
+        let secret = Fr::from(42);
+        let naive = NaiveProtocol::new(secret);
+        let fingerprint = transaction(1, 1, 2).complete_fingerprint(&naive).await?;
+
+        // The threshold protocol computes the same fingerprint
+        let (agent_info, topology) = InProcessAgentsTopology::share(secret, 2, 3);
+        let collaborative = CollaborativeProtocol::new(agent_info, topology);
+        assert_eq!(
+            transaction(1, 1, 2)
+                .complete_fingerprint(&collaborative)
+                .await?,
+            fingerprint
+        );
+
+        // Other chain, the transfer back and the salted transaction do not share the fingerprint
+        assert_ne!(
+            transaction(10, 1, 2).complete_fingerprint(&naive).await?,
+            fingerprint
+        );
+        assert_ne!(
+            transaction(1, 2, 1).complete_fingerprint(&naive).await?,
+            fingerprint
+        );
+        let salted = transaction(1, 1, 2).with_salt(Bytes::from_static(b"salt"))?;
+        assert_ne!(salted.complete_fingerprint(&naive).await?, fingerprint);
+
+        let empty = CryptoTransactionFingerprintData::<Fr>::new(
+            1,
+            Bytes32Component::new([7; 32]),
+            AddressComponent::new(Bytes::new()),
+            AddressComponent::new(Bytes::from(vec![2; 20])),
+            Bytes32Component::new([0; 32]),
+        );
+        assert!(empty.complete_fingerprint(&naive).await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+//! Differences between the fingerprints of the same transaction computed by two schema versions,
+//! for planning the migrations when a component, its encoding or the parameters change
+//!
+//! Both fingerprints are explained (see `explain`) and compared step by step: the serialized components of the
+//! preimage, the date time inputs and its evaluation, the wire version, the schema, the bound optional components,
+//! the namespace and the salt.
+//! Every step that differs is reported, so the report tells which change is behind the new fingerprint.
+
+use crate::explain::Explanation;
+use crate::Compact;
+use std::fmt;
+
+/// Step of the computation producing the different value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// Serialized component of the preimage, e.g. `bic`
+    Encoding(&'static str),
+    /// Seconds, days or nonce the date time scalar is squeezed from
+    DateTimeInputs,
+    /// Same date time scalar evaluated to the different point, the secret or the hash-to-curve changed
+    Evaluation,
+    /// Layout of the preimage
+    WireVersion(u8, u8),
+    /// Fingerprint schema of the preimage
+    Schema(u32, u32),
+    /// Optional component bound by the second version only
+    Added(String),
+    /// Optional component bound by the first version only
+    Removed(String),
+    /// Optional component bound by both versions with the different values
+    Changed(String),
+    /// Same optional components bound in the different order
+    Order,
+    Namespace(Option<String>, Option<String>),
+    Salt,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let namespace = |namespace: &Option<String>| namespace.clone().unwrap_or("none".into());
+
+        match self {
+            Difference::Encoding(component) => write!(f, "encoding of the {} changed", component),
+            Difference::DateTimeInputs => write!(f, "date time inputs changed"),
+            Difference::Evaluation => {
+                write!(f, "date time evaluation changed (secret or hash-to-curve)")
+            }
+            Difference::WireVersion(from, to) => {
+                write!(f, "wire version changed from {} to {}", from, to)
+            }
+            Difference::Schema(from, to) => write!(f, "schema changed from {} to {}", from, to),
+            Difference::Added(tag) => write!(f, "component {} is added", tag),
+            Difference::Removed(tag) => write!(f, "component {} is removed", tag),
+            Difference::Changed(tag) => write!(f, "value of the component {} changed", tag),
+            Difference::Order => write!(f, "components are bound in the different order"),
+            Difference::Namespace(from, to) => write!(
+                f,
+                "namespace changed from {} to {}",
+                namespace(from),
+                namespace(to)
+            ),
+            Difference::Salt => write!(f, "salt changed"),
+        }
+    }
+}
+
+/// Fingerprints of both versions and the steps they differ in
+#[derive(Debug, Clone)]
+pub struct FingerprintDiff {
+    pub from: String,
+    pub to: String,
+    pub differences: Vec<Difference>,
+}
+
+impl FingerprintDiff {
+    pub fn is_same(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/// Compares the explanations of the same transaction computed by the `from` and `to` versions
+pub fn diff(from: &Explanation, to: &Explanation) -> FingerprintDiff {
+    let mut differences = vec![];
+
+    let encodings = [
+        ("bic", &from.bic, &to.bic),
+        ("amount", &from.amount, &to.amount),
+        ("currency", &from.currency, &to.currency),
+    ];
+    for (component, from, to) in encodings {
+        if from != to {
+            differences.push(Difference::Encoding(component));
+        }
+    }
+    if from.date_time_inputs != to.date_time_inputs {
+        differences.push(Difference::DateTimeInputs);
+    } else if from.date_time_fingerprint != to.date_time_fingerprint {
+        differences.push(Difference::Evaluation);
+    }
+    if from.wire_version != to.wire_version {
+        differences.push(Difference::WireVersion(
+            from.wire_version.as_u8(),
+            to.wire_version.as_u8(),
+        ));
+    }
+    if from.schema_id != to.schema_id {
+        differences.push(Difference::Schema(from.schema_id, to.schema_id));
+    }
+
+    let tags = |explanation: &Explanation| {
+        explanation
+            .bound_components
+            .iter()
+            .map(|(tag, _)| tag.clone())
+            .collect::<Vec<_>>()
+    };
+    let (from_tags, to_tags) = (tags(from), tags(to));
+    for (tag, value) in from.bound_components.iter() {
+        match to.bound_components.iter().find(|(other, _)| other == tag) {
+            Some((_, other)) if other != value => {
+                differences.push(Difference::Changed(tag.clone()))
+            }
+            Some(_) => {}
+            None => differences.push(Difference::Removed(tag.clone())),
+        }
+    }
+    for tag in to_tags.iter().filter(|tag| !from_tags.contains(tag)) {
+        differences.push(Difference::Added(tag.clone()));
+    }
+    let common = |tags: &[String], others: &[String]| {
+        tags.iter()
+            .filter(|tag| others.contains(tag))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    if common(&from_tags, &to_tags) != common(&to_tags, &from_tags) {
+        differences.push(Difference::Order);
+    }
+
+    if from.namespace != to.namespace {
+        differences.push(Difference::Namespace(
+            from.namespace.clone(),
+            to.namespace.clone(),
+        ));
+    }
+    if from.salt != to.salt {
+        differences.push(Difference::Salt);
+    }
+
+    FingerprintDiff {
+        from: from.fingerprint.compact(),
+        to: to.fingerprint.compact(),
+        differences,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explain::ExplainSecret;
+    use crate::namespace::Namespace;
+    use crate::TransactionFingerprintData;
+    use anyhow::Error;
+    use chrono::{TimeZone, Utc};
+    use fingerprinting_types::{RawTransaction, RawTransactionBuilder};
+    use halo2_axiom::halo2curves::bn256::Fr;
+
+    fn transaction() -> Result<RawTransaction, Error> {
+        let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+
+        Ok(RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount((1000u64, "EUR"))
+            .merchant_id(Some("MID-1".to_string()))
+            .date_time(tx_date)
+            .wwd(tx_date.date_naive())
+            .build()?)
+    }
+
+    #[test]
+    fn test_fingerprint_diff() -> Result<(), Error> {
+        let secret = ExplainSecret::Naive(Fr::from(42));
+        let explain = |tx: RawTransaction| -> Result<Explanation, Error> {
+            TransactionFingerprintData::<Fr>::try_from(tx)?.explain(&secret)
+        };
+
+        let current = explain(transaction()?)?;
+        let same = diff(&current, &explain(transaction()?)?);
+        assert!(same.is_same());
+        assert!(same.differences.is_empty());
+
+        // Merchant dropped and the country added by the new version
+        let migrated = explain(RawTransaction {
+            merchant_id: None,
+            country_code: Some("LU".into()),
+            ..transaction()?
+        })?;
+        let migration = diff(&current, &migrated);
+        assert!(!migration.is_same());
+        assert_eq!(
+            migration.differences,
+            vec![
+                Difference::Removed(crate::MERCHANT_DOMAIN_PREFIX.into()),
+                Difference::Added(crate::COUNTRY_DOMAIN_PREFIX.into())
+            ]
+        );
+
+        // Another secret and namespace
+        let tx: TransactionFingerprintData<Fr> = transaction()?.try_into()?;
+        let rotated = tx
+            .with_namespace(Some(Namespace::new("prod")?))
+            .explain(&ExplainSecret::Naive(Fr::from(43)))?;
+        assert_eq!(
+            diff(&current, &rotated).differences,
+            vec![
+                Difference::Evaluation,
+                Difference::Namespace(None, Some("prod".into()))
+            ]
+        );
+
+        Ok(())
+    }
+}
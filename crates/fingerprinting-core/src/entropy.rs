@@ -0,0 +1,431 @@
+//! Randomness of the blinding factors and the generated secrets
+//!
+//! It is drawn from the `EntropySource`, the operating system RNG by default. Deployments bound by the cryptographic
+//! module requirements draw it from the hardware device (HSM, TPM, `/dev/hwrng`) either directly or through
+//! the HMAC-DRBG of NIST SP 800-90A seeded and reseeded from the device. Every draw passes the continuous health
+//! tests of the `HealthTested` source, the stuck or repeating source fails the computation rather than producing
+//! the predictable blinding factors.
+
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+use halo2_axiom::halo2curves::ff::{Field, FromUniformBytes};
+use hmac::{Hmac, Mac};
+use rand_core::{CryptoRng, OsRng, RngCore};
+use sha2::Sha256;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Consecutive identical bytes failing the repetition count test, SP 800-90B 4.4.1 for the false positive
+/// probability of 2^-40 at the assumed min-entropy of 4 bits per byte
+pub const REPETITION_CUTOFF: usize = 11;
+
+/// Bytes of the consecutive draws compared by the continuous test
+const COMPARED_BYTES: usize = 16;
+
+pub trait EntropySource: Send + Sync {
+    /// Fills the `dest` with the random bytes
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), Error>;
+
+    /// Draws the sample and tests it, run before the source is used
+    fn health_check(&self) -> Result<(), Error> {
+        let mut sample = [0u8; 256];
+        self.fill_bytes(&mut sample)?;
+        repetition_count_test(&sample)
+    }
+}
+
+/// The operating system RNG
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OsEntropy;
+
+impl EntropySource for OsEntropy {
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), Error> {
+        OsRng
+            .try_fill_bytes(dest)
+            .map_err(|e| anyhow!("OS entropy source failed: {}", e))
+    }
+}
+
+/// Character device of the hardware RNG, e.g. `/dev/hwrng` of the HSM or TPM
+pub struct DeviceEntropy {
+    path: PathBuf,
+    device: Mutex<File>,
+}
+
+impl DeviceEntropy {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let device = File::open(&path)
+            .map_err(|e| anyhow!("Cannot open entropy device {}: {}", path.display(), e))?;
+
+        Ok(Self {
+            path,
+            device: Mutex::new(device),
+        })
+    }
+}
+
+impl EntropySource for DeviceEntropy {
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), Error> {
+        self.device
+            .lock()
+            .map_err(|_| anyhow!("Entropy device {} is poisoned", self.path.display()))?
+            .read_exact(dest)
+            .map_err(|e| anyhow!("Entropy device {} failed: {}", self.path.display(), e))
+    }
+}
+
+/// HMAC-DRBG with SHA-256 of NIST SP 800-90A, seeded from the `seed` source and reseeded from it
+/// after every `reseed_interval` requests
+pub struct HmacDrbg {
+    seed: Arc<dyn EntropySource>,
+    reseed_interval: u64,
+    state: Mutex<DrbgState>,
+}
+
+struct DrbgState {
+    key: [u8; 32],
+    value: [u8; 32],
+    requests: u64,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Entropy input of the instantiation and the reseeding, 256 bits of the security strength and the nonce
+const SEED_SIZE: usize = 48;
+
+/// Largest request of a single generation, the longer ones are split
+const MAX_REQUEST_SIZE: usize = 1 << 16;
+
+const PERSONALIZATION: &[u8] = b"CRA_FP_DRBG";
+
+impl HmacDrbg {
+    /// Instantiated from the `seed` source, reseeded after 2^16 requests by default
+    pub fn new(seed: Arc<dyn EntropySource>) -> Result<Self, Error> {
+        let mut state = DrbgState {
+            key: [0u8; 32],
+            value: [1u8; 32],
+            requests: 0,
+        };
+        let mut entropy = [0u8; SEED_SIZE];
+        seed.fill_bytes(&mut entropy)?;
+        state.update(&[&entropy, PERSONALIZATION]);
+
+        Ok(Self {
+            seed,
+            reseed_interval: 1 << 16,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Requests served before reseeding from the seed source
+    pub fn with_reseed_interval(mut self, reseed_interval: u64) -> Self {
+        self.reseed_interval = reseed_interval.max(1);
+        self
+    }
+}
+
+impl DrbgState {
+    fn hmac(key: &[u8; 32], data: &[&[u8]]) -> [u8; 32] {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+        for data in data {
+            mac.update(data);
+        }
+        mac.finalize().into_bytes().into()
+    }
+
+    /// HMAC_DRBG_Update of the `provided` data
+    fn update(&mut self, provided: &[&[u8]]) {
+        let provided_len = provided.iter().map(|data| data.len()).sum::<usize>();
+        for round in [0x00u8, 0x01] {
+            let round = [round];
+            let mut data = vec![&self.value[..], &round[..]];
+            data.extend_from_slice(provided);
+            self.key = Self::hmac(&self.key, &data);
+            self.value = Self::hmac(&self.key, &[&self.value]);
+            if provided_len == 0 {
+                break;
+            }
+        }
+    }
+
+    fn generate(&mut self, dest: &mut [u8]) {
+        for block in dest.chunks_mut(32) {
+            self.value = Self::hmac(&self.key, &[&self.value]);
+            block.copy_from_slice(&self.value[..block.len()]);
+        }
+        self.update(&[]);
+        self.requests += 1;
+    }
+}
+
+impl EntropySource for HmacDrbg {
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), Error> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow!("DRBG state is poisoned"))?;
+        for request in dest.chunks_mut(MAX_REQUEST_SIZE) {
+            if state.requests >= self.reseed_interval {
+                let mut entropy = [0u8; SEED_SIZE];
+                self.seed.fill_bytes(&mut entropy)?;
+                state.update(&[&entropy]);
+                state.requests = 0;
+                log::debug!("DRBG is reseeded");
+            }
+            state.generate(request);
+        }
+
+        Ok(())
+    }
+
+    /// Tests the seed source as well
+    fn health_check(&self) -> Result<(), Error> {
+        self.seed.health_check()?;
+        let mut sample = [0u8; 256];
+        self.fill_bytes(&mut sample)?;
+        repetition_count_test(&sample)
+    }
+}
+
+/// Source running the continuous health tests on every draw: the repetition count test of SP 800-90B
+/// and the comparison with the previous draw of FIPS 140-2
+pub struct HealthTested<S> {
+    source: S,
+    previous: Mutex<Option<[u8; COMPARED_BYTES]>>,
+}
+
+impl<S: EntropySource> HealthTested<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            previous: Mutex::new(None),
+        }
+    }
+}
+
+impl<S: EntropySource> EntropySource for HealthTested<S> {
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), Error> {
+        self.source.fill_bytes(dest)?;
+        repetition_count_test(dest)?;
+
+        if dest.len() >= COMPARED_BYTES {
+            let mut previous = self
+                .previous
+                .lock()
+                .map_err(|_| anyhow!("Entropy health test is poisoned"))?;
+            let drawn: [u8; COMPARED_BYTES] = dest[..COMPARED_BYTES].try_into()?;
+            if previous.replace(drawn) == Some(drawn) {
+                return Err(anyhow!(
+                    "Entropy source failed the continuous test, the draw repeats the previous one"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn health_check(&self) -> Result<(), Error> {
+        self.source.health_check()?;
+        let mut sample = [0u8; 256];
+        self.fill_bytes(&mut sample)?;
+        self.fill_bytes(&mut sample)
+    }
+}
+
+/// Fails the `sample` with the run of `REPETITION_CUTOFF` identical bytes
+pub fn repetition_count_test(sample: &[u8]) -> Result<(), Error> {
+    let mut run = 1;
+    for pair in sample.windows(2) {
+        run = if pair[0] == pair[1] { run + 1 } else { 1 };
+        if run >= REPETITION_CUTOFF {
+            return Err(anyhow!(
+                "Entropy source failed the repetition count test, {} identical bytes in a row",
+                run
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// OS RNG with the continuous health tests
+pub fn default_source() -> Arc<dyn EntropySource> {
+    Arc::new(HealthTested::new(OsEntropy))
+}
+
+/// Uniform non-zero scalar, the wide reduction of 512 bits leaves no noticeable bias
+pub fn random_scalar(source: &dyn EntropySource) -> Result<Fr, Error> {
+    loop {
+        let mut bytes = [0u8; 64];
+        source.fill_bytes(&mut bytes)?;
+        let scalar = Fr::from_uniform_bytes(&bytes);
+        if !bool::from(scalar.is_zero()) {
+            return Ok(scalar);
+        }
+    }
+}
+
+/// `RngCore` of the entropy source for the generic code, such as the secret sharing of any field.
+/// The first failure of the source is kept and the remaining draws are zeroes, so the result
+/// must be discarded when `failure` returns it
+pub struct EntropyRng<'a> {
+    source: &'a dyn EntropySource,
+    failure: Option<Error>,
+}
+
+impl<'a> EntropyRng<'a> {
+    pub fn new(source: &'a dyn EntropySource) -> Self {
+        Self {
+            source,
+            failure: None,
+        }
+    }
+
+    /// First failure of the source, if any
+    pub fn failure(self) -> Option<Error> {
+        self.failure
+    }
+}
+
+impl RngCore for EntropyRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if self.failure.is_none() {
+            if let Err(e) = self.source.fill_bytes(dest) {
+                self.failure = Some(e);
+            }
+        }
+        if self.failure.is_some() {
+            dest.fill(0);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.source
+            .fill_bytes(dest)
+            .map_err(|e| rand_core::Error::new(e.into_boxed_dyn_error()))
+    }
+}
+
+impl CryptoRng for EntropyRng<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Source repeating the same byte
+    struct Stuck;
+
+    impl EntropySource for Stuck {
+        fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), Error> {
+            dest.fill(0x5a);
+            Ok(())
+        }
+    }
+
+    /// Source repeating the same block
+    struct Repeating;
+
+    impl EntropySource for Repeating {
+        fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), Error> {
+            dest.iter_mut()
+                .enumerate()
+                .for_each(|(i, byte)| *byte = i as u8);
+            Ok(())
+        }
+    }
+
+    /// Counter source, counting the draws
+    #[derive(Default)]
+    struct Counting(AtomicU64);
+
+    impl EntropySource for Counting {
+        fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), Error> {
+            let draw = self.0.fetch_add(1, Ordering::Relaxed);
+            dest.iter_mut()
+                .zip(draw.to_le_bytes().iter().cycle())
+                .for_each(|(byte, b)| *byte = *b);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_health_tests() {
+        assert!(HealthTested::new(OsEntropy).health_check().is_ok());
+        assert!(HealthTested::new(Stuck).health_check().is_err());
+
+        // Every single draw passes the repetition count test, the second one repeats the first
+        let repeating = HealthTested::new(Repeating);
+        let mut draw = [0u8; 32];
+        assert!(repeating.fill_bytes(&mut draw).is_ok());
+        assert!(repeating.fill_bytes(&mut draw).is_err());
+        assert!(repeating.health_check().is_err());
+
+        assert!(random_scalar(&HealthTested::new(Stuck)).is_err());
+    }
+
+    #[test]
+    fn test_hmac_drbg() -> Result<(), Error> {
+        // NIST CAVP HMAC_DRBG SHA-256, no prediction resistance, no reseed, the first vector
+        let entropy =
+            hex::decode("ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488")?;
+        let nonce = hex::decode("659ba96c601dc69fc902940805ec0ca8")?;
+        let mut state = DrbgState {
+            key: [0u8; 32],
+            value: [1u8; 32],
+            requests: 0,
+        };
+        state.update(&[&entropy, &nonce]);
+        let mut output = [0u8; 128];
+        state.generate(&mut output);
+        state.generate(&mut output);
+        assert_eq!(
+            hex::encode(output),
+            "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89\
+             d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1\
+             07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668\
+             961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8"
+        );
+
+        // Reseeded from the source after every 2 requests
+        let seed = Arc::new(Counting::default());
+        let drbg = HmacDrbg::new(seed.clone())?.with_reseed_interval(2);
+        let mut draw = [0u8; 100];
+        for _ in 0..5 {
+            drbg.fill_bytes(&mut draw)?;
+        }
+        assert_eq!(seed.0.load(Ordering::Relaxed), 3);
+        assert!(drbg.health_check().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entropy_rng() {
+        let mut rng = EntropyRng::new(&Stuck);
+        assert_eq!(rng.next_u32(), 0x5a5a5a5a);
+        assert!(rng.failure().is_none());
+
+        struct Failing;
+        impl EntropySource for Failing {
+            fn fill_bytes(&self, _: &mut [u8]) -> Result<(), Error> {
+                Err(anyhow!("Device is gone"))
+            }
+        }
+        let mut rng = EntropyRng::new(&Failing);
+        assert_eq!(rng.next_u64(), 0);
+        assert!(rng.try_fill_bytes(&mut [0u8; 8]).is_err());
+        assert!(rng.failure().is_some());
+    }
+}
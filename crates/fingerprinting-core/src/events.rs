@@ -0,0 +1,180 @@
+//! Lifecycle events of the fingerprinting the side effects (metrics, webhooks, Kafka, audit log, etc.) subscribe to
+//!
+//! Services publish the events to the `EventBus` rather than calling the side effects from their methods, so the new
+//! side effect is the new subscriber and the services stay unchanged. Publishing never blocks nor fails the publisher:
+//! the events published without the subscribers are dropped, the subscribers lagging behind skip the oldest events.
+//! Events carry the fingerprints and the agents only, never the transaction data, see `pii`.
+
+use chrono::{DateTime, Utc};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+/// Events kept for the subscribers lagging behind
+pub const EVENTS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    /// Fingerprint is completed by the protocol round
+    FingerprintComputed { fingerprint: Fr, at: DateTime<Utc> },
+
+    /// Stored fingerprint is seen once more within the duplicate window
+    DuplicateDetected {
+        fingerprint: Fr,
+        first_seen: DateTime<Utc>,
+        at: DateTime<Utc>,
+    },
+
+    /// Agent is excluded from the topology, the fingerprints are computed without it
+    AgentExcluded { agent: usize, reason: String },
+
+    /// Share of the agent is replaced by the share of the same secret, e.g. by the resharing ceremony
+    ShareRotated { agent: usize, at: DateTime<Utc> },
+}
+
+impl LifecycleEvent {
+    /// Name of the event, e.g. for the metric labels
+    pub fn name(&self) -> &'static str {
+        match self {
+            LifecycleEvent::FingerprintComputed { .. } => "fingerprint_computed",
+            LifecycleEvent::DuplicateDetected { .. } => "duplicate_detected",
+            LifecycleEvent::AgentExcluded { .. } => "agent_excluded",
+            LifecycleEvent::ShareRotated { .. } => "share_rotated",
+        }
+    }
+}
+
+impl fmt::Display for LifecycleEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LifecycleEvent::FingerprintComputed { fingerprint, at } => {
+                write!(f, "{} {:?} at {}", self.name(), fingerprint, at)
+            }
+            LifecycleEvent::DuplicateDetected {
+                fingerprint,
+                first_seen,
+                at,
+            } => write!(
+                f,
+                "{} {:?} at {}, first seen at {}",
+                self.name(),
+                fingerprint,
+                at,
+                first_seen
+            ),
+            LifecycleEvent::AgentExcluded { agent, reason } => {
+                write!(f, "{} {}: {}", self.name(), agent, reason)
+            }
+            LifecycleEvent::ShareRotated { agent, at } => {
+                write!(f, "{} {} at {}", self.name(), agent, at)
+            }
+        }
+    }
+}
+
+pub trait EventBus: Send + Sync {
+    /// Delivers the event to the current subscribers, never blocks nor fails
+    fn publish(&self, event: LifecycleEvent);
+
+    /// Events published from now on
+    fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent>;
+}
+
+/// Bus within the single process over the tokio broadcast channel
+pub struct BroadcastEventBus {
+    sender: broadcast::Sender<LifecycleEvent>,
+}
+
+impl BroadcastEventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+
+        Self { sender }
+    }
+}
+
+impl Default for BroadcastEventBus {
+    fn default() -> Self {
+        Self::new(EVENTS_CAPACITY)
+    }
+}
+
+impl EventBus for BroadcastEventBus {
+    fn publish(&self, event: LifecycleEvent) {
+        // Error means there are no subscribers at the moment, nothing to do
+        let _ = self.sender.send(event);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Runs the `sink` on every event published from now on until the bus is dropped.
+/// The sink runs on the single task, so the slow sink skips the events rather than slowing the publishers
+pub fn spawn_sink<S>(bus: &dyn EventBus, name: &str, mut sink: S) -> JoinHandle<()>
+where
+    S: FnMut(LifecycleEvent) + Send + 'static,
+{
+    let mut events = bus.subscribe();
+    let name = name.to_string();
+
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => sink(event),
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Event sink {} skipped {} events", name, skipped)
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_event_bus() {
+        let bus = BroadcastEventBus::new(2);
+        let at = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        // Events without the subscribers are dropped
+        bus.publish(LifecycleEvent::ShareRotated { agent: 1, at });
+
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = spawn_sink(&bus, "test", {
+            let received = received.clone();
+            move |event| received.lock().unwrap().push(event)
+        });
+        let mut lagging = bus.subscribe();
+
+        let computed = LifecycleEvent::FingerprintComputed {
+            fingerprint: Fr::from(42),
+            at,
+        };
+        let excluded = LifecycleEvent::AgentExcluded {
+            agent: 2,
+            reason: "protocol version 1".to_string(),
+        };
+        for event in [computed.clone(), excluded.clone(), computed.clone()] {
+            bus.publish(event);
+            tokio::task::yield_now().await;
+        }
+
+        // Lagging subscriber skips the oldest events
+        assert!(matches!(lagging.recv().await, Err(RecvError::Lagged(1))));
+        assert_eq!(lagging.recv().await.unwrap(), excluded);
+
+        drop(bus);
+        sink.await.unwrap();
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![computed.clone(), excluded, computed]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Step by step computation of the fingerprint exposing every intermediate value,
+//! used for debugging mismatches with external implementations
+//!
+//! The secret is held locally, either as a whole (naive mode) or as the threshold of shares (in-process cooperative mode),
+//! so the values are deterministic and the blinding is skipped.
+
+use crate::components::{bind_scalar, FingerprintComponent, SqueezeComponent};
+use crate::secret_sharing::SecretSharing;
+use crate::wire::WireVersion;
+use crate::{hash_to_curve, HashSqueeze, TransactionFingerprintData};
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+
+/// Secret the date time scalar is evaluated with
+#[derive(Debug, Clone)]
+pub enum ExplainSecret {
+    Naive(Fr),
+    /// Agent positions and their shares, all of them take part in the reconstruction
+    Shares(Vec<(usize, Fr)>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    /// Serialized components, as they are written to the preimage
+    pub bic: Bytes,
+    pub amount: Bytes,
+    pub currency: Bytes,
+    /// Bought leg of the FX transaction, bound on top of the preimage hash
+    pub counter_amount: Option<Bytes>,
+
+    /// Poseidon inputs of the date time component: seconds since epoch, days since epoch and the nonce
+    pub date_time_inputs: [Fr; 3],
+    pub date_time_scalar: Fr,
+    pub curve_point: G1,
+
+    /// Partial evaluations `[k_i] P` of the agents, empty in naive mode
+    pub partial_shares: Vec<(usize, G1)>,
+    /// Reconstructed `[k] P`
+    pub evaluated_point: G1,
+    pub date_time_fingerprint: Fr,
+
+    pub preimage: Bytes,
+    /// Layout of the preimage, the version follows the prefix since V2
+    pub wire_version: WireVersion,
+    /// Fingerprint schema, its id follows the components in the preimage unless it is the built-in one
+    pub schema_id: u32,
+    /// Optional components bound on top of the preimage hash, as their domain tags and squeezed values
+    pub bound_components: Vec<(String, Fr)>,
+    /// Namespace the unsalted fingerprint is separated into
+    pub namespace: Option<String>,
+    /// Poseidon of the preimage with the bound optional components, separated into the namespace when it is set
+    pub unsalted_fingerprint: Fr,
+    /// Squeezed salt applied on top of the unsalted fingerprint
+    pub salt: Option<Fr>,
+    pub fingerprint: Fr,
+}
+
+impl TransactionFingerprintData<Fr> {
+    /// Computes the fingerprint the same way as the protocols do, keeping the intermediate values
+    pub fn explain(&self, secret: &ExplainSecret) -> Result<Explanation, Error> {
+        let mut bic = Vec::with_capacity(32);
+        self.bic.serialize(&mut bic)?;
+        let mut amount = Vec::with_capacity(32);
+        self.amount.serialize(&mut amount)?;
+        let mut currency = Vec::with_capacity(32);
+        self.currency.serialize(&mut currency)?;
+        let counter_amount = match &self.counter_amount {
+            Some(counter_amount) => {
+                let mut serialized = Vec::with_capacity(34);
+                counter_amount.serialize(&mut serialized)?;
+                Some(Bytes::from(serialized))
+            }
+            None => None,
+        };
+
+        let date_time_inputs = self.date_time.inputs()?;
+        let date_time_scalar = self.date_time.squeeze()?;
+        let curve_point = hash_to_curve(&date_time_scalar);
+
+        let (partial_shares, evaluated_point) = match secret {
+            ExplainSecret::Naive(secret) => (vec![], curve_point * secret),
+            ExplainSecret::Shares(shares) => {
+                if shares.is_empty() {
+                    return Err(anyhow!("At least one share is required"));
+                }
+                let indices = shares.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+                let partial_shares = shares
+                    .iter()
+                    .map(|(i, share)| (*i, curve_point * share))
+                    .collect::<Vec<_>>();
+
+                let mut evaluated_point = G1::default(); // zero point
+                for (i, e_i) in partial_shares.iter() {
+                    evaluated_point +=
+                        e_i * SecretSharing::<Fr>::lagrange_coefficient(*i, &indices);
+                }
+
+                (partial_shares, evaluated_point)
+            }
+        };
+
+        let date_time_fingerprint = evaluated_point.squeeze()?;
+        let preimage = self.preimage(date_time_fingerprint)?;
+        let unsalted_fingerprint = preimage.squeeze()?;
+        let bound_components = self.bound_components()?;
+        let unsalted_fingerprint = bound_components
+            .iter()
+            .fold(unsalted_fingerprint, |fingerprint, (tag, value)| {
+                bind_scalar(tag, fingerprint, *value)
+            });
+        let unsalted_fingerprint = match &self.namespace {
+            Some(namespace) => namespace.separate(unsalted_fingerprint),
+            None => unsalted_fingerprint,
+        };
+        let fingerprint = match &self.salt {
+            Some(salt) => salt.blind(unsalted_fingerprint)?,
+            None => unsalted_fingerprint,
+        };
+
+        Ok(Explanation {
+            bic: bic.into(),
+            amount: amount.into(),
+            currency: currency.into(),
+            counter_amount,
+            date_time_inputs,
+            date_time_scalar,
+            curve_point,
+            partial_shares,
+            evaluated_point,
+            date_time_fingerprint,
+            preimage,
+            wire_version: self.wire_version(),
+            schema_id: self.schema_id(),
+            bound_components: bound_components
+                .into_iter()
+                .map(|(tag, value)| (tag.to_string(), value))
+                .collect(),
+            namespace: self
+                .namespace
+                .as_ref()
+                .map(|namespace| namespace.name().to_string()),
+            unsalted_fingerprint,
+            salt: self.salt.as_ref().map(|salt| salt.squeeze()).transpose()?,
+            fingerprint,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespace::Namespace;
+    use crate::{NaiveProtocol, ProtocolFingerprint};
+    use chrono::{TimeZone, Utc};
+    use fingerprinting_types::RawTransactionBuilder;
+    use halo2_axiom::arithmetic::Field;
+    use rand_core::OsRng;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_explain() -> Result<(), Error> {
+        let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        let tx: TransactionFingerprintData<Fr> = RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount((1000u64, "EUR"))
+            .date_time(tx_date)
+            .wwd(tx_date.date_naive())
+            .build()?
+            .try_into()?;
+        let tx = tx.with_salt(Bytes::from_static(b"consumer-a"))?;
+
+        let secret = Fr::random(OsRng);
+        let expected = tx
+            .complete_fingerprint(&NaiveProtocol::new(secret))
+            .await?
+            .into_inner();
+
+        let naive = tx.explain(&ExplainSecret::Naive(secret))?;
+        assert_eq!(naive.fingerprint, expected);
+        assert_eq!(naive.bic.as_ref(), b"BCEELU");
+        assert_eq!(naive.currency.as_ref(), 978u16.to_be_bytes());
+        assert_eq!(
+            naive.preimage.len(),
+            TransactionFingerprintData::<Fr>::fingerprint_size()
+        );
+
+        let sss = SecretSharing::generate(secret, 3, 5);
+        let shares = [1, 3, 5]
+            .iter()
+            .map(|i| (*i, sss.get_shares()[i]))
+            .collect::<Vec<_>>();
+        let cooperative = tx.explain(&ExplainSecret::Shares(shares))?;
+        assert_eq!(cooperative.partial_shares.len(), 3);
+        assert_eq!(cooperative.evaluated_point, naive.evaluated_point);
+        assert_eq!(cooperative.fingerprint, expected);
+
+        let tx = tx.with_namespace(Some(Namespace::new("staging")?));
+        let namespaced = tx.explain(&ExplainSecret::Naive(secret))?;
+        assert_eq!(namespaced.preimage, naive.preimage);
+        assert_ne!(namespaced.fingerprint, expected);
+        assert_eq!(
+            namespaced.fingerprint,
+            tx.complete_fingerprint(&NaiveProtocol::new(secret))
+                .await?
+                .into_inner()
+        );
+
+        Ok(())
+    }
+}
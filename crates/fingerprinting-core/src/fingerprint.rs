@@ -0,0 +1,128 @@
+//! Fingerprint computed via the protocol round, see `ProtocolFingerprint`
+//!
+//! Wraps the field element, so the fingerprints are not mixed up with the other scalars (secrets, shares, blinding
+//! factors). Displayed and parsed as the compact base58 string, converted to and from the 32 bytes of the wire.
+
+use crate::{wire, Compact};
+use anyhow::Error;
+use halo2_axiom::halo2curves::bn256::Fr;
+use halo2_axiom::halo2curves::ff::PrimeField as PF;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint<F = Fr>(F);
+
+impl<F: PF> Fingerprint<F> {
+    pub fn new(value: F) -> Self {
+        Self(value)
+    }
+
+    pub fn value(&self) -> &F {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> F {
+        self.0
+    }
+}
+
+/// Hashes the canonical representation, equal fingerprints have the same one
+impl<F: PF> Hash for Fingerprint<F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_repr().as_ref().hash(state);
+    }
+}
+
+impl<F: PF> From<F> for Fingerprint<F> {
+    fn from(value: F) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Fingerprint> for Fr {
+    fn from(fingerprint: Fingerprint) -> Self {
+        fingerprint.0
+    }
+}
+
+impl From<Fingerprint> for [u8; 32] {
+    fn from(fingerprint: Fingerprint) -> Self {
+        wire::encode_scalar(&fingerprint.0)
+    }
+}
+
+impl TryFrom<[u8; 32]> for Fingerprint {
+    type Error = Error;
+
+    fn try_from(bytes: [u8; 32]) -> Result<Self, Error> {
+        Ok(Self(wire::decode_scalar(&bytes)?))
+    }
+}
+
+impl Compact for Fingerprint {
+    fn compact(&self) -> String {
+        self.0.compact()
+    }
+
+    fn unwrap(compacted: &str) -> Result<Self, Error> {
+        Ok(Self(Fr::unwrap(compacted)?))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.compact())
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = Error;
+
+    fn from_str(compacted: &str) -> Result<Self, Error> {
+        Self::unwrap(compacted)
+    }
+}
+
+/// Serialized as the compact string, the way the fingerprints are displayed
+#[cfg(feature = "serde")]
+impl serde::Serialize for Fingerprint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Fingerprint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let compacted = String::deserialize(deserializer)?;
+
+        compacted.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_fingerprint_conversions() -> Result<(), Error> {
+        let fingerprint = Fingerprint::new(Fr::from(42));
+
+        assert_eq!(fingerprint.to_string(), Fr::from(42).compact());
+        assert_eq!(fingerprint.to_string().parse::<Fingerprint>()?, fingerprint);
+        assert!("0OIl".parse::<Fingerprint>().is_err());
+
+        let bytes: [u8; 32] = fingerprint.into();
+        assert_eq!(Fingerprint::try_from(bytes)?, fingerprint);
+        assert!(Fingerprint::try_from([0xff; 32]).is_err());
+        assert_eq!(Fr::from(fingerprint), Fr::from(42));
+
+        let fingerprints = HashSet::from([fingerprint, Fingerprint::from(Fr::from(42))]);
+        assert_eq!(fingerprints.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+//! Time-bucketed fuzzy fingerprints, for the near-duplicate detection of the transactions recorded by the several
+//! systems a few seconds apart
+//!
+//! The date time is truncated to the start of its bucket (minute or hour) before it is squeezed, the truncated date
+//! time is evaluated by the same protocol round as the exact one and the fingerprint is completed the usual way.
+//! The result is bound to the bucket (`Poseidon(FUZZY_DOMAIN | fingerprint | bucket seconds)`), so the fuzzy
+//! fingerprints never match the exact ones, nor the fuzzy ones of the other bucket.
+//!
+//! Buckets are aligned to the epoch of the Unix time, the transactions recorded on the both sides of the bucket
+//! boundary keep the different fuzzy fingerprints however close they are.
+
+use crate::components::{bind_scalar, SqueezeComponent};
+use crate::{
+    Fingerprint, FingerprintProtocol, LocalFingerprint, TransactionFingerprintData,
+    FUZZY_DOMAIN_PREFIX,
+};
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Utc};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::fmt;
+use std::str::FromStr;
+
+/// Granularity the date time of the fuzzy fingerprint is truncated to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeBucket {
+    Minute,
+    Hour,
+}
+
+impl TimeBucket {
+    pub fn seconds(&self) -> i64 {
+        match self {
+            TimeBucket::Minute => 60,
+            TimeBucket::Hour => 3600,
+        }
+    }
+
+    /// Start of the bucket the date time falls into
+    pub fn truncate(&self, date_time: &DateTime<Utc>) -> DateTime<Utc> {
+        let start = date_time.timestamp().div_euclid(self.seconds()) * self.seconds();
+
+        DateTime::from_timestamp(start, 0).unwrap_or(*date_time)
+    }
+}
+
+impl fmt::Display for TimeBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeBucket::Minute => "minute",
+            TimeBucket::Hour => "hour",
+        })
+    }
+}
+
+impl FromStr for TimeBucket {
+    type Err = Error;
+
+    fn from_str(bucket: &str) -> Result<Self, Self::Err> {
+        match bucket {
+            "minute" => Ok(TimeBucket::Minute),
+            "hour" => Ok(TimeBucket::Hour),
+            _ => Err(anyhow!(
+                "Time bucket {} should be either minute or hour",
+                bucket
+            )),
+        }
+    }
+}
+
+/// Exact fingerprint of the transaction with its fuzzy fingerprint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzyFingerprints {
+    pub exact: Fingerprint,
+    pub fuzzy: Fingerprint,
+}
+
+impl TransactionFingerprintData<Fr> {
+    /// Date time truncated to the `bucket`, evaluated by the protocol round of the fuzzy fingerprint
+    pub fn fuzzy_unblinded_datetime(&self, bucket: TimeBucket) -> Result<Fr, Error> {
+        self.date_time_component().bucketed(bucket).squeeze()
+    }
+
+    /// Completes the fuzzy fingerprint with the `date_time` scalar evaluated from `fuzzy_unblinded_datetime`
+    pub fn fuzzy_fingerprint_with_datetime_scalar(
+        &self,
+        bucket: TimeBucket,
+        date_time: Fr,
+    ) -> Result<Fr, Error> {
+        let fingerprint = self.fingerprint_with_datetime_scalar(date_time)?;
+
+        Ok(bind_scalar(
+            FUZZY_DOMAIN_PREFIX,
+            fingerprint,
+            Fr::from(bucket.seconds() as u64),
+        ))
+    }
+
+    /// Computes the exact and the fuzzy fingerprints, both date times are evaluated in one protocol round
+    pub async fn complete_fuzzy_fingerprint<P: FingerprintProtocol<Fr> + Sync>(
+        &self,
+        via_protocol: &P,
+        bucket: TimeBucket,
+    ) -> Result<FuzzyFingerprints, Error> {
+        let round = vec![
+            self.unblinded_datetime()?,
+            self.fuzzy_unblinded_datetime(bucket)?,
+        ];
+        let mut evaluated = via_protocol.process_batch(round).await.into_iter();
+        let (Some(exact), Some(fuzzy)) = (evaluated.next(), evaluated.next()) else {
+            return Err(anyhow!("Protocol round should evaluate both date times"));
+        };
+
+        Ok(FuzzyFingerprints {
+            exact: Fingerprint::new(self.fingerprint(exact?)?),
+            fuzzy: Fingerprint::new(self.fuzzy_fingerprint_with_datetime_scalar(bucket, fuzzy?)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NaiveProtocol, ProtocolFingerprint};
+    use chrono::TimeZone;
+    use fingerprinting_types::RawTransactionBuilder;
+
+    fn transaction(date_time: DateTime<Utc>) -> Result<TransactionFingerprintData<Fr>, Error> {
+        RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount((1000u64, "EUR"))
+            .date_time(date_time)
+            .wwd(date_time.date_naive())
+            .build()?
+            .try_into()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fuzzy_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let recorded = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        let later = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 48).unwrap();
+        assert_eq!(
+            TimeBucket::Hour.truncate(&later),
+            Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap()
+        );
+        assert_eq!("minute".parse::<TimeBucket>()?, TimeBucket::Minute);
+        assert!("day".parse::<TimeBucket>().is_err());
+
+        // Same transaction recorded seconds apart differs in the exact fingerprint only
+        let first = transaction(recorded)?
+            .complete_fuzzy_fingerprint(&protocol, TimeBucket::Minute)
+            .await?;
+        let second = transaction(later)?
+            .complete_fuzzy_fingerprint(&protocol, TimeBucket::Minute)
+            .await?;
+        assert_ne!(first.exact, second.exact);
+        assert_eq!(first.fuzzy, second.fuzzy);
+        assert_eq!(
+            first.exact,
+            transaction(recorded)?
+                .complete_fingerprint(&protocol)
+                .await?
+        );
+
+        // Fuzzy fingerprint of the truncated date time is not the exact one, nor the fuzzy one of the other bucket
+        let truncated = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 0).unwrap();
+        let exact = transaction(truncated)?
+            .complete_fingerprint(&protocol)
+            .await?;
+        assert_ne!(exact, first.fuzzy);
+        let hourly = transaction(recorded)?
+            .complete_fuzzy_fingerprint(&protocol, TimeBucket::Hour)
+            .await?;
+        assert_ne!(hourly.fuzzy, first.fuzzy);
+
+        // Other minute is another fuzzy fingerprint
+        let next_minute = Utc.with_ymd_and_hms(2025, 9, 16, 12, 31, 2).unwrap();
+        let third = transaction(next_minute)?
+            .complete_fuzzy_fingerprint(&protocol, TimeBucket::Minute)
+            .await?;
+        assert_ne!(third.fuzzy, first.fuzzy);
+        assert_eq!(
+            transaction(next_minute)?
+                .complete_fuzzy_fingerprint(&protocol, TimeBucket::Hour)
+                .await?
+                .fuzzy,
+            hourly.fuzzy
+        );
+
+        Ok(())
+    }
+}
@@ -1,37 +1,80 @@
-mod components;
+pub mod breaker;
+pub mod builder;
+pub mod clock;
+pub mod commitment;
+pub mod components;
+pub mod crypto;
+pub mod diff;
+pub mod entropy;
+pub mod events;
+pub mod explain;
+pub mod fingerprint;
+pub mod fuzzy;
+pub mod namespace;
+pub mod pii;
+pub mod pipeline;
 mod protocols;
+pub mod pseudonym;
+pub mod rollup;
+pub mod schema;
 pub mod secret_sharing;
-
-use crate::components::{DateTimeRaw, ScalarComponent, SqueezeComponent};
+pub mod series;
+pub mod version;
+pub mod warmup;
+
+use crate::clock::Epoch;
+use crate::components::{
+    bind_scalar, validate_domain_tag, AmountTierComponent, BoundComponent, CounterpartyComponent,
+    CountryCodeComponent, DateTimeRaw, MccComponent, MerchantIdComponent, PairedAmountComponent,
+    SaltComponent, SeriesComponent, SqueezeComponent, TransactionReferenceComponent,
+};
+use crate::namespace::Namespace;
+use crate::schema::{FingerprintSchema, SchemaComponent};
+use crate::wire::{TextEncoding, WireVersion};
 use anyhow::{anyhow, Error};
-use bytes::{BufMut, Bytes, BytesMut};
+use bytes::Bytes;
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use components::{
     AmountComponent, BankIdentifierComponent, CurrencyComponent, DateTimeComponent,
     FingerprintComponent,
 };
-use fingerprinting_poseidon::{Poseidon, Spec};
-use fingerprinting_types::RawTransaction;
+use fingerprinting_types::{Counterparty, InputLimits, Money, RawTransaction};
 use halo2_axiom::halo2curves::bn256::{Fr, G1};
 use halo2_axiom::halo2curves::ff::PrimeField as PF;
-use halo2_axiom::halo2curves::group::GroupEncoding;
 use iso_currency::Currency;
-use std::io::Write;
 use std::marker::PhantomData;
-use std::sync::LazyLock;
-
+use std::sync::Arc;
+
+pub use crate::builder::FingerprintBuilder;
+pub use crate::components::{
+    alpha2_country_code, numeric_country_code, numeric_mcc, AddressComponent, AmountTiers,
+    Bytes32Component, MAX_ADDRESS_SIZE, MAX_AMOUNT_TIERS, MAX_COUNTERPARTY_ID_SIZE, MAX_MCC,
+    MAX_MERCHANT_ID_SIZE, MAX_SALT_SIZE, MAX_SERIES_ID_SIZE, MAX_TRANSACTION_REFERENCE_SIZE,
+    NOT_PROVIDED_REFERENCE,
+};
+pub use crate::crypto::CryptoTransactionFingerprintData;
+pub use crate::fingerprint::Fingerprint;
+pub use crate::fuzzy::{FuzzyFingerprints, TimeBucket};
+pub use crate::pipeline::{fingerprint_batch, fingerprint_stream};
+pub use crate::protocols::members::{Member, Members};
+pub use crate::protocols::phases::{
+    AgentInMaintenance, DiversityPolicy, EvaluationStats, EvaluationVerifier, NonIdentity, Phase,
+    PhaseError, PhaseMetrics, PhaseStats,
+};
 pub use crate::protocols::{
-    AgentsTopology, CollaborativeProtocol, FingerprintProtocol, NaiveProtocol,
+    AgentsTopology, CollaborativeProtocol, FingerprintProtocol, HierarchicalProtocol,
+    InProcessAgentsTopology, NaiveProtocol, ViaAgents, BATCH_ROUNDS_IN_FLIGHT,
 };
+pub use crate::rollup::{Rollup, RollupKey, RollupScope, Rollups};
 
-// Hash related cashed spec 8 full rounds, 57 partial rounds, with 1 Fr as an input
-pub(crate) static SPEC: LazyLock<Spec<Fr, 2, 1>> = LazyLock::new(|| Spec::new(8, 57));
+pub use fingerprinting_verify::{
+    batch_merkle, fixed_point, hash_to_g1, parameters_digest, wire, HASH_TO_CURVE_PREFIX,
+    MAX_HASH_TO_CURVE_DOMAIN, POSEIDON_FULL_ROUNDS, POSEIDON_PARTIAL_ROUNDS,
+};
 
-// Hash related cashed spec 8 full rounds, 57 partial rounds, with 4 Fr as an input
-pub(crate) static SPEC_BIG: LazyLock<Spec<Fr, 5, 4>> = LazyLock::new(|| Spec::new(8, 57));
+pub use fingerprinting_verify::{fingerprint_poseidon, fingerprint_spec};
 
-// Hash related cashed spec 8 full rounds, 57 partial rounds, with 3 Fr as an input
-pub(crate) static SPEC_DC: LazyLock<Spec<Fr, 4, 3>> = LazyLock::new(|| Spec::new(8, 57));
+pub(crate) use fingerprinting_verify::hash_to_curve;
 
 // Base Epoch used for offsetting dates components
 pub(crate) static EPOCH: NaiveDateTime = NaiveDateTime::new(
@@ -39,142 +82,207 @@ pub(crate) static EPOCH: NaiveDateTime = NaiveDateTime::new(
     NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
 );
 
-pub const HASH_TO_CURVE_PREFIX: &'static str = "CRA_FINGERPRINT";
+pub const SALT_DOMAIN_PREFIX: &str = "CRA_FINGERPRINT_SALT";
 
-pub trait HashSqueeze<F: PF> {
-    fn squeeze(&self) -> Result<F, Error>;
-}
+pub const PSEUDONYM_DOMAIN_PREFIX: &str = "CRA_BIC_PSEUDONYM";
 
-impl HashSqueeze<Fr> for G1 {
-    fn squeeze(&self) -> Result<Fr, Error> {
-        let bytes = self.to_bytes();
-        let bytes_array = bytes.as_ref();
+pub const COMMITMENT_DOMAIN_PREFIX: &str = "CRA_FP_COMMITMENT";
 
-        // Split the 32 bytes of compressed point to the 2 limbs
-        // Each limb represent as first 16 bytes in 32 bytes array (to be sure that it will fit into Fr
-        // Each of the generated array convert into the Fr
-        // Hash the result and squeeze into single Fr
+pub const NAMESPACE_DOMAIN_PREFIX: &str = "CRA_FP_NS";
 
-        let frs: Vec<Fr> = bytes_array
-            .chunks(16)
-            .map(|chunk| {
-                let mut buffer_32 = [0u8; 32];
-                buffer_32[0..16].copy_from_slice(chunk.as_ref());
+pub const PAIRED_AMOUNT_DOMAIN_PREFIX: &str = "CRA_FP_FX_LEG";
 
-                Fr::from_bytes(&buffer_32).unwrap_or(Fr::zero())
-            })
-            .collect();
+pub const SERIES_DOMAIN_PREFIX: &str = "CRA_FP_SERIES";
+
+pub const MERCHANT_DOMAIN_PREFIX: &str = "CRA_FP_MERCHANT";
+
+pub const COUNTRY_DOMAIN_PREFIX: &str = "CRA_FP_COUNTRY";
+
+pub const REFERENCE_DOMAIN_PREFIX: &str = "CRA_FP_E2E_REF";
+
+pub const MCC_DOMAIN_PREFIX: &str = "CRA_FP_MCC";
+
+pub const COUNTERPARTY_DOMAIN_PREFIX: &str = "CRA_FP_COUNTERPARTY";
+
+pub const CRYPTO_DOMAIN_PREFIX: &str = "CRA_FP_CRYPTO_TX";
+
+pub const BYTES_DOMAIN_PREFIX: &str = "CRA_FP_BYTES";
+
+pub const AMOUNT_TIER_DOMAIN_PREFIX: &str = "CRA_FP_AMOUNT_TIER";
+
+pub const ROLLUP_DOMAIN_PREFIX: &str = "CRA_FP_ROLLUP";
+
+pub const FUZZY_DOMAIN_PREFIX: &str = "CRA_FP_FUZZY";
 
-        let mut poseidon = Poseidon::new_with_spec(SPEC.clone());
+// Size of the padded bytes limb, 31 bytes always fit into Fr
+const BYTES_LIMB_SIZE: usize = 31;
 
-        poseidon.update(frs.as_slice());
-        let squeezed_salted_hash = poseidon.squeeze();
+// Preimages split into the 4 equal limbs, see `HashSqueeze` of `Bytes`
+const LEGACY_LIMBS: usize = 4;
 
-        Ok(squeezed_salted_hash)
+// Sizes of the preimage layouts hashed as the 4 limbs: V1, versioned and versioned with the schema id
+// (V1 with the schema id takes the size of the versioned one)
+const LEGACY_PREIMAGE_SIZES: [usize; 3] = [
+    wire::PREIMAGE_SIZE,
+    wire::PREIMAGE_SIZE + wire::VERSION_FIELD_SIZE,
+    wire::PREIMAGE_SIZE + wire::VERSION_FIELD_SIZE + wire::SCHEMA_ID_SIZE,
+];
+
+/// Applies the caller supplied salt to the unsalted fingerprint,
+/// so anyone holding the salt is able to verify the salted fingerprint
+pub fn salt_fingerprint(fingerprint: Fr, salt: &[u8]) -> Result<Fr, Error> {
+    SaltComponent::validate(salt)?;
+
+    SaltComponent::new(Bytes::copy_from_slice(salt)).blind(fingerprint)
+}
+
+pub trait HashSqueeze<F: PF> {
+    fn squeeze(&self) -> Result<F, Error>;
+}
+
+impl HashSqueeze<Fr> for G1 {
+    fn squeeze(&self) -> Result<Fr, Error> {
+        Ok(fingerprinting_verify::squeeze_point(self))
     }
 }
 
+/// Bytes of the preimage layouts so far (`LEGACY_PREIMAGE_SIZES`) are absorbed as the 4 equal limbs, so the
+/// fingerprints stay the same. Any other length is split into the 31 bytes limbs, which are absorbed after the
+/// `BYTES_DOMAIN_PREFIX` tag and the length by the rate of the sponge:
+/// Poseidon(BYTES_DOMAIN | len | limb_1 | ... | limb_n)
 impl HashSqueeze<Fr> for Bytes {
     fn squeeze(&self) -> Result<Fr, Error> {
-        // TODO make more generic
-        let mut poseidon = Poseidon::new_with_spec(SPEC_BIG.clone());
-        let limb_size = self.len() / 4;
-
-        let mut limbs = Vec::with_capacity(4);
-        for offset in (0..self.len()).step_by(limb_size) {
-            let mut buffer_32 = [0u8; 32];
-            buffer_32[0..limb_size].copy_from_slice(&self[offset..offset + limb_size]);
-
-            limbs.push(Fr::from_bytes(&buffer_32).unwrap_or(Fr::zero()));
-        }
-
+        let limbs = match LEGACY_PREIMAGE_SIZES.contains(&self.len()) {
+            true => self
+                .chunks(self.len() / LEGACY_LIMBS)
+                .map(bytes_limb)
+                .collect::<Vec<_>>(),
+            false => tagged_limbs(self),
+        };
+
+        let mut poseidon = fingerprint_poseidon::<5, 4>();
         poseidon.update(limbs.as_slice());
 
         Ok(poseidon.squeeze())
     }
 }
 
-pub trait Fingerprint<F: PF, P: FingerprintProtocol<F>> {
-    /// perform Fingerprint computation
-    fn complete_fingerprint(&self, via_protocol: &P) -> impl std::future::Future<Output = Result<F, Error>> + Send;
-    fn datetime_fingerprint(&self, via_protocol: &P) -> impl std::future::Future<Output = Result<F, Error>> + Send;
+fn bytes_limb(chunk: &[u8]) -> Fr {
+    let mut buffer_32 = [0u8; 32];
+    buffer_32[0..chunk.len()].copy_from_slice(chunk);
 
-    fn fingerprint(&self, date_time: F, _: PhantomData<P>) -> Result<F, Error>;
+    Fr::from_bytes(&buffer_32).unwrap_or(Fr::zero())
 }
 
-pub trait Compact
-where
-    Self: Sized,
-{
-    fn compact(&self) -> String;
+// The length goes before the limbs, so the values with the trailing zero bytes do not collide
+fn tagged_limbs(bytes: &[u8]) -> Vec<Fr> {
+    let mut limbs = vec![
+        bytes_limb(BYTES_DOMAIN_PREFIX.as_bytes()),
+        Fr::from(bytes.len() as u64),
+    ];
+    limbs.extend(bytes.chunks(BYTES_LIMB_SIZE).map(bytes_limb));
 
-    fn unwrap(compacted: &String) -> Result<Self, Error>;
+    limbs
 }
 
-impl<P: FingerprintProtocol<Fr> + Sync> Fingerprint<Fr, P> for TransactionFingerprintData<Fr> {
-    async fn complete_fingerprint(&self, via_protocol: &P) -> Result<Fr, Error> {
+/// Local part of the fingerprint computation, pure hashing without the protocol round.
+/// Data types implementing it are fingerprinted via any protocol, see `ProtocolFingerprint`
+pub trait LocalFingerprint<F: PF> {
+    /// Date time value evaluated by the protocol round
+    fn unblinded_datetime(&self) -> Result<F, Error>;
+
+    /// Completes the fingerprint with the `date_time` scalar evaluated by the protocol round
+    fn fingerprint(&self, date_time: F) -> Result<F, Error>;
+}
+
+/// Fingerprint computed via the protocol round, implemented for every `LocalFingerprint`
+pub trait ProtocolFingerprint<F: PF, P: FingerprintProtocol<F>>: LocalFingerprint<F> {
+    /// perform Fingerprint computation
+    fn complete_fingerprint(
+        &self,
+        via_protocol: &P,
+    ) -> impl std::future::Future<Output = Result<Fingerprint<F>, Error>> + Send;
+    fn datetime_fingerprint(
+        &self,
+        via_protocol: &P,
+    ) -> impl std::future::Future<Output = Result<F, Error>> + Send;
+}
+
+impl<F, P, T> ProtocolFingerprint<F, P> for T
+where
+    F: PF + Send,
+    P: FingerprintProtocol<F> + Sync,
+    T: LocalFingerprint<F> + Sync,
+{
+    async fn complete_fingerprint(&self, via_protocol: &P) -> Result<Fingerprint<F>, Error> {
         let date_time = self.datetime_fingerprint(via_protocol).await?;
 
-        self.fingerprint(date_time, PhantomData::<P>::default())
+        self.fingerprint(date_time).map(Fingerprint::new)
     }
 
-    async fn datetime_fingerprint(&self, via_protocol: &P) -> Result<Fr, Error> {
-        let date_time = &self.date_time;
-        let squeezed = date_time.squeeze()?;
-
-        via_protocol.process(squeezed).await
+    async fn datetime_fingerprint(&self, via_protocol: &P) -> Result<F, Error> {
+        via_protocol.process(self.unblinded_datetime()?).await
     }
+}
 
-    fn fingerprint(&self, date_time: Fr, _: PhantomData<P>) -> Result<Fr, Error> {
-        let fingerprint_size = TransactionFingerprintData::<Fr>::fingerprint_size();
-        let buffer = BytesMut::with_capacity(fingerprint_size);
-        let mut writer = buffer.writer();
-        writer.write(&[0xFF, 0xFE, 0xED, 0xDD, 0xCC, 0x00, 0xDD, 0xEE])?; // Prefix for serialization
+pub trait Compact
+where
+    Self: Sized,
+{
+    fn compact(&self) -> String;
 
-        let date_time = ScalarComponent::<Fr, 32>::new(date_time);
-        let bic = &self.bic;
-        let amount = &self.amount;
-        let currency = &self.currency;
+    fn unwrap(compacted: &str) -> Result<Self, Error>;
 
-        bic.serialize(&mut writer)?;
-        amount.serialize(&mut writer)?;
-        currency.serialize(&mut writer)?;
-        date_time.serialize(&mut writer)?;
+    /// Representation in the text `encoding`, `compact` is the base58 one. The values copied between
+    /// the systems are safer in `TextEncoding::Base58Check`, the corrupted value is refused rather than decoded
+    fn encode(&self, encoding: TextEncoding) -> String {
+        let bytes = wire::decode_compact(&self.compact()).expect("Compacted value is base58");
+
+        wire::encode_text(encoding, &bytes)
+    }
 
-        let buffer = writer.into_inner().freeze();
-        let fingerprint = buffer.squeeze()?;
+    /// Value of the representation in the text `encoding`, the checksum is verified when the encoding has one
+    fn decode(encoded: &str, encoding: TextEncoding) -> Result<Self, Error> {
+        Self::unwrap(&wire::encode_compact(&wire::decode_text(
+            encoding, encoded,
+        )?))
+    }
+}
 
-        log::info!("Transaction fingerprint generated successfully: {}", fingerprint.compact());
+impl LocalFingerprint<Fr> for TransactionFingerprintData<Fr> {
+    fn unblinded_datetime(&self) -> Result<Fr, Error> {
+        self.date_time.squeeze()
+    }
 
-        Ok(fingerprint)
+    fn fingerprint(&self, date_time: Fr) -> Result<Fr, Error> {
+        self.fingerprint_with_datetime_scalar(date_time)
     }
 }
 
 impl Compact for Bytes {
     fn compact(&self) -> String {
-        bs58::encode(&self).into_string()
+        wire::encode_compact(self)
     }
 
-    fn unwrap(compacted: &String) -> Result<Bytes, Error> {
-        let bytes = bs58::decode(&compacted).into_vec()?;
-
-        Ok(Bytes::copy_from_slice(&bytes))
+    fn unwrap(compacted: &str) -> Result<Bytes, Error> {
+        Ok(Bytes::from(wire::decode_compact(compacted)?))
     }
 }
 
 impl Compact for Fr {
     fn compact(&self) -> String {
-        bs58::encode(&self.to_bytes()).into_string()
+        wire::encode_compact(&wire::encode_scalar(self))
     }
 
-    fn unwrap(compacted: &String) -> Result<Self, Error> {
-        let bytes = bs58::decode(&compacted).into_vec()?;
-        let fixed_bytes = bytes.first_chunk::<32>()
-            .ok_or(anyhow!("failed to decode Fr from compacted string, given array is less than 32 bytes long"))?;
+    fn unwrap(compacted: &str) -> Result<Self, Error> {
+        let bytes = wire::decode_compact(compacted)?;
+        let fixed_bytes = bytes.first_chunk::<32>().ok_or(anyhow!(
+            "failed to decode Fr from compacted string, given array is less than 32 bytes long"
+        ))?;
 
-        Fr::from_bytes(fixed_bytes)
-            .into_option()
-            .ok_or(anyhow!("failed to decode Fr from compacted string, value does not represent Fr"))
+        wire::decode_scalar(fixed_bytes).map_err(|_| {
+            anyhow!("failed to decode Fr from compacted string, value does not represent Fr")
+        })
     }
 }
 
@@ -183,17 +291,170 @@ pub struct TransactionFingerprintData<F> {
     bic: BankIdentifierComponent,
     amount: AmountComponent,
     currency: CurrencyComponent,
+    counter_amount: Option<PairedAmountComponent>,
+    series: Option<SeriesComponent>,
+    merchant: Option<MerchantIdComponent>,
+    country: Option<CountryCodeComponent>,
+    reference: Option<TransactionReferenceComponent>,
+    mcc: Option<MccComponent>,
+    counterparty: Option<CounterpartyComponent>,
+    amount_tier: Option<AmountTierComponent>,
+    /// Components of the deployment, see `components::BoundComponent`
+    extensions: Vec<Box<dyn BoundComponent>>,
     date_time: DateTimeComponent,
+    salt: Option<SaltComponent>,
+    namespace: Option<Namespace>,
+    /// Built-in layout when absent, see `schema::FingerprintSchema`
+    schema: Option<Arc<FingerprintSchema>>,
+    /// Layout of the preimage, see `WireVersion`
+    wire_version: WireVersion,
 
     _p: PhantomData<F>,
 }
 
+impl TransactionFingerprintData<Fr> {
+    /// Serialized components hashed into the unsalted fingerprint
+    /// Date time enters the preimage as the evaluated scalar Poseidon([k] Poseidon(Ts|WWD|Nonce))
+    /// The id of the schema other than the built-in one follows the components,
+    /// the direction agnostic schema leaves the reporting BIC out (zeroed)
+    pub(crate) fn preimage(&self, date_time: Fr) -> Result<Bytes, Error> {
+        let mut bic = wire::encode_bic(self.bic.raw())?;
+        if self.is_direction_agnostic() {
+            bic = [0u8; wire::BIC_SIZE];
+        }
+
+        let preimage = wire::preimage(
+            self.wire_version,
+            &bic,
+            &wire::encode_versioned_amount(self.wire_version, *self.amount.raw())?,
+            &wire::encode_currency(*self.currency.raw()),
+            &wire::encode_scalar(&date_time),
+        );
+
+        Ok(match &self.schema {
+            Some(schema) => wire::schema_preimage(&preimage, schema.id()),
+            None => preimage,
+        })
+    }
+
+    fn is_direction_agnostic(&self) -> bool {
+        self.schema
+            .as_ref()
+            .is_some_and(|schema| schema.is_direction_agnostic())
+    }
+
+    /// Optional component of the transaction, if it carries one
+    fn schema_component(&self, component: SchemaComponent) -> Option<&dyn SqueezeComponent<Fr>> {
+        match component {
+            SchemaComponent::CounterAmount => self.counter_amount.as_ref().map(|c| c as _),
+            SchemaComponent::Series => self.series.as_ref().map(|c| c as _),
+            SchemaComponent::Merchant => self.merchant.as_ref().map(|c| c as _),
+            SchemaComponent::Country => self.country.as_ref().map(|c| c as _),
+            SchemaComponent::Reference => self.reference.as_ref().map(|c| c as _),
+            SchemaComponent::Mcc => self.mcc.as_ref().map(|c| c as _),
+            SchemaComponent::Counterparty => self.counterparty.as_ref().map(|c| c as _),
+            SchemaComponent::AmountTier => self.amount_tier.as_ref().map(|c| c as _),
+        }
+    }
+
+    /// Optional components bound on top of the preimage hash as their domain tags and squeezed values, in the
+    /// binding order of the schema, by default: the FX leg, series, merchant, country, end-to-end reference,
+    /// merchant category, counterparty, amount tier; and then the deployment components in the order they were added.
+    /// Fails when the transaction carries the component the schema does not list,
+    /// or carries no counterparty for the direction agnostic schema, which binds the sorted pair
+    pub fn bound_components(&self) -> Result<Vec<(&str, Fr)>, Error> {
+        let layout = match &self.schema {
+            Some(schema) => {
+                if schema.is_direction_agnostic() && self.counterparty.is_none() {
+                    return Err(anyhow!(
+                        "Transaction should carry the counterparty for the direction agnostic schema {}",
+                        schema.id()
+                    ));
+                }
+                let excluded = SchemaComponent::ALL.into_iter().find(|component| {
+                    !schema.includes(*component) && self.schema_component(*component).is_some()
+                });
+                if let Some(component) = excluded {
+                    return Err(anyhow!(
+                        "Component {} is not in the fingerprint schema {}",
+                        component,
+                        schema.id()
+                    ));
+                }
+                schema.components()
+            }
+            None => &SchemaComponent::ALL,
+        };
+
+        let mut bound = vec![];
+        for component in layout {
+            match (component, &self.counterparty) {
+                (SchemaComponent::Counterparty, Some(counterparty))
+                    if self.is_direction_agnostic() =>
+                {
+                    bound.push((component.domain_tag(), counterparty.squeeze_unordered()?));
+                }
+                _ => {
+                    if let Some(value) = self.schema_component(*component) {
+                        bound.push((component.domain_tag(), value.squeeze()?));
+                    }
+                }
+            }
+        }
+        for component in self.extensions.iter() {
+            bound.push((component.domain_tag(), component.squeeze()?));
+        }
+
+        Ok(bound)
+    }
+
+    /// Checks the components are encoded and bound the way the fingerprint is computed, without the protocol round
+    pub fn validate(&self) -> Result<(), Error> {
+        self.preimage(Fr::zero())?;
+        self.bound_components()?;
+
+        Ok(())
+    }
+
+    /// Binds the optional components on top of the preimage hash, see `bound_components`
+    pub(crate) fn bind_components(&self, fingerprint: Fr) -> Result<Fr, Error> {
+        Ok(self
+            .bound_components()?
+            .into_iter()
+            .fold(fingerprint, |fingerprint, (tag, value)| {
+                bind_scalar(tag, fingerprint, value)
+            }))
+    }
+
+    /// Completes the fingerprint with the `date_time` scalar evaluated by the protocol round run out-of-band,
+    /// e.g. by the offline agents. The round evaluates `unblinded_datetime()`, the scalar it returns
+    /// is the evaluated Poseidon([k] Poseidon(Ts|WWD|Nonce)), not the squeezed date time itself.
+    /// No agent is contacted, a wrong scalar produces a wrong fingerprint rather than an error.
+    pub fn fingerprint_with_datetime_scalar(&self, date_time: Fr) -> Result<Fr, Error> {
+        let fingerprint = self.bind_components(self.preimage(date_time)?.squeeze()?)?;
+        let fingerprint = match &self.namespace {
+            Some(namespace) => namespace.separate(fingerprint),
+            None => fingerprint,
+        };
+
+        // Salt is applied on top of the fingerprint, so unsalted fingerprints stay the same
+        let fingerprint = match &self.salt {
+            Some(salt) => salt.blind(fingerprint)?,
+            None => fingerprint,
+        };
+
+        log::info!(
+            "Transaction fingerprint generated successfully: {}",
+            fingerprint.compact()
+        );
+
+        Ok(fingerprint)
+    }
+}
+
 impl<F> TransactionFingerprintData<F> {
     pub fn fingerprint_size() -> usize {
-        8 + BankIdentifierComponent::size()
-            + AmountComponent::size()
-            + CurrencyComponent::size()
-            + DateTimeComponent::size()
+        wire::PREIMAGE_SIZE
     }
 }
 impl<F: PF> TransactionFingerprintData<F> {
@@ -207,21 +468,241 @@ impl<F: PF> TransactionFingerprintData<F> {
             bic,
             amount,
             currency,
+            counter_amount: None,
+            series: None,
+            merchant: None,
+            country: None,
+            reference: None,
+            mcc: None,
+            counterparty: None,
+            amount_tier: None,
+            extensions: vec![],
             date_time,
-            _p: PhantomData::default(),
+            salt: None,
+            namespace: None,
+            schema: None,
+            wire_version: WireVersion::CURRENT,
+            _p: PhantomData,
         }
     }
 
+    /// Domain separates the caller supplied salt into the fingerprint
+    pub fn with_salt(mut self, salt: Bytes) -> Result<Self, Error> {
+        SaltComponent::validate(&salt)?;
+
+        self.salt = Some(SaltComponent::new(salt));
+        Ok(self)
+    }
+
+    /// Separates the fingerprint into the namespace of the environment
+    pub fn with_namespace(mut self, namespace: Option<Namespace>) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    pub fn namespace(&self) -> Option<&Namespace> {
+        self.namespace.as_ref()
+    }
+
+    /// Binds the optional components by the schema of the deployment, the built-in layout when absent
+    pub fn with_schema(mut self, schema: Option<Arc<FingerprintSchema>>) -> Self {
+        self.schema = schema.filter(|schema| schema.id() != schema::BUILT_IN_SCHEMA_ID);
+        self
+    }
+
+    /// Id of the schema the optional components are bound by
+    pub fn schema_id(&self) -> u32 {
+        self.schema
+            .as_ref()
+            .map_or(schema::BUILT_IN_SCHEMA_ID, |schema| schema.id())
+    }
+
+    /// Computes the fingerprint with the preimage of the requested `version`, `WireVersion::CURRENT` by default.
+    /// The fingerprints of the different versions never match
+    pub fn with_wire_version(mut self, version: WireVersion) -> Self {
+        self.wire_version = version;
+        self.amount = self.amount.with_wire_version(version);
+        self.date_time = self.date_time.with_wire_version(version);
+        self
+    }
+
+    pub fn wire_version(&self) -> WireVersion {
+        self.wire_version
+    }
+
+    /// Offsets the date components from the `epoch`, `Epoch::default()` (01.01.2025) by default.
+    /// The fingerprints of the different epochs never match
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.date_time = self.date_time.with_epoch(epoch);
+        self
+    }
+
+    pub fn epoch(&self) -> Epoch {
+        self.date_time.epoch()
+    }
+
+    /// Binds the bought leg of the FX transaction, `amount` and `currency` are the sold leg then
+    pub fn with_counter_amount(
+        mut self,
+        amount: (u64, u64),
+        currency_code: u16,
+    ) -> Result<Self, Error> {
+        if currency_code == self.currency_code() {
+            return Err(anyhow!(
+                "Both legs of the FX transaction should not be in the same currency"
+            ));
+        }
+
+        self.counter_amount = Some(PairedAmountComponent::new((amount, currency_code)));
+        Ok(self)
+    }
+
+    /// Bought leg of the FX transaction as the amount and the numeric currency code
+    pub fn counter_amount(&self) -> Option<((u64, u64), u16)> {
+        self.counter_amount
+            .as_ref()
+            .map(|counter_amount| *counter_amount.raw())
+    }
+
+    /// Links the transaction to the standing order or the installment plan
+    pub fn with_series(mut self, series_id: String) -> Result<Self, Error> {
+        SeriesComponent::validate(&series_id)?;
+
+        self.series = Some(SeriesComponent::new(series_id));
+        Ok(self)
+    }
+
+    pub fn series_id(&self) -> Option<&str> {
+        self.series.as_ref().map(|series| series.raw().as_str())
+    }
+
+    /// Binds the merchant (or the acquirer) the transaction is paid to
+    pub fn with_merchant_id(mut self, merchant_id: String) -> Result<Self, Error> {
+        MerchantIdComponent::validate(&merchant_id)?;
+
+        self.merchant = Some(MerchantIdComponent::new(merchant_id));
+        Ok(self)
+    }
+
+    pub fn merchant_id(&self) -> Option<&str> {
+        self.merchant
+            .as_ref()
+            .map(|merchant| merchant.raw().as_str())
+    }
+
+    /// Binds the jurisdiction the transaction took place in, as the ISO 3166-1 numeric code
+    pub fn with_country_code(mut self, country_code: u16) -> Result<Self, Error> {
+        CountryCodeComponent::validate(country_code)?;
+
+        self.country = Some(CountryCodeComponent::new(country_code));
+        Ok(self)
+    }
+
+    pub fn country_code(&self) -> Option<u16> {
+        self.country.as_ref().map(|country| *country.raw())
+    }
+
+    /// Binds the end-to-end reference (ISO 20022 `EndToEndId`) or the remittance id of the transaction, so the
+    /// recurring payments are told apart from the duplicates. The `NOTPROVIDED` placeholder is refused
+    pub fn with_transaction_reference(mut self, reference: String) -> Result<Self, Error> {
+        TransactionReferenceComponent::validate(&reference)?;
+
+        self.reference = Some(TransactionReferenceComponent::new(reference));
+        Ok(self)
+    }
+
+    pub fn transaction_reference(&self) -> Option<&str> {
+        self.reference
+            .as_ref()
+            .map(|reference| reference.raw().as_str())
+    }
+
+    /// Binds the merchant category of the card payment, as the ISO 18245 numeric code
+    pub fn with_mcc(mut self, mcc: u16) -> Result<Self, Error> {
+        MccComponent::validate(mcc)?;
+
+        self.mcc = Some(MccComponent::new(mcc));
+        Ok(self)
+    }
+
+    pub fn mcc(&self) -> Option<u16> {
+        self.mcc.as_ref().map(|mcc| *mcc.raw())
+    }
+
+    /// Binds both sides of the transfer, the pair is ordered: the payer first, then the payee
+    pub fn with_counterparty(mut self, payer: String, payee: String) -> Result<Self, Error> {
+        CounterpartyComponent::validate(&payer, &payee)?;
+
+        self.counterparty = Some(CounterpartyComponent::new((payer, payee)));
+        Ok(self)
+    }
+
+    /// Payer and payee identifiers
+    pub fn counterparty(&self) -> Option<(&str, &str)> {
+        self.counterparty.as_ref().map(|counterparty| {
+            let (payer, payee) = counterparty.raw();
+            (payer.as_str(), payee.as_str())
+        })
+    }
+
+    /// Binds the tier of the amount by the `tiers` rather than the amount itself, see `components::AmountTiers`
+    pub fn with_amount_tier(mut self, tiers: &AmountTiers) -> Self {
+        let tier = tiers.tier(self.amount());
+
+        self.amount_tier = Some(AmountTierComponent::new((tier, self.currency_code())));
+        self
+    }
+
+    /// Range of the amount tier, from its bound inclusive to the next one exclusive
+    pub fn amount_tier(&self) -> Option<(u64, u64)> {
+        self.amount_tier
+            .as_ref()
+            .map(|amount_tier| amount_tier.raw().0)
+    }
+
+    /// Binds the component of the deployment after the built-in ones, the domain tag of every component
+    /// should be unique and outside of the reserved prefix, see `components::BoundComponent`
+    pub fn with_component(
+        mut self,
+        component: impl BoundComponent + 'static,
+    ) -> Result<Self, Error> {
+        let tag = component.domain_tag();
+        validate_domain_tag(tag)?;
+        if self
+            .extensions
+            .iter()
+            .any(|extension| extension.domain_tag() == tag)
+        {
+            return Err(anyhow!(
+                "Component with the domain tag {} is already bound",
+                tag
+            ));
+        }
+        component.squeeze()?;
+
+        self.extensions.push(Box::new(component));
+        Ok(self)
+    }
+
+    /// Components of the deployment in the order they are bound
+    pub fn components(&self) -> impl Iterator<Item = &dyn BoundComponent> {
+        self.extensions.iter().map(|component| component.as_ref())
+    }
+
+    pub fn salt(&self) -> Option<&Bytes> {
+        self.salt.as_ref().map(|salt| salt.raw())
+    }
+
     pub fn bic(&self) -> &str {
         self.bic.raw()
     }
 
     pub fn amount(&self) -> (u64, u64) {
-        self.amount.raw().clone()
+        *self.amount.raw()
     }
 
     pub fn currency_code(&self) -> u16 {
-        self.currency.raw().clone()
+        *self.currency.raw()
     }
 
     pub fn currency(&self) -> Option<Currency> {
@@ -229,7 +710,11 @@ impl<F: PF> TransactionFingerprintData<F> {
     }
 
     pub fn date_time(&self) -> &DateTime<Utc> {
-        unimplemented!()
+        self.date_time.raw().date_time()
+    }
+
+    pub fn wwd(&self) -> NaiveDate {
+        self.date_time.raw().wwd()
     }
 
     pub fn date_time_component(&self) -> &DateTimeComponent {
@@ -241,13 +726,10 @@ impl<F: PF> TryFrom<RawTransaction> for TransactionFingerprintData<F> {
     type Error = Error;
 
     fn try_from(tx: RawTransaction) -> Result<Self, Self::Error> {
+        tx.check_input_sizes(&InputLimits::MAX)?;
+
         let money = tx.amount;
-        let iso_currency = Currency::from_code(&money.currency)
-            .ok_or(anyhow!("Currency is not in the ISO 4217 currency"))?;
-        if iso_currency.is_special() {
-            return Err(anyhow!("Currency should have numeric value"));
-        }
-        let iso_currency_code = iso_currency.numeric();
+        let iso_currency_code = numeric_currency_code(&money.currency)?;
 
         let bic = BankIdentifierComponent::new(tx.bic.to_string());
         let amount = AmountComponent::new((money.amount_base, money.amount_atto));
@@ -258,16 +740,134 @@ impl<F: PF> TryFrom<RawTransaction> for TransactionFingerprintData<F> {
 
         let date_time = DateTimeComponent::new(dt_raw_data);
 
-        Ok(Self {
+        let tx_data = Self {
             bic,
             amount,
             currency,
+            counter_amount: None,
+            series: None,
+            merchant: None,
+            country: None,
+            reference: None,
+            mcc: None,
+            counterparty: None,
+            amount_tier: None,
+            extensions: vec![],
             date_time,
+            salt: None,
+            namespace: None,
+            schema: None,
+            wire_version: WireVersion::CURRENT,
             _p: Default::default(),
+        };
+
+        let tx_data = match tx.counter_amount {
+            Some(counter) => tx_data.with_counter_amount(
+                (counter.amount_base, counter.amount_atto),
+                numeric_currency_code(&counter.currency)?,
+            )?,
+            None => tx_data,
+        };
+
+        let tx_data = match tx.series_id {
+            Some(series_id) => tx_data.with_series(series_id)?,
+            None => tx_data,
+        };
+
+        let tx_data = match tx.merchant_id {
+            Some(merchant_id) => tx_data.with_merchant_id(merchant_id)?,
+            None => tx_data,
+        };
+
+        let tx_data = match tx.country_code {
+            Some(country_code) => {
+                tx_data.with_country_code(numeric_country_code(&country_code)?)?
+            }
+            None => tx_data,
+        };
+
+        let tx_data = match tx.transaction_reference {
+            Some(reference) => tx_data.with_transaction_reference(reference)?,
+            None => tx_data,
+        };
+
+        let tx_data = match tx.mcc {
+            Some(mcc) => tx_data.with_mcc(numeric_mcc(&mcc)?)?,
+            None => tx_data,
+        };
+
+        match tx.counterparty {
+            Some(counterparty) => tx_data.with_counterparty(counterparty.payer, counterparty.payee),
+            None => Ok(tx_data),
+        }
+    }
+}
+
+impl<F> TransactionFingerprintData<F> {
+    /// Transaction the data is built from, the numeric codes back to the ISO codes (the country as alpha-2), so
+    /// the transaction fingerprints the same. The salt, the namespace, the schema, the amount tier, the deployment
+    /// components and the wire version are the parameters of the computation rather than of the transaction,
+    /// they are not carried
+    pub fn into_raw_transaction(self) -> Result<RawTransaction, Error> {
+        let money =
+            |(amount_base, amount_atto): (u64, u64), currency_code| -> Result<Money, Error> {
+                Ok(Money {
+                    amount_base,
+                    amount_atto,
+                    currency: alphabetic_currency_code(currency_code)?.to_string(),
+                })
+            };
+        let raw = *self.date_time.raw();
+
+        Ok(RawTransaction {
+            bic: self.bic.raw().clone(),
+            amount: money(*self.amount.raw(), *self.currency.raw())?,
+            counter_amount: self
+                .counter_amount
+                .map(|counter_amount| {
+                    let (amount, currency_code) = *counter_amount.raw();
+                    money(amount, currency_code)
+                })
+                .transpose()?,
+            series_id: self.series.map(|series| series.raw().clone()),
+            merchant_id: self.merchant.map(|merchant| merchant.raw().clone()),
+            country_code: self
+                .country
+                .map(|country| alpha2_country_code(*country.raw()).map(str::to_string))
+                .transpose()?,
+            transaction_reference: self.reference.map(|reference| reference.raw().clone()),
+            mcc: self.mcc.map(|mcc| format!("{:04}", mcc.raw())),
+            counterparty: self.counterparty.map(|counterparty| {
+                let (payer, payee) = counterparty.raw().clone();
+                Counterparty { payer, payee }
+            }),
+            date_time: *raw.date_time(),
+            wwd: raw.wwd(),
         })
     }
 }
 
+/// ISO 4217 alphabetic code of the numeric currency code, the inverse of `numeric_currency_code`
+pub fn alphabetic_currency_code(code: u16) -> Result<&'static str, Error> {
+    Currency::from_numeric(code)
+        .map(|currency| currency.code())
+        .ok_or(anyhow!(
+            "Currency {} is not in the ISO 4217 currencies",
+            code
+        ))
+}
+
+/// ISO 4217 numeric code of the alphabetic currency code
+pub fn numeric_currency_code(code: &str) -> Result<u16, Error> {
+    let iso_currency =
+        Currency::from_code(code).ok_or(anyhow!("Currency is not in the ISO 4217 currency"))?;
+    if iso_currency.is_special() {
+        return Err(anyhow!("Currency should have numeric value"));
+    }
+
+    Ok(iso_currency.numeric())
+}
+
 impl<F: PF> TryFrom<&RawTransaction> for TransactionFingerprintData<F> {
     type Error = Error;
 
@@ -279,15 +879,15 @@ impl<F: PF> TryFrom<&RawTransaction> for TransactionFingerprintData<F> {
 #[cfg(test)]
 mod tests {
 
-use super::*;
+    use super::*;
     use rand::Rng;
     use std::cmp::PartialEq;
 
     use crate::protocols::NaiveProtocol;
     use chrono::{TimeZone, Utc};
-    use rand_core::OsRng;
-    use fingerprinting_types::RawTransactionBuilder;
+    use fingerprinting_types::{Money, RawTransactionBuilder};
     use halo2_axiom::arithmetic::Field;
+    use rand_core::OsRng;
 
     impl PartialEq for &TransactionFingerprintData<Fr> {
         fn eq(&self, other: &Self) -> bool {
@@ -336,8 +936,7 @@ use super::*;
 
         println!("Phase 2 (Build Fingerprints): {}", Utc::now());
 
-        for i in 0..n {
-            let tx = &tx_data_set[i];
+        for tx in tx_data_set.iter() {
             let tx_fingerprint = tx.complete_fingerprint(&protocol).await?;
 
             tx_fingerprint_set.push(tx_fingerprint);
@@ -374,4 +973,713 @@ use super::*;
         assert_eq!(fr, back_to_fr);
         Ok(())
     }
+
+    #[test]
+    fn test_text_encodings() -> Result<(), Error> {
+        let fr = Fr::random(OsRng);
+        for encoding in TextEncoding::ALL {
+            assert_eq!(Fr::decode(&fr.encode(encoding), encoding)?, fr);
+        }
+        assert_eq!(fr.encode(TextEncoding::Base58), fr.compact());
+        assert_eq!(
+            fr.encode(TextEncoding::Hex),
+            hex::encode(wire::encode_scalar(&fr))
+        );
+        assert!(fr
+            .encode(TextEncoding::Base58Check)
+            .starts_with(wire::CHECKED_PREFIX));
+
+        // Bare base58 is not the checked encoding
+        assert!(Fr::decode(&fr.compact(), TextEncoding::Base58Check).is_err());
+        let bytes = Bytes::from_static(b"fingerprint");
+        assert_eq!(
+            Bytes::decode(
+                &bytes.encode(TextEncoding::Base64Url),
+                TextEncoding::Base64Url
+            )?,
+            bytes
+        );
+
+        Ok(())
+    }
+
+    fn sample_transaction() -> Result<TransactionFingerprintData<Fr>, Error> {
+        let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+
+        RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount((1000u64, "EUR"))
+            .date_time(tx_date)
+            .wwd(tx_date.date_naive())
+            .build()?
+            .try_into()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_salted_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+
+        let unsalted = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let salted_a = sample_transaction()?
+            .with_salt(Bytes::from_static(b"consumer-a"))?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let salted_a_again = sample_transaction()?
+            .with_salt(Bytes::from_static(b"consumer-a"))?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let salted_b = sample_transaction()?
+            .with_salt(Bytes::from_static(b"consumer-b"))?
+            .complete_fingerprint(&protocol)
+            .await?;
+
+        assert_eq!(salted_a, salted_a_again);
+        assert_ne!(salted_a, unsalted);
+        assert_ne!(salted_a, salted_b);
+
+        // Anyone holding the salt can verify the salted fingerprint
+        assert_eq!(
+            salted_a.into_inner(),
+            salt_fingerprint(unsalted.into_inner(), b"consumer-a")?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fx_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+
+        let single = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let fx = |amount: u64, currency: &str| -> Result<TransactionFingerprintData<Fr>, Error> {
+            let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+
+            RawTransactionBuilder::default()
+                .bic("BCEELU21")
+                .amount((1000u64, "EUR"))
+                .counter_amount(Money::from((amount, currency)))
+                .date_time(tx_date)
+                .wwd(tx_date.date_naive())
+                .build()?
+                .try_into()
+        };
+
+        let usd = fx(1170, "USD")?;
+        assert_eq!(usd.counter_amount(), Some(((1170, 0), 840)));
+        let usd = usd.complete_fingerprint(&protocol).await?;
+        assert_ne!(usd, single);
+        assert_eq!(usd, fx(1170, "USD")?.complete_fingerprint(&protocol).await?);
+        assert_ne!(usd, fx(1171, "USD")?.complete_fingerprint(&protocol).await?);
+        assert_ne!(usd, fx(1170, "CHF")?.complete_fingerprint(&protocol).await?);
+
+        assert!(fx(1000, "EUR").is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fingerprint_with_datetime_scalar() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let tx = sample_transaction()?;
+
+        // Round run out-of-band on the squeezed date time
+        let unblinded = tx.date_time_component().squeeze()?;
+        let date_time = protocol.process(unblinded).await?;
+
+        assert_eq!(
+            tx.fingerprint_with_datetime_scalar(date_time)?,
+            tx.complete_fingerprint(&protocol).await?.into_inner()
+        );
+        assert_ne!(
+            tx.fingerprint_with_datetime_scalar(unblinded)?,
+            tx.complete_fingerprint(&protocol).await?.into_inner()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_custom_local_fingerprint() -> Result<(), Error> {
+        /// Data type of the integrator, only the local part is implemented
+        struct Voucher(u64);
+
+        impl LocalFingerprint<Fr> for Voucher {
+            fn unblinded_datetime(&self) -> Result<Fr, Error> {
+                Ok(Fr::from(self.0))
+            }
+
+            fn fingerprint(&self, date_time: Fr) -> Result<Fr, Error> {
+                Ok(date_time + Fr::one())
+            }
+        }
+
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let voucher = Voucher(7);
+        assert_eq!(
+            voucher.complete_fingerprint(&protocol).await?.into_inner(),
+            protocol.process(Fr::from(7)).await? + Fr::one()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_merchant_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+
+        let unbound = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let paid_to = |merchant_id: &str| -> Result<TransactionFingerprintData<Fr>, Error> {
+            sample_transaction()?.with_merchant_id(merchant_id.to_string())
+        };
+
+        let merchant = paid_to("MID-000123")?;
+        assert_eq!(merchant.merchant_id(), Some("MID-000123"));
+        let merchant = merchant.complete_fingerprint(&protocol).await?;
+        assert_ne!(merchant, unbound);
+        assert_eq!(
+            merchant,
+            paid_to("MID-000123")?
+                .complete_fingerprint(&protocol)
+                .await?
+        );
+        assert_ne!(
+            merchant,
+            paid_to("MID-000124")?
+                .complete_fingerprint(&protocol)
+                .await?
+        );
+
+        // Merchant is bound separately from the series, the same identifier does not collide
+        let series = sample_transaction()?
+            .with_series("MID-000123".to_string())?
+            .complete_fingerprint(&protocol)
+            .await?;
+        assert_ne!(merchant, series);
+
+        assert!(paid_to("").is_err());
+        assert!(paid_to(&"M".repeat(MAX_MERCHANT_ID_SIZE + 1)).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_deployment_components() -> Result<(), Error> {
+        use crate::components::{squeeze_bytes, BoundComponent};
+
+        /// Component of the deployment, outside of the crate
+        #[derive(Debug)]
+        struct LoyaltyId(&'static str, String);
+
+        impl SqueezeComponent<Fr> for LoyaltyId {
+            fn squeeze(&self) -> Result<Fr, Error> {
+                Ok(squeeze_bytes(self.1.as_bytes()))
+            }
+        }
+
+        impl BoundComponent for LoyaltyId {
+            fn domain_tag(&self) -> &str {
+                self.0
+            }
+        }
+
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let unbound = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let loyalty = |tag: &'static str, id: &str| {
+            sample_transaction()?.with_component(LoyaltyId(tag, id.into()))
+        };
+
+        let bound = loyalty("ACME_LOYALTY_ID", "L-1")?;
+        assert_eq!(
+            bound
+                .components()
+                .map(|c| c.domain_tag())
+                .collect::<Vec<_>>(),
+            vec!["ACME_LOYALTY_ID"]
+        );
+        let bound = bound.complete_fingerprint(&protocol).await?;
+        assert_ne!(bound, unbound);
+        assert_ne!(
+            bound,
+            loyalty("ACME_LOYALTY_ID", "L-2")?
+                .complete_fingerprint(&protocol)
+                .await?
+        );
+        assert_ne!(
+            bound,
+            loyalty("ACME_LOYALTY_REF", "L-1")?
+                .complete_fingerprint(&protocol)
+                .await?
+        );
+
+        // Tags of the crate, too long and repeated tags are refused
+        assert!(loyalty("CRA_FP_MERCHANT", "L-1").is_err());
+        assert!(loyalty("ACME_LOYALTY_IDENTIFIER_OF_THE_CARD", "L-1").is_err());
+        assert!(loyalty("ACME_LOYALTY_ID", "L-1")?
+            .with_component(LoyaltyId("ACME_LOYALTY_ID", "L-2".into()))
+            .is_err());
+
+        // Deployment components are bound the same way as the built-in ones
+        assert_eq!(
+            sample_transaction()?
+                .with_merchant_id("L-1".into())?
+                .complete_fingerprint(&protocol)
+                .await?
+                .into_inner(),
+            components::bind_scalar(
+                MERCHANT_DOMAIN_PREFIX,
+                unbound.into_inner(),
+                squeeze_bytes(b"L-1")
+            )
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_country_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+
+        assert_eq!(numeric_country_code("LU")?, 442);
+        assert_eq!(numeric_country_code("442")?, 442);
+        assert_eq!(numeric_country_code("004")?, 4);
+        for unknown in ["XX", "lu", "999", "42", "LUX", ""] {
+            assert!(numeric_country_code(unknown).is_err(), "{}", unknown);
+        }
+
+        let unbound = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let country = |code: &str| -> Result<TransactionFingerprintData<Fr>, Error> {
+            let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+
+            RawTransactionBuilder::default()
+                .bic("BCEELU21")
+                .amount((1000u64, "EUR"))
+                .country_code(Some(code.to_string()))
+                .date_time(tx_date)
+                .wwd(tx_date.date_naive())
+                .build()?
+                .try_into()
+        };
+
+        let luxembourg = country("LU")?;
+        assert_eq!(luxembourg.country_code(), Some(442));
+        let luxembourg = luxembourg.complete_fingerprint(&protocol).await?;
+        assert_ne!(luxembourg, unbound);
+        assert_eq!(
+            luxembourg,
+            country("442")?.complete_fingerprint(&protocol).await?
+        );
+        assert_ne!(
+            luxembourg,
+            country("BE")?.complete_fingerprint(&protocol).await?
+        );
+
+        assert!(country("XX").is_err());
+        assert!(sample_transaction()?.with_country_code(999).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_transaction_reference_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+
+        let unbound = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let referenced = |reference: &str| -> Result<TransactionFingerprintData<Fr>, Error> {
+            sample_transaction()?.with_transaction_reference(reference.to_string())
+        };
+
+        // Monthly payments of the same amount differ by the reference, the resubmitted one does not
+        let january = referenced("E2E-2025-01")?;
+        assert_eq!(january.transaction_reference(), Some("E2E-2025-01"));
+        let january = january.complete_fingerprint(&protocol).await?;
+        assert_ne!(january, unbound);
+        assert_eq!(
+            january,
+            referenced("E2E-2025-01")?
+                .complete_fingerprint(&protocol)
+                .await?
+        );
+        assert_ne!(
+            january,
+            referenced("E2E-2025-02")?
+                .complete_fingerprint(&protocol)
+                .await?
+        );
+
+        // Bound under its own domain, the same value as the merchant is another fingerprint
+        let merchant = sample_transaction()?.with_merchant_id("E2E-2025-01".into())?;
+        assert_ne!(january, merchant.complete_fingerprint(&protocol).await?);
+
+        assert!(referenced("").is_err());
+        assert!(referenced(NOT_PROVIDED_REFERENCE).is_err());
+        assert!(referenced(&"R".repeat(MAX_TRANSACTION_REFERENCE_SIZE + 1)).is_err());
+        assert!(referenced(&"R".repeat(MAX_TRANSACTION_REFERENCE_SIZE)).is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_mcc_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+
+        assert_eq!(numeric_mcc("5411")?, 5411);
+        assert_eq!(numeric_mcc("0742")?, 742);
+        for malformed in ["742", "54111", "54a1", "+541", ""] {
+            assert!(numeric_mcc(malformed).is_err(), "{}", malformed);
+        }
+
+        let unbound = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let category = |mcc: u16| -> Result<TransactionFingerprintData<Fr>, Error> {
+            sample_transaction()?.with_mcc(mcc)
+        };
+
+        let grocery = category(5411)?;
+        assert_eq!(grocery.mcc(), Some(5411));
+        let grocery = grocery.complete_fingerprint(&protocol).await?;
+        assert_ne!(grocery, unbound);
+        assert_eq!(
+            grocery,
+            category(5411)?.complete_fingerprint(&protocol).await?
+        );
+        assert_ne!(
+            grocery,
+            category(5812)?.complete_fingerprint(&protocol).await?
+        );
+
+        // Bound under its own domain, the same code as the country is another fingerprint
+        let country = sample_transaction()?.with_country_code(442)?;
+        assert_ne!(
+            category(442)?.complete_fingerprint(&protocol).await?,
+            country.complete_fingerprint(&protocol).await?
+        );
+
+        assert!(category(MAX_MCC + 1).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_counterparty_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+
+        let unbound = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let transfer =
+            |payer: &str, payee: &str| -> Result<TransactionFingerprintData<Fr>, Error> {
+                sample_transaction()?.with_counterparty(payer.to_string(), payee.to_string())
+            };
+
+        let forth = transfer("LU280019400644750000", "BE68539007547034")?;
+        assert_eq!(
+            forth.counterparty(),
+            Some(("LU280019400644750000", "BE68539007547034"))
+        );
+        let forth = forth.complete_fingerprint(&protocol).await?;
+        assert_ne!(forth, unbound);
+        assert_eq!(
+            forth,
+            transfer("LU280019400644750000", "BE68539007547034")?
+                .complete_fingerprint(&protocol)
+                .await?
+        );
+
+        // Pair is ordered, the transfer back is another fingerprint
+        let back = transfer("BE68539007547034", "LU280019400644750000")?;
+        assert_ne!(forth, back.complete_fingerprint(&protocol).await?);
+
+        assert!(transfer("", "BE68539007547034").is_err());
+        assert!(transfer(
+            "LU280019400644750000",
+            &"9".repeat(MAX_COUNTERPARTY_ID_SIZE + 1)
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_amount_tier_fingerprint() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let tiers = AmountTiers::new(vec![1_000, 10_000])?;
+        assert_eq!(tiers.tier((999, 990_000_000_000_000_000)), (0, 1_000));
+        assert_eq!(tiers.tier((1_000, 0)), (1_000, 10_000));
+        assert_eq!(tiers.tier((25_000, 0)), (10_000, u64::MAX));
+
+        let tiered =
+            |amount: u64, currency: &str| -> Result<TransactionFingerprintData<Fr>, Error> {
+                let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+                let tx: TransactionFingerprintData<Fr> = RawTransactionBuilder::default()
+                    .bic("BCEELU21")
+                    .amount((amount, currency))
+                    .date_time(tx_date)
+                    .wwd(tx_date.date_naive())
+                    .build()?
+                    .try_into()?;
+
+                Ok(tx.with_amount_tier(&tiers))
+            };
+
+        // Amounts of the same tier and currency bind the same value
+        let bound = tiered(1_500, "EUR")?;
+        assert_eq!(bound.amount_tier(), Some((1_000, 10_000)));
+        let bound_components = bound.bound_components()?;
+        assert_eq!(bound_components.len(), 1);
+        assert_eq!(bound_components[0].0, AMOUNT_TIER_DOMAIN_PREFIX);
+        assert_eq!(bound_components, tiered(9_999, "EUR")?.bound_components()?);
+        assert_ne!(bound_components, tiered(10_000, "EUR")?.bound_components()?);
+        assert_ne!(bound_components, tiered(1_500, "USD")?.bound_components()?);
+
+        // Tier is bound on top of the exact fingerprint, the untiered one stays the same.
+        // Sample transaction of 1000 EUR falls into the tier of 1_500 EUR, so it binds the same value
+        let unbound = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        let bound = sample_transaction()?
+            .with_amount_tier(&tiers)
+            .complete_fingerprint(&protocol)
+            .await?;
+        assert_ne!(bound, unbound);
+        assert_eq!(
+            bound.into_inner(),
+            components::bind_scalar(
+                AMOUNT_TIER_DOMAIN_PREFIX,
+                unbound.into_inner(),
+                bound_components[0].1
+            )
+        );
+
+        // Schemas list the tier as any other optional component
+        let schema = FingerprintSchema::new(1, vec![SchemaComponent::AmountTier])?;
+        assert!(tiered(1_500, "EUR")?
+            .with_schema(Some(Arc::new(schema)))
+            .validate()
+            .is_ok());
+        assert_eq!(
+            "amount-tier".parse::<SchemaComponent>()?,
+            SchemaComponent::AmountTier
+        );
+
+        assert!(AmountTiers::new(vec![]).is_err());
+        assert!(AmountTiers::new(vec![0, 1_000]).is_err());
+        assert!(AmountTiers::new(vec![10_000, 1_000]).is_err());
+        assert!(AmountTiers::new((1..MAX_AMOUNT_TIERS as u64 + 1).collect()).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_raw_transaction_round_trip() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        let tx = RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount(Money {
+                amount_base: 1000,
+                amount_atto: 550_000_000_000_000_000,
+                currency: "EUR".to_string(),
+            })
+            .counter_amount(Money::from((1170u64, "USD")))
+            .series_id("SO-2025-001".to_string())
+            .merchant_id("MID-1".to_string())
+            .country_code("442".to_string())
+            .transaction_reference("E2E-4711".to_string())
+            .mcc("0742".to_string())
+            .counterparty(Counterparty {
+                payer: "LU280019400644750000".to_string(),
+                payee: "BE68539007547034".to_string(),
+            })
+            .date_time(tx_date)
+            .wwd(tx_date.date_naive().pred_opt().unwrap())
+            .build()?;
+
+        let tx_data: TransactionFingerprintData<Fr> = tx.clone().try_into()?;
+        assert_eq!(tx_data.date_time(), &tx_date);
+        assert_eq!(tx_data.wwd(), tx.wwd);
+        assert_eq!(tx_data.amount(), (1000, 550_000_000_000_000_000));
+
+        // Numeric country code comes back as alpha-2, the transaction fingerprints the same
+        let raw = tx_data.into_raw_transaction()?;
+        assert_eq!(raw.country_code.as_deref(), Some("LU"));
+        assert_eq!(
+            raw,
+            RawTransaction {
+                country_code: Some("LU".to_string()),
+                ..tx.clone()
+            }
+        );
+        let raw_data: TransactionFingerprintData<Fr> = raw.try_into()?;
+        let tx_data: TransactionFingerprintData<Fr> = tx.try_into()?;
+        assert_eq!(
+            raw_data.complete_fingerprint(&protocol).await?,
+            tx_data.complete_fingerprint(&protocol).await?
+        );
+
+        let raw = sample_transaction()?.into_raw_transaction()?;
+        assert_eq!(raw.counter_amount, None);
+        assert_eq!(raw.amount.currency, "EUR");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_salt_size_validation() -> Result<(), Error> {
+        assert!(sample_transaction()?.with_salt(Bytes::new()).is_err());
+        assert!(sample_transaction()?
+            .with_salt(Bytes::from(vec![7u8; MAX_SALT_SIZE + 1]))
+            .is_err());
+        assert!(sample_transaction()?
+            .with_salt(Bytes::from(vec![7u8; MAX_SALT_SIZE]))
+            .is_ok());
+
+        // Trailing zero bytes produce a distinct salt
+        assert_ne!(
+            salt_fingerprint(Fr::from(42), &[1u8])?,
+            salt_fingerprint(Fr::from(42), &[1u8, 0u8])?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_squeeze() -> Result<(), Error> {
+        let four_limbs = |bytes: &Bytes| {
+            let limbs = bytes
+                .chunks(bytes.len() / 4)
+                .map(bytes_limb)
+                .collect::<Vec<_>>();
+            let mut poseidon = fingerprint_poseidon::<5, 4>();
+            poseidon.update(&limbs);
+            poseidon.squeeze()
+        };
+
+        // Preimage layouts so far keep their hash
+        for size in LEGACY_PREIMAGE_SIZES {
+            let preimage = Bytes::from((0..size as u8).collect::<Vec<_>>());
+            assert_eq!(preimage.squeeze()?, four_limbs(&preimage));
+        }
+
+        // Other lengths divisible into the 4 limbs are tagged
+        for size in [4, 8, 76, 92, 124] {
+            let bytes = Bytes::from((0..size as u8).collect::<Vec<_>>());
+            assert_ne!(bytes.squeeze()?, four_limbs(&bytes));
+        }
+
+        // Any other length is hashed, the trailing zero bytes included
+        let squeezed = (0..200u8)
+            .map(|len| Bytes::from((0..len).collect::<Vec<_>>()).squeeze())
+            .collect::<Result<Vec<_>, _>>()?;
+        for (len, value) in squeezed.iter().enumerate() {
+            assert!(squeezed[..len].iter().all(|other| other != value));
+        }
+        assert_ne!(
+            Bytes::from_static(&[1, 2, 3]).squeeze()?,
+            Bytes::from_static(&[1, 2, 3, 0]).squeeze()?
+        );
+        assert_ne!(
+            Bytes::from_static(&[1, 2, 3]).squeeze()?,
+            Bytes::from_static(&[1, 2, 3, 1]).squeeze()?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_wire_versions() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let fingerprint = |version| {
+            let protocol = &protocol;
+            async move {
+                sample_transaction()?
+                    .with_wire_version(version)
+                    .complete_fingerprint(protocol)
+                    .await
+            }
+        };
+
+        // Current version keeps the legacy preimage, the versioned one is another fingerprint
+        let current = sample_transaction()?
+            .complete_fingerprint(&protocol)
+            .await?;
+        assert_eq!(fingerprint(WireVersion::V1).await?, current);
+        let versioned = fingerprint(WireVersion::V2).await?;
+        assert_ne!(versioned, current);
+        assert_eq!(fingerprint(WireVersion::V2).await?, versioned);
+        // Packed nonce of the date time and the amount in the atto units are other fingerprints of the same layout
+        let packed = fingerprint(WireVersion::V3).await?;
+        assert_ne!(packed, versioned);
+        assert_ne!(fingerprint(WireVersion::V4).await?, packed);
+
+        // Versioned preimage of the schema other than the built-in one still splits into the limbs
+        let schema = FingerprintSchema::new(1, vec![SchemaComponent::Merchant])?;
+        let merchant = sample_transaction()?
+            .with_merchant_id("MID-1".to_string())?
+            .with_schema(Some(Arc::new(schema)))
+            .with_wire_version(WireVersion::V2);
+        assert_ne!(merchant.complete_fingerprint(&protocol).await?, versioned);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_input_size_limits() -> Result<(), Error> {
+        // Limits of the raw fields are the sizes the components encode
+        assert_eq!(InputLimits::MAX.merchant_id, MAX_MERCHANT_ID_SIZE);
+        assert_eq!(InputLimits::MAX.series_id, MAX_SERIES_ID_SIZE);
+        assert_eq!(
+            InputLimits::MAX.transaction_reference,
+            MAX_TRANSACTION_REFERENCE_SIZE
+        );
+        assert_eq!(InputLimits::MAX.counterparty_id, MAX_COUNTERPARTY_ID_SIZE);
+        assert_eq!(InputLimits::MAX.mcc, MAX_MCC.to_string().len());
+
+        let tx_date = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        let oversized = RawTransactionBuilder::default()
+            .bic("B".repeat(1024 * 1024))
+            .amount((1000u64, "EUR"))
+            .date_time(tx_date)
+            .wwd(tx_date.date_naive())
+            .build()?;
+        let e = TransactionFingerprintData::<Fr>::try_from(oversized).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Field bic should be at most 11 bytes long, given 1048576 bytes"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scrubbed_transaction_debug() -> Result<(), Error> {
+        let tx = sample_transaction()?
+            .with_merchant_id("MID-000123".to_string())?
+            .with_counterparty(
+                "LU280019400644750000".to_string(),
+                "BE68539007547034".to_string(),
+            )?;
+
+        // Confidential values are truncated and personal ones redacted by the default policy
+        let debug = format!("{:?}", tx);
+        assert!(debug.contains("BCEE***"), "{}", debug);
+        assert!(!debug.contains("BCEELU21"), "{}", debug);
+        assert!(!debug.contains("MID-000123"), "{}", debug);
+        assert!(!debug.contains("LU280019400644750000"), "{}", debug);
+        assert!(debug.contains("<counterparty>"), "{}", debug);
+
+        Ok(())
+    }
 }
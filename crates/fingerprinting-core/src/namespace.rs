@@ -0,0 +1,90 @@
+use crate::{fingerprint_poseidon, NAMESPACE_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::Fr;
+
+/// Maximum size of the namespace in bytes, so the domain tag with the namespace fits into Fr
+pub const MAX_NAMESPACE_SIZE: usize = 31 - NAMESPACE_DOMAIN_PREFIX.len();
+
+/// Environment the fingerprints are computed in (prod, staging, partner-x)
+/// The namespace is mixed into the domain tag: Poseidon(NAMESPACE_DOMAIN | namespace, fingerprint),
+/// so fingerprints of the different environments never collide, even with the same secret.
+/// Fingerprints without the namespace stay the same.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespace {
+    name: String,
+}
+
+impl Namespace {
+    /// Namespace consists of lowercase ASCII letters, digits, `-`, `_` and `.`
+    pub fn new(name: &str) -> Result<Self, Error> {
+        if name.is_empty() || name.len() > MAX_NAMESPACE_SIZE {
+            return Err(anyhow!(
+                "Namespace should be from 1 to {} bytes long, given {} bytes",
+                MAX_NAMESPACE_SIZE,
+                name.len()
+            ));
+        }
+        let valid = name.bytes().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-' || c == b'_' || c == b'.'
+        });
+        if !valid {
+            return Err(anyhow!(
+                "Namespace {} should consist of lowercase letters, digits, '-', '_' and '.'",
+                name
+            ));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Domain tag with the namespace, zero byte never appears in the namespace, so the padding is unambiguous
+    pub fn domain(&self) -> Fr {
+        let mut domain = [0u8; 32];
+        domain[0..NAMESPACE_DOMAIN_PREFIX.len()]
+            .copy_from_slice(NAMESPACE_DOMAIN_PREFIX.as_bytes());
+        domain[NAMESPACE_DOMAIN_PREFIX.len()..NAMESPACE_DOMAIN_PREFIX.len() + self.name.len()]
+            .copy_from_slice(self.name.as_bytes());
+
+        Fr::from_bytes(&domain).unwrap_or(Fr::zero())
+    }
+
+    /// Separates the unsalted fingerprint into the namespace
+    pub fn separate(&self, fingerprint: Fr) -> Fr {
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+        poseidon.update(&[self.domain(), fingerprint]);
+
+        poseidon.squeeze()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_separation() -> Result<(), Error> {
+        let prod = Namespace::new("prod")?;
+        let staging = Namespace::new("staging")?;
+
+        assert_eq!(
+            prod.separate(Fr::from(42)),
+            Namespace::new("prod")?.separate(Fr::from(42))
+        );
+        assert_ne!(prod.separate(Fr::from(42)), staging.separate(Fr::from(42)));
+        assert_ne!(prod.separate(Fr::from(42)), Fr::from(42));
+
+        assert!(Namespace::new("").is_err());
+        assert!(Namespace::new("Prod").is_err());
+        assert!(Namespace::new("prod\0").is_err());
+        assert!(Namespace::new(&"x".repeat(MAX_NAMESPACE_SIZE + 1)).is_err());
+        assert!(Namespace::new(&"x".repeat(MAX_NAMESPACE_SIZE)).is_ok());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+//! Sensitivity classes of the transaction fields and the scrubbing of their values out of the logs and the errors
+//!
+//! Every field of the transaction is classified, see `PiiField::sensitivity`. The values written into the log lines,
+//! the error messages and the `Debug` output of the components go through `scrubbed`, which applies the process wide
+//! `PiiPolicy` of their class: public values are written as is, confidential ones are truncated and personal ones are
+//! redacted by default. Debugging deployments could reveal the values with `set_policy(PiiPolicy::REVEAL)`.
+
+use std::fmt;
+use std::sync::RwLock;
+
+/// Characters of the truncated value kept in front of the mask
+pub const TRUNCATED_PREFIX_LEN: usize = 4;
+
+const MASK: &str = "***";
+
+/// Sensitivity class of the transaction field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sensitivity {
+    /// Code lists shared by many transactions, e.g. the currency
+    Public,
+    /// Values of the institution and of the transaction, e.g. the BIC and the amount
+    Confidential,
+    /// Values identifying the persons, e.g. the IBANs of the counterparties
+    Personal,
+}
+
+/// Field of the transaction, named as in the proto and in `RawTransaction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiField {
+    Bic,
+    Amount,
+    Currency,
+    DateTime,
+    CounterAmount,
+    SeriesId,
+    MerchantId,
+    CountryCode,
+    TransactionReference,
+    Mcc,
+    Counterparty,
+    Salt,
+}
+
+impl PiiField {
+    pub const ALL: [PiiField; 12] = [
+        PiiField::Bic,
+        PiiField::Amount,
+        PiiField::Currency,
+        PiiField::DateTime,
+        PiiField::CounterAmount,
+        PiiField::SeriesId,
+        PiiField::MerchantId,
+        PiiField::CountryCode,
+        PiiField::TransactionReference,
+        PiiField::Mcc,
+        PiiField::Counterparty,
+        PiiField::Salt,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PiiField::Bic => "bic",
+            PiiField::Amount => "amount",
+            PiiField::Currency => "currency",
+            PiiField::DateTime => "date_time",
+            PiiField::CounterAmount => "counter_amount",
+            PiiField::SeriesId => "series_id",
+            PiiField::MerchantId => "merchant_id",
+            PiiField::CountryCode => "country_code",
+            PiiField::TransactionReference => "transaction_reference",
+            PiiField::Mcc => "mcc",
+            PiiField::Counterparty => "counterparty",
+            PiiField::Salt => "salt",
+        }
+    }
+
+    pub fn sensitivity(&self) -> Sensitivity {
+        match self {
+            PiiField::Currency | PiiField::CountryCode | PiiField::Mcc => Sensitivity::Public,
+            PiiField::Bic
+            | PiiField::Amount
+            | PiiField::DateTime
+            | PiiField::CounterAmount
+            | PiiField::MerchantId => Sensitivity::Confidential,
+            PiiField::SeriesId
+            | PiiField::TransactionReference
+            | PiiField::Counterparty
+            | PiiField::Salt => Sensitivity::Personal,
+        }
+    }
+}
+
+/// How the values of the sensitivity class are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scrub {
+    Reveal,
+    /// First `TRUNCATED_PREFIX_LEN` characters followed by the mask
+    Truncate,
+    /// Name of the field only
+    Redact,
+}
+
+/// Scrubbing of the confidential and the personal values, the public ones are always revealed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PiiPolicy {
+    pub confidential: Scrub,
+    pub personal: Scrub,
+}
+
+impl PiiPolicy {
+    pub const DEFAULT: PiiPolicy = PiiPolicy {
+        confidential: Scrub::Truncate,
+        personal: Scrub::Redact,
+    };
+
+    /// Writes all the values as is, for the debugging on the test data only
+    pub const REVEAL: PiiPolicy = PiiPolicy {
+        confidential: Scrub::Reveal,
+        personal: Scrub::Reveal,
+    };
+
+    pub fn scrub(&self, sensitivity: Sensitivity) -> Scrub {
+        match sensitivity {
+            Sensitivity::Public => Scrub::Reveal,
+            Sensitivity::Confidential => self.confidential,
+            Sensitivity::Personal => self.personal,
+        }
+    }
+}
+
+impl Default for PiiPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static POLICY: RwLock<PiiPolicy> = RwLock::new(PiiPolicy::DEFAULT);
+
+/// Sets the policy of the process, applied by all the values scrubbed afterwards
+pub fn set_policy(policy: PiiPolicy) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+pub fn policy() -> PiiPolicy {
+    *POLICY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Value of the `field` written by the policy of the process, both by `Display` and by `Debug`
+pub fn scrubbed<T: fmt::Display>(field: PiiField, value: T) -> Scrubbed<T> {
+    Scrubbed { field, value }
+}
+
+pub struct Scrubbed<T> {
+    field: PiiField,
+    value: T,
+}
+
+impl<T: fmt::Display> fmt::Display for Scrubbed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match policy().scrub(self.field.sensitivity()) {
+            Scrub::Reveal => write!(f, "{}", self.value),
+            Scrub::Truncate => {
+                let value = self.value.to_string();
+                let prefix: String = value.chars().take(TRUNCATED_PREFIX_LEN).collect();
+                write!(f, "{}{}", prefix, MASK)
+            }
+            Scrub::Redact => write!(f, "<{}>", self.field.name()),
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Debug for Scrubbed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrubbed_values() {
+        let policy = PiiPolicy::DEFAULT;
+        assert_eq!(
+            policy.scrub(PiiField::Currency.sensitivity()),
+            Scrub::Reveal
+        );
+        assert_eq!(
+            policy.scrub(PiiField::Counterparty.sensitivity()),
+            Scrub::Redact
+        );
+
+        // Policy of the process is the default one, the tests do not change it
+        assert_eq!(scrubbed(PiiField::Currency, "EUR").to_string(), "EUR");
+        assert_eq!(scrubbed(PiiField::Bic, "BCEELU21").to_string(), "BCEE***");
+        assert_eq!(scrubbed(PiiField::Amount, "1000.55").to_string(), "1000***");
+        assert_eq!(
+            format!("{:?}", scrubbed(PiiField::TransactionReference, "E2E-42")),
+            "<transaction_reference>"
+        );
+    }
+}
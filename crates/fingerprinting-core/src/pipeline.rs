@@ -0,0 +1,345 @@
+//! Two-stage computation of the fingerprints for the batch orchestrators
+//!
+//! `prepare` does the local hashing of the transactions over the rayon threads (pure CPU, no agent is contacted),
+//! `finalize` evaluates the chunk in one protocol round (`FingerprintProtocol::process_batch`) and completes the
+//! fingerprints. So the orchestrator hashes the chunk N+1 while the agents evaluate the chunk N, rather than waiting
+//! for both in turn.
+//!
+//! `fingerprint_batch` computes the whole batch at once: the hashing is spread over the rayon threads, the calling task
+//! awaits it without blocking the async runtime, and all the items are evaluated by
+//! `FingerprintProtocol::process_batch`. `fingerprint_stream` does the same chunk by chunk for the datasets too large
+//! to collect, only one chunk of the input is held at once.
+
+use crate::{Fingerprint, FingerprintProtocol, LocalFingerprint, TransactionFingerprintData};
+use anyhow::{anyhow, Error};
+use fingerprinting_types::RawTransaction;
+use futures::channel::oneshot;
+use futures::stream::{self, Stream, StreamExt};
+use halo2_axiom::halo2curves::bn256::Fr;
+use rayon::prelude::*;
+use std::num::NonZeroUsize;
+
+/// Transaction with its unblinded date time value, ready for the protocol round
+#[derive(Debug)]
+pub struct PreparedItem<'a> {
+    data: &'a TransactionFingerprintData<Fr>,
+    /// Failure of the hashing is reported by `finalize`, in the place of the item
+    unblinded: Result<Fr, Error>,
+}
+
+impl<'a> PreparedItem<'a> {
+    pub fn data(&self) -> &'a TransactionFingerprintData<Fr> {
+        self.data
+    }
+
+    /// Value evaluated by the protocol round
+    pub fn unblinded(&self) -> Option<Fr> {
+        self.unblinded.as_ref().ok().copied()
+    }
+}
+
+/// Hashes the transactions locally on the rayon threads, no agent is contacted
+pub fn prepare(items: &[TransactionFingerprintData<Fr>]) -> Vec<PreparedItem<'_>> {
+    items
+        .par_iter()
+        .map(|data| PreparedItem {
+            data,
+            unblinded: data.unblinded_datetime(),
+        })
+        .collect()
+}
+
+/// Runs the protocol round of all the `prepared` items at once and completes their fingerprints,
+/// in the order of the items, see `FingerprintProtocol::process_batch` for the round
+pub async fn finalize<P: FingerprintProtocol<Fr> + Sync>(
+    prepared: Vec<PreparedItem<'_>>,
+    protocol: &P,
+) -> Vec<Result<Fr, Error>> {
+    let (items, unblinded): (Vec<_>, Vec<_>) = prepared
+        .into_iter()
+        .map(|item| (item.data, item.unblinded))
+        .unzip();
+    let date_times = evaluate(unblinded, protocol).await;
+
+    items
+        .into_iter()
+        .zip(date_times)
+        .map(|(data, date_time)| data.fingerprint_with_datetime_scalar(date_time?))
+        .collect()
+}
+
+/// Evaluates the hashed values in one protocol round, the failed ones keep their errors
+async fn evaluate<P: FingerprintProtocol<Fr> + Sync>(
+    unblinded: Vec<Result<Fr, Error>>,
+    protocol: &P,
+) -> Vec<Result<Fr, Error>> {
+    // Only the hashed items enter the round
+    let round = unblinded
+        .iter()
+        .filter_map(|unblinded| unblinded.as_ref().ok().copied())
+        .collect();
+    let mut evaluated = protocol.process_batch(round).await.into_iter();
+
+    unblinded
+        .into_iter()
+        .map(|unblinded| {
+            unblinded.and_then(|_| {
+                evaluated.next().unwrap_or_else(|| {
+                    Err(anyhow!(
+                        "Protocol round returned fewer values than evaluated"
+                    ))
+                })
+            })
+        })
+        .collect()
+}
+
+/// Runs the CPU bound `work` on the rayon threads, the calling task awaits it without holding its runtime thread
+async fn on_rayon<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, Error> {
+    let (result, computed) = oneshot::channel();
+    rayon::spawn(move || {
+        let _ = result.send(work());
+    });
+
+    computed
+        .await
+        .map_err(|_| anyhow!("Hashing on the rayon threads panicked"))
+}
+
+/// Fingerprints of the `items` in their order, the failure of the item fails only the item. The local hashing before
+/// and after the round runs on the rayon threads, see `FingerprintProtocol::process_batch` for the round
+pub async fn fingerprint_batch<P: FingerprintProtocol<Fr> + Sync>(
+    items: Vec<TransactionFingerprintData<Fr>>,
+    protocol: &P,
+) -> Vec<Result<Fingerprint, Error>> {
+    let len = items.len();
+    // Panicked hashing fails all the items
+    let failed = |e: Error| -> Vec<Result<Fingerprint, Error>> {
+        (0..len).map(|_| Err(anyhow!("{}", e))).collect()
+    };
+
+    let hashed = on_rayon(move || {
+        let unblinded = items
+            .par_iter()
+            .map(|data| data.unblinded_datetime())
+            .collect::<Vec<_>>();
+        (items, unblinded)
+    })
+    .await;
+    let (items, unblinded) = match hashed {
+        Ok(hashed) => hashed,
+        Err(e) => return failed(e),
+    };
+
+    let date_times = evaluate(unblinded, protocol).await;
+
+    on_rayon(move || {
+        items
+            .par_iter()
+            .zip(date_times)
+            .map(|(data, date_time)| {
+                data.fingerprint_with_datetime_scalar(date_time?)
+                    .map(Fingerprint::new)
+            })
+            .collect()
+    })
+    .await
+    .unwrap_or_else(failed)
+}
+
+/// Fingerprints of the `transactions` paired with their input ids, in the order of the input. The transactions are
+/// read `chunk_size` at once and every chunk is computed by `fingerprint_batch`, so the memory is bounded by the chunk
+/// rather than the dataset. The failure of the transaction fails only the transaction
+pub fn fingerprint_stream<'a, I, S, P>(
+    transactions: S,
+    protocol: &'a P,
+    chunk_size: NonZeroUsize,
+) -> impl Stream<Item = (I, Result<Fingerprint, Error>)> + 'a
+where
+    I: 'a,
+    S: Stream<Item = (I, RawTransaction)> + 'a,
+    P: FingerprintProtocol<Fr> + Sync,
+{
+    transactions
+        .chunks(chunk_size.get())
+        .then(move |chunk| fingerprint_chunk(chunk, protocol))
+        .flat_map(stream::iter)
+}
+
+async fn fingerprint_chunk<I, P: FingerprintProtocol<Fr> + Sync>(
+    chunk: Vec<(I, RawTransaction)>,
+    protocol: &P,
+) -> Vec<(I, Result<Fingerprint, Error>)> {
+    let mut items = Vec::with_capacity(chunk.len());
+    let converted = chunk
+        .into_iter()
+        .map(|(input_id, tx)| {
+            // Only the converted transactions enter the batch, the others keep their errors
+            let failure = TransactionFingerprintData::try_from(tx)
+                .map(|data| items.push(data))
+                .err();
+            (input_id, failure)
+        })
+        .collect::<Vec<_>>();
+
+    let mut fingerprints = fingerprint_batch(items, protocol).await.into_iter();
+    converted
+        .into_iter()
+        .map(|(input_id, failure)| {
+            let fingerprint = match failure {
+                Some(e) => Err(e),
+                None => fingerprints.next().unwrap_or_else(|| {
+                    Err(anyhow!("Batch returned fewer fingerprints than computed"))
+                }),
+            };
+            (input_id, fingerprint)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NaiveProtocol, ProtocolFingerprint};
+    use chrono::{Duration, TimeZone, Utc};
+    use fingerprinting_types::RawTransactionBuilder;
+    use std::sync::Mutex;
+
+    /// Protocol recording the sizes of the batched rounds
+    struct Batches {
+        batches: Mutex<Vec<usize>>,
+        protocol: NaiveProtocol,
+    }
+
+    impl FingerprintProtocol<Fr> for Batches {
+        async fn process(&self, unblinded: Fr) -> Result<Fr, Error> {
+            self.batches.lock().unwrap().push(1);
+            self.protocol.process(unblinded).await
+        }
+
+        async fn process_batch(&self, unblinded: Vec<Fr>) -> Vec<Result<Fr, Error>> {
+            self.batches.lock().unwrap().push(unblinded.len());
+            self.protocol.process_batch(unblinded).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prepare_finalize() -> Result<(), Error> {
+        let protocol = Batches {
+            batches: Mutex::new(vec![]),
+            protocol: NaiveProtocol::new(Fr::from(42)),
+        };
+        let chunk = |offset: i64| {
+            (0..4)
+                .map(|i| {
+                    let date_time = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap()
+                        + Duration::minutes(offset + i);
+                    RawTransactionBuilder::default()
+                        .bic("BCEELU21")
+                        .amount((1000u64 + i as u64, "EUR"))
+                        .date_time(date_time)
+                        .wwd(date_time.date_naive())
+                        .build()?
+                        .try_into()
+                })
+                .collect::<Result<Vec<TransactionFingerprintData<Fr>>, Error>>()
+        };
+        let (first, second) = (chunk(0)?, chunk(10)?);
+
+        // Hashing of the second chunk overlaps the round of the first one
+        let prepared = prepare(&first);
+        let (fingerprints, next) =
+            tokio::join!(finalize(prepared, &protocol), async { prepare(&second) });
+        let fingerprints = fingerprints
+            .into_iter()
+            .chain(finalize(next, &protocol).await)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Every chunk is evaluated by its single round
+        assert_eq!(*protocol.batches.lock().unwrap(), vec![4, 4]);
+
+        for (data, fingerprint) in first.iter().chain(&second).zip(fingerprints) {
+            assert_eq!(
+                data.complete_fingerprint(&protocol.protocol)
+                    .await?
+                    .into_inner(),
+                fingerprint
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_batch() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let items = || {
+            (0..16)
+                .map(|i| {
+                    let date_time = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap()
+                        + Duration::minutes(i);
+                    RawTransactionBuilder::default()
+                        .bic("BCEELU21")
+                        .amount((1000u64 + i as u64, "EUR"))
+                        .date_time(date_time)
+                        .wwd(date_time.date_naive())
+                        .build()?
+                        .try_into()
+                })
+                .collect::<Result<Vec<TransactionFingerprintData<Fr>>, Error>>()
+        };
+
+        let fingerprints = fingerprint_batch(items()?, &protocol)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(fingerprints.len(), 16);
+        for (data, fingerprint) in items()?.iter().zip(fingerprints) {
+            assert_eq!(data.complete_fingerprint(&protocol).await?, fingerprint);
+        }
+        assert!(fingerprint_batch(vec![], &protocol).await.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_stream() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+        let transactions = (0..10).map(|i| {
+            let date_time =
+                Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap() + Duration::minutes(i);
+            let currency = if i == 5 { "XXZ" } else { "EUR" };
+            let tx = RawTransactionBuilder::default()
+                .bic("BCEELU21")
+                .amount((1000u64 + i as u64, currency))
+                .date_time(date_time)
+                .wwd(date_time.date_naive())
+                .build()
+                .unwrap();
+            (format!("tx-{}", i), tx)
+        });
+
+        // Chunks of 4 leave the last one partial, the invalid transaction fails alone
+        let fingerprints = fingerprint_stream(
+            stream::iter(transactions.clone()),
+            &protocol,
+            NonZeroUsize::new(4).unwrap(),
+        )
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(fingerprints.len(), 10);
+        for ((input_id, tx), (fingerprinted_id, fingerprint)) in transactions.zip(fingerprints) {
+            assert_eq!(input_id, fingerprinted_id);
+            if input_id == "tx-5" {
+                assert!(fingerprint.is_err());
+                continue;
+            }
+            let data = TransactionFingerprintData::<Fr>::try_from(tx)?;
+            assert_eq!(data.complete_fingerprint(&protocol).await?, fingerprint?);
+        }
+
+        Ok(())
+    }
+}
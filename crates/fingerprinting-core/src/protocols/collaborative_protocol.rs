@@ -1,20 +1,22 @@
 use anyhow::{anyhow, Error};
-use halo2_axiom::arithmetic::Field;
 use halo2_axiom::halo2curves::bn256::{Fr, G1};
 use halo2_axiom::halo2curves::ff::PrimeField as PF;
 use halo2_axiom::halo2curves::group::Group;
-use halo2_axiom::halo2curves::CurveExt;
 
 use std::marker::PhantomData;
+use std::sync::Arc;
 
-use futures::future::ready;
-use futures::{StreamExt, TryFutureExt};
+use std::time::Duration;
 
+use crate::protocols::members::Members;
+use crate::protocols::phases::{
+    Blinded, DiversityPolicy, EvaluationVerifier, Phase, PhaseError, PhaseMetrics, Phases,
+};
 use crate::protocols::FingerprintProtocol;
-use crate::{Compact, HashSqueeze, HASH_TO_CURVE_PREFIX};
+use crate::Compact;
 
+use crate::entropy::{self, EntropySource};
 use crate::secret_sharing::SecretSharing;
-use rand_core::OsRng;
 
 pub trait AgentsTopology<F: PF, G: Group<Scalar = F>> {
     ///
@@ -43,6 +45,8 @@ pub struct CollaborativeProtocol<F: PF, G: Group<Scalar = F>, T: AgentsTopology<
     agent: usize,    // agent number
     secret_shard: F, // our own secret shard
     topology: T,
+    entropy: Arc<dyn EntropySource>,
+    phases: Phases,
     _phantom: PhantomData<G>,
 }
 
@@ -52,91 +56,173 @@ impl<F: PF, G: Group<Scalar = F>, T: AgentsTopology<F, G>> CollaborativeProtocol
             agent: agent_info.0,
             secret_shard: agent_info.1,
             topology,
+            entropy: entropy::default_source(),
+            phases: Phases::default(),
             _phantom: Default::default(),
         }
     }
+
+    /// Source of the blinding factors, the health tested OS RNG by default
+    pub fn with_entropy(mut self, entropy: Arc<dyn EntropySource>) -> Self {
+        self.entropy = entropy;
+        self
+    }
+
+    /// Fails the `phase` not completed within the `timeout`, the phases are not limited by default
+    pub fn with_phase_timeout(mut self, phase: Phase, timeout: Duration) -> Self {
+        self.phases.set_timeout(phase, timeout);
+        self
+    }
+
+    /// Verifier of the collected evaluations, `NonIdentity` by default
+    pub fn with_verifier(mut self, verifier: Arc<dyn EvaluationVerifier>) -> Self {
+        self.phases.set_verifier(verifier);
+        self
+    }
+
+    /// Evaluations of the agents spanning fewer institutions than the `diversity` requires are refused,
+    /// no institutions are required by default
+    pub fn with_diversity(mut self, diversity: DiversityPolicy) -> Self {
+        self.phases.set_diversity(diversity);
+        self
+    }
+
+    /// Metadata of the agents, the errors and the logs name the agents by it
+    pub fn with_members(mut self, members: Members) -> Self {
+        self.phases.set_members(members);
+        self
+    }
+
+    /// Counters and timings of the phases of this protocol
+    pub fn phase_metrics(&self) -> Arc<PhaseMetrics> {
+        self.phases.metrics()
+    }
+}
+
+impl<T: AgentsTopology<Fr, G1> + Sync> CollaborativeProtocol<Fr, G1, T> {
+    /// Evaluates `[k] B` of the secret `k` shared by the topology, the blinded point stays blinded
+    pub async fn evaluate(&self, blinded_value: G1) -> Result<G1, Error> {
+        self.evaluate_among(blinded_value, None).await
+    }
+
+    async fn evaluate_among(
+        &self,
+        blinded_value: G1,
+        agents: Option<&[usize]>,
+    ) -> Result<G1, Error> {
+        if let Some(agents) = agents {
+            if !agents.contains(&self.agent) {
+                return Err(anyhow!(
+                    "Agent {} is not one of the agents {:?} allowed",
+                    self.agent,
+                    agents
+                ));
+            }
+        }
+
+        interpolate(
+            &self.phases,
+            &self.topology,
+            (self.agent, blinded_value * self.secret_shard),
+            blinded_value,
+            agents,
+        )
+        .await
+    }
 }
 
 impl<T: AgentsTopology<Fr, G1> + Sync> FingerprintProtocol<Fr>
     for CollaborativeProtocol<Fr, G1, T>
 {
     async fn process(&self, unblinded: Fr) -> Result<Fr, Error> {
-        let mut rng = OsRng::default();
-
-        log::debug!("Processing unblinded value: {}", unblinded.compact());
-
-        let curve_point = {
-            // Reflect unblinded Fr on curve via hash_to_curve Eligator2 function
-            let hasher = G1::hash_to_curve(HASH_TO_CURVE_PREFIX);
-            hasher(&unblinded.to_bytes())
-        };
-
-        // Select the blinding factor `r`
-        let blinding_factor = Fr::random(&mut rng);
-
-        // Compute the blinded_hash
-        let blinded_hash = curve_point * blinding_factor;
-
-        // Collect the threshold responses from agents
-        let mut responses = futures::stream::iter(1..=self.topology.count())
-            .filter(|agent| ready(agent.clone() != self.agent))
-            .map(|i| {
-                let agent = i.clone();
-                self.topology
-                    .obtain_shard(i, 0, blinded_hash.clone())
-                    .map_err(move |e| {
-                        log::error!("Error while getting shard from agent {}: {}", agent, e);
-                        e
-                    })
-                    .map_ok_or_else(|_| (0, G1::generator()), |v| v) // Todo add logging here
-            })
-            .buffer_unordered(1024) // TODO parametrize concurrency
-            .filter(|(p, _)| ready(p.clone() > 0))
-            .take(self.topology.threshold() - 1) // Since we already have one response from self.agent
-            .collect::<Vec<(usize, G1)>>()
-            .await;
-
-        responses.push((self.agent, blinded_hash * self.secret_shard));
-
-        if responses.len() < self.topology.threshold() {
-            return Err(anyhow!("Not enough responses from other agents"));
-        }
+        blind_and_evaluate(
+            &self.phases,
+            self.entropy.as_ref(),
+            unblinded,
+            |blinded_hash| self.evaluate(blinded_hash),
+        )
+        .await
+    }
 
-        // Precompute cooperative agents indexes
-        let indices = responses.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>();
+    async fn process_via(&self, unblinded: Fr, agents: &[usize]) -> Result<Fr, Error> {
+        blind_and_evaluate(
+            &self.phases,
+            self.entropy.as_ref(),
+            unblinded,
+            |blinded_hash| self.evaluate_among(blinded_hash, Some(agents)),
+        )
+        .await
+    }
+}
 
-        log::debug!(
-            "Got {} results from other agents: {:?}",
-            indices.len(),
-            indices
-        );
+/// Combines the `own` evaluation with the threshold - 1 evaluations of the other members of the `topology`
+/// by the Lagrange interpolation, the members are only the `allowed` ones when given
+pub(crate) async fn interpolate<T: AgentsTopology<Fr, G1> + Sync>(
+    phases: &Phases,
+    topology: &T,
+    own: (usize, G1),
+    blinded_value: G1,
+    allowed: Option<&[usize]>,
+) -> Result<G1, Error> {
+    let collected = phases
+        .run(
+            Phase::Collect,
+            phases.collect(topology, own, blinded_value, allowed),
+        )
+        .await?;
+    let verified = phases
+        .run(Phase::Verify, async { collected.verify(phases.verifier()) })
+        .await
+        .inspect_err(|e| {
+            if let PhaseError::Refused { agent, reason } = e {
+                log::error!(
+                    "Evaluation of {} is refused: {}",
+                    phases.members().describe(*agent),
+                    reason
+                );
+            }
+        })?;
 
-        let mut y: G1 = Default::default(); // zero point
+    Ok(phases
+        .run(Phase::Interpolate, async {
+            Ok(verified.interpolate(topology))
+        })
+        .await?)
+}
 
-        // Compute blinded version of [r * k] P
-        for (i, e_i) in responses {
-            let lambda_i = self.topology.compute_coefficient(i, &indices);
+/// Blinds the hash of the `unblinded` value, evaluates it and unblinds the result into the fingerprint
+pub(crate) async fn blind_and_evaluate<E, Fut>(
+    phases: &Phases,
+    entropy: &dyn EntropySource,
+    unblinded: Fr,
+    evaluate: E,
+) -> Result<Fr, Error>
+where
+    E: FnOnce(G1) -> Fut,
+    Fut: ::std::future::Future<Output = Result<G1, Error>>,
+{
+    log::debug!("Processing unblinded value: {}", unblinded.compact());
 
-            y += e_i * lambda_i;
-        }
+    let blinded = phases
+        .run(Phase::Blind, async { Blinded::new(entropy, unblinded) })
+        .await?;
 
-        // Unblind
-        let unblinding_factor = blinding_factor.invert().unwrap();
-        let hash_with_secret = y * unblinding_factor; // This is [k] P
+    let y = evaluate(blinded.point()).await?;
 
-        let fingerprint = hash_with_secret.squeeze();
+    let fingerprint = phases
+        .run(Phase::Unblind, async { blinded.unblind(y) })
+        .await;
 
-        if log::log_enabled!(log::Level::Debug) {
-            match &fingerprint {
-                Ok(ref fp) => {
-                    log::debug!("Computed fingerprint: {}", fp.compact());
-                }
-                Err(ref e) => {
-                    log::error!("Error while computing fingerprint: {}", e);
-                }
+    if log::log_enabled!(log::Level::Debug) {
+        match &fingerprint {
+            Ok(ref fp) => {
+                log::debug!("Computed fingerprint: {}", fp.compact());
+            }
+            Err(ref e) => {
+                log::error!("Error while computing fingerprint: {}", e);
             }
         }
-
-        fingerprint
     }
+
+    Ok(fingerprint?)
 }
@@ -0,0 +1,207 @@
+use anyhow::{anyhow, Error};
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::entropy::{self, EntropySource};
+use crate::protocols::collaborative_protocol::{blind_and_evaluate, interpolate};
+use crate::protocols::phases::{EvaluationVerifier, Phase, PhaseMetrics, Phases};
+use crate::protocols::{AgentsTopology, CollaborativeProtocol, FingerprintProtocol};
+
+///
+/// Two-level protocol of the regional agent groups.
+///
+/// The secret is shared between the regions, the share of every region is shared again between the agents of
+/// the region. The agents of the coordinator region evaluate the regional share with the threshold within
+/// the region, the other regions are asked for their aggregate evaluations, which are combined with the threshold
+/// across the regions. So the round-trips to the single agents never leave the region.
+pub struct HierarchicalProtocol<L: AgentsTopology<Fr, G1>, R: AgentsTopology<Fr, G1>> {
+    region: usize,
+    regional: Arc<CollaborativeProtocol<Fr, G1, L>>,
+    regions: R,
+    entropy: Arc<dyn EntropySource>,
+    phases: Phases,
+}
+
+impl<L: AgentsTopology<Fr, G1>, R: AgentsTopology<Fr, G1>> HierarchicalProtocol<L, R> {
+    /// Protocol of the coordinator in the `region`, `regional` evaluates the share of the region and
+    /// `regions` is the topology of the regions, each of them reached via its regional coordinator
+    pub fn new(region: usize, regional: Arc<CollaborativeProtocol<Fr, G1, L>>, regions: R) -> Self {
+        Self {
+            region,
+            regional,
+            regions,
+            entropy: entropy::default_source(),
+            phases: Phases::default(),
+        }
+    }
+
+    /// Source of the blinding factors, the health tested OS RNG by default
+    pub fn with_entropy(mut self, entropy: Arc<dyn EntropySource>) -> Self {
+        self.entropy = entropy;
+        self
+    }
+
+    /// Fails the `phase` of the combination across the regions not completed within the `timeout`
+    pub fn with_phase_timeout(mut self, phase: Phase, timeout: Duration) -> Self {
+        self.phases.set_timeout(phase, timeout);
+        self
+    }
+
+    /// Verifier of the regional evaluations, `NonIdentity` by default
+    pub fn with_verifier(mut self, verifier: Arc<dyn EvaluationVerifier>) -> Self {
+        self.phases.set_verifier(verifier);
+        self
+    }
+
+    /// Counters and timings of the phases across the regions, the regional ones are counted by the regional protocol
+    pub fn phase_metrics(&self) -> Arc<PhaseMetrics> {
+        self.phases.metrics()
+    }
+}
+
+impl<L: AgentsTopology<Fr, G1> + Sync + Send, R: AgentsTopology<Fr, G1> + Sync>
+    FingerprintProtocol<Fr> for HierarchicalProtocol<L, R>
+{
+    async fn process(&self, unblinded: Fr) -> Result<Fr, Error> {
+        blind_and_evaluate(
+            &self.phases,
+            self.entropy.as_ref(),
+            unblinded,
+            |blinded_hash| self.evaluate(blinded_hash, None),
+        )
+        .await
+    }
+
+    /// Computes via the `regions` only, the agents within the regions are not restricted
+    async fn process_via(&self, unblinded: Fr, regions: &[usize]) -> Result<Fr, Error> {
+        if !regions.contains(&self.region) {
+            return Err(anyhow!(
+                "Region {} is not one of the regions {:?} allowed",
+                self.region,
+                regions
+            ));
+        }
+
+        blind_and_evaluate(
+            &self.phases,
+            self.entropy.as_ref(),
+            unblinded,
+            |blinded_hash| self.evaluate(blinded_hash, Some(regions)),
+        )
+        .await
+    }
+}
+
+impl<L: AgentsTopology<Fr, G1> + Sync + Send, R: AgentsTopology<Fr, G1> + Sync>
+    HierarchicalProtocol<L, R>
+{
+    async fn evaluate(&self, blinded_value: G1, regions: Option<&[usize]>) -> Result<G1, Error> {
+        let own = self.regional.evaluate(blinded_value).await?;
+        interpolate(
+            &self.phases,
+            &self.regions,
+            (self.region, own),
+            blinded_value,
+            regions,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::NaiveProtocol;
+    use crate::secret_sharing::SecretSharing;
+    use halo2_axiom::halo2curves::ff::Field;
+    use rand_core::OsRng;
+
+    /// Agents of the region, sharing the share of the region
+    struct RegionTopology {
+        sss: SecretSharing<Fr>,
+        agents: usize,
+    }
+
+    impl AgentsTopology<Fr, G1> for RegionTopology {
+        fn count(&self) -> usize {
+            self.agents
+        }
+
+        fn threshold(&self) -> usize {
+            self.sss.threshold
+        }
+
+        async fn obtain_shard(
+            &self,
+            agent: usize,
+            _: u64,
+            blinded_value: G1,
+        ) -> Result<(usize, G1), Error> {
+            Ok(self.sss.compute_exponent(agent, blinded_value))
+        }
+    }
+
+    /// Regional coordinators, each evaluating the share of its region with its agents
+    struct RegionsTopology {
+        threshold: usize,
+        regions: Vec<Arc<CollaborativeProtocol<Fr, G1, RegionTopology>>>,
+    }
+
+    impl AgentsTopology<Fr, G1> for RegionsTopology {
+        fn count(&self) -> usize {
+            self.regions.len()
+        }
+
+        fn threshold(&self) -> usize {
+            self.threshold
+        }
+
+        async fn obtain_shard(
+            &self,
+            region: usize,
+            _: u64,
+            blinded_value: G1,
+        ) -> Result<(usize, G1), Error> {
+            Ok((
+                region,
+                self.regions[region - 1].evaluate(blinded_value).await?,
+            ))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_hierarchical_protocol() -> Result<(), Error> {
+        let secret = Fr::random(OsRng);
+        let regions = SecretSharing::generate(secret, 2, 3);
+
+        // 2 of 3 regions, 2 of 3 agents in every region, the coordinator is the 1st agent of the region
+        let regional = (1..=3)
+            .map(|region| {
+                let sss = regions.reshare(region, 2, 3).unwrap();
+                let share = sss.get_share(1).unwrap();
+                Arc::new(CollaborativeProtocol::new(
+                    (1, share),
+                    RegionTopology { sss, agents: 3 },
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let protocol = HierarchicalProtocol::new(
+            1,
+            regional[0].clone(),
+            RegionsTopology {
+                threshold: 2,
+                regions: regional,
+            },
+        );
+
+        let origin = Fr::from(42u64);
+        assert_eq!(
+            protocol.process(origin).await?,
+            NaiveProtocol::new(secret).process(origin).await?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+//! Agents evaluating within the coordinator process, for the single node deployments running the threshold
+//! protocol without the agent fleet. The shares are held by the same process, so it protects the secret no
+//! better than the naive protocol, but computes the fingerprints the way the fleet does
+
+use anyhow::{anyhow, Error};
+use futures::future::{ready, Future};
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use std::collections::HashMap;
+
+use crate::protocols::AgentsTopology;
+use crate::secret_sharing::SecretSharing;
+
+/// Agents holding the shares of the single secret in memory
+pub struct InProcessAgentsTopology {
+    agents: usize,
+    threshold: usize,
+    shares: HashMap<usize, Fr>,
+}
+
+impl InProcessAgentsTopology {
+    /// Shares the `secret` between the `agents` with the `threshold`, returns the share of agent 1 coordinating
+    /// the evaluations together with the topology of the other agents
+    pub fn share(secret: Fr, threshold: usize, agents: usize) -> ((usize, Fr), Self) {
+        let mut shares = SecretSharing::generate(secret, threshold, agents)
+            .get_shares()
+            .clone();
+        let coordinator = shares.remove(&1).expect("Agent 1 is always shared");
+
+        (
+            (1, coordinator),
+            Self {
+                agents,
+                threshold,
+                shares,
+            },
+        )
+    }
+
+    /// Drops the share of the `agent`, its evaluations fail like the ones of the unreachable agent, to drill the
+    /// protocol tolerating the failed agents
+    pub fn with_unavailable_agent(mut self, agent: usize) -> Self {
+        self.shares.remove(&agent);
+        self
+    }
+}
+
+impl AgentsTopology<Fr, G1> for InProcessAgentsTopology {
+    fn count(&self) -> usize {
+        self.agents
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn obtain_shard(
+        &self,
+        agent: usize,
+        _: u64,
+        blinded_value: G1,
+    ) -> impl Future<Output = Result<(usize, G1), Error>> + Send {
+        ready(match self.shares.get(&agent) {
+            Some(share) => Ok((agent, blinded_value * share)),
+            None => Err(anyhow!("Agent {} is not in the topology", agent)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{CollaborativeProtocol, FingerprintProtocol, NaiveProtocol};
+    use halo2_axiom::halo2curves::ff::Field;
+    use rand_core::OsRng;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_in_process_protocol() -> Result<(), Error> {
+        let secret = Fr::random(OsRng);
+        let (agent_info, topology) = InProcessAgentsTopology::share(secret, 3, 5);
+        let protocol = CollaborativeProtocol::new(agent_info, topology);
+
+        let origin = Fr::from(42u64);
+        assert_eq!(
+            protocol.process(origin).await?,
+            NaiveProtocol::new(secret).process(origin).await?
+        );
+
+        // Threshold is reached without the failed agent, not without the three of them
+        let (agent_info, topology) = InProcessAgentsTopology::share(secret, 3, 5);
+        let degraded = CollaborativeProtocol::new(agent_info, topology.with_unavailable_agent(2));
+        assert_eq!(
+            degraded.process(origin).await?,
+            NaiveProtocol::new(secret).process(origin).await?
+        );
+        let (agent_info, topology) = InProcessAgentsTopology::share(secret, 3, 5);
+        let topology = topology
+            .with_unavailable_agent(2)
+            .with_unavailable_agent(3)
+            .with_unavailable_agent(4);
+        assert!(CollaborativeProtocol::new(agent_info, topology)
+            .process(origin)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Who runs the agents of the topology, for the diversity policies, the per-institution metrics and the messages
+//! naming the agents the way the operators know them
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::protocols::phases::DiversityPolicy;
+
+/// Metadata of the agent, all of it optional
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Member {
+    /// Name of the operator running the agent, e.g. `BankCorp`
+    pub operator: Option<String>,
+    /// Id of the institution the agent belongs to
+    pub institution: Option<String>,
+    /// Region the agent is deployed in, e.g. `eu-west`
+    pub region: Option<String>,
+    /// Compacted Ed25519 key the partial evaluations of the agent are signed with
+    pub verifying_key: Option<String>,
+}
+
+/// Metadata of the agents by their numbers
+#[derive(Debug, Clone, Default)]
+pub struct Members(BTreeMap<usize, Member>);
+
+impl Members {
+    pub fn with_member(mut self, agent: usize, member: Member) -> Self {
+        self.0.insert(agent, member);
+        self
+    }
+
+    pub fn get(&self, agent: usize) -> Option<&Member> {
+        self.0.get(&agent)
+    }
+
+    /// Members in the order of the agent numbers
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Member)> {
+        self.0.iter().map(|(agent, member)| (*agent, member))
+    }
+
+    pub fn institution(&self, agent: usize) -> Option<&str> {
+        self.get(agent)?.institution.as_deref()
+    }
+
+    /// Agent as the operators know it, e.g. `agent 3 (BankCorp, eu-west)`
+    pub fn describe(&self, agent: usize) -> String {
+        let mut described = format!("agent {}", agent);
+        let Some(member) = self.get(agent) else {
+            return described;
+        };
+
+        let known = [&member.operator, &member.region]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !known.is_empty() {
+            let _ = write!(described, " ({})", known.join(", "));
+        }
+
+        described
+    }
+
+    /// Policy requiring the agents of every evaluation to span `min_institutions` institutions of these members
+    pub fn diversity(&self, min_institutions: usize) -> DiversityPolicy {
+        self.iter().fold(
+            DiversityPolicy::new(min_institutions),
+            |policy, (agent, member)| match &member.institution {
+                Some(institution) => policy.with_institution(agent, institution),
+                None => policy,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_members() {
+        let members = Members::default()
+            .with_member(
+                3,
+                Member {
+                    operator: Some("BankCorp".into()),
+                    institution: Some("bank-corp".into()),
+                    region: Some("eu-west".into()),
+                    verifying_key: None,
+                },
+            )
+            .with_member(
+                2,
+                Member {
+                    institution: Some("bank-corp".into()),
+                    ..Default::default()
+                },
+            );
+
+        assert_eq!(members.describe(3), "agent 3 (BankCorp, eu-west)");
+        assert_eq!(members.describe(2), "agent 2");
+        assert_eq!(members.describe(1), "agent 1");
+        assert_eq!(members.institution(3), Some("bank-corp"));
+
+        // Agents 2 and 3 are the same institution, agent 1 counts as its own
+        let diversity = members.diversity(2);
+        assert_eq!(diversity.institutions(&[2, 3]), 1);
+        assert_eq!(diversity.institutions(&[1, 3]), 2);
+    }
+}
@@ -1,15 +1,74 @@
 mod collaborative_protocol;
+mod hierarchical_protocol;
+mod in_process;
+pub mod members;
 mod naive_protocol;
+pub mod phases;
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
+use futures::future::ready;
+use futures::stream::{self, StreamExt};
 use halo2_axiom::halo2curves::ff::PrimeField as PF;
 
 pub use collaborative_protocol::AgentsTopology;
 pub use collaborative_protocol::CollaborativeProtocol;
+pub use hierarchical_protocol::HierarchicalProtocol;
+pub use in_process::InProcessAgentsTopology;
 pub use naive_protocol::NaiveProtocol;
 
+/// Rounds of the batch the default `FingerprintProtocol::process_batch` keeps in flight
+pub const BATCH_ROUNDS_IN_FLIGHT: usize = 16;
+
 pub trait FingerprintProtocol<F: PF> {
-    fn process(&self, unblinded: F) -> impl ::std::future::Future<Output = Result<F, Error>> + Send;
+    fn process(&self, unblinded: F)
+        -> impl ::std::future::Future<Output = Result<F, Error>> + Send;
+
+    /// Computes via the `agents` only, e.g. the agents allowed to process the data residency of the transaction.
+    /// Protocols unable to restrict the agents refuse the computation
+    fn process_via(
+        &self,
+        unblinded: F,
+        agents: &[usize],
+    ) -> impl ::std::future::Future<Output = Result<F, Error>> + Send {
+        let _ = unblinded;
+        ready(Err(anyhow!(
+            "Protocol is not able to compute via the agents {:?} only",
+            agents
+        )))
+    }
+
+    /// Evaluates all the `unblinded` values, in their order. By default the values are evaluated by their own rounds,
+    /// at most `BATCH_ROUNDS_IN_FLIGHT` at once, protocols able to evaluate them in a single round trip override it
+    fn process_batch(
+        &self,
+        unblinded: Vec<F>,
+    ) -> impl ::std::future::Future<Output = Vec<Result<F, Error>>> + Send
+    where
+        Self: Sync,
+    {
+        stream::iter(unblinded)
+            .map(|unblinded| self.process(unblinded))
+            .buffered(BATCH_ROUNDS_IN_FLIGHT)
+            .collect()
+    }
+}
+
+/// Protocol computing via the given agents only, see `FingerprintProtocol::process_via`
+pub struct ViaAgents<'a, P> {
+    protocol: &'a P,
+    agents: &'a [usize],
+}
+
+impl<'a, P> ViaAgents<'a, P> {
+    pub fn new(protocol: &'a P, agents: &'a [usize]) -> Self {
+        Self { protocol, agents }
+    }
+}
+
+impl<F: PF, P: FingerprintProtocol<F> + Sync> FingerprintProtocol<F> for ViaAgents<'_, P> {
+    async fn process(&self, unblinded: F) -> Result<F, Error> {
+        self.protocol.process_via(unblinded, self.agents).await
+    }
 }
 
 #[cfg(test)]
@@ -19,6 +78,7 @@ mod tests {
     use halo2_axiom::halo2curves::bn256::{Fr, G1};
     use halo2_axiom::halo2curves::ff::Field;
     use rand_core::OsRng;
+    use std::sync::{Arc, Mutex};
 
     use crate::secret_sharing::SecretSharing;
 
@@ -28,6 +88,16 @@ mod tests {
 
     struct LocalAgentsTopology {
         sss: SecretSharing<Fr>,
+        contacted: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl LocalAgentsTopology {
+        fn new(sss: SecretSharing<Fr>) -> Self {
+            Self {
+                sss,
+                contacted: Default::default(),
+            }
+        }
     }
 
     impl AgentsTopology<Fr, G1> for LocalAgentsTopology {
@@ -49,6 +119,7 @@ mod tests {
             _: u64,
             blinded_value: G1,
         ) -> Result<(usize, G1), Error> {
+            self.contacted.lock().unwrap().push(agent);
             Ok(self.sss.compute_exponent(agent, blinded_value))
         }
     }
@@ -64,7 +135,7 @@ mod tests {
         // We are the 1st agent
         let current_share = sss.get_share(1).unwrap();
 
-        let topology = LocalAgentsTopology { sss };
+        let topology = LocalAgentsTopology::new(sss);
 
         let coop_protocol = CollaborativeProtocol::new((1, current_share), topology);
         let naive_protocol = NaiveProtocol::new(secret);
@@ -80,6 +151,30 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fingerprint_via_agents() -> Result<(), Error> {
+        let secret = Fr::random(OsRng);
+        let sss = SecretSharing::generate(secret, 3, 10);
+        let current_share = sss.get_share(1).unwrap();
+        let topology = LocalAgentsTopology::new(sss);
+        let contacted = topology.contacted.clone();
+        let protocol = CollaborativeProtocol::new((1, current_share), topology);
+
+        let origin = Fr::from(42u64);
+        let processed = ViaAgents::new(&protocol, &[1, 4, 7])
+            .process(origin)
+            .await?;
+        assert_eq!(processed, NaiveProtocol::new(secret).process(origin).await?);
+        // Agents outside of the allowed ones are never reached
+        assert_eq!(*contacted.lock().unwrap(), vec![4, 7]);
+
+        // Agent itself is not allowed, or the allowed agents do not reach the threshold
+        assert!(protocol.process_via(origin, &[2, 4, 7]).await.is_err());
+        assert!(protocol.process_via(origin, &[1, 4]).await.is_err());
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_fingerprint_protocol() -> Result<(), Error> {
         let mut rng = OsRng;
@@ -95,4 +190,50 @@ mod tests {
 
         Ok(())
     }
+
+    /// Protocol counting the rounds in flight
+    struct InFlight {
+        current: Mutex<usize>,
+        max: Mutex<usize>,
+        protocol: NaiveProtocol,
+    }
+
+    impl FingerprintProtocol<Fr> for InFlight {
+        async fn process(&self, unblinded: Fr) -> Result<Fr, Error> {
+            {
+                let mut current = self.current.lock().unwrap();
+                *current += 1;
+                let mut max = self.max.lock().unwrap();
+                *max = (*max).max(*current);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            *self.current.lock().unwrap() -= 1;
+
+            self.protocol.process(unblinded).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_batch_in_flight() -> Result<(), Error> {
+        let protocol = InFlight {
+            current: Default::default(),
+            max: Default::default(),
+            protocol: NaiveProtocol::new(Fr::from(42)),
+        };
+
+        let batch = (0..100u64).map(Fr::from).collect::<Vec<_>>();
+        let evaluated = protocol
+            .process_batch(batch.clone())
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(*protocol.max.lock().unwrap(), BATCH_ROUNDS_IN_FLIGHT);
+
+        // Values keep their order
+        for (unblinded, evaluated) in batch.into_iter().zip(evaluated) {
+            assert_eq!(protocol.protocol.process(unblinded).await?, evaluated);
+        }
+
+        Ok(())
+    }
 }
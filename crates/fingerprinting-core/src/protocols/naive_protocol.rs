@@ -1,9 +1,8 @@
 use anyhow::Error;
-use halo2_axiom::halo2curves::bn256::{Fr, G1};
-use halo2_axiom::halo2curves::CurveExt;
+use halo2_axiom::halo2curves::bn256::Fr;
 
 use crate::protocols::FingerprintProtocol;
-use crate::{HashSqueeze, HASH_TO_CURVE_PREFIX};
+use crate::{hash_to_curve, HashSqueeze};
 
 // Computes the [k] P without split and reconstruct from by cooperating with other agents
 pub struct NaiveProtocol {
@@ -18,11 +17,15 @@ impl NaiveProtocol {
 
 impl FingerprintProtocol<Fr> for NaiveProtocol {
     async fn process(&self, unblinded: Fr) -> Result<Fr, Error> {
-        let hasher = G1::hash_to_curve(HASH_TO_CURVE_PREFIX);
-        let curve_point = hasher(&unblinded.to_bytes());
+        let curve_point = hash_to_curve(&unblinded);
 
         let hash_with_secret = curve_point * self.secret;
 
         hash_with_secret.squeeze() // Use default compress for G1
     }
+
+    /// The whole secret is local, no agent is reached whatever the `agents` are
+    async fn process_via(&self, unblinded: Fr, _agents: &[usize]) -> Result<Fr, Error> {
+        self.process(unblinded).await
+    }
 }
@@ -0,0 +1,794 @@
+//! Typed phases of the collaborative computation
+//!
+//! Blind → Collect → Verify → Interpolate → Unblind, every phase consumes the state produced by the previous one, so
+//! the phases are tested one by one and the verification steps are inserted without touching the others. Each phase
+//! runs within its optional timeout, is counted and timed in the `PhaseMetrics` and fails with the `PhaseError`
+//! naming it.
+
+use anyhow::Error;
+use futures::future::ready;
+use futures::{FutureExt, StreamExt};
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use halo2_axiom::halo2curves::group::Group;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::breaker::AgentQuarantined;
+use crate::entropy::{self, EntropySource};
+use crate::protocols::members::Members;
+use crate::protocols::AgentsTopology;
+use crate::{hash_to_curve, HashSqueeze};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Hashing to the curve and multiplying by the random blinding factor
+    Blind,
+    /// Partial evaluations of the blinded point by the threshold agents
+    Collect,
+    /// Checks of the collected evaluations
+    Verify,
+    /// Lagrange interpolation of the evaluations in the exponent
+    Interpolate,
+    /// Removing the blinding factor and squeezing the point into the fingerprint
+    Unblind,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Blind,
+        Phase::Collect,
+        Phase::Verify,
+        Phase::Interpolate,
+        Phase::Unblind,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Failure of the phase, the `anyhow::Error` of the protocols could be downcast to it
+#[derive(Debug)]
+pub enum PhaseError {
+    /// Phase did not complete within its timeout
+    Timeout(Phase, Duration),
+    /// Fewer evaluations than the threshold were collected
+    NotEnoughEvaluations {
+        collected: usize,
+        threshold: usize,
+        /// Agents failed to evaluate, described by their `Members` metadata, with their errors
+        failures: Vec<String>,
+    },
+    /// Evaluation of the agent was refused by the verifier
+    Refused {
+        agent: usize,
+        reason: Error,
+    },
+    /// Evaluations collected span fewer institutions than the `DiversityPolicy` requires
+    NotDiverse {
+        institutions: usize,
+        required: usize,
+    },
+    Failed(Phase, Error),
+}
+
+impl PhaseError {
+    pub fn phase(&self) -> Phase {
+        match self {
+            PhaseError::Timeout(phase, _) | PhaseError::Failed(phase, _) => *phase,
+            PhaseError::NotEnoughEvaluations { .. } | PhaseError::NotDiverse { .. } => {
+                Phase::Collect
+            }
+            PhaseError::Refused { .. } => Phase::Verify,
+        }
+    }
+}
+
+impl Display for PhaseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhaseError::Timeout(phase, timeout) => {
+                write!(f, "{} phase timed out after {:?}", phase, timeout)
+            }
+            PhaseError::NotEnoughEvaluations {
+                collected,
+                threshold,
+                failures,
+            } => {
+                write!(
+                    f,
+                    "Not enough responses from other agents, {} of the threshold {}",
+                    collected, threshold
+                )?;
+                if !failures.is_empty() {
+                    write!(f, "; {}", failures.join("; "))?;
+                }
+                Ok(())
+            }
+            PhaseError::Refused { agent, reason } => {
+                write!(f, "Evaluation of agent {} is refused: {}", agent, reason)
+            }
+            PhaseError::NotDiverse {
+                institutions,
+                required,
+            } => write!(
+                f,
+                "Evaluations span {} distinct institutions, at least {} are required",
+                institutions, required
+            ),
+            PhaseError::Failed(phase, e) => write!(f, "{} phase failed: {}", phase, e),
+        }
+    }
+}
+
+impl std::error::Error for PhaseError {}
+
+/// Evaluation refused by the agent draining for the restart, the topology could fail `obtain_shard` with it.
+/// The agent is left out of the round without being counted as failed, see `collect`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgentInMaintenance {
+    pub agent: usize,
+}
+
+impl Display for AgentInMaintenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Agent {} is in maintenance", self.agent)
+    }
+}
+
+impl std::error::Error for AgentInMaintenance {}
+
+/// Check of the partial evaluation of the agent, inserted between the collection and the interpolation
+pub trait EvaluationVerifier: Send + Sync {
+    fn verify(&self, agent: usize, blinded_value: G1, evaluation: G1) -> Result<(), Error>;
+}
+
+/// Refuses the identity evaluations, they carry nothing of the share and cancel the fingerprint out
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NonIdentity;
+
+impl EvaluationVerifier for NonIdentity {
+    fn verify(&self, _: usize, _: G1, evaluation: G1) -> Result<(), Error> {
+        if bool::from(evaluation.is_identity()) {
+            return Err(anyhow::anyhow!("Evaluation is the identity point"));
+        }
+        Ok(())
+    }
+}
+
+/// Minimum number of the distinct institutions among the agents of every evaluation, so the threshold is not
+/// reached by the agents of the same operator
+#[derive(Debug, Clone, Default)]
+pub struct DiversityPolicy {
+    min_institutions: usize,
+    institutions: HashMap<usize, String>,
+}
+
+impl DiversityPolicy {
+    /// Agents without the known institution count as the institutions of their own
+    pub fn new(min_institutions: usize) -> Self {
+        Self {
+            min_institutions,
+            institutions: HashMap::new(),
+        }
+    }
+
+    pub fn with_institution(mut self, agent: usize, institution: impl Into<String>) -> Self {
+        self.institutions.insert(agent, institution.into());
+        self
+    }
+
+    pub fn min_institutions(&self) -> usize {
+        self.min_institutions
+    }
+
+    /// Number of the distinct institutions of the `agents`
+    pub fn institutions(&self, agents: &[usize]) -> usize {
+        agents
+            .iter()
+            .map(|agent| self.institutions.get(agent).ok_or(*agent))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    pub fn check(&self, agents: &[usize]) -> Result<(), PhaseError> {
+        let institutions = self.institutions(agents);
+        if institutions < self.min_institutions {
+            return Err(PhaseError::NotDiverse {
+                institutions,
+                required: self.min_institutions,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether the `candidate` joining the `selected` agents still leaves the policy satisfiable by
+    /// the rest of the `threshold`
+    fn admits(&self, selected: &[usize], candidate: usize, threshold: usize) -> bool {
+        let mut agents = selected.to_vec();
+        agents.push(candidate);
+
+        self.institutions(&agents) + threshold.saturating_sub(agents.len()) >= self.min_institutions
+    }
+}
+
+/// Hash of the unblinded value multiplied by the blinding factor, the factor never leaves the coordinator
+pub struct Blinded {
+    blinding_factor: Fr,
+    point: G1,
+}
+
+impl Blinded {
+    pub fn new(entropy: &dyn EntropySource, unblinded: Fr) -> Result<Self, PhaseError> {
+        let blinding_factor =
+            entropy::random_scalar(entropy).map_err(|e| PhaseError::Failed(Phase::Blind, e))?;
+
+        Ok(Self {
+            blinding_factor,
+            point: hash_to_curve(&unblinded) * blinding_factor,
+        })
+    }
+
+    /// Blinded point sent to the agents
+    pub fn point(&self) -> G1 {
+        self.point
+    }
+
+    /// Fingerprint of the `evaluation` of the blinded point
+    pub fn unblind(self, evaluation: G1) -> Result<Fr, PhaseError> {
+        // The blinding factor is never zero
+        let unblinding_factor = self.blinding_factor.invert().unwrap();
+
+        // This is [k] P
+        (evaluation * unblinding_factor)
+            .squeeze()
+            .map_err(|e| PhaseError::Failed(Phase::Unblind, e))
+    }
+}
+
+/// Partial evaluations of the blinded point, the own one included
+#[derive(Debug)]
+pub struct Collected {
+    blinded_value: G1,
+    evaluations: Vec<(usize, G1)>,
+}
+
+impl Collected {
+    pub fn new(blinded_value: G1, evaluations: Vec<(usize, G1)>) -> Self {
+        Self {
+            blinded_value,
+            evaluations,
+        }
+    }
+
+    /// Agents of the evaluations
+    pub fn agents(&self) -> Vec<usize> {
+        self.evaluations.iter().map(|(agent, _)| *agent).collect()
+    }
+
+    pub fn verify(self, verifier: &dyn EvaluationVerifier) -> Result<Verified, PhaseError> {
+        for (agent, evaluation) in &self.evaluations {
+            verifier
+                .verify(*agent, self.blinded_value, *evaluation)
+                .map_err(|reason| PhaseError::Refused {
+                    agent: *agent,
+                    reason,
+                })?;
+        }
+
+        Ok(Verified(self.evaluations))
+    }
+}
+
+/// Evaluations accepted by the verifier
+#[derive(Debug)]
+pub struct Verified(Vec<(usize, G1)>);
+
+impl Verified {
+    /// Combines the evaluations into `[k] B` by the Lagrange interpolation in the exponent
+    pub fn interpolate<T: AgentsTopology<Fr, G1>>(self, topology: &T) -> G1 {
+        let indices = self.0.iter().map(|(agent, _)| *agent).collect::<Vec<_>>();
+
+        self.0
+            .into_iter()
+            .fold(G1::identity(), |y, (agent, evaluation)| {
+                y + evaluation * topology.compute_coefficient(agent, &indices)
+            })
+    }
+}
+
+/// Collects the `own` evaluation and the threshold - 1 evaluations of the other members of the `topology`,
+/// the members are only the `allowed` ones when given. Evaluations the `diversity` could not be satisfied with
+/// are skipped, the failed agents are named by their `members` metadata and counted in the `metrics`
+pub async fn collect<T: AgentsTopology<Fr, G1> + Sync>(
+    topology: &T,
+    own: (usize, G1),
+    blinded_value: G1,
+    allowed: Option<&[usize]>,
+    diversity: &DiversityPolicy,
+    members: &Members,
+    metrics: Option<&PhaseMetrics>,
+) -> Result<Collected, PhaseError> {
+    let responses = futures::stream::iter(1..=topology.count())
+        .filter(|agent| {
+            ready(*agent != own.0 && allowed.is_none_or(|allowed| allowed.contains(agent)))
+        })
+        .map(|agent| {
+            let started = Instant::now();
+            topology
+                .obtain_shard(agent, 0, blinded_value)
+                .map(move |evaluation| (agent, evaluation, started.elapsed()))
+        })
+        .buffer_unordered(1024); // TODO parametrize concurrency
+    let mut responses = pin!(responses);
+
+    // Since we already have one response from the agent itself
+    let mut selected = vec![own.0];
+    let mut evaluations = Vec::with_capacity(topology.threshold());
+    let mut failures = vec![];
+    let mut skipped = false;
+    while selected.len() < topology.threshold() {
+        let Some((agent, evaluation, elapsed)) = responses.next().await else {
+            break;
+        };
+        let (agent, evaluation) = match evaluation {
+            Ok(evaluation) => {
+                if let Some(metrics) = metrics {
+                    metrics.record_evaluation(agent, true, elapsed);
+                }
+                evaluation
+            }
+            // Agent being restarted is excluded without the penalty of the failed one
+            Err(e) if e.downcast_ref::<AgentInMaintenance>().is_some() => {
+                log::info!(
+                    "{} is in maintenance, excluded from the round",
+                    members.describe(agent)
+                );
+                failures.push(format!("{}: {}", members.describe(agent), e));
+                continue;
+            }
+            // Quarantined agent is not asked, its failures are already counted
+            Err(e) if e.downcast_ref::<AgentQuarantined>().is_some() => {
+                log::debug!("{}, excluded from the round", e);
+                failures.push(format!("{}: {}", members.describe(agent), e));
+                continue;
+            }
+            Err(e) => {
+                if let Some(metrics) = metrics {
+                    metrics.record_evaluation(agent, false, elapsed);
+                }
+                log::error!(
+                    "Error while getting shard from {}: {}",
+                    members.describe(agent),
+                    e
+                );
+                failures.push(format!("{}: {}", members.describe(agent), e));
+                continue;
+            }
+        };
+        if diversity.admits(&selected, agent, topology.threshold()) {
+            selected.push(agent);
+            evaluations.push((agent, evaluation));
+        } else {
+            log::debug!(
+                "Evaluation of agent {} is skipped, its institution is already selected",
+                agent
+            );
+            skipped = true;
+        }
+    }
+
+    evaluations.push(own);
+
+    if evaluations.len() < topology.threshold() {
+        if skipped {
+            diversity.check(&selected)?;
+        }
+        return Err(PhaseError::NotEnoughEvaluations {
+            collected: evaluations.len(),
+            threshold: topology.threshold(),
+            failures,
+        });
+    }
+    diversity.check(&selected)?;
+
+    let collected = Collected::new(blinded_value, evaluations);
+    log::debug!(
+        "Got {} results from other agents: {:?}",
+        collected.evaluations.len(),
+        collected.agents()
+    );
+
+    Ok(collected)
+}
+
+/// Counters of the phase
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseStats {
+    pub completed: u64,
+    pub failed: u64,
+    /// Failures due to the timeout, included in `failed`
+    pub timed_out: u64,
+    /// Time spent in the phase, the failed runs included
+    pub total_time: Duration,
+}
+
+#[derive(Default)]
+struct PhaseCounters {
+    completed: AtomicU64,
+    failed: AtomicU64,
+    timed_out: AtomicU64,
+    total_micros: AtomicU64,
+}
+
+/// Partial evaluations of the agent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvaluationStats {
+    pub evaluated: u64,
+    pub failed: u64,
+    /// Time the agent took to respond, the failed evaluations included
+    pub total_time: Duration,
+}
+
+/// Counters of all the phases and of the evaluations by the agents
+#[derive(Default)]
+pub struct PhaseMetrics {
+    phases: [PhaseCounters; 5],
+    agents: Mutex<BTreeMap<usize, EvaluationStats>>,
+}
+
+impl PhaseMetrics {
+    pub fn stats(&self, phase: Phase) -> PhaseStats {
+        let counters = &self.phases[phase.index()];
+
+        PhaseStats {
+            completed: counters.completed.load(Ordering::Relaxed),
+            failed: counters.failed.load(Ordering::Relaxed),
+            timed_out: counters.timed_out.load(Ordering::Relaxed),
+            total_time: Duration::from_micros(counters.total_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Evaluations of the agents in the order of their numbers, the agents not asked yet are absent
+    pub fn evaluation_stats(&self) -> Vec<(usize, EvaluationStats)> {
+        let agents = self.agents.lock().unwrap_or_else(PoisonError::into_inner);
+
+        agents
+            .iter()
+            .map(|(agent, stats)| (*agent, *stats))
+            .collect()
+    }
+
+    fn record_evaluation(&self, agent: usize, evaluated: bool, elapsed: Duration) {
+        let mut agents = self.agents.lock().unwrap_or_else(PoisonError::into_inner);
+        let stats = agents.entry(agent).or_default();
+        stats.total_time += elapsed;
+        if evaluated {
+            stats.evaluated += 1;
+        } else {
+            stats.failed += 1;
+        }
+    }
+
+    fn record<T>(&self, phase: Phase, result: &Result<T, PhaseError>, elapsed: Duration) {
+        let counters = &self.phases[phase.index()];
+        counters
+            .total_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        match result {
+            Ok(_) => counters.completed.fetch_add(1, Ordering::Relaxed),
+            Err(e) => {
+                if let PhaseError::Timeout(..) = e {
+                    counters.timed_out.fetch_add(1, Ordering::Relaxed);
+                }
+                counters.failed.fetch_add(1, Ordering::Relaxed)
+            }
+        };
+    }
+}
+
+/// Runs the phases with their timeouts and verifier, recording them in the metrics
+#[derive(Clone)]
+pub(crate) struct Phases {
+    timeouts: [Option<Duration>; 5],
+    verifier: Arc<dyn EvaluationVerifier>,
+    diversity: Arc<DiversityPolicy>,
+    members: Arc<Members>,
+    metrics: Arc<PhaseMetrics>,
+}
+
+impl Default for Phases {
+    fn default() -> Self {
+        Self {
+            timeouts: [None; 5],
+            verifier: Arc::new(NonIdentity),
+            diversity: Arc::default(),
+            members: Arc::default(),
+            metrics: Arc::default(),
+        }
+    }
+}
+
+impl Phases {
+    pub(crate) fn set_timeout(&mut self, phase: Phase, timeout: Duration) {
+        self.timeouts[phase.index()] = Some(timeout);
+    }
+
+    pub(crate) fn set_verifier(&mut self, verifier: Arc<dyn EvaluationVerifier>) {
+        self.verifier = verifier;
+    }
+
+    pub(crate) fn verifier(&self) -> &dyn EvaluationVerifier {
+        self.verifier.as_ref()
+    }
+
+    pub(crate) fn set_diversity(&mut self, diversity: DiversityPolicy) {
+        self.diversity = Arc::new(diversity);
+    }
+
+    pub(crate) fn set_members(&mut self, members: Members) {
+        self.members = Arc::new(members);
+    }
+
+    pub(crate) fn members(&self) -> &Members {
+        self.members.as_ref()
+    }
+
+    /// Collects the evaluations with the diversity policy, members and metrics of these phases
+    pub(crate) async fn collect<T: AgentsTopology<Fr, G1> + Sync>(
+        &self,
+        topology: &T,
+        own: (usize, G1),
+        blinded_value: G1,
+        allowed: Option<&[usize]>,
+    ) -> Result<Collected, PhaseError> {
+        collect(
+            topology,
+            own,
+            blinded_value,
+            allowed,
+            &self.diversity,
+            &self.members,
+            Some(&self.metrics),
+        )
+        .await
+    }
+
+    pub(crate) fn metrics(&self) -> Arc<PhaseMetrics> {
+        self.metrics.clone()
+    }
+
+    pub(crate) async fn run<T, Fut>(&self, phase: Phase, future: Fut) -> Result<T, PhaseError>
+    where
+        Fut: Future<Output = Result<T, PhaseError>>,
+    {
+        let started = Instant::now();
+        let result = match self.timeouts[phase.index()] {
+            Some(timeout) => tokio::time::timeout(timeout, future)
+                .await
+                .unwrap_or(Err(PhaseError::Timeout(phase, timeout))),
+            None => future.await,
+        };
+        self.metrics.record(phase, &result, started.elapsed());
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entropy::OsEntropy;
+    use crate::protocols::members::Member;
+    use crate::protocols::{FingerprintProtocol, NaiveProtocol};
+    use crate::secret_sharing::SecretSharing;
+    use halo2_axiom::halo2curves::ff::Field;
+    use rand_core::OsRng;
+
+    struct SlowTopology {
+        sss: SecretSharing<Fr>,
+        slow: usize,
+        failing: usize,
+        maintenance: usize,
+    }
+
+    impl AgentsTopology<Fr, G1> for SlowTopology {
+        fn count(&self) -> usize {
+            3
+        }
+
+        fn threshold(&self) -> usize {
+            self.sss.threshold
+        }
+
+        async fn obtain_shard(
+            &self,
+            agent: usize,
+            _: u64,
+            blinded_value: G1,
+        ) -> Result<(usize, G1), Error> {
+            if agent == self.slow {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            if agent == self.failing {
+                return Err(anyhow::anyhow!("Connection refused"));
+            }
+            if agent == self.maintenance {
+                return Err(AgentInMaintenance { agent }.into());
+            }
+            Ok(self.sss.compute_exponent(agent, blinded_value))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phases() -> Result<(), Error> {
+        let secret = Fr::random(OsRng);
+        let sss = SecretSharing::generate(secret, 2, 3);
+        let own = sss.get_share(1).unwrap();
+
+        let blinded = Blinded::new(&OsEntropy, Fr::from(42u64))?;
+        let point = blinded.point();
+
+        // Agent 2 is too slow, the phase completes with agent 3
+        let phases = {
+            let mut phases = Phases::default();
+            phases.set_timeout(Phase::Collect, Duration::from_secs(1));
+            phases
+        };
+        let topology = SlowTopology {
+            sss,
+            slow: 2,
+            failing: 0,
+            maintenance: 0,
+        };
+        let collected = phases
+            .run(
+                Phase::Collect,
+                phases.collect(&topology, (1, point * own), point, None),
+            )
+            .await?;
+        assert_eq!(collected.agents(), vec![3, 1]);
+
+        let evaluation = collected.verify(&NonIdentity)?.interpolate(&topology);
+        assert_eq!(
+            blinded.unblind(evaluation)?,
+            NaiveProtocol::new(secret).process(Fr::from(42u64)).await?
+        );
+
+        // Only the slow agent is allowed
+        let timed_out = phases
+            .run(
+                Phase::Collect,
+                phases.collect(&topology, (1, point * own), point, Some(&[1, 2])),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(timed_out, PhaseError::Timeout(Phase::Collect, _)));
+
+        let stats = phases.metrics().stats(Phase::Collect);
+        assert_eq!((stats.completed, stats.failed, stats.timed_out), (1, 1, 1));
+
+        // Identity evaluation is refused
+        let refused = Collected::new(point, vec![(1, point * own), (3, G1::identity())])
+            .verify(&NonIdentity)
+            .unwrap_err();
+        assert!(matches!(refused, PhaseError::Refused { agent: 3, .. }));
+        assert_eq!(refused.phase(), Phase::Verify);
+
+        // Agents 1 and 3 are run by the same institution, the evaluation of agent 3 is skipped
+        let diversity = DiversityPolicy::new(2)
+            .with_institution(1, "bank-a")
+            .with_institution(2, "bank-b")
+            .with_institution(3, "bank-a");
+        let topology = SlowTopology {
+            sss: topology.sss,
+            slow: 0,
+            failing: 0,
+            maintenance: 0,
+        };
+        let no_members = Members::default();
+        let collected = collect(
+            &topology,
+            (1, point * own),
+            point,
+            None,
+            &diversity,
+            &no_members,
+            None,
+        )
+        .await?;
+        assert_eq!(collected.agents(), vec![2, 1]);
+        let not_diverse = collect(
+            &topology,
+            (1, point * own),
+            point,
+            Some(&[1, 3]),
+            &diversity,
+            &no_members,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            not_diverse,
+            PhaseError::NotDiverse {
+                institutions: 1,
+                required: 2
+            }
+        ));
+
+        // Failed agent is named by its metadata and counted
+        let phases = {
+            let mut phases = Phases::default();
+            phases.set_members(Members::default().with_member(
+                3,
+                Member {
+                    operator: Some("BankCorp".into()),
+                    region: Some("eu-west".into()),
+                    ..Default::default()
+                },
+            ));
+            phases
+        };
+        let topology = SlowTopology {
+            failing: 3,
+            ..topology
+        };
+        let failed = phases
+            .collect(&topology, (1, point * own), point, Some(&[1, 3]))
+            .await
+            .unwrap_err();
+        assert!(failed
+            .to_string()
+            .ends_with("agent 3 (BankCorp, eu-west): Connection refused"));
+        phases
+            .collect(&topology, (1, point * own), point, None)
+            .await?;
+        assert_eq!(
+            phases
+                .metrics()
+                .evaluation_stats()
+                .into_iter()
+                .map(|(agent, stats)| (agent, stats.evaluated, stats.failed))
+                .collect::<Vec<_>>(),
+            vec![(2, 1, 0), (3, 0, 1)]
+        );
+
+        // Agent in maintenance is excluded, neither the evaluation nor the failure of it is counted
+        let topology = SlowTopology {
+            failing: 0,
+            maintenance: 3,
+            ..topology
+        };
+        phases
+            .collect(&topology, (1, point * own), point, None)
+            .await?;
+        let excluded = phases
+            .collect(&topology, (1, point * own), point, Some(&[1, 3]))
+            .await
+            .unwrap_err();
+        assert!(excluded.to_string().ends_with("Agent 3 is in maintenance"));
+        assert_eq!(
+            phases
+                .metrics()
+                .evaluation_stats()
+                .into_iter()
+                .map(|(agent, stats)| (agent, stats.evaluated, stats.failed))
+                .collect::<Vec<_>>(),
+            vec![(2, 2, 0), (3, 0, 1)]
+        );
+
+        Ok(())
+    }
+}
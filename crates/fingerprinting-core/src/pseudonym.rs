@@ -0,0 +1,92 @@
+use crate::wire::parse_bic;
+use crate::{fingerprint_poseidon, PSEUDONYM_DOMAIN_PREFIX};
+use anyhow::Error;
+use halo2_axiom::halo2curves::bn256::Fr;
+
+/// Keyed pseudonymization of Bank Identifier Codes
+/// Pseudonym is a Poseidon based PRF: Poseidon(PSEUDONYM_DOMAIN | key | BIC), where the key
+/// is kept separately from the fingerprint secret, so pseudonyms are stable for the same key
+/// and cannot be linked to fingerprints
+pub struct BicPseudonymizer {
+    key: Fr,
+}
+
+impl BicPseudonymizer {
+    pub fn new(key: Fr) -> Self {
+        Self { key }
+    }
+
+    /// Computes the pseudonym of the given BIC
+    /// 8 characters BIC is treated as the primary office, so it equals to the `XXX` branch code
+    pub fn pseudonymize(&self, bic: &str) -> Result<Fr, Error> {
+        let bic = parse_bic(bic)?;
+
+        let mut normalized = [0u8; 32];
+        normalized[0..4].copy_from_slice(bic["bank_code"].as_bytes());
+        normalized[4..6].copy_from_slice(bic["country_code"].as_bytes());
+        normalized[6..8].copy_from_slice(bic["location_code"].as_bytes());
+        normalized[8..11].copy_from_slice(
+            bic.name("branch_code")
+                .map(|branch| branch.as_str())
+                .unwrap_or("XXX")
+                .as_bytes(),
+        );
+        let bic = Fr::from_bytes(&normalized).unwrap_or(Fr::zero());
+
+        let mut domain = [0u8; 32];
+        domain[0..PSEUDONYM_DOMAIN_PREFIX.len()]
+            .copy_from_slice(PSEUDONYM_DOMAIN_PREFIX.as_bytes());
+        let domain = Fr::from_bytes(&domain).unwrap_or(Fr::zero());
+
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+        poseidon.update(&[domain, self.key, bic]);
+
+        Ok(poseidon.squeeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pseudonym_is_stable() -> Result<(), Error> {
+        let pseudonymizer = BicPseudonymizer::new(Fr::from(42));
+
+        assert_eq!(
+            pseudonymizer.pseudonymize("BCEELU21")?,
+            pseudonymizer.pseudonymize("BCEELU21")?
+        );
+        assert_eq!(
+            pseudonymizer.pseudonymize("BCEELU21")?,
+            pseudonymizer.pseudonymize("BCEELU21XXX")?
+        );
+        assert_ne!(
+            pseudonymizer.pseudonymize("BCEELU21")?,
+            pseudonymizer.pseudonymize("BCEELU21001")?
+        );
+        assert_ne!(
+            pseudonymizer.pseudonymize("BCEELU21")?,
+            pseudonymizer.pseudonymize("DEUTDEFF")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pseudonym_depends_on_key() -> Result<(), Error> {
+        let pseudonym_a = BicPseudonymizer::new(Fr::from(42)).pseudonymize("BCEELU21")?;
+        let pseudonym_b = BicPseudonymizer::new(Fr::from(43)).pseudonymize("BCEELU21")?;
+
+        assert_ne!(pseudonym_a, pseudonym_b);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_bic_rejected() {
+        let pseudonymizer = BicPseudonymizer::new(Fr::from(42));
+
+        assert!(pseudonymizer.pseudonymize("bceelu21").is_err());
+        assert!(pseudonymizer.pseudonymize("BCE").is_err());
+    }
+}
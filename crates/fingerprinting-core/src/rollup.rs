@@ -0,0 +1,250 @@
+//! Daily roll-ups of the fingerprints, for the reporting pipelines keeping the digest of the day only
+//!
+//! Every fingerprint of the world wide day (or of the bank and the world wide day) is mapped to
+//! `Poseidon(ROLLUP_DOMAIN | fingerprint)` and the images are summed in Fr along with their count, so the roll-up
+//! does not depend on the order of the fingerprints, is updated one fingerprint at a time and merged from the partial
+//! roll-ups of the several workers. The digest of the day commits to its key, the sum and the count:
+//! `Poseidon(ROLLUP_DOMAIN | bic | wwd | sum | count)`, the absent BIC is zero. BICs are normalized by
+//! `wire::encode_bic` as the fingerprints are, so the branch codes of the same bank share the roll-up.
+//!
+//! The sum detects the missing, extra and repeated fingerprints between the consistent reports, it does not bind the
+//! fingerprints against the adversarial choice of them: anchor the batches with `batch_merkle::BatchTree` instead.
+
+use crate::wire::{self, BIC_SIZE};
+use crate::{fingerprint_poseidon, Fingerprint, HashSqueeze, ROLLUP_DOMAIN_PREFIX};
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use chrono::{Datelike, NaiveDate};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::collections::BTreeMap;
+
+/// Grouping of the fingerprints into the roll-ups
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RollupScope {
+    /// Roll-up of the world wide day
+    #[default]
+    Wwd,
+    /// Roll-up of the bank (BIC without the branch) within the world wide day
+    BicWwd,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RollupKey {
+    /// BIC encoded by `wire::encode_bic`, absent in the `RollupScope::Wwd` scope
+    pub bic: Option<[u8; BIC_SIZE]>,
+    pub wwd: NaiveDate,
+}
+
+fn domain() -> Fr {
+    let mut domain = [0u8; 32];
+    domain[0..ROLLUP_DOMAIN_PREFIX.len()].copy_from_slice(ROLLUP_DOMAIN_PREFIX.as_bytes());
+
+    Fr::from_bytes(&domain).unwrap_or(Fr::zero())
+}
+
+/// Fold of the fingerprints of the single key, kept (e.g. persisted) between the updates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rollup {
+    /// Sum of the images of the fingerprints
+    pub sum: Fr,
+    /// Number of the fingerprints folded, the repeated ones included
+    pub count: u64,
+}
+
+impl Default for Rollup {
+    fn default() -> Self {
+        Self {
+            sum: Fr::zero(),
+            count: 0,
+        }
+    }
+}
+
+impl Rollup {
+    pub fn add(&mut self, fingerprint: &Fingerprint) {
+        let mut poseidon = fingerprint_poseidon::<4, 3>();
+        poseidon.update(&[domain(), *fingerprint.value()]);
+
+        self.sum += poseidon.squeeze();
+        self.count += 1;
+    }
+
+    /// Folds the other roll-up of the same key in, as if its fingerprints were added one by one
+    pub fn merge(&mut self, other: &Rollup) {
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    pub fn digest(&self, key: &RollupKey) -> Result<Fingerprint, Error> {
+        let bic = match &key.bic {
+            Some(bic) => Bytes::copy_from_slice(bic).squeeze()?,
+            None => Fr::zero(),
+        };
+
+        let mut poseidon = fingerprint_poseidon::<5, 4>();
+        poseidon.update(&[
+            domain(),
+            bic,
+            Fr::from(key.wwd.num_days_from_ce() as u64),
+            self.sum,
+            Fr::from(self.count),
+        ]);
+
+        Ok(Fingerprint::new(poseidon.squeeze()))
+    }
+}
+
+/// Roll-ups of the fingerprints by the key of their scope
+#[derive(Debug, Clone, Default)]
+pub struct Rollups {
+    scope: RollupScope,
+    rollups: BTreeMap<RollupKey, Rollup>,
+}
+
+impl Rollups {
+    pub fn new(scope: RollupScope) -> Self {
+        Self {
+            scope,
+            rollups: BTreeMap::new(),
+        }
+    }
+
+    pub fn scope(&self) -> RollupScope {
+        self.scope
+    }
+
+    pub fn key(&self, bic: &str, wwd: NaiveDate) -> Result<RollupKey, Error> {
+        Ok(RollupKey {
+            bic: match self.scope {
+                RollupScope::Wwd => None,
+                RollupScope::BicWwd => Some(wire::encode_bic(bic)?),
+            },
+            wwd,
+        })
+    }
+
+    /// Folds the fingerprint of the transaction of the `bic` on the `wwd` into its roll-up
+    pub fn add(
+        &mut self,
+        bic: &str,
+        wwd: NaiveDate,
+        fingerprint: &Fingerprint,
+    ) -> Result<(), Error> {
+        let key = self.key(bic, wwd)?;
+        self.rollups.entry(key).or_default().add(fingerprint);
+
+        Ok(())
+    }
+
+    /// Merges the roll-ups of the other worker, refused unless it is of the same scope
+    pub fn merge(&mut self, other: &Rollups) -> Result<(), Error> {
+        if self.scope != other.scope {
+            return Err(anyhow!(
+                "Merged roll-ups scope {:?} should be {:?}",
+                other.scope,
+                self.scope
+            ));
+        }
+
+        for (key, rollup) in &other.rollups {
+            self.rollups.entry(key.clone()).or_default().merge(rollup);
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &RollupKey) -> Option<&Rollup> {
+        self.rollups.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&RollupKey, &Rollup)> {
+        self.rollups.iter()
+    }
+
+    /// Digests of all the keys ordered by the key
+    pub fn digests(&self) -> Result<Vec<(RollupKey, Fingerprint)>, Error> {
+        self.rollups
+            .iter()
+            .map(|(key, rollup)| Ok((key.clone(), rollup.digest(key)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollups() -> Result<(), Error> {
+        let day = NaiveDate::from_ymd_opt(2025, 9, 16).unwrap();
+        let next_day = day.succ_opt().unwrap();
+        let fingerprints = (1..=5u64)
+            .map(|value| Fingerprint::new(Fr::from(value)))
+            .collect::<Vec<_>>();
+
+        let mut daily = Rollups::new(RollupScope::Wwd);
+        let mut reversed = Rollups::new(RollupScope::Wwd);
+        for fingerprint in &fingerprints {
+            daily.add("BCEELU21", day, fingerprint)?;
+        }
+        for fingerprint in fingerprints.iter().rev() {
+            reversed.add("DEUTDEFF", day, fingerprint)?;
+        }
+        let key = daily.key("BCEELU21", day)?;
+        assert_eq!(key.bic, None);
+        assert_eq!(daily.get(&key).unwrap().count, 5);
+        assert_eq!(daily.digests()?, reversed.digests()?);
+
+        // Merged partial roll-ups give the digest of the whole day
+        let mut first = Rollups::new(RollupScope::Wwd);
+        let mut second = Rollups::new(RollupScope::Wwd);
+        for fingerprint in &fingerprints[..2] {
+            first.add("BCEELU21", day, fingerprint)?;
+        }
+        for fingerprint in &fingerprints[2..] {
+            second.add("BCEELU21", day, fingerprint)?;
+        }
+        first.merge(&second)?;
+        assert_eq!(first.digests()?, daily.digests()?);
+
+        // Roll-ups of the other scope are not merged
+        let mut other_scope = Rollups::new(RollupScope::BicWwd);
+        other_scope.add("BCEELU21", day, &fingerprints[0])?;
+        assert!(first.merge(&other_scope).is_err());
+        assert_eq!(first.digests()?, daily.digests()?);
+
+        // Missing and repeated fingerprints change the digest
+        let digest = daily.get(&key).unwrap().digest(&key)?;
+        let mut partial = Rollup::default();
+        fingerprints[1..].iter().for_each(|fp| partial.add(fp));
+        assert_ne!(partial.digest(&key)?, digest);
+        partial.add(&fingerprints[1]);
+        assert_ne!(partial.digest(&key)?, digest);
+        let next_key = RollupKey {
+            bic: None,
+            wwd: next_day,
+        };
+        assert_ne!(daily.get(&key).unwrap().digest(&next_key)?, digest);
+
+        let mut by_bic = Rollups::new(RollupScope::BicWwd);
+        by_bic.add("BCEELU21", day, &fingerprints[0])?;
+        by_bic.add("DEUTDEFF", day, &fingerprints[0])?;
+        by_bic.add("BCEELU21", next_day, &fingerprints[0])?;
+        let digests = by_bic.digests()?;
+        assert_eq!(digests.len(), 3);
+        assert_eq!(digests[0].0.bic, Some(*b"BCEELU"));
+        assert_ne!(digests[0].1, digests[2].1);
+
+        // Branch of the bank shares the roll-up of the bank, as it shares the fingerprints
+        by_bic.add("BCEELU21XXX", day, &fingerprints[1])?;
+        assert_eq!(by_bic.digests()?.len(), 3);
+        assert_eq!(
+            by_bic.key("BCEELU21XXX", day)?,
+            by_bic.key("BCEELU21", day)?
+        );
+        assert_eq!(by_bic.get(&by_bic.key("BCEELU21", day)?).unwrap().count, 2);
+        assert!(by_bic.add("BCE", day, &fingerprints[1]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+//! Fingerprint schemas: which optional components the deployment fingerprints and in what order
+//!
+//! Schema 0 is the built-in layout: every optional component the transaction carries is bound in the fixed order
+//! and the preimage is the one of the fingerprints computed before the schemas. Other schemas serialize their id
+//! into the preimage, so the fingerprints of the different schemas never collide, and bind only the listed
+//! components in the listed order. The transaction carrying the component its schema does not list is refused
+//! rather than fingerprinted without it.
+//!
+//! The direction agnostic schema fingerprints the transfer the same from both sides: the reporting BIC is left out of
+//! the preimage and the counterparty pair is sorted before hashing, so the debtor and the creditor institutions
+//! reporting the same interbank transfer get the same fingerprint.
+//!
+//! The id of the schema is never reused for another layout, deployments exchanging the fingerprints register
+//! the same schemas, see `FingerprintSchemaRegistry`.
+
+use crate::{
+    AMOUNT_TIER_DOMAIN_PREFIX, COUNTERPARTY_DOMAIN_PREFIX, COUNTRY_DOMAIN_PREFIX,
+    MCC_DOMAIN_PREFIX, MERCHANT_DOMAIN_PREFIX, PAIRED_AMOUNT_DOMAIN_PREFIX,
+    REFERENCE_DOMAIN_PREFIX, SERIES_DOMAIN_PREFIX,
+};
+use anyhow::{anyhow, Error};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Id of the built-in layout
+pub const BUILT_IN_SCHEMA_ID: u32 = 0;
+
+/// Optional component of the transaction bound on top of the preimage hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SchemaComponent {
+    CounterAmount,
+    Series,
+    Merchant,
+    Country,
+    Reference,
+    Mcc,
+    Counterparty,
+    AmountTier,
+}
+
+impl SchemaComponent {
+    /// All the components in the binding order of the built-in layout
+    pub const ALL: [SchemaComponent; 8] = [
+        SchemaComponent::CounterAmount,
+        SchemaComponent::Series,
+        SchemaComponent::Merchant,
+        SchemaComponent::Country,
+        SchemaComponent::Reference,
+        SchemaComponent::Mcc,
+        SchemaComponent::Counterparty,
+        SchemaComponent::AmountTier,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SchemaComponent::CounterAmount => "counter-amount",
+            SchemaComponent::Series => "series",
+            SchemaComponent::Merchant => "merchant",
+            SchemaComponent::Country => "country",
+            SchemaComponent::Reference => "reference",
+            SchemaComponent::Mcc => "mcc",
+            SchemaComponent::Counterparty => "counterparty",
+            SchemaComponent::AmountTier => "amount-tier",
+        }
+    }
+
+    pub fn domain_tag(&self) -> &'static str {
+        match self {
+            SchemaComponent::CounterAmount => PAIRED_AMOUNT_DOMAIN_PREFIX,
+            SchemaComponent::Series => SERIES_DOMAIN_PREFIX,
+            SchemaComponent::Merchant => MERCHANT_DOMAIN_PREFIX,
+            SchemaComponent::Country => COUNTRY_DOMAIN_PREFIX,
+            SchemaComponent::Reference => REFERENCE_DOMAIN_PREFIX,
+            SchemaComponent::Mcc => MCC_DOMAIN_PREFIX,
+            SchemaComponent::Counterparty => COUNTERPARTY_DOMAIN_PREFIX,
+            SchemaComponent::AmountTier => AMOUNT_TIER_DOMAIN_PREFIX,
+        }
+    }
+}
+
+impl fmt::Display for SchemaComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SchemaComponent {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        SchemaComponent::ALL
+            .into_iter()
+            .find(|component| component.name() == name)
+            .ok_or(anyhow!(
+                "Unknown component {}, should be one of {}",
+                name,
+                SchemaComponent::ALL
+                    .map(|component| component.name())
+                    .join(", ")
+            ))
+    }
+}
+
+/// Optional components the fingerprints of the schema bind, in the binding order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FingerprintSchema {
+    id: u32,
+    components: Vec<SchemaComponent>,
+    direction_agnostic: bool,
+}
+
+impl FingerprintSchema {
+    /// Schema of the deployment, the id is above 0 and every component is listed once
+    pub fn new(id: u32, components: Vec<SchemaComponent>) -> Result<Self, Error> {
+        if id == BUILT_IN_SCHEMA_ID {
+            return Err(anyhow!(
+                "Schema id {} is reserved for the built-in layout",
+                BUILT_IN_SCHEMA_ID
+            ));
+        }
+        for (i, component) in components.iter().enumerate() {
+            if components[..i].contains(component) {
+                return Err(anyhow!(
+                    "Component {} is listed more than once in the schema {}",
+                    component,
+                    id
+                ));
+            }
+        }
+
+        Ok(Self {
+            id,
+            components,
+            direction_agnostic: false,
+        })
+    }
+
+    /// Schema fingerprinting the transfer the same whichever side reports it, lists the counterparty
+    /// and its transactions carry one
+    pub fn direction_agnostic(id: u32, components: Vec<SchemaComponent>) -> Result<Self, Error> {
+        if !components.contains(&SchemaComponent::Counterparty) {
+            return Err(anyhow!(
+                "Direction agnostic schema {} should list the {} component",
+                id,
+                SchemaComponent::Counterparty
+            ));
+        }
+
+        Ok(Self {
+            direction_agnostic: true,
+            ..Self::new(id, components)?
+        })
+    }
+
+    /// Built-in layout binding all the components the transaction carries
+    pub fn built_in() -> Self {
+        Self {
+            id: BUILT_IN_SCHEMA_ID,
+            components: SchemaComponent::ALL.to_vec(),
+            direction_agnostic: false,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn components(&self) -> &[SchemaComponent] {
+        &self.components
+    }
+
+    pub fn includes(&self, component: SchemaComponent) -> bool {
+        self.components.contains(&component)
+    }
+
+    pub fn is_direction_agnostic(&self) -> bool {
+        self.direction_agnostic
+    }
+}
+
+/// Schemas known to the deployment by their ids, the built-in one is always registered
+#[derive(Debug, Clone)]
+pub struct FingerprintSchemaRegistry {
+    schemas: BTreeMap<u32, Arc<FingerprintSchema>>,
+}
+
+impl Default for FingerprintSchemaRegistry {
+    fn default() -> Self {
+        Self {
+            schemas: BTreeMap::from([(
+                BUILT_IN_SCHEMA_ID,
+                Arc::new(FingerprintSchema::built_in()),
+            )]),
+        }
+    }
+}
+
+impl FingerprintSchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the schema, the id registered with another layout is refused
+    pub fn register(&mut self, schema: FingerprintSchema) -> Result<Arc<FingerprintSchema>, Error> {
+        match self.schemas.get(&schema.id) {
+            Some(registered) if registered.as_ref() != &schema => Err(anyhow!(
+                "Schema {} is already registered with the components [{}]{}",
+                schema.id,
+                registered
+                    .components
+                    .iter()
+                    .map(SchemaComponent::name)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                if registered.direction_agnostic {
+                    ", direction agnostic"
+                } else {
+                    ""
+                }
+            )),
+            Some(registered) => Ok(registered.clone()),
+            None => {
+                let schema = Arc::new(schema);
+                self.schemas.insert(schema.id, schema.clone());
+                Ok(schema)
+            }
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Result<Arc<FingerprintSchema>, Error> {
+        self.schemas
+            .get(&id)
+            .cloned()
+            .ok_or(anyhow!("Schema {} is not registered", id))
+    }
+
+    /// Registered schemas in the order of their ids
+    pub fn schemas(&self) -> impl Iterator<Item = &FingerprintSchema> {
+        self.schemas.values().map(|schema| schema.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NaiveProtocol, ProtocolFingerprint, TransactionFingerprintData};
+    use chrono::{TimeZone, Utc};
+    use fingerprinting_types::{Counterparty, Money, RawTransaction};
+    use halo2_axiom::halo2curves::bn256::Fr;
+
+    fn raw_transaction() -> RawTransaction {
+        let date_time = Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap();
+        RawTransaction {
+            bic: "BCEELU21".to_string(),
+            amount: Money {
+                amount_base: 1000,
+                amount_atto: 550_000_000_000_000_000,
+                currency: "EUR".to_string(),
+            },
+            counter_amount: None,
+            series_id: None,
+            merchant_id: Some("MID-1".to_string()),
+            country_code: Some("LU".to_string()),
+            transaction_reference: None,
+            mcc: None,
+            counterparty: None,
+            date_time,
+            wwd: date_time.date_naive(),
+        }
+    }
+
+    fn transaction() -> Result<TransactionFingerprintData<Fr>, Error> {
+        raw_transaction().try_into()
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_schemas() -> Result<(), Error> {
+        use SchemaComponent::*;
+
+        let mut registry = FingerprintSchemaRegistry::new();
+        let ordered = registry.register(FingerprintSchema::new(1, vec![Merchant, Country])?)?;
+        let reversed = registry.register(FingerprintSchema::new(2, vec![Country, Merchant])?)?;
+        let extended =
+            registry.register(FingerprintSchema::new(3, vec![Merchant, Country, Mcc])?)?;
+        assert!(registry
+            .register(FingerprintSchema::new(1, vec![Merchant])?)
+            .is_err());
+        assert!(FingerprintSchema::new(0, vec![]).is_err());
+        assert!(FingerprintSchema::new(4, vec![Mcc, Mcc]).is_err());
+        assert_eq!(registry.get(0)?.components(), SchemaComponent::ALL);
+        assert!(registry.get(7).is_err());
+        assert_eq!("counter-amount".parse::<SchemaComponent>()?, CounterAmount);
+
+        let naive = NaiveProtocol::new(Fr::from(42));
+        let fingerprint = |schema: Option<Arc<FingerprintSchema>>| {
+            let naive = &naive;
+            async move {
+                transaction()?
+                    .with_schema(schema)
+                    .complete_fingerprint(naive)
+                    .await
+            }
+        };
+        let built_in = fingerprint(None).await?;
+        assert_eq!(fingerprint(Some(registry.get(0)?)).await?, built_in);
+
+        // Schema id is in the preimage, the order of the components is the schema's
+        let ordered = fingerprint(Some(ordered)).await?;
+        assert_ne!(ordered, built_in);
+        assert_ne!(fingerprint(Some(reversed)).await?, ordered);
+        assert_ne!(fingerprint(Some(extended)).await?, ordered);
+
+        // Component outside of the schema is refused
+        let merchant_only = Arc::new(FingerprintSchema::new(5, vec![Merchant])?);
+        assert!(fingerprint(Some(merchant_only)).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_direction_agnostic_schema() -> Result<(), Error> {
+        use SchemaComponent::{Country, Merchant};
+
+        let mut registry = FingerprintSchemaRegistry::new();
+        let agnostic = registry.register(FingerprintSchema::direction_agnostic(
+            1,
+            vec![Merchant, Country, SchemaComponent::Counterparty],
+        )?)?;
+        let ordered = Arc::new(FingerprintSchema::new(
+            2,
+            vec![Merchant, Country, SchemaComponent::Counterparty],
+        )?);
+        assert!(agnostic.is_direction_agnostic());
+        assert!(!ordered.is_direction_agnostic());
+        assert!(FingerprintSchema::direction_agnostic(3, vec![Merchant]).is_err());
+        assert!(registry
+            .register(FingerprintSchema::new(
+                1,
+                vec![Merchant, Country, SchemaComponent::Counterparty]
+            )?)
+            .is_err());
+
+        let naive = NaiveProtocol::new(Fr::from(42));
+        let transfer = |bic: &str, payer: &str, payee: &str, schema: &Arc<FingerprintSchema>| {
+            let tx = TransactionFingerprintData::<Fr>::try_from(RawTransaction {
+                bic: bic.to_string(),
+                counterparty: Some(Counterparty {
+                    payer: payer.to_string(),
+                    payee: payee.to_string(),
+                }),
+                ..raw_transaction()
+            });
+            let schema = schema.clone();
+            let naive = &naive;
+            async move {
+                tx?.with_schema(Some(schema))
+                    .complete_fingerprint(naive)
+                    .await
+            }
+        };
+        let (debtor, creditor) = ("LU280019400644750000", "BE68539007547034");
+
+        // Debtor and creditor institutions report the same transfer
+        let reported = transfer("BCEELU21", debtor, creditor, &agnostic).await?;
+        assert_eq!(
+            transfer("GEBABEBB", creditor, debtor, &agnostic).await?,
+            reported
+        );
+        assert_ne!(
+            transfer("BCEELU21", debtor, "BE71096123456769", &agnostic).await?,
+            reported
+        );
+
+        // Ordered schema tells the sides apart
+        assert_ne!(
+            transfer("BCEELU21", debtor, creditor, &ordered).await?,
+            transfer("BCEELU21", creditor, debtor, &ordered).await?
+        );
+
+        // Transfer without the counterparty is refused
+        let unpaired = transaction()?.with_schema(Some(agnostic));
+        assert!(unpaired.complete_fingerprint(&naive).await.is_err());
+
+        Ok(())
+    }
+}
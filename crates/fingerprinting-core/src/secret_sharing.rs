@@ -1,5 +1,5 @@
 use halo2_axiom::halo2curves::ff::PrimeField;
-use rand_core::OsRng;
+use rand_core::{CryptoRng, OsRng, RngCore};
 use std::collections::HashMap;
 
 #[cfg(test)]
@@ -13,14 +13,23 @@ pub struct SecretSharing<F: PrimeField> {
 
 impl<F: PrimeField> SecretSharing<F> {
     pub fn generate(k: F, t: usize, n: usize) -> Self {
+        Self::generate_with_rng(k, t, n, &mut OsRng)
+    }
+
+    /// Generates the polynomial coefficients with the `rng`, such as the `EntropyRng` of the configured source
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(
+        k: F,
+        t: usize,
+        n: usize,
+        rng: &mut R,
+    ) -> Self {
         assert!(t <= n, "Threshold must be <= total shares");
         assert!(t > 0, "Threshold must be >= 1");
 
-        let mut rng = OsRng;
         let mut coefficients = vec![k];
 
         for _ in 1..t {
-            coefficients.push(F::random(&mut rng));
+            coefficients.push(F::random(&mut *rng));
         }
 
         let mut shares = HashMap::new();
@@ -75,6 +84,24 @@ impl<F: PrimeField> SecretSharing<F> {
         self.shares.get(&i).cloned()
     }
 
+    /// Shares the share `i` again, between the `n` agents of the region `i` with the threshold `t`.
+    /// The regional shares combined by the Lagrange interpolation give the share `i`
+    pub fn reshare(&self, i: usize, t: usize, n: usize) -> Option<Self> {
+        self.reshare_with_rng(i, t, n, &mut OsRng)
+    }
+
+    pub fn reshare_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        i: usize,
+        t: usize,
+        n: usize,
+        rng: &mut R,
+    ) -> Option<Self> {
+        self.shares
+            .get(&i)
+            .map(|share| SecretSharing::generate_with_rng(*share, t, n, rng))
+    }
+
     pub fn get_shares(&self) -> &HashMap<usize, F> {
         &self.shares
     }
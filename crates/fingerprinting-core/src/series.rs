@@ -0,0 +1,119 @@
+//! Expected fingerprints of the standing orders and installment plans
+//!
+//! The schedule expands the first installment to the future ones, which differ only by the date time,
+//! so their fingerprints can be derived in advance and pre-registered for matching of the recurring payments.
+
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Duration, Months, Utc};
+use fingerprinting_types::RawTransaction;
+
+/// Maximum number of installments derived at once
+pub const MAX_INSTALLMENTS: usize = 400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recurrence {
+    Days(u32),
+    /// Day of the month is clamped to the last day of the shorter months
+    Months(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub recurrence: Recurrence,
+    /// Number of installments including the first one
+    pub installments: usize,
+}
+
+impl Schedule {
+    pub fn new(recurrence: Recurrence, installments: usize) -> Result<Self, Error> {
+        if matches!(recurrence, Recurrence::Days(0) | Recurrence::Months(0)) {
+            return Err(anyhow!("Recurrence interval should be positive"));
+        }
+        if installments == 0 || installments > MAX_INSTALLMENTS {
+            return Err(anyhow!(
+                "Number of installments should be from 1 to {}, given {}",
+                MAX_INSTALLMENTS,
+                installments
+            ));
+        }
+
+        Ok(Self {
+            recurrence,
+            installments,
+        })
+    }
+
+    /// Date time of the installment, the first one has the sequence 0
+    pub fn date_time(&self, first: DateTime<Utc>, sequence: usize) -> Result<DateTime<Utc>, Error> {
+        let date_time = match self.recurrence {
+            Recurrence::Days(days) => {
+                first.checked_add_signed(Duration::days(days as i64 * sequence as i64))
+            }
+            Recurrence::Months(months) => {
+                first.checked_add_months(Months::new(months * sequence as u32))
+            }
+        };
+
+        date_time.ok_or(anyhow!("Installment {} is out of range", sequence))
+    }
+
+    /// Future installments of the series starting with the `first` one,
+    /// the world wide day follows the date time offset of the first installment
+    pub fn installments(&self, first: &RawTransaction) -> Result<Vec<RawTransaction>, Error> {
+        let wwd_offset = first.wwd - first.date_time.date_naive();
+
+        (0..self.installments)
+            .map(|sequence| {
+                let date_time = self.date_time(first.date_time, sequence)?;
+
+                Ok(RawTransaction {
+                    date_time,
+                    wwd: date_time.date_naive() + wwd_offset,
+                    ..first.clone()
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use fingerprinting_types::RawTransactionBuilder;
+
+    #[test]
+    fn test_monthly_installments() -> Result<(), Error> {
+        let first = Utc.with_ymd_and_hms(2025, 1, 31, 9, 0, 0).unwrap();
+        let tx = RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount((250u64, "EUR"))
+            .series_id("loan-42".to_string())
+            .date_time(first)
+            .wwd(first.date_naive())
+            .build()?;
+
+        let installments = Schedule::new(Recurrence::Months(1), 3)?.installments(&tx)?;
+        assert_eq!(
+            installments
+                .iter()
+                .map(|tx| tx.date_time)
+                .collect::<Vec<_>>(),
+            vec![
+                first,
+                Utc.with_ymd_and_hms(2025, 2, 28, 9, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2025, 3, 31, 9, 0, 0).unwrap(),
+            ]
+        );
+        assert_eq!(installments[2].wwd, installments[2].date_time.date_naive());
+        assert_eq!(installments[2].series_id.as_deref(), Some("loan-42"));
+
+        let weekly = Schedule::new(Recurrence::Days(7), 2)?;
+        assert_eq!(weekly.date_time(first, 1)?, first + Duration::days(7));
+
+        assert!(Schedule::new(Recurrence::Days(0), 2).is_err());
+        assert!(Schedule::new(Recurrence::Months(1), MAX_INSTALLMENTS + 1).is_err());
+
+        Ok(())
+    }
+}
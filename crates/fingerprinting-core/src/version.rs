@@ -0,0 +1,146 @@
+//! Versions of the agents cooperating in the fingerprint computation
+//!
+//! Agents report their versions in the handshake, the coordinator checks them against the compatibility matrix
+//! when the topology is built: the cooperation protocol version should be in the supported range and the
+//! agent should support the wire version of the coordinator.
+//!
+//! Agents of the same version could still be built with the different parameters or messages, so the
+//! coordinator and the agents exchange the `compatibility_digest` of their builds as well.
+
+use crate::wire::{WireVersion, PREIMAGE_PREFIX};
+use crate::{
+    parameters_digest, COMMITMENT_DOMAIN_PREFIX, COUNTERPARTY_DOMAIN_PREFIX, COUNTRY_DOMAIN_PREFIX,
+    CRYPTO_DOMAIN_PREFIX, EPOCH, MCC_DOMAIN_PREFIX, MERCHANT_DOMAIN_PREFIX,
+    NAMESPACE_DOMAIN_PREFIX, PAIRED_AMOUNT_DOMAIN_PREFIX, PSEUDONYM_DOMAIN_PREFIX,
+    REFERENCE_DOMAIN_PREFIX, SALT_DOMAIN_PREFIX, SERIES_DOMAIN_PREFIX,
+};
+use anyhow::{anyhow, Error};
+use sha2::{Digest, Sha256};
+
+/// Version of the cooperation between the agents, changed on the incompatible change of the messages
+pub const AGENT_PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest cooperation protocol version the agents are still compatible with
+pub const MIN_AGENT_PROTOCOL_VERSION: u32 = 1;
+
+pub const SOFTWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentVersion {
+    pub protocol: u32,
+    pub software: String,
+    /// Wire versions supported by the agent, see `WireVersion`
+    pub wire_versions: Vec<u8>,
+}
+
+impl AgentVersion {
+    /// Version of this implementation
+    pub fn current() -> Self {
+        Self {
+            protocol: AGENT_PROTOCOL_VERSION,
+            software: SOFTWARE_VERSION.to_string(),
+            wire_versions: WireVersion::SUPPORTED
+                .iter()
+                .map(|version| version.as_u8())
+                .collect(),
+        }
+    }
+
+    /// Checks the agent is able to cooperate with this implementation
+    pub fn check_compatible(&self) -> Result<(), Error> {
+        if !(MIN_AGENT_PROTOCOL_VERSION..=AGENT_PROTOCOL_VERSION).contains(&self.protocol) {
+            return Err(anyhow!(
+                "Agent protocol version {} (software {}) is not supported, should be from {} to {}",
+                self.protocol,
+                self.software,
+                MIN_AGENT_PROTOCOL_VERSION,
+                AGENT_PROTOCOL_VERSION
+            ));
+        }
+        if !self.wire_versions.contains(&WireVersion::CURRENT.as_u8()) {
+            return Err(anyhow!(
+                "Agent (software {}) does not support wire version {}, it supports {:?}",
+                self.software,
+                WireVersion::CURRENT.as_u8(),
+                self.wire_versions
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Digest of the fingerprint parameters and the compiled proto `descriptors` of the build,
+/// builds computing the different fingerprints of the same transaction have the different digests
+pub fn compatibility_digest(descriptors: &[Vec<u8>]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([WireVersion::CURRENT.as_u8()]);
+    hasher.update(PREIMAGE_PREFIX);
+    // Poseidon constants and the hash-to-curve domain
+    hasher.update(parameters_digest());
+    for prefix in [
+        SALT_DOMAIN_PREFIX,
+        PSEUDONYM_DOMAIN_PREFIX,
+        COMMITMENT_DOMAIN_PREFIX,
+        NAMESPACE_DOMAIN_PREFIX,
+        PAIRED_AMOUNT_DOMAIN_PREFIX,
+        SERIES_DOMAIN_PREFIX,
+        MERCHANT_DOMAIN_PREFIX,
+        COUNTRY_DOMAIN_PREFIX,
+        REFERENCE_DOMAIN_PREFIX,
+        MCC_DOMAIN_PREFIX,
+        COUNTERPARTY_DOMAIN_PREFIX,
+        CRYPTO_DOMAIN_PREFIX,
+    ] {
+        hasher.update((prefix.len() as u32).to_be_bytes());
+        hasher.update(prefix);
+    }
+    hasher.update(EPOCH.and_utc().timestamp().to_be_bytes());
+    for descriptor in descriptors {
+        hasher.update((descriptor.len() as u32).to_be_bytes());
+        hasher.update(descriptor);
+    }
+
+    hasher.finalize().into()
+}
+
+/// What the coordinator does with the agents of the incompatible versions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IncompatibleAgentPolicy {
+    /// Topology is not built at all
+    #[default]
+    Refuse,
+    /// Agents are excluded from the topology with the warning, while the threshold is still reachable
+    Exclude,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_compatibility() {
+        assert!(AgentVersion::current().check_compatible().is_ok());
+
+        let newer = AgentVersion {
+            protocol: AGENT_PROTOCOL_VERSION + 1,
+            ..AgentVersion::current()
+        };
+        assert!(newer.check_compatible().is_err());
+
+        let unknown_wire = AgentVersion {
+            wire_versions: vec![WireVersion::CURRENT.as_u8() + 1],
+            ..AgentVersion::current()
+        };
+        assert!(unknown_wire.check_compatible().is_err());
+
+        assert_eq!(
+            compatibility_digest(&[vec![1, 2, 3]]),
+            compatibility_digest(&[vec![1, 2, 3]])
+        );
+        assert_ne!(
+            compatibility_digest(&[vec![1, 2, 3]]),
+            compatibility_digest(&[vec![1, 2], vec![3]])
+        );
+    }
+}
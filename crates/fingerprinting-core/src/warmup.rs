@@ -0,0 +1,55 @@
+//! Warm-up of the lazily initialized state before the service is reported ready
+//!
+//! The Poseidon specs are generated on the first hash and the agent connections are established on the first
+//! cooperation, so without the warm-up the first requests pay hundreds of milliseconds for them.
+
+use crate::{
+    Fingerprint, FingerprintProtocol, ProtocolFingerprint, TransactionFingerprintData, EPOCH,
+};
+use anyhow::Error;
+use chrono::Duration;
+use fingerprinting_types::RawTransactionBuilder;
+use halo2_axiom::halo2curves::bn256::Fr;
+
+/// Generates the Poseidon specs of all the fingerprint hashes
+pub fn init_specs() {
+    fingerprinting_verify::init_specs();
+}
+
+/// Generates the specs and computes the dummy fingerprint via the `protocol`, reaching the agents of the topology
+pub async fn warm_up<P: FingerprintProtocol<Fr> + Sync>(
+    protocol: &P,
+) -> Result<Fingerprint, Error> {
+    init_specs();
+
+    let date_time = EPOCH.and_utc() + Duration::days(1);
+    let dummy: TransactionFingerprintData<Fr> = RawTransactionBuilder::default()
+        .bic("AAAAAAAA")
+        .amount((1u64, "EUR"))
+        .date_time(date_time)
+        .wwd(date_time.date_naive())
+        .build()?
+        .try_into()?;
+
+    dummy.complete_fingerprint(protocol).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NaiveProtocol;
+
+    #[tokio::test]
+    async fn test_warm_up() -> Result<(), Error> {
+        let protocol = NaiveProtocol::new(Fr::from(42));
+
+        let fingerprint = warm_up(&protocol).await?;
+        assert_eq!(warm_up(&protocol).await?, fingerprint);
+        assert_ne!(
+            warm_up(&NaiveProtocol::new(Fr::from(43))).await?,
+            fingerprint
+        );
+
+        Ok(())
+    }
+}
@@ -1,18 +1,33 @@
-use crate::net::outbe::fingerprint::agent::v1::{CooperationRequest, CooperationServiceClient};
-use anyhow::Error;
-use fingerprinting_core::AgentsTopology;
-use halo2_axiom::halo2curves::bn256::{Fr, G1Compressed, G1};
-use halo2_axiom::halo2curves::group::GroupEncoding;
+use crate::net::outbe::fingerprint::agent::v1::{
+    CooperationRequest, CooperationScope, CooperationServiceClient, GetAgentInfoRequest,
+};
+use crate::signature::{PartialTranscript, SignedPartial, REQUEST_ID_SIZE};
+use crate::{compatibility_digest, is_maintenance_refusal, AgentClientTls};
+use anyhow::{anyhow, Error};
+use ed25519_dalek::VerifyingKey;
+use fingerprinting_core::breaker::CircuitBreakers;
+use fingerprinting_core::events::{EventBus, LifecycleEvent};
+use fingerprinting_core::version::{AgentVersion, IncompatibleAgentPolicy, AGENT_PROTOCOL_VERSION};
+use fingerprinting_core::{wire, AgentInMaintenance, AgentsTopology};
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
 use pilota::Bytes;
 use rand::Rng;
 use std::collections::HashMap;
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use volo::net::tls::ClientTlsConfig;
 use volo::net::Address;
+use volo_grpc::Code;
 
 pub struct GrpcAgentsTopology {
     count: usize,
     threshold: usize,
     members: HashMap<usize, Vec<CooperationServiceClient>>,
+    scope: CooperationScope,
+    verifying_keys: HashMap<usize, VerifyingKey>,
+    transcript: Option<Arc<dyn PartialTranscript>>,
+    events: Option<Arc<dyn EventBus>>,
+    breakers: Option<Arc<CircuitBreakers>>,
 }
 
 impl GrpcAgentsTopology {
@@ -20,9 +35,10 @@ impl GrpcAgentsTopology {
         let members: HashMap<usize, Vec<CooperationServiceClient>> = members
             .iter()
             .map(|(position, addr)| {
-                let clients_for_addr = GrpcAgentsTopology::build_client(addr).unwrap_or_default();
+                let clients_for_addr =
+                    GrpcAgentsTopology::build_client(addr, None).unwrap_or_default();
 
-                (position.clone(), clients_for_addr)
+                (*position, clients_for_addr)
             })
             .collect();
 
@@ -30,30 +46,237 @@ impl GrpcAgentsTopology {
             count,
             threshold,
             members,
+            scope: CooperationScope::COOPERATION_SCOPE_AGENT,
+            verifying_keys: HashMap::new(),
+            transcript: None,
+            events: None,
+            breakers: None,
         }
     }
 
+    /// Topology with the TLS connections to the agents, each agent could have its own TLS settings
+    pub fn with_client_tls(
+        count: usize,
+        threshold: usize,
+        members: Vec<(usize, String, Option<AgentClientTls>)>,
+    ) -> Result<Self, Error> {
+        let members = members
+            .iter()
+            .map(|(position, addr, tls)| {
+                let tls_config = tls
+                    .as_ref()
+                    .map(|tls| tls.client_config(addr))
+                    .transpose()
+                    .map_err(|e| {
+                        anyhow::anyhow!("Invalid TLS settings of agent {}: {}", position, e)
+                    })?;
+
+                Ok((
+                    *position,
+                    GrpcAgentsTopology::build_client(addr, tls_config)?,
+                ))
+            })
+            .collect::<Result<HashMap<_, _>, Error>>()?;
+
+        Ok(Self {
+            count,
+            threshold,
+            members,
+            scope: CooperationScope::COOPERATION_SCOPE_AGENT,
+            verifying_keys: HashMap::new(),
+            transcript: None,
+            events: None,
+            breakers: None,
+        })
+    }
+
+    /// Topology of the regions, the members are the regional coordinators evaluating the blinded values with
+    /// the shares of their regions rather than with their own shards
+    pub fn with_regions(mut self) -> Self {
+        self.scope = CooperationScope::COOPERATION_SCOPE_REGION;
+        self
+    }
+
+    /// Requires the partial evaluations of the agents with the known keys to be signed, the evaluations without
+    /// the valid signature are refused the same as the failed ones
+    pub fn with_verifying_keys(mut self, keys: Vec<(usize, VerifyingKey)>) -> Self {
+        self.verifying_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// Records the signed partial evaluations, the evidence for the later disputes about the fingerprints
+    pub fn with_transcript(mut self, transcript: Arc<dyn PartialTranscript>) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// Quarantines the agents failing the evaluations in a row, see `CircuitBreakers`
+    pub fn with_breakers(mut self, breakers: Arc<CircuitBreakers>) -> Self {
+        self.breakers = Some(breakers);
+        self
+    }
+
+    /// Publishes the agents excluded by `check_versions`, see `EventBus`
+    pub fn with_events(mut self, events: Arc<dyn EventBus>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Checks the versions the agents report in the handshake, so the incompatible agents are found before
+    /// the first fingerprint. Agents of the incompatible versions are refused or excluded by the `policy`,
+    /// unreachable agents are kept since they could be upgraded by the time they are reachable.
+    /// Agents of the different builds are always refused, they compute the different fingerprints.
+    pub async fn check_versions(mut self, policy: IncompatibleAgentPolicy) -> Result<Self, Error> {
+        let mut incompatible = vec![];
+        let mut different_builds = vec![];
+        for (agent, clients) in &self.members {
+            for client in clients {
+                match GrpcAgentsTopology::handshake(client).await {
+                    Ok(Handshake::Compatible(version)) => log::info!(
+                        "Agent {} runs protocol version {} (software {})",
+                        agent,
+                        version.protocol,
+                        version.software
+                    ),
+                    Ok(Handshake::Incompatible(e)) => {
+                        incompatible.push((*agent, e));
+                        break;
+                    }
+                    Ok(Handshake::DifferentBuild(version)) => {
+                        different_builds.push((*agent, version.software));
+                        break;
+                    }
+                    Err(e) => log::warn!(
+                        "Version of agent {} is unknown, it is unreachable: {}",
+                        agent,
+                        e
+                    ),
+                }
+            }
+        }
+        if !different_builds.is_empty() {
+            different_builds.sort();
+            return Err(anyhow!(
+                "Agents are built with the different fingerprint parameters or messages (agent, software): {:?}",
+                different_builds
+            ));
+        }
+        if incompatible.is_empty() {
+            return Ok(self);
+        }
+
+        incompatible.sort_by_key(|(agent, _)| *agent);
+        let describe = incompatible
+            .iter()
+            .map(|(agent, e)| format!("agent {}: {}", agent, e))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if policy == IncompatibleAgentPolicy::Refuse {
+            return Err(anyhow!("Incompatible agents in the topology, {}", describe));
+        }
+
+        log::warn!(
+            "Excluding incompatible agents from the topology, {}",
+            describe
+        );
+        for (agent, e) in &incompatible {
+            self.members.remove(agent);
+            if let Some(events) = &self.events {
+                events.publish(LifecycleEvent::AgentExcluded {
+                    agent: *agent,
+                    reason: e.to_string(),
+                });
+            }
+        }
+        // Local agent is not a member
+        if self.members.len() + 1 < self.threshold {
+            return Err(anyhow!(
+                "Threshold {} is not reachable without the incompatible agents, {} agents are left",
+                self.threshold,
+                self.members.len() + 1
+            ));
+        }
+
+        Ok(self)
+    }
+
+    /// Exchanges the versions and the compatibility digests with the agent, fails for the unreachable agent
+    async fn handshake(client: &CooperationServiceClient) -> Result<Handshake, Error> {
+        let digest = compatibility_digest();
+        let info = match client
+            .get_agent_info(GetAgentInfoRequest {
+                compatibility_digest: Bytes::copy_from_slice(&digest),
+                _unknown_fields: Default::default(),
+            })
+            .await
+        {
+            Ok(info) => info.into_inner(),
+            Err(status) if status.code() == Code::Unimplemented => {
+                return Ok(Handshake::Incompatible(anyhow!(
+                    "Agent does not report its version, it predates protocol version {}",
+                    AGENT_PROTOCOL_VERSION
+                )))
+            }
+            Err(status) => return Err(status.into()),
+        };
+
+        let version = AgentVersion {
+            protocol: info.protocol_version,
+            software: info.software_version.to_string(),
+            wire_versions: info
+                .wire_versions
+                .into_iter()
+                .map(|version| u8::try_from(version).unwrap_or(u8::MAX))
+                .collect(),
+        };
+
+        Ok(match version.check_compatible() {
+            Err(e) => Handshake::Incompatible(e),
+            Ok(_) if info.compatibility_digest.as_ref() != digest => {
+                Handshake::DifferentBuild(version)
+            }
+            Ok(_) => Handshake::Compatible(version),
+        })
+    }
+
     fn build_client(
         remote_address: &String,
+        tls_config: Option<ClientTlsConfig>,
     ) -> Result<Vec<CooperationServiceClient>, anyhow::Error> {
         let clients = remote_address
             .to_socket_addrs()?
-            .map(|address| GrpcAgentsTopology::get_client(address))
+            .map(|addr| GrpcAgentsTopology::get_client(addr, tls_config.clone()))
             .collect::<Vec<_>>();
 
         Ok(clients)
     }
 
-    fn get_client(addr: SocketAddr) -> CooperationServiceClient {
-        crate::net::outbe::fingerprint::agent::v1::CooperationServiceClientBuilder::new(format!(
-            "inter-agent-coop-service-{}",
-            addr
-        ))
-        .address(Address::from(addr))
-        .build()
+    fn get_client(
+        addr: SocketAddr,
+        tls_config: Option<ClientTlsConfig>,
+    ) -> CooperationServiceClient {
+        let builder =
+            crate::net::outbe::fingerprint::agent::v1::CooperationServiceClientBuilder::new(
+                format!("inter-agent-coop-service-{}", addr),
+            )
+            .address(Address::from(addr));
+
+        match tls_config {
+            Some(tls_config) => builder.tls_config(tls_config).build(),
+            None => builder.build(),
+        }
     }
 }
 
+/// Outcome of the handshake with the reachable agent
+enum Handshake {
+    Compatible(AgentVersion),
+    /// Agent runs the incompatible version
+    Incompatible(Error),
+    /// Agent is built with the different fingerprint parameters or messages
+    DifferentBuild(AgentVersion),
+}
+
 impl AgentsTopology<Fr, G1> for GrpcAgentsTopology {
     fn count(&self) -> usize {
         self.count
@@ -63,7 +286,12 @@ impl AgentsTopology<Fr, G1> for GrpcAgentsTopology {
         self.threshold
     }
 
-    async fn obtain_shard(&self, agent: usize, generation: u64, blinded_value: G1) -> Result<(usize, G1), Error> {
+    async fn obtain_shard(
+        &self,
+        agent: usize,
+        generation: u64,
+        blinded_value: G1,
+    ) -> Result<(usize, G1), Error> {
         if agent == 0 || agent > self.count {
             return Err(anyhow::anyhow!(
                 "Invalid agent number, should be in range 1 to {}",
@@ -75,32 +303,310 @@ impl AgentsTopology<Fr, G1> for GrpcAgentsTopology {
             .members
             .get(&agent)
             .ok_or(anyhow::anyhow!("No clients for agent {}", agent))?;
+        let Some(breakers) = &self.breakers else {
+            return self
+                .request_shard(clients, agent, generation, blinded_value)
+                .await;
+        };
+
+        breakers.admit(agent)?;
+        let evaluation = self
+            .request_shard(clients, agent, generation, blinded_value)
+            .await;
+        match &evaluation {
+            Err(e) if e.downcast_ref::<AgentInMaintenance>().is_some() => {}
+            evaluation => breakers.record(agent, evaluation.is_ok()),
+        }
+
+        evaluation
+    }
+}
+
+impl GrpcAgentsTopology {
+    /// Asks one of the `clients` of the agent for its evaluation, verifying and recording the signed one
+    async fn request_shard(
+        &self,
+        clients: &[CooperationServiceClient],
+        agent: usize,
+        generation: u64,
+        blinded_value: G1,
+    ) -> Result<(usize, G1), Error> {
         let client = rand::thread_rng().gen_range(0..clients.len());
         let client = &clients[client];
 
-        let bytes = blinded_value.to_bytes();
+        let bytes = wire::encode_point(&blinded_value);
+        let request_id = rand::random::<[u8; REQUEST_ID_SIZE]>();
 
-        let exponent = client
+        let response = client
             .compute_exponent(CooperationRequest {
                 generation,
+                protocol_version: AGENT_PROTOCOL_VERSION,
+                scope: self.scope,
+                request_id: Bytes::copy_from_slice(&request_id),
                 blinded_value: Bytes::copy_from_slice(bytes.as_ref()),
                 _unknown_fields: Default::default(),
             })
-            .await?;
+            .await
+            .map_err(|status| -> Error {
+                // Agent draining for the restart is excluded from the round without being counted as failed
+                if is_maintenance_refusal(&status) {
+                    AgentInMaintenance { agent }.into()
+                } else {
+                    status.into()
+                }
+            })?
+            .into_inner();
 
-        let exponent = exponent.into_inner().blinded_exponent;
-        let mut exponent_point = G1Compressed::default();
+        if !response.signature.is_empty() || self.verifying_keys.contains_key(&agent) {
+            let partial = SignedPartial {
+                agent,
+                request_id: request_id.to_vec(),
+                generation,
+                blinded_value: bytes.as_ref().to_vec(),
+                evaluation: response.blinded_exponent.to_vec(),
+                signature: response.signature.to_vec(),
+            };
+            if let Some(key) = self.verifying_keys.get(&agent) {
+                if partial.signature.is_empty() {
+                    return Err(anyhow!("Agent {} did not sign its evaluation", agent));
+                }
+                partial.verify(key)?;
+            }
+            if let Some(transcript) = &self.transcript {
+                transcript.record(&partial)?;
+            }
+        }
 
-        // todo verify that received bytes are exactly 32 bytes
-        exponent_point.as_mut().copy_from_slice(exponent.as_ref());
-        let exponent_point =
-            G1::from_bytes(&exponent_point)
-                .into_option()
-                .ok_or(anyhow::anyhow!(
-                    "Invalid exponent point, agent {} returned wrong value",
-                    agent
-                ))?;
+        let exponent = response.blinded_exponent;
+        let exponent_point = wire::decode_point(exponent.as_ref()).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid exponent point, agent {} returned wrong value: {}",
+                agent,
+                e
+            )
+        })?;
 
         Ok((agent, exponent_point))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::outbe::fingerprint::agent::v1::{
+        CooperationResponse, CooperationService, CooperationServiceServer, GetAgentInfoResponse,
+    };
+    use crate::CooperationAgentService;
+    use fingerprinting_core::breaker::AgentQuarantined;
+    use fingerprinting_core::events::BroadcastEventBus;
+    use volo_grpc::server::{Server, ServiceBuilder};
+    use volo_grpc::{Request, Response, Status};
+
+    /// Agent reporting the given protocol version and compatibility digest
+    struct FakeAgentService {
+        protocol_version: u32,
+        compatibility_digest: [u8; 32],
+    }
+
+    impl CooperationService for FakeAgentService {
+        async fn compute_exponent(
+            &self,
+            _: Request<CooperationRequest>,
+        ) -> Result<Response<CooperationResponse>, Status> {
+            Err(Status::new(Code::Unavailable, "Not expected"))
+        }
+
+        async fn get_agent_info(
+            &self,
+            _: Request<GetAgentInfoRequest>,
+        ) -> Result<Response<GetAgentInfoResponse>, Status> {
+            Ok(Response::new(GetAgentInfoResponse {
+                protocol_version: self.protocol_version,
+                software_version: "99.0.0".into(),
+                wire_versions: vec![wire::WireVersion::CURRENT.as_u8() as u32],
+                compatibility_digest: Bytes::copy_from_slice(&self.compatibility_digest),
+                _unknown_fields: Default::default(),
+            }))
+        }
+    }
+
+    fn serve<S: CooperationService + Send + Sync + 'static>(service: S) -> Result<String, Error> {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        tokio::spawn(
+            Server::new()
+                .add_service(ServiceBuilder::new(CooperationServiceServer::new(service)).build())
+                .run(Address::from(addr)),
+        );
+
+        Ok(addr.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_version_check() -> Result<(), Error> {
+        let current = serve(CooperationAgentService::new(Fr::from(42)))?;
+        let newer = serve(FakeAgentService {
+            protocol_version: AGENT_PROTOCOL_VERSION + 1,
+            compatibility_digest: compatibility_digest(),
+        })?;
+        let different_build = serve(FakeAgentService {
+            protocol_version: AGENT_PROTOCOL_VERSION,
+            compatibility_digest: [0u8; 32],
+        })?;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let members = || vec![(2, current.clone()), (3, newer.clone())];
+
+        let refused = GrpcAgentsTopology::new(3, 2, members())
+            .check_versions(IncompatibleAgentPolicy::Refuse)
+            .await;
+        assert!(refused.err().unwrap().to_string().contains("agent 3"));
+
+        let events = Arc::new(BroadcastEventBus::default());
+        let mut excluded = events.subscribe();
+        let topology = GrpcAgentsTopology::new(3, 2, members())
+            .with_events(events.clone())
+            .check_versions(IncompatibleAgentPolicy::Exclude)
+            .await?;
+        assert!(topology.members.contains_key(&2));
+        assert!(!topology.members.contains_key(&3));
+        assert!(matches!(
+            excluded.try_recv()?,
+            LifecycleEvent::AgentExcluded { agent: 3, .. }
+        ));
+        assert!(excluded.try_recv().is_err());
+
+        // Threshold is not reachable by the local and the compatible agents
+        assert!(GrpcAgentsTopology::new(3, 3, members())
+            .check_versions(IncompatibleAgentPolicy::Exclude)
+            .await
+            .is_err());
+
+        // Different build is refused whatever the policy is
+        let refused =
+            GrpcAgentsTopology::new(3, 2, vec![(2, current.clone()), (3, different_build)])
+                .check_versions(IncompatibleAgentPolicy::Exclude)
+                .await;
+        assert!(refused
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("different fingerprint parameters"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agent_in_maintenance() -> Result<(), Error> {
+        let maintenance = Arc::new(crate::Maintenance::default());
+        let agent = serve(
+            CooperationAgentService::new(Fr::from(42)).with_maintenance(maintenance.clone()),
+        )?;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let topology = GrpcAgentsTopology::new(3, 2, vec![(2, agent)]);
+        let point = G1::generator();
+        maintenance.enter();
+        let excluded = topology.obtain_shard(2, 0, point).await.unwrap_err();
+        assert_eq!(
+            excluded.downcast_ref::<AgentInMaintenance>(),
+            Some(&AgentInMaintenance { agent: 2 })
+        );
+
+        maintenance.leave();
+        assert_eq!(
+            topology.obtain_shard(2, 0, point).await?,
+            (2, point * Fr::from(42))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quarantined_agent() -> Result<(), Error> {
+        let maintenance = Arc::new(crate::Maintenance::default());
+        let agent = serve(
+            CooperationAgentService::new(Fr::from(42)).with_maintenance(maintenance.clone()),
+        )?;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let breakers = Arc::new(CircuitBreakers::new(1, chrono::Duration::minutes(5)));
+        let point = G1::generator();
+
+        // Maintenance is not the failure of the agent
+        let topology =
+            GrpcAgentsTopology::new(3, 2, vec![(2, agent.clone())]).with_breakers(breakers.clone());
+        maintenance.enter();
+        assert!(topology.obtain_shard(2, 0, point).await.is_err());
+        assert!(breakers.admit(2).is_ok());
+        maintenance.leave();
+
+        // Agent expected to sign does not
+        let key = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let topology = GrpcAgentsTopology::new(3, 2, vec![(2, agent)])
+            .with_verifying_keys(vec![(2, key.verifying_key())])
+            .with_breakers(breakers.clone());
+        assert!(topology.obtain_shard(2, 0, point).await.is_err());
+        let refused = topology.obtain_shard(2, 0, point).await.unwrap_err();
+        assert!(refused.downcast_ref::<AgentQuarantined>().is_some());
+
+        Ok(())
+    }
+
+    /// Transcript kept in memory
+    #[derive(Default)]
+    struct Recorded(std::sync::Mutex<Vec<SignedPartial>>);
+
+    impl PartialTranscript for Recorded {
+        fn record(&self, partial: &SignedPartial) -> Result<(), Error> {
+            self.0.lock().unwrap().push(partial.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_evaluations() -> Result<(), Error> {
+        let key = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let signing =
+            serve(CooperationAgentService::new(Fr::from(42)).with_signing_key(key.clone()))?;
+        let unsigned = serve(CooperationAgentService::new(Fr::from(43)))?;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let transcript = Arc::new(Recorded::default());
+        let topology =
+            GrpcAgentsTopology::new(3, 2, vec![(2, signing.clone()), (3, unsigned.clone())])
+                .with_verifying_keys(vec![(2, key.verifying_key()), (3, key.verifying_key())])
+                .with_transcript(transcript.clone());
+
+        let point = G1::generator();
+        assert_eq!(
+            topology.obtain_shard(2, 0, point).await?,
+            (2, point * Fr::from(42))
+        );
+        let recorded = transcript.0.lock().unwrap().clone();
+        assert_eq!(recorded.len(), 1);
+        recorded[0].verify(&key.verifying_key())?;
+        assert_eq!(
+            recorded[0].evaluation,
+            wire::encode_point(&(point * Fr::from(42))).to_vec()
+        );
+
+        // Agent expected to sign does not
+        let refused = topology.obtain_shard(3, 0, point).await.unwrap_err();
+        assert!(refused.to_string().contains("did not sign"));
+
+        // Signature by another key
+        let other = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let topology = GrpcAgentsTopology::new(3, 2, vec![(2, signing)])
+            .with_verifying_keys(vec![(2, other.verifying_key())]);
+        assert!(topology.obtain_shard(2, 0, point).await.is_err());
+
+        // Unsigned agents are accepted while their keys are unknown
+        let topology = GrpcAgentsTopology::new(3, 2, vec![(3, unsigned)]);
+        assert_eq!(
+            topology.obtain_shard(3, 0, point).await?,
+            (3, point * Fr::from(43))
+        );
+
+        Ok(())
+    }
+}
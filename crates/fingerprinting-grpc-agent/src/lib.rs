@@ -1,4 +1,10 @@
 mod agents_topology;
+mod limits;
+mod maintenance;
+pub mod relay;
+pub mod signature;
+mod svid;
+mod tls;
 
 // hide generated values in private module
 mod generator {
@@ -6,66 +12,519 @@ mod generator {
 }
 pub use agents_topology::GrpcAgentsTopology;
 pub use generator::proto_gen::*;
+pub use limits::EvaluationLimits;
+pub use maintenance::{
+    is_maintenance_refusal, AgentAdmin, AgentHealth, Maintenance, MAINTENANCE_CODE,
+    MAINTENANCE_METADATA_KEY,
+};
+pub use relay::{PipeSigner, ShareSigner};
+pub use svid::{peer_spiffe_id, SpiffeId, SpiffeSource, X509Context};
+pub use tls::AgentClientTls;
 
-use halo2_axiom::halo2curves::bn256::{Fr, G1Compressed, G1};
-use halo2_axiom::halo2curves::group::GroupEncoding;
+use ed25519_dalek::SigningKey;
+use fingerprinting_core::version::{self, AgentVersion, MIN_AGENT_PROTOCOL_VERSION};
+use fingerprinting_core::wire;
+use futures::future::BoxFuture;
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use lru::LruCache;
+use pilota::pb::PbMessage;
 use pilota::Bytes;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, PoisonError};
 use volo_grpc::{Code, Request, Response, Status};
 
-use net::outbe::fingerprint::agent::v1::{CooperationRequest, CooperationResponse};
+use net::outbe::fingerprint::agent::v1::{
+    CooperationRequest, CooperationResponse, CooperationScope, GetAgentInfoRequest,
+    GetAgentInfoResponse,
+};
+
+/// Evaluation of the blinded point with the share of the agent region, see `CooperationAgentService::with_region`
+pub type RegionalEvaluation =
+    Arc<dyn Fn(G1) -> BoxFuture<'static, Result<G1, anyhow::Error>> + Send + Sync>;
+
+/// Digest of the fingerprint parameters and the cooperation messages of this build, see `version::compatibility_digest`
+pub fn compatibility_digest() -> [u8; 32] {
+    let descriptor =
+        net::outbe::fingerprint::agent::v1::file_descriptor_proto_cooperation_service()
+            .write_to_bytes()
+            .expect("Compiled file descriptor is encodable");
+
+    version::compatibility_digest(&[descriptor])
+}
+
+/// Share the partial evaluations are computed with
+enum AgentShare {
+    Held(Fr),
+    /// Held by the downstream signer, see `relay`
+    Relayed(Arc<dyn ShareSigner>),
+}
 
 pub struct CooperationAgentService {
-    agent_secret_shard: Fr,
+    share: AgentShare,
+    exponents: Option<Mutex<LruCache<(u64, Bytes), Bytes>>>,
+    regional: Option<RegionalEvaluation>,
+    signing_key: Option<SigningKey>,
+    limits: Option<EvaluationLimits>,
+    maintenance: Option<Arc<Maintenance>>,
 }
 
 impl CooperationAgentService {
     pub fn new(secret_shard: Fr) -> CooperationAgentService {
+        Self::with_share(AgentShare::Held(secret_shard))
+    }
+
+    /// Agent holding no share, the partial evaluations are relayed to the `signer` holding it, see `relay`
+    pub fn relay(signer: Arc<dyn ShareSigner>) -> CooperationAgentService {
+        Self::with_share(AgentShare::Relayed(signer))
+    }
+
+    fn with_share(share: AgentShare) -> CooperationAgentService {
         CooperationAgentService {
-            agent_secret_shard: secret_shard,
+            share,
+            exponents: None,
+            regional: None,
+            signing_key: None,
+            limits: None,
+            maintenance: None,
+        }
+    }
+
+    /// Signs the partial evaluations, so the coordinators could attribute them to this agent, see `signature`
+    pub fn with_signing_key(mut self, key: SigningKey) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// Serves the coordinators of the other regions as the regional coordinator, their requests are evaluated
+    /// with the share of the region by the threshold of the region agents
+    pub fn with_region(mut self, regional: RegionalEvaluation) -> Self {
+        self.regional = Some(regional);
+        self
+    }
+
+    /// Keeps the partial evaluations of the last `capacity` blinded points, so the coordinator retries of the same
+    /// point skip the scalar multiplication.
+    ///
+    /// The cached response is exactly the one the multiplication gives, so the cache neither weakens nor replaces the
+    /// replay protection: a replayed point gets the same evaluation either way, and the entries are keyed by the
+    /// generation too, the evaluations of one secret generation are never returned for another one.
+    /// Points are cached blinded only, the same as they are seen on the wire.
+    pub fn with_cache(mut self, capacity: NonZeroUsize) -> Self {
+        self.exponents = Some(Mutex::new(LruCache::new(capacity)));
+        self
+    }
+
+    /// Bounds the evaluations served at once and waiting for them, the ones beyond are refused with
+    /// `RESOURCE_EXHAUSTED`, see `EvaluationLimits`. Cached evaluations are served regardless
+    pub fn with_limits(mut self, limits: EvaluationLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Refuses the new evaluations with `MAINTENANCE_CODE` once the agent enters the `maintenance`,
+    /// the ones in flight are finished
+    pub fn with_maintenance(mut self, maintenance: Arc<Maintenance>) -> Self {
+        self.maintenance = Some(maintenance);
+        self
+    }
+
+    fn cached_exponent(&self, key: &(u64, Bytes)) -> Option<Bytes> {
+        let exponents = self.exponents.as_ref()?;
+        let mut exponents = exponents.lock().unwrap_or_else(PoisonError::into_inner);
+        exponents.get(key).cloned()
+    }
+
+    fn cache_exponent(&self, key: (u64, Bytes), exponent: Bytes) {
+        if let Some(exponents) = &self.exponents {
+            let mut exponents = exponents.lock().unwrap_or_else(PoisonError::into_inner);
+            exponents.put(key, exponent);
         }
     }
 }
 
+impl CooperationAgentService {
+    /// Slot of the evaluation when the limits are configured
+    async fn admit(&self) -> Result<Option<tokio::sync::OwnedSemaphorePermit>, Status> {
+        match &self.limits {
+            Some(limits) => limits.admit().await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Response with the evaluation, signed when the agent has the signing key
+    fn respond(
+        &self,
+        request_id: &[u8],
+        generation: u64,
+        blinded_value: &[u8],
+        blinded_exponent: Bytes,
+    ) -> Response<CooperationResponse> {
+        let signature = self
+            .signing_key
+            .as_ref()
+            .map(|key| {
+                let signature = signature::sign_partial(
+                    key,
+                    request_id,
+                    generation,
+                    blinded_value,
+                    &blinded_exponent,
+                );
+                Bytes::copy_from_slice(&signature)
+            })
+            .unwrap_or_default();
+
+        Response::new(CooperationResponse {
+            generation,
+            blinded_exponent,
+            proof_of_computation: Default::default(),
+            signature,
+            _unknown_fields: Default::default(),
+        })
+    }
+
+    /// Evaluation of the blinded point with the share of this agent
+    async fn evaluate(&self, generation: u64, b_point: G1) -> Result<G1, Status> {
+        match &self.share {
+            AgentShare::Held(secret_shard) => Ok(b_point * secret_shard),
+            AgentShare::Relayed(signer) => {
+                signer.evaluate(generation, b_point).await.map_err(|e| {
+                    log::warn!("Signer has not evaluated the point: {}", e);
+                    Status::new(
+                        Code::Unavailable,
+                        format!("Signer evaluation failed: {}", e),
+                    )
+                })
+            }
+        }
+    }
+
+    async fn compute_regional_exponent(
+        &self,
+        request_id: &[u8],
+        generation: u64,
+        blinded_value: Bytes,
+    ) -> Result<Response<CooperationResponse>, Status> {
+        let regional = self.regional.as_ref().ok_or_else(|| {
+            Status::new(
+                Code::FailedPrecondition,
+                "Agent is not the regional coordinator",
+            )
+        })?;
+        let _slot = self.admit().await?;
+
+        let b_point = wire::decode_point(blinded_value.as_ref()).map_err(invalid_blinded_value)?;
+        let exponent = regional(b_point).await.map_err(|e| {
+            Status::new(
+                Code::Unavailable,
+                format!("Regional evaluation failed: {}", e),
+            )
+        })?;
+
+        Ok(self.respond(
+            request_id,
+            generation,
+            &blinded_value,
+            Bytes::copy_from_slice(wire::encode_point(&exponent).as_ref()),
+        ))
+    }
+}
+
+fn invalid_blinded_value(e: anyhow::Error) -> Status {
+    Status::new(
+        Code::InvalidArgument,
+        format!("Invalid blinded value: {}", e),
+    )
+}
+
 impl net::outbe::fingerprint::agent::v1::CooperationService for CooperationAgentService {
     async fn compute_exponent(
         &self,
         req: Request<CooperationRequest>,
     ) -> Result<Response<CooperationResponse>, Status> {
+        let _in_flight = self
+            .maintenance
+            .as_ref()
+            .map(Maintenance::admit)
+            .transpose()
+            .map_err(|status| *status)?;
         let request = req.into_inner();
         let blinded_value = request.blinded_value;
         let generation = request.generation;
 
-        if generation != 0 {
+        // Coordinators before the versioning do not send the version
+        if request.protocol_version != 0 && request.protocol_version < MIN_AGENT_PROTOCOL_VERSION {
             return Err(Status::new(
-                Code::InvalidArgument,
-                "Current implementation doesn't support secret generations",
+                Code::FailedPrecondition,
+                format!(
+                    "Coordinator protocol version {} is not supported, should be at least {}",
+                    request.protocol_version, MIN_AGENT_PROTOCOL_VERSION
+                ),
             ));
         }
 
-        if blinded_value.len() != 32 {
+        if generation != 0 {
             return Err(Status::new(
                 Code::InvalidArgument,
-                "Invalid blinded value, it should be exactly 32 bytes long",
+                "Current implementation doesn't support secret generations",
             ));
         }
-        let mut point = G1Compressed::default();
-        point.as_mut().copy_from_slice(blinded_value.as_ref());
 
-        let b_point = G1::from_bytes(&point).into_option().ok_or(Status::new(
-            Code::InvalidArgument,
-            "Invalid blinded value, it should be a valid G1 point",
-        ))?;
+        match request.scope {
+            CooperationScope::COOPERATION_SCOPE_AGENT => {}
+            CooperationScope::COOPERATION_SCOPE_REGION => {
+                return self
+                    .compute_regional_exponent(&request.request_id, generation, blinded_value)
+                    .await
+            }
+            scope => {
+                return Err(Status::new(
+                    Code::InvalidArgument,
+                    format!("Unknown cooperation scope {}", i32::from(scope)),
+                ))
+            }
+        }
+
+        let key = (generation, blinded_value);
+        if let Some(blinded_exponent) = self.cached_exponent(&key) {
+            return Ok(self.respond(&request.request_id, generation, &key.1, blinded_exponent));
+        }
 
-        let exponent = b_point * self.agent_secret_shard;
-        let exponent_bytes = exponent.to_bytes();
+        let b_point = wire::decode_point(key.1.as_ref()).map_err(invalid_blinded_value)?;
 
-        let response = CooperationResponse {
+        let slot = self.admit().await?;
+        let exponent = self.evaluate(generation, b_point).await?;
+        drop(slot);
+        let exponent_bytes = wire::encode_point(&exponent);
+        let blinded_exponent = Bytes::copy_from_slice(exponent_bytes.as_ref());
+        let response = self.respond(
+            &request.request_id,
             generation,
-            blinded_exponent: Bytes::copy_from_slice(exponent_bytes.as_ref()),
-            proof_of_computation: Default::default(),
+            &key.1,
+            blinded_exponent.clone(),
+        );
+        self.cache_exponent(key, blinded_exponent);
+
+        Ok(response)
+    }
+
+    async fn get_agent_info(
+        &self,
+        req: Request<GetAgentInfoRequest>,
+    ) -> Result<Response<GetAgentInfoResponse>, Status> {
+        let version = AgentVersion::current();
+        let digest = compatibility_digest();
+        // Coordinator refuses the agent itself, the mismatch is reported on both sides for the diagnostics
+        if req.into_inner().compatibility_digest.as_ref() != digest {
+            log::error!(
+                "Coordinator is built with the different fingerprint parameters or messages"
+            );
+        }
+
+        Ok(Response::new(GetAgentInfoResponse {
+            protocol_version: version.protocol,
+            software_version: version.software.into(),
+            wire_versions: version.wire_versions.into_iter().map(u32::from).collect(),
+            compatibility_digest: Bytes::copy_from_slice(&digest),
+            _unknown_fields: Default::default(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_axiom::halo2curves::bn256::G1;
+    use halo2_axiom::halo2curves::group::Group;
+    use net::outbe::fingerprint::agent::v1::CooperationService as _;
+
+    fn request(generation: u64, point: &G1) -> Request<CooperationRequest> {
+        Request::new(CooperationRequest {
+            generation,
+            protocol_version: version::AGENT_PROTOCOL_VERSION,
+            scope: CooperationScope::COOPERATION_SCOPE_AGENT,
+            request_id: Bytes::from_static(&[1; signature::REQUEST_ID_SIZE]),
+            blinded_value: Bytes::copy_from_slice(wire::encode_point(point).as_ref()),
             _unknown_fields: Default::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_cached_exponents() -> Result<(), anyhow::Error> {
+        let service =
+            CooperationAgentService::new(Fr::from(42)).with_cache(NonZeroUsize::new(2).unwrap());
+        let points = [G1::generator(), G1::generator().double(), -G1::generator()];
+
+        let computed = service
+            .compute_exponent(request(0, &points[0]))
+            .await?
+            .into_inner();
+        let retried = service
+            .compute_exponent(request(0, &points[0]))
+            .await?
+            .into_inner();
+        assert_eq!(computed.blinded_exponent, retried.blinded_exponent);
+        assert_eq!(
+            computed.blinded_exponent.as_ref(),
+            wire::encode_point(&(points[0] * Fr::from(42))).as_ref()
+        );
+
+        // Least recently used point is evicted
+        service.compute_exponent(request(0, &points[1])).await?;
+        service.compute_exponent(request(0, &points[2])).await?;
+        let key = |point: &G1| {
+            (
+                0,
+                Bytes::copy_from_slice(wire::encode_point(point).as_ref()),
+            )
+        };
+        assert!(service.cached_exponent(&key(&points[0])).is_none());
+        assert!(service.cached_exponent(&key(&points[2])).is_some());
+
+        // Unsupported generations are refused before the cache is consulted
+        assert!(service
+            .compute_exponent(request(1, &points[2]))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_limits() -> Result<(), anyhow::Error> {
+        let service = CooperationAgentService::new(Fr::from(42))
+            .with_cache(NonZeroUsize::new(2).unwrap())
+            .with_limits(EvaluationLimits::new(NonZeroUsize::new(1).unwrap(), 0));
+        let points = [G1::generator(), G1::generator().double()];
+        service.compute_exponent(request(0, &points[0])).await?;
+
+        // Evaluation beyond the capacity is refused, the cached one is still served
+        let running = service.limits.as_ref().unwrap().admit().await?;
+        let refused = service
+            .compute_exponent(request(0, &points[1]))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(refused.code(), Code::ResourceExhausted);
+        service.compute_exponent(request(0, &points[0])).await?;
+
+        drop(running);
+        service.compute_exponent(request(0, &points[1])).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_regional_exponents() -> Result<(), anyhow::Error> {
+        let point = G1::generator();
+        let regional = |point: G1| {
+            let mut request = request(0, &point);
+            request.get_mut().scope = CooperationScope::COOPERATION_SCOPE_REGION;
+            request
         };
 
-        Ok(Response::new(response))
+        let service = CooperationAgentService::new(Fr::from(42));
+        let refused = service
+            .compute_exponent(regional(point))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(refused.code(), Code::FailedPrecondition);
+
+        // Regional coordinator evaluates with the share of the region, not with its own shard
+        let service = service.with_region(Arc::new(|point: G1| {
+            Box::pin(async move { Ok(point * Fr::from(7)) })
+        }));
+        let evaluated = service
+            .compute_exponent(regional(point))
+            .await?
+            .into_inner();
+        assert_eq!(
+            evaluated.blinded_exponent.as_ref(),
+            wire::encode_point(&(point * Fr::from(7))).as_ref()
+        );
+        let evaluated = service
+            .compute_exponent(request(0, &point))
+            .await?
+            .into_inner();
+        assert_eq!(
+            evaluated.blinded_exponent.as_ref(),
+            wire::encode_point(&(point * Fr::from(42))).as_ref()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_maintenance() -> Result<(), anyhow::Error> {
+        let maintenance = Arc::new(Maintenance::default());
+        let service = CooperationAgentService::new(Fr::from(42))
+            .with_cache(NonZeroUsize::new(2).unwrap())
+            .with_maintenance(maintenance.clone());
+        let point = G1::generator();
+        service.compute_exponent(request(0, &point)).await?;
+
+        // Even the cached evaluation is refused, the coordinator is to route around the agent
+        maintenance.enter();
+        let refused = service
+            .compute_exponent(request(0, &point))
+            .await
+            .err()
+            .unwrap();
+        assert!(is_maintenance_refusal(&refused));
+        assert_eq!(maintenance.in_flight(), 0);
+
+        maintenance.leave();
+        service.compute_exponent(request(0, &point)).await?;
+
+        Ok(())
+    }
+
+    /// Signer holding the share in memory, unreachable without it
+    struct MemorySigner(Option<Fr>);
+
+    impl ShareSigner for MemorySigner {
+        fn evaluate(&self, _: u64, blinded_value: G1) -> BoxFuture<'_, Result<G1, anyhow::Error>> {
+            let evaluation = self
+                .0
+                .map(|share| blinded_value * share)
+                .ok_or_else(|| anyhow::anyhow!("Signer is unreachable"));
+            Box::pin(async move { evaluation })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relayed_exponents() -> Result<(), anyhow::Error> {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let point = G1::generator().double();
+        let held = CooperationAgentService::new(Fr::from(42)).with_signing_key(key.clone());
+        let relay = CooperationAgentService::relay(Arc::new(MemorySigner(Some(Fr::from(42)))))
+            .with_signing_key(key)
+            .with_cache(NonZeroUsize::new(2).unwrap());
+
+        // Coordinator gets the same signed evaluation from the relay as from the agent holding the share
+        let expected = held
+            .compute_exponent(request(0, &point))
+            .await?
+            .into_inner();
+        let relayed = relay
+            .compute_exponent(request(0, &point))
+            .await?
+            .into_inner();
+        assert_eq!(relayed.blinded_exponent, expected.blinded_exponent);
+        assert_eq!(relayed.signature, expected.signature);
+
+        // Cached evaluation is served without the signer, the failed one is retried with the other agents
+        let cached = relay.compute_exponent(request(0, &point)).await?;
+        assert_eq!(cached.get_ref().blinded_exponent, expected.blinded_exponent);
+        let unreachable = CooperationAgentService::relay(Arc::new(MemorySigner(None)));
+        let refused = unreachable
+            .compute_exponent(request(0, &point))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(refused.code(), Code::Unavailable);
+
+        Ok(())
     }
 }
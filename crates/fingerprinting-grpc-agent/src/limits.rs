@@ -0,0 +1,100 @@
+//! Limits of the partial evaluations served by the agent at once
+//!
+//! Evaluations run in the pool of the limited number of slots, the ones above it wait in the queue. Once the queue
+//! depth reaches the limit the evaluations are refused with `RESOURCE_EXHAUSTED`, so a burst from the coordinator
+//! is failed fast rather than driving the small agent host into swap. The coordinator takes the refused agent
+//! as the failed one and completes the round with the other agents of the threshold.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use volo_grpc::{Code, Status};
+
+pub struct EvaluationLimits {
+    slots: Arc<Semaphore>,
+    queued: AtomicUsize,
+    max_queue: usize,
+    rejected: AtomicU64,
+}
+
+impl EvaluationLimits {
+    /// Runs up to `concurrency` evaluations at once, the evaluations are refused when `max_queue` ones are waiting
+    pub fn new(concurrency: NonZeroUsize, max_queue: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(concurrency.get())),
+            queued: AtomicUsize::new(0),
+            max_queue,
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of the evaluations refused since the start
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Slot of the evaluation, released when the permit is dropped
+    pub(crate) async fn admit(&self) -> Result<OwnedSemaphorePermit, Status> {
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let depth = self.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        let _queued = Queued(&self.queued);
+        if depth > self.max_queue {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            log::debug!("Evaluation is refused, {} are queued", depth - 1);
+            return Err(Status::new(
+                Code::ResourceExhausted,
+                "Agent is saturated, evaluate the point via the other agents",
+            ));
+        }
+
+        self.slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Status::new(Code::Unavailable, "Agent is shutting down"))
+    }
+}
+
+/// Place in the queue, left when the waiting evaluation is either admitted or cancelled
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_evaluation_limits() -> Result<(), anyhow::Error> {
+        let limits = Arc::new(EvaluationLimits::new(NonZeroUsize::new(1).unwrap(), 1));
+        let running = limits.admit().await?;
+
+        let waiting = tokio::spawn({
+            let limits = limits.clone();
+            async move { limits.admit().await.map(drop) }
+        });
+        tokio::task::yield_now().await;
+
+        let refused = limits.admit().await.err().unwrap();
+        assert_eq!(refused.code(), Code::ResourceExhausted);
+        assert_eq!(limits.rejected(), 1);
+        assert_eq!(limits.queued.load(Ordering::Relaxed), 1);
+
+        // Waiting evaluation is admitted once the slot is released and leaves the queue
+        assert!(!waiting.is_finished());
+        drop(running);
+        waiting.await??;
+        assert_eq!(limits.queued.load(Ordering::Relaxed), 0);
+        drop(limits.admit().await?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+//! Maintenance mode of the agent, for the rolling restarts of the shard holders
+//!
+//! Once the agent enters the maintenance, its health is reported `NOT_SERVING`, the evaluations in flight are
+//! finished and the new ones are refused with `MAINTENANCE_CODE`, marked by `MAINTENANCE_METADATA_KEY`. Coordinators take the refused agent as the one in
+//! maintenance rather than the failed one, see `fingerprinting_core::AgentInMaintenance`, and complete the round with
+//! the other agents of the threshold. The maintenance is entered via `AgentAdminService`, served on the admin address.
+
+use crate::grpc::health::v1::health_check_response::ServingStatus;
+use crate::grpc::health::v1::{HealthCheckRequest, HealthCheckResponse};
+use crate::net::outbe::fingerprint::agent::v1::{SetMaintenanceRequest, SetMaintenanceResponse};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{watch, Notify};
+use volo_grpc::metadata::MetadataValue;
+use volo_grpc::{Code, Request, Response, Status};
+
+/// Code the evaluations are refused with during the maintenance
+pub const MAINTENANCE_CODE: Code = Code::Aborted;
+
+/// Metadata key marking the refusal as the maintenance one, `ABORTED` alone is returned by the proxies too
+pub const MAINTENANCE_METADATA_KEY: &str = "x-agent-maintenance";
+
+/// Whether the `status` is the refusal of the agent in maintenance
+pub fn is_maintenance_refusal(status: &Status) -> bool {
+    status.code() == MAINTENANCE_CODE && status.metadata().contains_key(MAINTENANCE_METADATA_KEY)
+}
+
+/// Name of the cooperation service in the health checks, the empty name is the agent as a whole
+const COOPERATION_SERVICE: &str = "net.outbe.fingerprint.agent.v1.CooperationService";
+
+pub struct Maintenance {
+    active: AtomicBool,
+    in_flight: AtomicUsize,
+    drained: Notify,
+    serving: watch::Sender<bool>,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            active: AtomicBool::new(false),
+            in_flight: AtomicUsize::new(0),
+            drained: Notify::new(),
+            serving: watch::Sender::new(true),
+        }
+    }
+}
+
+impl Maintenance {
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Number of the evaluations admitted and not finished yet
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Refuses the new evaluations and reports `NOT_SERVING`, the evaluations in flight are finished
+    pub fn enter(&self) {
+        self.active.store(true, Ordering::SeqCst);
+        self.serving.send_replace(false);
+        log::info!(
+            "Agent entered the maintenance, {} evaluations in flight",
+            self.in_flight()
+        );
+    }
+
+    pub fn leave(&self) {
+        self.active.store(false, Ordering::SeqCst);
+        self.serving.send_replace(true);
+        log::info!("Agent left the maintenance");
+    }
+
+    /// Completes once no evaluations are in flight
+    pub async fn drained(&self) {
+        loop {
+            let mut notified = pin!(self.drained.notified());
+            notified.as_mut().enable();
+            if self.in_flight() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Evaluation in flight until the guard is dropped, refused during the maintenance
+    pub(crate) fn admit(self: &Arc<Self>) -> Result<InFlight, Box<Status>> {
+        // Counted before the check, so the evaluation admitted concurrently with `enter` is waited for
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let in_flight = InFlight(self.clone());
+        if self.is_active() {
+            let mut refused = Status::new(
+                MAINTENANCE_CODE,
+                "Agent is in maintenance, evaluate the point via the other agents",
+            );
+            refused
+                .metadata_mut()
+                .insert(MAINTENANCE_METADATA_KEY, MetadataValue::from_static("true"));
+            return Err(Box::new(refused));
+        }
+
+        Ok(in_flight)
+    }
+
+    fn status(&self) -> ServingStatus {
+        serving_status(*self.serving.borrow())
+    }
+}
+
+fn serving_status(serving: bool) -> ServingStatus {
+    if serving {
+        ServingStatus::SERVING
+    } else {
+        ServingStatus::NOT_SERVING
+    }
+}
+
+/// Admitted evaluation, see `Maintenance::admit`
+pub(crate) struct InFlight(Arc<Maintenance>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.drained.notify_waiters();
+        }
+    }
+}
+
+/// Admin calls of the agent, served on the admin address only
+pub struct AgentAdmin {
+    maintenance: Arc<Maintenance>,
+}
+
+impl AgentAdmin {
+    pub fn new(maintenance: Arc<Maintenance>) -> Self {
+        Self { maintenance }
+    }
+}
+
+impl crate::net::outbe::fingerprint::agent::v1::AgentAdminService for AgentAdmin {
+    async fn set_maintenance(
+        &self,
+        req: Request<SetMaintenanceRequest>,
+    ) -> Result<Response<SetMaintenanceResponse>, Status> {
+        if req.into_inner().maintenance {
+            self.maintenance.enter();
+            self.maintenance.drained().await;
+        } else {
+            self.maintenance.leave();
+        }
+
+        Ok(Response::new(SetMaintenanceResponse {
+            maintenance: self.maintenance.is_active(),
+            in_flight: self.maintenance.in_flight() as u64,
+            _unknown_fields: Default::default(),
+        }))
+    }
+}
+
+/// `grpc.health.v1.Health` of the agent, `NOT_SERVING` during the maintenance
+pub struct AgentHealth {
+    maintenance: Arc<Maintenance>,
+}
+
+impl AgentHealth {
+    pub fn new(maintenance: Arc<Maintenance>) -> Self {
+        Self { maintenance }
+    }
+}
+
+fn health_response(status: ServingStatus) -> HealthCheckResponse {
+    HealthCheckResponse {
+        status,
+        _unknown_fields: Default::default(),
+    }
+}
+
+fn check_service(request: &HealthCheckRequest) -> Result<(), Box<Status>> {
+    match request.service.as_str() {
+        "" | COOPERATION_SERVICE => Ok(()),
+        service => Err(Box::new(Status::new(
+            Code::NotFound,
+            format!("Unknown service {}", service),
+        ))),
+    }
+}
+
+impl crate::grpc::health::v1::Health for AgentHealth {
+    async fn check(
+        &self,
+        req: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        check_service(req.get_ref()).map_err(|status| *status)?;
+
+        Ok(Response::new(health_response(self.maintenance.status())))
+    }
+
+    async fn watch(
+        &self,
+        req: Request<HealthCheckRequest>,
+    ) -> Result<Response<BoxStream<'static, Result<HealthCheckResponse, Status>>>, Status> {
+        check_service(req.get_ref()).map_err(|status| *status)?;
+
+        // Current status first, then every change of it
+        let mut serving = self.maintenance.serving.subscribe();
+        serving.mark_changed();
+        let updates = stream::unfold(serving, |mut serving| async move {
+            serving.changed().await.ok()?;
+            let status = serving_status(*serving.borrow_and_update());
+            Some((Ok(health_response(status)), serving))
+        });
+
+        Ok(Response::new(updates.boxed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::health::v1::Health as _;
+    use crate::net::outbe::fingerprint::agent::v1::AgentAdminService as _;
+
+    async fn switch(
+        admin: &AgentAdmin,
+        maintenance: bool,
+    ) -> Result<SetMaintenanceResponse, Status> {
+        let request = Request::new(SetMaintenanceRequest {
+            maintenance,
+            _unknown_fields: Default::default(),
+        });
+
+        Ok(admin.set_maintenance(request).await?.into_inner())
+    }
+
+    #[tokio::test]
+    async fn test_maintenance() -> Result<(), anyhow::Error> {
+        let maintenance = Arc::new(Maintenance::default());
+        let admin = Arc::new(AgentAdmin::new(maintenance.clone()));
+        let health = AgentHealth::new(maintenance.clone());
+        let check = || Request::new(HealthCheckRequest::default());
+
+        let mut updates = health.watch(check()).await?.into_inner();
+        assert_eq!(
+            updates.next().await.unwrap()?.status,
+            ServingStatus::SERVING
+        );
+        let in_flight = maintenance.admit()?;
+
+        // Maintenance is entered once the evaluation in flight is finished
+        let entering = tokio::spawn({
+            let admin = admin.clone();
+            async move { switch(&admin, true).await }
+        });
+        assert_eq!(
+            updates.next().await.unwrap()?.status,
+            ServingStatus::NOT_SERVING
+        );
+        let checked = health.check(check()).await?.into_inner();
+        assert_eq!(checked.status, ServingStatus::NOT_SERVING);
+        assert!(is_maintenance_refusal(&maintenance.admit().err().unwrap()));
+        assert!(!is_maintenance_refusal(&Status::new(
+            MAINTENANCE_CODE,
+            "Aborted by the proxy"
+        )));
+        assert!(!entering.is_finished());
+        drop(in_flight);
+        let entered = entering.await??;
+        assert!(entered.maintenance);
+        assert_eq!(entered.in_flight, 0);
+
+        let left = switch(&admin, false).await?;
+        assert!(!left.maintenance);
+        assert_eq!(
+            updates.next().await.unwrap()?.status,
+            ServingStatus::SERVING
+        );
+        drop(maintenance.admit()?);
+
+        let unknown = Request::new(HealthCheckRequest {
+            service: "grpc.reflection.v1.ServerReflection".into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            health.check(unknown).await.err().unwrap().code(),
+            Code::NotFound
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+//! Relay role of the agent holding no share
+//!
+//! The relay serves the cooperation protocol to the coordinators like any other agent, while the partial
+//! evaluations are delegated to the downstream signer (HSM, air-gapped signer) keeping the share within the
+//! perimeter of the institution. The signer is reached over the narrow `ShareSigner` interface: the blinded point
+//! in, the evaluated point out, nothing else of the request leaves the relay.
+//!
+//! `PipeSigner` speaks the line protocol to the signer process over its standard input and output, the points are
+//! compacted (base58) wire encodings:
+//!
+//! ```text
+//! request:  <sequence> <generation> <blinded point>
+//! response: <sequence> <evaluated point> | <sequence> error <message>
+//! ```
+//!
+//! `serve_signer` is the signer side of the protocol with the share in memory, the reference for the bridges to
+//! the actual signers.
+
+use anyhow::{anyhow, Error};
+use fingerprinting_core::wire;
+use futures::future::{BoxFuture, FutureExt};
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+
+pub trait ShareSigner: Send + Sync {
+    /// Evaluates the blinded point with the share of the `generation` held by the signer
+    fn evaluate(&self, generation: u64, blinded_value: G1) -> BoxFuture<'_, Result<G1, Error>>;
+}
+
+type Input = Box<dyn AsyncWrite + Send + Unpin>;
+type Output = Lines<BufReader<Box<dyn AsyncRead + Send + Unpin>>>;
+
+/// Signer answering the line protocol over the pipe, one evaluation at a time
+pub struct PipeSigner {
+    pipe: Mutex<Pipe>,
+    timeout: Duration,
+    _process: Option<Child>,
+}
+
+struct Pipe {
+    input: Input,
+    output: Output,
+    sequence: u64,
+}
+
+impl PipeSigner {
+    pub fn new(
+        input: impl AsyncWrite + Send + Unpin + 'static,
+        output: impl AsyncRead + Send + Unpin + 'static,
+    ) -> Self {
+        let output: Box<dyn AsyncRead + Send + Unpin> = Box::new(output);
+        Self {
+            pipe: Mutex::new(Pipe {
+                input: Box::new(input),
+                output: BufReader::new(output).lines(),
+                sequence: 0,
+            }),
+            timeout: Duration::from_secs(10),
+            _process: None,
+        }
+    }
+
+    /// Starts the signer `command` (the program and its arguments), the process is killed with the signer.
+    ///
+    /// The process is started once, so the relay could be confined by seccomp afterwards, the profile does not
+    /// allow to start the processes.
+    pub fn spawn(command: &[String]) -> Result<Self, Error> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| anyhow!("Signer command is empty"))?;
+        let mut process = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow!("Cannot start the signer {}: {}", program, e))?;
+        let input = process.stdin.take().expect("Signer input is piped");
+        let output = process.stdout.take().expect("Signer output is piped");
+
+        let mut signer = Self::new(input, output);
+        signer._process = Some(process);
+        Ok(signer)
+    }
+
+    /// Time the signer answers within, 10 seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl ShareSigner for PipeSigner {
+    fn evaluate(&self, generation: u64, blinded_value: G1) -> BoxFuture<'_, Result<G1, Error>> {
+        async move {
+            let mut pipe = self.pipe.lock().await;
+            pipe.sequence += 1;
+            let sequence = pipe.sequence;
+            let request = format!(
+                "{} {} {}\n",
+                sequence,
+                generation,
+                wire::encode_compact(&wire::encode_point(&blinded_value))
+            );
+
+            tokio::time::timeout(self.timeout, pipe.exchange(sequence, &request))
+                .await
+                .map_err(|_| anyhow!("Signer has not answered within {:?}", self.timeout))?
+        }
+        .boxed()
+    }
+}
+
+impl Pipe {
+    async fn exchange(&mut self, sequence: u64, request: &str) -> Result<G1, Error> {
+        self.input.write_all(request.as_bytes()).await?;
+        self.input.flush().await?;
+
+        loop {
+            let line = self
+                .output
+                .next_line()
+                .await?
+                .ok_or_else(|| anyhow!("Signer has closed its output"))?;
+            let (answered, response) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("Malformed signer response: {}", line))?;
+            // Late answers to the timed out requests are skipped
+            if answered.parse::<u64>().ok() != Some(sequence) {
+                continue;
+            }
+
+            return match response.strip_prefix("error ") {
+                Some(message) => Err(anyhow!("Signer has refused the evaluation: {}", message)),
+                None => wire::decode_point(&wire::decode_compact(response)?),
+            };
+        }
+    }
+}
+
+/// Answers the requests of the relay from `input` to `output` with the `shard` in memory until the input is closed
+pub async fn serve_signer(
+    shard: Fr,
+    input: impl AsyncRead + Unpin,
+    mut output: impl AsyncWrite + Unpin,
+) -> Result<(), Error> {
+    let mut requests = BufReader::new(input).lines();
+    while let Some(request) = requests.next_line().await? {
+        let mut fields = request.splitn(3, ' ');
+        let (Some(sequence), Some(generation), Some(blinded_value)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            log::warn!("Malformed relay request is skipped");
+            continue;
+        };
+
+        let response = signer_evaluation(shard, generation, blinded_value)
+            .unwrap_or_else(|e| format!("error {}", e));
+        output
+            .write_all(format!("{} {}\n", sequence, response).as_bytes())
+            .await?;
+        output.flush().await?;
+    }
+
+    Ok(())
+}
+
+fn signer_evaluation(shard: Fr, generation: &str, blinded_value: &str) -> Result<String, Error> {
+    if generation.parse::<u64>()? != 0 {
+        return Err(anyhow!(
+            "Current implementation doesn't support secret generations"
+        ));
+    }
+    let point = wire::decode_point(&wire::decode_compact(blinded_value)?)?;
+
+    Ok(wire::encode_compact(&wire::encode_point(&(point * shard))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_axiom::halo2curves::group::Group;
+
+    #[tokio::test]
+    async fn test_pipe_signer() -> Result<(), Error> {
+        let (relay_input, signer_input) = tokio::io::duplex(1024);
+        let (signer_output, relay_output) = tokio::io::duplex(1024);
+        let signer = tokio::spawn(serve_signer(Fr::from(42), signer_input, signer_output));
+
+        let relay = PipeSigner::new(relay_input, relay_output);
+        let point = G1::generator().double();
+        assert_eq!(relay.evaluate(0, point).await?, point * Fr::from(42));
+        assert_eq!(
+            relay.evaluate(0, G1::generator()).await?,
+            G1::generator() * Fr::from(42)
+        );
+
+        // Refusal of the signer fails the single evaluation only
+        let refused = relay.evaluate(1, point).await.err().unwrap();
+        assert!(refused.to_string().contains("secret generations"));
+        assert_eq!(relay.evaluate(0, point).await?, point * Fr::from(42));
+
+        drop(relay);
+        signer.await??;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_late_signer_answers() -> Result<(), Error> {
+        let (relay_input, _signer_input) = tokio::io::duplex(1024);
+        let (mut signer_output, relay_output) = tokio::io::duplex(1024);
+        let relay =
+            PipeSigner::new(relay_input, relay_output).with_timeout(Duration::from_millis(50));
+        assert!(relay.evaluate(0, G1::generator()).await.is_err());
+
+        // Answer to the timed out request is skipped, the next one is matched by its sequence
+        let answer = |sequence: u64, point: &G1| {
+            format!(
+                "{} {}\n",
+                sequence,
+                wire::encode_compact(&wire::encode_point(point))
+            )
+        };
+        let point = G1::generator().double();
+        signer_output
+            .write_all(answer(1, &G1::generator()).as_bytes())
+            .await?;
+        signer_output
+            .write_all(answer(2, &point).as_bytes())
+            .await?;
+        assert_eq!(relay.evaluate(0, G1::generator()).await?, point);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+//! Signatures of the partial evaluations, so a later dispute about a fingerprint attributes a wrong partial
+//! evaluation to the agent that returned it
+//!
+//! The agent signs the request id chosen by the coordinator, the generation, the blinded point and its evaluation
+//! with Ed25519. The coordinator verifies the signatures of the agents with the known verifying keys and appends
+//! every signed partial evaluation to the transcript, the agent cannot deny the evaluation it signed afterwards.
+
+use anyhow::{anyhow, Error};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use fingerprinting_core::wire;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Domain separation of the signed partial evaluations
+const PARTIAL_PREFIX: &[u8] = b"outbe-fingerprint-partial-v1";
+
+/// Size of the request id the coordinator generates for every partial evaluation
+pub const REQUEST_ID_SIZE: usize = 16;
+
+/// Message signed by the agent, the fields are length prefixed so none of them could be shifted into another
+pub fn partial_message(
+    request_id: &[u8],
+    generation: u64,
+    blinded_value: &[u8],
+    evaluation: &[u8],
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(
+        PARTIAL_PREFIX.len()
+            + 8
+            + 4 * 3
+            + request_id.len()
+            + blinded_value.len()
+            + evaluation.len(),
+    );
+    message.extend_from_slice(PARTIAL_PREFIX);
+    message.extend_from_slice(&generation.to_be_bytes());
+    for field in [request_id, blinded_value, evaluation] {
+        message.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        message.extend_from_slice(field);
+    }
+
+    message
+}
+
+/// Signature of the agent over its partial evaluation
+pub fn sign_partial(
+    key: &SigningKey,
+    request_id: &[u8],
+    generation: u64,
+    blinded_value: &[u8],
+    evaluation: &[u8],
+) -> [u8; 64] {
+    key.sign(&partial_message(
+        request_id,
+        generation,
+        blinded_value,
+        evaluation,
+    ))
+    .to_bytes()
+}
+
+/// Partial evaluation of the agent with its signature, as kept in the transcript
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedPartial {
+    pub agent: usize,
+    pub request_id: Vec<u8>,
+    pub generation: u64,
+    /// Encoded blinded point sent to the agent
+    pub blinded_value: Vec<u8>,
+    /// Encoded evaluation returned by the agent
+    pub evaluation: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl SignedPartial {
+    pub fn verify(&self, key: &VerifyingKey) -> Result<(), Error> {
+        let signature = Signature::from_slice(&self.signature)
+            .map_err(|_| anyhow!("Signature of agent {} is malformed", self.agent))?;
+
+        key.verify(
+            &partial_message(
+                &self.request_id,
+                self.generation,
+                &self.blinded_value,
+                &self.evaluation,
+            ),
+            &signature,
+        )
+        .map_err(|_| {
+            anyhow!(
+                "Signature of agent {} does not match its evaluation",
+                self.agent
+            )
+        })
+    }
+
+    /// Transcript line: agent, generation and the compacted request id, blinded value, evaluation and signature
+    pub fn encode(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            self.agent,
+            self.generation,
+            wire::encode_compact(&self.request_id),
+            wire::encode_compact(&self.blinded_value),
+            wire::encode_compact(&self.evaluation),
+            wire::encode_compact(&self.signature)
+        )
+    }
+
+    pub fn decode(line: &str) -> Result<Self, Error> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let [agent, generation, request_id, blinded_value, evaluation, signature] = fields[..]
+        else {
+            return Err(anyhow!(
+                "Transcript line should have 6 fields, got {}",
+                fields.len()
+            ));
+        };
+
+        Ok(Self {
+            agent: agent.parse()?,
+            generation: generation.parse()?,
+            request_id: wire::decode_compact(request_id)?,
+            blinded_value: wire::decode_compact(blinded_value)?,
+            evaluation: wire::decode_compact(evaluation)?,
+            signature: wire::decode_compact(signature)?,
+        })
+    }
+}
+
+/// Sink of the signed partial evaluations collected by the coordinator
+pub trait PartialTranscript: Send + Sync {
+    /// Evaluation is not used when it is not recorded, the fingerprint without the evidence is not computed
+    fn record(&self, partial: &SignedPartial) -> Result<(), Error>;
+}
+
+/// Transcript appended to the file, one `SignedPartial::encode` line per partial evaluation
+pub struct FileTranscript {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl FileTranscript {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: Mutex::new(BufWriter::new(file)),
+        })
+    }
+}
+
+impl PartialTranscript for FileTranscript {
+    fn record(&self, partial: &SignedPartial) -> Result<(), Error> {
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow!("Transcript file lock is poisoned"))?;
+        writeln!(file, "{}", partial.encode())?;
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_signed_partial() -> Result<(), Error> {
+        let key = SigningKey::generate(&mut OsRng);
+        let partial = SignedPartial {
+            agent: 2,
+            request_id: vec![7; REQUEST_ID_SIZE],
+            generation: 0,
+            blinded_value: vec![1, 2, 3],
+            evaluation: vec![4, 5, 6],
+            signature: sign_partial(&key, &[7; REQUEST_ID_SIZE], 0, &[1, 2, 3], &[4, 5, 6])
+                .to_vec(),
+        };
+        partial.verify(&key.verifying_key())?;
+        assert_eq!(SignedPartial::decode(&partial.encode())?, partial);
+
+        // Evaluation of another request or of another agent key is refused
+        let replayed = SignedPartial {
+            request_id: vec![8; REQUEST_ID_SIZE],
+            ..partial.clone()
+        };
+        assert!(replayed.verify(&key.verifying_key()).is_err());
+        let other = SigningKey::generate(&mut OsRng);
+        assert!(partial.verify(&other.verifying_key()).is_err());
+
+        // Field boundaries are part of the message
+        assert_ne!(
+            partial_message(&[1, 2], 0, &[3], &[]),
+            partial_message(&[1], 0, &[2, 3], &[])
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,625 @@
+//! SPIFFE workload identities (X.509 SVIDs) for the mutual TLS between the coordinator and agents
+//!
+//! SVIDs and trust bundles are fetched from the SPIFFE Workload API (e.g. SPIRE agent) and kept
+//! up to date in background, since SVIDs are short-lived. The peer is authenticated by the SPIFFE ID
+//! in the URI SAN of its certificate: the ID should belong to one of the trusted domains and the certificate
+//! should chain up to the bundle of that domain. DNS names are not verified.
+
+use crate::spiffe::workload::{X509svidRequest, X509svidResponse};
+use crate::tls::crypto_provider;
+use anyhow::{anyhow, Error};
+use futures::stream::{self, BoxStream, StreamExt};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::ResolvesClientCert;
+use rustls::crypto::WebPkiSupportedAlgorithms;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, DistinguishedName, OtherError,
+    ServerConfig, SignatureScheme,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use volo::net::tls::{ServerTlsConfig, TlsAcceptor};
+use volo::net::Address;
+use volo_grpc::body::BoxBody;
+use volo_grpc::codec::compression::CompressionEncoding;
+use volo_grpc::codec::decode::Kind;
+use volo_grpc::codegen::{Bytes, Frame};
+use volo_grpc::metadata::MetadataValue;
+use volo_grpc::{RecvEntryMessage, RecvStream, Request, SendEntryMessage, Status};
+use x509_parser::extensions::GeneralName;
+
+/// Path of the Workload API method, the API is defined without a package
+const FETCH_X509_SVID: &str = "/SpiffeWorkloadAPI/FetchX509SVID";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// SPIFFE ID `spiffe://<trust domain>/<path>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SpiffeId {
+    trust_domain: String,
+    path: String,
+}
+
+impl SpiffeId {
+    pub fn parse(id: &str) -> Result<Self, Error> {
+        let rest = id
+            .strip_prefix("spiffe://")
+            .ok_or(anyhow!("SPIFFE ID {} should have spiffe scheme", id))?;
+        let (trust_domain, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+
+        if trust_domain.is_empty()
+            || !trust_domain
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-._".contains(c))
+        {
+            return Err(anyhow!("SPIFFE ID {} has invalid trust domain", id));
+        }
+        if path.ends_with('/') || path.contains("//") {
+            return Err(anyhow!("SPIFFE ID {} has invalid path", id));
+        }
+
+        Ok(Self {
+            trust_domain: trust_domain.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    pub fn trust_domain(&self) -> &str {
+        &self.trust_domain
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl fmt::Display for SpiffeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "spiffe://{}{}", self.trust_domain, self.path)
+    }
+}
+
+/// SVID of the workload with the bundles of the trust domains, keyed by the trust domain name
+pub struct X509Context {
+    spiffe_id: SpiffeId,
+    certified_key: Arc<CertifiedKey>,
+    bundles: HashMap<String, Vec<CertificateDer<'static>>>,
+}
+
+impl X509Context {
+    pub fn new(
+        spiffe_id: SpiffeId,
+        certificate_chain: Vec<CertificateDer<'static>>,
+        private_key: PrivateKeyDer<'static>,
+        bundles: HashMap<String, Vec<CertificateDer<'static>>>,
+    ) -> Result<Self, Error> {
+        let certified_key =
+            CertifiedKey::from_der(certificate_chain, private_key, &crypto_provider())?;
+
+        Ok(Self {
+            spiffe_id,
+            certified_key: Arc::new(certified_key),
+            bundles,
+        })
+    }
+
+    pub fn spiffe_id(&self) -> &SpiffeId {
+        &self.spiffe_id
+    }
+
+    // The first SVID is the default identity of the workload
+    fn from_response(response: X509svidResponse) -> Result<Self, Error> {
+        let svid = response
+            .svids
+            .into_iter()
+            .next()
+            .ok_or(anyhow!("Workload API returned no SVIDs"))?;
+        let spiffe_id = SpiffeId::parse(&svid.spiffe_id)?;
+
+        let mut bundles = HashMap::new();
+        for (trust_domain, bundle) in response.federated_bundles.iter() {
+            bundles.insert(
+                trust_domain.trim_start_matches("spiffe://").to_string(),
+                der_certificates(bundle)?,
+            );
+        }
+        bundles.insert(
+            spiffe_id.trust_domain.clone(),
+            der_certificates(&svid.bundle)?,
+        );
+
+        Self::new(
+            spiffe_id,
+            der_certificates(&svid.x509_svid)?,
+            PrivateKeyDer::Pkcs8(svid.x509_svid_key.to_vec().into()),
+            bundles,
+        )
+    }
+}
+
+/// Current X.509 context of the workload, shared by all the TLS configurations built from it
+#[derive(Clone)]
+pub struct SpiffeSource {
+    context: Arc<RwLock<Arc<X509Context>>>,
+}
+
+impl fmt::Debug for SpiffeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpiffeSource")
+            .field("spiffe_id", &self.current().spiffe_id)
+            .finish()
+    }
+}
+
+impl SpiffeSource {
+    /// Source with the static context, which is never updated
+    pub fn new(context: X509Context) -> Self {
+        Self {
+            context: Arc::new(RwLock::new(Arc::new(context))),
+        }
+    }
+
+    /// Fetches the SVID from the Workload API listening on the unix socket
+    /// (`unix:///run/spire/agent.sock` or the plain path) and watches its updates in background
+    pub async fn from_workload_api(socket: &str) -> Result<Self, Error> {
+        let address = workload_api_address(socket)?;
+
+        let mut updates = fetch_x509_svid(address.clone()).await?;
+        let response = updates.next().await.ok_or(anyhow!(
+            "Workload API closed the stream before returning SVID"
+        ))??;
+
+        let source = Self::new(X509Context::from_response(response)?);
+        log::info!("== Fetched SVID {}", source.current().spiffe_id);
+
+        tokio::spawn(source.clone().watch(address, updates));
+
+        Ok(source)
+    }
+
+    pub fn current(&self) -> Arc<X509Context> {
+        self.context.read().unwrap().clone()
+    }
+
+    /// Client configuration presenting the SVID and accepting agents of the `trust_domains` only
+    pub fn client_config(&self, trust_domains: &[String]) -> Result<ClientConfig, Error> {
+        let config = ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(SpiffeVerifier::new(self, trust_domains)))
+            .with_client_cert_resolver(Arc::new(SvidResolver(self.clone())));
+
+        Ok(config)
+    }
+
+    /// Server configuration presenting the SVID and requiring coordinators of the `trust_domains`
+    pub fn server_tls_config(&self, trust_domains: &[String]) -> Result<ServerTlsConfig, Error> {
+        let mut config = ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()?
+            .with_client_cert_verifier(Arc::new(SpiffeVerifier::new(self, trust_domains)))
+            .with_cert_resolver(Arc::new(SvidResolver(self.clone())));
+        config.alpn_protocols = vec![b"h2".to_vec()];
+
+        Ok(ServerTlsConfig {
+            acceptor: TlsAcceptor::from(config),
+        })
+    }
+
+    fn update(&self, context: X509Context) {
+        *self.context.write().unwrap() = Arc::new(context);
+    }
+
+    // Applies updates of the stream, reconnecting when the stream breaks. The last SVID is used meanwhile.
+    async fn watch(
+        self,
+        address: Address,
+        mut updates: BoxStream<'static, Result<X509svidResponse, Status>>,
+    ) {
+        loop {
+            while let Some(update) = updates.next().await {
+                match update
+                    .map_err(Error::from)
+                    .and_then(X509Context::from_response)
+                {
+                    Ok(context) => {
+                        log::info!("== Updated SVID {}", context.spiffe_id);
+                        self.update(context);
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to update SVID: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            updates = match fetch_x509_svid(address.clone()).await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    log::warn!("Failed to reconnect to Workload API: {}", e);
+                    stream::empty().boxed()
+                }
+            };
+        }
+    }
+}
+
+fn workload_api_address(socket: &str) -> Result<Address, Error> {
+    let path = socket.strip_prefix("unix://").unwrap_or(socket);
+    let address = std::os::unix::net::SocketAddr::from_pathname(path)
+        .map_err(|e| anyhow!("Invalid Workload API socket {}: {}", socket, e))?;
+
+    Ok(Address::from(address))
+}
+
+async fn fetch_x509_svid(
+    address: Address,
+) -> Result<BoxStream<'static, Result<X509svidResponse, Status>>, Error> {
+    let client = volo_grpc::client::ClientBuilder::new(MkWorkloadApiClient, "spiffe-workload-api")
+        .address(address)
+        .build();
+
+    let message = stream::once(async { Ok(X509svidRequest::default()) }).boxed();
+    let mut request = Request::new(WorkloadApiSend(message));
+    request
+        .metadata_mut()
+        .insert("workload.spiffe.io", MetadataValue::from_static("true"));
+
+    let mut cx = client.make_cx(FETCH_X509_SVID);
+    let response = volo::Service::call(&client, &mut cx, request).await?;
+    let WorkloadApiRecv(updates) = response.into_inner();
+
+    Ok(updates.boxed())
+}
+
+/// Concatenated ASN.1 DER certificates
+fn der_certificates(der: &[u8]) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let mut certificates = vec![];
+    let mut rest = der;
+    while !rest.is_empty() {
+        let (next, _) = x509_parser::parse_x509_certificate(rest)
+            .map_err(|e| anyhow!("Invalid certificate: {}", e))?;
+        certificates.push(CertificateDer::from(
+            rest[..rest.len() - next.len()].to_vec(),
+        ));
+        rest = next;
+    }
+
+    Ok(certificates)
+}
+
+/// SPIFFE ID of the certificate, X.509 SVID has exactly one URI SAN
+pub fn peer_spiffe_id(certificate: &CertificateDer<'_>) -> Result<SpiffeId, Error> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(certificate)
+        .map_err(|e| anyhow!("Invalid certificate: {}", e))?;
+
+    let uris = certificate
+        .subject_alternative_name()?
+        .map(|san| {
+            san.value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::URI(uri) => Some(*uri),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    match uris.as_slice() {
+        [uri] => SpiffeId::parse(uri),
+        _ => Err(anyhow!(
+            "Certificate should have exactly one URI SAN, found {}",
+            uris.len()
+        )),
+    }
+}
+
+#[derive(Debug)]
+struct SpiffeVerifier {
+    source: SpiffeSource,
+    trust_domains: Vec<String>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl SpiffeVerifier {
+    fn new(source: &SpiffeSource, trust_domains: &[String]) -> Self {
+        Self {
+            source: source.clone(),
+            trust_domains: trust_domains.to_vec(),
+            algorithms: crypto_provider().signature_verification_algorithms,
+        }
+    }
+
+    fn verify(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+        usage: webpki::KeyUsage,
+    ) -> Result<(), rustls::Error> {
+        let spiffe_id =
+            peer_spiffe_id(end_entity).map_err(|e| rustls::Error::General(e.to_string()))?;
+        if !self
+            .trust_domains
+            .iter()
+            .any(|domain| domain == spiffe_id.trust_domain())
+        {
+            return Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+
+        let context = self.source.current();
+        let bundle = context.bundles.get(spiffe_id.trust_domain()).ok_or(
+            rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer),
+        )?;
+        let anchors = bundle
+            .iter()
+            .map(|certificate| webpki::anchor_from_trusted_cert(certificate).map_err(pki_error))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        webpki::EndEntityCert::try_from(end_entity)
+            .map_err(pki_error)?
+            .verify_for_usage(
+                self.algorithms.all,
+                &anchors,
+                intermediates,
+                now,
+                usage,
+                None,
+                None,
+            )
+            .map_err(pki_error)?;
+
+        Ok(())
+    }
+}
+
+fn pki_error(error: webpki::Error) -> rustls::Error {
+    rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(Arc::new(error))))
+}
+
+impl ServerCertVerifier for SpiffeVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verify(
+            end_entity,
+            intermediates,
+            now,
+            webpki::KeyUsage::server_auth(),
+        )?;
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+impl ClientCertVerifier for SpiffeVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.verify(
+            end_entity,
+            intermediates,
+            now,
+            webpki::KeyUsage::client_auth(),
+        )?;
+
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// Presents the current SVID, so the rotated SVID is used by the next handshake
+#[derive(Debug)]
+struct SvidResolver(SpiffeSource);
+
+impl ResolvesClientCert for SvidResolver {
+    fn resolve(
+        &self,
+        _root_hint_subjects: &[&[u8]],
+        _sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.current().certified_key.clone())
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+impl ResolvesServerCert for SvidResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.current().certified_key.clone())
+    }
+}
+
+// Workload API client, the generated one addresses methods by the package qualified path
+struct WorkloadApiSend(BoxStream<'static, Result<X509svidRequest, Status>>);
+
+impl SendEntryMessage for WorkloadApiSend {
+    fn into_body(
+        self,
+        compression_encoding: Option<CompressionEncoding>,
+    ) -> volo_grpc::BoxStream<'static, Result<Frame<Bytes>, Status>> {
+        volo_grpc::codec::encode::encode(self.0, compression_encoding)
+    }
+}
+
+struct WorkloadApiRecv(RecvStream<X509svidResponse>);
+
+impl RecvEntryMessage for WorkloadApiRecv {
+    fn from_body(
+        method: Option<&str>,
+        body: BoxBody,
+        kind: Kind,
+        compression_encoding: Option<CompressionEncoding>,
+    ) -> Result<Self, Status> {
+        match method {
+            Some(FETCH_X509_SVID) => Ok(Self(RecvStream::new(body, kind, compression_encoding))),
+            _ => Err(Status::new(
+                volo_grpc::Code::Unimplemented,
+                "Method not found.",
+            )),
+        }
+    }
+}
+
+struct MkWorkloadApiClient;
+
+impl<S> volo::client::MkClient<volo_grpc::Client<S>> for MkWorkloadApiClient {
+    type Target = volo_grpc::Client<S>;
+
+    fn mk_client(&self, service: volo_grpc::Client<S>) -> Self::Target {
+        service
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair, SanType};
+
+    fn certificate(
+        spiffe_id: &str,
+        issuer: Option<(&rcgen::Certificate, &KeyPair)>,
+    ) -> (rcgen::Certificate, KeyPair) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::default();
+        params.subject_alt_names = vec![SanType::URI(spiffe_id.try_into().unwrap())];
+        let certificate = match issuer {
+            Some((ca, ca_key)) => {
+                params.extended_key_usages = vec![
+                    rcgen::ExtendedKeyUsagePurpose::ServerAuth,
+                    rcgen::ExtendedKeyUsagePurpose::ClientAuth,
+                ];
+                params.signed_by(&key, ca, ca_key).unwrap()
+            }
+            None => {
+                params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+                params.self_signed(&key).unwrap()
+            }
+        };
+
+        (certificate, key)
+    }
+
+    #[test]
+    fn test_spiffe_verifier() -> Result<(), Error> {
+        assert_eq!(
+            SpiffeId::parse("spiffe://bank-a.example/agent/2")?.to_string(),
+            "spiffe://bank-a.example/agent/2"
+        );
+        assert!(SpiffeId::parse("https://bank-a.example/agent").is_err());
+        assert!(SpiffeId::parse("spiffe://Bank-A/agent").is_err());
+
+        let (ca_a, ca_a_key) = certificate("spiffe://bank-a.example", None);
+        let (ca_b, ca_b_key) = certificate("spiffe://bank-b.example", None);
+        let (svid, svid_key) = certificate(
+            "spiffe://bank-a.example/coordinator",
+            Some((&ca_a, &ca_a_key)),
+        );
+        let (peer, _) = certificate("spiffe://bank-b.example/agent/2", Some((&ca_b, &ca_b_key)));
+        let (forged, _) = certificate("spiffe://bank-b.example/agent/3", Some((&ca_a, &ca_a_key)));
+
+        let bundle_b: Vec<u8> = [ca_b.der().to_vec(), ca_b.der().to_vec()].concat();
+        let context = X509Context::new(
+            SpiffeId::parse("spiffe://bank-a.example/coordinator")?,
+            vec![svid.der().clone()],
+            PrivateKeyDer::Pkcs8(svid_key.serialize_der().into()),
+            HashMap::from([
+                ("bank-a.example".to_string(), vec![ca_a.der().clone()]),
+                ("bank-b.example".to_string(), der_certificates(&bundle_b)?),
+            ]),
+        )?;
+        let source = SpiffeSource::new(context);
+        let now = UnixTime::now();
+
+        assert_eq!(peer_spiffe_id(peer.der())?.path(), "/agent/2");
+
+        let verifier = SpiffeVerifier::new(&source, &["bank-b.example".to_string()]);
+        assert!(verifier
+            .verify(peer.der(), &[], now, webpki::KeyUsage::server_auth())
+            .is_ok());
+        // Signed by the CA of another trust domain
+        assert!(verifier
+            .verify(forged.der(), &[], now, webpki::KeyUsage::server_auth())
+            .is_err());
+        // Trust domain is not trusted
+        assert!(verifier
+            .verify(svid.der(), &[], now, webpki::KeyUsage::client_auth())
+            .is_err());
+
+        assert!(source
+            .client_config(&["bank-b.example".to_string()])
+            .is_ok());
+        assert!(source
+            .server_tls_config(&["bank-b.example".to_string()])
+            .is_ok());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use crate::svid::SpiffeSource;
+use anyhow::{anyhow, Error};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ClientConfig, RootCertStore};
+use std::path::Path;
+use std::sync::Arc;
+use volo::net::tls::ClientTlsConfig;
+
+/// TLS settings of the client connecting to the single agent
+/// Agents are operated by different institutions, so every agent could have its own PKI
+#[derive(Debug, Clone, Default)]
+pub struct AgentClientTls {
+    ca_bundle: Option<Vec<u8>>,
+    server_name: Option<String>,
+    client_identity: Option<(Vec<u8>, Vec<u8>)>,
+    spiffe: Option<(SpiffeSource, Vec<String>)>,
+}
+
+impl AgentClientTls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// PEM bundle of CA certificates the agent certificate is verified with,
+    /// Mozilla root certificates are used when not provided
+    pub fn with_ca_bundle(mut self, pem: Vec<u8>) -> Self {
+        self.ca_bundle = Some(pem);
+        self
+    }
+
+    pub fn with_ca_bundle_file(self, path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(self.with_ca_bundle(read(path.as_ref())?))
+    }
+
+    /// Server name used for SNI and the agent certificate verification,
+    /// the host of the agent address is used when not provided
+    pub fn with_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// PEM certificate chain and private key presented to the agent for mutual TLS
+    pub fn with_client_identity(mut self, certificate_chain: Vec<u8>, key: Vec<u8>) -> Self {
+        self.client_identity = Some((certificate_chain, key));
+        self
+    }
+
+    pub fn with_client_identity_files(
+        self,
+        certificate_chain: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        Ok(self.with_client_identity(read(certificate_chain.as_ref())?, read(key.as_ref())?))
+    }
+
+    /// Authenticates with the SVID of the `source` and accepts the agent with SPIFFE ID
+    /// of the `trust_domains`, the CA bundle and the client identity are not used then
+    pub fn with_spiffe(mut self, source: SpiffeSource, trust_domains: Vec<String>) -> Self {
+        self.spiffe = Some((source, trust_domains));
+        self
+    }
+
+    /// Builds the client configuration for the agent listening on `remote_address` (`host:port`)
+    pub fn client_config(&self, remote_address: &str) -> Result<ClientTlsConfig, Error> {
+        let server_name = match &self.server_name {
+            Some(server_name) => server_name.clone(),
+            None => host(remote_address)?,
+        };
+
+        let mut config = match &self.spiffe {
+            Some((source, trust_domains)) => source.client_config(trust_domains)?,
+            None => self.webpki_client_config()?,
+        };
+        config.alpn_protocols = vec![b"h2".to_vec()];
+
+        Ok(ClientTlsConfig::new(server_name, config))
+    }
+
+    fn webpki_client_config(&self) -> Result<ClientConfig, Error> {
+        let mut roots = RootCertStore::empty();
+        match &self.ca_bundle {
+            Some(pem) => {
+                let certificates = certificates(pem)?;
+                if certificates.is_empty() {
+                    return Err(anyhow!("CA bundle contains no certificates"));
+                }
+                for certificate in certificates {
+                    roots.add(certificate)?;
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+
+        let builder = ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots);
+
+        let config = match &self.client_identity {
+            Some((certificate_chain, key)) => {
+                let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut key.as_slice())?
+                    .ok_or(anyhow!("Client key contains no private key"))?;
+
+                builder.with_client_auth_cert(certificates(certificate_chain)?, key)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        Ok(config)
+    }
+}
+
+pub(crate) fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::aws_lc_rs::default_provider())
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))
+}
+
+fn certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, Error> {
+    Ok(rustls_pemfile::certs(&mut &pem[..]).collect::<Result<Vec<_>, _>>()?)
+}
+
+// Host of the `host:port` address, IPv6 addresses are enclosed in brackets
+fn host(remote_address: &str) -> Result<String, Error> {
+    let (host, _) = remote_address
+        .rsplit_once(':')
+        .ok_or(anyhow!("Agent address {} has no port", remote_address))?;
+
+    Ok(host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_config() -> Result<(), Error> {
+        let config = AgentClientTls::new().client_config("agent-2.bank.example:9001")?;
+        assert_eq!(config.server_name, "agent-2.bank.example");
+
+        let config = AgentClientTls::new()
+            .with_server_name("agent-2.internal")
+            .client_config("[::1]:9001")?;
+        assert_eq!(config.server_name, "agent-2.internal");
+        assert_eq!(host("[::1]:9001")?, "::1");
+
+        let not_a_bundle = AgentClientTls::new().with_ca_bundle(b"not a certificate".to_vec());
+        assert!(not_a_bundle
+            .client_config("agent-2.bank.example:9001")
+            .is_err());
+
+        Ok(())
+    }
+}
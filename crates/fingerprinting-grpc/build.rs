@@ -1,3 +1,71 @@
+use std::path::{Path, PathBuf};
+
 fn main() {
     volo_build::ConfigBuilder::default().write().unwrap();
+
+    if std::env::var_os("CARGO_FEATURE_TYPESCRIPT").is_some() {
+        emit_typescript().unwrap();
+    }
+}
+
+/// Emits the TypeScript SDK package: the proto files and the wrapper over the stubs,
+/// stubs are generated from the emitted protos by `npm run generate`.
+/// The package is written to `FINGERPRINTING_TS_OUT`, `$OUT_DIR/typescript` by default.
+fn emit_typescript() -> std::io::Result<()> {
+    println!("cargo:rerun-if-env-changed=FINGERPRINTING_TS_OUT");
+    let out = match std::env::var_os("FINGERPRINTING_TS_OUT") {
+        Some(out) => PathBuf::from(out),
+        None => PathBuf::from(std::env::var_os("OUT_DIR").unwrap()).join("typescript"),
+    };
+
+    copy_dir(Path::new("proto"), &out.join("proto"))?;
+    std::fs::create_dir_all(out.join("src"))?;
+    std::fs::copy("typescript/index.ts", out.join("src/index.ts"))?;
+    std::fs::copy("typescript/buf.gen.yaml", out.join("buf.gen.yaml"))?;
+    std::fs::copy("typescript/tsconfig.json", out.join("tsconfig.json"))?;
+
+    // SDK is versioned together with the service
+    std::fs::write(
+        out.join("package.json"),
+        format!(
+            r#"{{
+  "name": "@outbe/fingerprinting-client",
+  "version": "{}",
+  "type": "module",
+  "main": "dist/index.js",
+  "types": "dist/index.d.ts",
+  "scripts": {{
+    "generate": "buf generate",
+    "build": "buf generate && tsc"
+  }},
+  "dependencies": {{
+    "@bufbuild/protobuf": "^2.2.0",
+    "@connectrpc/connect": "^2.0.0"
+  }},
+  "devDependencies": {{
+    "@bufbuild/buf": "^1.47.0",
+    "@bufbuild/protoc-gen-es": "^2.2.0",
+    "typescript": "^5.6.0"
+  }}
+}}
+"#,
+            std::env::var("CARGO_PKG_VERSION").unwrap()
+        ),
+    )?;
+
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()))?;
+        } else {
+            std::fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+
+    Ok(())
 }
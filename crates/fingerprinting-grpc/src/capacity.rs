@@ -0,0 +1,45 @@
+//! Counters of the fingerprints served by the service, sampled into the capacity log of the agent
+//!
+//! Counters are cumulative since the start of the service, the capacity report takes the differences between
+//! the samples. Fingerprints failed to be computed are counted, the refused requests (malformed transactions,
+//! load shedding) are not.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Fingerprints served since the start of the service
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServiceStats {
+    pub computed: u64,
+    /// Computed fingerprints found to be duplicates by the store
+    pub duplicates: u64,
+    /// Fingerprints the protocol failed to compute
+    pub failed: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ServiceCounters {
+    computed: AtomicU64,
+    duplicates: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl ServiceCounters {
+    pub fn stats(&self) -> ServiceStats {
+        ServiceStats {
+            computed: self.computed.load(Ordering::Relaxed),
+            duplicates: self.duplicates.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn record_computation(&self, computed: bool) {
+        match computed {
+            true => self.computed.fetch_add(1, Ordering::Relaxed),
+            false => self.failed.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub(crate) fn record_duplicate(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,204 @@
+//! Responses of the completed computations by the idempotency keys of the callers
+//!
+//! Caller retrying the call it got no response to presents the same key in the `x-idempotency-key` metadata and gets
+//! the response of the completed call, so the retry is neither stored as the duplicate nor counted in the usage twice.
+//! Keys are scoped by the API key of the caller and kept hashed. Responses are kept for the `ttl` up to the `capacity`
+//! ones, the oldest are evicted first.
+
+use crate::net::outbe::fingerprint::v1::ComputeSingleFingerprintResponse;
+use crate::usage::API_KEY;
+use anyhow::Error;
+use chrono::{DateTime, Duration, Utc};
+use pilota::pb::Message;
+use pilota::{Bytes, LinkedBytes};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, PoisonError};
+use volo_grpc::metadata::MetadataMap;
+
+/// Metadata key of the idempotency key of the call
+pub const IDEMPOTENCY_KEY: &str = "x-idempotency-key";
+
+/// Encoded response of the call completed `at`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+    /// Hex of BLAKE3 of the API key and the idempotency key
+    pub key: String,
+    pub at: DateTime<Utc>,
+    pub response: Vec<u8>,
+}
+
+pub struct IdempotencyCache {
+    capacity: usize,
+    ttl: Duration,
+    responses: Mutex<Responses>,
+}
+
+/// Responses by the keys with the keys in the order of the insertion
+#[derive(Default)]
+struct Responses {
+    by_key: HashMap<String, CachedResponse>,
+    order: VecDeque<String>,
+}
+
+impl Responses {
+    fn insert(&mut self, cached: CachedResponse, capacity: usize) {
+        if self.by_key.contains_key(&cached.key) {
+            return;
+        }
+        while self.order.len() >= capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.by_key.remove(&evicted);
+            }
+        }
+        self.order.push_back(cached.key.clone());
+        self.by_key.insert(cached.key.clone(), cached);
+    }
+}
+
+impl IdempotencyCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            responses: Mutex::default(),
+        }
+    }
+
+    /// Key of the call in the cache, absent when the caller presents no idempotency key
+    pub(crate) fn key(metadata: &MetadataMap) -> Option<String> {
+        let idempotency_key = metadata.get(IDEMPOTENCY_KEY)?.as_bytes();
+        let api_key = metadata
+            .get(API_KEY)
+            .map(|key| key.as_bytes())
+            .unwrap_or_default();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(api_key.len() as u64).to_be_bytes());
+        hasher.update(api_key);
+        hasher.update(idempotency_key);
+        Some(hasher.finalize().to_hex().to_string())
+    }
+
+    /// Response of the call completed within the `ttl`
+    pub(crate) fn get(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+    ) -> Option<ComputeSingleFingerprintResponse> {
+        let responses = self
+            .responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let cached = responses
+            .by_key
+            .get(key)
+            .filter(|cached| cached.at + self.ttl > now)?;
+
+        ComputeSingleFingerprintResponse::decode(Bytes::copy_from_slice(&cached.response))
+            .inspect_err(|e| log::error!("Failed to decode the cached response: {}", e))
+            .ok()
+    }
+
+    pub(crate) fn insert(
+        &self,
+        key: String,
+        response: &ComputeSingleFingerprintResponse,
+        now: DateTime<Utc>,
+    ) {
+        let mut encoded = LinkedBytes::new();
+        if let Err(e) = response.encode(&mut encoded) {
+            log::error!("Failed to encode the response to cache: {}", e);
+            return;
+        }
+
+        self.responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                CachedResponse {
+                    key,
+                    at: now,
+                    response: encoded.concat().to_vec(),
+                },
+                self.capacity,
+            );
+    }
+
+    /// Responses still within the `ttl` at `now`, the oldest first
+    pub fn responses(&self, now: DateTime<Utc>) -> Vec<CachedResponse> {
+        let responses = self
+            .responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        responses
+            .order
+            .iter()
+            .filter_map(|key| responses.by_key.get(key))
+            .filter(|cached| cached.at + self.ttl > now)
+            .cloned()
+            .collect()
+    }
+
+    /// Replaces the responses, e.g. by the ones saved before the restart
+    pub fn restore(&self, cached: Vec<CachedResponse>) -> Result<(), Error> {
+        let mut restored = Responses::default();
+        for cached in cached {
+            ComputeSingleFingerprintResponse::decode(Bytes::copy_from_slice(&cached.response))?;
+            restored.insert(cached, self.capacity);
+        }
+        *self
+            .responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = restored;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::outbe::fingerprint::v1::Fingerprint;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_idempotency_cache() -> Result<(), Error> {
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let response = |fingerprint: &'static [u8]| ComputeSingleFingerprintResponse {
+            fingerprint: Some(Fingerprint {
+                fingerprint: fingerprint.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let key = |api_key: &str, idempotency_key: &str| {
+            let mut metadata = MetadataMap::new();
+            metadata.insert(API_KEY, api_key.parse().unwrap());
+            metadata.insert(IDEMPOTENCY_KEY, idempotency_key.parse().unwrap());
+            IdempotencyCache::key(&metadata).unwrap()
+        };
+        assert!(IdempotencyCache::key(&MetadataMap::new()).is_none());
+        assert_ne!(key("bank-a", "1"), key("bank-b", "1"));
+
+        let cache = IdempotencyCache::new(2, Duration::hours(1));
+        cache.insert(key("bank-a", "1"), &response(b"1"), now);
+        cache.insert(key("bank-a", "2"), &response(b"2"), now);
+        assert_eq!(cache.get(&key("bank-a", "1"), now), Some(response(b"1")));
+        assert_eq!(cache.get(&key("bank-b", "1"), now), None);
+        assert_eq!(
+            cache.get(&key("bank-a", "1"), now + Duration::hours(1)),
+            None
+        );
+
+        // The oldest response is evicted
+        cache.insert(key("bank-a", "3"), &response(b"3"), now);
+        assert_eq!(cache.get(&key("bank-a", "1"), now), None);
+        assert_eq!(cache.responses(now).len(), 2);
+
+        let restored = IdempotencyCache::new(2, Duration::hours(1));
+        restored.restore(cache.responses(now))?;
+        assert_eq!(restored.get(&key("bank-a", "3"), now), Some(response(b"3")));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Item ids derived from the transactions, for the callers having no ids of their own
+//!
+//! The id is BLAKE3 of the canonical JSON of the transaction and the salt: the keys sorted, no whitespace, the amounts
+//! as their base and atto integers and the absent fields as `null`. The same transaction and salt get the same id
+//! on the caller and on the service, see `ComputeBatchFingerprintRequest.derive_item_ids`.
+
+use crate::net::outbe::fingerprint::v1::compute_batch_fingerprint_request::Item;
+use chrono::SecondsFormat;
+use fingerprinting_types::{Money, RawTransaction};
+use pilota::FastStr;
+use serde_json::json;
+use std::collections::HashSet;
+use volo_grpc::{Code, Status};
+
+/// Hex of BLAKE3 of the canonical JSON of the transaction and the salt
+pub fn derive_item_id(tx: &RawTransaction, salt: &[u8]) -> String {
+    blake3::hash(canonical_json(tx, salt).as_bytes())
+        .to_hex()
+        .to_string()
+}
+
+fn canonical_json(tx: &RawTransaction, salt: &[u8]) -> String {
+    let money = |money: &Money| {
+        json!({
+            "amount_atto": money.amount_atto,
+            "amount_base": money.amount_base,
+            "currency": money.currency,
+        })
+    };
+    let salt = salt
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    // Keys are listed in their order, whether the map of `serde_json` is sorted or keeps the insertion order
+    let canonical = json!({
+        "amount": money(&tx.amount),
+        "bic": tx.bic,
+        "counter_amount": tx.counter_amount.as_ref().map(money),
+        "counterparty": tx.counterparty.as_ref().map(|counterparty| json!({
+            "payee": counterparty.payee,
+            "payer": counterparty.payer,
+        })),
+        "country_code": tx.country_code,
+        "date_time": tx.date_time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        "mcc": tx.mcc,
+        "merchant_id": tx.merchant_id,
+        "salt": salt,
+        "series_id": tx.series_id,
+        "transaction_reference": tx.transaction_reference,
+        "wwd": tx.wwd.to_string(),
+    });
+
+    canonical.to_string()
+}
+
+/// Gives the items with the empty id the id derived from their transaction, the items which transaction could not be
+/// read are left as they are and fail when computed
+pub(crate) fn derive_item_ids(items: &mut [Item], salt: &[u8]) {
+    for item in items.iter_mut().filter(|item| item.item_id.is_empty()) {
+        let raw_tx: Option<RawTransaction> = item
+            .transaction_data
+            .clone()
+            .and_then(|tx_data| tx_data.try_into().ok());
+        if let Some(raw_tx) = raw_tx {
+            item.item_id = FastStr::new(derive_item_id(&raw_tx, salt));
+        }
+    }
+}
+
+/// Refuses the batch listing the same id more than once, the results streamed in the completion order could not be
+/// correlated with the items then. Items with no id are not checked
+pub(crate) fn check_item_ids(items: &[Item]) -> Result<(), Box<Status>> {
+    let mut item_ids = HashSet::new();
+    match items
+        .iter()
+        .filter(|item| !item.item_id.is_empty())
+        .find(|item| !item_ids.insert(&item.item_id))
+    {
+        Some(item) => Err(Box::new(Status::new(
+            Code::InvalidArgument,
+            format!(
+                "Item id {} is listed more than once in the batch",
+                item.item_id
+            ),
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use fingerprinting_types::{Counterparty, RawTransactionBuilder};
+
+    #[test]
+    fn test_derive_item_id() -> Result<(), anyhow::Error> {
+        let tx = RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount(Money {
+                amount_base: 1000,
+                amount_atto: 550_000_000_000_000_000,
+                currency: "EUR".into(),
+            })
+            .date_time(Utc.with_ymd_and_hms(2025, 9, 16, 12, 30, 15).unwrap())
+            .wwd(NaiveDate::from_ymd_opt(2025, 9, 16).unwrap())
+            .build()?;
+
+        assert_eq!(
+            canonical_json(&tx, b"\x01\xff"),
+            concat!(
+                r#"{"amount":{"amount_atto":550000000000000000,"amount_base":1000,"currency":"EUR"},"#,
+                r#""bic":"BCEELU21","counter_amount":null,"counterparty":null,"country_code":null,"#,
+                r#""date_time":"2025-09-16T12:30:15Z","mcc":null,"merchant_id":null,"salt":"01ff","#,
+                r#""series_id":null,"transaction_reference":null,"wwd":"2025-09-16"}"#
+            )
+        );
+
+        let item_id = derive_item_id(&tx, b"");
+        assert_eq!(item_id.len(), 64);
+        assert_eq!(derive_item_id(&tx.clone(), b""), item_id);
+        assert_ne!(derive_item_id(&tx, b"salt"), item_id);
+        let with_counterparty = RawTransaction {
+            counterparty: Some(Counterparty {
+                payer: "LU280019400644750000".into(),
+                payee: "LU120010001234567891".into(),
+            }),
+            ..tx
+        };
+        assert_ne!(derive_item_id(&with_counterparty, b""), item_id);
+
+        Ok(())
+    }
+}
@@ -2,31 +2,430 @@
 mod generator {
     include!(concat!(env!("OUT_DIR"), "/proto_gen.rs"));
 }
+mod capacity;
+mod idempotency;
+mod item_id;
+mod record;
+mod recording;
+mod residency;
+mod sampling;
+mod scheduler;
+mod shadow;
+mod shedding;
+mod status;
+mod topology;
+mod usage;
+mod verifier;
 
 use crate::net::outbe::fingerprint::v1::{
-    compute_batch_fingerprint_request::Item, ComputeBatchFingerprintRequest, ComputeBatchFingerprintResponse,
-    ComputeSingleFingerprintRequest, ComputeSingleFingerprintResponse,
+    compute_batch_fingerprint_request::Item, BicPseudonym, CheckDuplicateRequest,
+    CheckDuplicateResponse, ComponentCommitments, ComputeBatchFingerprintRequest,
+    ComputeBatchFingerprintResponse, ComputeSingleFingerprintRequest,
+    ComputeSingleFingerprintResponse, DeriveSeriesFingerprintsRequest,
+    DeriveSeriesFingerprintsResponse, DuplicateCheck as DuplicateCheckDto, DuplicateStatus,
+    ExpectedInstallment, Fingerprint as FingerprintDto, FingerprintEncoding,
+    FingerprintStatusUpdate as FingerprintStatusUpdateDto, GetInclusionProofRequest,
+    GetInclusionProofResponse, GetServiceInfoRequest, GetServiceInfoResponse, GetUsageRequest,
+    GetUsageResponse, ItemFailure, KeyUsage, MerkleProofStep, PseudonymizeBicRequest,
+    PseudonymizeBicResponse, ShadowStats as ShadowStatsDto, SubscribeFingerprintStatusRequest,
+    TransactionFingerprintData as TransactionFingerprintDataDto, ValidationVerdict,
 };
-use fingerprinting_core::{Fingerprint, FingerprintProtocol, TransactionFingerprintData};
-use fingerprinting_types::RawTransaction;
-use futures::stream::StreamExt;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use ed25519_dalek::SigningKey;
+use fingerprinting_core::clock::{self, Clock, Epoch};
+use fingerprinting_core::entropy::{self, EntropySource};
+use fingerprinting_core::events::{BroadcastEventBus, EventBus, LifecycleEvent};
+use fingerprinting_core::namespace::Namespace;
+use fingerprinting_core::pseudonym::BicPseudonymizer;
+use fingerprinting_core::schema::{FingerprintSchema, SchemaComponent, BUILT_IN_SCHEMA_ID};
+use fingerprinting_core::series::{Recurrence, Schedule};
+use fingerprinting_core::wire::{TextEncoding, WireVersion};
+use fingerprinting_core::{
+    parameters_digest, wire, Compact, FingerprintProtocol, ProtocolFingerprint, TimeBucket,
+    TransactionFingerprintData, ViaAgents, HASH_TO_CURVE_PREFIX, POSEIDON_FULL_ROUNDS,
+    POSEIDON_PARTIAL_ROUNDS,
+};
+use fingerprinting_store::merkle::{EpochAccumulator, EpochInclusion, EpochProofs};
+use fingerprinting_store::tally::TallyLedger;
+use fingerprinting_store::usage::usage_month;
+use fingerprinting_store::{DuplicateWindow, FingerprintStore, InsertOutcome};
+use fingerprinting_types::{InputLimits, RawTransaction};
+use futures::stream::{StreamExt, TryStreamExt};
 use halo2_axiom::halo2curves::bn256::Fr;
-use std::sync::Arc;
+use pilota::FastStr;
+use scheduler::ComputationScheduler;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::mpsc;
+use usage::CallUsage;
 use volo_grpc::codegen::ReceiverStream;
 use volo_grpc::{BoxStream, Code, Request, Response, Status};
 
+pub use capacity::{ServiceCounters, ServiceStats};
 pub use generator::proto_gen::*; // Reexport only subpackage from `proto_gen`
+pub use idempotency::{CachedResponse, IdempotencyCache, IDEMPOTENCY_KEY};
+pub use item_id::derive_item_id;
+pub use record::{check_record, fingerprint_record, read_records, FingerprintRecordWriter};
+pub use recording::{read_recording, FingerprintRecorder};
+pub use residency::ResidencyRouting;
+pub use sampling::FingerprintSampling;
+pub use scheduler::SchedulerLimits;
+pub use shadow::{ShadowFingerprinting, ShadowStats};
+pub use shedding::{LoadShedding, Priority, RETRY_AFTER};
+pub use status::{
+    FingerprintStatus, FingerprintStatusHub, FingerprintStatusUpdate,
+    DEFAULT_STATUS_SUBSCRIPTION_TIMEOUT,
+};
+pub use topology::TopologyStatus;
+pub use usage::{ApiKey, UsageAccounting, API_KEY};
+pub use verifier::FingerprintVerifierService;
 
 pub struct FingerprintService<P: FingerprintProtocol<Fr>> {
     protocol: Arc<P>,
+    pseudonymizer: Option<BicPseudonymizer>,
+    commitment_key: Option<Arc<SigningKey>>,
+    entropy: Arc<dyn EntropySource>,
+    status_hub: Arc<FingerprintStatusHub>,
+    store: Option<Arc<dyn FingerprintStore>>,
+    archive: Option<Arc<dyn EpochProofs>>,
+    tallies: Option<Arc<dyn TallyLedger>>,
+    accumulator: Option<Arc<Mutex<EpochAccumulator>>>,
+    duplicate_window: DuplicateWindow,
+    namespace: Option<Namespace>,
+    schema: Option<Arc<FingerprintSchema>>,
+    wire_version: WireVersion,
+    epoch: Epoch,
+    fuzzy_bucket: Option<TimeBucket>,
+    input_limits: InputLimits,
+    recorder: Option<Arc<FingerprintRecorder>>,
+    shadow: Option<Arc<ShadowFingerprinting>>,
+    sampling: Option<Arc<FingerprintSampling>>,
+    shedding: Option<Arc<LoadShedding>>,
+    residency: Arc<ResidencyRouting>,
+    scheduler: ComputationScheduler<P>,
+    topology: Option<Arc<TopologyStatus>>,
+    usage: Option<Arc<UsageAccounting>>,
+    idempotency: Option<Arc<IdempotencyCache>>,
+    counters: Arc<ServiceCounters>,
+    events: Arc<dyn EventBus>,
+    clock: Arc<dyn Clock>,
 }
 
-impl<P: FingerprintProtocol<Fr> + Sync> FingerprintService<P> {
+// Current implementation supports only the single secret generation
+pub(crate) const KEY_EPOCH: u64 = 0;
+
+impl<P: FingerprintProtocol<Fr> + Send + Sync + 'static> FingerprintService<P> {
     pub fn new(protocol: P) -> FingerprintService<P> {
+        let protocol = Arc::new(protocol);
         FingerprintService {
-            protocol: Arc::new(protocol),
+            protocol: protocol.clone(),
+            pseudonymizer: None,
+            commitment_key: None,
+            entropy: entropy::default_source(),
+            status_hub: Arc::new(FingerprintStatusHub::default()),
+            store: None,
+            archive: None,
+            tallies: None,
+            accumulator: None,
+            duplicate_window: DuplicateWindow::Unbounded,
+            namespace: None,
+            schema: None,
+            wire_version: WireVersion::CURRENT,
+            epoch: Epoch::default(),
+            fuzzy_bucket: None,
+            input_limits: InputLimits::default(),
+            recorder: None,
+            shadow: None,
+            sampling: None,
+            shedding: None,
+            residency: Default::default(),
+            scheduler: ComputationScheduler::new(protocol, SchedulerLimits::default()),
+            topology: None,
+            usage: None,
+            idempotency: None,
+            counters: Arc::default(),
+            events: Arc::new(BroadcastEventBus::default()),
+            clock: clock::system_clock(),
+        }
+    }
+
+    /// Time of the stored fingerprints and the recorded exchanges, the system time by default
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Fails the computations fast when the service is saturated, see `LoadShedding`
+    pub fn with_load_shedding(mut self, shedding: Option<LoadShedding>) -> Self {
+        self.shedding = shedding.map(Arc::new);
+        self
+    }
+
+    /// Limits of the work queue the protocol rounds of all the requests are computed from, see `SchedulerLimits`
+    pub fn with_scheduler(mut self, limits: SchedulerLimits) -> Self {
+        self.scheduler = ComputationScheduler::new(self.protocol.clone(), limits);
+        self
+    }
+
+    /// Members of the topology reported by `GetServiceInfo`, see `TopologyStatus`
+    pub fn with_topology(mut self, topology: Option<TopologyStatus>) -> Self {
+        self.topology = topology.map(Arc::new);
+        self
+    }
+
+    /// Accounts the calls by the API keys of the callers and enforces their quotas, see `UsageAccounting`.
+    /// Calls are not authenticated without the accounting
+    pub fn with_usage(mut self, usage: Option<UsageAccounting>) -> Self {
+        self.usage = usage.map(Arc::new);
+        self
+    }
+
+    /// Answers the retried calls by their idempotency keys with the responses of the completed ones,
+    /// see `IdempotencyCache`
+    pub fn with_idempotency(mut self, idempotency: Option<Arc<IdempotencyCache>>) -> Self {
+        self.idempotency = idempotency;
+        self
+    }
+
+    /// Agents the tagged transactions are computed via, see `ResidencyRouting`.
+    /// Tagged transactions are refused without the routing
+    pub fn with_residency(mut self, residency: ResidencyRouting) -> Self {
+        self.residency = Arc::new(residency);
+        self
+    }
+
+    /// Recomputes the sample of the fingerprints with the reference implementation, see `FingerprintSampling`
+    pub fn with_sampling(mut self, sampling: Option<FingerprintSampling>) -> Self {
+        self.sampling = sampling.map(Arc::new);
+        self
+    }
+
+    /// Computes every fingerprint once more with the candidate configuration, see `ShadowFingerprinting`
+    pub fn with_shadow(mut self, shadow: Option<ShadowFingerprinting>) -> Self {
+        self.shadow = shadow.map(Arc::new);
+        self
+    }
+
+    /// Records the unsalted exchanges for the debugging, see `FingerprintRecorder`
+    pub fn with_recorder(mut self, recorder: Option<Arc<FingerprintRecorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Separates computed fingerprints into the namespace of the environment
+    pub fn with_namespace(mut self, namespace: Option<Namespace>) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Binds the optional components by the schema of the deployment, the built-in layout when absent
+    pub fn with_schema(mut self, schema: Option<Arc<FingerprintSchema>>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Lays the preimages out by the wire version the fingerprints are exchanged with, the current one by default
+    pub fn with_wire_version(mut self, version: WireVersion) -> Self {
+        self.wire_version = version;
+        self
+    }
+
+    /// Offsets the date components from the `epoch`, e.g. the earlier one of the back-fills, 01.01.2025 by default
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    /// Returns the fuzzy fingerprint of the date time truncated to the `bucket` alongside every computed one,
+    /// see `fingerprinting_core::fuzzy`
+    pub fn with_fuzzy_bucket(mut self, bucket: Option<TimeBucket>) -> Self {
+        self.fuzzy_bucket = bucket;
+        self
+    }
+
+    /// Refuses the transactions with the string fields above the `limits`, the sizes the components encode by default
+    pub fn with_input_limits(mut self, limits: InputLimits) -> Self {
+        self.input_limits = limits;
+        self
+    }
+
+    /// Enables persisting of computed fingerprints with duplicates detection
+    pub fn with_store(mut self, store: Option<Arc<dyn FingerprintStore>>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Archived epochs the inclusion proofs of the fingerprints are served from, see `EpochProofs`
+    pub fn with_archive(mut self, archive: Option<Arc<dyn EpochProofs>>) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Counts the new fingerprints to the statistics of their epochs by the currency, see `TallyLedger`
+    pub fn with_tallies(mut self, tallies: Option<Arc<dyn TallyLedger>>) -> Self {
+        self.tallies = tallies;
+        self
+    }
+
+    /// Accumulates the new fingerprints into the tree of their epoch, see `EpochAccumulator`
+    pub fn with_accumulator(mut self, accumulator: Option<Arc<Mutex<EpochAccumulator>>>) -> Self {
+        self.accumulator = accumulator;
+        self
+    }
+
+    /// Sets the window repeated fingerprints are considered as duplicates within
+    pub fn with_duplicate_window(mut self, window: DuplicateWindow) -> Self {
+        self.duplicate_window = window;
+        self
+    }
+
+    /// Counters of the served fingerprints, shared with the capacity log, see `ServiceCounters`
+    pub fn with_counters(mut self, counters: Arc<ServiceCounters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    pub fn counters(&self) -> Arc<ServiceCounters> {
+        self.counters.clone()
+    }
+
+    /// Bus the computed fingerprints and the detected duplicates are published to, see `EventBus`
+    pub fn with_events(mut self, events: Arc<dyn EventBus>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn events(&self) -> Arc<dyn EventBus> {
+        self.events.clone()
+    }
+
+    /// Hub the status updates are published to, shared with the archiver anchoring the fingerprints
+    pub fn with_status_hub(mut self, status_hub: Arc<FingerprintStatusHub>) -> Self {
+        self.status_hub = status_hub;
+        self
+    }
+
+    /// Hub for publishing fingerprint status updates to the subscribers
+    pub fn status_hub(&self) -> Arc<FingerprintStatusHub> {
+        self.status_hub.clone()
+    }
+
+    /// Enables BIC pseudonymization with the separate pseudonymization key
+    pub fn with_pseudonymizer(mut self, pseudonymizer: Option<BicPseudonymizer>) -> Self {
+        self.pseudonymizer = pseudonymizer;
+        self
+    }
+
+    /// Signs the commitments to the components with the `key`, binding them to the fingerprint.
+    /// Commitments are refused without the key
+    pub fn with_commitment_key(mut self, key: Option<SigningKey>) -> Self {
+        self.commitment_key = key.map(Arc::new);
+        self
+    }
+
+    /// Source of the openings of the commitments, the operating system by default
+    pub fn with_entropy(mut self, entropy: Arc<dyn EntropySource>) -> Self {
+        self.entropy = entropy;
+        self
+    }
+
+    async fn compute_single(
+        &self,
+        request: ComputeSingleFingerprintRequest,
+        call: &CallUsage,
+    ) -> Result<(Fr, ComputeSingleFingerprintResponse), Box<Status>> {
+        let encodings = Encodings::new(&request.encodings)?;
+        let commitment_key =
+            requested_commitment_key(self.commitment_key.as_deref(), request.with_commitments)?;
+        let tx_data = request.transaction_data.ok_or(Status::new(
+            Code::InvalidArgument,
+            "Transaction data missing",
+        ))?;
+        let agents = self
+            .residency
+            .agents(&tx_data.residency)?
+            .map(<[usize]>::to_vec);
+        let recorded = recorded_data(self.recorder.as_deref(), &tx_data, &request.salt);
+        let raw_tx: RawTransaction = tx_data.try_into()?;
+        check_input_sizes(&raw_tx, &self.input_limits)?;
+        let shadowed = shadowed_data(self.shadow.as_deref(), &raw_tx, &request.salt);
+        let sampled = sampled_data(self.sampling.as_deref(), &raw_tx, &request.salt);
+
+        // preparing TransactionFingerprintData
+        let raw_tx: TransactionFingerprintData<Fr> = raw_tx.try_into().map_err(Status::from)?;
+        let raw_tx = raw_tx
+            .with_namespace(self.namespace.clone())
+            .with_schema(self.schema.clone())
+            .with_wire_version(self.wire_version)
+            .with_epoch(self.epoch);
+        let raw_tx = apply_salt(raw_tx, request.salt)?;
+
+        let slot = shedding::admit(self.shedding.as_deref(), Priority::Interactive).await?;
+        // using the provided protocol built the fingerprint
+        let lane = self.scheduler.request();
+        let (fingerprint, fuzzy) = complete_fingerprint(
+            &raw_tx,
+            &lane,
+            agents.as_deref(),
+            self.fuzzy_bucket,
+            &self.counters,
+            call,
+            self.events.as_ref(),
+            self.clock.as_ref(),
+        )
+        .await?;
+        drop(slot);
+
+        let now = self.clock.now();
+        let duplicate = store_fingerprint(
+            self.store.as_deref(),
+            self.duplicate_window,
+            &self.status_hub,
+            &self.counters,
+            self.events.as_ref(),
+            fingerprint,
+            now,
+        )
+        .await?;
+        record_tally(
+            self.tallies.as_deref(),
+            duplicate.as_ref(),
+            raw_tx.currency_code(),
+            now,
+        )
+        .await;
+        accumulate(
+            self.accumulator.as_deref(),
+            duplicate.as_ref(),
+            fingerprint,
+            now,
+        );
+
+        let commitments =
+            commit_components(&raw_tx, fingerprint, commitment_key, self.entropy.as_ref())?;
+
+        let dto = fingerprint_dto(fingerprint, self.namespace.as_ref());
+        if let (Some(recorder), Some(recorded)) = (&self.recorder, recorded) {
+            recorder.record(&recorded, &dto, self.clock.now());
         }
+        if let (Some(shadow), Some((raw_tx, salt))) = (&self.shadow, shadowed) {
+            shadow.compare(raw_tx, salt, fingerprint);
+        }
+        if let (Some(sampling), Some((raw_tx, salt))) = (&self.sampling, sampled) {
+            sampling.verify(raw_tx, salt, fingerprint);
+        }
+
+        let response = ComputeSingleFingerprintResponse {
+            fingerprint: Some(encodings.encode(dto)),
+            commitments,
+            duplicate,
+            validation: None,
+            fuzzy_fingerprint: fuzzy
+                .map(|fuzzy| encodings.encode(fingerprint_dto(fuzzy, self.namespace.as_ref()))),
+            _unknown_fields: Default::default(),
+        };
+
+        Ok((fingerprint, response))
     }
 }
 
@@ -37,34 +436,167 @@ impl<P: FingerprintProtocol<Fr> + Send + Sync + 'static>
         &self,
         req: Request<ComputeSingleFingerprintRequest>,
     ) -> Result<Response<ComputeSingleFingerprintResponse>, Status> {
+        let now = self.clock.now();
+        let idempotency_key = self
+            .idempotency
+            .as_ref()
+            .filter(|_| !req.get_ref().validate_only)
+            .and_then(|_| IdempotencyCache::key(req.metadata()));
+        // Retried call is authenticated but neither computed nor counted again
+        let replayed = self
+            .idempotency
+            .as_ref()
+            .zip(idempotency_key.as_ref())
+            .and_then(|(idempotency, idempotency_key)| idempotency.get(idempotency_key, now));
+        if let Some(response) = replayed {
+            usage::admit(self.usage.as_deref(), req.metadata(), 0, now)
+                .await
+                .map_err(|status| *status)?;
+            return Ok(Response::new(response));
+        }
+
+        let fingerprints = if req.get_ref().validate_only { 0 } else { 1 };
+        let key = usage::admit(self.usage.as_deref(), req.metadata(), fingerprints, now)
+            .await
+            .map_err(|status| *status)?;
         let request = req.into_inner();
-        let tx_data = request.transaction_data.ok_or(Status::new(
-            Code::InvalidArgument,
-            "Transaction data missing",
-        ))?;
-        let raw_tx: RawTransaction = tx_data.try_into()?;
+        if request.validate_only {
+            let validation = validation_verdict(
+                request.transaction_data,
+                request.salt,
+                &self.residency,
+                &self.input_limits,
+                self.schema.clone(),
+            );
+            return Ok(Response::new(ComputeSingleFingerprintResponse {
+                validation: Some(validation),
+                ..Default::default()
+            }));
+        }
 
-        // preparing TransactionFingerprintData
-        let raw_tx: TransactionFingerprintData<Fr> = raw_tx.try_into()?;
+        let call = CallUsage::default();
+        let computed = self.compute_single(request, &call).await;
+        usage::record(self.usage.as_deref(), key.as_ref(), &call, now).await;
+        let (_, response) = computed.map_err(|status| *status)?;
+        if let (Some(idempotency), Some(idempotency_key)) = (&self.idempotency, idempotency_key) {
+            idempotency.insert(idempotency_key, &response, now);
+        }
 
-        // using the provided protocol built the fingerprint
-        let fingerprint = raw_tx
-            .complete_fingerprint(self.protocol.as_ref())
+        Ok(Response::new(response))
+    }
+
+    async fn compute_single_fingerprint_and_subscribe(
+        &self,
+        req: Request<ComputeSingleFingerprintRequest>,
+    ) -> Result<Response<BoxStream<'static, Result<FingerprintStatusUpdateDto, Status>>>, Status>
+    {
+        let now = self.clock.now();
+        let key = usage::admit(self.usage.as_deref(), req.metadata(), 1, now)
+            .await
+            .map_err(|status| *status)?;
+        let request = req.into_inner();
+        if request.validate_only {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "Validation only requests have no status updates to subscribe to",
+            ));
+        }
+
+        // Subscribe before the computation, so no updates published right after it are lost
+        let updates = self.status_hub.subscribe();
+        let subscription_timeout = self.status_hub.subscription_timeout();
+        let namespace = self.namespace.clone();
+
+        let call = CallUsage::default();
+        let computed = self.compute_single(request, &call).await;
+        usage::record(self.usage.as_deref(), key.as_ref(), &call, now).await;
+        let (fingerprint, _) = computed.map_err(|status| *status)?;
+
+        let computed = FingerprintStatusUpdate {
+            fingerprint,
+            status: FingerprintStatus::Computed,
+            epoch_root: None,
+        };
+
+        Ok(Response::new(forward_status_updates(
+            vec![computed],
+            updates,
+            subscription_timeout,
+            namespace,
+        )))
+    }
+
+    async fn subscribe_fingerprint_status(
+        &self,
+        req: Request<SubscribeFingerprintStatusRequest>,
+    ) -> Result<Response<BoxStream<'static, Result<FingerprintStatusUpdateDto, Status>>>, Status>
+    {
+        usage::admit(self.usage.as_deref(), req.metadata(), 0, self.clock.now())
+            .await
+            .map_err(|status| *status)?;
+        let store = self.store.as_ref().ok_or(Status::new(
+            Code::FailedPrecondition,
+            "Fingerprints store is not configured",
+        ))?;
+        let fingerprint = wire::decode_scalar(&req.into_inner().fingerprint).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "Fingerprint should be 32 bytes representation of the field element",
+            )
+        })?;
+
+        // Subscribe before the stored status is read, so no updates published in between are lost
+        let updates = self.status_hub.subscribe();
+        let stored = store
+            .lookup(fingerprint, KEY_EPOCH)
             .await
             .map_err(|e| {
                 Status::new(
-                    Code::Aborted,
-                    format!("Failed to complete fingerprint computation: {}", e),
+                    Code::Unavailable,
+                    format!("Failed to look up fingerprint: {}", e),
                 )
             })?
-            .into();
+            .ok_or(Status::new(Code::NotFound, "Fingerprint is not stored"))?;
 
-        let response = ComputeSingleFingerprintResponse {
-            fingerprint: Some(fingerprint),
-            _unknown_fields: Default::default(),
-        };
+        let mut replayed = vec![FingerprintStatusUpdate {
+            fingerprint,
+            status: match stored.occurrences {
+                0 | 1 => FingerprintStatus::Stored,
+                _ => FingerprintStatus::DuplicateFound,
+            },
+            epoch_root: None,
+        }];
+        // Archived epoch of the first occurrence is the persisted anchored status
+        if let Some(archive) = &self.archive {
+            let inclusion = archive
+                .inclusion(stored.first_seen.date_naive(), fingerprint, KEY_EPOCH)
+                .await
+                .map_err(|e| {
+                    Status::new(
+                        Code::Unavailable,
+                        format!("Failed to read the archive: {}", e),
+                    )
+                })?;
+            if let Some(EpochInclusion {
+                root,
+                proof: Some(_),
+                ..
+            }) = inclusion
+            {
+                replayed.push(FingerprintStatusUpdate {
+                    fingerprint,
+                    status: FingerprintStatus::Anchored,
+                    epoch_root: Some(root),
+                });
+            }
+        }
 
-        Ok(Response::new(response))
+        Ok(Response::new(forward_status_updates(
+            replayed,
+            updates,
+            self.status_hub.subscription_timeout(),
+            self.namespace.clone(),
+        )))
     }
 
     async fn compute_batch_fingerprint(
@@ -72,47 +604,223 @@ impl<P: FingerprintProtocol<Fr> + Send + Sync + 'static>
         req: Request<ComputeBatchFingerprintRequest>,
     ) -> Result<Response<BoxStream<'static, Result<ComputeBatchFingerprintResponse, Status>>>, Status>
     {
-        let request = req.into_inner();
+        let now = self.clock.now();
+        let fingerprints = match req.get_ref().validate_only {
+            true => 0,
+            false => req.get_ref().transaction_batch.len() as u64,
+        };
+        let key = usage::admit(self.usage.as_deref(), req.metadata(), fingerprints, now)
+            .await
+            .map_err(|status| *status)?;
+        let mut request = req.into_inner();
+        if request.derive_item_ids {
+            item_id::derive_item_ids(&mut request.transaction_batch, &request.salt);
+        }
+        item_id::check_item_ids(&request.transaction_batch).map_err(|status| *status)?;
         let tx_data = request.transaction_batch;
-        let protocol = self.protocol.clone();
+        let salt = request.salt;
+        let with_commitments = request.with_commitments;
+        let dedup = request.dedup;
+        let validate_only = request.validate_only;
+        let dead_letter = request.dead_letter;
+        let encodings = Encodings::new(&request.encodings).map_err(|status| *status)?;
+        if dedup && self.store.is_none() {
+            return Err(Status::new(
+                Code::FailedPrecondition,
+                "Fingerprints store is not configured, batch can not be deduplicated",
+            ));
+        }
+        requested_commitment_key(self.commitment_key.as_deref(), with_commitments)
+            .map_err(|status| *status)?;
+        let commitment_key = self.commitment_key.clone().filter(|_| with_commitments);
+        let entropy = self.entropy.clone();
+        // All the items of the batch share the lane of the request, keeping no more of them in flight than the workers
+        // of the scheduler take at once
+        let lane = Arc::new(self.scheduler.request());
+        let depth = self.scheduler.limits().lane_depth();
+        let status_hub = self.status_hub.clone();
+        let store = self.store.clone();
+        let tallies = self.tallies.clone();
+        let accumulator = self.accumulator.clone();
+        let duplicate_window = self.duplicate_window;
+        let namespace = self.namespace.clone();
+        let schema = self.schema.clone();
+        let wire_version = self.wire_version;
+        let epoch = self.epoch;
+        let fuzzy_bucket = self.fuzzy_bucket;
+        let input_limits = self.input_limits;
+        let recorder = self.recorder.clone();
+        let shadow = self.shadow.clone();
+        let sampling = self.sampling.clone();
+        let shedding = self.shedding.clone();
+        let residency = self.residency.clone();
+        let counters = self.counters.clone();
+        let events = self.events.clone();
+        let clock = self.clock.clone();
+        let accounting = self.usage.clone();
+        let call = Arc::new(CallUsage::default());
+        let item_call = call.clone();
 
         let mut stream = futures::stream::iter(tx_data)
             .map(move |item: Item| {
-                let protocol = protocol.clone();
-                async move {
+                let lane = lane.clone();
+                let commitment_key = commitment_key.clone();
+                let entropy = entropy.clone();
+                let status_hub = status_hub.clone();
+                let store = store.clone();
+                let tallies = tallies.clone();
+                let accumulator = accumulator.clone();
+                let salt = salt.clone();
+                let namespace = namespace.clone();
+                let schema = schema.clone();
+                let recorder = recorder.clone();
+                let shadow = shadow.clone();
+                let sampling = sampling.clone();
+                let shedding = shedding.clone();
+                let residency = residency.clone();
+                let counters = counters.clone();
+                let events = events.clone();
+                let clock = clock.clone();
+                let call = item_call.clone();
+                let failed_id = dead_letter.then(|| item.item_id.clone());
+                let computed = async move {
                     let item_id = item.item_id;
+                    if validate_only {
+                        let validation = validation_verdict(
+                            item.transaction_data,
+                            salt,
+                            &residency,
+                            &input_limits,
+                            schema,
+                        );
+                        return Ok(ComputeBatchFingerprintResponse {
+                            item_id,
+                            validation: Some(validation),
+                            ..Default::default()
+                        });
+                    }
+
                     let raw_tx = item.transaction_data.ok_or(Status::new(
                         Code::InvalidArgument,
                         "Transaction data missing",
                     ))?;
+                    let agents = residency.agents(&raw_tx.residency)?.map(<[usize]>::to_vec);
+                    let recorded = recorded_data(recorder.as_deref(), &raw_tx, &salt);
 
                     let raw_tx: RawTransaction = raw_tx.try_into()?;
+                    check_input_sizes(&raw_tx, &input_limits)?;
+                    let shadowed = shadowed_data(shadow.as_deref(), &raw_tx, &salt);
+                    let sampled = sampled_data(sampling.as_deref(), &raw_tx, &salt);
 
                     // preparing TransactionFingerprintData
-                    let raw_tx: TransactionFingerprintData<Fr> = raw_tx.try_into()?;
+                    let raw_tx: TransactionFingerprintData<Fr> =
+                        raw_tx.try_into().map_err(Status::from)?;
+                    let raw_tx = raw_tx
+                        .with_namespace(namespace.clone())
+                        .with_schema(schema.clone())
+                        .with_wire_version(wire_version)
+                        .with_epoch(epoch);
+                    let raw_tx = apply_salt(raw_tx, salt)?;
 
+                    let slot = shedding::admit(shedding.as_deref(), Priority::Batch).await?;
                     // using the provided protocol built the fingerprint
-                    let fingerprint = raw_tx
-                        .complete_fingerprint(protocol.as_ref())
-                        .await
-                        .map_err(|e| {
-                            Status::new(
-                                Code::Aborted,
-                                format!("Failed to complete fingerprint computation: {}", e),
-                            )
-                        })?
-                        .into();
-
-                    Ok(ComputeBatchFingerprintResponse {
+                    let (fingerprint, fuzzy) = complete_fingerprint(
+                        &raw_tx,
+                        lane.as_ref(),
+                        agents.as_deref(),
+                        fuzzy_bucket,
+                        &counters,
+                        &call,
+                        events.as_ref(),
+                        clock.as_ref(),
+                    )
+                    .await?;
+                    drop(slot);
+
+                    let now = clock.now();
+                    let duplicate = store_fingerprint(
+                        store.as_deref(),
+                        duplicate_window,
+                        &status_hub,
+                        &counters,
+                        events.as_ref(),
+                        fingerprint,
+                        now,
+                    )
+                    .await?;
+                    record_tally(
+                        tallies.as_deref(),
+                        duplicate.as_ref(),
+                        raw_tx.currency_code(),
+                        now,
+                    )
+                    .await;
+                    accumulate(accumulator.as_deref(), duplicate.as_ref(), fingerprint, now);
+
+                    let commitments = commit_components(
+                        &raw_tx,
+                        fingerprint,
+                        commitment_key.as_deref(),
+                        entropy.as_ref(),
+                    )?;
+
+                    // Store is checked by the insertion, so the duplicates within the batch are seen as well
+                    let (seen_before, first_seen) = match duplicate.as_ref().filter(|_| dedup) {
+                        Some(duplicate) => match duplicate.status {
+                            DuplicateStatus::DUPLICATE_STATUS_DUPLICATE
+                            | DuplicateStatus::DUPLICATE_STATUS_RECURRING => {
+                                (true, duplicate.first_seen.clone())
+                            }
+                            _ => (false, None),
+                        },
+                        None => (false, None),
+                    };
+
+                    let dto = fingerprint_dto(fingerprint, namespace.as_ref());
+                    if let (Some(recorder), Some(recorded)) = (&recorder, recorded) {
+                        recorder.record(&recorded, &dto, clock.now());
+                    }
+                    if let (Some(shadow), Some((raw_tx, salt))) = (&shadow, shadowed) {
+                        shadow.compare(raw_tx, salt, fingerprint);
+                    }
+                    if let (Some(sampling), Some((raw_tx, salt))) = (&sampling, sampled) {
+                        sampling.verify(raw_tx, salt, fingerprint);
+                    }
+
+                    Ok::<_, Box<Status>>(ComputeBatchFingerprintResponse {
                         item_id,
-                        fingerprint: Some(fingerprint),
+                        fingerprint: Some(encodings.encode(dto)),
+                        commitments,
+                        duplicate,
+                        seen_before,
+                        first_seen,
+                        validation: None,
+                        failure: None,
+                        fuzzy_fingerprint: fuzzy.map(|fuzzy| {
+                            encodings.encode(fingerprint_dto(fuzzy, namespace.as_ref()))
+                        }),
                         _unknown_fields: Default::default(),
                     })
+                };
+
+                async move {
+                    match (computed.await, failed_id) {
+                        (Err(status), Some(item_id)) => Ok(ComputeBatchFingerprintResponse {
+                            item_id,
+                            failure: Some(ItemFailure {
+                                code: status.code() as i32,
+                                message: FastStr::new(status.message()),
+                                _unknown_fields: Default::default(),
+                            }),
+                            ..Default::default()
+                        }),
+                        (response, _) => response.map_err(|status| *status),
+                    }
                 }
             })
-            .buffer_unordered(16);
+            .buffer_unordered(depth);
 
-        let (tx, rx) = mpsc::channel(16);
+        let (tx, rx) = mpsc::channel(depth);
 
         tokio::spawn(async move {
             while let Some(resp) = stream.next().await {
@@ -123,175 +831,2389 @@ impl<P: FingerprintProtocol<Fr> + Send + Sync + 'static>
                     }
                 }
             }
+            usage::record(accounting.as_deref(), key.as_ref(), &call, now).await;
         });
 
         Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
     }
-}
 
-mod dto_convert {
-    use crate::net;
-    use anyhow::anyhow;
-    use chrono::{DateTime, NaiveDate, Utc};
-    use fingerprinting_core::Compact;
-    use fingerprinting_types::{Money, RawTransaction, RawTransactionBuilder};
-    use halo2_axiom::halo2curves::bn256::Fr;
-    use pilota::FastStr;
-    use volo_grpc::{Code, Status};
+    async fn check_duplicate(
+        &self,
+        req: Request<CheckDuplicateRequest>,
+    ) -> Result<Response<CheckDuplicateResponse>, Status> {
+        usage::admit(self.usage.as_deref(), req.metadata(), 0, self.clock.now())
+            .await
+            .map_err(|status| *status)?;
+        let store = self.store.as_ref().ok_or(Status::new(
+            Code::FailedPrecondition,
+            "Fingerprints store is not configured",
+        ))?;
 
-    impl TryInto<DateTime<Utc>> for net::outbe::common::v1::Timestamp {
-        type Error = anyhow::Error;
+        let now = self.clock.now();
+        let mut checks = vec![];
 
-        fn try_into(self) -> Result<DateTime<Utc>, Self::Error> {
-            DateTime::from_timestamp(self.seconds as i64, self.nanos)
-                .ok_or(anyhow!("Timestamp is not valid"))
-        }
-    }
+        for fingerprint in req.into_inner().fingerprints {
+            let fingerprint = wire::decode_scalar(&fingerprint).map_err(|_| {
+                Status::new(
+                    Code::InvalidArgument,
+                    "Fingerprint should be 32 bytes representation of the field element",
+                )
+            })?;
 
-    impl TryInto<NaiveDate> for net::outbe::common::v1::Date {
-        type Error = anyhow::Error;
+            let check = store
+                .check(fingerprint, KEY_EPOCH, now, self.duplicate_window)
+                .await
+                .map_err(|e| {
+                    Status::new(
+                        Code::Unavailable,
+                        format!("Failed to check fingerprint: {}", e),
+                    )
+                })?;
 
-        fn try_into(self) -> Result<NaiveDate, Self::Error> {
-            NaiveDate::from_ymd_opt(self.year as i32, self.month, self.day)
-                .ok_or(anyhow!("Invalid or out-of-range date"))
+            checks.push(check.into());
         }
+
+        Ok(Response::new(CheckDuplicateResponse {
+            checks,
+            _unknown_fields: Default::default(),
+        }))
     }
 
-    impl TryInto<Money> for net::outbe::common::v1::Money {
-        type Error = anyhow::Error;
+    async fn pseudonymize_bic(
+        &self,
+        req: Request<PseudonymizeBicRequest>,
+    ) -> Result<Response<PseudonymizeBicResponse>, Status> {
+        usage::admit(self.usage.as_deref(), req.metadata(), 0, self.clock.now())
+            .await
+            .map_err(|status| *status)?;
+        let pseudonymizer = self.pseudonymizer.as_ref().ok_or(Status::new(
+            Code::FailedPrecondition,
+            "BIC pseudonymization key is not configured",
+        ))?;
 
-        fn try_into(self) -> Result<Money, Self::Error> {
-            let currency = self.currency.to_string();
-            let currency = currency
-                .strip_prefix("CURRENCY_")
-                .ok_or(anyhow!("Provided invalid currency {}", currency))?
-                .to_string();
+        let pseudonyms = req
+            .into_inner()
+            .bics
+            .into_iter()
+            .map(|bic| {
+                let pseudonym = pseudonymizer.pseudonymize(&bic).map_err(|e| {
+                    Box::new(Status::new(
+                        Code::InvalidArgument,
+                        format!("Failed to pseudonymize BIC: {}", e),
+                    ))
+                })?;
 
-            Ok(Money {
-                amount_base: self.units,
-                amount_atto: self.atto,
-                currency
+                Ok(BicPseudonym {
+                    bic,
+                    pseudonym: pilota::Bytes::copy_from_slice(&wire::encode_scalar(&pseudonym)),
+                    compact_pseudonym: FastStr::new(pseudonym.compact()),
+                    _unknown_fields: Default::default(),
+                })
             })
-        }
+            .collect::<Result<Vec<_>, Box<Status>>>()
+            .map_err(|status| *status)?;
+
+        Ok(Response::new(PseudonymizeBicResponse {
+            pseudonyms,
+            _unknown_fields: Default::default(),
+        }))
     }
 
-    impl TryInto<RawTransaction> for net::outbe::fingerprint::v1::TransactionFingerprintData {
-        type Error = Status;
+    async fn derive_series_fingerprints(
+        &self,
+        req: Request<DeriveSeriesFingerprintsRequest>,
+    ) -> Result<Response<DeriveSeriesFingerprintsResponse>, Status> {
+        let now = self.clock.now();
+        let installments = req.get_ref().installments as u64;
+        let key = usage::admit(self.usage.as_deref(), req.metadata(), installments, now)
+            .await
+            .map_err(|status| *status)?;
+        let request = req.into_inner();
+        let encodings = Encodings::new(&request.encodings).map_err(|status| *status)?;
+        let tx_data = request.transaction_data.ok_or(Status::new(
+            Code::InvalidArgument,
+            "Transaction data missing",
+        ))?;
+        let agents = self
+            .residency
+            .agents(&tx_data.residency)
+            .map_err(|status| *status)?
+            .map(<[usize]>::to_vec);
+        let first: RawTransaction = tx_data.try_into()?;
+        check_input_sizes(&first, &self.input_limits).map_err(|status| *status)?;
 
-        fn try_into(self) -> Result<RawTransaction, Self::Error> {
-            let tx_date_time = self.date_time.ok_or(Status::new(
-                Code::InvalidArgument,
-                "Transaction date time information is missing",
-            ))?;
-            let tx_wwd = self.wwd.ok_or(Status::new(
-                Code::InvalidArgument,
-                "Transaction WWD is missing",
-            ))?;
-            let tx_amount = self.amount.ok_or(Status::new(
-                Code::InvalidArgument,
-                "Transaction amount is missing",
-            ))?;
+        let recurrence = match (request.interval_days, request.interval_months) {
+            (days, 0) if days > 0 => Recurrence::Days(days),
+            (0, months) if months > 0 => Recurrence::Months(months),
+            _ => {
+                return Err(Status::new(
+                    Code::InvalidArgument,
+                    "Exactly one of interval_days and interval_months should be set",
+                ))
+            }
+        };
+        let installments = Schedule::new(recurrence, request.installments as usize)
+            .and_then(|schedule| schedule.installments(&first))
+            .map_err(|e| Status::new(Code::InvalidArgument, format!("Invalid schedule: {}", e)))?;
 
-            let date_time: DateTime<Utc> = tx_date_time.try_into()?;
-            let wwd: NaiveDate = tx_wwd.try_into()?;
-            let amount: Money = tx_amount.try_into()?;
+        let lane = self.scheduler.request();
+        let depth = self.scheduler.limits().lane_depth();
+        let call = CallUsage::default();
+        let installments = futures::stream::iter(installments.into_iter().enumerate())
+            .map(|(sequence, installment)| {
+                let salt = request.salt.clone();
+                let agents = agents.as_deref();
+                let lane = &lane;
+                let call = &call;
+                async move {
+                    let (date_time, wwd) = (installment.date_time, installment.wwd);
+                    let tx: TransactionFingerprintData<Fr> =
+                        installment.try_into().map_err(Status::from)?;
+                    let tx = tx
+                        .with_namespace(self.namespace.clone())
+                        .with_schema(self.schema.clone())
+                        .with_wire_version(self.wire_version)
+                        .with_epoch(self.epoch);
+                    let tx = apply_salt(tx, salt)?;
 
-            let raw_tx = RawTransactionBuilder::default()
-                .bic(self.bic)
-                .date_time(date_time)
-                .wwd(wwd)
-                .amount(amount)
-                .build()
-                .map_err(|e| {
-                    Status::new(
-                        Code::InvalidArgument,
-                        format!("Failed to build transaction: {}", e),
+                    let _slot = shedding::admit(self.shedding.as_deref(), Priority::Batch).await?;
+                    let (fingerprint, _) = complete_fingerprint(
+                        &tx,
+                        lane,
+                        agents,
+                        None,
+                        &self.counters,
+                        call,
+                        self.events.as_ref(),
+                        self.clock.as_ref(),
                     )
-                })?;
+                    .await?;
 
-            Ok(raw_tx)
-        }
+                    Ok::<_, Box<Status>>(ExpectedInstallment {
+                        sequence: sequence as u32,
+                        date_time: Some(date_time.into()),
+                        wwd: Some(wwd.into()),
+                        fingerprint: Some(
+                            encodings.encode(fingerprint_dto(fingerprint, self.namespace.as_ref())),
+                        ),
+                        _unknown_fields: Default::default(),
+                    })
+                }
+            })
+            .buffered(depth)
+            .try_collect()
+            .await;
+        usage::record(self.usage.as_deref(), key.as_ref(), &call, now).await;
+        let installments = installments.map_err(|status| *status)?;
+
+        Ok(Response::new(DeriveSeriesFingerprintsResponse {
+            installments,
+            _unknown_fields: Default::default(),
+        }))
     }
 
-    impl From<Fr> for net::outbe::fingerprint::v1::Fingerprint {
-        fn from(value: Fr) -> Self {
-            net::outbe::fingerprint::v1::Fingerprint {
-                fingerprint: pilota::Bytes::copy_from_slice(value.to_bytes().as_slice()),
-                compact_fingerprint: FastStr::new(value.compact()),
-                _unknown_fields: Default::default(),
+    async fn get_service_info(
+        &self,
+        _req: Request<GetServiceInfoRequest>,
+    ) -> Result<Response<GetServiceInfoResponse>, Status> {
+        Ok(Response::new(service_info(
+            self.namespace.as_ref(),
+            self.schema.as_deref(),
+            self.wire_version,
+            self.epoch,
+            self.shadow.as_ref().map(|shadow| shadow.stats()),
+            self.sampling.as_ref().map(|sampling| sampling.stats()),
+            self.topology.as_deref(),
+        )))
+    }
+
+    async fn get_usage(
+        &self,
+        req: Request<GetUsageRequest>,
+    ) -> Result<Response<GetUsageResponse>, Status> {
+        let accounting = self.usage.as_ref().ok_or(Status::new(
+            Code::FailedPrecondition,
+            "Usage accounting is not configured",
+        ))?;
+        let now = self.clock.now();
+        let key = accounting
+            .admit(req.metadata(), 0, now)
+            .await
+            .map_err(|status| *status)?;
+
+        let month = match req.get_ref().month.as_str() {
+            "" => usage_month(now),
+            month => parse_month(month).map_err(|status| *status)?,
+        };
+        let usages = match key.admin {
+            true => accounting.report(month).await,
+            false => accounting
+                .usage(&key.name, month)
+                .await
+                .map(|usage| vec![(key.name.clone(), usage)]),
+        }
+        .map_err(|status| *status)?;
+
+        Ok(Response::new(GetUsageResponse {
+            month: format!("{:04}-{:02}", month.year(), month.month()).into(),
+            keys: usages
+                .into_iter()
+                .map(|(name, usage)| KeyUsage {
+                    monthly_quota: accounting.monthly_quota(&name).unwrap_or_default(),
+                    name: name.into(),
+                    fingerprints: usage.fingerprints,
+                    agent_rounds: usage.agent_rounds,
+                    _unknown_fields: Default::default(),
+                })
+                .collect(),
+            _unknown_fields: Default::default(),
+        }))
+    }
+
+    async fn get_inclusion_proof(
+        &self,
+        req: Request<GetInclusionProofRequest>,
+    ) -> Result<Response<GetInclusionProofResponse>, Status> {
+        usage::admit(self.usage.as_deref(), req.metadata(), 0, self.clock.now())
+            .await
+            .map_err(|status| *status)?;
+        let archive = self.archive.as_ref().ok_or(Status::new(
+            Code::FailedPrecondition,
+            "Fingerprint archive is not configured",
+        ))?;
+
+        let request = req.into_inner();
+        let fingerprint = wire::decode_scalar(&request.fingerprint).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "Fingerprint should be 32 bytes representation of the field element",
+            )
+        })?;
+        let epoch = NaiveDate::parse_from_str(&request.epoch, "%Y-%m-%d").map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                format!("Epoch {} should be the date YYYY-MM-DD", request.epoch),
+            )
+        })?;
+
+        let inclusion = archive
+            .inclusion(epoch, fingerprint, KEY_EPOCH)
+            .await
+            .map_err(|e| {
+                Status::new(
+                    Code::Unavailable,
+                    format!("Failed to read the archive: {}", e),
+                )
+            })?
+            .ok_or(Status::new(
+                Code::NotFound,
+                format!("Epoch {} is not archived", epoch),
+            ))?;
+        let proof = inclusion.proof.ok_or(Status::new(
+            Code::NotFound,
+            format!("Fingerprint is not included into the epoch {}", epoch),
+        ))?;
+
+        Ok(Response::new(GetInclusionProofResponse {
+            key_epoch: KEY_EPOCH,
+            root: inclusion.root.to_vec().into(),
+            steps: proof
+                .steps
+                .into_iter()
+                .map(|step| MerkleProofStep {
+                    sibling: step.sibling.to_vec().into(),
+                    sibling_left: step.sibling_left,
+                    _unknown_fields: Default::default(),
+                })
+                .collect(),
+            anchor: inclusion.anchor.unwrap_or_default().into(),
+            _unknown_fields: Default::default(),
+        }))
+    }
+}
+
+/// First day of the month formatted as `YYYY-MM`
+fn parse_month(month: &str) -> Result<NaiveDate, Box<Status>> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").map_err(|_| {
+        Box::new(Status::new(
+            Code::InvalidArgument,
+            format!("Month {} should be formatted as YYYY-MM", month),
+        ))
+    })
+}
+
+/// Parameters the fingerprints are computed with
+pub(crate) fn service_info(
+    namespace: Option<&Namespace>,
+    schema: Option<&FingerprintSchema>,
+    wire_version: WireVersion,
+    epoch: Epoch,
+    shadow: Option<ShadowStats>,
+    sampling: Option<ShadowStats>,
+    topology: Option<&TopologyStatus>,
+) -> GetServiceInfoResponse {
+    GetServiceInfoResponse {
+        namespace: namespace_name(namespace),
+        schema_id: schema.map_or(BUILT_IN_SCHEMA_ID, FingerprintSchema::id),
+        schema_components: schema
+            .map_or(&SchemaComponent::ALL[..], FingerprintSchema::components)
+            .iter()
+            .map(|component| FastStr::from_static_str(component.name()))
+            .collect(),
+        wire_version: wire_version.as_u8() as u32,
+        supported_wire_versions: WireVersion::SUPPORTED
+            .iter()
+            .map(|version| version.as_u8() as u32)
+            .collect(),
+        epoch: FastStr::from(epoch.to_string()),
+        curve: FastStr::from_static_str("bn256"),
+        hash_to_curve_domain: FastStr::from_static_str(HASH_TO_CURVE_PREFIX),
+        poseidon_full_rounds: POSEIDON_FULL_ROUNDS as u32,
+        poseidon_partial_rounds: POSEIDON_PARTIAL_ROUNDS as u32,
+        parameters_digest: pilota::Bytes::copy_from_slice(&parameters_digest()),
+        shadow: shadow.map(shadow_stats),
+        sampling: sampling.map(shadow_stats),
+        members: topology.map(TopologyStatus::members).unwrap_or_default(),
+        institutions: topology
+            .map(TopologyStatus::institutions)
+            .unwrap_or_default(),
+        _unknown_fields: Default::default(),
+    }
+}
+
+fn shadow_stats(stats: ShadowStats) -> ShadowStatsDto {
+    ShadowStatsDto {
+        compared: stats.compared,
+        diverged: stats.diverged,
+        failed: stats.failed,
+        _unknown_fields: Default::default(),
+    }
+}
+
+/// Checks the service computes the fingerprints with the same parameters as this build, clients call it
+/// before use since the fingerprints of the different parameters are never comparable
+pub fn check_service_parameters(info: &GetServiceInfoResponse) -> Result<(), anyhow::Error> {
+    if info.parameters_digest.as_ref() != parameters_digest() {
+        return Err(anyhow::anyhow!(
+            "Service parameters digest {} differs from the local one {}, the Poseidon constants or \
+            the hash-to-curve domain ({} on the service) have drifted",
+            wire::encode_compact(&info.parameters_digest),
+            wire::encode_compact(&parameters_digest()),
+            info.hash_to_curve_domain
+        ));
+    }
+    if !info
+        .supported_wire_versions
+        .contains(&(WireVersion::CURRENT.as_u8() as u32))
+    {
+        return Err(anyhow::anyhow!(
+            "Service does not support wire version {}, it supports {:?}",
+            WireVersion::CURRENT.as_u8(),
+            info.supported_wire_versions
+        ));
+    }
+    if u8::try_from(info.wire_version)
+        .map_err(anyhow::Error::from)
+        .and_then(WireVersion::try_from)
+        .is_err()
+    {
+        return Err(anyhow::anyhow!(
+            "Service computes the fingerprints with wire version {}, unknown to this build",
+            info.wire_version
+        ));
+    }
+
+    Ok(())
+}
+
+/// Empty name stands for the absent namespace
+fn namespace_name(namespace: Option<&Namespace>) -> FastStr {
+    namespace
+        .map(|namespace| FastStr::new(namespace.name()))
+        .unwrap_or_default()
+}
+
+/// Fingerprint labeled with the namespace it is computed in
+fn fingerprint_dto(fingerprint: Fr, namespace: Option<&Namespace>) -> FingerprintDto {
+    FingerprintDto {
+        namespace: namespace_name(namespace),
+        ..fingerprint.into()
+    }
+}
+
+/// Characters of the compact fingerprint kept in the short form
+const SHORT_FINGERPRINT_SIZE: usize = 12;
+
+/// Encodings of the returned fingerprints requested by the caller
+#[derive(Debug, Clone, Copy)]
+struct Encodings {
+    raw: bool,
+    compact: bool,
+    hex: bool,
+    short: bool,
+    base64url: bool,
+    checked: bool,
+}
+
+impl Encodings {
+    /// Raw bytes and compact are returned when none is requested
+    fn new(requested: &[FingerprintEncoding]) -> Result<Self, Box<Status>> {
+        if requested.is_empty() {
+            return Ok(Self {
+                raw: true,
+                compact: true,
+                hex: false,
+                short: false,
+                base64url: false,
+                checked: false,
+            });
+        }
+
+        let mut encodings = Self {
+            raw: false,
+            compact: false,
+            hex: false,
+            short: false,
+            base64url: false,
+            checked: false,
+        };
+        for encoding in requested {
+            match *encoding {
+                FingerprintEncoding::FINGERPRINT_ENCODING_RAW => encodings.raw = true,
+                FingerprintEncoding::FINGERPRINT_ENCODING_COMPACT => encodings.compact = true,
+                FingerprintEncoding::FINGERPRINT_ENCODING_HEX => encodings.hex = true,
+                FingerprintEncoding::FINGERPRINT_ENCODING_SHORT => encodings.short = true,
+                FingerprintEncoding::FINGERPRINT_ENCODING_BASE64URL => encodings.base64url = true,
+                FingerprintEncoding::FINGERPRINT_ENCODING_CHECKED => encodings.checked = true,
+                encoding => {
+                    return Err(Box::new(Status::new(
+                        Code::InvalidArgument,
+                        format!("Unknown fingerprint encoding {}", encoding.inner()),
+                    )))
+                }
+            }
+        }
+
+        Ok(encodings)
+    }
+
+    /// Keeps only the requested encodings of the fingerprint with the raw bytes and compact
+    fn encode(&self, mut fingerprint: FingerprintDto) -> FingerprintDto {
+        let raw = fingerprint.fingerprint.clone();
+        let text = |encoding| -> FastStr { wire::encode_text(encoding, &raw).into() };
+        if self.hex {
+            fingerprint.hex_fingerprint = text(TextEncoding::Hex);
+        }
+        if self.base64url {
+            fingerprint.base64url_fingerprint = text(TextEncoding::Base64Url);
+        }
+        if self.checked {
+            fingerprint.checked_fingerprint = text(TextEncoding::Base58Check);
+        }
+        if self.short {
+            fingerprint.short_fingerprint = fingerprint
+                .compact_fingerprint
+                .chars()
+                .take(SHORT_FINGERPRINT_SIZE)
+                .collect::<String>()
+                .into();
+        }
+        if !self.raw {
+            fingerprint.fingerprint = Default::default();
+        }
+        if !self.compact {
+            fingerprint.compact_fingerprint = Default::default();
+        }
+
+        fingerprint
+    }
+}
+
+fn status_update_dto(
+    update: FingerprintStatusUpdate,
+    namespace: Option<&Namespace>,
+) -> FingerprintStatusUpdateDto {
+    let fingerprint = update.fingerprint;
+
+    FingerprintStatusUpdateDto {
+        fingerprint: Some(fingerprint_dto(fingerprint, namespace)),
+        ..update.into()
+    }
+}
+
+/// Stream of the `replayed` updates followed by the published updates of their fingerprint,
+/// until the fingerprint is anchored or the `subscription_timeout` passes
+fn forward_status_updates(
+    replayed: Vec<FingerprintStatusUpdate>,
+    mut updates: broadcast::Receiver<FingerprintStatusUpdate>,
+    subscription_timeout: std::time::Duration,
+    namespace: Option<Namespace>,
+) -> BoxStream<'static, Result<FingerprintStatusUpdateDto, Status>> {
+    let (tx, rx) = mpsc::channel(16);
+
+    tokio::spawn(async move {
+        let Some(fingerprint) = replayed.first().map(|update| update.fingerprint) else {
+            return;
+        };
+        for update in replayed {
+            let is_final = update.status.is_final();
+            let update = status_update_dto(update, namespace.as_ref());
+            if tx.send(Ok(update)).await.is_err() || is_final {
+                return;
+            }
+        }
+
+        let deadline = tokio::time::sleep(subscription_timeout);
+        tokio::pin!(deadline);
+
+        loop {
+            tokio::select! {
+                _ = &mut deadline => break,
+                update = updates.recv() => match update {
+                    Ok(update) if update.fingerprint == fingerprint => {
+                        let is_final = update.status.is_final();
+
+                        let update = status_update_dto(update, namespace.as_ref());
+                        if tx.send(Ok(update)).await.is_err() || is_final {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Status subscriber lagged behind, {} updates skipped", skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    });
+
+    Box::pin(ReceiverStream::new(rx))
+}
+
+/// Exact fingerprint with the fuzzy one of the `fuzzy_bucket` when given, evaluated in the same round
+async fn complete_with_fuzzy<P: FingerprintProtocol<Fr> + Sync>(
+    tx: &TransactionFingerprintData<Fr>,
+    protocol: &P,
+    fuzzy_bucket: Option<TimeBucket>,
+) -> Result<(Fr, Option<Fr>), anyhow::Error> {
+    match fuzzy_bucket {
+        Some(bucket) => tx
+            .complete_fuzzy_fingerprint(protocol, bucket)
+            .await
+            .map(|fingerprints| {
+                (
+                    fingerprints.exact.into_inner(),
+                    Some(fingerprints.fuzzy.into_inner()),
+                )
+            }),
+        None => tx
+            .complete_fingerprint(protocol)
+            .await
+            .map(|fingerprint| (fingerprint.into_inner(), None)),
+    }
+}
+
+/// Completes the fingerprint via the `agents` only when given, see `ResidencyRouting`, with the fuzzy one of
+/// the `fuzzy_bucket` when given, counting it in the `counters` and in the usage of the `call`
+#[allow(clippy::too_many_arguments)]
+async fn complete_fingerprint<P: FingerprintProtocol<Fr> + Sync>(
+    tx: &TransactionFingerprintData<Fr>,
+    protocol: &P,
+    agents: Option<&[usize]>,
+    fuzzy_bucket: Option<TimeBucket>,
+    counters: &ServiceCounters,
+    call: &CallUsage,
+    events: &dyn EventBus,
+    clock: &dyn Clock,
+) -> Result<(Fr, Option<Fr>), Box<Status>> {
+    let completed = match agents {
+        Some(agents) => {
+            complete_with_fuzzy(tx, &ViaAgents::new(protocol, agents), fuzzy_bucket).await
+        }
+        None => complete_with_fuzzy(tx, protocol, fuzzy_bucket).await,
+    };
+    counters.record_computation(completed.is_ok());
+    call.record_round(completed.is_ok());
+    if let Ok((fingerprint, _)) = completed {
+        events.publish(LifecycleEvent::FingerprintComputed {
+            fingerprint,
+            at: clock.now(),
+        });
+    }
+
+    completed.map_err(|e| {
+        Box::new(Status::new(
+            Code::Aborted,
+            format!("Failed to complete fingerprint computation: {}", e),
+        ))
+    })
+}
+
+/// Refuses the transaction with the string fields above the `limits` before they are parsed or hashed
+fn check_input_sizes(raw_tx: &RawTransaction, limits: &InputLimits) -> Result<(), Box<Status>> {
+    raw_tx
+        .check_input_sizes(limits)
+        .map_err(|e| Box::new(Status::new(Code::InvalidArgument, e.to_string())))
+}
+
+/// Parses and validates the transaction the way it is computed, without the protocol round and the store
+fn validation_verdict(
+    tx_data: Option<TransactionFingerprintDataDto>,
+    salt: pilota::Bytes,
+    residency: &ResidencyRouting,
+    input_limits: &InputLimits,
+    schema: Option<Arc<FingerprintSchema>>,
+) -> ValidationVerdict {
+    let validate = || {
+        let tx_data = tx_data.ok_or(Status::new(
+            Code::InvalidArgument,
+            "Transaction data missing",
+        ))?;
+        residency.agents(&tx_data.residency)?;
+        let raw_tx: RawTransaction = tx_data.try_into()?;
+        check_input_sizes(&raw_tx, input_limits)?;
+
+        let tx: TransactionFingerprintData<Fr> = raw_tx.try_into().map_err(Status::from)?;
+        let tx = apply_salt(tx.with_schema(schema), salt)?;
+        tx.validate()
+            .map_err(|e| Status::new(Code::InvalidArgument, e.to_string()))?;
+
+        Ok::<_, Box<Status>>(())
+    };
+
+    match validate() {
+        Ok(()) => ValidationVerdict {
+            valid: true,
+            ..Default::default()
+        },
+        Err(status) => ValidationVerdict {
+            valid: false,
+            reason: FastStr::new(status.message()),
+            _unknown_fields: Default::default(),
+        },
+    }
+}
+
+/// Applies the caller supplied salt, empty salt means the salt is not provided
+fn apply_salt(
+    tx: TransactionFingerprintData<Fr>,
+    salt: pilota::Bytes,
+) -> Result<TransactionFingerprintData<Fr>, Box<Status>> {
+    if salt.is_empty() {
+        return Ok(tx);
+    }
+
+    tx.with_salt(salt).map_err(|e| {
+        Box::new(Status::new(
+            Code::InvalidArgument,
+            format!("Invalid salt: {}", e),
+        ))
+    })
+}
+
+/// Persists the computed fingerprint when the store is configured
+/// and notifies status subscribers whether the fingerprint is a duplicate
+async fn store_fingerprint(
+    store: Option<&dyn FingerprintStore>,
+    window: DuplicateWindow,
+    status_hub: &FingerprintStatusHub,
+    counters: &ServiceCounters,
+    events: &dyn EventBus,
+    fingerprint: Fr,
+    now: DateTime<Utc>,
+) -> Result<Option<DuplicateCheckDto>, Box<Status>> {
+    let Some(store) = store else {
+        return Ok(None);
+    };
+
+    let outcome = store
+        .insert(fingerprint, KEY_EPOCH, now, window)
+        .await
+        .map_err(|e| {
+            Status::new(
+                Code::Unavailable,
+                format!("Failed to store fingerprint: {}", e),
+            )
+        })?;
+    if let InsertOutcome::Duplicate(stored) = &outcome {
+        counters.record_duplicate();
+        events.publish(LifecycleEvent::DuplicateDetected {
+            fingerprint,
+            first_seen: stored.first_seen,
+            at: now,
+        });
+    }
+
+    status_hub.publish(FingerprintStatusUpdate {
+        fingerprint,
+        status: match outcome {
+            InsertOutcome::Duplicate(_) => FingerprintStatus::DuplicateFound,
+            InsertOutcome::Inserted(_) | InsertOutcome::Recurring(_) => FingerprintStatus::Stored,
+        },
+        epoch_root: None,
+    });
+
+    Ok(Some(outcome.into()))
+}
+
+/// Counts the fingerprint the store has never seen before to the epoch (UTC day) it is stored in.
+/// The fingerprint is stored already, so the failed count is logged rather than failing the computation
+async fn record_tally(
+    tallies: Option<&dyn TallyLedger>,
+    duplicate: Option<&DuplicateCheckDto>,
+    currency: u16,
+    now: DateTime<Utc>,
+) {
+    let (Some(tallies), Some(duplicate)) = (tallies, duplicate) else {
+        return;
+    };
+    if duplicate.status != DuplicateStatus::DUPLICATE_STATUS_NEW {
+        return;
+    }
+
+    if let Err(e) = tallies.record(now.date_naive(), currency).await {
+        log::warn!(
+            "Failed to count the fingerprint to the epoch statistics: {}",
+            e
+        );
+    }
+}
+
+/// Appends the fingerprint the store has never seen before to the tree of the epoch (UTC day) it is stored in,
+/// the tree of the completed epoch is replaced by the new one
+fn accumulate(
+    accumulator: Option<&Mutex<EpochAccumulator>>,
+    duplicate: Option<&DuplicateCheckDto>,
+    fingerprint: Fr,
+    now: DateTime<Utc>,
+) {
+    let (Some(accumulator), Some(duplicate)) = (accumulator, duplicate) else {
+        return;
+    };
+    if duplicate.status != DuplicateStatus::DUPLICATE_STATUS_NEW {
+        return;
+    }
+
+    let mut accumulator = accumulator.lock().unwrap_or_else(PoisonError::into_inner);
+    if accumulator.epoch() != now.date_naive() {
+        log::info!(
+            "== Epoch {} accumulated {} fingerprints",
+            accumulator.epoch(),
+            accumulator.leaves()
+        );
+        *accumulator = EpochAccumulator::new(now.date_naive());
+    }
+    accumulator.append(KEY_EPOCH, &fingerprint);
+}
+
+/// Transaction data to record, salted exchanges are never recorded since the salt is the secret of the caller
+fn recorded_data(
+    recorder: Option<&FingerprintRecorder>,
+    tx_data: &TransactionFingerprintDataDto,
+    salt: &[u8],
+) -> Option<TransactionFingerprintDataDto> {
+    recorder
+        .filter(|_| salt.is_empty())
+        .map(|_| tx_data.clone())
+}
+
+/// Transaction with the salt computed once more by the shadow
+fn shadowed_data(
+    shadow: Option<&ShadowFingerprinting>,
+    raw_tx: &RawTransaction,
+    salt: &pilota::Bytes,
+) -> Option<(RawTransaction, pilota::Bytes)> {
+    shadow.map(|_| (raw_tx.clone(), salt.clone()))
+}
+
+/// Transaction with the salt recomputed by the sampling, if sampled
+fn sampled_data(
+    sampling: Option<&FingerprintSampling>,
+    raw_tx: &RawTransaction,
+    salt: &pilota::Bytes,
+) -> Option<(RawTransaction, pilota::Bytes)> {
+    sampling
+        .filter(|sampling| sampling.sample())
+        .map(|_| (raw_tx.clone(), salt.clone()))
+}
+
+/// Key the commitments are signed with when requested, refused up front when the key is not configured
+fn requested_commitment_key(
+    key: Option<&SigningKey>,
+    with_commitments: bool,
+) -> Result<Option<&SigningKey>, Box<Status>> {
+    match (with_commitments, key) {
+        (false, _) => Ok(None),
+        (true, Some(key)) => Ok(Some(key)),
+        (true, None) => Err(Box::new(Status::new(
+            Code::FailedPrecondition,
+            "Commitment key is not configured, commitments can not be bound to the fingerprint",
+        ))),
+    }
+}
+
+/// Computes commitments to the transaction components signed with the `key` only when requested
+fn commit_components(
+    tx: &TransactionFingerprintData<Fr>,
+    fingerprint: Fr,
+    key: Option<&SigningKey>,
+    entropy: &dyn EntropySource,
+) -> Result<Option<ComponentCommitments>, Box<Status>> {
+    let Some(key) = key else {
+        return Ok(None);
+    };
+
+    let commitments = tx
+        .commit_components(fingerprint, key, entropy)
+        .map_err(|e| {
+            Status::new(
+                Code::Aborted,
+                format!("Failed to commit fingerprint components: {}", e),
+            )
+        })?;
+
+    Ok(Some(commitments.into()))
+}
+
+mod dto_convert {
+    use crate::net;
+    use anyhow::anyhow;
+    use chrono::{DateTime, Datelike, NaiveDate, Utc};
+    use fingerprinting_core::commitment::{CommittedComponent, FingerprintCommitments};
+    use fingerprinting_core::{wire, Compact};
+    use fingerprinting_store::{DuplicateCheck, InsertOutcome, StoredFingerprint};
+    use fingerprinting_types::{
+        Counterparty, InputLimits, Money, RawTransaction, RawTransactionBuilder,
+    };
+    use halo2_axiom::halo2curves::bn256::Fr;
+    use pilota::FastStr;
+    use volo_grpc::{Code, Status};
+
+    impl TryInto<DateTime<Utc>> for net::outbe::common::v1::Timestamp {
+        type Error = anyhow::Error;
+
+        fn try_into(self) -> Result<DateTime<Utc>, Self::Error> {
+            DateTime::from_timestamp(self.seconds as i64, self.nanos)
+                .ok_or(anyhow!("Timestamp is not valid"))
+        }
+    }
+
+    impl TryInto<NaiveDate> for net::outbe::common::v1::Date {
+        type Error = anyhow::Error;
+
+        fn try_into(self) -> Result<NaiveDate, Self::Error> {
+            NaiveDate::from_ymd_opt(self.year as i32, self.month, self.day)
+                .ok_or(anyhow!("Invalid or out-of-range date"))
+        }
+    }
+
+    impl TryInto<Money> for net::outbe::common::v1::Money {
+        type Error = anyhow::Error;
+
+        fn try_into(self) -> Result<Money, Self::Error> {
+            let currency = self.currency.to_string();
+            let currency = currency
+                .strip_prefix("CURRENCY_")
+                .ok_or(anyhow!("Provided invalid currency {}", currency))?
+                .to_string();
+
+            Ok(Money {
+                amount_base: self.units,
+                amount_atto: self.atto,
+                currency,
+            })
+        }
+    }
+
+    impl TryInto<RawTransaction> for net::outbe::fingerprint::v1::TransactionFingerprintData {
+        type Error = Status;
+
+        fn try_into(self) -> Result<RawTransaction, Self::Error> {
+            let tx_date_time = self.date_time.ok_or(Status::new(
+                Code::InvalidArgument,
+                "Transaction date time information is missing",
+            ))?;
+            let tx_wwd = self.wwd.ok_or(Status::new(
+                Code::InvalidArgument,
+                "Transaction WWD is missing",
+            ))?;
+            let tx_amount = self.amount.ok_or(Status::new(
+                Code::InvalidArgument,
+                "Transaction amount is missing",
+            ))?;
+
+            let date_time: DateTime<Utc> = tx_date_time.try_into()?;
+            let wwd: NaiveDate = tx_wwd.try_into()?;
+            let amount: Money = tx_amount.try_into()?;
+            let counter_amount: Option<Money> = self
+                .counter_amount
+                .map(|counter_amount| counter_amount.try_into())
+                .transpose()?;
+
+            let raw_tx = RawTransactionBuilder::default()
+                .bic(self.bic)
+                .date_time(date_time)
+                .wwd(wwd)
+                .amount(amount)
+                .counter_amount(counter_amount)
+                .series_id(
+                    Some(self.series_id.to_string()).filter(|series_id| !series_id.is_empty()),
+                )
+                .merchant_id(
+                    Some(self.merchant_id.to_string())
+                        .filter(|merchant_id| !merchant_id.is_empty()),
+                )
+                .country_code(
+                    Some(self.country_code.to_string())
+                        .filter(|country_code| !country_code.is_empty()),
+                )
+                .transaction_reference(
+                    Some(self.transaction_reference.to_string())
+                        .filter(|reference| !reference.is_empty()),
+                )
+                .mcc(Some(self.mcc.to_string()).filter(|mcc| !mcc.is_empty()))
+                .counterparty(self.counterparty.map(Into::into))
+                .build()
+                .map_err(|e| {
+                    Status::new(
+                        Code::InvalidArgument,
+                        format!("Failed to build transaction: {}", e),
+                    )
+                })?;
+            crate::check_input_sizes(&raw_tx, &InputLimits::MAX).map_err(|status| *status)?;
+
+            Ok(raw_tx)
+        }
+    }
+
+    impl From<FingerprintCommitments> for net::outbe::fingerprint::v1::ComponentCommitments {
+        fn from(value: FingerprintCommitments) -> Self {
+            use net::outbe::fingerprint::v1::component_commitment::Component;
+
+            let components = value
+                .components
+                .iter()
+                .map(|c| net::outbe::fingerprint::v1::ComponentCommitment {
+                    component: match c.component {
+                        CommittedComponent::Bic => Component::COMPONENT_BIC,
+                        CommittedComponent::Amount => Component::COMPONENT_AMOUNT,
+                        CommittedComponent::Currency => Component::COMPONENT_CURRENCY,
+                        CommittedComponent::DateTime => Component::COMPONENT_DATE_TIME,
+                    },
+                    commitment: pilota::Bytes::copy_from_slice(&wire::encode_scalar(&c.commitment)),
+                    opening: pilota::Bytes::copy_from_slice(&wire::encode_scalar(&c.opening)),
+                    _unknown_fields: Default::default(),
+                })
+                .collect();
+
+            net::outbe::fingerprint::v1::ComponentCommitments {
+                components,
+                binding: pilota::Bytes::copy_from_slice(&value.binding.to_bytes()),
+                wire_version: value.wire_version.as_u8() as u32,
+                epoch: value.epoch.to_string().into(),
+                _unknown_fields: Default::default(),
+            }
+        }
+    }
+
+    impl From<crate::FingerprintStatusUpdate> for net::outbe::fingerprint::v1::FingerprintStatusUpdate {
+        fn from(value: crate::FingerprintStatusUpdate) -> Self {
+            use crate::FingerprintStatus;
+            use net::outbe::fingerprint::v1::FingerprintStatus as FingerprintStatusDto;
+
+            net::outbe::fingerprint::v1::FingerprintStatusUpdate {
+                fingerprint: Some(value.fingerprint.into()),
+                status: match value.status {
+                    FingerprintStatus::Computed => {
+                        FingerprintStatusDto::FINGERPRINT_STATUS_COMPUTED
+                    }
+                    FingerprintStatus::Stored => FingerprintStatusDto::FINGERPRINT_STATUS_STORED,
+                    FingerprintStatus::DuplicateFound => {
+                        FingerprintStatusDto::FINGERPRINT_STATUS_DUPLICATE_FOUND
+                    }
+                    FingerprintStatus::Anchored => {
+                        FingerprintStatusDto::FINGERPRINT_STATUS_ANCHORED
+                    }
+                },
+                epoch_root: value
+                    .epoch_root
+                    .map(|root| pilota::Bytes::copy_from_slice(&root))
+                    .unwrap_or_default(),
+                _unknown_fields: Default::default(),
+            }
+        }
+    }
+
+    impl From<NaiveDate> for net::outbe::common::v1::Date {
+        fn from(value: NaiveDate) -> Self {
+            net::outbe::common::v1::Date {
+                year: value.year() as u32,
+                month: value.month(),
+                day: value.day(),
+                _unknown_fields: Default::default(),
+            }
+        }
+    }
+
+    impl From<DateTime<Utc>> for net::outbe::common::v1::Timestamp {
+        fn from(value: DateTime<Utc>) -> Self {
+            net::outbe::common::v1::Timestamp {
+                seconds: value.timestamp() as u64,
+                nanos: value.timestamp_subsec_nanos(),
+                _unknown_fields: Default::default(),
+            }
+        }
+    }
+
+    impl TryFrom<Money> for net::outbe::common::v1::Money {
+        type Error = anyhow::Error;
+
+        fn try_from(value: Money) -> Result<Self, Self::Error> {
+            let code = fingerprinting_core::numeric_currency_code(&value.currency)?;
+            // Currency enum values are the ISO 4217 numeric codes
+            let currency = net::outbe::common::v1::Currency::try_from_i32(code as i32)
+                .ok_or(anyhow!("Currency {} is not supported", value.currency))?;
+
+            Ok(net::outbe::common::v1::Money {
+                currency,
+                units: value.amount_base,
+                atto: value.amount_atto,
+                _unknown_fields: Default::default(),
+            })
+        }
+    }
+
+    impl From<net::outbe::fingerprint::v1::Counterparty> for Counterparty {
+        fn from(value: net::outbe::fingerprint::v1::Counterparty) -> Self {
+            Counterparty {
+                payer: value.payer.to_string(),
+                payee: value.payee.to_string(),
+            }
+        }
+    }
+
+    impl From<Counterparty> for net::outbe::fingerprint::v1::Counterparty {
+        fn from(value: Counterparty) -> Self {
+            net::outbe::fingerprint::v1::Counterparty {
+                payer: value.payer.into(),
+                payee: value.payee.into(),
+                _unknown_fields: Default::default(),
+            }
+        }
+    }
+
+    impl TryFrom<RawTransaction> for net::outbe::fingerprint::v1::TransactionFingerprintData {
+        type Error = anyhow::Error;
+
+        fn try_from(value: RawTransaction) -> Result<Self, Self::Error> {
+            Ok(net::outbe::fingerprint::v1::TransactionFingerprintData {
+                bic: value.bic.into(),
+                amount: Some(value.amount.try_into()?),
+                counter_amount: value.counter_amount.map(TryInto::try_into).transpose()?,
+                date_time: Some(value.date_time.into()),
+                wwd: Some(value.wwd.into()),
+                series_id: value.series_id.unwrap_or_default().into(),
+                merchant_id: value.merchant_id.unwrap_or_default().into(),
+                country_code: value.country_code.unwrap_or_default().into(),
+                transaction_reference: value.transaction_reference.unwrap_or_default().into(),
+                mcc: value.mcc.unwrap_or_default().into(),
+                counterparty: value.counterparty.map(Into::into),
+                residency: Default::default(),
+                _unknown_fields: Default::default(),
+            })
+        }
+    }
+
+    fn duplicate_check(
+        status: net::outbe::fingerprint::v1::DuplicateStatus,
+        stored: Option<&StoredFingerprint>,
+    ) -> net::outbe::fingerprint::v1::DuplicateCheck {
+        net::outbe::fingerprint::v1::DuplicateCheck {
+            status,
+            first_seen: stored.map(|stored| stored.first_seen.into()),
+            last_seen: stored.map(|stored| stored.last_seen.into()),
+            window_start: stored.map(|stored| stored.window_start.into()),
+            occurrences: stored.map(|stored| stored.occurrences).unwrap_or_default(),
+            _unknown_fields: Default::default(),
+        }
+    }
+
+    impl From<InsertOutcome> for net::outbe::fingerprint::v1::DuplicateCheck {
+        fn from(value: InsertOutcome) -> Self {
+            use net::outbe::fingerprint::v1::DuplicateStatus;
+
+            let status = match value {
+                InsertOutcome::Inserted(_) => DuplicateStatus::DUPLICATE_STATUS_NEW,
+                InsertOutcome::Duplicate(_) => DuplicateStatus::DUPLICATE_STATUS_DUPLICATE,
+                InsertOutcome::Recurring(_) => DuplicateStatus::DUPLICATE_STATUS_RECURRING,
+            };
+
+            duplicate_check(status, Some(value.stored()))
+        }
+    }
+
+    impl From<DuplicateCheck> for net::outbe::fingerprint::v1::DuplicateCheck {
+        fn from(value: DuplicateCheck) -> Self {
+            use net::outbe::fingerprint::v1::DuplicateStatus;
+
+            match value {
+                DuplicateCheck::New => duplicate_check(DuplicateStatus::DUPLICATE_STATUS_NEW, None),
+                DuplicateCheck::Duplicate(stored) => {
+                    duplicate_check(DuplicateStatus::DUPLICATE_STATUS_DUPLICATE, Some(&stored))
+                }
+                DuplicateCheck::Recurring(stored) => {
+                    duplicate_check(DuplicateStatus::DUPLICATE_STATUS_RECURRING, Some(&stored))
+                }
+            }
+        }
+    }
+
+    impl From<Fr> for net::outbe::fingerprint::v1::Fingerprint {
+        fn from(value: Fr) -> Self {
+            net::outbe::fingerprint::v1::Fingerprint {
+                fingerprint: pilota::Bytes::copy_from_slice(&wire::encode_scalar(&value)),
+                compact_fingerprint: FastStr::new(value.compact()),
+                namespace: Default::default(),
+                hex_fingerprint: Default::default(),
+                short_fingerprint: Default::default(),
+                base64url_fingerprint: Default::default(),
+                checked_fingerprint: Default::default(),
+                _unknown_fields: Default::default(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+    use fingerprinting_core::clock::FixedClock;
+    use fingerprinting_core::{Compact, NaiveProtocol};
+    use lazy_static::lazy_static;
+    use std::net::SocketAddr;
+    use volo::FastStr;
+
+    lazy_static! {
+        static ref CLIENT: net::outbe::fingerprint::v1::FingerprintServiceClient = {
+            let addr: SocketAddr = "[::1]:9000".parse().unwrap();
+
+            net::outbe::fingerprint::v1::FingerprintServiceClientBuilder::new(
+                "fingerprinting-grpc-agent-client",
+            )
+            .address(addr)
+            .build()
+        };
+    }
+    fn transaction_data(
+        tx_date: chrono::DateTime<Utc>,
+    ) -> net::outbe::fingerprint::v1::TransactionFingerprintData {
+        net::outbe::fingerprint::v1::TransactionFingerprintData {
+            bic: FastStr::new("BCEELU21"),
+            amount: Some(net::outbe::common::v1::Money {
+                currency: net::outbe::common::v1::Currency::CURRENCY_EUR,
+                units: 1000,
+                atto: 0,
+                _unknown_fields: Default::default(),
+            }),
+            counter_amount: None,
+            date_time: Some(net::outbe::common::v1::Timestamp {
+                seconds: tx_date.timestamp() as u64,
+                nanos: tx_date.timestamp_subsec_nanos(),
+                _unknown_fields: Default::default(),
+            }),
+            wwd: Some(net::outbe::common::v1::Date {
+                year: tx_date.year() as u32,
+                month: tx_date.month(),
+                day: tx_date.day(),
+                _unknown_fields: Default::default(),
+            }),
+            series_id: Default::default(),
+            merchant_id: Default::default(),
+            country_code: Default::default(),
+            transaction_reference: Default::default(),
+            mcc: Default::default(),
+            counterparty: None,
+            residency: Default::default(),
+            _unknown_fields: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_fingerprint_computation() -> Result<(), anyhow::Error> {
+        let tx_date = Utc::now();
+
+        let transaction_data = transaction_data(tx_date);
+
+        println!("Transaction data: {:?}", transaction_data);
+        println!("Requesting the fingerprint computation... from cooperative agents");
+
+        let response = CLIENT
+            .compute_single_fingerprint(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data),
+                salt: Default::default(),
+                with_commitments: false,
+                encodings: Default::default(),
+                validate_only: false,
+                _unknown_fields: Default::default(),
+            })
+            .await?;
+
+        let fingerprint = response.into_inner();
+        let fingerprint = fingerprint.fingerprint.unwrap();
+        let fixed_bytes = fingerprint.fingerprint.first_chunk::<32>().unwrap();
+
+        let fr_fingerprint = Fr::from_bytes(fixed_bytes).unwrap();
+        let compact_fingerprint = fingerprint.compact_fingerprint.to_string();
+
+        println!(
+            "Fingerprint: {} fr {}",
+            compact_fingerprint,
+            fr_fingerprint.compact()
+        );
+
+        Ok(())
+    }
+    #[tokio::test]
+    pub async fn test_fingerprint_status_subscription() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+        use net::outbe::fingerprint::v1::FingerprintStatus as FingerprintStatusDto;
+
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)));
+        let status_hub = service.status_hub();
+
+        let mut updates = service
+            .compute_single_fingerprint_and_subscribe(Request::new(
+                ComputeSingleFingerprintRequest {
+                    transaction_data: Some(transaction_data(Utc::now())),
+                    salt: Default::default(),
+                    with_commitments: false,
+                    encodings: Default::default(),
+                    validate_only: false,
+                    _unknown_fields: Default::default(),
+                },
+            ))
+            .await?
+            .into_inner();
+
+        let computed = updates.next().await.unwrap()?;
+        assert_eq!(
+            computed.status,
+            FingerprintStatusDto::FINGERPRINT_STATUS_COMPUTED
+        );
+
+        let fingerprint = computed.fingerprint.unwrap().fingerprint;
+        let fingerprint = Fr::from_bytes(fingerprint.first_chunk::<32>().unwrap()).unwrap();
+
+        let update = |fingerprint, status| FingerprintStatusUpdate {
+            fingerprint,
+            status,
+            epoch_root: None,
+        };
+        status_hub.publish(update(Fr::from(1), FingerprintStatus::Stored));
+        status_hub.publish(update(fingerprint, FingerprintStatus::Stored));
+        // Archiver anchors the epoch the fingerprint is first seen in
+        status_hub.publish_anchored([7; 32], [Fr::from(1), fingerprint]);
+
+        let updates = updates
+            .map_ok(|update| (update.status, update.epoch_root))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        assert_eq!(
+            updates,
+            vec![
+                (
+                    FingerprintStatusDto::FINGERPRINT_STATUS_STORED,
+                    pilota::Bytes::new()
+                ),
+                (
+                    FingerprintStatusDto::FINGERPRINT_STATUS_ANCHORED,
+                    pilota::Bytes::copy_from_slice(&[7; 32])
+                )
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_fingerprint_status_resume() -> Result<(), anyhow::Error> {
+        use chrono::TimeZone;
+        use fingerprinting_store::merkle::{self, EpochInclusion};
+        use fingerprinting_store::{MemoryFingerprintStore, StoredFingerprint};
+        use futures::future::{BoxFuture, FutureExt};
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+        use net::outbe::fingerprint::v1::FingerprintStatus as FingerprintStatusDto;
+
+        // Single archived epoch
+        struct Archive(NaiveDate, Vec<StoredFingerprint>);
+
+        impl EpochProofs for Archive {
+            fn inclusion(
+                &self,
+                epoch: NaiveDate,
+                fingerprint: Fr,
+                key_epoch: u64,
+            ) -> BoxFuture<'_, Result<Option<EpochInclusion>, anyhow::Error>> {
+                let inclusion = (epoch == self.0).then(|| EpochInclusion {
+                    root: merkle::archive_root(&self.1),
+                    proof: merkle::inclusion_proof(&self.1, key_epoch, &fingerprint),
+                    anchor: None,
+                });
+                async move { Ok(inclusion) }.boxed()
+            }
+        }
+
+        let archived_at = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let store = Arc::new(MemoryFingerprintStore::new());
+        let archived = store
+            .insert(
+                Fr::from(7),
+                KEY_EPOCH,
+                archived_at,
+                DuplicateWindow::Unbounded,
+            )
+            .await?;
+        store
+            .insert(
+                Fr::from(9),
+                KEY_EPOCH,
+                archived_at + chrono::Duration::days(1),
+                DuplicateWindow::Unbounded,
+            )
+            .await?;
+        let archive = Archive(archived_at.date_naive(), vec![archived.stored().clone()]);
+        let root = merkle::archive_root(&archive.1);
+
+        let request = |fingerprint: u64| {
+            Request::new(SubscribeFingerprintStatusRequest {
+                fingerprint: Fr::from(fingerprint).to_bytes().to_vec().into(),
+                ..Default::default()
+            })
+        };
+        let statuses = |updates: BoxStream<'static, Result<FingerprintStatusUpdateDto, Status>>| {
+            updates
+                .map_ok(|update| (update.status, update.epoch_root))
+                .try_collect::<Vec<_>>()
+        };
+
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)));
+        let status = service.subscribe_fingerprint_status(request(7)).await;
+        assert_eq!(status.err().unwrap().code(), Code::FailedPrecondition);
+
+        let service = service
+            .with_store(Some(store))
+            .with_archive(Some(Arc::new(archive)));
+        let status_hub = service.status_hub();
+        let status = service.subscribe_fingerprint_status(request(8)).await;
+        assert_eq!(status.err().unwrap().code(), Code::NotFound);
+
+        // Anchored status of the archived epoch is replayed long after the archival
+        let updates = service.subscribe_fingerprint_status(request(7)).await?;
+        assert_eq!(
+            statuses(updates.into_inner()).await?,
+            vec![
+                (
+                    FingerprintStatusDto::FINGERPRINT_STATUS_STORED,
+                    pilota::Bytes::new()
+                ),
+                (
+                    FingerprintStatusDto::FINGERPRINT_STATUS_ANCHORED,
+                    pilota::Bytes::copy_from_slice(&root)
+                )
+            ]
+        );
+
+        // Resumed subscription of the epoch not archived yet waits for the anchoring
+        let updates = service.subscribe_fingerprint_status(request(9)).await?;
+        status_hub.publish_anchored([9; 32], [Fr::from(9)]);
+        assert_eq!(
+            statuses(updates.into_inner()).await?,
+            vec![
+                (
+                    FingerprintStatusDto::FINGERPRINT_STATUS_STORED,
+                    pilota::Bytes::new()
+                ),
+                (
+                    FingerprintStatusDto::FINGERPRINT_STATUS_ANCHORED,
+                    pilota::Bytes::copy_from_slice(&[9; 32])
+                )
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_duplicate_detection() -> Result<(), anyhow::Error> {
+        use fingerprinting_store::MemoryFingerprintStore;
+        use net::outbe::fingerprint::v1::DuplicateStatus;
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let tx_date = Utc::now();
+        let clock = Arc::new(FixedClock::new(tx_date));
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .with_store(Some(Arc::new(MemoryFingerprintStore::new())))
+            .with_duplicate_window(DuplicateWindow::Within(chrono::Duration::days(90)))
+            .with_clock(clock.clone());
+        let mut events = service.events().subscribe();
+
+        let request = || {
+            Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data(tx_date)),
+                salt: Default::default(),
+                with_commitments: false,
+                encodings: Default::default(),
+                validate_only: false,
+                _unknown_fields: Default::default(),
+            })
+        };
+
+        let first = service
+            .compute_single_fingerprint(request())
+            .await?
+            .into_inner();
+        assert_eq!(
+            first.duplicate.unwrap().status,
+            DuplicateStatus::DUPLICATE_STATUS_NEW
+        );
+
+        let second = service
+            .compute_single_fingerprint(request())
+            .await?
+            .into_inner();
+        let duplicate = second.duplicate.unwrap();
+        assert_eq!(
+            duplicate.status,
+            DuplicateStatus::DUPLICATE_STATUS_DUPLICATE
+        );
+        assert_eq!(duplicate.occurrences, 2);
+        let fingerprint = wire::decode_scalar(&first.fingerprint.clone().unwrap().fingerprint)?;
+        let computed = LifecycleEvent::FingerprintComputed {
+            fingerprint,
+            at: tx_date,
+        };
+        assert_eq!(events.try_recv()?, computed);
+        assert_eq!(events.try_recv()?, computed);
+        assert_eq!(
+            events.try_recv()?,
+            LifecycleEvent::DuplicateDetected {
+                fingerprint,
+                first_seen: tx_date,
+                at: tx_date,
             }
-        }
+        );
+
+        let checks = service
+            .check_duplicate(Request::new(CheckDuplicateRequest {
+                fingerprints: vec![
+                    first.fingerprint.unwrap().fingerprint,
+                    pilota::Bytes::from_static(&[0u8; 32]),
+                ],
+                _unknown_fields: Default::default(),
+            }))
+            .await?
+            .into_inner()
+            .checks;
+        assert_eq!(
+            checks.iter().map(|check| check.status).collect::<Vec<_>>(),
+            vec![
+                DuplicateStatus::DUPLICATE_STATUS_DUPLICATE,
+                DuplicateStatus::DUPLICATE_STATUS_NEW
+            ]
+        );
+        assert_eq!(checks[0].occurrences, 2);
+
+        let malformed = service
+            .check_duplicate(Request::new(CheckDuplicateRequest {
+                fingerprints: vec![pilota::Bytes::from_static(&[0u8; 31])],
+                _unknown_fields: Default::default(),
+            }))
+            .await;
+        assert_eq!(malformed.unwrap_err().code(), Code::InvalidArgument);
+
+        // Repeated after the window is the recurring fingerprint
+        clock.advance(chrono::Duration::days(91));
+        let third = service
+            .compute_single_fingerprint(request())
+            .await?
+            .into_inner();
+        assert_eq!(
+            third.duplicate.unwrap().status,
+            DuplicateStatus::DUPLICATE_STATUS_RECURRING
+        );
+
+        // Recurring fingerprint is not counted as the duplicate
+        assert_eq!(
+            service.counters().stats(),
+            ServiceStats {
+                computed: 3,
+                duplicates: 1,
+                failed: 0
+            }
+        );
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use chrono::{Datelike, Utc};
-    use lazy_static::lazy_static;
-    use std::net::SocketAddr;
-    use volo::FastStr;
-    use fingerprinting_core::Compact;
-    use super::*;
+    #[tokio::test]
+    pub async fn test_idempotent_retry() -> Result<(), anyhow::Error> {
+        use chrono::TimeZone;
+        use fingerprinting_store::MemoryFingerprintStore;
+        use net::outbe::fingerprint::v1::FingerprintService as _;
 
-    lazy_static! {
-        static ref CLIENT: net::outbe::fingerprint::v1::FingerprintServiceClient = {
-            let addr: SocketAddr = "[::1]:9000".parse().unwrap();
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let accumulator = Arc::new(Mutex::new(EpochAccumulator::new(now.date_naive())));
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .with_store(Some(Arc::new(MemoryFingerprintStore::new())))
+            .with_idempotency(Some(Arc::new(IdempotencyCache::new(
+                16,
+                chrono::Duration::hours(1),
+            ))))
+            .with_accumulator(Some(accumulator.clone()))
+            .with_clock(Arc::new(FixedClock::new(now)));
+        let request = |idempotency_key: Option<&str>| {
+            let mut request = Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data(now)),
+                ..Default::default()
+            });
+            if let Some(idempotency_key) = idempotency_key {
+                request
+                    .metadata_mut()
+                    .insert(IDEMPOTENCY_KEY, idempotency_key.parse().unwrap());
+            }
+            request
+        };
 
-            net::outbe::fingerprint::v1::FingerprintServiceClientBuilder::new(
-                "fingerprinting-grpc-agent-client",
-            )
-            .address(addr)
-            .build()
+        let first = service
+            .compute_single_fingerprint(request(Some("retry-1")))
+            .await?
+            .into_inner();
+        // Retry is answered with the completed response, it is neither computed nor stored again
+        let retried = service
+            .compute_single_fingerprint(request(Some("retry-1")))
+            .await?
+            .into_inner();
+        assert_eq!(retried, first);
+        assert_eq!(service.counters().stats().computed, 1);
+
+        let repeated = service
+            .compute_single_fingerprint(request(Some("retry-2")))
+            .await?
+            .into_inner();
+        assert_eq!(
+            repeated.duplicate.unwrap().status,
+            DuplicateStatus::DUPLICATE_STATUS_DUPLICATE
+        );
+
+        // Only the new fingerprint is accumulated into the epoch
+        let fingerprint = wire::decode_scalar(&first.fingerprint.unwrap().fingerprint)?;
+        let mut expected = EpochAccumulator::new(now.date_naive());
+        expected.append(KEY_EPOCH, &fingerprint);
+        assert_eq!(*accumulator.lock().unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_namespaced_fingerprint() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let tx_date = Utc::now();
+        let request = || {
+            Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data(tx_date)),
+                salt: Default::default(),
+                with_commitments: false,
+                encodings: Default::default(),
+                validate_only: false,
+                _unknown_fields: Default::default(),
+            })
+        };
+        let fingerprint = |service: FingerprintService<NaiveProtocol>| async move {
+            service
+                .compute_single_fingerprint(request())
+                .await
+                .map(|response| response.into_inner().fingerprint.unwrap())
+        };
+
+        let unnamed = FingerprintService::new(NaiveProtocol::new(Fr::from(42)));
+        let staging = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .with_namespace(Some(Namespace::new("staging")?));
+        let versioned = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .with_wire_version(WireVersion::V2);
+
+        let info = staging
+            .get_service_info(Request::new(GetServiceInfoRequest::default()))
+            .await?
+            .into_inner();
+        assert_eq!(info.namespace, "staging");
+        assert_eq!(info.wire_version, 1);
+        check_service_parameters(&info)?;
+        let drifted = GetServiceInfoResponse {
+            parameters_digest: pilota::Bytes::from_static(&[0u8; 32]),
+            ..info.clone()
         };
+        assert!(check_service_parameters(&drifted).is_err());
+
+        let info = versioned
+            .get_service_info(Request::new(GetServiceInfoRequest::default()))
+            .await?
+            .into_inner();
+        assert_eq!(info.wire_version, 2);
+        check_service_parameters(&info)?;
+        let unknown = GetServiceInfoResponse {
+            wire_version: 7,
+            ..info
+        };
+        assert!(check_service_parameters(&unknown).is_err());
+
+        let unnamed = fingerprint(unnamed).await?;
+        let staging = fingerprint(staging).await?;
+        assert_eq!(unnamed.namespace, "");
+        assert_eq!(staging.namespace, "staging");
+        assert_ne!(unnamed.fingerprint, staging.fingerprint);
+        assert_ne!(
+            fingerprint(versioned).await?.fingerprint,
+            unnamed.fingerprint
+        );
+
+        Ok(())
     }
+
     #[tokio::test]
-    pub async fn test_fingerprint_computation() -> Result<(), anyhow::Error> {
+    pub async fn test_residency_tagged_transactions() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
         let tx_date = Utc::now();
+        let request = |residency: &'static str| {
+            Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(TransactionFingerprintDataDto {
+                    residency: residency.into(),
+                    ..transaction_data(tx_date)
+                }),
+                salt: Default::default(),
+                with_commitments: false,
+                encodings: Default::default(),
+                validate_only: false,
+                _unknown_fields: Default::default(),
+            })
+        };
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .with_residency(ResidencyRouting::default().with_route("EU", vec![1, 2, 3]));
 
-        let transaction_data = net::outbe::fingerprint::v1::TransactionFingerprintData {
-            bic: FastStr::new("BCEELU21"),
-            amount: Some(net::outbe::common::v1::Money {
-                currency: net::outbe::common::v1::Currency::CURRENCY_EUR,
-                units: 1000,
-                atto: 0,
+        let untagged = service
+            .compute_single_fingerprint(request(""))
+            .await?
+            .into_inner();
+        let tagged = service
+            .compute_single_fingerprint(request("EU"))
+            .await?
+            .into_inner();
+        // Residency routes the computation, it does not change the fingerprint
+        assert_eq!(untagged.fingerprint, tagged.fingerprint);
+
+        let refused = service
+            .compute_single_fingerprint(request("US"))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(refused.code(), Code::FailedPrecondition);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_input_size_limits() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let tx_date = Utc::now();
+        let request = |merchant_id: String| {
+            Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(TransactionFingerprintDataDto {
+                    merchant_id: merchant_id.into(),
+                    ..transaction_data(tx_date)
+                }),
+                salt: Default::default(),
+                with_commitments: false,
+                encodings: Default::default(),
+                validate_only: false,
                 _unknown_fields: Default::default(),
-            }),
-            date_time: Some(net::outbe::common::v1::Timestamp {
-                seconds: tx_date.timestamp() as u64,
-                nanos: tx_date.timestamp_subsec_nanos(),
+            })
+        };
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42))).with_input_limits(
+            InputLimits {
+                merchant_id: 8,
+                ..InputLimits::default()
+            },
+        );
+
+        assert!(service
+            .compute_single_fingerprint(request("MID-1".to_string()))
+            .await
+            .is_ok());
+        let refused = service
+            .compute_single_fingerprint(request("MID-000123".to_string()))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(refused.code(), Code::InvalidArgument);
+        assert_eq!(
+            refused.message(),
+            "Field merchant_id should be at most 8 bytes long, given 10 bytes"
+        );
+
+        // Megabyte long fields are refused by the conversion whatever the limits of the service
+        let oversized: Result<RawTransaction, Status> = TransactionFingerprintDataDto {
+            bic: "B".repeat(1024 * 1024).into(),
+            ..transaction_data(tx_date)
+        }
+        .try_into();
+        assert_eq!(oversized.err().unwrap().code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_fingerprint_encodings() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)));
+        let tx_date = Utc::now();
+        let fingerprint = |encodings: Vec<FingerprintEncoding>| {
+            service.compute_single_fingerprint(Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data(tx_date)),
+                salt: Default::default(),
+                with_commitments: false,
+                encodings,
+                validate_only: false,
                 _unknown_fields: Default::default(),
-            }),
-            wwd: Some(net::outbe::common::v1::Date {
-                year: tx_date.year() as u32,
-                month: tx_date.month(),
-                day: tx_date.day(),
+            }))
+        };
+
+        let default = fingerprint(vec![]).await?.into_inner().fingerprint.unwrap();
+        assert_eq!(default.fingerprint.len(), 32);
+        assert!(default.hex_fingerprint.is_empty() && default.short_fingerprint.is_empty());
+
+        let encoded = fingerprint(vec![
+            FingerprintEncoding::FINGERPRINT_ENCODING_HEX,
+            FingerprintEncoding::FINGERPRINT_ENCODING_SHORT,
+            FingerprintEncoding::FINGERPRINT_ENCODING_BASE64URL,
+            FingerprintEncoding::FINGERPRINT_ENCODING_CHECKED,
+        ])
+        .await?
+        .into_inner()
+        .fingerprint
+        .unwrap();
+        assert!(encoded.fingerprint.is_empty() && encoded.compact_fingerprint.is_empty());
+        assert_eq!(
+            encoded.hex_fingerprint.as_str(),
+            default
+                .fingerprint
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        );
+        assert!(default
+            .compact_fingerprint
+            .starts_with(encoded.short_fingerprint.as_str()));
+        assert_eq!(encoded.short_fingerprint.len(), SHORT_FINGERPRINT_SIZE);
+        assert_eq!(
+            wire::decode_text(TextEncoding::Base64Url, &encoded.base64url_fingerprint)?,
+            default.fingerprint.as_ref()
+        );
+        assert_eq!(
+            wire::decode_checked(&encoded.checked_fingerprint)?,
+            default.fingerprint.as_ref()
+        );
+
+        let unknown =
+            fingerprint(vec![FingerprintEncoding::FINGERPRINT_ENCODING_UNSPECIFIED]).await;
+        assert_eq!(unknown.unwrap_err().code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_shadow_fingerprints() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let tx_date = Utc::now();
+        let request = || {
+            Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data(tx_date)),
+                salt: "salt".into(),
+                with_commitments: false,
+                encodings: Default::default(),
+                validate_only: false,
                 _unknown_fields: Default::default(),
-            }),
-            _unknown_fields: Default::default(),
+            })
         };
+        let shadowed = |candidate: Fr| async move {
+            let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42))).with_shadow(
+                Some(ShadowFingerprinting::new(NaiveProtocol::new(candidate))),
+            );
+            service.compute_single_fingerprint(request()).await?;
+            service.compute_single_fingerprint(request()).await?;
 
-        println!("Transaction data: {:?}", transaction_data);
-        println!("Requesting the fingerprint computation... from cooperative agents");
+            // Candidate fingerprints are computed in the background
+            for _ in 0..100 {
+                let info = service
+                    .get_service_info(Request::new(GetServiceInfoRequest::default()))
+                    .await?
+                    .into_inner();
+                let shadow = info.shadow.unwrap();
+                if shadow.compared == 2 {
+                    return Ok::<_, anyhow::Error>(shadow);
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            Err(anyhow::anyhow!("Shadow fingerprints are not compared"))
+        };
 
-        let response = CLIENT
-            .compute_single_fingerprint(ComputeSingleFingerprintRequest {
-                transaction_data: Some(transaction_data),
+        // Upgrade keeping the secret never diverges
+        let upgrade = shadowed(Fr::from(42)).await?;
+        assert_eq!((upgrade.diverged, upgrade.failed), (0, 0));
+
+        let rotation = shadowed(Fr::from(7)).await?;
+        assert_eq!((rotation.diverged, rotation.failed), (2, 0));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_fingerprint_sampling() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        assert!(FingerprintSampling::new(NaiveProtocol::new(Fr::from(42)), 0.0).is_err());
+
+        // Reference of the different secret diverges on every sampled fingerprint
+        let sampling = FingerprintSampling::new(NaiveProtocol::new(Fr::from(7)), 0.5)?;
+        let service =
+            FingerprintService::new(NaiveProtocol::new(Fr::from(42))).with_sampling(Some(sampling));
+        let tx_date = Utc::now();
+        for _ in 0..4 {
+            service
+                .compute_single_fingerprint(Request::new(ComputeSingleFingerprintRequest {
+                    transaction_data: Some(transaction_data(tx_date)),
+                    salt: Default::default(),
+                    with_commitments: false,
+                    encodings: Default::default(),
+                    validate_only: false,
+                    _unknown_fields: Default::default(),
+                }))
+                .await?;
+        }
+
+        for _ in 0..100 {
+            let info = service
+                .get_service_info(Request::new(GetServiceInfoRequest::default()))
+                .await?
+                .into_inner();
+            assert!(info.shadow.is_none());
+            let sampling = info.sampling.unwrap();
+            if sampling.compared == 2 {
+                assert_eq!((sampling.diverged, sampling.failed), (2, 0));
+                return Ok(());
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        Err(anyhow::anyhow!("Sampled fingerprints are not recomputed"))
+    }
+
+    #[tokio::test]
+    pub async fn test_series_fingerprints() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)));
+        let first = Utc::now();
+        let installment = |date_time| net::outbe::fingerprint::v1::TransactionFingerprintData {
+            series_id: FastStr::new("loan-42"),
+            ..transaction_data(date_time)
+        };
+
+        let derived = service
+            .derive_series_fingerprints(Request::new(DeriveSeriesFingerprintsRequest {
+                transaction_data: Some(installment(first)),
+                interval_days: 7,
+                interval_months: 0,
+                installments: 3,
+                salt: Default::default(),
+                encodings: Default::default(),
+                _unknown_fields: Default::default(),
+            }))
+            .await?
+            .into_inner()
+            .installments;
+        assert_eq!(derived.len(), 3);
+        assert_eq!(derived[2].sequence, 2);
+
+        // Fingerprint of the actual installment matches the derived one
+        let actual = service
+            .compute_single_fingerprint(Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(installment(first + chrono::Duration::days(14))),
+                salt: Default::default(),
+                with_commitments: false,
+                encodings: Default::default(),
+                validate_only: false,
+                _unknown_fields: Default::default(),
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(actual.fingerprint, derived[2].fingerprint);
+
+        let invalid = service
+            .derive_series_fingerprints(Request::new(DeriveSeriesFingerprintsRequest {
+                transaction_data: Some(installment(first)),
+                interval_days: 7,
+                interval_months: 1,
+                installments: 3,
+                salt: Default::default(),
+                encodings: Default::default(),
+                _unknown_fields: Default::default(),
+            }))
+            .await;
+        assert_eq!(invalid.unwrap_err().code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_batch_dedup() -> Result<(), anyhow::Error> {
+        use fingerprinting_store::tally::MemoryTallyLedger;
+        use fingerprinting_store::MemoryFingerprintStore;
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let seen = Utc::now();
+        let tallies = Arc::new(MemoryTallyLedger::new());
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .with_store(Some(Arc::new(MemoryFingerprintStore::new())))
+            .with_tallies(Some(tallies.clone()))
+            .with_clock(Arc::new(FixedClock::new(seen)));
+
+        let unseen = seen - chrono::Duration::days(1);
+        service
+            .compute_single_fingerprint(Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data(seen)),
+                salt: Default::default(),
+                with_commitments: false,
+                encodings: Default::default(),
+                validate_only: false,
+                _unknown_fields: Default::default(),
+            }))
+            .await?;
+
+        let item = |item_id: &str, tx_date| Item {
+            item_id: FastStr::new(item_id),
+            transaction_data: Some(transaction_data(tx_date)),
+        };
+        let batch = |dedup| {
+            Request::new(ComputeBatchFingerprintRequest {
+                transaction_batch: vec![
+                    item("seen", seen),
+                    item("unseen-1", unseen),
+                    item("unseen-2", unseen),
+                ],
+                salt: Default::default(),
+                with_commitments: false,
+                dedup,
+                encodings: Default::default(),
+                validate_only: false,
+                dead_letter: false,
+                derive_item_ids: false,
                 _unknown_fields: Default::default(),
             })
+        };
+
+        let mut responses = service
+            .compute_batch_fingerprint(batch(true))
+            .await?
+            .into_inner()
+            .try_collect::<Vec<_>>()
             .await?;
+        responses.sort_by(|a, b| a.item_id.cmp(&b.item_id));
 
-        let fingerprint = response.into_inner();
-        let fingerprint = fingerprint.fingerprint.unwrap();
-        let fixed_bytes = fingerprint.fingerprint.first_chunk::<32>().unwrap();
+        assert!(responses[0].seen_before);
+        assert!(responses[0].first_seen.is_some());
+        // Repeated item of the same batch is seen before, whichever of them is computed first
+        assert!(responses[1].seen_before ^ responses[2].seen_before);
 
-        let fr_fingerprint = Fr::from_bytes(fixed_bytes).unwrap();
-        let compact_fingerprint = fingerprint.compact_fingerprint.to_string();
+        // Only the new fingerprints are counted to the statistics of the epoch they are stored in
+        assert_eq!(
+            tallies.tallies(seen.date_naive()).await?,
+            std::collections::BTreeMap::from([(978, 2)])
+        );
 
-        println!(
-            "Fingerprint: {} fr {}",
-            compact_fingerprint,
-            fr_fingerprint.compact()
+        let without_store = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .compute_batch_fingerprint(batch(true))
+            .await;
+        assert_eq!(
+            without_store.err().unwrap().code(),
+            Code::FailedPrecondition
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_validate_only() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42))).with_input_limits(
+            InputLimits {
+                merchant_id: 8,
+                ..InputLimits::default()
+            },
+        );
+        let tx_date = Utc::now();
+        let tx = |merchant_id: &str| TransactionFingerprintDataDto {
+            merchant_id: FastStr::new(merchant_id),
+            ..transaction_data(tx_date)
+        };
+
+        let validate = |transaction_data| {
+            service.compute_single_fingerprint(Request::new(ComputeSingleFingerprintRequest {
+                transaction_data: Some(transaction_data),
+                validate_only: true,
+                ..Default::default()
+            }))
+        };
+        let valid = validate(tx("MID-1")).await?.into_inner();
+        assert!(valid.validation.unwrap().valid);
+        assert!(valid.fingerprint.is_none());
+        let invalid = validate(tx("MID-000123")).await?.into_inner();
+        assert_eq!(
+            invalid.validation.unwrap().reason,
+            "Field merchant_id should be at most 8 bytes long, given 10 bytes"
+        );
+
+        // Invalid items of the batch do not fail it
+        let item = |item_id: &str, transaction_data| Item {
+            item_id: FastStr::new(item_id),
+            transaction_data: Some(transaction_data),
+        };
+        let mut responses = service
+            .compute_batch_fingerprint(Request::new(ComputeBatchFingerprintRequest {
+                transaction_batch: vec![
+                    item("1", tx("MID-1")),
+                    item("2", tx("MID-000123")),
+                    item(
+                        "3",
+                        TransactionFingerprintDataDto {
+                            bic: FastStr::new("BCEE"),
+                            ..tx("MID-1")
+                        },
+                    ),
+                ],
+                validate_only: true,
+                ..Default::default()
+            }))
+            .await?
+            .into_inner()
+            .try_collect::<Vec<_>>()
+            .await?;
+        responses.sort_by(|a, b| a.item_id.cmp(&b.item_id));
+        let verdicts: Vec<_> = responses
+            .iter()
+            .map(|response| response.validation.as_ref().unwrap().valid)
+            .collect();
+        assert_eq!(verdicts, [true, false, false]);
+        assert!(responses
+            .iter()
+            .all(|response| response.fingerprint.is_none()));
+
+        // Nothing is computed
+        assert_eq!(service.counters().stats().computed, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_batch_dead_letter() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)));
+        let tx_date = Utc::now();
+        let item = |item_id: &str, bic: &str| Item {
+            item_id: FastStr::new(item_id),
+            transaction_data: Some(TransactionFingerprintDataDto {
+                bic: FastStr::new(bic),
+                ..transaction_data(tx_date)
+            }),
+        };
+        let batch = |dead_letter| {
+            Request::new(ComputeBatchFingerprintRequest {
+                transaction_batch: vec![item("1", "BCEELU21"), item("2", "BCEE")],
+                dead_letter,
+                ..Default::default()
+            })
+        };
+
+        // Failed item ends the stream with its status
+        let failed = service
+            .compute_batch_fingerprint(batch(false))
+            .await?
+            .into_inner()
+            .try_collect::<Vec<_>>()
+            .await;
+        let status = failed.unwrap_err();
+
+        // Dead letter of the failed item is reported in-band, the rest of the batch is computed
+        let mut responses = service
+            .compute_batch_fingerprint(batch(true))
+            .await?
+            .into_inner()
+            .try_collect::<Vec<_>>()
+            .await?;
+        responses.sort_by(|a, b| a.item_id.cmp(&b.item_id));
+        assert!(responses[0].fingerprint.is_some());
+        assert!(responses[0].failure.is_none());
+        let failure = responses[1].failure.as_ref().unwrap();
+        assert_eq!(failure.code, status.code() as i32);
+        assert_eq!(failure.message, status.message());
+        assert!(responses[1].fingerprint.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_batch_item_ids() -> Result<(), anyhow::Error> {
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)));
+        let tx_date = Utc::now();
+        let item = |item_id: &str, tx_date| Item {
+            item_id: FastStr::new(item_id),
+            transaction_data: Some(transaction_data(tx_date)),
+        };
+        let batch = |items, derive_item_ids| {
+            Request::new(ComputeBatchFingerprintRequest {
+                transaction_batch: items,
+                salt: pilota::Bytes::from_static(b"salt"),
+                derive_item_ids,
+                ..Default::default()
+            })
+        };
+
+        // Colliding ids would scramble the correlation of the results
+        let refused = service
+            .compute_batch_fingerprint(batch(vec![item("a", tx_date), item("a", tx_date)], false))
+            .await;
+        assert_eq!(refused.err().unwrap().code(), Code::InvalidArgument);
+
+        // Derived ids are the ones the caller derives, the ids of the caller are kept
+        let later = tx_date + chrono::Duration::seconds(1);
+        let responses = service
+            .compute_batch_fingerprint(batch(vec![item("", tx_date), item("b", later)], true))
+            .await?
+            .into_inner()
+            .try_collect::<Vec<_>>()
+            .await?;
+        let item_ids = responses
+            .iter()
+            .map(|response| response.item_id.to_string())
+            .collect::<std::collections::HashSet<_>>();
+        let raw_tx: RawTransaction = transaction_data(tx_date).try_into()?;
+        let derived = derive_item_id(&raw_tx, b"salt");
+        assert_eq!(
+            item_ids,
+            std::collections::HashSet::from([derived, "b".to_string()])
+        );
+
+        // Same transaction listed twice gets the same derived id
+        let refused = service
+            .compute_batch_fingerprint(batch(vec![item("", tx_date), item("", tx_date)], true))
+            .await;
+        assert_eq!(refused.err().unwrap().code(), Code::InvalidArgument);
+        service
+            .compute_batch_fingerprint(batch(vec![item("", tx_date), item("", tx_date)], false))
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_usage_accounting() -> Result<(), anyhow::Error> {
+        use chrono::TimeZone;
+        use fingerprinting_store::usage::MemoryUsageLedger;
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        fn keyed<T>(message: T, key: &'static str) -> Request<T> {
+            let mut request = Request::new(message);
+            request.metadata_mut().insert(API_KEY, key.parse().unwrap());
+            request
+        }
+
+        let accounting = UsageAccounting::new(Arc::new(MemoryUsageLedger::new()))
+            .with_key(
+                "secret-a",
+                ApiKey {
+                    name: "bank-a".to_string(),
+                    monthly_quota: Some(2),
+                    admin: false,
+                },
+            )
+            .with_key(
+                "secret-ops",
+                ApiKey {
+                    name: "ops".to_string(),
+                    monthly_quota: None,
+                    admin: true,
+                },
+            );
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)))
+            .with_clock(Arc::new(FixedClock::new(now)))
+            .with_usage(Some(accounting));
+        let single = || ComputeSingleFingerprintRequest {
+            transaction_data: Some(transaction_data(now)),
+            ..Default::default()
+        };
+
+        let status = service
+            .compute_single_fingerprint(Request::new(single()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        service
+            .compute_single_fingerprint(keyed(single(), "secret-a"))
+            .await?;
+
+        // Batch above the quota is refused as a whole
+        let item = |item_id: &str| Item {
+            item_id: FastStr::new(item_id),
+            transaction_data: Some(transaction_data(now)),
+        };
+        let batch = |items| ComputeBatchFingerprintRequest {
+            transaction_batch: items,
+            ..Default::default()
+        };
+        let status = service
+            .compute_batch_fingerprint(keyed(batch(vec![item("1"), item("2")]), "secret-a"))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        service
+            .compute_batch_fingerprint(keyed(batch(vec![item("1")]), "secret-a"))
+            .await?
+            .into_inner()
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let usage = service
+            .get_usage(keyed(GetUsageRequest::default(), "secret-a"))
+            .await?
+            .into_inner();
+        assert_eq!(usage.month, "2025-09");
+        assert_eq!(usage.keys.len(), 1);
+        assert_eq!(
+            (usage.keys[0].fingerprints, usage.keys[0].agent_rounds),
+            (2, 2)
         );
+        assert_eq!(usage.keys[0].monthly_quota, 2);
+
+        // Admin key gets the report of all the keys
+        let report = service
+            .get_usage(keyed(
+                GetUsageRequest {
+                    month: FastStr::new("2025-09"),
+                    ..Default::default()
+                },
+                "secret-ops",
+            ))
+            .await?
+            .into_inner();
+        assert_eq!(report.keys.len(), 1);
+        assert_eq!(report.keys[0].name, "bank-a");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_inclusion_proofs() -> Result<(), anyhow::Error> {
+        use chrono::TimeZone;
+        use fingerprinting_store::merkle::{self, EpochInclusion, InclusionProof, ProofStep};
+        use fingerprinting_store::StoredFingerprint;
+        use futures::future::{BoxFuture, FutureExt};
+        use net::outbe::fingerprint::v1::FingerprintService as _;
+
+        // Single archived epoch
+        struct Archive(NaiveDate, Vec<StoredFingerprint>);
+
+        impl EpochProofs for Archive {
+            fn inclusion(
+                &self,
+                epoch: NaiveDate,
+                fingerprint: Fr,
+                key_epoch: u64,
+            ) -> BoxFuture<'_, Result<Option<EpochInclusion>, anyhow::Error>> {
+                let inclusion = (epoch == self.0).then(|| EpochInclusion {
+                    root: merkle::archive_root(&self.1),
+                    proof: merkle::inclusion_proof(&self.1, key_epoch, &fingerprint),
+                    anchor: Some(format!("archive/epoch={}/manifest.json", epoch)),
+                });
+                async move { Ok(inclusion) }.boxed()
+            }
+        }
+
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let stored = |fingerprint: u64| StoredFingerprint {
+            fingerprint: Fr::from(fingerprint),
+            key_epoch: KEY_EPOCH,
+            first_seen: now,
+            last_seen: now,
+            occurrences: 1,
+            window_start: now,
+        };
+        let archive = Archive(now.date_naive(), (40..45).map(stored).collect());
+        let request = |fingerprint: u64, epoch: &str| {
+            Request::new(GetInclusionProofRequest {
+                fingerprint: Fr::from(fingerprint).to_bytes().to_vec().into(),
+                epoch: FastStr::new(epoch),
+                ..Default::default()
+            })
+        };
+
+        let service = FingerprintService::new(NaiveProtocol::new(Fr::from(42)));
+        let status = service
+            .get_inclusion_proof(request(42, "2025-09-16"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        let service = service.with_archive(Some(Arc::new(archive)));
+        let response = service
+            .get_inclusion_proof(request(42, "2025-09-16"))
+            .await?
+            .into_inner();
+        let proof = InclusionProof {
+            steps: response
+                .steps
+                .iter()
+                .map(|step| ProofStep {
+                    sibling: step.sibling.as_ref().try_into().unwrap(),
+                    sibling_left: step.sibling_left,
+                })
+                .collect(),
+        };
+        let root: [u8; 32] = response.root.as_ref().try_into()?;
+        assert!(proof.verify(&root, response.key_epoch, &Fr::from(42)));
+        assert_eq!(response.anchor, "archive/epoch=2025-09-16/manifest.json");
+
+        for (fingerprint, epoch, code) in [
+            (45, "2025-09-16", Code::NotFound),
+            (42, "2025-09-17", Code::NotFound),
+            (42, "16.09.2025", Code::InvalidArgument),
+        ] {
+            let status = service
+                .get_inclusion_proof(request(fingerprint, epoch))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), code, "{}", status.message());
+        }
 
         Ok(())
     }
@@ -0,0 +1,122 @@
+//! Canonical records of the computed fingerprints for the archives, see `record.proto`
+
+use crate::net::outbe::fingerprint::v1::{Fingerprint as FingerprintDto, FingerprintRecord};
+use crate::recording::read_length_delimited;
+use crate::KEY_EPOCH;
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Utc};
+use fingerprinting_core::wire::{self, WireVersion};
+use halo2_axiom::halo2curves::bn256::Fr;
+use pilota::pb::Message;
+use pilota::LinkedBytes;
+use std::io::Write;
+use std::path::Path;
+
+/// Record of the fingerprint returned by the service, the raw encoding of the fingerprint is required
+pub fn fingerprint_record(
+    fingerprint: &FingerprintDto,
+    computed_at: DateTime<Utc>,
+) -> Result<FingerprintRecord, Error> {
+    if fingerprint.fingerprint.len() != wire::SCALAR_SIZE {
+        return Err(anyhow!(
+            "Raw encoding of the fingerprint is required for the record"
+        ));
+    }
+
+    Ok(FingerprintRecord {
+        fingerprint: fingerprint.fingerprint.clone(),
+        schema_version: WireVersion::CURRENT.as_u8() as u32,
+        key_epoch: KEY_EPOCH,
+        namespace: fingerprint.namespace.clone(),
+        computed_at: Some(computed_at.into()),
+        transcript_hash: Default::default(),
+        _unknown_fields: Default::default(),
+    })
+}
+
+/// Checks the record is well-formed, returns the fingerprint
+pub fn check_record(record: &FingerprintRecord) -> Result<Fr, Error> {
+    let schema_version = u8::try_from(record.schema_version)
+        .map_err(|_| anyhow!("Unknown schema version {}", record.schema_version))?;
+    let schema_version = WireVersion::try_from(schema_version)?;
+    if !WireVersion::SUPPORTED.contains(&schema_version) {
+        return Err(anyhow!(
+            "Schema version {} is not supported",
+            schema_version.as_u8()
+        ));
+    }
+    if record.computed_at.is_none() {
+        return Err(anyhow!("Computation time is missing"));
+    }
+    if !matches!(record.transcript_hash.len(), 0 | 32) {
+        return Err(anyhow!("Transcript hash should be 32 bytes long"));
+    }
+
+    wire::decode_scalar(record.fingerprint.as_ref())
+}
+
+/// Writes the length-delimited records
+pub struct FingerprintRecordWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> FingerprintRecordWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn write(&mut self, record: &FingerprintRecord) -> Result<(), Error> {
+        let mut buffer = LinkedBytes::new();
+        record.encode_length_delimited(&mut buffer)?;
+        self.writer.write_all(&buffer.concat())?;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// Reads the records file
+pub fn read_records(path: &Path) -> Result<Vec<FingerprintRecord>, Error> {
+    read_length_delimited(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fingerprinting_core::Compact;
+    use std::fs::File;
+
+    #[test]
+    fn test_records() -> Result<(), Error> {
+        let path =
+            std::env::temp_dir().join(format!("fingerprints-{}.records", std::process::id()));
+        let fingerprint = FingerprintDto::from(Fr::from(42));
+
+        let mut writer = FingerprintRecordWriter::new(File::create(&path)?);
+        writer.write(&fingerprint_record(&fingerprint, Utc::now())?)?;
+        writer.write(&fingerprint_record(&fingerprint, Utc::now())?)?;
+        writer.flush()?;
+
+        let records = read_records(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(check_record(&records[0])?.compact(), Fr::from(42).compact());
+
+        let unknown_schema = FingerprintRecord {
+            schema_version: 99,
+            ..records[0].clone()
+        };
+        assert!(check_record(&unknown_schema).is_err());
+
+        let compact_only = FingerprintDto {
+            fingerprint: Default::default(),
+            ..fingerprint
+        };
+        assert!(fingerprint_record(&compact_only, Utc::now()).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+//! Debug recording of the fingerprint exchanges, replayed against another instance to validate upgrades
+//! and key rotations
+//!
+//! The recording is a file of the length-delimited `RecordedExchange` messages, see `recording.proto`
+//! for what is (not) recorded.
+
+use crate::net::outbe::fingerprint::v1::{
+    Fingerprint as FingerprintDto, RecordedExchange, TransactionFingerprintData,
+};
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Utc};
+use pilota::pb::Message;
+use pilota::{Bytes, LinkedBytes};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+pub struct FingerprintRecorder {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl FingerprintRecorder {
+    /// Appends the exchanges to the recording at `path`
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    /// Records the exchange, failures are only logged since the recording never affects the caller
+    pub fn record(
+        &self,
+        transaction_data: &TransactionFingerprintData,
+        fingerprint: &FingerprintDto,
+        recorded_at: DateTime<Utc>,
+    ) {
+        let exchange = RecordedExchange {
+            transaction_data: Some(transaction_data.clone()),
+            fingerprint: Some(fingerprint.clone()),
+            recorded_at: Some(recorded_at.into()),
+            _unknown_fields: Default::default(),
+        };
+
+        if let Err(e) = self.write(&exchange) {
+            log::warn!("Failed to record the exchange: {}", e);
+        }
+    }
+
+    fn write(&self, exchange: &RecordedExchange) -> Result<(), Error> {
+        let mut buffer = LinkedBytes::new();
+        exchange.encode_length_delimited(&mut buffer)?;
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow!("Recording file lock is poisoned"))?;
+        file.write_all(&buffer.concat())?;
+        // Exchanges are flushed one by one, so the recording survives the crash of the instance
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Reads the exchanges of the recording
+pub fn read_recording(path: &Path) -> Result<Vec<RecordedExchange>, Error> {
+    read_length_delimited(path)
+}
+
+/// Reads the file of the length-delimited messages
+pub(crate) fn read_length_delimited<M: Message + Default>(path: &Path) -> Result<Vec<M>, Error> {
+    let mut file = Bytes::from(std::fs::read(path)?);
+    let mut messages = vec![];
+
+    while !file.is_empty() {
+        let length = pilota::pb::decode_length_delimiter(file.clone())?;
+        let frame = pilota::pb::length_delimiter_len(length) + length;
+        if frame > file.len() {
+            return Err(anyhow!(
+                "File is truncated after {} messages",
+                messages.len()
+            ));
+        }
+
+        messages.push(M::decode_length_delimited(file.split_to(frame))?);
+    }
+
+    Ok(messages)
+}
@@ -0,0 +1,58 @@
+//! Data-residency routing of the transactions
+//!
+//! Transactions could carry the residency tag, e.g. `EU`, restricting the agents their blinded values are sent to.
+//! Every tag processed by the coordinator is routed to its own subset of agents (of regions for the regional
+//! topologies), the transactions of the other tags are refused rather than computed via the agents not allowed
+//! for them. Untagged transactions are computed via any agents.
+
+use std::collections::HashMap;
+use volo_grpc::{Code, Status};
+
+#[derive(Debug, Clone, Default)]
+pub struct ResidencyRouting {
+    routes: HashMap<String, Vec<usize>>,
+}
+
+impl ResidencyRouting {
+    /// Transactions of the `residency` are computed via the `agents` only, the coordinator agent included
+    pub fn with_route(mut self, residency: impl Into<String>, agents: Vec<usize>) -> Self {
+        self.routes.insert(residency.into(), agents);
+        self
+    }
+
+    /// Agents allowed for the `residency`, `None` when the transaction is not tagged
+    pub(crate) fn agents(&self, residency: &str) -> Result<Option<&[usize]>, Box<Status>> {
+        if residency.is_empty() {
+            return Ok(None);
+        }
+
+        match self.routes.get(residency) {
+            Some(agents) => Ok(Some(agents)),
+            None => Err(Box::new(Status::new(
+                Code::FailedPrecondition,
+                format!(
+                    "Transactions of residency {} are not processed by this coordinator",
+                    residency
+                ),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_residency_routing() {
+        let routing = ResidencyRouting::default().with_route("EU", vec![1, 2, 3]);
+
+        assert_eq!(routing.agents("").unwrap(), None);
+        assert_eq!(routing.agents("EU").unwrap(), Some(&[1, 2, 3][..]));
+
+        let refused = routing.agents("US").err().unwrap();
+        assert_eq!(refused.code(), Code::FailedPrecondition);
+        // Tags are never routed by the coordinators without the routing
+        assert!(ResidencyRouting::default().agents("EU").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Sampled recomputation of the fingerprints with the reference implementation, guarding the optimized pipeline
+//!
+//! Every n-th returned fingerprint is computed once more in the background with the reference protocol, usually
+//! the naive protocol with the secret reconstructed from the shares (test environments only). Unlike the shadow
+//! mode the reference is expected to match always, any divergence is the bug of the pipeline and is logged as the error.
+
+use crate::shadow::{ShadowFingerprinting, ShadowStats};
+use anyhow::{anyhow, Error};
+use fingerprinting_core::clock::Epoch;
+use fingerprinting_core::namespace::Namespace;
+use fingerprinting_core::schema::FingerprintSchema;
+use fingerprinting_core::wire::WireVersion;
+use fingerprinting_core::FingerprintProtocol;
+use fingerprinting_types::RawTransaction;
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub struct FingerprintSampling {
+    reference: ShadowFingerprinting,
+    /// Every n-th fingerprint is sampled
+    every: u64,
+    fingerprints: AtomicU64,
+}
+
+impl FingerprintSampling {
+    /// Samples the `rate` (above 0, up to 1) of the fingerprints, recomputed with the `reference` protocol
+    pub fn new<P: FingerprintProtocol<Fr> + Send + Sync + 'static>(
+        reference: P,
+        rate: f64,
+    ) -> Result<Self, Error> {
+        if !(rate > 0.0 && rate <= 1.0) {
+            return Err(anyhow!(
+                "Sampling rate {} should be above 0 and up to 1",
+                rate
+            ));
+        }
+
+        Ok(Self {
+            reference: ShadowFingerprinting::new(reference).alerting(),
+            every: (1.0 / rate).round() as u64,
+            fingerprints: AtomicU64::new(0),
+        })
+    }
+
+    /// Namespace of the service, the reference computes the same fingerprints
+    pub fn with_namespace(mut self, namespace: Option<Namespace>) -> Self {
+        self.reference = self.reference.with_namespace(namespace);
+        self
+    }
+
+    /// Fingerprint schema of the service, the reference computes the same fingerprints
+    pub fn with_schema(mut self, schema: Option<Arc<FingerprintSchema>>) -> Self {
+        self.reference = self.reference.with_schema(schema);
+        self
+    }
+
+    /// Wire version of the service, the reference computes the same fingerprints
+    pub fn with_wire_version(mut self, version: WireVersion) -> Self {
+        self.reference = self.reference.with_wire_version(version);
+        self
+    }
+
+    /// Epoch of the service, the reference computes the same fingerprints
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.reference = self.reference.with_epoch(epoch);
+        self
+    }
+
+    pub fn stats(&self) -> ShadowStats {
+        self.reference.stats()
+    }
+
+    /// Whether the next fingerprint is recomputed
+    pub(crate) fn sample(&self) -> bool {
+        self.fingerprints
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(self.every)
+    }
+
+    /// Recomputes the fingerprint of the sampled transaction in the background
+    pub(crate) fn verify(&self, raw_tx: RawTransaction, salt: pilota::Bytes, fingerprint: Fr) {
+        self.reference.compare(raw_tx, salt, fingerprint);
+    }
+}
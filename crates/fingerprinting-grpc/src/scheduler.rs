@@ -0,0 +1,383 @@
+//! Work queue of the protocol rounds shared by all the in-flight requests
+//!
+//! Every request (single call, batch, series) queues its rounds into its own lane, the workers take the rounds from
+//! the lanes in turn, so the single call waits for one round of every busy batch at most rather than for the whole
+//! batches queued before it. A worker takes up to `round_size` rounds of the different requests at once and evaluates
+//! the ones via the same agents by one `FingerprintProtocol::process_batch` call, so the concurrent batches share the
+//! fixed number of workers instead of each running its own pipeline. The protocol is bound by the construction, every
+//! request of the service is computed via the same one.
+
+use anyhow::{anyhow, Error};
+use fingerprinting_core::{FingerprintProtocol, ViaAgents};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, Notify};
+
+/// Workers of the `ComputationScheduler` and the rounds each of them dispatches at once
+#[derive(Debug, Clone, Copy)]
+pub struct SchedulerLimits {
+    pub workers: NonZeroUsize,
+    pub round_size: NonZeroUsize,
+}
+
+impl Default for SchedulerLimits {
+    /// 16 workers dispatching up to 4 rounds each
+    fn default() -> Self {
+        Self::new(
+            NonZeroUsize::new(16).unwrap(),
+            NonZeroUsize::new(4).unwrap(),
+        )
+    }
+}
+
+impl SchedulerLimits {
+    /// `workers` dispatching up to `round_size` rounds at once each
+    pub fn new(workers: NonZeroUsize, round_size: NonZeroUsize) -> Self {
+        Self {
+            workers,
+            round_size,
+        }
+    }
+
+    /// Rounds all the workers take at once, the items a request keeps in flight are bounded by it
+    pub fn lane_depth(&self) -> usize {
+        self.workers.get() * self.round_size.get()
+    }
+}
+
+pub(crate) struct ComputationScheduler<P> {
+    protocol: Arc<P>,
+    limits: SchedulerLimits,
+    queue: Arc<WorkQueue>,
+    started: AtomicBool,
+}
+
+impl<P: FingerprintProtocol<Fr> + Send + Sync + 'static> ComputationScheduler<P> {
+    /// Work queue computing via the `protocol`, the workers are started by the first request
+    pub(crate) fn new(protocol: Arc<P>, limits: SchedulerLimits) -> Self {
+        Self {
+            protocol,
+            limits,
+            queue: Arc::default(),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn limits(&self) -> SchedulerLimits {
+        self.limits
+    }
+
+    /// Lane of the new request
+    pub(crate) fn request(&self) -> RequestLane {
+        if !self.started.swap(true, Ordering::AcqRel) {
+            for _ in 0..self.limits.workers.get() {
+                tokio::spawn(work(
+                    self.queue.clone(),
+                    self.protocol.clone(),
+                    self.limits.round_size.get(),
+                ));
+            }
+        }
+
+        RequestLane {
+            id: self.queue.open(),
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<P> Drop for ComputationScheduler<P> {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+struct Round {
+    unblinded: Fr,
+    agents: Option<Vec<usize>>,
+    result: oneshot::Sender<Result<Fr, Error>>,
+}
+
+#[derive(Default)]
+struct WorkQueue {
+    state: Mutex<QueueState>,
+    available: Notify,
+}
+
+#[derive(Default)]
+struct QueueState {
+    next_lane: u64,
+    /// Lanes with the queued rounds, served in turn
+    lanes: VecDeque<(u64, VecDeque<Round>)>,
+    closed: bool,
+}
+
+impl WorkQueue {
+    fn state(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn open(&self) -> u64 {
+        let mut state = self.state();
+        state.next_lane += 1;
+        state.next_lane
+    }
+
+    fn push(&self, lane: u64, round: Round) -> Result<(), Error> {
+        {
+            let mut state = self.state();
+            if state.closed {
+                return Err(anyhow!("Service is shutting down"));
+            }
+            match state.lanes.iter_mut().find(|(id, _)| *id == lane) {
+                Some((_, rounds)) => rounds.push_back(round),
+                None => state.lanes.push_back((lane, VecDeque::from([round]))),
+            }
+        }
+        self.available.notify_one();
+
+        Ok(())
+    }
+
+    /// Rounds of the cancelled request are not computed
+    fn cancel(&self, lane: u64) {
+        self.state().lanes.retain(|(id, _)| *id != lane);
+    }
+
+    fn close(&self) {
+        self.state().closed = true;
+        self.available.notify_waiters();
+    }
+
+    /// Up to `max` rounds taken from the lanes in turn, none once the queue is closed
+    async fn take(&self, max: usize) -> Vec<Round> {
+        loop {
+            let available = self.available.notified();
+            {
+                let mut state = self.state();
+                if state.closed {
+                    return vec![];
+                }
+
+                let mut rounds = Vec::with_capacity(max);
+                while rounds.len() < max {
+                    let Some((lane, mut queued)) = state.lanes.pop_front() else {
+                        break;
+                    };
+                    if let Some(round) = queued.pop_front() {
+                        // Waiting caller could have gone, e.g. the client cancelled the call
+                        if !round.result.is_closed() {
+                            rounds.push(round);
+                        }
+                    }
+                    if !queued.is_empty() {
+                        state.lanes.push_back((lane, queued));
+                    }
+                }
+                if !rounds.is_empty() {
+                    // Other workers pick the rest
+                    if !state.lanes.is_empty() {
+                        self.available.notify_one();
+                    }
+                    return rounds;
+                }
+            }
+            available.await;
+        }
+    }
+}
+
+async fn work<P: FingerprintProtocol<Fr> + Send + Sync>(
+    queue: Arc<WorkQueue>,
+    protocol: Arc<P>,
+    round_size: usize,
+) {
+    loop {
+        let rounds = queue.take(round_size).await;
+        if rounds.is_empty() {
+            return;
+        }
+
+        // Rounds via the same agents are evaluated by one `process_batch` call
+        let mut batches: Vec<(Option<Vec<usize>>, Vec<Round>)> = vec![];
+        for round in rounds {
+            match batches
+                .iter_mut()
+                .find(|(agents, _)| *agents == round.agents)
+            {
+                Some((_, batch)) => batch.push(round),
+                None => batches.push((round.agents.clone(), vec![round])),
+            }
+        }
+
+        futures::future::join_all(batches.into_iter().map(|(agents, batch)| {
+            let protocol = protocol.as_ref();
+            async move {
+                let (unblinded, results): (Vec<_>, Vec<_>) = batch
+                    .into_iter()
+                    .map(|round| (round.unblinded, round.result))
+                    .unzip();
+                let evaluated = match &agents {
+                    Some(agents) => {
+                        ViaAgents::new(protocol, agents)
+                            .process_batch(unblinded)
+                            .await
+                    }
+                    None => protocol.process_batch(unblinded).await,
+                };
+                for (result, evaluated) in results.into_iter().zip(evaluated) {
+                    // The caller could have gone meanwhile
+                    let _ = result.send(evaluated);
+                }
+            }
+        }))
+        .await;
+    }
+}
+
+/// Lane of the request, its queued rounds are dropped with it
+pub(crate) struct RequestLane {
+    id: u64,
+    queue: Arc<WorkQueue>,
+}
+
+impl RequestLane {
+    async fn evaluate(&self, unblinded: Fr, agents: Option<&[usize]>) -> Result<Fr, Error> {
+        let (result, computed) = oneshot::channel();
+        self.queue.push(
+            self.id,
+            Round {
+                unblinded,
+                agents: agents.map(<[usize]>::to_vec),
+                result,
+            },
+        )?;
+
+        computed
+            .await
+            .map_err(|_| anyhow!("Service is shutting down"))?
+    }
+}
+
+impl Drop for RequestLane {
+    fn drop(&mut self) {
+        self.queue.cancel(self.id);
+    }
+}
+
+impl FingerprintProtocol<Fr> for RequestLane {
+    async fn process(&self, unblinded: Fr) -> Result<Fr, Error> {
+        self.evaluate(unblinded, None).await
+    }
+
+    async fn process_via(&self, unblinded: Fr, agents: &[usize]) -> Result<Fr, Error> {
+        self.evaluate(unblinded, Some(agents)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fingerprinting_core::NaiveProtocol;
+    use std::time::Duration;
+
+    /// Protocol recording the order the rounds are computed in and the sizes of the batched rounds
+    struct Recording {
+        computed: Mutex<Vec<u64>>,
+        batches: Mutex<Vec<usize>>,
+        protocol: NaiveProtocol,
+    }
+
+    impl FingerprintProtocol<Fr> for Recording {
+        async fn process(&self, unblinded: Fr) -> Result<Fr, Error> {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            let round = unblinded.to_bytes()[0] as u64;
+            self.computed.lock().unwrap().push(round);
+            self.protocol.process(unblinded).await
+        }
+
+        async fn process_batch(&self, unblinded: Vec<Fr>) -> Vec<Result<Fr, Error>> {
+            self.batches.lock().unwrap().push(unblinded.len());
+            futures::future::join_all(unblinded.into_iter().map(|u| self.process(u))).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fair_scheduling() -> Result<(), Error> {
+        let protocol = Arc::new(Recording {
+            computed: Default::default(),
+            batches: Default::default(),
+            protocol: NaiveProtocol::new(Fr::from(42)),
+        });
+        let limits =
+            SchedulerLimits::new(NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        assert_eq!(limits.lane_depth(), 2);
+        let scheduler = ComputationScheduler::new(protocol.clone(), limits);
+
+        // Batch of 10 rounds queued before the single call
+        let batch = scheduler.request();
+        let single = scheduler.request();
+        let (batch_rounds, single_round) = tokio::join!(
+            futures::future::join_all((1..=10u64).map(|i| batch.process(Fr::from(i)))),
+            async {
+                tokio::task::yield_now().await;
+                single.process(Fr::from(100)).await
+            }
+        );
+        assert_eq!(
+            single_round?,
+            NaiveProtocol::new(Fr::from(42))
+                .process(Fr::from(100))
+                .await?
+        );
+        assert_eq!(
+            batch_rounds
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()?
+                .len(),
+            10
+        );
+
+        // Single call is dispatched in the first rounds rather than after the batch
+        let computed = protocol.computed.lock().unwrap().clone();
+        let position = computed.iter().position(|round| *round == 100).unwrap();
+        assert!(
+            position < 4,
+            "single call computed as {} of {:?}",
+            position,
+            computed
+        );
+
+        // Rounds taken together are evaluated by one batched protocol round
+        let batches = protocol.batches.lock().unwrap().clone();
+        assert_eq!(batches.iter().sum::<usize>(), 11);
+        assert!(batches.iter().all(|size| *size <= 2));
+        assert!(batches.contains(&2), "batched rounds {:?}", batches);
+
+        // Rounds of the dropped request are not computed
+        let queue = WorkQueue::default();
+        let lane = queue.open();
+        let (result, _computed) = oneshot::channel();
+        queue.push(
+            lane,
+            Round {
+                unblinded: Fr::from(200),
+                agents: None,
+                result,
+            },
+        )?;
+        queue.cancel(lane);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), queue.take(1))
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+}
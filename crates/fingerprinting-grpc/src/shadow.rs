@@ -0,0 +1,173 @@
+//! Shadow computation of the fingerprints with the candidate configuration, validating migrations on live traffic
+//!
+//! Every fingerprint returned to the caller is computed once more in the background with the candidate protocol
+//! (new secret), namespace and schema, the caller never waits for the candidate and never sees it.
+//! Divergences are counted and logged: migration keeping the fingerprints (upgrade, resharing) should never diverge,
+//! migration to the new secret or namespace should diverge always and never fail.
+
+use anyhow::Error;
+use fingerprinting_core::clock::Epoch;
+use fingerprinting_core::namespace::Namespace;
+use fingerprinting_core::schema::FingerprintSchema;
+use fingerprinting_core::wire::WireVersion;
+use fingerprinting_core::{
+    Compact, Fingerprint, FingerprintProtocol, ProtocolFingerprint, TransactionFingerprintData,
+};
+use fingerprinting_types::RawTransaction;
+use futures::future::BoxFuture;
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+type CandidateProtocol = Box<
+    dyn Fn(TransactionFingerprintData<Fr>) -> BoxFuture<'static, Result<Fr, Error>> + Send + Sync,
+>;
+
+/// Counters of the shadow computations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShadowStats {
+    pub compared: u64,
+    /// Candidate fingerprint differs from the current one
+    pub diverged: u64,
+    /// Candidate fingerprint could not be computed
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct ShadowCounters {
+    compared: AtomicU64,
+    diverged: AtomicU64,
+    failed: AtomicU64,
+}
+
+pub struct ShadowFingerprinting {
+    protocol: CandidateProtocol,
+    namespace: Option<Namespace>,
+    schema: Option<Arc<FingerprintSchema>>,
+    wire_version: WireVersion,
+    epoch: Epoch,
+    counters: Arc<ShadowCounters>,
+    /// Divergences are the errors rather than the expected outcome of the migration
+    alerting: bool,
+}
+
+impl ShadowFingerprinting {
+    /// Candidate computing fingerprints via the `protocol` without the namespace
+    pub fn new<P: FingerprintProtocol<Fr> + Send + Sync + 'static>(protocol: P) -> Self {
+        let protocol = Arc::new(protocol);
+
+        Self {
+            protocol: Box::new(move |tx| {
+                let protocol = protocol.clone();
+                Box::pin(async move {
+                    tx.complete_fingerprint(protocol.as_ref())
+                        .await
+                        .map(Fingerprint::into_inner)
+                })
+            }),
+            namespace: None,
+            schema: None,
+            wire_version: WireVersion::CURRENT,
+            epoch: Epoch::default(),
+            counters: Arc::default(),
+            alerting: false,
+        }
+    }
+
+    /// Namespace of the candidate, it is not inherited from the current configuration
+    pub fn with_namespace(mut self, namespace: Option<Namespace>) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Fingerprint schema of the candidate, it is not inherited from the current configuration either
+    pub fn with_schema(mut self, schema: Option<Arc<FingerprintSchema>>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Wire version of the candidate, the current one unless the migration to another version is shadowed
+    pub fn with_wire_version(mut self, version: WireVersion) -> Self {
+        self.wire_version = version;
+        self
+    }
+
+    /// Epoch of the candidate, the one of the current configuration unless the migration to another epoch is shadowed
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    /// Logs the divergences and the failures as the errors, see `FingerprintSampling`
+    pub(crate) fn alerting(mut self) -> Self {
+        self.alerting = true;
+        self
+    }
+
+    pub fn stats(&self) -> ShadowStats {
+        ShadowStats {
+            compared: self.counters.compared.load(Ordering::Acquire),
+            diverged: self.counters.diverged.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Computes the candidate fingerprint of the transaction in the background and compares it with the `current` one
+    pub(crate) fn compare(&self, raw_tx: RawTransaction, salt: pilota::Bytes, current: Fr) {
+        let candidate = self.candidate(raw_tx, salt);
+        let counters = self.counters.clone();
+        let (diverged_level, failed_level) = match self.alerting {
+            true => (log::Level::Error, log::Level::Error),
+            false => (log::Level::Debug, log::Level::Warn),
+        };
+
+        tokio::spawn(async move {
+            match candidate.await {
+                Ok(candidate) if candidate == current => {}
+                Ok(candidate) => {
+                    counters.diverged.fetch_add(1, Ordering::Relaxed);
+                    log::log!(
+                        diverged_level,
+                        "Shadow fingerprint {} diverged from {}",
+                        candidate.compact(),
+                        current.compact()
+                    );
+                }
+                Err(e) => {
+                    counters.failed.fetch_add(1, Ordering::Relaxed);
+                    log::log!(
+                        failed_level,
+                        "Shadow fingerprint of {} failed: {}",
+                        current.compact(),
+                        e
+                    );
+                }
+            }
+            // Counted last, so the divergences are up to date with the compared fingerprints
+            counters.compared.fetch_add(1, Ordering::Release);
+        });
+    }
+
+    fn candidate(
+        &self,
+        raw_tx: RawTransaction,
+        salt: pilota::Bytes,
+    ) -> BoxFuture<'static, Result<Fr, Error>> {
+        let tx = TransactionFingerprintData::try_from(raw_tx).and_then(|tx| {
+            let tx = tx
+                .with_namespace(self.namespace.clone())
+                .with_schema(self.schema.clone())
+                .with_wire_version(self.wire_version)
+                .with_epoch(self.epoch);
+            match salt.is_empty() {
+                true => Ok(tx),
+                false => tx.with_salt(salt),
+            }
+        });
+
+        match tx {
+            Ok(tx) => (self.protocol)(tx),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
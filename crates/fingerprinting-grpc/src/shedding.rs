@@ -0,0 +1,167 @@
+//! Load shedding of the fingerprint computations when the service is saturated
+//!
+//! Computations run in the pool of the limited number of slots, the ones above it wait in the queue. Once the queue
+//! depth reaches the limit the lowest priority work (batch items and series installments) is failed fast with
+//! `UNAVAILABLE` and the `retry-after` hint, rather than letting the latency grow until every call times out.
+//! Interactive calls keep the headroom of the same depth on top of it and are shed only past the double limit.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use volo_grpc::{Code, Status};
+
+/// Metadata key of the seconds the shed call should be retried after
+pub const RETRY_AFTER: &str = "retry-after";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Single fingerprint calls, someone is waiting for them
+    Interactive,
+    /// Batch items and series installments, shed first
+    Batch,
+}
+
+pub struct LoadShedding {
+    slots: Arc<Semaphore>,
+    queued: AtomicUsize,
+    max_queue: usize,
+    retry_after: Duration,
+    shed: AtomicU64,
+}
+
+impl LoadShedding {
+    /// Runs up to `concurrency` computations at once, the batch work is shed when `max_queue` ones are waiting
+    pub fn new(concurrency: NonZeroUsize, max_queue: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(concurrency.get())),
+            queued: AtomicUsize::new(0),
+            max_queue,
+            retry_after: Duration::from_secs(1),
+            shed: AtomicU64::new(0),
+        }
+    }
+
+    /// Retry hint of the shed calls, 1 second by default
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Number of the computations shed since the start
+    pub fn shed(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+
+    /// Slot of the computation, released when the permit is dropped
+    pub(crate) async fn admit(
+        &self,
+        priority: Priority,
+    ) -> Result<OwnedSemaphorePermit, Box<Status>> {
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let limit = match priority {
+            Priority::Interactive => self.max_queue.saturating_mul(2),
+            Priority::Batch => self.max_queue,
+        };
+        let queued = Queued::enter(&self.queued);
+        if queued.depth > limit {
+            self.shed.fetch_add(1, Ordering::Relaxed);
+            log::debug!(
+                "{:?} computation is shed, {} are queued",
+                priority,
+                queued.depth - 1
+            );
+            return Err(Box::new(self.unavailable()));
+        }
+
+        self.slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Box::new(Status::new(Code::Unavailable, "Service is shutting down")))
+    }
+
+    fn unavailable(&self) -> Status {
+        let mut status = Status::new(
+            Code::Unavailable,
+            "Service is saturated, retry the computation later",
+        );
+        status
+            .metadata_mut()
+            .insert(RETRY_AFTER, self.retry_after.as_secs().max(1).into());
+
+        status
+    }
+}
+
+/// Slot of the computation when the shedding is configured
+pub(crate) async fn admit(
+    shedding: Option<&LoadShedding>,
+    priority: Priority,
+) -> Result<Option<OwnedSemaphorePermit>, Box<Status>> {
+    match shedding {
+        Some(shedding) => shedding.admit(priority).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Place in the queue, left when the waiting call is either admitted or cancelled
+struct Queued<'a> {
+    queued: &'a AtomicUsize,
+    /// Queue depth including this call
+    depth: usize,
+}
+
+impl<'a> Queued<'a> {
+    fn enter(queued: &'a AtomicUsize) -> Self {
+        let depth = queued.fetch_add(1, Ordering::Relaxed) + 1;
+        Self { queued, depth }
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_shedding() -> Result<(), anyhow::Error> {
+        let shedding = Arc::new(LoadShedding::new(NonZeroUsize::new(1).unwrap(), 1));
+        let running = shedding.admit(Priority::Interactive).await?;
+
+        let waiting = tokio::spawn({
+            let shedding = shedding.clone();
+            async move { shedding.admit(Priority::Batch).await.map(drop) }
+        });
+        tokio::task::yield_now().await;
+
+        // Queue is full for the batch work, not for the interactive calls
+        let shed = shedding.admit(Priority::Batch).await.err().unwrap();
+        assert_eq!(shed.code(), Code::Unavailable);
+        assert_eq!(shed.metadata().get(RETRY_AFTER).unwrap(), "1");
+        assert_eq!(shedding.shed(), 1);
+
+        let interactive = shedding.admit(Priority::Interactive);
+        assert!(tokio::time::timeout(Duration::from_millis(50), interactive)
+            .await
+            .is_err());
+        // Cancelled call leaves the queue
+        assert_eq!(shedding.queued.load(Ordering::Relaxed), 1);
+
+        drop(running);
+        waiting.await??;
+        assert_eq!(shedding.queued.load(Ordering::Relaxed), 0);
+        drop(shedding.admit(Priority::Batch).await?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+// How many of the updates could be buffered for slow subscribers
+const STATUS_UPDATES_CAPACITY: usize = 1024;
+
+// How long the subscriber waits for the fingerprint to be anchored
+pub const DEFAULT_STATUS_SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Lifecycle status of the computed fingerprint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintStatus {
+    Computed,
+    Stored,
+    DuplicateFound,
+    Anchored,
+}
+
+impl FingerprintStatus {
+    /// No more updates are expected after the final status
+    pub fn is_final(&self) -> bool {
+        matches!(self, FingerprintStatus::Anchored)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FingerprintStatusUpdate {
+    pub fingerprint: Fr,
+    pub status: FingerprintStatus,
+
+    /// Merkle root of the archived epoch the fingerprint is anchored in, see `fingerprinting_store::merkle::archive_root`
+    pub epoch_root: Option<[u8; 32]>,
+}
+
+/// Hub of the fingerprint status updates
+/// Storage and anchoring subsystems publish updates, while subscribers receive updates only
+/// for fingerprints they are interested in
+pub struct FingerprintStatusHub {
+    sender: broadcast::Sender<FingerprintStatusUpdate>,
+    subscription_timeout: Duration,
+}
+
+impl FingerprintStatusHub {
+    pub fn new(subscription_timeout: Duration) -> Self {
+        let (sender, _) = broadcast::channel(STATUS_UPDATES_CAPACITY);
+
+        Self {
+            sender,
+            subscription_timeout,
+        }
+    }
+
+    pub fn publish(&self, update: FingerprintStatusUpdate) {
+        // Error means there are no subscribers at the moment, nothing to do
+        let _ = self.sender.send(update);
+    }
+
+    /// Fingerprints of the epoch archived with the `epoch_root` are anchored
+    pub fn publish_anchored(
+        &self,
+        epoch_root: [u8; 32],
+        fingerprints: impl IntoIterator<Item = Fr>,
+    ) {
+        for fingerprint in fingerprints {
+            self.publish(FingerprintStatusUpdate {
+                fingerprint,
+                status: FingerprintStatus::Anchored,
+                epoch_root: Some(epoch_root),
+            });
+        }
+    }
+
+    pub fn subscription_timeout(&self) -> Duration {
+        self.subscription_timeout
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<FingerprintStatusUpdate> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for FingerprintStatusHub {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATUS_SUBSCRIPTION_TIMEOUT)
+    }
+}
@@ -0,0 +1,141 @@
+//! Members of the cooperative topology as reported by `GetServiceInfo`, with their metadata and the partial
+//! evaluations counted by the protocol, in total and by the institutions
+
+use crate::net::outbe::fingerprint::v1::{
+    EvaluationStats as EvaluationStatsDto, InstitutionStats, TopologyMember,
+};
+use fingerprinting_core::{EvaluationStats, Members, PhaseMetrics};
+use pilota::FastStr;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// Metadata of the members and the metrics of the protocol evaluating with them
+pub struct TopologyStatus {
+    members: Members,
+    metrics: Arc<PhaseMetrics>,
+}
+
+impl TopologyStatus {
+    pub fn new(members: Members, metrics: Arc<PhaseMetrics>) -> Self {
+        Self { members, metrics }
+    }
+
+    /// Metrics of the protocol, sampled into the capacity log as well
+    pub fn metrics(&self) -> Arc<PhaseMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Configured members and the agents asked for the evaluations, in the order of the agent numbers
+    pub(crate) fn members(&self) -> Vec<TopologyMember> {
+        let evaluations = self
+            .metrics
+            .evaluation_stats()
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let agents = self
+            .members
+            .iter()
+            .map(|(agent, _)| agent)
+            .chain(evaluations.keys().copied())
+            .collect::<BTreeSet<_>>();
+
+        agents
+            .into_iter()
+            .map(|agent| {
+                let member = self.members.get(agent).cloned().unwrap_or_default();
+                let text = |value: Option<String>| FastStr::from(value.unwrap_or_default());
+
+                TopologyMember {
+                    agent_id: agent as u64,
+                    operator: text(member.operator),
+                    institution: text(member.institution),
+                    region: text(member.region),
+                    verifying_key: text(member.verifying_key),
+                    evaluations: Some(evaluation_stats(
+                        evaluations.get(&agent).copied().unwrap_or_default(),
+                    )),
+                    _unknown_fields: Default::default(),
+                }
+            })
+            .collect()
+    }
+
+    /// Evaluations of the members summed by their institutions
+    pub(crate) fn institutions(&self) -> Vec<InstitutionStats> {
+        let evaluations = self
+            .metrics
+            .evaluation_stats()
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        let mut institutions = BTreeMap::<&str, (u32, EvaluationStats)>::new();
+        for (agent, member) in self.members.iter() {
+            let Some(institution) = &member.institution else {
+                continue;
+            };
+            let (agents, total) = institutions.entry(institution).or_default();
+            let stats = evaluations.get(&agent).copied().unwrap_or_default();
+            *agents += 1;
+            total.evaluated += stats.evaluated;
+            total.failed += stats.failed;
+            total.total_time += stats.total_time;
+        }
+
+        institutions
+            .into_iter()
+            .map(|(institution, (agents, stats))| InstitutionStats {
+                institution: FastStr::new(institution),
+                agents,
+                evaluations: Some(evaluation_stats(stats)),
+                _unknown_fields: Default::default(),
+            })
+            .collect()
+    }
+}
+
+fn evaluation_stats(stats: EvaluationStats) -> EvaluationStatsDto {
+    EvaluationStatsDto {
+        evaluated: stats.evaluated,
+        failed: stats.failed,
+        _unknown_fields: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fingerprinting_core::Member;
+
+    #[test]
+    fn test_topology_status() {
+        let member = |institution: &str, region: &str| Member {
+            operator: Some("BankCorp".into()),
+            institution: Some(institution.into()),
+            region: Some(region.into()),
+            verifying_key: None,
+        };
+        let status = TopologyStatus::new(
+            Members::default()
+                .with_member(3, member("bank-corp", "eu-west"))
+                .with_member(2, member("bank-corp", "us-east"))
+                .with_member(4, Member::default()),
+            Arc::default(),
+        );
+
+        let members = status.members();
+        assert_eq!(
+            members
+                .iter()
+                .map(|member| member.agent_id)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(members[1].region, "eu-west");
+        assert_eq!(members[2].institution, "");
+
+        let institutions = status.institutions();
+        assert_eq!(institutions.len(), 1);
+        assert_eq!(institutions[0].institution, "bank-corp");
+        assert_eq!(institutions[0].agents, 2);
+    }
+}
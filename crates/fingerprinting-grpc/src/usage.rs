@@ -0,0 +1,227 @@
+//! Usage accounting of the institutions sharing the coordinator by their API keys
+//!
+//! Callers present the key in the `x-api-key` metadata, the calls without a known key are refused with
+//! `UNAUTHENTICATED`. The fingerprints returned and the agent rounds consumed by every call are added to the month of
+//! the key in the `UsageLedger`. Keys with the monthly quota are refused with `RESOURCE_EXHAUSTED` once the call would
+//! take them above it. The quota is checked before the computation and recorded after it, so the concurrent calls of
+//! the same key may overshoot it by their size.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use fingerprinting_store::usage::{usage_month, Usage, UsageLedger};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use volo_grpc::metadata::MetadataMap;
+use volo_grpc::{Code, Status};
+
+/// Metadata key of the API key of the caller
+pub const API_KEY: &str = "x-api-key";
+
+/// API key the usage is accounted by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    /// Name the usage is recorded and reported by
+    pub name: String,
+
+    /// Fingerprints the key may compute within the calendar month, unlimited when absent
+    pub monthly_quota: Option<u64>,
+
+    /// Gets the usage of all the keys from `GetUsage`
+    pub admin: bool,
+}
+
+pub struct UsageAccounting {
+    keys: HashMap<String, ApiKey>,
+    ledger: Arc<dyn UsageLedger>,
+}
+
+impl UsageAccounting {
+    pub fn new(ledger: Arc<dyn UsageLedger>) -> Self {
+        Self {
+            keys: HashMap::new(),
+            ledger,
+        }
+    }
+
+    /// Accepts the calls presenting the `key`
+    pub fn with_key(mut self, key: impl Into<String>, api_key: ApiKey) -> Self {
+        self.keys.insert(key.into(), api_key);
+        self
+    }
+
+    /// Key of the call, refused when unknown or when the `fingerprints` would take it above the quota
+    pub(crate) async fn admit(
+        &self,
+        metadata: &MetadataMap,
+        fingerprints: u64,
+        now: DateTime<Utc>,
+    ) -> Result<ApiKey, Box<Status>> {
+        let key = metadata
+            .get(API_KEY)
+            .and_then(|key| key.to_str().ok())
+            .and_then(|key| self.keys.get(key))
+            .ok_or(Status::new(
+                Code::Unauthenticated,
+                format!("Metadata {} should carry the known API key", API_KEY),
+            ))?;
+
+        if let Some(quota) = key.monthly_quota.filter(|_| fingerprints > 0) {
+            let used = self.usage(&key.name, usage_month(now)).await?;
+            if used.fingerprints.saturating_add(fingerprints) > quota {
+                return Err(Box::new(Status::new(
+                    Code::ResourceExhausted,
+                    format!(
+                        "Monthly quota of {} fingerprints of the key {} is exhausted, {} are used",
+                        quota, key.name, used.fingerprints
+                    ),
+                )));
+            }
+        }
+
+        Ok(key.clone())
+    }
+
+    /// Adds the usage of the completed call, the failure is logged since the call has been served already
+    pub(crate) async fn record(&self, key: &ApiKey, usage: Usage, now: DateTime<Utc>) {
+        if usage == Usage::default() {
+            return;
+        }
+        if let Err(e) = self.ledger.record(&key.name, usage_month(now), usage).await {
+            log::warn!("Failed to record the usage of the key {}: {}", key.name, e);
+        }
+    }
+
+    pub(crate) async fn usage(&self, name: &str, month: NaiveDate) -> Result<Usage, Box<Status>> {
+        self.ledger.usage(name, month).await.map_err(|e| {
+            Box::new(Status::new(
+                Code::Unavailable,
+                format!("Failed to read usage: {}", e),
+            ))
+        })
+    }
+
+    /// Usage of all the keys used within the month
+    pub(crate) async fn report(
+        &self,
+        month: NaiveDate,
+    ) -> Result<Vec<(String, Usage)>, Box<Status>> {
+        self.ledger.report(month).await.map_err(|e| {
+            Box::new(Status::new(
+                Code::Unavailable,
+                format!("Failed to read usage: {}", e),
+            ))
+        })
+    }
+
+    /// Quota of the key by its name
+    pub(crate) fn monthly_quota(&self, name: &str) -> Option<u64> {
+        self.keys
+            .values()
+            .find(|key| key.name == name)
+            .and_then(|key| key.monthly_quota)
+    }
+}
+
+/// Key of the call when the accounting is configured
+pub(crate) async fn admit(
+    accounting: Option<&UsageAccounting>,
+    metadata: &MetadataMap,
+    fingerprints: u64,
+    now: DateTime<Utc>,
+) -> Result<Option<ApiKey>, Box<Status>> {
+    match accounting {
+        Some(accounting) => accounting
+            .admit(metadata, fingerprints, now)
+            .await
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Usage of the single call, shared by its items
+#[derive(Debug, Default)]
+pub(crate) struct CallUsage {
+    fingerprints: AtomicU64,
+    agent_rounds: AtomicU64,
+}
+
+impl CallUsage {
+    pub(crate) fn record_round(&self, completed: bool) {
+        self.agent_rounds.fetch_add(1, Ordering::Relaxed);
+        if completed {
+            self.fingerprints.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn usage(&self) -> Usage {
+        Usage {
+            fingerprints: self.fingerprints.load(Ordering::Relaxed),
+            agent_rounds: self.agent_rounds.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Records the usage of the call when it is accounted
+pub(crate) async fn record(
+    accounting: Option<&UsageAccounting>,
+    key: Option<&ApiKey>,
+    call: &CallUsage,
+    now: DateTime<Utc>,
+) {
+    if let (Some(accounting), Some(key)) = (accounting, key) {
+        accounting.record(key, call.usage(), now).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use fingerprinting_store::usage::MemoryUsageLedger;
+
+    #[tokio::test]
+    async fn test_usage_quota() -> Result<(), Box<Status>> {
+        let accounting = UsageAccounting::new(Arc::new(MemoryUsageLedger::new())).with_key(
+            "secret-a",
+            ApiKey {
+                name: "bank-a".to_string(),
+                monthly_quota: Some(3),
+                admin: false,
+            },
+        );
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let mut metadata = MetadataMap::new();
+
+        let refused = accounting.admit(&metadata, 1, now).await.unwrap_err();
+        assert_eq!(refused.code(), Code::Unauthenticated);
+        metadata.insert(API_KEY, "secret-b".parse().unwrap());
+        assert!(accounting.admit(&metadata, 1, now).await.is_err());
+
+        metadata.insert(API_KEY, "secret-a".parse().unwrap());
+        let key = accounting.admit(&metadata, 3, now).await?;
+        let call = CallUsage::default();
+        call.record_round(true);
+        call.record_round(true);
+        call.record_round(false);
+        accounting.record(&key, call.usage(), now).await;
+
+        // Failed round is not the fingerprint, the quota keeps one more
+        assert!(accounting.admit(&metadata, 1, now).await.is_ok());
+        let exhausted = accounting.admit(&metadata, 2, now).await.unwrap_err();
+        assert_eq!(exhausted.code(), Code::ResourceExhausted);
+        // Non computing calls are not refused by the quota, nor is the next month
+        assert!(accounting.admit(&metadata, 0, now).await.is_ok());
+        let next_month = Utc.with_ymd_and_hms(2025, 10, 1, 0, 0, 0).unwrap();
+        assert!(accounting.admit(&metadata, 3, next_month).await.is_ok());
+
+        assert_eq!(
+            accounting.usage("bank-a", usage_month(now)).await?,
+            Usage {
+                fingerprints: 2,
+                agent_rounds: 3,
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+//! Read-only verifier of the fingerprints for the partners, who must verify but never compute fingerprints
+//!
+//! The service holds no secret: it checks the selective disclosure proofs against the public commitment key of
+//! the fingerprint service and the Merkle proofs of the archived epochs, looks up fingerprints in the (replicated)
+//! store and describes the parameters of the computation.
+
+use crate::net::outbe::fingerprint::v1::component_commitment::Component;
+use crate::net::outbe::fingerprint::v1::{
+    ComponentCommitments, FingerprintLookup, GetServiceInfoRequest, GetServiceInfoResponse,
+    LookupFingerprintsRequest, LookupFingerprintsResponse, RevealedComponent,
+    VerifyFingerprintProofRequest, VerifyFingerprintProofResponse, VerifyInclusionProofRequest,
+    VerifyInclusionProofResponse,
+};
+use crate::{service_info, KEY_EPOCH};
+use anyhow::{anyhow, Error};
+use ed25519_dalek::{Signature, VerifyingKey};
+use fingerprinting_core::clock::Epoch;
+use fingerprinting_core::commitment::{
+    CommittedComponent, ComponentCommitment, ComponentValue, FingerprintCommitments,
+};
+use fingerprinting_core::namespace::Namespace;
+use fingerprinting_core::schema::FingerprintSchema;
+use fingerprinting_core::wire::WireVersion;
+use fingerprinting_core::{numeric_currency_code, wire};
+use fingerprinting_store::merkle::{InclusionProof, ProofStep};
+use fingerprinting_store::FingerprintStore;
+use fingerprinting_types::Money;
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::sync::Arc;
+use volo_grpc::{Code, Request, Response, Status};
+
+pub struct FingerprintVerifierService {
+    store: Option<Arc<dyn FingerprintStore>>,
+    commitment_key: Option<VerifyingKey>,
+    namespace: Option<Namespace>,
+    schema: Option<Arc<FingerprintSchema>>,
+    wire_version: WireVersion,
+    epoch: Epoch,
+}
+
+impl Default for FingerprintVerifierService {
+    fn default() -> Self {
+        Self {
+            store: None,
+            commitment_key: None,
+            namespace: None,
+            schema: None,
+            wire_version: WireVersion::CURRENT,
+            epoch: Epoch::default(),
+        }
+    }
+}
+
+impl FingerprintVerifierService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables lookups of the fingerprints, the store is only read
+    pub fn with_store(mut self, store: Option<Arc<dyn FingerprintStore>>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Verifying key of the fingerprint service the commitments are bound by,
+    /// disclosures are not verified without it
+    pub fn with_commitment_key(mut self, key: Option<VerifyingKey>) -> Self {
+        self.commitment_key = key;
+        self
+    }
+
+    /// Namespace of the environment the verified fingerprints are computed in
+    pub fn with_namespace(mut self, namespace: Option<Namespace>) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Fingerprint schema of the environment the verified fingerprints are computed by
+    pub fn with_schema(mut self, schema: Option<Arc<FingerprintSchema>>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Wire version of the environment the verified fingerprints are computed with
+    pub fn with_wire_version(mut self, version: WireVersion) -> Self {
+        self.wire_version = version;
+        self
+    }
+
+    /// Epoch of the environment the verified fingerprints are computed with
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.epoch = epoch;
+        self
+    }
+}
+
+impl crate::net::outbe::fingerprint::v1::FingerprintVerifierService for FingerprintVerifierService {
+    async fn verify_fingerprint_proof(
+        &self,
+        req: Request<VerifyFingerprintProofRequest>,
+    ) -> Result<Response<VerifyFingerprintProofResponse>, Status> {
+        let key = self.commitment_key.as_ref().ok_or(Status::new(
+            Code::FailedPrecondition,
+            "Commitment key of the fingerprint service is not configured",
+        ))?;
+        let request = req.into_inner();
+        let fingerprint =
+            decode_scalar(&request.fingerprint, "fingerprint").map_err(|status| *status)?;
+        let commitments = request.commitments.ok_or(Status::new(
+            Code::InvalidArgument,
+            "Commitments are missing",
+        ))?;
+
+        let reason = verify_disclosure(
+            key,
+            fingerprint,
+            commitments,
+            request.revealed,
+            self.wire_version,
+            self.epoch,
+        )
+        .map_err(|e| Status::new(Code::InvalidArgument, format!("Malformed proof: {}", e)))?;
+
+        Ok(Response::new(VerifyFingerprintProofResponse {
+            valid: reason.is_none(),
+            reason: reason.unwrap_or_default().into(),
+            _unknown_fields: Default::default(),
+        }))
+    }
+
+    async fn lookup_fingerprints(
+        &self,
+        req: Request<LookupFingerprintsRequest>,
+    ) -> Result<Response<LookupFingerprintsResponse>, Status> {
+        let store = self.store.as_ref().ok_or(Status::new(
+            Code::FailedPrecondition,
+            "Fingerprints store is not configured",
+        ))?;
+
+        let mut lookups = vec![];
+        for requested in req.into_inner().fingerprints {
+            let fingerprint = decode_scalar(&requested, "fingerprint").map_err(|status| *status)?;
+
+            let stored = store.lookup(fingerprint, KEY_EPOCH).await.map_err(|e| {
+                Status::new(
+                    Code::Unavailable,
+                    format!("Failed to look up fingerprint: {}", e),
+                )
+            })?;
+
+            lookups.push(FingerprintLookup {
+                fingerprint: requested,
+                found: stored.is_some(),
+                first_seen: stored.as_ref().map(|stored| stored.first_seen.into()),
+                last_seen: stored.as_ref().map(|stored| stored.last_seen.into()),
+                occurrences: stored.map(|stored| stored.occurrences).unwrap_or_default(),
+                _unknown_fields: Default::default(),
+            });
+        }
+
+        Ok(Response::new(LookupFingerprintsResponse {
+            lookups,
+            _unknown_fields: Default::default(),
+        }))
+    }
+
+    async fn verify_inclusion_proof(
+        &self,
+        req: Request<VerifyInclusionProofRequest>,
+    ) -> Result<Response<VerifyInclusionProofResponse>, Status> {
+        let request = req.into_inner();
+        let fingerprint =
+            decode_scalar(&request.fingerprint, "fingerprint").map_err(|status| *status)?;
+        let root = decode_hash(&request.root, "root").map_err(|status| *status)?;
+
+        let steps = request
+            .steps
+            .iter()
+            .map(|step| {
+                Ok(ProofStep {
+                    sibling: decode_hash(&step.sibling, "sibling")?,
+                    sibling_left: step.sibling_left,
+                })
+            })
+            .collect::<Result<Vec<_>, Box<Status>>>()
+            .map_err(|status| *status)?;
+
+        Ok(Response::new(VerifyInclusionProofResponse {
+            valid: InclusionProof { steps }.verify(&root, request.key_epoch, &fingerprint),
+            _unknown_fields: Default::default(),
+        }))
+    }
+
+    async fn get_service_info(
+        &self,
+        _req: Request<GetServiceInfoRequest>,
+    ) -> Result<Response<GetServiceInfoResponse>, Status> {
+        Ok(Response::new(service_info(
+            self.namespace.as_ref(),
+            self.schema.as_deref(),
+            self.wire_version,
+            self.epoch,
+            None,
+            None,
+            None,
+        )))
+    }
+}
+
+fn decode_scalar(bytes: &[u8], name: &str) -> Result<Fr, Box<Status>> {
+    wire::decode_scalar(bytes).map_err(|e| {
+        Box::new(Status::new(
+            Code::InvalidArgument,
+            format!("Invalid {}: {}", name, e),
+        ))
+    })
+}
+
+fn decode_hash(bytes: &[u8], name: &str) -> Result<[u8; 32], Box<Status>> {
+    bytes.try_into().map_err(|_| {
+        Box::new(Status::new(
+            Code::InvalidArgument,
+            format!("Invalid {}, it should be exactly 32 bytes long", name),
+        ))
+    })
+}
+
+/// Reason the disclosure is not valid, malformed proof is the error.
+/// Commitments without the wire version or the epoch are opened with the ones of the verifier
+fn verify_disclosure(
+    key: &VerifyingKey,
+    fingerprint: Fr,
+    commitments: ComponentCommitments,
+    revealed: Vec<RevealedComponent>,
+    wire_version: WireVersion,
+    epoch: Epoch,
+) -> Result<Option<String>, Error> {
+    let wire_version = match commitments.wire_version {
+        0 => wire_version,
+        version => u8::try_from(version)
+            .map_err(|_| anyhow!("Unknown wire version {}", version))?
+            .try_into()?,
+    };
+    let epoch = match commitments.epoch.is_empty() {
+        true => epoch,
+        false => commitments.epoch.parse()?,
+    };
+    let components = commitments
+        .components
+        .iter()
+        .map(|c| {
+            let opening = match c.opening.is_empty() {
+                true => None,
+                false => Some(wire::decode_scalar(&c.opening)?),
+            };
+
+            Ok((
+                committed_component(c.component)?,
+                wire::decode_scalar(&c.commitment)?,
+                opening,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let binding = Signature::from_slice(&commitments.binding)?;
+    let committed = components
+        .iter()
+        .map(|(component, commitment, _)| (*component, *commitment))
+        .collect::<Vec<_>>();
+    let message =
+        FingerprintCommitments::binding_message(fingerprint, wire_version, epoch, &committed);
+    if key.verify_strict(&message, &binding).is_err() {
+        return Ok(Some(
+            "Commitments are not bound to the fingerprint".to_string(),
+        ));
+    }
+
+    for revealed in revealed {
+        let value = revealed_value(revealed)?;
+        let component = value.component();
+
+        let Some((_, commitment, opening)) = components.iter().find(|(c, _, _)| *c == component)
+        else {
+            return Ok(Some(format!("Component {:?} is not committed", component)));
+        };
+        let opening = opening.ok_or(anyhow!("Opening of {:?} is missing", component))?;
+
+        let commitment = ComponentCommitment {
+            component,
+            commitment: *commitment,
+            opening,
+        };
+        if !commitment.verify(&value, wire_version, epoch)? {
+            return Ok(Some(format!(
+                "Revealed {:?} does not match the commitment",
+                component
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+fn committed_component(component: Component) -> Result<CommittedComponent, Error> {
+    match component {
+        Component::COMPONENT_BIC => Ok(CommittedComponent::Bic),
+        Component::COMPONENT_AMOUNT => Ok(CommittedComponent::Amount),
+        Component::COMPONENT_CURRENCY => Ok(CommittedComponent::Currency),
+        Component::COMPONENT_DATE_TIME => Ok(CommittedComponent::DateTime),
+        _ => Err(anyhow!("Unknown component {:?}", component)),
+    }
+}
+
+fn revealed_value(revealed: RevealedComponent) -> Result<ComponentValue, Error> {
+    let amount = || -> Result<Money, Error> {
+        revealed
+            .amount
+            .clone()
+            .ok_or(anyhow!("Revealed amount is missing"))?
+            .try_into()
+    };
+
+    match committed_component(revealed.component)? {
+        CommittedComponent::Bic => Ok(ComponentValue::Bic(revealed.bic.to_string())),
+        CommittedComponent::Amount => {
+            let amount = amount()?;
+            Ok(ComponentValue::Amount((
+                amount.amount_base,
+                amount.amount_atto,
+            )))
+        }
+        CommittedComponent::Currency => Ok(ComponentValue::Currency(numeric_currency_code(
+            &amount()?.currency,
+        )?)),
+        CommittedComponent::DateTime => {
+            let amount = amount()?;
+            let date_time = revealed
+                .date_time
+                .clone()
+                .ok_or(anyhow!("Revealed date time is missing"))?
+                .try_into()?;
+            let wwd = revealed
+                .wwd
+                .clone()
+                .ok_or(anyhow!("Revealed WWD is missing"))?
+                .try_into()?;
+
+            Ok(ComponentValue::DateTime(
+                date_time,
+                wwd,
+                (amount.amount_base, amount.amount_atto),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::outbe::fingerprint::v1::FingerprintVerifierService as _;
+    use ed25519_dalek::SigningKey;
+    use fingerprinting_core::entropy::OsEntropy;
+    use fingerprinting_core::{NaiveProtocol, ProtocolFingerprint, TransactionFingerprintData};
+    use fingerprinting_types::RawTransaction;
+
+    #[tokio::test]
+    pub async fn test_selective_disclosure() -> Result<(), anyhow::Error> {
+        let date_time = "2025-09-16T12:30:15Z".parse::<chrono::DateTime<chrono::Utc>>()?;
+        let tx: TransactionFingerprintData<Fr> = RawTransaction {
+            bic: "BCEELU21".to_string(),
+            amount: Money {
+                amount_base: 1000,
+                amount_atto: 0,
+                currency: "EUR".to_string(),
+            },
+            counter_amount: None,
+            series_id: None,
+            merchant_id: None,
+            country_code: None,
+            transaction_reference: None,
+            mcc: None,
+            counterparty: None,
+            date_time,
+            wwd: date_time.date_naive(),
+        }
+        .try_into()?;
+        let fingerprint = tx
+            .complete_fingerprint(&NaiveProtocol::new(Fr::from(42)))
+            .await?
+            .into_inner();
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let commitments: ComponentCommitments =
+            tx.commit_components(fingerprint, &key, &OsEntropy)?.into();
+
+        let verify = |bic: &str, fingerprint: &Fr| {
+            Request::new(VerifyFingerprintProofRequest {
+                fingerprint: pilota::Bytes::copy_from_slice(&wire::encode_scalar(fingerprint)),
+                commitments: Some(commitments.clone()),
+                revealed: vec![RevealedComponent {
+                    component: Component::COMPONENT_BIC,
+                    bic: bic.to_string().into(),
+                    amount: None,
+                    date_time: None,
+                    wwd: None,
+                    _unknown_fields: Default::default(),
+                }],
+                _unknown_fields: Default::default(),
+            })
+        };
+
+        // Disclosures are not verified without the key of the service
+        assert!(FingerprintVerifierService::new()
+            .verify_fingerprint_proof(verify("BCEELU21", &fingerprint))
+            .await
+            .is_err());
+
+        let verifier =
+            FingerprintVerifierService::new().with_commitment_key(Some(key.verifying_key()));
+        let valid = verifier
+            .verify_fingerprint_proof(verify("BCEELU21", &fingerprint))
+            .await?
+            .into_inner();
+        assert!(valid.valid);
+
+        let wrong_bic = verifier
+            .verify_fingerprint_proof(verify("DEUTDEFF", &fingerprint))
+            .await?
+            .into_inner();
+        assert!(!wrong_bic.valid);
+
+        let wrong_fingerprint = verifier
+            .verify_fingerprint_proof(verify("BCEELU21", &Fr::from(7)))
+            .await?
+            .into_inner();
+        assert!(!wrong_fingerprint.valid);
+
+        // Commitments bound by anyone but the service disclose nothing
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let forged =
+            FingerprintVerifierService::new().with_commitment_key(Some(other.verifying_key()));
+        let forged = forged
+            .verify_fingerprint_proof(verify("BCEELU21", &fingerprint))
+            .await?
+            .into_inner();
+        assert!(!forged.valid);
+
+        // Verifier holds no store unless configured
+        assert!(verifier
+            .lookup_fingerprints(Request::new(LookupFingerprintsRequest {
+                fingerprints: vec![],
+                _unknown_fields: Default::default(),
+            }))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+// Thin wrapper over the generated stubs, mirroring the DTO conversions of the fingerprinting service:
+// date-time and decimal amount conversions and the compact (base58) values of the fingerprints
+import { create } from "@bufbuild/protobuf";
+import { Currency } from "./gen/net/outbe/common/v1/currency_pb.js";
+import { type Date as DateDto, DateSchema } from "./gen/net/outbe/common/v1/date_pb.js";
+import { type Money, MoneySchema } from "./gen/net/outbe/common/v1/money_pb.js";
+import { type Timestamp, TimestampSchema } from "./gen/net/outbe/common/v1/timestamp_pb.js";
+
+export * from "./gen/net/outbe/common/v1/currency_pb.js";
+export * from "./gen/net/outbe/common/v1/date_pb.js";
+export * from "./gen/net/outbe/common/v1/money_pb.js";
+export * from "./gen/net/outbe/common/v1/timestamp_pb.js";
+export * from "./gen/net/outbe/fingerprint/v1/external_service_pb.js";
+export * from "./gen/net/outbe/fingerprint/v1/record_pb.js";
+export * from "./gen/net/outbe/fingerprint/v1/verifier_service_pb.js";
+
+// Amounts are transferred as the whole units and the atto (10^-18) units
+const ATTO_DIGITS = 18;
+
+const BASE58_ALPHABET = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/** Timestamp of the date, the service does not accept the dates before the Unix epoch */
+export function toTimestamp(date: globalThis.Date): Timestamp {
+  const millis = BigInt(date.getTime());
+  if (millis < 0n) {
+    throw new Error(`Date ${date.toISOString()} is before the Unix epoch`);
+  }
+
+  return create(TimestampSchema, {
+    seconds: millis / 1000n,
+    nanos: Number(millis % 1000n) * 1_000_000,
+  });
+}
+
+/** Date of the timestamp, truncated to milliseconds */
+export function fromTimestamp(timestamp: Timestamp): globalThis.Date {
+  return new globalThis.Date(Number(timestamp.seconds) * 1000 + Math.floor(timestamp.nanos / 1_000_000));
+}
+
+/** UTC calendar date, e.g. the working day of the transaction */
+export function toDate(date: globalThis.Date): DateDto {
+  return create(DateSchema, {
+    year: date.getUTCFullYear(),
+    month: date.getUTCMonth() + 1,
+    day: date.getUTCDate(),
+  });
+}
+
+/** Parses ISO 8601 calendar date "2025-09-16" */
+export function parseDate(value: string): DateDto {
+  const match = /^(\d{4})-(\d{2})-(\d{2})$/.exec(value);
+  if (!match) {
+    throw new Error(`Invalid date ${value}, should be YYYY-MM-DD`);
+  }
+
+  return create(DateSchema, { year: Number(match[1]), month: Number(match[2]), day: Number(match[3]) });
+}
+
+/** Money of the decimal amount ("1000.55") and the ISO 4217 alphabetic currency code, no rounding is applied */
+export function toMoney(amount: string, currency: string): Money {
+  const match = /^(\d+)(?:\.(\d{1,18}))?$/.exec(amount);
+  if (!match) {
+    throw new Error(`Invalid amount ${amount}, at most ${ATTO_DIGITS} fraction digits are supported`);
+  }
+  const code = /^[A-Z]{3}$/.test(currency) ? Currency[currency as keyof typeof Currency] : undefined;
+  if (code === undefined || code === Currency.UNSPECIFIED) {
+    throw new Error(`Unknown currency ${currency}`);
+  }
+
+  return create(MoneySchema, {
+    currency: code,
+    units: BigInt(match[1]),
+    atto: BigInt((match[2] ?? "").padEnd(ATTO_DIGITS, "0")),
+  });
+}
+
+/** Decimal amount without the trailing zeros and the alphabetic currency code of the money */
+export function fromMoney(money: Money): { amount: string; currency: string } {
+  const fraction = money.atto.toString().padStart(ATTO_DIGITS, "0").replace(/0+$/, "");
+
+  return {
+    amount: fraction ? `${money.units}.${fraction}` : `${money.units}`,
+    currency: Currency[money.currency],
+  };
+}
+
+/** Compact (base58) representation of the binary value, e.g. of the fingerprint */
+export function encodeCompact(bytes: Uint8Array): string {
+  let value = 0n;
+  for (const byte of bytes) {
+    value = (value << 8n) + BigInt(byte);
+  }
+
+  let encoded = "";
+  while (value > 0n) {
+    encoded = BASE58_ALPHABET[Number(value % 58n)] + encoded;
+    value /= 58n;
+  }
+  // Leading zero bytes are kept as the leading "1"
+  for (let i = 0; i < bytes.length && bytes[i] === 0; i++) {
+    encoded = BASE58_ALPHABET[0] + encoded;
+  }
+
+  return encoded;
+}
+
+export function decodeCompact(compacted: string): Uint8Array {
+  let value = 0n;
+  for (const char of compacted) {
+    const digit = BASE58_ALPHABET.indexOf(char);
+    if (digit < 0) {
+      throw new Error(`Invalid compact value ${compacted}`);
+    }
+    value = value * 58n + BigInt(digit);
+  }
+
+  const bytes: number[] = [];
+  while (value > 0n) {
+    bytes.unshift(Number(value & 0xffn));
+    value >>= 8n;
+  }
+  for (let i = 0; i < compacted.length && compacted[i] === BASE58_ALPHABET[0]; i++) {
+    bytes.unshift(0);
+  }
+
+  return Uint8Array.from(bytes);
+}
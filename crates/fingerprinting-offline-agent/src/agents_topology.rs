@@ -0,0 +1,255 @@
+use crate::{OfflineRequest, OfflineResponse, SigningKey, VerifyingKey};
+use anyhow::{anyhow, Error};
+use fingerprinting_core::clock::{self, Clock};
+use fingerprinting_core::AgentsTopology;
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Topology where some of the agents are offline, the rest are reached via the `online` topology
+///
+/// Requests to the offline agents are written as signed files to the `outbox` and the coordinator
+/// waits for the signed response files in the `inbox` within the extended deadline.
+/// Since the protocol takes the first threshold responses, offline agents are waited for only
+/// when the online agents are not enough.
+pub struct OfflineAgentsTopology<T> {
+    online: T,
+    offline: HashMap<usize, VerifyingKey>,
+    signing_key: SigningKey,
+    outbox: PathBuf,
+    inbox: PathBuf,
+    deadline: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl<T> OfflineAgentsTopology<T> {
+    pub fn new(
+        online: T,
+        signing_key: SigningKey,
+        outbox: impl Into<PathBuf>,
+        inbox: impl Into<PathBuf>,
+        deadline: Duration,
+    ) -> Self {
+        Self {
+            online,
+            offline: HashMap::new(),
+            signing_key,
+            outbox: outbox.into(),
+            inbox: inbox.into(),
+            deadline,
+            clock: clock::system_clock(),
+        }
+    }
+
+    /// Time the deadlines of the requests are set from, it should agree with the clocks of the offline agents
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Agent at `position` is offline, its responses are signed with `key`
+    pub fn with_offline_agent(mut self, position: usize, key: VerifyingKey) -> Self {
+        self.offline.insert(position, key);
+        self
+    }
+
+    async fn obtain_offline_shard(
+        &self,
+        agent: usize,
+        agent_key: &VerifyingKey,
+        generation: u64,
+        blinded_value: G1,
+    ) -> Result<(usize, G1), Error> {
+        let deadline = chrono::Duration::from_std(self.deadline)?;
+        let request = OfflineRequest::new(
+            agent,
+            generation,
+            blinded_value,
+            self.clock.now() + deadline,
+            &self.signing_key,
+        );
+
+        let request_file = RemoveOnDrop(self.outbox.join(request.file_name()));
+        tokio::fs::write(&request_file.0, serde_json::to_vec_pretty(&request)?).await?;
+        log::info!(
+            "== Waiting up to {:?} for offline agent {} to answer request {}",
+            self.deadline,
+            agent,
+            request.request_id
+        );
+
+        let response_file = RemoveOnDrop(self.inbox.join(format!(
+            "{}-{}{}",
+            agent,
+            request.request_id,
+            crate::RESPONSE_SUFFIX
+        )));
+        let exponent = tokio::time::timeout(self.deadline, async {
+            loop {
+                match tokio::fs::read(&response_file.0).await {
+                    Ok(content) => {
+                        let response: OfflineResponse = serde_json::from_slice(&content)?;
+                        return response.verify(&request, agent_key);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        tokio::time::sleep(POLL_INTERVAL).await
+                    }
+                    Err(e) => return Err(Error::from(e)),
+                }
+            }
+        })
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "Offline agent {} did not answer request {} in time",
+                agent,
+                request.request_id
+            )
+        })??;
+
+        Ok((agent, exponent))
+    }
+}
+
+/// Request and response files are not needed once the coordinator stops waiting,
+/// including the case the protocol completes with other agents and drops the request
+struct RemoveOnDrop(PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        remove(&self.0);
+    }
+}
+
+fn remove(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl<T: AgentsTopology<Fr, G1> + Sync> AgentsTopology<Fr, G1> for OfflineAgentsTopology<T> {
+    fn count(&self) -> usize {
+        self.online.count()
+    }
+
+    fn threshold(&self) -> usize {
+        self.online.threshold()
+    }
+
+    async fn obtain_shard(
+        &self,
+        agent: usize,
+        generation: u64,
+        blinded_value: G1,
+    ) -> Result<(usize, G1), Error> {
+        match self.offline.get(&agent) {
+            Some(agent_key) => {
+                self.obtain_offline_shard(agent, agent_key, generation, blinded_value)
+                    .await
+            }
+            None => {
+                self.online
+                    .obtain_shard(agent, generation, blinded_value)
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OfflineAgent;
+    use chrono::{TimeZone, Utc};
+    use fingerprinting_core::clock::FixedClock;
+    use halo2_axiom::arithmetic::Field;
+    use halo2_axiom::halo2curves::group::Group;
+    use rand_core::OsRng;
+
+    struct NoOnlineAgents;
+
+    impl AgentsTopology<Fr, G1> for NoOnlineAgents {
+        fn count(&self) -> usize {
+            3
+        }
+
+        fn threshold(&self) -> usize {
+            2
+        }
+
+        async fn obtain_shard(&self, agent: usize, _: u64, _: G1) -> Result<(usize, G1), Error> {
+            Err(anyhow!("Agent {} is not reachable", agent))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offline_shard() -> Result<(), Error> {
+        let directory = std::env::temp_dir().join(format!("offline-agent-{}", std::process::id()));
+        let (outbox, inbox) = (directory.join("outbox"), directory.join("inbox"));
+        std::fs::create_dir_all(&outbox)?;
+        std::fs::create_dir_all(&inbox)?;
+
+        let coordinator_key = SigningKey::generate(&mut OsRng);
+        let agent_key = SigningKey::generate(&mut OsRng);
+        let shard = Fr::random(OsRng);
+        let clock = Arc::new(FixedClock::new(
+            Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap(),
+        ));
+
+        let topology = OfflineAgentsTopology::new(
+            NoOnlineAgents,
+            coordinator_key.clone(),
+            &outbox,
+            &inbox,
+            Duration::from_secs(30),
+        )
+        .with_clock(clock.clone())
+        .with_offline_agent(3, agent_key.verifying_key());
+        assert!(topology
+            .obtain_shard(2, 0, G1::random(OsRng))
+            .await
+            .is_err());
+
+        // Requests are carried into the air-gapped room and responses are carried back
+        let agent = OfflineAgent::new(3, shard, agent_key, vec![coordinator_key.verifying_key()]);
+        let stranger = OfflineAgent::new(
+            3,
+            shard,
+            SigningKey::generate(&mut OsRng),
+            vec![SigningKey::generate(&mut OsRng).verifying_key()],
+        );
+        let room = async {
+            loop {
+                assert_eq!(stranger.process_directory(&outbox, &inbox, clock.now())?, 0);
+                if agent.process_directory(&outbox, &inbox, clock.now())? > 0 {
+                    return Ok::<_, Error>(());
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+
+        let blinded_value = G1::random(OsRng);
+        let (shard_result, room_result) =
+            tokio::join!(topology.obtain_shard(3, 0, blinded_value), room);
+        room_result?;
+        assert_eq!(shard_result?, (3, blinded_value * shard));
+
+        // Files are cleaned up by the coordinator
+        assert_eq!(std::fs::read_dir(&outbox)?.count(), 0);
+        assert_eq!(std::fs::read_dir(&inbox)?.count(), 0);
+
+        // Expired requests are rejected by the agent
+        let request = OfflineRequest::new(3, 0, blinded_value, clock.now(), &coordinator_key);
+        assert!(agent.evaluate(&request, clock.now()).is_err());
+
+        std::fs::remove_dir_all(&directory)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+//! Offline agents for air-gapped environments, e.g. HSM rooms
+//!
+//! The coordinator writes signed evaluation requests to files, which are carried into the air-gapped room.
+//! The offline agent evaluates them with its secret shard and writes signed response files, which are carried back.
+//! Both sides sign with Ed25519 keys, so the files could be transferred over untrusted media.
+
+mod agents_topology;
+
+pub use agents_topology::OfflineAgentsTopology;
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, Verifier};
+use fingerprinting_core::wire;
+use fingerprinting_core::Compact;
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
+
+const REQUEST_DOMAIN: &[u8] = b"outbe/fingerprint/offline-request/1";
+const RESPONSE_DOMAIN: &[u8] = b"outbe/fingerprint/offline-response/1";
+
+const REQUEST_SUFFIX: &str = ".request.json";
+const RESPONSE_SUFFIX: &str = ".response.json";
+
+/// Evaluation request signed by the coordinator, binary values are compacted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OfflineRequest {
+    pub request_id: String,
+    pub agent: usize,
+    pub generation: u64,
+    pub blinded_value: String,
+    /// Agent rejects the request after the deadline, since the coordinator does not wait for it anymore
+    pub deadline: DateTime<Utc>,
+    pub signature: String,
+}
+
+/// Evaluation response signed by the offline agent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OfflineResponse {
+    pub request_id: String,
+    pub agent: usize,
+    pub generation: u64,
+    pub blinded_exponent: String,
+    pub signature: String,
+}
+
+impl OfflineRequest {
+    pub fn new(
+        agent: usize,
+        generation: u64,
+        blinded_value: G1,
+        deadline: DateTime<Utc>,
+        signing_key: &SigningKey,
+    ) -> Self {
+        let mut request_id = [0u8; 16];
+        rand_core::RngCore::fill_bytes(&mut rand_core::OsRng, &mut request_id);
+
+        let mut request = Self {
+            request_id: Bytes::copy_from_slice(&request_id).compact(),
+            agent,
+            generation,
+            blinded_value: wire::encode_compact(&wire::encode_point(&blinded_value)),
+            deadline,
+            signature: String::new(),
+        };
+        request.signature = signature(signing_key, &request.payload());
+
+        request
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("{}-{}{}", self.agent, self.request_id, REQUEST_SUFFIX)
+    }
+
+    pub fn verify(&self, coordinators: &[VerifyingKey]) -> Result<(), Error> {
+        verify(coordinators, &self.payload(), &self.signature).map_err(|e| {
+            anyhow!(
+                "Request {} is not signed by coordinator: {}",
+                self.request_id,
+                e
+            )
+        })
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        [
+            REQUEST_DOMAIN,
+            self.request_id.as_bytes(),
+            &(self.agent as u64).to_be_bytes(),
+            &self.generation.to_be_bytes(),
+            self.blinded_value.as_bytes(),
+            &self.deadline.timestamp().to_be_bytes(),
+        ]
+        .join(&0u8)
+    }
+}
+
+impl OfflineResponse {
+    pub fn file_name(&self) -> String {
+        format!("{}-{}{}", self.agent, self.request_id, RESPONSE_SUFFIX)
+    }
+
+    /// Verifies the response is signed by the agent and answers the `request`
+    pub fn verify(&self, request: &OfflineRequest, agent_key: &VerifyingKey) -> Result<G1, Error> {
+        if self.request_id != request.request_id
+            || self.agent != request.agent
+            || self.generation != request.generation
+        {
+            return Err(anyhow!(
+                "Response does not match request {}",
+                request.request_id
+            ));
+        }
+        verify(
+            &[*agent_key],
+            &self.payload(&request.blinded_value),
+            &self.signature,
+        )
+        .map_err(|e| anyhow!("Response is not signed by agent {}: {}", self.agent, e))?;
+
+        decode_point(&self.blinded_exponent)
+    }
+
+    // Response is bound to the blinded value, so it can't be replayed for another request
+    fn payload(&self, blinded_value: &str) -> Vec<u8> {
+        [
+            RESPONSE_DOMAIN,
+            self.request_id.as_bytes(),
+            &(self.agent as u64).to_be_bytes(),
+            &self.generation.to_be_bytes(),
+            blinded_value.as_bytes(),
+            self.blinded_exponent.as_bytes(),
+        ]
+        .join(&0u8)
+    }
+}
+
+/// Agent evaluating request files in the air-gapped environment
+pub struct OfflineAgent {
+    agent: usize,
+    secret_shard: Fr,
+    signing_key: SigningKey,
+    coordinators: Vec<VerifyingKey>,
+}
+
+impl OfflineAgent {
+    pub fn new(
+        agent: usize,
+        secret_shard: Fr,
+        signing_key: SigningKey,
+        coordinators: Vec<VerifyingKey>,
+    ) -> Self {
+        Self {
+            agent,
+            secret_shard,
+            signing_key,
+            coordinators,
+        }
+    }
+
+    pub fn evaluate(
+        &self,
+        request: &OfflineRequest,
+        now: DateTime<Utc>,
+    ) -> Result<OfflineResponse, Error> {
+        request.verify(&self.coordinators)?;
+
+        if request.agent != self.agent {
+            return Err(anyhow!(
+                "Request {} is addressed to agent {}",
+                request.request_id,
+                request.agent
+            ));
+        }
+        if request.deadline <= now {
+            return Err(anyhow!(
+                "Request {} has expired at {}",
+                request.request_id,
+                request.deadline
+            ));
+        }
+        if request.generation != 0 {
+            return Err(anyhow!(
+                "Current implementation doesn't support secret generations"
+            ));
+        }
+
+        let exponent = decode_point(&request.blinded_value)? * self.secret_shard;
+
+        let mut response = OfflineResponse {
+            request_id: request.request_id.clone(),
+            agent: self.agent,
+            generation: request.generation,
+            blinded_exponent: wire::encode_compact(&wire::encode_point(&exponent)),
+            signature: String::new(),
+        };
+        response.signature =
+            signature(&self.signing_key, &response.payload(&request.blinded_value));
+
+        Ok(response)
+    }
+
+    /// Evaluates request files of the agent found in `requests`, writing the response files to `responses`
+    /// Invalid and expired requests are skipped, returns the number of written responses
+    pub fn process_directory(
+        &self,
+        requests: &Path,
+        responses: &Path,
+        now: DateTime<Utc>,
+    ) -> Result<usize, Error> {
+        let mut processed = 0;
+
+        for entry in std::fs::read_dir(requests)? {
+            let path = entry?.path();
+            let is_request = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(&format!("{}-", self.agent)) && name.ends_with(REQUEST_SUFFIX)
+                });
+            if !is_request {
+                continue;
+            }
+
+            let response = serde_json::from_slice::<OfflineRequest>(&std::fs::read(&path)?)
+                .map_err(Error::from)
+                .and_then(|request| self.evaluate(&request, now));
+            match response {
+                Ok(response) => {
+                    std::fs::write(
+                        responses.join(response.file_name()),
+                        serde_json::to_vec_pretty(&response)?,
+                    )?;
+                    processed += 1;
+                }
+                Err(e) => log::warn!("Skipped request {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(processed)
+    }
+}
+
+fn signature(signing_key: &SigningKey, payload: &[u8]) -> String {
+    Bytes::copy_from_slice(&signing_key.sign(payload).to_bytes()).compact()
+}
+
+fn verify(keys: &[VerifyingKey], payload: &[u8], signature: &str) -> Result<(), Error> {
+    let signature: Bytes = Compact::unwrap(signature)?;
+    let signature = Signature::from_slice(&signature)?;
+
+    if keys
+        .iter()
+        .any(|key| key.verify(payload, &signature).is_ok())
+    {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid signature"))
+    }
+}
+
+fn decode_point(compacted: &str) -> Result<G1, Error> {
+    wire::decode_point(&wire::decode_compact(compacted)?)
+}
+
+/// Ed25519 signing key from the compacted 32 bytes secret
+pub fn signing_key(compacted: &str) -> Result<SigningKey, Error> {
+    let bytes: Bytes = Compact::unwrap(compacted)?;
+    let secret = bytes
+        .as_ref()
+        .try_into()
+        .map_err(|_| anyhow!("Signing key should be exactly 32 bytes long"))?;
+
+    Ok(SigningKey::from_bytes(secret))
+}
+
+/// Ed25519 verifying key from the compacted 32 bytes public key
+pub fn verifying_key(compacted: &str) -> Result<VerifyingKey, Error> {
+    let bytes: Bytes = Compact::unwrap(compacted)?;
+    let public = bytes
+        .as_ref()
+        .try_into()
+        .map_err(|_| anyhow!("Verifying key should be exactly 32 bytes long"))?;
+
+    Ok(VerifyingKey::from_bytes(public)?)
+}
@@ -0,0 +1,272 @@
+use crate::codec::{CooperationCodec, CooperationRequest, CooperationResponse, PROTOCOL};
+use crate::compute_exponent;
+use anyhow::{anyhow, Error};
+use fingerprinting_core::wire;
+use fingerprinting_core::AgentsTopology;
+use futures::StreamExt;
+use halo2_axiom::halo2curves::bn256::{Fr, G1};
+use libp2p::identity::Keypair;
+use libp2p::multiaddr::Protocol;
+use libp2p::request_response::{self, OutboundRequestId, ProtocolSupport};
+use libp2p::swarm::SwarmEvent;
+use libp2p::{noise, tcp, yamux, Multiaddr, PeerId, Swarm};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Behaviour = request_response::Behaviour<CooperationCodec>;
+
+struct ObtainShard {
+    peer: PeerId,
+    request: CooperationRequest,
+    reply: oneshot::Sender<Result<CooperationResponse, Error>>,
+}
+
+/// Topology communicating with the agents over libp2p, for deployments where gRPC between institutions is blocked
+///
+/// Agents are addressed by their peer ids, which the Noise handshake authenticates,
+/// so the agent address should end with `/p2p/<peer id>`. The same node serves requests of the other members.
+pub struct P2pAgentsTopology {
+    count: usize,
+    threshold: usize,
+    members: HashMap<usize, PeerId>,
+    commands: mpsc::Sender<ObtainShard>,
+}
+
+impl P2pAgentsTopology {
+    /// Starts the node listening on `listen`, it should be called within the Tokio runtime
+    pub fn start(
+        count: usize,
+        threshold: usize,
+        keypair: Keypair,
+        listen: Multiaddr,
+        secret_shard: Fr,
+        members: Vec<(usize, Multiaddr)>,
+    ) -> Result<Self, Error> {
+        let mut swarm = libp2p::SwarmBuilder::with_existing_identity(keypair)
+            .with_tokio()
+            .with_tcp(
+                tcp::Config::default(),
+                noise::Config::new,
+                yamux::Config::default,
+            )?
+            .with_behaviour(|_| {
+                Behaviour::new(
+                    [(PROTOCOL, ProtocolSupport::Full)],
+                    request_response::Config::default().with_request_timeout(REQUEST_TIMEOUT),
+                )
+            })?
+            .with_swarm_config(|config| {
+                config.with_idle_connection_timeout(IDLE_CONNECTION_TIMEOUT)
+            })
+            .build();
+
+        let mut peers = HashMap::new();
+        for (position, address) in members {
+            let peer = match address.iter().last() {
+                Some(Protocol::P2p(peer)) => peer,
+                _ => {
+                    return Err(anyhow!(
+                        "Address {} of agent {} should end with /p2p/<peer id>",
+                        address,
+                        position
+                    ))
+                }
+            };
+            swarm.add_peer_address(peer, address);
+            peers.insert(position, peer);
+        }
+
+        swarm.listen_on(listen)?;
+        log::info!("== Started libp2p agent node {}", swarm.local_peer_id());
+
+        let (commands, receiver) = mpsc::channel(1024);
+        let allowed = peers.values().cloned().collect();
+        tokio::spawn(run(swarm, receiver, secret_shard, allowed));
+
+        Ok(Self {
+            count,
+            threshold,
+            members: peers,
+            commands,
+        })
+    }
+}
+
+// Drives the swarm: sends requests of the topology and serves requests of the members
+async fn run(
+    mut swarm: Swarm<Behaviour>,
+    mut commands: mpsc::Receiver<ObtainShard>,
+    secret_shard: Fr,
+    members: HashSet<PeerId>,
+) {
+    let mut pending: HashMap<
+        OutboundRequestId,
+        oneshot::Sender<Result<CooperationResponse, Error>>,
+    > = HashMap::new();
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(command) => {
+                    let request_id = swarm.behaviour_mut().send_request(&command.peer, command.request);
+                    pending.insert(request_id, command.reply);
+                }
+                // topology is dropped
+                None => break,
+            },
+            event = swarm.select_next_some() => match event {
+                SwarmEvent::Behaviour(request_response::Event::Message { peer, message }) => match message {
+                    request_response::Message::Request { request, channel, .. } => {
+                        let response = if members.contains(&peer) {
+                            compute_exponent(secret_shard, &request)
+                        } else {
+                            log::warn!("Rejected request of peer {}, it is not a member of the topology", peer);
+                            Err("Peer is not a member of the topology".to_string())
+                        };
+                        let _ = swarm.behaviour_mut().send_response(channel, response);
+                    }
+                    request_response::Message::Response { request_id, response } => {
+                        if let Some(reply) = pending.remove(&request_id) {
+                            let _ = reply.send(Ok(response));
+                        }
+                    }
+                },
+                SwarmEvent::Behaviour(request_response::Event::OutboundFailure { peer, request_id, error }) => {
+                    if let Some(reply) = pending.remove(&request_id) {
+                        let _ = reply.send(Err(anyhow!("Request to peer {} failed: {}", peer, error)));
+                    }
+                }
+                SwarmEvent::NewListenAddr { address, .. } => {
+                    log::info!("== libp2p agent node listens on {}", address);
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+impl AgentsTopology<Fr, G1> for P2pAgentsTopology {
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    async fn obtain_shard(
+        &self,
+        agent: usize,
+        generation: u64,
+        blinded_value: G1,
+    ) -> Result<(usize, G1), Error> {
+        let peer = *self
+            .members
+            .get(&agent)
+            .ok_or(anyhow!("No peer for agent {}", agent))?;
+
+        let request = CooperationRequest {
+            generation,
+            blinded_value: wire::encode_point(&blinded_value),
+        };
+
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .send(ObtainShard {
+                peer,
+                request,
+                reply,
+            })
+            .await
+            .map_err(|_| anyhow!("libp2p agent node is stopped"))?;
+
+        let exponent = response
+            .await
+            .map_err(|_| anyhow!("libp2p agent node is stopped"))??
+            .map_err(|e| anyhow!("Agent {} rejected the request: {}", agent, e))?;
+
+        let exponent_point = wire::decode_point(&exponent.blinded_exponent).map_err(|e| {
+            anyhow!(
+                "Invalid exponent point, agent {} returned wrong value: {}",
+                agent,
+                e
+            )
+        })?;
+
+        Ok((agent, exponent_point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_axiom::arithmetic::Field;
+    use halo2_axiom::halo2curves::group::Group;
+    use rand_core::OsRng;
+
+    fn free_address() -> Multiaddr {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_obtain_shard() -> Result<(), Error> {
+        let (key_1, key_2, outsider_key) = (
+            Keypair::generate_ed25519(),
+            Keypair::generate_ed25519(),
+            Keypair::generate_ed25519(),
+        );
+        let (address_1, address_2, outsider_address) =
+            (free_address(), free_address(), free_address());
+        let address = |address: &Multiaddr, key: &Keypair| {
+            address
+                .clone()
+                .with(Protocol::P2p(key.public().to_peer_id()))
+        };
+        let shard_2 = Fr::random(OsRng);
+
+        let agent_1 = P2pAgentsTopology::start(
+            2,
+            2,
+            key_1.clone(),
+            address_1.clone(),
+            Fr::random(OsRng),
+            vec![(2, address(&address_2, &key_2))],
+        )?;
+        let _agent_2 = P2pAgentsTopology::start(
+            2,
+            2,
+            key_2.clone(),
+            address_2.clone(),
+            shard_2,
+            vec![(1, address(&address_1, &key_1))],
+        )?;
+        let outsider = P2pAgentsTopology::start(
+            2,
+            2,
+            outsider_key,
+            outsider_address,
+            Fr::random(OsRng),
+            vec![(2, address(&address_2, &key_2))],
+        )?;
+
+        let blinded_value = G1::random(OsRng);
+        let (agent, exponent) = agent_1.obtain_shard(2, 0, blinded_value).await?;
+        assert_eq!(agent, 2);
+        assert_eq!(exponent, blinded_value * shard_2);
+
+        assert!(agent_1.obtain_shard(3, 0, blinded_value).await.is_err());
+        assert!(outsider.obtain_shard(2, 0, blinded_value).await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response;
+use libp2p::StreamProtocol;
+use std::io;
+
+pub const PROTOCOL: StreamProtocol = StreamProtocol::new("/outbe/fingerprint/cooperation/1");
+
+/// Generation (8 bytes) and compressed G1 point (32 bytes)
+const REQUEST_SIZE: usize = 40;
+const MAX_RESPONSE_SIZE: u64 = 1024;
+
+const RESPONSE_OK: u8 = 0;
+const RESPONSE_ERROR: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CooperationRequest {
+    pub generation: u64,
+    pub blinded_value: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CooperationExponent {
+    pub generation: u64,
+    pub blinded_exponent: [u8; 32],
+}
+
+/// Computed exponent or the reason the agent rejected the request
+pub type CooperationResponse = Result<CooperationExponent, String>;
+
+/// Fixed size binary encoding of the cooperation messages, the streams are Noise encrypted already
+#[derive(Debug, Clone, Default)]
+pub struct CooperationCodec;
+
+#[async_trait]
+impl request_response::Codec for CooperationCodec {
+    type Protocol = StreamProtocol;
+    type Request = CooperationRequest;
+    type Response = CooperationResponse;
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut buffer = [0u8; REQUEST_SIZE];
+        io.read_exact(&mut buffer).await?;
+
+        let (generation, blinded_value) = buffer.split_at(8);
+        Ok(CooperationRequest {
+            generation: u64::from_be_bytes(generation.try_into().unwrap()),
+            blinded_value: blinded_value.try_into().unwrap(),
+        })
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut buffer = vec![];
+        io.take(MAX_RESPONSE_SIZE).read_to_end(&mut buffer).await?;
+
+        match buffer.split_first() {
+            Some((&RESPONSE_OK, exponent)) if exponent.len() == REQUEST_SIZE => {
+                let (generation, blinded_exponent) = exponent.split_at(8);
+                Ok(Ok(CooperationExponent {
+                    generation: u64::from_be_bytes(generation.try_into().unwrap()),
+                    blinded_exponent: blinded_exponent.try_into().unwrap(),
+                }))
+            }
+            Some((&RESPONSE_ERROR, message)) => {
+                Ok(Err(String::from_utf8_lossy(message).to_string()))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid cooperation response",
+            )),
+        }
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        request: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        io.write_all(&request.generation.to_be_bytes()).await?;
+        io.write_all(&request.blinded_value).await?;
+        io.close().await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        response: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        match response {
+            Ok(exponent) => {
+                io.write_all(&[RESPONSE_OK]).await?;
+                io.write_all(&exponent.generation.to_be_bytes()).await?;
+                io.write_all(&exponent.blinded_exponent).await?;
+            }
+            Err(message) => {
+                let message = message.as_bytes();
+                io.write_all(&[RESPONSE_ERROR]).await?;
+                io.write_all(&message[..message.len().min(MAX_RESPONSE_SIZE as usize - 1)])
+                    .await?;
+            }
+        }
+        io.close().await
+    }
+}
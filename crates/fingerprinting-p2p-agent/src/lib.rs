@@ -0,0 +1,31 @@
+mod agents_topology;
+pub mod codec;
+
+pub use agents_topology::P2pAgentsTopology;
+pub use libp2p::identity::Keypair;
+pub use libp2p::{Multiaddr, PeerId};
+
+use anyhow::Error;
+use codec::{CooperationExponent, CooperationRequest, CooperationResponse};
+use fingerprinting_core::wire;
+use halo2_axiom::halo2curves::bn256::Fr;
+
+/// Ed25519 identity of the node from the 32 bytes secret key
+pub fn ed25519_keypair(secret: &[u8]) -> Result<Keypair, Error> {
+    Ok(Keypair::ed25519_from_bytes(secret.to_vec())?)
+}
+
+/// Computes the exponent of the blinded value with the agent secret shard, same as the gRPC cooperation service
+fn compute_exponent(secret_shard: Fr, request: &CooperationRequest) -> CooperationResponse {
+    if request.generation != 0 {
+        return Err("Current implementation doesn't support secret generations".to_string());
+    }
+
+    let b_point = wire::decode_point(&request.blinded_value)
+        .map_err(|e| format!("Invalid blinded value: {}", e))?;
+
+    Ok(CooperationExponent {
+        generation: request.generation,
+        blinded_exponent: wire::encode_point(&(b_point * secret_shard)),
+    })
+}
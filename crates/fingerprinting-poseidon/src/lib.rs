@@ -2,12 +2,16 @@ mod grain;
 mod matrix;
 mod permutation;
 mod poseidon;
+#[cfg(test)]
+mod reference;
+mod registry;
 mod spec;
 
 pub(crate) mod ff {
     // Simple re-export types for simplify imports
-    pub(crate) use halo2_axiom::halo2curves::group::ff::{FromUniformBytes, PrimeField};
+    pub(crate) use halo2curves_axiom::ff::{FromUniformBytes, PrimeField};
 }
 
 pub use crate::poseidon::Poseidon;
+pub use crate::registry::cached_spec;
 pub use crate::spec::{MDSMatrices, MDSMatrix, SparseMDSMatrix, Spec, State};
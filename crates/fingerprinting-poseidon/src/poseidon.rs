@@ -1,28 +1,30 @@
 use crate::ff::{FromUniformBytes, PrimeField};
-use crate::{Spec, State};
+use crate::{cached_spec, Spec, State};
+use std::sync::Arc;
 
 /// Poseidon hasher that maintains state and inputs and yields single element
 /// output when desired
 #[derive(Debug, Clone)]
 pub struct Poseidon<F: PrimeField, const T: usize, const RATE: usize> {
     state: State<F, T>,
-    spec: Spec<F, T, RATE>,
+    spec: Arc<Spec<F, T, RATE>>,
     absorbing: Vec<F>,
 }
 
 impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Poseidon<F, T, RATE> {
-    /// Constructs a clear state poseidon instance
+    /// Constructs a clear state poseidon instance with the cached spec, see `cached_spec`
     pub fn new(r_f: usize, r_p: usize) -> Self {
         Self {
-            spec: Spec::new(r_f, r_p),
+            spec: cached_spec(r_f, r_p),
             state: State::default(),
             absorbing: Vec::new(),
         }
     }
 
-    pub fn new_with_spec(spec: Spec<F, T, RATE>) -> Self {
+    /// Constructs a clear state poseidon instance with the given or the shared spec
+    pub fn new_with_spec(spec: impl Into<Arc<Spec<F, T, RATE>>>) -> Self {
         Self {
-            spec,
+            spec: spec.into(),
             state: State::default(),
             absorbing: Vec::new(),
         }
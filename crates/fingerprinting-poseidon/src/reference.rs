@@ -0,0 +1,177 @@
+//! Differential fuzzing of the optimized permutation and sponge against the slow reference implementation
+//!
+//! The reference follows the Poseidon paper literally: every round adds the unoptimized Grain constants,
+//! applies the sbox and multiplies by the original MDS matrix, without the sparse matrices trick.
+//! Set `POSEIDON_FUZZ_SEED` to reproduce the failed run and `POSEIDON_FUZZ_ITERATIONS` to fuzz longer.
+
+use crate::ff::{FromUniformBytes, PrimeField};
+use crate::grain::Grain;
+use crate::{Poseidon, Spec, State};
+use halo2curves_axiom::bn256::Fr;
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+struct ReferencePoseidon<F: PrimeField, const T: usize> {
+    r_f: usize,
+    r_p: usize,
+    constants: Vec<[F; T]>,
+    mds: [[F; T]; T],
+}
+
+impl<F: FromUniformBytes<64>, const T: usize> ReferencePoseidon<F, T> {
+    fn new<const RATE: usize>(r_f: usize, r_p: usize) -> Self {
+        let (constants, mds) = Grain::<F, T, RATE>::generate(r_f, r_p);
+
+        Self {
+            r_f,
+            r_p,
+            constants,
+            mds: mds.rows(),
+        }
+    }
+
+    fn sbox(x: F) -> F {
+        x * x * x * x * x
+    }
+
+    fn permute(&self, state: &mut [F; T]) {
+        for round in 0..self.r_f + self.r_p {
+            for (word, constant) in state.iter_mut().zip(self.constants[round].iter()) {
+                *word += constant;
+            }
+
+            let full_round = round < self.r_f / 2 || round >= self.r_f / 2 + self.r_p;
+            if full_round {
+                for word in state.iter_mut() {
+                    *word = Self::sbox(*word);
+                }
+            } else {
+                state[0] = Self::sbox(state[0]);
+            }
+
+            let mut mixed = [F::ZERO; T];
+            for (i, row) in self.mds.iter().enumerate() {
+                for (j, cell) in row.iter().enumerate() {
+                    mixed[i] += *cell * state[j];
+                }
+            }
+            *state = mixed;
+        }
+    }
+
+    // Sponge with the rate `T - 1`, the last chunk is padded with one
+    fn hash(&self, inputs: &[F]) -> F {
+        let mut state = [F::ZERO; T];
+        state[0] = F::from_u128(1 << 64);
+
+        let mut padded = inputs.to_vec();
+        padded.push(F::ONE);
+        for chunk in padded.chunks(T - 1) {
+            for (word, input) in state.iter_mut().skip(1).zip(chunk.iter()) {
+                *word += input;
+            }
+            self.permute(&mut state);
+        }
+
+        state[1]
+    }
+}
+
+struct Fuzzer {
+    rng: ChaCha20Rng,
+}
+
+impl Fuzzer {
+    fn new() -> Self {
+        let seed = std::env::var("POSEIDON_FUZZ_SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(|| rand_chacha::rand_core::OsRng.next_u64());
+        // Printed output is shown for the failed test only
+        println!("POSEIDON_FUZZ_SEED={}", seed);
+
+        Self {
+            rng: ChaCha20Rng::seed_from_u64(seed),
+        }
+    }
+
+    fn iterations() -> usize {
+        std::env::var("POSEIDON_FUZZ_ITERATIONS")
+            .ok()
+            .and_then(|iterations| iterations.parse().ok())
+            .unwrap_or(4)
+    }
+
+    fn below(&mut self, bound: u64) -> usize {
+        (self.rng.next_u64() % bound) as usize
+    }
+
+    fn element(&mut self) -> Fr {
+        let mut bytes = [0u8; 64];
+        self.rng.fill_bytes(&mut bytes);
+        Fr::from_uniform_bytes(&bytes)
+    }
+
+    fn check_random_spec<const T: usize, const RATE: usize>(&mut self) {
+        // Full rounds are split into the halves, at least one full round is needed in each of them
+        let r_f = 2 * (1 + self.below(4));
+        let r_p = 1 + self.below(64);
+
+        self.check::<T, RATE>(r_f, r_p);
+    }
+
+    fn check<const T: usize, const RATE: usize>(&mut self, r_f: usize, r_p: usize) {
+        let spec = Spec::<Fr, T, RATE>::new(r_f, r_p);
+        let reference = ReferencePoseidon::<Fr, T>::new::<RATE>(r_f, r_p);
+
+        for _ in 0..Self::iterations() {
+            let mut state: [Fr; T] = std::array::from_fn(|_| self.element());
+            let mut optimized = State(state);
+            spec.permute(&mut optimized);
+            reference.permute(&mut state);
+            assert_eq!(optimized.words(), state, "T={} r_f={} r_p={}", T, r_f, r_p);
+
+            // Inputs are absorbed by the random sized updates
+            let inputs = (0..self.below(4 * RATE as u64 + 2))
+                .map(|_| self.element())
+                .collect::<Vec<_>>();
+            let mut poseidon = Poseidon::new_with_spec(spec.clone());
+            let mut rest = inputs.as_slice();
+            while !rest.is_empty() {
+                let (update, remaining) = rest.split_at(1 + self.below(rest.len() as u64));
+                poseidon.update(update);
+                rest = remaining;
+            }
+            assert_eq!(
+                poseidon.squeeze(),
+                reference.hash(&inputs),
+                "T={} r_f={} r_p={} inputs={}",
+                T,
+                r_f,
+                r_p,
+                inputs.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_differential_random_specs() {
+    let mut fuzzer = Fuzzer::new();
+
+    for _ in 0..Fuzzer::iterations() {
+        fuzzer.check_random_spec::<2, 1>();
+        fuzzer.check_random_spec::<3, 2>();
+        fuzzer.check_random_spec::<4, 3>();
+        fuzzer.check_random_spec::<5, 4>();
+    }
+}
+
+#[test]
+fn test_differential_fingerprint_specs() {
+    let mut fuzzer = Fuzzer::new();
+
+    fuzzer.check::<2, 1>(8, 57);
+    fuzzer.check::<4, 3>(8, 57);
+    fuzzer.check::<5, 4>(8, 57);
+}
@@ -0,0 +1,54 @@
+use crate::ff::FromUniformBytes;
+use crate::Spec;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Shape of the spec: field, width, rate, full and partial rounds
+type SpecKey = (TypeId, usize, usize, usize, usize);
+
+static SPECS: LazyLock<Mutex<HashMap<SpecKey, Arc<dyn Any + Send + Sync>>>> =
+    LazyLock::new(Default::default);
+
+/// Spec of the requested shape, generated on the first request and shared by all the later ones,
+/// since the constants generation is slow. The lock is held while generating, so every shape is generated once
+pub fn cached_spec<F: FromUniformBytes<64>, const T: usize, const RATE: usize>(
+    r_f: usize,
+    r_p: usize,
+) -> Arc<Spec<F, T, RATE>> {
+    let key = (TypeId::of::<F>(), T, RATE, r_f, r_p);
+    let mut specs = SPECS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    specs
+        .entry(key)
+        .or_insert_with(|| Arc::new(Spec::<F, T, RATE>::new(r_f, r_p)))
+        .clone()
+        .downcast::<Spec<F, T, RATE>>()
+        .expect("Spec of the shape is keyed by its type")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Poseidon;
+    use halo2curves_axiom::bn256::Fr;
+
+    #[test]
+    fn test_cached_spec() {
+        let spec = cached_spec::<Fr, 3, 2>(8, 57);
+        assert!(Arc::ptr_eq(&spec, &cached_spec::<Fr, 3, 2>(8, 57)));
+        assert_eq!(
+            cached_spec::<Fr, 3, 2>(8, 56).constants().partial().len(),
+            56
+        );
+
+        // Cached spec hashes the same as the generated one
+        let mut cached = Poseidon::new_with_spec(spec);
+        let mut generated = Poseidon::new_with_spec(Spec::<Fr, 3, 2>::new(8, 57));
+        cached.update(&[Fr::from(1), Fr::from(2), Fr::from(3)]);
+        generated.update(&[Fr::from(1), Fr::from(2), Fr::from(3)]);
+        assert_eq!(cached.squeeze(), generated.squeeze());
+    }
+}
@@ -0,0 +1,13 @@
+-- Fingerprints are unique within the secret key epoch, since the same transaction
+-- produces different fingerprints with different secrets
+CREATE TABLE IF NOT EXISTS fingerprints (
+    fingerprint BYTEA NOT NULL CHECK (octet_length(fingerprint) = 32),
+    key_epoch BIGINT NOT NULL CHECK (key_epoch >= 0),
+    first_seen TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    occurrences BIGINT NOT NULL DEFAULT 1,
+
+    CONSTRAINT fingerprints_fingerprint_key_epoch_unique UNIQUE (fingerprint, key_epoch)
+);
+
+CREATE INDEX IF NOT EXISTS fingerprints_first_seen_idx ON fingerprints (first_seen);
@@ -0,0 +1,6 @@
+-- Start of the current duplicate window, i.e. time of the latest new occurrence of the fingerprint
+ALTER TABLE fingerprints ADD COLUMN window_start TIMESTAMPTZ;
+
+UPDATE fingerprints SET window_start = first_seen;
+
+ALTER TABLE fingerprints ALTER COLUMN window_start SET NOT NULL;
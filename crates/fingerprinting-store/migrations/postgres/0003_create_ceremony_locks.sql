@@ -0,0 +1,9 @@
+-- Leases of the administrative ceremonies, the single row per ceremony is kept
+-- after the release, so the fencing token keeps growing
+CREATE TABLE IF NOT EXISTS ceremony_locks (
+    name TEXT PRIMARY KEY,
+    holder TEXT NOT NULL,
+    acquired_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    fencing_token BIGINT NOT NULL
+);
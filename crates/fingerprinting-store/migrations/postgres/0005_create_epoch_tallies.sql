@@ -0,0 +1,8 @@
+-- New fingerprints of every epoch (UTC day) by the ISO 4217 numeric code of the currency
+CREATE TABLE IF NOT EXISTS epoch_tallies (
+    epoch DATE NOT NULL,
+    currency INTEGER NOT NULL,
+    fingerprints BIGINT NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (epoch, currency)
+);
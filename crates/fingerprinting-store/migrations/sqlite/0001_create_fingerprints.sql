@@ -0,0 +1,15 @@
+-- Fingerprints are unique within the secret key epoch, since the same transaction
+-- produces different fingerprints with different secrets.
+-- Times are microseconds since the Unix epoch, so they are compared and subtracted as integers
+CREATE TABLE IF NOT EXISTS fingerprints (
+    fingerprint BLOB NOT NULL CHECK (length(fingerprint) = 32),
+    key_epoch INTEGER NOT NULL CHECK (key_epoch >= 0),
+    first_seen INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL,
+    occurrences INTEGER NOT NULL DEFAULT 1,
+    window_start INTEGER NOT NULL,
+
+    PRIMARY KEY (fingerprint, key_epoch)
+);
+
+CREATE INDEX IF NOT EXISTS fingerprints_first_seen_idx ON fingerprints (first_seen);
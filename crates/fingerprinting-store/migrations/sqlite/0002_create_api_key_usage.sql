@@ -0,0 +1,9 @@
+-- Usage of the API keys per calendar month, keyed by the name of the key and the first day of the month
+CREATE TABLE IF NOT EXISTS api_key_usage (
+    name TEXT NOT NULL,
+    month TEXT NOT NULL,
+    fingerprints INTEGER NOT NULL DEFAULT 0,
+    agent_rounds INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (name, month)
+);
@@ -0,0 +1,770 @@
+//! Archival of completed epochs into Parquet objects on S3 compatible object storage
+//!
+//! Every epoch (UTC day of the `first_seen`) is written as `<prefix>/epoch=<date>/fingerprints.parquet`
+//! alongside `<prefix>/epoch=<date>/manifest.json`. The manifest commits to the archived fingerprints
+//! with the root hash, so archived epochs could be audited without the hot store.
+//! With the tally ledger the manifest commits to the statistics of the epoch as well, see
+//! `fingerprinting_verify::statistics`.
+
+pub use crate::merkle::archive_root;
+use crate::merkle::{self, EpochInclusion, EpochProofs};
+use crate::tally::TallyLedger;
+use crate::{DuplicateWindow, FingerprintStore, InsertOutcome, StoredFingerprint};
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use chrono::{DateTime, Days, NaiveDate, TimeZone, Utc};
+use fingerprinting_core::clock::{self, Clock};
+use fingerprinting_verify::statistics::EpochStatistics;
+use futures::future::{BoxFuture, FutureExt};
+use futures::TryStreamExt;
+use halo2_axiom::halo2curves::bn256::Fr;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::bloom_filter::Sbbf;
+use parquet::data_type::{ByteArray, FixedLenByteArray, FixedLenByteArrayType, Int64Type};
+use parquet::file::properties::{ReaderProperties, WriterProperties};
+use parquet::file::reader::FileReader;
+use parquet::file::serialized_reader::{ReadOptionsBuilder, SerializedFileReader};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::record::{Row, RowAccessor};
+use parquet::schema::parser::parse_message_type;
+use parquet::schema::types::ColumnPath;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Called with the root and the fingerprints of every archived epoch
+pub type ArchiveListener = Arc<dyn Fn([u8; 32], &[StoredFingerprint]) + Send + Sync>;
+
+const FINGERPRINTS_OBJECT: &str = "fingerprints.parquet";
+const MANIFEST_OBJECT: &str = "manifest.json";
+
+const ARCHIVE_SCHEMA: &str = "
+message fingerprints {
+    REQUIRED FIXED_LEN_BYTE_ARRAY (32) fingerprint;
+    REQUIRED INT64 key_epoch (INTEGER(64, false));
+    REQUIRED INT64 first_seen (TIMESTAMP(MICROS, true));
+    REQUIRED INT64 last_seen (TIMESTAMP(MICROS, true));
+    REQUIRED INT64 occurrences (INTEGER(64, false));
+    REQUIRED INT64 window_start (TIMESTAMP(MICROS, true));
+}
+";
+
+/// Description of the archived epoch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub epoch: NaiveDate,
+
+    /// Location of the Parquet object relative to the object store root
+    pub object: String,
+
+    /// Number of distinct fingerprints in the epoch
+    pub fingerprints: u64,
+
+    /// Total occurrences of the fingerprints at the time of archival
+    pub occurrences: u64,
+
+    /// Hex encoded SHA-256 Merkle root over the archived fingerprints, see [`archive_root`]
+    pub root: String,
+
+    pub archived_at: DateTime<Utc>,
+
+    /// Committed statistics of the epoch, missing when the archive runs without the tally ledger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<StatisticsManifest>,
+}
+
+/// Statistics of the epoch published along the root, see [`EpochStatistics`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatisticsManifest {
+    /// Fingerprints first seen within the epoch over all the currencies
+    pub fingerprints: u64,
+
+    /// Hex encoded Poseidon tallies by the ISO 4217 numeric code of the currency
+    pub tallies: BTreeMap<u16, String>,
+
+    /// Hex encoded Poseidon commitment to the total and the tallies
+    pub commitment: String,
+}
+
+impl From<&EpochStatistics> for StatisticsManifest {
+    fn from(statistics: &EpochStatistics) -> Self {
+        Self {
+            fingerprints: statistics.fingerprints(),
+            tallies: statistics
+                .tallies()
+                .into_iter()
+                .map(|(currency, tally)| (currency, to_hex(&tally.to_bytes())))
+                .collect(),
+            commitment: to_hex(&statistics.commitment().to_bytes()),
+        }
+    }
+}
+
+/// Archive of the completed epochs
+/// Manifests are kept in memory, Parquet objects are fetched on demand and only their bloom
+/// filters are cached, so negative membership checks do not hit the object storage
+pub struct FingerprintArchive {
+    objects: Arc<dyn ObjectStore>,
+    prefix: Path,
+    manifests: Mutex<BTreeMap<NaiveDate, ArchiveManifest>>,
+    bloom_filters: Mutex<HashMap<NaiveDate, Option<Sbbf>>>,
+    tallies: Option<Arc<dyn TallyLedger>>,
+    listener: Option<ArchiveListener>,
+    clock: Arc<dyn Clock>,
+}
+
+impl FingerprintArchive {
+    /// Archive in the S3 bucket, credentials and region are taken from the standard `AWS_*` environment.
+    /// Custom `endpoint` allows S3 compatible storages (MinIO, Ceph, etc.)
+    pub fn s3(bucket: &str, endpoint: Option<&str>, prefix: &str) -> Result<Self, Error> {
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+        if let Some(endpoint) = endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+
+        Ok(Self::new(Arc::new(builder.build()?), prefix))
+    }
+
+    pub fn new(objects: Arc<dyn ObjectStore>, prefix: &str) -> Self {
+        Self {
+            objects,
+            prefix: Path::from(prefix),
+            manifests: Mutex::new(BTreeMap::new()),
+            bloom_filters: Mutex::new(HashMap::new()),
+            tallies: None,
+            listener: None,
+            clock: clock::system_clock(),
+        }
+    }
+
+    /// Commits the manifests of the archived epochs to the statistics of the `tallies`
+    pub fn with_tallies(mut self, tallies: Option<Arc<dyn TallyLedger>>) -> Self {
+        self.tallies = tallies;
+        self
+    }
+
+    /// Time the manifests are stamped with as archived
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Notifies the `listener` once the epoch is archived, e.g. to publish its fingerprints as anchored
+    pub fn with_listener(mut self, listener: Option<ArchiveListener>) -> Self {
+        self.listener = listener;
+        self
+    }
+
+    /// Loads manifests of the previously archived epochs, returns the number of archived epochs
+    pub async fn load_manifests(&self) -> Result<usize, Error> {
+        let objects = self
+            .objects
+            .list(Some(&self.prefix))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut manifests = BTreeMap::new();
+        for object in objects {
+            if object.location.filename() != Some(MANIFEST_OBJECT) {
+                continue;
+            }
+
+            let content = self.objects.get(&object.location).await?.bytes().await?;
+            let manifest: ArchiveManifest = serde_json::from_slice(&content)?;
+            manifests.insert(manifest.epoch, manifest);
+        }
+
+        let loaded = manifests.len();
+        *self.manifests.lock().unwrap() = manifests;
+
+        Ok(loaded)
+    }
+
+    pub fn manifest(&self, epoch: NaiveDate) -> Option<ArchiveManifest> {
+        self.manifests.lock().unwrap().get(&epoch).cloned()
+    }
+
+    pub fn latest_epoch(&self) -> Option<NaiveDate> {
+        self.manifests.lock().unwrap().keys().next_back().copied()
+    }
+
+    fn epoch_prefix(&self, epoch: NaiveDate) -> Path {
+        self.prefix.child(format!("epoch={}", epoch))
+    }
+
+    /// Writes fingerprints first seen within the `epoch` to the object storage
+    /// Existing archive of the epoch is overwritten
+    pub async fn archive_epoch(
+        &self,
+        store: &dyn FingerprintStore,
+        epoch: NaiveDate,
+    ) -> Result<ArchiveManifest, Error> {
+        let (from, to) = epoch_bounds(epoch)?;
+        let mut fingerprints = store.first_seen_between(from, to).await?;
+        fingerprints.sort_by_key(|stored| (stored.key_epoch, stored.fingerprint.to_bytes()));
+
+        let object = self.epoch_prefix(epoch).child(FINGERPRINTS_OBJECT);
+        self.objects
+            .put(&object, PutPayload::from(write_parquet(&fingerprints)?))
+            .await?;
+
+        let statistics = match &self.tallies {
+            Some(tallies) => Some(EpochStatistics {
+                currencies: tallies.tallies(epoch).await?,
+            }),
+            None => None,
+        };
+        if let Some(statistics) = statistics
+            .as_ref()
+            .filter(|statistics| statistics.fingerprints() != fingerprints.len() as u64)
+        {
+            log::warn!(
+                "Tallies of the epoch {} count {} fingerprints, {} are archived",
+                epoch,
+                statistics.fingerprints(),
+                fingerprints.len()
+            );
+        }
+
+        let root = archive_root(&fingerprints);
+        let manifest = ArchiveManifest {
+            epoch,
+            object: object.to_string(),
+            fingerprints: fingerprints.len() as u64,
+            occurrences: fingerprints.iter().map(|stored| stored.occurrences).sum(),
+            root: to_hex(&root),
+            archived_at: self.clock.now(),
+            statistics: statistics.as_ref().map(StatisticsManifest::from),
+        };
+
+        // Manifest goes last, so the epoch is never considered archived without the data
+        self.objects
+            .put(
+                &self.epoch_prefix(epoch).child(MANIFEST_OBJECT),
+                PutPayload::from(serde_json::to_vec_pretty(&manifest)?),
+            )
+            .await?;
+
+        log::info!(
+            "Archived epoch {} with {} fingerprints to {}",
+            epoch,
+            manifest.fingerprints,
+            manifest.object
+        );
+
+        self.bloom_filters.lock().unwrap().remove(&epoch);
+        self.manifests
+            .lock()
+            .unwrap()
+            .insert(epoch, manifest.clone());
+        if let Some(listener) = &self.listener {
+            listener(root, &fingerprints);
+        }
+
+        Ok(manifest)
+    }
+
+    /// Archives all the epochs completed before `now` and not archived yet,
+    /// then expires fingerprints older than `retention` from the hot store.
+    /// Only archived epochs are ever expired
+    pub async fn archive_completed(
+        &self,
+        store: &dyn FingerprintStore,
+        now: DateTime<Utc>,
+        retention: chrono::Duration,
+    ) -> Result<Vec<ArchiveManifest>, Error> {
+        let current_epoch = now.date_naive();
+
+        let first_epoch = match self.latest_epoch() {
+            Some(latest) => latest.succ_opt(),
+            None => store
+                .earliest_first_seen()
+                .await?
+                .map(|earliest| earliest.date_naive()),
+        };
+
+        let mut archived = vec![];
+        let mut epoch = first_epoch.unwrap_or(current_epoch);
+        while epoch < current_epoch {
+            archived.push(self.archive_epoch(store, epoch).await?);
+            epoch = epoch
+                .succ_opt()
+                .ok_or(anyhow!("Epoch {} is out of range", epoch))?;
+        }
+
+        let (current_epoch_start, _) = epoch_bounds(current_epoch)?;
+        let expired = store
+            .expire((now - retention).min(current_epoch_start))
+            .await?;
+        if expired > 0 {
+            log::info!("Expired {} archived fingerprints from the store", expired);
+        }
+
+        Ok(archived)
+    }
+
+    /// Looks up the fingerprint through all the archived epochs, the latest epoch wins
+    pub async fn lookup(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+    ) -> Result<Option<StoredFingerprint>, Error> {
+        let manifests = self
+            .manifests
+            .lock()
+            .unwrap()
+            .values()
+            .rev()
+            .filter(|manifest| manifest.fingerprints > 0)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let value = FixedLenByteArray::from(ByteArray::from(fingerprint.to_bytes().to_vec()));
+
+        for manifest in manifests {
+            let cached = self
+                .bloom_filters
+                .lock()
+                .unwrap()
+                .get(&manifest.epoch)
+                .map(|bloom_filter| bloom_filter.as_ref().is_none_or(|bf| bf.check(&value)));
+
+            if cached == Some(false) {
+                continue;
+            }
+
+            let content = self
+                .objects
+                .get(&Path::from(manifest.object.as_str()))
+                .await?
+                .bytes()
+                .await?;
+            let reader = read_parquet(content)?;
+
+            if cached.is_none() {
+                let bloom_filter = bloom_filter(&reader)?;
+                let maybe_present = bloom_filter.as_ref().is_none_or(|bf| bf.check(&value));
+
+                self.bloom_filters
+                    .lock()
+                    .unwrap()
+                    .insert(manifest.epoch, bloom_filter);
+
+                if !maybe_present {
+                    continue;
+                }
+            }
+
+            if let Some(stored) = find(&reader, fingerprint, key_epoch)? {
+                return Ok(Some(stored));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl EpochProofs for FingerprintArchive {
+    /// Proof over the archived fingerprints of the epoch, checked against the root of its manifest
+    fn inclusion(
+        &self,
+        epoch: NaiveDate,
+        fingerprint: Fr,
+        key_epoch: u64,
+    ) -> BoxFuture<'_, Result<Option<EpochInclusion>, Error>> {
+        async move {
+            let Some(manifest) = self.manifest(epoch) else {
+                return Ok(None);
+            };
+            let content = self
+                .objects
+                .get(&Path::from(manifest.object.as_str()))
+                .await?
+                .bytes()
+                .await?;
+            let fingerprints = read_all(&read_parquet(content)?)?;
+
+            let root = archive_root(&fingerprints);
+            if to_hex(&root) != manifest.root {
+                return Err(anyhow!(
+                    "Archive of the epoch {} does not match the root of its manifest",
+                    epoch
+                ));
+            }
+
+            Ok(Some(EpochInclusion {
+                root,
+                proof: merkle::inclusion_proof(&fingerprints, key_epoch, &fingerprint),
+                anchor: Some(self.epoch_prefix(epoch).child(MANIFEST_OBJECT).to_string()),
+            }))
+        }
+        .boxed()
+    }
+}
+
+/// Hot store backed by the archive: fingerprints expired from the hot store
+/// are still detected as duplicates
+pub struct ArchivedFingerprintStore {
+    hot: Arc<dyn FingerprintStore>,
+    archive: Arc<FingerprintArchive>,
+}
+
+impl ArchivedFingerprintStore {
+    pub fn new(hot: Arc<dyn FingerprintStore>, archive: Arc<FingerprintArchive>) -> Self {
+        Self { hot, archive }
+    }
+}
+
+impl FingerprintStore for ArchivedFingerprintStore {
+    fn insert(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+        seen_at: DateTime<Utc>,
+        window: DuplicateWindow,
+    ) -> BoxFuture<'_, Result<InsertOutcome, Error>> {
+        async move {
+            let outcome = self
+                .hot
+                .insert(fingerprint, key_epoch, seen_at, window)
+                .await?;
+
+            let InsertOutcome::Inserted(stored) = outcome else {
+                return Ok(outcome);
+            };
+
+            Ok(match self.archive.lookup(fingerprint, key_epoch).await? {
+                Some(archived) if window.contains(archived.window_start, seen_at) => {
+                    InsertOutcome::Duplicate(merge(archived, stored))
+                }
+                Some(archived) => InsertOutcome::Recurring(merge(archived, stored)),
+                None => InsertOutcome::Inserted(stored),
+            })
+        }
+        .boxed()
+    }
+
+    fn lookup(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+    ) -> BoxFuture<'_, Result<Option<StoredFingerprint>, Error>> {
+        async move {
+            let hot = self.hot.lookup(fingerprint, key_epoch).await?;
+            let archived = self.archive.lookup(fingerprint, key_epoch).await?;
+
+            Ok(match (archived, hot) {
+                (Some(archived), Some(hot)) => Some(merge(archived, hot)),
+                (archived, hot) => hot.or(archived),
+            })
+        }
+        .boxed()
+    }
+
+    fn first_seen_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Vec<StoredFingerprint>, Error>> {
+        self.hot.first_seen_between(from, to)
+    }
+
+    fn earliest_first_seen(&self) -> BoxFuture<'_, Result<Option<DateTime<Utc>>, Error>> {
+        self.hot.earliest_first_seen()
+    }
+
+    fn expire(&self, before: DateTime<Utc>) -> BoxFuture<'_, Result<u64, Error>> {
+        self.hot.expire(before)
+    }
+}
+
+// Fingerprint seen again after it has been expired from the hot store,
+// the hot store keeps its own window which starts at the repeated occurrence
+fn merge(archived: StoredFingerprint, hot: StoredFingerprint) -> StoredFingerprint {
+    StoredFingerprint {
+        first_seen: archived.first_seen.min(hot.first_seen),
+        last_seen: archived.last_seen.max(hot.last_seen),
+        occurrences: archived.occurrences + hot.occurrences,
+        ..hot
+    }
+}
+
+fn epoch_bounds(epoch: NaiveDate) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let from = Utc.from_utc_datetime(&epoch.and_time(Default::default()));
+    let to = from
+        .checked_add_days(Days::new(1))
+        .ok_or(anyhow!("Epoch {} is out of range", epoch))?;
+
+    Ok((from, to))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn write_parquet(fingerprints: &[StoredFingerprint]) -> Result<Vec<u8>, Error> {
+    let schema = Arc::new(parse_message_type(ARCHIVE_SCHEMA)?);
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .set_column_bloom_filter_enabled(ColumnPath::from("fingerprint"), true)
+        .set_column_bloom_filter_ndv(
+            ColumnPath::from("fingerprint"),
+            fingerprints.len().max(1) as u64,
+        )
+        .build();
+
+    let mut writer = SerializedFileWriter::new(Vec::new(), schema, Arc::new(properties))?;
+    let mut row_group = writer.next_row_group()?;
+
+    let values = fingerprints
+        .iter()
+        .map(|stored| {
+            FixedLenByteArray::from(ByteArray::from(stored.fingerprint.to_bytes().to_vec()))
+        })
+        .collect::<Vec<_>>();
+    let mut column = row_group
+        .next_column()?
+        .ok_or(anyhow!("Fingerprint column is missing"))?;
+    column
+        .typed::<FixedLenByteArrayType>()
+        .write_batch(&values, None, None)?;
+    column.close()?;
+
+    let int64_columns: [fn(&StoredFingerprint) -> i64; 5] = [
+        |stored| stored.key_epoch as i64,
+        |stored| stored.first_seen.timestamp_micros(),
+        |stored| stored.last_seen.timestamp_micros(),
+        |stored| stored.occurrences as i64,
+        |stored| stored.window_start.timestamp_micros(),
+    ];
+    for value in int64_columns {
+        let values = fingerprints.iter().map(value).collect::<Vec<_>>();
+        let mut column = row_group
+            .next_column()?
+            .ok_or(anyhow!("Archive column is missing"))?;
+        column
+            .typed::<Int64Type>()
+            .write_batch(&values, None, None)?;
+        column.close()?;
+    }
+
+    row_group.close()?;
+
+    Ok(writer.into_inner()?)
+}
+
+fn read_parquet(content: Bytes) -> Result<SerializedFileReader<Bytes>, Error> {
+    let options = ReadOptionsBuilder::new()
+        .with_reader_properties(
+            ReaderProperties::builder()
+                .set_read_bloom_filter(true)
+                .build(),
+        )
+        .build();
+
+    Ok(SerializedFileReader::new_with_options(content, options)?)
+}
+
+// Archive is written as the single row group, the fingerprint is the first column
+fn bloom_filter(reader: &SerializedFileReader<Bytes>) -> Result<Option<Sbbf>, Error> {
+    if reader.num_row_groups() == 0 {
+        return Ok(None);
+    }
+
+    Ok(reader.get_row_group(0)?.get_column_bloom_filter(0).cloned())
+}
+
+fn find(
+    reader: &SerializedFileReader<Bytes>,
+    fingerprint: Fr,
+    key_epoch: u64,
+) -> Result<Option<StoredFingerprint>, Error> {
+    let expected = fingerprint.to_bytes();
+
+    for row in reader.get_row_iter(None)? {
+        let row = row?;
+        if row.get_bytes(0)?.data() != expected || row.get_ulong(1)? != key_epoch {
+            continue;
+        }
+
+        return Ok(Some(stored(&row)?));
+    }
+
+    Ok(None)
+}
+
+fn read_all(reader: &SerializedFileReader<Bytes>) -> Result<Vec<StoredFingerprint>, Error> {
+    reader
+        .get_row_iter(None)?
+        .map(|row| stored(&row?))
+        .collect()
+}
+
+fn stored(row: &Row) -> Result<StoredFingerprint, Error> {
+    let timestamp = |micros: i64| {
+        DateTime::from_timestamp_micros(micros)
+            .ok_or(anyhow!("Archived timestamp {} is out of range", micros))
+    };
+
+    let fingerprint = row
+        .get_bytes(0)?
+        .data()
+        .first_chunk::<32>()
+        .and_then(|bytes| Fr::from_bytes(bytes).into_option())
+        .ok_or(anyhow!("Archived fingerprint does not represent Fr"))?;
+
+    Ok(StoredFingerprint {
+        fingerprint,
+        key_epoch: row.get_ulong(1)?,
+        first_seen: timestamp(row.get_timestamp_micros(2)?)?,
+        last_seen: timestamp(row.get_timestamp_micros(3)?)?,
+        occurrences: row.get_ulong(4)?,
+        window_start: timestamp(row.get_timestamp_micros(5)?)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tally::MemoryTallyLedger;
+    use crate::MemoryFingerprintStore;
+    use fingerprinting_core::clock::FixedClock;
+    use fingerprinting_verify::statistics;
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn test_archive_serves_expired_fingerprints() -> Result<(), Error> {
+        let day_1 = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let day_2 = Utc.with_ymd_and_hms(2025, 9, 17, 12, 0, 0).unwrap();
+        let day_3 = Utc.with_ymd_and_hms(2025, 9, 18, 12, 0, 0).unwrap();
+
+        let hot = Arc::new(MemoryFingerprintStore::new());
+        let tallies = Arc::new(MemoryTallyLedger::new());
+        let anchored = Arc::new(Mutex::new(vec![]));
+        let listened = anchored.clone();
+        let archive = Arc::new(
+            FingerprintArchive::new(Arc::new(InMemory::new()), "archive")
+                .with_tallies(Some(tallies.clone()))
+                .with_clock(Arc::new(FixedClock::new(day_3)))
+                .with_listener(Some(Arc::new(
+                    move |root: [u8; 32], fingerprints: &[StoredFingerprint]| {
+                        let fingerprints = fingerprints.iter().map(|stored| stored.fingerprint);
+                        listened
+                            .lock()
+                            .unwrap()
+                            .extend(fingerprints.map(|fingerprint| (to_hex(&root), fingerprint)));
+                    },
+                ))),
+        );
+        let store = ArchivedFingerprintStore::new(hot.clone(), archive.clone());
+
+        let window = DuplicateWindow::Within(chrono::Duration::days(1));
+        store.insert(Fr::from(42), 0, day_1, window).await?;
+        store.insert(Fr::from(42), 0, day_1, window).await?;
+        store.insert(Fr::from(43), 0, day_2, window).await?;
+        tallies.record(day_1.date_naive(), 978).await?;
+        tallies.record(day_2.date_naive(), 840).await?;
+
+        let archived = archive
+            .archive_completed(hot.as_ref(), day_3, chrono::Duration::zero())
+            .await?;
+        assert_eq!(archived.len(), 2);
+        assert_eq!(archived[0].fingerprints, 1);
+        assert_eq!(archived[0].occurrences, 2);
+        assert_eq!(archived[1].epoch, day_2.date_naive());
+        assert_eq!(archived[1].archived_at, day_3);
+        // Fingerprints are anchored in the roots of their epochs
+        assert_eq!(
+            *anchored.lock().unwrap(),
+            vec![
+                (archived[0].root.clone(), Fr::from(42)),
+                (archived[1].root.clone(), Fr::from(43))
+            ]
+        );
+
+        // Auditor checks the reported volume against the committed statistics
+        let committed = archived[0].statistics.clone().unwrap();
+        assert_eq!(committed.fingerprints, 1);
+        assert_eq!(
+            committed.tallies[&978],
+            to_hex(&statistics::tally(978, 1).to_bytes())
+        );
+        assert_eq!(
+            committed.commitment,
+            to_hex(&statistics::commitment(1, &[statistics::tally(978, 1)]).to_bytes())
+        );
+
+        // Hot store has expired everything before the current epoch
+        assert!(hot.lookup(Fr::from(42), 0).await?.is_none());
+
+        let stored = store.lookup(Fr::from(42), 0).await?.unwrap();
+        assert_eq!(stored.first_seen, day_1);
+        assert_eq!(stored.occurrences, 2);
+        assert!(store.lookup(Fr::from(44), 0).await?.is_none());
+        assert!(store.lookup(Fr::from(42), 1).await?.is_none());
+
+        let duplicate = store.insert(Fr::from(43), 0, day_3, window).await?;
+        assert!(duplicate.is_duplicate());
+        assert_eq!(duplicate.stored().first_seen, day_2);
+        assert_eq!(duplicate.stored().occurrences, 2);
+
+        // Archived occurrence is out of the window already
+        let recurring = store.insert(Fr::from(42), 0, day_3, window).await?;
+        assert!(matches!(recurring, InsertOutcome::Recurring(_)));
+        assert_eq!(recurring.stored().occurrences, 3);
+
+        assert!(!store
+            .insert(Fr::from(44), 0, day_3, window)
+            .await?
+            .is_duplicate());
+
+        // Proofs are served from the archived epochs only
+        let inclusion = archive
+            .inclusion(day_2.date_naive(), Fr::from(43), 0)
+            .await?
+            .unwrap();
+        assert_eq!(to_hex(&inclusion.root), archived[1].root);
+        assert!(inclusion
+            .proof
+            .unwrap()
+            .verify(&inclusion.root, 0, &Fr::from(43)));
+        assert_eq!(
+            inclusion.anchor.as_deref(),
+            Some("archive/epoch=2025-09-17/manifest.json")
+        );
+        let excluded = archive
+            .inclusion(day_2.date_naive(), Fr::from(42), 0)
+            .await?;
+        assert!(excluded.unwrap().proof.is_none());
+        assert!(archive
+            .inclusion(day_3.date_naive(), Fr::from(44), 0)
+            .await?
+            .is_none());
+
+        // Manifests survive the restart
+        let restored = FingerprintArchive::new(archive.objects.clone(), "archive");
+        assert_eq!(restored.load_manifests().await?, 2);
+        assert_eq!(
+            restored.manifest(day_1.date_naive()),
+            archive.manifest(day_1.date_naive())
+        );
+        assert_eq!(
+            restored.manifest(day_1.date_naive()).unwrap().root,
+            to_hex(&archive_root(&[StoredFingerprint {
+                fingerprint: Fr::from(42),
+                key_epoch: 0,
+                first_seen: day_1,
+                last_seen: day_1,
+                occurrences: 2,
+                window_start: day_1,
+            }]))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod lock;
+mod memory;
+#[cfg(feature = "merkle")]
+pub mod merkle;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub mod tally;
+pub mod usage;
+
+use anyhow::Error;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::{BoxFuture, FutureExt};
+use halo2_axiom::halo2curves::bn256::Fr;
+
+pub use memory::MemoryFingerprintStore;
+#[cfg(feature = "postgres")]
+pub use postgres::{
+    PostgresCeremonyLock, PostgresFingerprintStore, PostgresTallyLedger, PostgresUsageLedger,
+};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteFingerprintStore, SqliteTallyLedger, SqliteUsageLedger};
+
+/// Fingerprint persisted in the store
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredFingerprint {
+    pub fingerprint: Fr,
+
+    /// Epoch (generation) of the secret the fingerprint is computed with
+    pub key_epoch: u64,
+
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+
+    /// How many times the fingerprint has been stored
+    pub occurrences: u64,
+
+    /// Start of the current duplicate window, i.e. time of the latest new occurrence
+    pub window_start: DateTime<Utc>,
+}
+
+/// Rule deciding whether the repeated fingerprint is a duplicate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateWindow {
+    /// Any repeated fingerprint is a duplicate
+    #[default]
+    Unbounded,
+
+    /// Fingerprint repeated within the window is a duplicate,
+    /// after that it is a new occurrence which opens the next window
+    Within(Duration),
+}
+
+impl DuplicateWindow {
+    /// Whether the fingerprint seen at `seen_at` falls into the window opened at `window_start`
+    pub fn contains(&self, window_start: DateTime<Utc>, seen_at: DateTime<Utc>) -> bool {
+        match self {
+            DuplicateWindow::Unbounded => true,
+            DuplicateWindow::Within(window) => seen_at - window_start <= *window,
+        }
+    }
+}
+
+/// Result of storing the fingerprint
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsertOutcome {
+    /// Fingerprint is seen for the first time
+    Inserted(StoredFingerprint),
+
+    /// Fingerprint is already present in the store and seen within the duplicate window
+    Duplicate(StoredFingerprint),
+
+    /// Fingerprint is already present in the store, but the duplicate window has passed,
+    /// so it is counted as a new occurrence
+    Recurring(StoredFingerprint),
+}
+
+impl InsertOutcome {
+    pub fn stored(&self) -> &StoredFingerprint {
+        match self {
+            InsertOutcome::Inserted(stored)
+            | InsertOutcome::Duplicate(stored)
+            | InsertOutcome::Recurring(stored) => stored,
+        }
+    }
+
+    pub fn is_duplicate(&self) -> bool {
+        matches!(self, InsertOutcome::Duplicate(_))
+    }
+}
+
+/// Result of checking the fingerprint without storing it
+#[derive(Debug, Clone, PartialEq)]
+pub enum DuplicateCheck {
+    /// Fingerprint has never been seen
+    New,
+
+    /// Fingerprint would be a duplicate
+    Duplicate(StoredFingerprint),
+
+    /// Fingerprint has been seen, but outside of the duplicate window
+    Recurring(StoredFingerprint),
+}
+
+/// Persistent storage of computed fingerprints
+/// Fingerprints are unique within the key epoch
+pub trait FingerprintStore: Send + Sync {
+    /// Stores the fingerprint seen at `seen_at`, reports whether it is a duplicate within the `window`
+    fn insert(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+        seen_at: DateTime<Utc>,
+        window: DuplicateWindow,
+    ) -> BoxFuture<'_, Result<InsertOutcome, Error>>;
+
+    /// Looks up the fingerprint computed in the given key epoch
+    fn lookup(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+    ) -> BoxFuture<'_, Result<Option<StoredFingerprint>, Error>>;
+
+    /// Checks whether the fingerprint seen at `seen_at` would be a duplicate within the `window`
+    fn check(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+        seen_at: DateTime<Utc>,
+        window: DuplicateWindow,
+    ) -> BoxFuture<'_, Result<DuplicateCheck, Error>> {
+        async move {
+            Ok(match self.lookup(fingerprint, key_epoch).await? {
+                None => DuplicateCheck::New,
+                Some(stored) if window.contains(stored.window_start, seen_at) => {
+                    DuplicateCheck::Duplicate(stored)
+                }
+                Some(stored) => DuplicateCheck::Recurring(stored),
+            })
+        }
+        .boxed()
+    }
+
+    /// Lists fingerprints first seen within `[from, to)`
+    fn first_seen_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Vec<StoredFingerprint>, Error>>;
+
+    /// Time the oldest fingerprint in the store was first seen
+    fn earliest_first_seen(&self) -> BoxFuture<'_, Result<Option<DateTime<Utc>>, Error>>;
+
+    /// Removes fingerprints first seen before `before`, returns the number of removed fingerprints
+    fn expire(&self, before: DateTime<Utc>) -> BoxFuture<'_, Result<u64, Error>>;
+}
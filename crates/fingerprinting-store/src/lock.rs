@@ -0,0 +1,221 @@
+//! Store backed mutual exclusion of administrative ceremonies (share rotation, refresh, etc.)
+//! between several coordinators
+//!
+//! The lock is a lease: it expires unless renewed by the holder, so a crashed coordinator
+//! never blocks ceremonies forever. Every change of the holder increments the fencing token,
+//! which ceremonies should pass to agents, so a stale holder is rejected.
+
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Duration, Utc};
+use fingerprinting_core::clock::Clock;
+use futures::future::{ready, BoxFuture, FutureExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+/// Lease of the ceremony lock
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    /// Name of the ceremony
+    pub name: String,
+
+    /// Coordinator holding the lease
+    pub holder: String,
+
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+
+    /// Monotonically increasing with every change of the holder
+    pub fencing_token: u64,
+}
+
+impl Lease {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now
+    }
+}
+
+pub trait CeremonyLock: Send + Sync {
+    /// Acquires the lease or renews it when already held by the `holder`
+    /// Returns `None` when the active lease is held by another coordinator
+    fn try_acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl: Duration,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<Lease>, Error>>;
+
+    /// Releases the lease, returns `false` when the lease has been taken over already
+    fn release(&self, lease: &Lease, now: DateTime<Utc>) -> BoxFuture<'_, Result<bool, Error>>;
+
+    /// Latest lease of the ceremony, either active or expired
+    fn status(&self, name: &str) -> BoxFuture<'_, Result<Option<Lease>, Error>>;
+}
+
+/// Runs the ceremony exclusively, fails when another coordinator runs it at the moment
+/// The `ttl` should cover the whole ceremony, since the lease is not renewed while it runs
+pub async fn run_exclusive<T, F, Fut>(
+    lock: &dyn CeremonyLock,
+    clock: &dyn Clock,
+    name: &str,
+    holder: &str,
+    ttl: Duration,
+    ceremony: F,
+) -> Result<T, Error>
+where
+    F: FnOnce(Lease) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let lease = match lock.try_acquire(name, holder, ttl, clock.now()).await? {
+        Some(lease) => lease,
+        None => {
+            let current = lock.status(name).await?;
+            return Err(anyhow!(
+                "Ceremony {} is already running by {}",
+                name,
+                current.map(|lease| lease.holder).unwrap_or_default()
+            ));
+        }
+    };
+
+    let result = ceremony(lease.clone()).await;
+
+    if !lock.release(&lease, clock.now()).await? {
+        log::warn!(
+            "Lease of the ceremony {} has expired before it completed, fencing token {}",
+            name,
+            lease.fencing_token
+        );
+    }
+
+    result
+}
+
+/// Lock within the single process, suitable for development and testing purposes
+#[derive(Default)]
+pub struct MemoryCeremonyLock {
+    leases: Mutex<HashMap<String, Lease>>,
+}
+
+impl MemoryCeremonyLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CeremonyLock for MemoryCeremonyLock {
+    fn try_acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl: Duration,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<Lease>, Error>> {
+        let mut leases = self.leases.lock().unwrap();
+
+        let lease = match leases.get(name) {
+            Some(current) if current.holder == holder && current.is_active(now) => Lease {
+                expires_at: now + ttl,
+                ..current.clone()
+            },
+            Some(current) if current.is_active(now) => return ready(Ok(None)).boxed(),
+            current => Lease {
+                name: name.to_string(),
+                holder: holder.to_string(),
+                acquired_at: now,
+                expires_at: now + ttl,
+                fencing_token: current.map(|lease| lease.fencing_token + 1).unwrap_or(1),
+            },
+        };
+        leases.insert(name.to_string(), lease.clone());
+
+        ready(Ok(Some(lease))).boxed()
+    }
+
+    fn release(&self, lease: &Lease, now: DateTime<Utc>) -> BoxFuture<'_, Result<bool, Error>> {
+        let mut leases = self.leases.lock().unwrap();
+
+        let released = match leases.get_mut(&lease.name) {
+            Some(current)
+                if current.fencing_token == lease.fencing_token && current.is_active(now) =>
+            {
+                current.expires_at = now;
+                true
+            }
+            _ => false,
+        };
+
+        ready(Ok(released)).boxed()
+    }
+
+    fn status(&self, name: &str) -> BoxFuture<'_, Result<Option<Lease>, Error>> {
+        let lease = self.leases.lock().unwrap().get(name).cloned();
+
+        ready(Ok(lease)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use fingerprinting_core::clock::FixedClock;
+
+    #[tokio::test]
+    async fn test_lease_exclusion() -> Result<(), Error> {
+        let lock = MemoryCeremonyLock::new();
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let ttl = Duration::minutes(10);
+
+        let lease = lock
+            .try_acquire("rotation", "coordinator-1", ttl, now)
+            .await?
+            .unwrap();
+        assert_eq!(lease.fencing_token, 1);
+
+        // Another coordinator waits until the lease expires
+        let later = now + Duration::minutes(5);
+        assert!(lock
+            .try_acquire("rotation", "coordinator-2", ttl, later)
+            .await?
+            .is_none());
+        assert!(lock
+            .try_acquire("refresh", "coordinator-2", ttl, later)
+            .await?
+            .is_some());
+
+        // Holder renews the lease keeping the fencing token
+        let renewed = lock
+            .try_acquire("rotation", "coordinator-1", ttl, later)
+            .await?
+            .unwrap();
+        assert_eq!(renewed.fencing_token, 1);
+        assert_eq!(renewed.expires_at, later + ttl);
+
+        let expired = later + ttl;
+        let taken_over = lock
+            .try_acquire("rotation", "coordinator-2", ttl, expired)
+            .await?
+            .unwrap();
+        assert_eq!(taken_over.fencing_token, 2);
+
+        // Stale holder can not release the lease taken over
+        assert!(!lock.release(&renewed, expired).await?);
+        assert!(lock.release(&taken_over, expired).await?);
+        assert!(!lock.status("rotation").await?.unwrap().is_active(expired));
+
+        let result = run_exclusive(
+            &lock,
+            &FixedClock::new(expired),
+            "rotation",
+            "coordinator-1",
+            ttl,
+            |lease| async move { Ok(lease.fencing_token) },
+        )
+        .await?;
+        assert_eq!(result, 3);
+
+        Ok(())
+    }
+}
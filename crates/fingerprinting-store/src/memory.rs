@@ -0,0 +1,180 @@
+use crate::{DuplicateWindow, FingerprintStore, InsertOutcome, StoredFingerprint};
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use futures::future::{ready, BoxFuture, FutureExt};
+use halo2_axiom::halo2curves::bn256::Fr;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Non persistent store, suitable for development and testing purposes
+#[derive(Default)]
+pub struct MemoryFingerprintStore {
+    fingerprints: Mutex<HashMap<([u8; 32], u64), StoredFingerprint>>,
+}
+
+impl MemoryFingerprintStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl FingerprintStore for MemoryFingerprintStore {
+    fn insert(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+        seen_at: DateTime<Utc>,
+        window: DuplicateWindow,
+    ) -> BoxFuture<'_, Result<InsertOutcome, Error>> {
+        let mut fingerprints = self.fingerprints.lock().unwrap();
+
+        let outcome = match fingerprints.get_mut(&(fingerprint.to_bytes(), key_epoch)) {
+            Some(stored) => {
+                stored.last_seen = stored.last_seen.max(seen_at);
+                stored.occurrences += 1;
+
+                match window.contains(stored.window_start, seen_at) {
+                    true => InsertOutcome::Duplicate(stored.clone()),
+                    false => {
+                        stored.window_start = seen_at;
+                        InsertOutcome::Recurring(stored.clone())
+                    }
+                }
+            }
+            None => {
+                let stored = StoredFingerprint {
+                    fingerprint,
+                    key_epoch,
+                    first_seen: seen_at,
+                    last_seen: seen_at,
+                    occurrences: 1,
+                    window_start: seen_at,
+                };
+                fingerprints.insert((fingerprint.to_bytes(), key_epoch), stored.clone());
+
+                InsertOutcome::Inserted(stored)
+            }
+        };
+
+        ready(Ok(outcome)).boxed()
+    }
+
+    fn lookup(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+    ) -> BoxFuture<'_, Result<Option<StoredFingerprint>, Error>> {
+        let fingerprints = self.fingerprints.lock().unwrap();
+        let stored = fingerprints
+            .get(&(fingerprint.to_bytes(), key_epoch))
+            .cloned();
+
+        ready(Ok(stored)).boxed()
+    }
+
+    fn first_seen_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Vec<StoredFingerprint>, Error>> {
+        let fingerprints = self.fingerprints.lock().unwrap();
+        let stored = fingerprints
+            .values()
+            .filter(|stored| stored.first_seen >= from && stored.first_seen < to)
+            .cloned()
+            .collect();
+
+        ready(Ok(stored)).boxed()
+    }
+
+    fn earliest_first_seen(&self) -> BoxFuture<'_, Result<Option<DateTime<Utc>>, Error>> {
+        let fingerprints = self.fingerprints.lock().unwrap();
+        let earliest = fingerprints.values().map(|stored| stored.first_seen).min();
+
+        ready(Ok(earliest)).boxed()
+    }
+
+    fn expire(&self, before: DateTime<Utc>) -> BoxFuture<'_, Result<u64, Error>> {
+        let mut fingerprints = self.fingerprints.lock().unwrap();
+        let size = fingerprints.len();
+        fingerprints.retain(|_, stored| stored.first_seen >= before);
+
+        ready(Ok((size - fingerprints.len()) as u64)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DuplicateCheck;
+    use chrono::{Duration, TimeZone};
+
+    #[tokio::test]
+    async fn test_duplicates_within_key_epoch() -> Result<(), Error> {
+        let store = MemoryFingerprintStore::new();
+        let first_seen = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let last_seen = Utc.with_ymd_and_hms(2025, 9, 17, 12, 0, 0).unwrap();
+
+        let inserted = store
+            .insert(Fr::from(42), 0, first_seen, DuplicateWindow::Unbounded)
+            .await?;
+        assert!(!inserted.is_duplicate());
+
+        let duplicate = store
+            .insert(Fr::from(42), 0, last_seen, DuplicateWindow::Unbounded)
+            .await?;
+        assert!(duplicate.is_duplicate());
+        assert_eq!(duplicate.stored().first_seen, first_seen);
+        assert_eq!(duplicate.stored().last_seen, last_seen);
+        assert_eq!(duplicate.stored().occurrences, 2);
+
+        // Other key epoch has its own fingerprints
+        assert!(!store
+            .insert(Fr::from(42), 1, last_seen, DuplicateWindow::Unbounded)
+            .await?
+            .is_duplicate());
+
+        assert!(store.lookup(Fr::from(42), 0).await?.is_some());
+        assert!(store.lookup(Fr::from(43), 0).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_window() -> Result<(), Error> {
+        let store = MemoryFingerprintStore::new();
+        let window = DuplicateWindow::Within(Duration::days(90));
+        let day = |month, day| Utc.with_ymd_and_hms(2025, month, day, 12, 0, 0).unwrap();
+
+        let first = store.insert(Fr::from(42), 0, day(1, 1), window).await?;
+        assert!(matches!(first, InsertOutcome::Inserted(_)));
+
+        let duplicate = store.insert(Fr::from(42), 0, day(3, 1), window).await?;
+        assert!(duplicate.is_duplicate());
+
+        // The window is counted from the occurrence, not from the latest duplicate
+        let recurring = store.insert(Fr::from(42), 0, day(4, 15), window).await?;
+        assert!(matches!(recurring, InsertOutcome::Recurring(_)));
+        assert_eq!(recurring.stored().first_seen, day(1, 1));
+        assert_eq!(recurring.stored().window_start, day(4, 15));
+        assert_eq!(recurring.stored().occurrences, 3);
+
+        assert!(matches!(
+            store.check(Fr::from(42), 0, day(5, 1), window).await?,
+            DuplicateCheck::Duplicate(_)
+        ));
+        assert!(matches!(
+            store.check(Fr::from(42), 0, day(9, 1), window).await?,
+            DuplicateCheck::Recurring(_)
+        ));
+        assert_eq!(
+            store.check(Fr::from(43), 0, day(9, 1), window).await?,
+            DuplicateCheck::New
+        );
+
+        // Checks do not store anything
+        assert_eq!(store.lookup(Fr::from(42), 0).await?.unwrap().occurrences, 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! SHA-256 Merkle tree over the fingerprints of the archived epoch with the inclusion proofs,
+//! see `fingerprinting_verify::merkle` for the tree layout and the proof verification
+
+use crate::StoredFingerprint;
+use anyhow::{anyhow, Error};
+use chrono::NaiveDate;
+use fingerprinting_verify::merkle::{leaf, parent};
+use futures::future::BoxFuture;
+use halo2_axiom::halo2curves::bn256::Fr;
+
+pub use fingerprinting_verify::merkle::{InclusionProof, ProofStep};
+
+/// Published root of the epoch with the inclusion proof of the fingerprint
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochInclusion {
+    pub root: [u8; 32],
+
+    /// Proof of the fingerprint into the `root`, absent when the fingerprint is not included into the epoch
+    pub proof: Option<InclusionProof>,
+
+    /// Where the root is published, e.g. the location of the epoch manifest
+    pub anchor: Option<String>,
+}
+
+/// Published epochs the inclusion proofs of the fingerprints are served from
+pub trait EpochProofs: Send + Sync {
+    /// Inclusion of the fingerprint into the `epoch`, absent when the epoch is not published
+    fn inclusion(
+        &self,
+        epoch: NaiveDate,
+        fingerprint: Fr,
+        key_epoch: u64,
+    ) -> BoxFuture<'_, Result<Option<EpochInclusion>, Error>>;
+}
+
+fn leaves(fingerprints: &[StoredFingerprint]) -> Vec<(u64, [u8; 32])> {
+    let mut leaves = fingerprints
+        .iter()
+        .map(|stored| (stored.key_epoch, stored.fingerprint.to_bytes()))
+        .collect::<Vec<_>>();
+    leaves.sort();
+
+    leaves
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => parent(left, right),
+            [odd] => *odd,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Merkle root over the fingerprints
+pub fn archive_root(fingerprints: &[StoredFingerprint]) -> [u8; 32] {
+    let mut level = leaves(fingerprints)
+        .iter()
+        .map(|(key_epoch, fingerprint)| leaf(*key_epoch, fingerprint))
+        .collect::<Vec<_>>();
+
+    if level.is_empty() {
+        return [0u8; 32];
+    }
+
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+
+    level[0]
+}
+
+/// Inclusion proof of the fingerprint, absent when the fingerprint is not among the `fingerprints`
+pub fn inclusion_proof(
+    fingerprints: &[StoredFingerprint],
+    key_epoch: u64,
+    fingerprint: &Fr,
+) -> Option<InclusionProof> {
+    let leaves = leaves(fingerprints);
+    let mut index = leaves
+        .binary_search(&(key_epoch, fingerprint.to_bytes()))
+        .ok()?;
+
+    let mut level = leaves
+        .iter()
+        .map(|(key_epoch, fingerprint)| leaf(*key_epoch, fingerprint))
+        .collect::<Vec<_>>();
+    let mut proof = InclusionProof::default();
+
+    while level.len() > 1 {
+        // The odd node has no sibling, it is promoted as is
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.steps.push(ProofStep {
+                sibling: *sibling,
+                sibling_left: index % 2 == 1,
+            });
+        }
+
+        level = next_level(&level);
+        index /= 2;
+    }
+
+    Some(proof)
+}
+
+/// Tree of the fingerprints stored within the epoch in the order of their insertion, kept as the partial tree:
+/// the roots of the complete subtrees, at most one per level. Nodes are laid out as the ones of the archived epoch,
+/// but the leaves are not sorted, so the root matches the archived one only for the fingerprints inserted in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochAccumulator {
+    epoch: NaiveDate,
+    leaves: u64,
+    /// Root of the complete subtree of 2^level leaves by the level, present for the bits set in `leaves`
+    frontier: Vec<Option<[u8; 32]>>,
+}
+
+impl EpochAccumulator {
+    pub fn new(epoch: NaiveDate) -> Self {
+        Self {
+            epoch,
+            leaves: 0,
+            frontier: vec![],
+        }
+    }
+
+    /// Accumulator of the saved partial tree, see `frontier`
+    pub fn from_frontier(
+        epoch: NaiveDate,
+        leaves: u64,
+        frontier: Vec<Option<[u8; 32]>>,
+    ) -> Result<Self, Error> {
+        let levels = (u64::BITS - leaves.leading_zeros()) as usize;
+        let consistent = frontier.len() == levels
+            && frontier
+                .iter()
+                .enumerate()
+                .all(|(level, node)| node.is_some() == ((leaves >> level) & 1 == 1));
+        if !consistent {
+            return Err(anyhow!(
+                "Partial tree should have the subtree roots of {} leaves",
+                leaves
+            ));
+        }
+
+        Ok(Self {
+            epoch,
+            leaves,
+            frontier,
+        })
+    }
+
+    pub fn epoch(&self) -> NaiveDate {
+        self.epoch
+    }
+
+    pub fn leaves(&self) -> u64 {
+        self.leaves
+    }
+
+    /// Roots of the complete subtrees by their levels
+    pub fn frontier(&self) -> &[Option<[u8; 32]>] {
+        &self.frontier
+    }
+
+    pub fn append(&mut self, key_epoch: u64, fingerprint: &Fr) {
+        let mut carry = leaf(key_epoch, &fingerprint.to_bytes());
+        for node in self.frontier.iter_mut() {
+            match node.take() {
+                Some(left) => carry = parent(&left, &carry),
+                None => {
+                    *node = Some(carry);
+                    self.leaves += 1;
+                    return;
+                }
+            }
+        }
+        self.frontier.push(Some(carry));
+        self.leaves += 1;
+    }
+
+    /// Root of the fingerprints accumulated so far, zero for the empty epoch
+    pub fn root(&self) -> [u8; 32] {
+        // Smaller subtrees are the odd nodes promoted up to the level of the larger ones
+        self.frontier
+            .iter()
+            .flatten()
+            .fold(None, |right, left| match right {
+                Some(right) => Some(parent(left, &right)),
+                None => Some(*left),
+            })
+            .unwrap_or([0u8; 32])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_inclusion_proofs() {
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let stored = |fingerprint: u64, key_epoch| StoredFingerprint {
+            fingerprint: Fr::from(fingerprint),
+            key_epoch,
+            first_seen: now,
+            last_seen: now,
+            occurrences: 1,
+            window_start: now,
+        };
+
+        // Odd number of leaves on the several levels
+        let fingerprints = (0..11).map(|i| stored(i, i % 2)).collect::<Vec<_>>();
+        let root = archive_root(&fingerprints);
+
+        for stored in &fingerprints {
+            let proof =
+                inclusion_proof(&fingerprints, stored.key_epoch, &stored.fingerprint).unwrap();
+            assert!(proof.verify(&root, stored.key_epoch, &stored.fingerprint));
+            assert!(!proof.verify(&root, stored.key_epoch + 1, &stored.fingerprint));
+            assert!(!proof.verify(&root, stored.key_epoch, &Fr::from(100)));
+        }
+
+        assert!(inclusion_proof(&fingerprints, 0, &Fr::from(1)).is_none());
+
+        let single = [stored(42, 0)];
+        let proof = inclusion_proof(&single, 0, &Fr::from(42)).unwrap();
+        assert!(proof.steps.is_empty());
+        assert!(proof.verify(&archive_root(&single), 0, &Fr::from(42)));
+    }
+
+    #[test]
+    fn test_epoch_accumulator() -> Result<(), Error> {
+        let now = Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap();
+        let stored = |fingerprint: u64| StoredFingerprint {
+            fingerprint: Fr::from(fingerprint),
+            key_epoch: 0,
+            first_seen: now,
+            last_seen: now,
+            occurrences: 1,
+            window_start: now,
+        };
+        let mut fingerprints = (0..11).map(stored).collect::<Vec<_>>();
+        fingerprints.sort_by_key(|stored| stored.fingerprint.to_bytes());
+
+        let mut accumulator = EpochAccumulator::new(now.date_naive());
+        assert_eq!(accumulator.root(), [0u8; 32]);
+        for (inserted, stored) in fingerprints.iter().enumerate() {
+            accumulator.append(stored.key_epoch, &stored.fingerprint);
+            // Inserted in the order of the leaves, it is the tree of the archived epoch
+            assert_eq!(accumulator.root(), archive_root(&fingerprints[..=inserted]));
+        }
+
+        let restored = EpochAccumulator::from_frontier(
+            accumulator.epoch(),
+            accumulator.leaves(),
+            accumulator.frontier().to_vec(),
+        )?;
+        assert_eq!(restored, accumulator);
+        assert!(EpochAccumulator::from_frontier(
+            accumulator.epoch(),
+            accumulator.leaves() + 1,
+            accumulator.frontier().to_vec(),
+        )
+        .is_err());
+
+        Ok(())
+    }
+}
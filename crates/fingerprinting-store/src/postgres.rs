@@ -0,0 +1,451 @@
+use crate::lock::{CeremonyLock, Lease};
+use crate::tally::TallyLedger;
+use crate::usage::{Usage, UsageLedger};
+use crate::{DuplicateWindow, FingerprintStore, InsertOutcome, StoredFingerprint};
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::future::{BoxFuture, FutureExt};
+use halo2_axiom::halo2curves::bn256::Fr;
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use sqlx::Row;
+use std::collections::BTreeMap;
+
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Postgres backed store, fingerprints uniqueness is enforced by the `(fingerprint, key_epoch)` constraint
+pub struct PostgresFingerprintStore {
+    pool: PgPool,
+}
+
+impl PostgresFingerprintStore {
+    /// Connects to the database and applies pending migrations
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self, Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await?;
+
+        MIGRATOR.run(&pool).await?;
+
+        Ok(Self { pool })
+    }
+
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Ceremony lock sharing the connection pool with the store
+    pub fn ceremony_lock(&self) -> PostgresCeremonyLock {
+        PostgresCeremonyLock {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// Usage ledger sharing the connection pool with the store
+    pub fn usage_ledger(&self) -> PostgresUsageLedger {
+        PostgresUsageLedger {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// Tally ledger sharing the connection pool with the store
+    pub fn tally_ledger(&self) -> PostgresTallyLedger {
+        PostgresTallyLedger {
+            pool: self.pool.clone(),
+        }
+    }
+
+    fn key_epoch(key_epoch: u64) -> Result<i64, Error> {
+        i64::try_from(key_epoch).map_err(|_| anyhow!("Key epoch {} is out of range", key_epoch))
+    }
+
+    fn stored(row: &PgRow) -> Result<StoredFingerprint, Error> {
+        let fingerprint: Vec<u8> = row.try_get("fingerprint")?;
+        let fingerprint = fingerprint
+            .first_chunk::<32>()
+            .and_then(|bytes| Fr::from_bytes(bytes).into_option())
+            .ok_or(anyhow!("Stored fingerprint does not represent Fr"))?;
+
+        let key_epoch: i64 = row.try_get("key_epoch")?;
+        let occurrences: i64 = row.try_get("occurrences")?;
+
+        Ok(StoredFingerprint {
+            fingerprint,
+            key_epoch: key_epoch as u64,
+            first_seen: row.try_get("first_seen")?,
+            last_seen: row.try_get("last_seen")?,
+            occurrences: occurrences as u64,
+            window_start: row.try_get("window_start")?,
+        })
+    }
+}
+
+impl FingerprintStore for PostgresFingerprintStore {
+    fn insert(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+        seen_at: DateTime<Utc>,
+        window: DuplicateWindow,
+    ) -> BoxFuture<'_, Result<InsertOutcome, Error>> {
+        async move {
+            // Window in seconds, NULL for the unbounded window
+            let window_secs = match window {
+                DuplicateWindow::Unbounded => None,
+                DuplicateWindow::Within(window) => Some(window.num_seconds()),
+            };
+
+            let row = sqlx::query(
+                r#"
+                WITH previous AS (
+                    SELECT window_start FROM fingerprints WHERE fingerprint = $1 AND key_epoch = $2
+                )
+                INSERT INTO fingerprints (fingerprint, key_epoch, first_seen, last_seen, occurrences, window_start)
+                VALUES ($1, $2, $3, $3, 1, $3)
+                ON CONFLICT (fingerprint, key_epoch) DO UPDATE
+                    SET last_seen = GREATEST(fingerprints.last_seen, EXCLUDED.last_seen),
+                        occurrences = fingerprints.occurrences + 1,
+                        window_start = CASE
+                            WHEN $4::BIGINT IS NOT NULL
+                                AND EXCLUDED.window_start - fingerprints.window_start > make_interval(secs => $4::DOUBLE PRECISION)
+                            THEN EXCLUDED.window_start
+                            ELSE fingerprints.window_start
+                        END
+                RETURNING fingerprint, key_epoch, first_seen, last_seen, occurrences, window_start,
+                    (SELECT window_start FROM previous) AS previous_window_start
+                "#,
+            )
+            .bind(fingerprint.to_bytes().as_slice())
+            .bind(Self::key_epoch(key_epoch)?)
+            .bind(seen_at)
+            .bind(window_secs)
+            .fetch_one(&self.pool)
+            .await?;
+
+            let stored = Self::stored(&row)?;
+            let previous_window_start: Option<DateTime<Utc>> = row.try_get("previous_window_start")?;
+
+            Ok(match (stored.occurrences, previous_window_start) {
+                (1, _) => InsertOutcome::Inserted(stored),
+                (_, Some(previous)) if !window.contains(previous, seen_at) => {
+                    InsertOutcome::Recurring(stored)
+                }
+                // Concurrent insert of the same fingerprint is a duplicate as well
+                _ => InsertOutcome::Duplicate(stored),
+            })
+        }
+        .boxed()
+    }
+
+    fn lookup(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+    ) -> BoxFuture<'_, Result<Option<StoredFingerprint>, Error>> {
+        async move {
+            let row = sqlx::query(
+                r#"
+                SELECT fingerprint, key_epoch, first_seen, last_seen, occurrences, window_start
+                FROM fingerprints
+                WHERE fingerprint = $1 AND key_epoch = $2
+                "#,
+            )
+            .bind(fingerprint.to_bytes().as_slice())
+            .bind(Self::key_epoch(key_epoch)?)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            row.as_ref().map(Self::stored).transpose()
+        }
+        .boxed()
+    }
+
+    fn first_seen_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Vec<StoredFingerprint>, Error>> {
+        async move {
+            let rows = sqlx::query(
+                r#"
+                SELECT fingerprint, key_epoch, first_seen, last_seen, occurrences, window_start
+                FROM fingerprints
+                WHERE first_seen >= $1 AND first_seen < $2
+                "#,
+            )
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(Self::stored).collect()
+        }
+        .boxed()
+    }
+
+    fn earliest_first_seen(&self) -> BoxFuture<'_, Result<Option<DateTime<Utc>>, Error>> {
+        async move {
+            let earliest: Option<DateTime<Utc>> =
+                sqlx::query_scalar("SELECT MIN(first_seen) FROM fingerprints")
+                    .fetch_one(&self.pool)
+                    .await?;
+
+            Ok(earliest)
+        }
+        .boxed()
+    }
+
+    fn expire(&self, before: DateTime<Utc>) -> BoxFuture<'_, Result<u64, Error>> {
+        async move {
+            let result = sqlx::query("DELETE FROM fingerprints WHERE first_seen < $1")
+                .bind(before)
+                .execute(&self.pool)
+                .await?;
+
+            Ok(result.rows_affected())
+        }
+        .boxed()
+    }
+}
+
+/// Postgres backed ceremony lock, leases of all coordinators are kept in the `ceremony_locks` table
+pub struct PostgresCeremonyLock {
+    pool: PgPool,
+}
+
+impl PostgresCeremonyLock {
+    fn lease(row: &PgRow) -> Result<Lease, Error> {
+        let fencing_token: i64 = row.try_get("fencing_token")?;
+
+        Ok(Lease {
+            name: row.try_get("name")?,
+            holder: row.try_get("holder")?,
+            acquired_at: row.try_get("acquired_at")?,
+            expires_at: row.try_get("expires_at")?,
+            fencing_token: fencing_token as u64,
+        })
+    }
+}
+
+impl CeremonyLock for PostgresCeremonyLock {
+    fn try_acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl: Duration,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<Lease>, Error>> {
+        let name = name.to_string();
+        let holder = holder.to_string();
+
+        async move {
+            // Renewal keeps the fencing token, takeover of the expired lease increments it
+            let row = sqlx::query(
+                r#"
+                INSERT INTO ceremony_locks (name, holder, acquired_at, expires_at, fencing_token)
+                VALUES ($1, $2, $3, $4, 1)
+                ON CONFLICT (name) DO UPDATE
+                    SET holder = EXCLUDED.holder,
+                        acquired_at = CASE
+                            WHEN ceremony_locks.holder = EXCLUDED.holder AND ceremony_locks.expires_at > EXCLUDED.acquired_at
+                            THEN ceremony_locks.acquired_at
+                            ELSE EXCLUDED.acquired_at
+                        END,
+                        expires_at = EXCLUDED.expires_at,
+                        fencing_token = CASE
+                            WHEN ceremony_locks.holder = EXCLUDED.holder AND ceremony_locks.expires_at > EXCLUDED.acquired_at
+                            THEN ceremony_locks.fencing_token
+                            ELSE ceremony_locks.fencing_token + 1
+                        END
+                    WHERE ceremony_locks.holder = EXCLUDED.holder
+                        OR ceremony_locks.expires_at <= EXCLUDED.acquired_at
+                RETURNING name, holder, acquired_at, expires_at, fencing_token
+                "#,
+            )
+            .bind(&name)
+            .bind(&holder)
+            .bind(now)
+            .bind(now + ttl)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            row.as_ref().map(Self::lease).transpose()
+        }
+        .boxed()
+    }
+
+    fn release(&self, lease: &Lease, now: DateTime<Utc>) -> BoxFuture<'_, Result<bool, Error>> {
+        let name = lease.name.clone();
+        let fencing_token = lease.fencing_token as i64;
+
+        async move {
+            let result = sqlx::query(
+                r#"
+                UPDATE ceremony_locks SET expires_at = $3
+                WHERE name = $1 AND fencing_token = $2 AND expires_at > $3
+                "#,
+            )
+            .bind(&name)
+            .bind(fencing_token)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(result.rows_affected() == 1)
+        }
+        .boxed()
+    }
+
+    fn status(&self, name: &str) -> BoxFuture<'_, Result<Option<Lease>, Error>> {
+        let name = name.to_string();
+
+        async move {
+            let row = sqlx::query(
+                r#"
+                SELECT name, holder, acquired_at, expires_at, fencing_token
+                FROM ceremony_locks
+                WHERE name = $1
+                "#,
+            )
+            .bind(&name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            row.as_ref().map(Self::lease).transpose()
+        }
+        .boxed()
+    }
+}
+
+pub struct PostgresUsageLedger {
+    pool: PgPool,
+}
+
+impl PostgresUsageLedger {
+    fn usage(row: &PgRow) -> Result<Usage, Error> {
+        let fingerprints: i64 = row.try_get("fingerprints")?;
+        let agent_rounds: i64 = row.try_get("agent_rounds")?;
+
+        Ok(Usage {
+            fingerprints: fingerprints as u64,
+            agent_rounds: agent_rounds as u64,
+        })
+    }
+}
+
+impl UsageLedger for PostgresUsageLedger {
+    fn record(
+        &self,
+        name: &str,
+        month: NaiveDate,
+        usage: Usage,
+    ) -> BoxFuture<'_, Result<Usage, Error>> {
+        let name = name.to_string();
+
+        async move {
+            let row = sqlx::query(
+                r#"
+                INSERT INTO api_key_usage (name, month, fingerprints, agent_rounds)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (name, month) DO UPDATE
+                    SET fingerprints = api_key_usage.fingerprints + EXCLUDED.fingerprints,
+                        agent_rounds = api_key_usage.agent_rounds + EXCLUDED.agent_rounds
+                RETURNING fingerprints, agent_rounds
+                "#,
+            )
+            .bind(&name)
+            .bind(month)
+            .bind(usage.fingerprints as i64)
+            .bind(usage.agent_rounds as i64)
+            .fetch_one(&self.pool)
+            .await?;
+
+            Self::usage(&row)
+        }
+        .boxed()
+    }
+
+    fn usage(&self, name: &str, month: NaiveDate) -> BoxFuture<'_, Result<Usage, Error>> {
+        let name = name.to_string();
+
+        async move {
+            let row = sqlx::query(
+                "SELECT fingerprints, agent_rounds FROM api_key_usage WHERE name = $1 AND month = $2",
+            )
+            .bind(&name)
+            .bind(month)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            Ok(row.as_ref().map(Self::usage).transpose()?.unwrap_or_default())
+        }
+        .boxed()
+    }
+
+    fn report(&self, month: NaiveDate) -> BoxFuture<'_, Result<Vec<(String, Usage)>, Error>> {
+        async move {
+            let rows = sqlx::query(
+                r#"
+                SELECT name, fingerprints, agent_rounds
+                FROM api_key_usage
+                WHERE month = $1
+                ORDER BY name
+                "#,
+            )
+            .bind(month)
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter()
+                .map(|row| Ok((row.try_get("name")?, Self::usage(row)?)))
+                .collect()
+        }
+        .boxed()
+    }
+}
+
+pub struct PostgresTallyLedger {
+    pool: PgPool,
+}
+
+impl TallyLedger for PostgresTallyLedger {
+    fn record(&self, epoch: NaiveDate, currency: u16) -> BoxFuture<'_, Result<(), Error>> {
+        async move {
+            sqlx::query(
+                r#"
+                INSERT INTO epoch_tallies (epoch, currency, fingerprints)
+                VALUES ($1, $2, 1)
+                ON CONFLICT (epoch, currency) DO UPDATE
+                    SET fingerprints = epoch_tallies.fingerprints + 1
+                "#,
+            )
+            .bind(epoch)
+            .bind(currency as i32)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn tallies(&self, epoch: NaiveDate) -> BoxFuture<'_, Result<BTreeMap<u16, u64>, Error>> {
+        async move {
+            let rows =
+                sqlx::query("SELECT currency, fingerprints FROM epoch_tallies WHERE epoch = $1")
+                    .bind(epoch)
+                    .fetch_all(&self.pool)
+                    .await?;
+
+            rows.iter()
+                .map(|row| {
+                    let currency: i32 = row.try_get("currency")?;
+                    let fingerprints: i64 = row.try_get("fingerprints")?;
+
+                    Ok((u16::try_from(currency)?, fingerprints as u64))
+                })
+                .collect()
+        }
+        .boxed()
+    }
+}
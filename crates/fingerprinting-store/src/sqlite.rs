@@ -0,0 +1,438 @@
+use crate::tally::TallyLedger;
+use crate::usage::{Usage, UsageLedger};
+use crate::{DuplicateWindow, FingerprintStore, InsertOutcome, StoredFingerprint};
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::future::{BoxFuture, FutureExt};
+use halo2_axiom::halo2curves::bn256::Fr;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::Row;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// SQLite backed store of the single node deployments, the database is a single file next to the service.
+/// The pool keeps one connection, SQLite serializes the writers anyway and the insert reads the previous
+/// window in the same transaction
+pub struct SqliteFingerprintStore {
+    pool: SqlitePool,
+}
+
+impl SqliteFingerprintStore {
+    /// Opens the database file, creating it if missing, and applies pending migrations.
+    /// `:memory:` opens the non persistent database
+    pub async fn connect(path: &str) -> Result<Self, Error> {
+        let options = match path {
+            ":memory:" => SqliteConnectOptions::from_str("sqlite::memory:")?,
+            path => SqliteConnectOptions::new()
+                .filename(path)
+                .create_if_missing(true),
+        };
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        MIGRATOR.run(&pool).await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Usage ledger sharing the connection with the store
+    pub fn usage_ledger(&self) -> SqliteUsageLedger {
+        SqliteUsageLedger {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// Tally ledger sharing the connection with the store
+    pub fn tally_ledger(&self) -> SqliteTallyLedger {
+        SqliteTallyLedger {
+            pool: self.pool.clone(),
+        }
+    }
+
+    fn key_epoch(key_epoch: u64) -> Result<i64, Error> {
+        i64::try_from(key_epoch).map_err(|_| anyhow!("Key epoch {} is out of range", key_epoch))
+    }
+
+    fn time(micros: i64) -> Result<DateTime<Utc>, Error> {
+        DateTime::from_timestamp_micros(micros)
+            .ok_or(anyhow!("Stored time {} is out of range", micros))
+    }
+
+    fn stored(row: &SqliteRow) -> Result<StoredFingerprint, Error> {
+        let fingerprint: Vec<u8> = row.try_get("fingerprint")?;
+        let fingerprint = fingerprint
+            .first_chunk::<32>()
+            .and_then(|bytes| Fr::from_bytes(bytes).into_option())
+            .ok_or(anyhow!("Stored fingerprint does not represent Fr"))?;
+
+        let key_epoch: i64 = row.try_get("key_epoch")?;
+        let occurrences: i64 = row.try_get("occurrences")?;
+
+        Ok(StoredFingerprint {
+            fingerprint,
+            key_epoch: key_epoch as u64,
+            first_seen: Self::time(row.try_get("first_seen")?)?,
+            last_seen: Self::time(row.try_get("last_seen")?)?,
+            occurrences: occurrences as u64,
+            window_start: Self::time(row.try_get("window_start")?)?,
+        })
+    }
+}
+
+impl FingerprintStore for SqliteFingerprintStore {
+    fn insert(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+        seen_at: DateTime<Utc>,
+        window: DuplicateWindow,
+    ) -> BoxFuture<'_, Result<InsertOutcome, Error>> {
+        async move {
+            let fingerprint = fingerprint.to_bytes();
+            let key_epoch = Self::key_epoch(key_epoch)?;
+            let mut tx = self.pool.begin().await?;
+
+            let previous_window_start: Option<i64> = sqlx::query_scalar(
+                "SELECT window_start FROM fingerprints WHERE fingerprint = ?1 AND key_epoch = ?2",
+            )
+            .bind(fingerprint.as_slice())
+            .bind(key_epoch)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            // Window in microseconds, NULL for the unbounded window
+            let window_micros = match window {
+                DuplicateWindow::Unbounded => None,
+                DuplicateWindow::Within(window) => window.num_microseconds(),
+            };
+
+            let row = sqlx::query(
+                r#"
+                INSERT INTO fingerprints (fingerprint, key_epoch, first_seen, last_seen, occurrences, window_start)
+                VALUES (?1, ?2, ?3, ?3, 1, ?3)
+                ON CONFLICT (fingerprint, key_epoch) DO UPDATE
+                    SET last_seen = MAX(fingerprints.last_seen, excluded.last_seen),
+                        occurrences = fingerprints.occurrences + 1,
+                        window_start = CASE
+                            WHEN ?4 IS NOT NULL AND excluded.window_start - fingerprints.window_start > ?4
+                            THEN excluded.window_start
+                            ELSE fingerprints.window_start
+                        END
+                RETURNING fingerprint, key_epoch, first_seen, last_seen, occurrences, window_start
+                "#,
+            )
+            .bind(fingerprint.as_slice())
+            .bind(key_epoch)
+            .bind(seen_at.timestamp_micros())
+            .bind(window_micros)
+            .fetch_one(&mut *tx)
+            .await?;
+            let stored = Self::stored(&row)?;
+
+            tx.commit().await?;
+
+            Ok(match previous_window_start.map(Self::time).transpose()? {
+                None => InsertOutcome::Inserted(stored),
+                Some(previous) if !window.contains(previous, seen_at) => {
+                    InsertOutcome::Recurring(stored)
+                }
+                Some(_) => InsertOutcome::Duplicate(stored),
+            })
+        }
+        .boxed()
+    }
+
+    fn lookup(
+        &self,
+        fingerprint: Fr,
+        key_epoch: u64,
+    ) -> BoxFuture<'_, Result<Option<StoredFingerprint>, Error>> {
+        async move {
+            let row = sqlx::query(
+                r#"
+                SELECT fingerprint, key_epoch, first_seen, last_seen, occurrences, window_start
+                FROM fingerprints
+                WHERE fingerprint = ?1 AND key_epoch = ?2
+                "#,
+            )
+            .bind(fingerprint.to_bytes().as_slice())
+            .bind(Self::key_epoch(key_epoch)?)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            row.as_ref().map(Self::stored).transpose()
+        }
+        .boxed()
+    }
+
+    fn first_seen_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Vec<StoredFingerprint>, Error>> {
+        async move {
+            let rows = sqlx::query(
+                r#"
+                SELECT fingerprint, key_epoch, first_seen, last_seen, occurrences, window_start
+                FROM fingerprints
+                WHERE first_seen >= ?1 AND first_seen < ?2
+                "#,
+            )
+            .bind(from.timestamp_micros())
+            .bind(to.timestamp_micros())
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(Self::stored).collect()
+        }
+        .boxed()
+    }
+
+    fn earliest_first_seen(&self) -> BoxFuture<'_, Result<Option<DateTime<Utc>>, Error>> {
+        async move {
+            let earliest: Option<i64> =
+                sqlx::query_scalar("SELECT MIN(first_seen) FROM fingerprints")
+                    .fetch_one(&self.pool)
+                    .await?;
+
+            earliest.map(Self::time).transpose()
+        }
+        .boxed()
+    }
+
+    fn expire(&self, before: DateTime<Utc>) -> BoxFuture<'_, Result<u64, Error>> {
+        async move {
+            let result = sqlx::query("DELETE FROM fingerprints WHERE first_seen < ?1")
+                .bind(before.timestamp_micros())
+                .execute(&self.pool)
+                .await?;
+
+            Ok(result.rows_affected())
+        }
+        .boxed()
+    }
+}
+
+pub struct SqliteUsageLedger {
+    pool: SqlitePool,
+}
+
+impl SqliteUsageLedger {
+    fn usage(row: &SqliteRow) -> Result<Usage, Error> {
+        let fingerprints: i64 = row.try_get("fingerprints")?;
+        let agent_rounds: i64 = row.try_get("agent_rounds")?;
+
+        Ok(Usage {
+            fingerprints: fingerprints as u64,
+            agent_rounds: agent_rounds as u64,
+        })
+    }
+}
+
+impl UsageLedger for SqliteUsageLedger {
+    fn record(
+        &self,
+        name: &str,
+        month: NaiveDate,
+        usage: Usage,
+    ) -> BoxFuture<'_, Result<Usage, Error>> {
+        let name = name.to_string();
+
+        async move {
+            let row = sqlx::query(
+                r#"
+                INSERT INTO api_key_usage (name, month, fingerprints, agent_rounds)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (name, month) DO UPDATE
+                    SET fingerprints = api_key_usage.fingerprints + excluded.fingerprints,
+                        agent_rounds = api_key_usage.agent_rounds + excluded.agent_rounds
+                RETURNING fingerprints, agent_rounds
+                "#,
+            )
+            .bind(&name)
+            .bind(month)
+            .bind(usage.fingerprints as i64)
+            .bind(usage.agent_rounds as i64)
+            .fetch_one(&self.pool)
+            .await?;
+
+            Self::usage(&row)
+        }
+        .boxed()
+    }
+
+    fn usage(&self, name: &str, month: NaiveDate) -> BoxFuture<'_, Result<Usage, Error>> {
+        let name = name.to_string();
+
+        async move {
+            let row = sqlx::query(
+                "SELECT fingerprints, agent_rounds FROM api_key_usage WHERE name = ?1 AND month = ?2",
+            )
+            .bind(&name)
+            .bind(month)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            Ok(row.as_ref().map(Self::usage).transpose()?.unwrap_or_default())
+        }
+        .boxed()
+    }
+
+    fn report(&self, month: NaiveDate) -> BoxFuture<'_, Result<Vec<(String, Usage)>, Error>> {
+        async move {
+            let rows = sqlx::query(
+                r#"
+                SELECT name, fingerprints, agent_rounds
+                FROM api_key_usage
+                WHERE month = ?1
+                ORDER BY name
+                "#,
+            )
+            .bind(month)
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter()
+                .map(|row| Ok((row.try_get("name")?, Self::usage(row)?)))
+                .collect()
+        }
+        .boxed()
+    }
+}
+
+pub struct SqliteTallyLedger {
+    pool: SqlitePool,
+}
+
+impl TallyLedger for SqliteTallyLedger {
+    fn record(&self, epoch: NaiveDate, currency: u16) -> BoxFuture<'_, Result<(), Error>> {
+        async move {
+            sqlx::query(
+                r#"
+                INSERT INTO epoch_tallies (epoch, currency, fingerprints)
+                VALUES (?1, ?2, 1)
+                ON CONFLICT (epoch, currency) DO UPDATE
+                    SET fingerprints = epoch_tallies.fingerprints + 1
+                "#,
+            )
+            .bind(epoch)
+            .bind(currency as i32)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn tallies(&self, epoch: NaiveDate) -> BoxFuture<'_, Result<BTreeMap<u16, u64>, Error>> {
+        async move {
+            let rows =
+                sqlx::query("SELECT currency, fingerprints FROM epoch_tallies WHERE epoch = ?1")
+                    .bind(epoch)
+                    .fetch_all(&self.pool)
+                    .await?;
+
+            rows.iter()
+                .map(|row| {
+                    let currency: i32 = row.try_get("currency")?;
+                    let fingerprints: i64 = row.try_get("fingerprints")?;
+
+                    Ok((u16::try_from(currency)?, fingerprints as u64))
+                })
+                .collect()
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[tokio::test]
+    async fn test_sqlite_duplicate_window() -> Result<(), Error> {
+        let store = SqliteFingerprintStore::connect(":memory:").await?;
+        let window = DuplicateWindow::Within(Duration::days(90));
+        let day = |month, day| Utc.with_ymd_and_hms(2025, month, day, 12, 0, 0).unwrap();
+
+        let first = store.insert(Fr::from(42), 0, day(1, 1), window).await?;
+        assert!(matches!(first, InsertOutcome::Inserted(_)));
+
+        let duplicate = store.insert(Fr::from(42), 0, day(3, 1), window).await?;
+        assert!(duplicate.is_duplicate());
+        assert_eq!(duplicate.stored().last_seen, day(3, 1));
+
+        let recurring = store.insert(Fr::from(42), 0, day(4, 15), window).await?;
+        assert!(matches!(recurring, InsertOutcome::Recurring(_)));
+        assert_eq!(recurring.stored().first_seen, day(1, 1));
+        assert_eq!(recurring.stored().window_start, day(4, 15));
+        assert_eq!(recurring.stored().occurrences, 3);
+
+        // Other key epoch has its own fingerprints
+        assert!(!store
+            .insert(Fr::from(42), 1, day(5, 1), DuplicateWindow::Unbounded)
+            .await?
+            .is_duplicate());
+
+        assert_eq!(store.earliest_first_seen().await?, Some(day(1, 1)));
+        assert_eq!(
+            store.first_seen_between(day(2, 1), day(6, 1)).await?.len(),
+            1
+        );
+        assert_eq!(store.expire(day(2, 1)).await?, 1);
+        assert!(store.lookup(Fr::from(42), 0).await?.is_none());
+        assert!(store.lookup(Fr::from(42), 1).await?.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_usage_ledger() -> Result<(), Error> {
+        let ledger = SqliteFingerprintStore::connect(":memory:")
+            .await?
+            .usage_ledger();
+        let month = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let usage = Usage {
+            fingerprints: 9,
+            agent_rounds: 10,
+        };
+
+        ledger.record("bank-a", month, usage).await?;
+        assert_eq!(ledger.record("bank-a", month, usage).await?, usage + usage);
+        assert_eq!(ledger.usage("bank-a", month).await?, usage + usage);
+        assert_eq!(ledger.usage("bank-b", month).await?, Usage::default());
+        assert_eq!(
+            ledger.report(month).await?,
+            vec![("bank-a".to_string(), usage + usage)]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_tally_ledger() -> Result<(), Error> {
+        let ledger = SqliteFingerprintStore::connect(":memory:")
+            .await?
+            .tally_ledger();
+        let epoch = NaiveDate::from_ymd_opt(2025, 9, 16).unwrap();
+
+        ledger.record(epoch, 978).await?;
+        ledger.record(epoch, 978).await?;
+        ledger.record(epoch, 840).await?;
+        ledger.record(epoch.succ_opt().unwrap(), 978).await?;
+
+        assert_eq!(
+            ledger.tallies(epoch).await?,
+            BTreeMap::from([(840, 1), (978, 2)])
+        );
+        assert!(ledger.tallies(epoch.pred_opt().unwrap()).await?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+//! Tallies of the new fingerprints of every epoch (UTC day) by the currency of the transaction
+//!
+//! The service counts the fingerprint the store has never seen before to the epoch it is stored in, the archive of
+//! the epoch commits to the tallies, see `fingerprinting_verify::statistics`. Only the counts are kept, never the
+//! fingerprints of the currency.
+
+use anyhow::Error;
+use chrono::NaiveDate;
+use futures::future::{ready, BoxFuture, FutureExt};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+pub trait TallyLedger: Send + Sync {
+    /// Counts the new fingerprint of the currency (ISO 4217 numeric code) to the `epoch`
+    fn record(&self, epoch: NaiveDate, currency: u16) -> BoxFuture<'_, Result<(), Error>>;
+
+    /// New fingerprints of the `epoch` by the currency, empty when nothing is recorded
+    fn tallies(&self, epoch: NaiveDate) -> BoxFuture<'_, Result<BTreeMap<u16, u64>, Error>>;
+}
+
+/// Ledger within the single process, suitable for development and testing purposes
+#[derive(Default)]
+pub struct MemoryTallyLedger {
+    epochs: Mutex<BTreeMap<(NaiveDate, u16), u64>>,
+}
+
+impl MemoryTallyLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TallyLedger for MemoryTallyLedger {
+    fn record(&self, epoch: NaiveDate, currency: u16) -> BoxFuture<'_, Result<(), Error>> {
+        *self
+            .epochs
+            .lock()
+            .unwrap()
+            .entry((epoch, currency))
+            .or_default() += 1;
+
+        ready(Ok(())).boxed()
+    }
+
+    fn tallies(&self, epoch: NaiveDate) -> BoxFuture<'_, Result<BTreeMap<u16, u64>, Error>> {
+        let tallies = self
+            .epochs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((recorded, _), _)| *recorded == epoch)
+            .map(|((_, currency), count)| (*currency, *count))
+            .collect();
+
+        ready(Ok(tallies)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tally_ledger() -> Result<(), Error> {
+        let ledger = MemoryTallyLedger::new();
+        let day_1 = NaiveDate::from_ymd_opt(2025, 9, 16).unwrap();
+        let day_2 = day_1.succ_opt().unwrap();
+
+        ledger.record(day_1, 978).await?;
+        ledger.record(day_1, 978).await?;
+        ledger.record(day_1, 840).await?;
+        ledger.record(day_2, 978).await?;
+
+        assert_eq!(
+            ledger.tallies(day_1).await?,
+            BTreeMap::from([(840, 1), (978, 2)])
+        );
+        assert_eq!(ledger.tallies(day_2).await?, BTreeMap::from([(978, 1)]));
+        assert!(ledger.tallies(day_2.succ_opt().unwrap()).await?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Usage of the coordinator by the API keys of the institutions, accounted per calendar month (UTC)
+//!
+//! Every request adds the fingerprints it computed and the agent rounds it consumed to the month of its key.
+//! The coordinators sharing the store share the ledger, so the monthly quotas hold across all of them.
+//! Keys are accounted by their names, the keys themselves are never stored.
+
+use anyhow::Error;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use futures::future::{ready, BoxFuture, FutureExt};
+use std::collections::BTreeMap;
+use std::ops::Add;
+use std::sync::Mutex;
+
+/// Usage of the key within the month
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Fingerprints returned to the key
+    pub fingerprints: u64,
+
+    /// Protocol rounds run with the agents, the failed ones included
+    pub agent_rounds: u64,
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(self, other: Usage) -> Usage {
+        Usage {
+            fingerprints: self.fingerprints.saturating_add(other.fingerprints),
+            agent_rounds: self.agent_rounds.saturating_add(other.agent_rounds),
+        }
+    }
+}
+
+/// First day of the month of `at`, the ledger accounts the usage by it
+pub fn usage_month(at: DateTime<Utc>) -> NaiveDate {
+    at.date_naive()
+        .with_day(1)
+        .expect("First day of the month exists")
+}
+
+pub trait UsageLedger: Send + Sync {
+    /// Adds the `usage` to the month of the key, returns the usage of the month including it
+    fn record(
+        &self,
+        name: &str,
+        month: NaiveDate,
+        usage: Usage,
+    ) -> BoxFuture<'_, Result<Usage, Error>>;
+
+    /// Usage of the key in the month, zero when nothing is recorded
+    fn usage(&self, name: &str, month: NaiveDate) -> BoxFuture<'_, Result<Usage, Error>>;
+
+    /// Usage of all the keys in the month ordered by their names
+    fn report(&self, month: NaiveDate) -> BoxFuture<'_, Result<Vec<(String, Usage)>, Error>>;
+}
+
+/// Ledger within the single process, suitable for development and testing purposes
+#[derive(Default)]
+pub struct MemoryUsageLedger {
+    months: Mutex<BTreeMap<(NaiveDate, String), Usage>>,
+}
+
+impl MemoryUsageLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl UsageLedger for MemoryUsageLedger {
+    fn record(
+        &self,
+        name: &str,
+        month: NaiveDate,
+        usage: Usage,
+    ) -> BoxFuture<'_, Result<Usage, Error>> {
+        let mut months = self.months.lock().unwrap();
+
+        let total = months.entry((month, name.to_string())).or_default();
+        *total = *total + usage;
+
+        ready(Ok(*total)).boxed()
+    }
+
+    fn usage(&self, name: &str, month: NaiveDate) -> BoxFuture<'_, Result<Usage, Error>> {
+        let usage = self
+            .months
+            .lock()
+            .unwrap()
+            .get(&(month, name.to_string()))
+            .copied()
+            .unwrap_or_default();
+
+        ready(Ok(usage)).boxed()
+    }
+
+    fn report(&self, month: NaiveDate) -> BoxFuture<'_, Result<Vec<(String, Usage)>, Error>> {
+        let report = self
+            .months
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((recorded, _), _)| *recorded == month)
+            .map(|((_, name), usage)| (name.clone(), *usage))
+            .collect();
+
+        ready(Ok(report)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_usage_ledger() -> Result<(), Error> {
+        let ledger = MemoryUsageLedger::new();
+        let september = usage_month(Utc.with_ymd_and_hms(2025, 9, 16, 12, 0, 0).unwrap());
+        let october = usage_month(Utc.with_ymd_and_hms(2025, 10, 1, 0, 0, 0).unwrap());
+        assert_eq!(september, NaiveDate::from_ymd_opt(2025, 9, 1).unwrap());
+
+        let batch = Usage {
+            fingerprints: 9,
+            agent_rounds: 10,
+        };
+        ledger.record("bank-b", september, batch).await?;
+        let total = ledger.record("bank-b", september, batch).await?;
+        assert_eq!(total, batch + batch);
+        ledger.record("bank-a", september, batch).await?;
+        ledger.record("bank-a", october, batch).await?;
+
+        assert_eq!(ledger.usage("bank-b", september).await?, total);
+        assert_eq!(ledger.usage("bank-b", october).await?, Usage::default());
+        assert_eq!(
+            ledger.report(september).await?,
+            vec![("bank-a".to_string(), batch), ("bank-b".to_string(), total)]
+        );
+
+        Ok(())
+    }
+}
@@ -2,6 +2,8 @@ use chrono::{DateTime, NaiveDate, Utc};
 use derive_builder::Builder;
 use fixed_num::Dec19x19;
 use fixed_num_helper::FRAC_SCALE_I128;
+use std::error::Error;
+use std::fmt;
 
 // Amount with currency representation
 #[derive(Default, Builder, Debug, Clone, PartialEq)]
@@ -12,16 +14,178 @@ pub struct Money {
     pub currency: String,
 }
 
+// Ordered identifiers of both sides of the transfer
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Counterparty {
+    pub payer: String,
+    pub payee: String,
+}
+
 // Raw Transaction representation
 #[derive(Default, Builder, Debug, Clone, PartialEq)]
 #[builder(setter(into))]
 pub struct RawTransaction {
     pub bic: String,
     pub amount: Money,
+    /// Bought leg of the FX transaction, `amount` is the sold leg then
+    #[builder(default)]
+    pub counter_amount: Option<Money>,
+    /// Standing order or installment plan the transaction belongs to
+    #[builder(default)]
+    pub series_id: Option<String>,
+    /// Merchant or acquirer the transaction is paid to
+    #[builder(default)]
+    pub merchant_id: Option<String>,
+    /// ISO 3166-1 alpha-2 (e.g. `LU`) or numeric (e.g. `442`) code of the jurisdiction the transaction took place in
+    #[builder(default)]
+    pub country_code: Option<String>,
+    /// End-to-end reference (ISO 20022 `EndToEndId`) or remittance id set by the originator
+    #[builder(default)]
+    pub transaction_reference: Option<String>,
+    /// ISO 18245 merchant category code of the card payment, 4 decimal digits (e.g. `5411`)
+    #[builder(default)]
+    pub mcc: Option<String>,
+    /// Payer and payee of the transfer, e.g. their IBANs
+    #[builder(default)]
+    pub counterparty: Option<Counterparty>,
     pub date_time: DateTime<Utc>,
     pub wwd: NaiveDate,
 }
 
+// Maximum sizes of the string fields in bytes, checked before the fields are parsed or hashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputLimits {
+    pub bic: usize,
+    pub currency: usize,
+    pub series_id: usize,
+    pub merchant_id: usize,
+    pub country_code: usize,
+    pub transaction_reference: usize,
+    pub mcc: usize,
+    /// Limit of the payer and of the payee identifier each
+    pub counterparty_id: usize,
+}
+
+impl InputLimits {
+    /// Largest sizes the fingerprint components encode, the configured limits are at most these
+    pub const MAX: InputLimits = InputLimits {
+        bic: 11,
+        currency: 3,
+        series_id: 64,
+        merchant_id: 64,
+        country_code: 3,
+        transaction_reference: 35,
+        mcc: 4,
+        counterparty_id: 64,
+    };
+
+    fn fields(&self) -> [(&'static str, usize); 8] {
+        [
+            ("bic", self.bic),
+            ("currency", self.currency),
+            ("series_id", self.series_id),
+            ("merchant_id", self.merchant_id),
+            ("country_code", self.country_code),
+            ("transaction_reference", self.transaction_reference),
+            ("mcc", self.mcc),
+            ("counterparty_id", self.counterparty_id),
+        ]
+    }
+
+    /// Checks that no limit is above the one of `InputLimits::MAX`
+    pub fn validate(&self) -> Result<(), InputSizeError> {
+        for ((field, size), (_, max)) in self.fields().into_iter().zip(Self::MAX.fields()) {
+            if size > max {
+                return Err(InputSizeError { field, size, max });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self::MAX
+    }
+}
+
+// String field longer than its limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSizeError {
+    pub field: &'static str,
+    pub size: usize,
+    pub max: usize,
+}
+
+impl fmt::Display for InputSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Field {} should be at most {} bytes long, given {} bytes",
+            self.field, self.max, self.size
+        )
+    }
+}
+
+impl Error for InputSizeError {}
+
+impl RawTransaction {
+    /// Checks the sizes of all the string fields against the `limits`, before any of them is parsed or hashed
+    pub fn check_input_sizes(&self, limits: &InputLimits) -> Result<(), InputSizeError> {
+        let check = |field: &'static str, value: &str, max: usize| {
+            if value.len() > max {
+                return Err(InputSizeError {
+                    field,
+                    size: value.len(),
+                    max,
+                });
+            }
+            Ok(())
+        };
+
+        check("bic", &self.bic, limits.bic)?;
+        check("currency", &self.amount.currency, limits.currency)?;
+        if let Some(counter_amount) = &self.counter_amount {
+            check(
+                "counter_amount.currency",
+                &counter_amount.currency,
+                limits.currency,
+            )?;
+        }
+        let optional = [
+            ("series_id", &self.series_id, limits.series_id),
+            ("merchant_id", &self.merchant_id, limits.merchant_id),
+            ("country_code", &self.country_code, limits.country_code),
+            (
+                "transaction_reference",
+                &self.transaction_reference,
+                limits.transaction_reference,
+            ),
+            ("mcc", &self.mcc, limits.mcc),
+        ];
+        for (field, value, max) in optional {
+            if let Some(value) = value {
+                check(field, value, max)?;
+            }
+        }
+        if let Some(counterparty) = &self.counterparty {
+            check(
+                "counterparty.payer",
+                &counterparty.payer,
+                limits.counterparty_id,
+            )?;
+            check(
+                "counterparty.payee",
+                &counterparty.payee,
+                limits.counterparty_id,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 impl From<(Dec19x19, &str)> for Money {
     fn from(value: (Dec19x19, &str)) -> Self {
         let amount = value.0;
@@ -95,4 +259,43 @@ mod tests {
 
         assert_eq!(money_1, money_2);
     }
+
+    #[test]
+    pub fn test_input_sizes() {
+        let date_time = DateTime::from_timestamp(1_758_025_815, 0).unwrap();
+        let transaction = RawTransactionBuilder::default()
+            .bic("BCEELU21")
+            .amount(Money::from((1000u32, "EUR")))
+            .merchant_id(Some("MID-1".to_string()))
+            .date_time(date_time)
+            .wwd(date_time.date_naive())
+            .build()
+            .unwrap();
+        assert!(transaction
+            .check_input_sizes(&InputLimits::default())
+            .is_ok());
+
+        // Megabyte long merchant is refused before it is hashed
+        let oversized = RawTransaction {
+            merchant_id: Some("M".repeat(1024 * 1024)),
+            ..transaction.clone()
+        };
+        let e = oversized
+            .check_input_sizes(&InputLimits::default())
+            .unwrap_err();
+        assert_eq!((e.field, e.max), ("merchant_id", 64));
+
+        let limits = InputLimits {
+            merchant_id: 4,
+            ..InputLimits::default()
+        };
+        assert!(limits.validate().is_ok());
+        assert!(transaction.check_input_sizes(&limits).is_err());
+        assert!(InputLimits {
+            bic: 12,
+            ..InputLimits::default()
+        }
+        .validate()
+        .is_err());
+    }
 }
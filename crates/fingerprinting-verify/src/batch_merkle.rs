@@ -0,0 +1,171 @@
+//! Poseidon Merkle tree over the batch of fingerprints, e.g. the daily batch anchored on-chain
+//!
+//! Leaves are `Poseidon(LEAF_DOMAIN | fingerprint)` in the order of the batch, nodes are
+//! `Poseidon(NODE_DOMAIN | left | right)`, both with the fingerprint hasher of 3 Fr (`fingerprint_poseidon::<4, 3>`),
+//! so the circuits and contracts verifying the fingerprints verify the proofs with the same parameters.
+//! The odd node is promoted to the next level as is. Empty batch has zero root.
+
+use crate::fingerprint_poseidon;
+use halo2curves_axiom::bn256::Fr;
+
+pub const MERKLE_LEAF_DOMAIN_PREFIX: &str = "CRA_FP_MERKLE_LEAF";
+
+pub const MERKLE_NODE_DOMAIN_PREFIX: &str = "CRA_FP_MERKLE_NODE";
+
+/// Sibling of the node on the path from the leaf to the root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchProofStep {
+    pub sibling: Fr,
+    /// Sibling is the left child of the parent node
+    pub sibling_left: bool,
+}
+
+/// Inclusion proof of the fingerprint into the batch root, steps go from the leaf up to the root
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BatchProof {
+    pub steps: Vec<BatchProofStep>,
+}
+
+impl BatchProof {
+    /// Checks that the fingerprint is included into the batch with the given root
+    pub fn verify(&self, root: &Fr, fingerprint: &Fr) -> bool {
+        let computed =
+            self.steps
+                .iter()
+                .fold(leaf(fingerprint), |node, step| match step.sibling_left {
+                    true => node_hash(&step.sibling, &node),
+                    false => node_hash(&node, &step.sibling),
+                });
+
+        &computed == root
+    }
+}
+
+/// Tree over the fingerprints of the batch, all the levels are kept to serve the proofs
+#[derive(Debug, Clone, Default)]
+pub struct BatchTree {
+    /// Levels from the leaves up to the root
+    levels: Vec<Vec<Fr>>,
+}
+
+impl BatchTree {
+    pub fn new(fingerprints: impl IntoIterator<Item = Fr>) -> Self {
+        let leaves = fingerprints
+            .into_iter()
+            .map(|fingerprint| leaf(&fingerprint))
+            .collect::<Vec<_>>();
+        if leaves.is_empty() {
+            return Self::default();
+        }
+
+        let mut levels = vec![leaves];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [odd] => *odd,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Self { levels }
+    }
+
+    /// Number of the fingerprints of the batch
+    pub fn len(&self) -> usize {
+        self.levels.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn root(&self) -> Fr {
+        self.levels.last().map_or(Fr::zero(), |root| root[0])
+    }
+
+    /// Inclusion proof of the fingerprint at the `index` of the batch, absent beyond the batch
+    pub fn proof(&self, index: usize) -> Option<BatchProof> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut index = index;
+        let mut proof = BatchProof::default();
+        for level in &self.levels[..self.levels.len() - 1] {
+            // The odd node has no sibling, it is promoted as is
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.steps.push(BatchProofStep {
+                    sibling: *sibling,
+                    sibling_left: index % 2 == 1,
+                });
+            }
+            index /= 2;
+        }
+
+        Some(proof)
+    }
+}
+
+fn domain(tag: &str) -> Fr {
+    let mut domain = [0u8; 32];
+    domain[0..tag.len()].copy_from_slice(tag.as_bytes());
+
+    Fr::from_bytes(&domain).unwrap_or(Fr::zero())
+}
+
+/// Leaf of the fingerprint, separated by its domain so it never collides with the inner node
+pub fn leaf(fingerprint: &Fr) -> Fr {
+    let mut poseidon = fingerprint_poseidon::<4, 3>();
+    poseidon.update(&[domain(MERKLE_LEAF_DOMAIN_PREFIX), *fingerprint]);
+
+    poseidon.squeeze()
+}
+
+/// Inner node over the children
+pub fn node_hash(left: &Fr, right: &Fr) -> Fr {
+    let mut poseidon = fingerprint_poseidon::<4, 3>();
+    poseidon.update(&[domain(MERKLE_NODE_DOMAIN_PREFIX), *left, *right]);
+
+    poseidon.squeeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_tree() {
+        // Odd number of leaves on the several levels
+        let fingerprints = (0..11).map(Fr::from).collect::<Vec<_>>();
+        let tree = BatchTree::new(fingerprints.clone());
+        assert_eq!(tree.len(), 11);
+        let root = tree.root();
+
+        for (index, fingerprint) in fingerprints.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            assert!(proof.verify(&root, fingerprint));
+            assert!(!proof.verify(&root, &Fr::from(100)));
+        }
+        assert!(tree.proof(11).is_none());
+        assert_ne!(leaf(&Fr::from(1)), Fr::from(1));
+
+        // Root depends on the order of the batch
+        let reversed = BatchTree::new(fingerprints.iter().rev().copied());
+        assert_ne!(reversed.root(), root);
+
+        let single = BatchTree::new([Fr::from(42)]);
+        let proof = single.proof(0).unwrap();
+        assert!(proof.steps.is_empty());
+        assert_eq!(single.root(), leaf(&Fr::from(42)));
+        assert!(proof.verify(&single.root(), &Fr::from(42)));
+
+        let empty = BatchTree::new([]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.root(), Fr::zero());
+        assert!(empty.proof(0).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+//! Fixed-point arithmetic of the amounts: the whole units (base) and the 18 decimal places (atto) of the amount
+//! combined into the single integer of the smallest units
+//!
+//! The encodings before `WireVersion::V4` scale the base by `10 ^ 18`, which is XOR (24) rather than the power, so
+//! the different amounts share the value. The scale is kept as `LEGACY_SCALE` since it is the part of the fingerprints
+//! computed with them. `WireVersion::V4` scales by `ATTO_PER_UNIT` and refuses the atto part of the whole unit or more,
+//! so every amount has the single value and the value converts back to the amount.
+
+use anyhow::{anyhow, Error};
+use bigint::U256;
+
+/// Amount as the whole units and the atto units (10^-18) of the unit
+pub type Amount = (u64, u64);
+
+/// Atto units in the whole unit
+pub const ATTO_PER_UNIT: u64 = 1_000_000_000_000_000_000;
+
+/// Scale of the encodings before `WireVersion::V4`, `10 ^ 18` is XOR (24) rather than the power
+pub const LEGACY_SCALE: u64 = 10 ^ 18;
+
+/// base * scale + atto, fails when it does not fit into 256 bits
+pub fn scale(amount: Amount, scale: u64) -> Result<U256, Error> {
+    let (scaled, overflow) = U256::from(amount.0).overflowing_mul(U256::from(scale));
+    let (full, carry) = scaled.overflowing_add(U256::from(amount.1));
+    if overflow || carry {
+        return Err(anyhow!("Amount scaled by {} overflows 256 bits", scale));
+    }
+
+    Ok(full)
+}
+
+/// Amount in the smallest units of the encodings before `WireVersion::V4`, not injective
+pub fn legacy_units(amount: Amount) -> Result<U256, Error> {
+    scale(amount, LEGACY_SCALE)
+}
+
+/// Amount in the atto units, fails when the atto part is the whole unit or more
+pub fn atto_units(amount: Amount) -> Result<U256, Error> {
+    if amount.1 >= ATTO_PER_UNIT {
+        return Err(anyhow!(
+            "Atto part of the amount should be less than {}",
+            ATTO_PER_UNIT
+        ));
+    }
+
+    scale(amount, ATTO_PER_UNIT)
+}
+
+/// Amount of the atto units, the inverse of `atto_units`, fails when the whole units do not fit into u64
+pub fn from_atto_units(units: U256) -> Result<Amount, Error> {
+    let (base, atto) = (
+        units / U256::from(ATTO_PER_UNIT),
+        units % U256::from(ATTO_PER_UNIT),
+    );
+    if base > U256::from(u64::MAX) {
+        return Err(anyhow!("Whole units of the amount overflow u64"));
+    }
+
+    Ok((base.as_u64(), atto.as_u64()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::{OsRng, RngCore};
+
+    fn random_amount() -> Amount {
+        (OsRng.next_u64(), OsRng.next_u64() % ATTO_PER_UNIT)
+    }
+
+    #[test]
+    fn test_atto_units_round_trip() -> Result<(), Error> {
+        for _ in 0..1000 {
+            let amount = random_amount();
+            assert_eq!(from_atto_units(atto_units(amount)?)?, amount);
+        }
+
+        let max = (u64::MAX, ATTO_PER_UNIT - 1);
+        assert_eq!(from_atto_units(atto_units(max)?)?, max);
+        assert_eq!(atto_units((0, 0))?, U256::zero());
+        assert_eq!(atto_units((1, 0))?, U256::from(ATTO_PER_UNIT));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_atto_units_bounds() -> Result<(), Error> {
+        for _ in 0..1000 {
+            let (base, _) = random_amount();
+            let atto = ATTO_PER_UNIT + OsRng.next_u64() % (u64::MAX - ATTO_PER_UNIT);
+            assert!(atto_units((base, atto)).is_err());
+            // Values of the amounts are ordered like the amounts
+            let (lower, upper) = (random_amount(), random_amount());
+            assert_eq!(
+                atto_units(lower)?.cmp(&atto_units(upper)?),
+                lower.cmp(&upper)
+            );
+        }
+
+        let max = atto_units((u64::MAX, ATTO_PER_UNIT - 1))?;
+        assert!(from_atto_units(max + U256::one()).is_err());
+        assert!(scale((1, 0), u64::MAX).is_ok());
+        assert!(scale((u64::MAX, u64::MAX), u64::MAX).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_units() -> Result<(), Error> {
+        // Legacy scale is part of the V1 fingerprints, distinct amounts share the value
+        assert_eq!(LEGACY_SCALE, 24);
+        assert_eq!(legacy_units((1, 0))?, legacy_units((0, 24))?);
+        for _ in 0..1000 {
+            let amount = (OsRng.next_u64(), OsRng.next_u64());
+            assert_eq!(
+                legacy_units(amount)?,
+                U256::from(amount.0) * U256::from(24) + U256::from(amount.1)
+            );
+        }
+
+        Ok(())
+    }
+}
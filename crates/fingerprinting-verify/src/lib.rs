@@ -0,0 +1,181 @@
+//! Verification-only part of the fingerprinting: Poseidon parameters, byte encodings, Merkle proofs
+//! and statistics commitments of the archived epochs and public-key checks of the VOPRF evaluations
+//!
+//! The crate holds no secret and depends neither on the runtime nor on the halo2 proving machinery,
+//! so auditors and partner chains can verify fingerprints without pulling in the service.
+
+pub mod batch_merkle;
+pub mod fixed_point;
+pub mod merkle;
+pub mod statistics;
+pub mod voprf;
+pub mod wire;
+
+use anyhow::{anyhow, Error};
+use fingerprinting_poseidon::{Poseidon, Spec};
+use halo2curves_axiom::bn256::{Fr, G1};
+use halo2curves_axiom::ff::PrimeField;
+use halo2curves_axiom::group::GroupEncoding;
+use halo2curves_axiom::CurveExt;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+pub use fingerprinting_poseidon as poseidon;
+pub use halo2curves_axiom as halo2curves;
+
+/// Poseidon full rounds of all the fingerprint hashes
+pub const POSEIDON_FULL_ROUNDS: usize = 8;
+
+/// Poseidon partial rounds of all the fingerprint hashes
+pub const POSEIDON_PARTIAL_ROUNDS: usize = 57;
+
+/// Poseidon spec of the fingerprint hashes absorbing `RATE` Fr at once, from the registry of the cached specs,
+/// see `poseidon::cached_spec`. The fingerprints hash 1 Fr (T = 2), 3 Fr (T = 4) and 4 Fr (T = 5) at once
+pub fn fingerprint_spec<const T: usize, const RATE: usize>() -> Arc<Spec<Fr, T, RATE>> {
+    fingerprinting_poseidon::cached_spec(POSEIDON_FULL_ROUNDS, POSEIDON_PARTIAL_ROUNDS)
+}
+
+/// Poseidon hasher of the fingerprint hashes with the cached spec, see `fingerprint_spec`
+pub fn fingerprint_poseidon<const T: usize, const RATE: usize>() -> Poseidon<Fr, T, RATE> {
+    Poseidon::new_with_spec(fingerprint_spec())
+}
+
+/// Generates the constants of all the Poseidon specs ahead of the first hash, e.g. when the service starts
+pub fn init_specs() {
+    fingerprint_spec::<2, 1>();
+    fingerprint_spec::<5, 4>();
+    fingerprint_spec::<4, 3>();
+}
+
+pub const HASH_TO_CURVE_PREFIX: &str = "CRA_FINGERPRINT";
+
+/// Longest domain of the messages mapped onto the curve, see `hash_to_g1`
+pub const MAX_HASH_TO_CURVE_DOMAIN: usize = 64;
+
+/// Digest of the active Poseidon specs (round constants and MDS matrices) and the hash-to-curve domain,
+/// the implementations with the same digest compute the same hashes of the fingerprints
+pub fn parameters_digest() -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    absorb_spec(&mut hasher, &fingerprint_spec::<2, 1>());
+    absorb_spec(&mut hasher, &fingerprint_spec::<5, 4>());
+    absorb_spec(&mut hasher, &fingerprint_spec::<4, 3>());
+    hasher.update((HASH_TO_CURVE_PREFIX.len() as u32).to_be_bytes());
+    hasher.update(HASH_TO_CURVE_PREFIX);
+
+    hasher.finalize().into()
+}
+
+fn absorb_spec<const T: usize, const RATE: usize>(hasher: &mut Sha256, spec: &Spec<Fr, T, RATE>) {
+    let constants = spec.constants();
+    hasher.update([T as u8, RATE as u8, spec.r_f() as u8]);
+    hasher.update((constants.partial().len() as u32).to_be_bytes());
+    for row in constants.start().iter().chain(constants.end()) {
+        row.iter()
+            .for_each(|constant| hasher.update(constant.to_repr()));
+    }
+    constants
+        .partial()
+        .iter()
+        .for_each(|constant| hasher.update(constant.to_repr()));
+    let mds = spec.mds_matrices().mds();
+    for row in 0..T {
+        mds[row]
+            .iter()
+            .for_each(|element| hasher.update(element.to_repr()));
+    }
+}
+
+/// Maps the `message` onto G1 in constant time with the Shallue-van de Woestijne method of RFC 9380
+/// (`expand_message_xmd` with BLAKE2b, two field elements mapped and added, so the point is uniform).
+///
+/// Messages are separated by `HASH_TO_CURVE_PREFIX` and their `domain` as `<prefix>_<domain>`, the domain is
+/// at most `MAX_HASH_TO_CURVE_DOMAIN` bytes. The empty domain is the one of the fingerprint preimages: the protocols
+/// map the unblinded value onto the curve with it, see `hash_to_curve`.
+pub fn hash_to_g1(domain: &str, message: &[u8]) -> Result<G1, Error> {
+    if domain.len() > MAX_HASH_TO_CURVE_DOMAIN {
+        return Err(anyhow!(
+            "Hash to curve domain should be at most {} bytes, got {}",
+            MAX_HASH_TO_CURVE_DOMAIN,
+            domain.len()
+        ));
+    }
+    let domain_prefix = match domain {
+        "" => HASH_TO_CURVE_PREFIX.to_string(),
+        domain => format!("{}_{}", HASH_TO_CURVE_PREFIX, domain),
+    };
+
+    Ok(map_to_g1(&domain_prefix, message))
+}
+
+fn map_to_g1(domain_prefix: &str, message: &[u8]) -> G1 {
+    let hasher = G1::hash_to_curve(domain_prefix);
+    hasher(message)
+}
+
+/// Reflects the unblinded value (squeezed preimage of the fingerprint) on the curve, `hash_to_g1` of the empty
+/// domain. The naive, the collaborative and the VOPRF evaluations all start from this point
+pub fn hash_to_curve(unblinded: &Fr) -> G1 {
+    map_to_g1(HASH_TO_CURVE_PREFIX, &unblinded.to_bytes())
+}
+
+/// Squeezes the evaluated curve point into the fingerprint
+pub fn squeeze_point(point: &G1) -> Fr {
+    // Split the 32 bytes of compressed point to the 2 limbs
+    // Each limb represent as first 16 bytes in 32 bytes array (to be sure that it will fit into Fr
+    // Each of the generated array convert into the Fr
+    // Hash the result and squeeze into single Fr
+    let bytes = point.to_bytes();
+
+    let frs: Vec<Fr> = bytes
+        .as_ref()
+        .chunks(16)
+        .map(|chunk| {
+            let mut buffer_32 = [0u8; 32];
+            buffer_32[0..16].copy_from_slice(chunk);
+
+            Fr::from_bytes(&buffer_32).unwrap_or(Fr::zero())
+        })
+        .collect();
+
+    let mut poseidon = fingerprint_poseidon::<2, 1>();
+    poseidon.update(frs.as_slice());
+
+    poseidon.squeeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameters_digest() {
+        // Pinned, the digest changes only with the parameters and so with the fingerprints
+        assert_eq!(
+            hex::encode(parameters_digest()),
+            "ef1729ca48e7cd2157e37afee5f7f230f70821643ef7b0e42c83af63ae393faa"
+        );
+    }
+
+    #[test]
+    fn test_hash_to_g1() -> Result<(), Error> {
+        let point = hash_to_g1("PREIMAGE", b"fingerprint preimage")?;
+        assert!(bool::from(point.is_on_curve()));
+        assert_eq!(point, hash_to_g1("PREIMAGE", b"fingerprint preimage")?);
+        assert_ne!(point, hash_to_g1("PREIMAGE", b"fingerprint preimage.")?);
+        assert_ne!(point, hash_to_g1("NONCE", b"fingerprint preimage")?);
+        assert_ne!(point, hash_to_g1("", b"fingerprint preimage")?);
+
+        // Unblinded values keep their points, and so the fingerprints
+        let unblinded = Fr::from(42);
+        assert_eq!(
+            hash_to_curve(&unblinded),
+            hash_to_g1("", &unblinded.to_bytes())?
+        );
+
+        let longest = "D".repeat(MAX_HASH_TO_CURVE_DOMAIN);
+        assert!(hash_to_g1(&longest, b"fingerprint preimage").is_ok());
+        assert!(hash_to_g1(&format!("{}D", longest), b"fingerprint preimage").is_err());
+
+        Ok(())
+    }
+}
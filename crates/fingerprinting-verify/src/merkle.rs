@@ -0,0 +1,55 @@
+//! SHA-256 Merkle proofs of the fingerprints included into the archived epoch
+//!
+//! Leaves are `SHA256(0x00 | key_epoch | fingerprint)` ordered by `(key_epoch, fingerprint)`,
+//! nodes are `SHA256(0x01 | left | right)`, the odd node is promoted to the next level as is.
+//! Empty epoch has zero root.
+
+use halo2curves_axiom::bn256::Fr;
+use sha2::{Digest, Sha256};
+
+/// Sibling of the node on the path from the leaf to the root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofStep {
+    pub sibling: [u8; 32],
+    /// Sibling is the left child of the parent node
+    pub sibling_left: bool,
+}
+
+/// Inclusion proof of the fingerprint into the archive root, steps go from the leaf up to the root
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InclusionProof {
+    pub steps: Vec<ProofStep>,
+}
+
+impl InclusionProof {
+    /// Checks that the fingerprint of the key epoch is included into the tree with the given root
+    pub fn verify(&self, root: &[u8; 32], key_epoch: u64, fingerprint: &Fr) -> bool {
+        let computed = self.steps.iter().fold(
+            leaf(key_epoch, &fingerprint.to_bytes()),
+            |node, step| match step.sibling_left {
+                true => parent(&step.sibling, &node),
+                false => parent(&node, &step.sibling),
+            },
+        );
+
+        &computed == root
+    }
+}
+
+/// Leaf of the fingerprint, prefixed so it never collides with the inner node
+pub fn leaf(key_epoch: u64, fingerprint: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(key_epoch.to_be_bytes());
+    hasher.update(fingerprint);
+    <[u8; 32]>::from(hasher.finalize())
+}
+
+/// Inner node over the children
+pub fn parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    <[u8; 32]>::from(hasher.finalize())
+}
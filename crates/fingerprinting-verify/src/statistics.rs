@@ -0,0 +1,99 @@
+//! Poseidon commitments to the statistics of the archived epochs
+//!
+//! Every currency of the epoch is tallied as `Poseidon(STATISTICS_DOMAIN | currency | fingerprints)`, the epoch
+//! commits to the total and to the tallies ordered by the ISO 4217 numeric code of the currency:
+//! `Poseidon(STATISTICS_DOMAIN | total | tally_1 | ... | tally_n)`. The archive publishes the total, the tallies and
+//! the commitment along the root of the epoch, so the volumes reported by the currency are checked against them
+//! without the fingerprints.
+
+use crate::fingerprint_poseidon;
+use halo2curves_axiom::bn256::Fr;
+use std::collections::BTreeMap;
+
+pub const STATISTICS_DOMAIN_PREFIX: &str = "CRA_FP_STATISTICS";
+
+fn domain() -> Fr {
+    let mut domain = [0u8; 32];
+    domain[0..STATISTICS_DOMAIN_PREFIX.len()].copy_from_slice(STATISTICS_DOMAIN_PREFIX.as_bytes());
+
+    Fr::from_bytes(&domain).unwrap_or(Fr::zero())
+}
+
+/// Fingerprints first seen within the epoch by the ISO 4217 numeric code of the currency
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpochStatistics {
+    pub currencies: BTreeMap<u16, u64>,
+}
+
+impl EpochStatistics {
+    pub fn fingerprints(&self) -> u64 {
+        self.currencies
+            .values()
+            .fold(0, |total, count| total.saturating_add(*count))
+    }
+
+    /// Tallies of the currencies ordered by the currency
+    pub fn tallies(&self) -> BTreeMap<u16, Fr> {
+        self.currencies
+            .iter()
+            .map(|(currency, count)| (*currency, tally(*currency, *count)))
+            .collect()
+    }
+
+    pub fn commitment(&self) -> Fr {
+        let tallies = self.tallies().into_values().collect::<Vec<_>>();
+
+        commitment(self.fingerprints(), &tallies)
+    }
+}
+
+/// Tally of the fingerprints of the single currency
+pub fn tally(currency: u16, fingerprints: u64) -> Fr {
+    let mut poseidon = fingerprint_poseidon::<4, 3>();
+    poseidon.update(&[domain(), Fr::from(currency as u64), Fr::from(fingerprints)]);
+
+    poseidon.squeeze()
+}
+
+/// Commitment of the epoch to the total and to the `tallies` ordered by the currency
+pub fn commitment(fingerprints: u64, tallies: &[Fr]) -> Fr {
+    let mut inputs = vec![domain(), Fr::from(fingerprints)];
+    inputs.extend_from_slice(tallies);
+
+    let mut poseidon = fingerprint_poseidon::<5, 4>();
+    poseidon.update(&inputs);
+
+    poseidon.squeeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics_commitment() {
+        let statistics = EpochStatistics {
+            currencies: BTreeMap::from([(978, 120), (840, 3)]),
+        };
+        assert_eq!(statistics.fingerprints(), 123);
+
+        // Auditor holding the published tallies checks the single reported volume and the commitment
+        let published = statistics.tallies();
+        assert_eq!(published[&978], tally(978, 120));
+        assert_ne!(published[&978], tally(978, 121));
+        assert_ne!(published[&978], tally(840, 120));
+        let tallies = published.values().copied().collect::<Vec<_>>();
+        assert_eq!(commitment(123, &tallies), statistics.commitment());
+        assert_ne!(commitment(124, &tallies), statistics.commitment());
+
+        // Tallies are ordered by the currency, the order of the counting does not matter
+        let reordered = EpochStatistics {
+            currencies: [(840, 3), (978, 120)].into_iter().collect(),
+        };
+        assert_eq!(reordered.commitment(), statistics.commitment());
+        assert_ne!(
+            EpochStatistics::default().commitment(),
+            statistics.commitment()
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Public-key checks of the VOPRF evaluations
+//!
+//! Holder of the secret `k` (or of the agent share) publishes the public key `[k] G2`, the evaluation
+//! `[k] P` of the curve point `P` is then checked with the pairing `e(P, [k] G2) = e([k] P, G2)`
+//! without revealing the secret. Blinded evaluations of the agents are checked the same way.
+
+use crate::{hash_to_curve, squeeze_point};
+use anyhow::{anyhow, Error};
+use halo2curves_axiom::bn256::{pairing, Fr, G2Affine, G2Compressed, G1, G2};
+use halo2curves_axiom::group::cofactor::CofactorGroup;
+use halo2curves_axiom::group::{Curve, Group, GroupEncoding};
+
+pub const PUBLIC_KEY_SIZE: usize = 64;
+
+/// Public key of the secret (or of the agent share)
+pub fn public_key(secret: &Fr) -> G2 {
+    G2::generator() * secret
+}
+
+/// Compressed public key
+pub fn encode_public_key(public_key: &G2) -> [u8; PUBLIC_KEY_SIZE] {
+    let mut encoded = [0u8; PUBLIC_KEY_SIZE];
+    encoded.copy_from_slice(public_key.to_bytes().as_ref());
+
+    encoded
+}
+
+/// Decodes the public key, rejecting the points out of the prime order subgroup and the identity
+pub fn decode_public_key(bytes: &[u8]) -> Result<G2, Error> {
+    if bytes.len() != PUBLIC_KEY_SIZE {
+        return Err(anyhow!(
+            "Invalid public key, it should be exactly {} bytes long",
+            PUBLIC_KEY_SIZE
+        ));
+    }
+    let mut point = G2Compressed::default();
+    point.as_mut().copy_from_slice(bytes);
+
+    let public_key = G2::from_bytes(&point)
+        .into_option()
+        .ok_or(anyhow!("Invalid public key, it should be a valid G2 point"))?;
+    check_public_key(&public_key)?;
+
+    Ok(public_key)
+}
+
+/// Identity is the public key of the zero secret, it makes any evaluation to the identity valid
+pub fn check_public_key(public_key: &G2) -> Result<(), Error> {
+    if bool::from(public_key.is_identity()) {
+        return Err(anyhow!("Invalid public key, it should not be the identity"));
+    }
+    if !bool::from(public_key.is_torsion_free()) {
+        return Err(anyhow!(
+            "Invalid public key, it should be in the prime order subgroup"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks that the `evaluation` is `[k] point` for the secret `k` of the `public_key`
+pub fn verify_evaluation(public_key: &G2, point: &G1, evaluation: &G1) -> bool {
+    if bool::from(point.is_identity()) || check_public_key(public_key).is_err() {
+        return false;
+    }
+
+    let lhs = pairing(&point.to_affine(), &public_key.to_affine());
+    let rhs = pairing(&evaluation.to_affine(), &G2Affine::generator());
+
+    lhs == rhs
+}
+
+/// Checks the fingerprint of the unblinded value computed by the protocol, before the paired amount,
+/// series, namespace and salt are applied
+pub fn verify_fingerprint(
+    public_key: &G2,
+    unblinded: &Fr,
+    evaluation: &G1,
+    fingerprint: &Fr,
+) -> bool {
+    verify_evaluation(public_key, &hash_to_curve(unblinded), evaluation)
+        && squeeze_point(evaluation) == *fingerprint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves_axiom::bn256::G1Affine;
+    use halo2curves_axiom::ff::Field;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_public_key_checks() -> Result<(), Error> {
+        let secret = Fr::random(OsRng);
+        let public_key = decode_public_key(&encode_public_key(&public_key(&secret)))?;
+
+        let unblinded = Fr::from(42);
+        let evaluation = hash_to_curve(&unblinded) * secret;
+        let fingerprint = squeeze_point(&evaluation);
+        assert!(verify_fingerprint(
+            &public_key,
+            &unblinded,
+            &evaluation,
+            &fingerprint
+        ));
+
+        // Blinded evaluation of the agent is checked the same way
+        let blinded = hash_to_curve(&unblinded) * Fr::random(OsRng);
+        assert!(verify_evaluation(
+            &public_key,
+            &blinded,
+            &(blinded * secret)
+        ));
+
+        let other = G1Affine::generator() * Fr::random(OsRng);
+        assert!(!verify_fingerprint(
+            &public_key,
+            &unblinded,
+            &other,
+            &fingerprint
+        ));
+        assert!(!verify_fingerprint(
+            &public_key,
+            &Fr::from(7),
+            &evaluation,
+            &fingerprint
+        ));
+        assert!(!verify_fingerprint(
+            &public_key,
+            &unblinded,
+            &evaluation,
+            &Fr::from(7)
+        ));
+
+        assert!(decode_public_key(&encode_public_key(&G2::identity())).is_err());
+        assert!(decode_public_key(&[0u8; 32]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,486 @@
+//! Byte-level encodings of the fingerprinting: component serialization, preimage layout, curve points,
+//! compact (base58) and the other text encodings of the values and the share format
+//!
+//! Fingerprints are only comparable when every implementation encodes the values byte to byte the same way,
+//! so the encodings are versioned. Changing any of them requires the new `WireVersion`.
+//!
+//! The version is the part of the preimage since `WireVersion::V2`, the V1 preimage is the legacy layout without it.
+//! Deployments opt in the later versions explicitly, so upgrading the build never changes the fingerprints.
+
+use crate::fixed_point;
+use anyhow::{anyhow, Error};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bytes::{BufMut, Bytes, BytesMut};
+use halo2curves_axiom::bn256::{Fr, G1Compressed, G1};
+use halo2curves_axiom::ff::PrimeField;
+use halo2curves_axiom::group::GroupEncoding;
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum WireVersion {
+    /// Prefix followed by the components
+    V1 = 1,
+    /// Prefix followed by the version and the components
+    V2 = 2,
+    /// V2 layout, the nonce of the date time packs the seconds and the amount instead of the Cantor pairing
+    V3 = 3,
+    /// V3 layout, the amount is scaled by 10^18 instead of the legacy `fixed_point::LEGACY_SCALE`
+    V4 = 4,
+}
+
+impl WireVersion {
+    /// Version the fingerprints are computed with unless the deployment requests another one
+    pub const CURRENT: WireVersion = WireVersion::V1;
+
+    /// Versions supported by this implementation
+    pub const SUPPORTED: &'static [WireVersion] = &[
+        WireVersion::V1,
+        WireVersion::V2,
+        WireVersion::V3,
+        WireVersion::V4,
+    ];
+
+    /// Highest version supported by both sides, `peer` versions are given as bytes since they could be unknown
+    pub fn negotiate(peer: &[u8]) -> Result<WireVersion, Error> {
+        Self::SUPPORTED
+            .iter()
+            .rev()
+            .find(|version| peer.contains(&version.as_u8()))
+            .copied()
+            .ok_or(anyhow!(
+                "No common wire version, peer supports {:?}, local implementation supports {:?}",
+                peer,
+                Self::SUPPORTED
+            ))
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl TryFrom<u8> for WireVersion {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(WireVersion::V1),
+            2 => Ok(WireVersion::V2),
+            3 => Ok(WireVersion::V3),
+            4 => Ok(WireVersion::V4),
+            _ => Err(anyhow!("Unknown wire version {}", value)),
+        }
+    }
+}
+
+pub const PREIMAGE_PREFIX: [u8; 8] = [0xFF, 0xFE, 0xED, 0xDD, 0xCC, 0x00, 0xDD, 0xEE];
+
+pub const BIC_SIZE: usize = 6;
+pub const AMOUNT_SIZE: usize = 32;
+pub const CURRENCY_SIZE: usize = 2;
+pub const SCALAR_SIZE: usize = 32;
+pub const POINT_SIZE: usize = 32;
+
+pub const PREIMAGE_SIZE: usize =
+    PREIMAGE_PREFIX.len() + BIC_SIZE + AMOUNT_SIZE + CURRENCY_SIZE + SCALAR_SIZE;
+
+/// Size of the version following the prefix of the versioned preimage, the big-endian version keeps the preimage
+/// divisible into the 4 limbs
+pub const VERSION_FIELD_SIZE: usize = 4;
+
+/// Size of the fingerprint schema id following the preimage of the schema other than the built-in one,
+/// the preimage of the schema stays divisible into the 4 limbs
+pub const SCHEMA_ID_SIZE: usize = 4;
+
+// Compiled once BIC format: bank code, country code, location code and the optional branch code
+static BIC_FORMAT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)
+(?P<bank_code>[A-Z]{4})  # 4-letter bank code
+(?P<country_code>[A-Z]{2}) # 2-letter country code
+(?P<location_code>[A-Z0-9]{2}) # 2-character location code
+(?P<branch_code>[A-Z0-9]{3})? # optional 3-character branch code
+$",
+    )
+    .expect("BIC format regex is valid")
+});
+
+/// Validates the BIC format ([A-Z]{4})([A-Z]{2})([A-Z0-9]{2})([A-Z0-9]{3})?$
+pub fn parse_bic(bic: &str) -> Result<Captures<'_>, Error> {
+    BIC_FORMAT
+        .captures(bic)
+        .ok_or(anyhow!("BIC is invalid format, should be BBBBCCLLBRN"))
+}
+
+/// Bank code and country code of the BIC, truncating removes branch-specific details
+/// while maintaining bank identification, normalizing variations from different aggregators
+pub fn encode_bic(bic: &str) -> Result<[u8; BIC_SIZE], Error> {
+    let bic = parse_bic(bic)?;
+
+    let mut encoded = [0u8; BIC_SIZE];
+    encoded[0..4].copy_from_slice(bic["bank_code"].as_bytes());
+    encoded[4..6].copy_from_slice(bic["country_code"].as_bytes());
+
+    Ok(encoded)
+}
+
+/// 256-bit unsigned big-endian integer of the amount in the smallest units,
+/// so there are no decimal formatting differences. Legacy scale of the encodings before `WireVersion::V4`
+pub fn encode_amount(amount: (u64, u64)) -> Result<[u8; AMOUNT_SIZE], Error> {
+    let mut encoded = [0u8; AMOUNT_SIZE];
+    fixed_point::legacy_units(amount)?.to_big_endian(&mut encoded);
+
+    Ok(encoded)
+}
+
+/// Amount encoded as the `version` does, in the atto units since `WireVersion::V4`
+pub fn encode_versioned_amount(
+    version: WireVersion,
+    amount: (u64, u64),
+) -> Result<[u8; AMOUNT_SIZE], Error> {
+    match version {
+        WireVersion::V1 | WireVersion::V2 | WireVersion::V3 => encode_amount(amount),
+        WireVersion::V4 => {
+            let mut encoded = [0u8; AMOUNT_SIZE];
+            fixed_point::atto_units(amount)?.to_big_endian(&mut encoded);
+
+            Ok(encoded)
+        }
+    }
+}
+
+/// ISO 4217 numeric currency code, big-endian
+pub fn encode_currency(currency_code: u16) -> [u8; CURRENCY_SIZE] {
+    currency_code.to_be_bytes()
+}
+
+/// Little-endian canonical representation of the scalar
+pub fn encode_scalar(scalar: &Fr) -> [u8; SCALAR_SIZE] {
+    scalar.to_repr()
+}
+
+pub fn decode_scalar(bytes: &[u8]) -> Result<Fr, Error> {
+    let bytes: [u8; SCALAR_SIZE] = bytes
+        .try_into()
+        .map_err(|_| anyhow!("Invalid scalar, it should be exactly 32 bytes long"))?;
+
+    Fr::from_repr(bytes)
+        .into_option()
+        .ok_or(anyhow!("Invalid scalar, value does not represent Fr"))
+}
+
+/// Compressed curve point
+pub fn encode_point(point: &G1) -> [u8; POINT_SIZE] {
+    let mut encoded = [0u8; POINT_SIZE];
+    encoded.copy_from_slice(point.to_bytes().as_ref());
+
+    encoded
+}
+
+pub fn decode_point(bytes: &[u8]) -> Result<G1, Error> {
+    if bytes.len() != POINT_SIZE {
+        return Err(anyhow!("Invalid point, it should be exactly 32 bytes long"));
+    }
+    let mut point = G1Compressed::default();
+    point.as_mut().copy_from_slice(bytes);
+
+    G1::from_bytes(&point)
+        .into_option()
+        .ok_or(anyhow!("Invalid point, it should be a valid G1 point"))
+}
+
+/// Size of the preimage of the `version` without the schema id
+pub fn preimage_size(version: WireVersion) -> usize {
+    match version {
+        WireVersion::V1 => PREIMAGE_SIZE,
+        WireVersion::V2 | WireVersion::V3 | WireVersion::V4 => PREIMAGE_SIZE + VERSION_FIELD_SIZE,
+    }
+}
+
+/// Serialized components hashed into the unsalted fingerprint, laid out by the `version`
+pub fn preimage(
+    version: WireVersion,
+    bic: &[u8; BIC_SIZE],
+    amount: &[u8; AMOUNT_SIZE],
+    currency: &[u8; CURRENCY_SIZE],
+    date_time: &[u8; SCALAR_SIZE],
+) -> Bytes {
+    let mut buffer = BytesMut::with_capacity(preimage_size(version));
+    buffer.put_slice(&PREIMAGE_PREFIX);
+    if version != WireVersion::V1 {
+        buffer.put_u32(version.as_u8() as u32);
+    }
+    buffer.put_slice(bic);
+    buffer.put_slice(amount);
+    buffer.put_slice(currency);
+    buffer.put_slice(date_time);
+
+    buffer.freeze()
+}
+
+/// Preimage of the fingerprint schema `schema_id`: the serialized components followed by the big-endian schema id,
+/// schema 0 is the built-in layout without the id
+pub fn schema_preimage(preimage: &[u8], schema_id: u32) -> Bytes {
+    if schema_id == 0 {
+        return Bytes::copy_from_slice(preimage);
+    }
+    let mut buffer = BytesMut::with_capacity(preimage.len() + SCHEMA_ID_SIZE);
+    buffer.put_slice(preimage);
+    buffer.put_u32(schema_id);
+
+    buffer.freeze()
+}
+
+/// Base58 representation of the binary value
+pub fn encode_compact(bytes: &[u8]) -> String {
+    bs58::encode(bytes).into_string()
+}
+
+pub fn decode_compact(compacted: &str) -> Result<Vec<u8>, Error> {
+    Ok(bs58::decode(compacted).into_vec()?)
+}
+
+/// Human-readable prefix of the checked text encoding
+pub const CHECKED_PREFIX: &str = "fp1";
+
+const CHECKSUM_SIZE: usize = 4;
+
+/// Text encodings of the binary values, see `encode_text`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextEncoding {
+    /// Base58 without any checksum, the compact representation
+    Base58,
+    /// Lowercase hex
+    Hex,
+    /// URL-safe base64 without the padding
+    Base64Url,
+    /// `CHECKED_PREFIX` followed by the base58 of the bytes and the checksum, see `encode_checked`
+    Base58Check,
+}
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 4] = [
+        TextEncoding::Base58,
+        TextEncoding::Hex,
+        TextEncoding::Base64Url,
+        TextEncoding::Base58Check,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Base58 => "base58",
+            TextEncoding::Hex => "hex",
+            TextEncoding::Base64Url => "base64url",
+            TextEncoding::Base58Check => "base58check",
+        }
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for TextEncoding {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        TextEncoding::ALL
+            .into_iter()
+            .find(|encoding| encoding.name() == name)
+            .ok_or(anyhow!(
+                "Unknown text encoding {}, should be one of {}",
+                name,
+                TextEncoding::ALL.map(|encoding| encoding.name()).join(", ")
+            ))
+    }
+}
+
+pub fn encode_text(encoding: TextEncoding, bytes: &[u8]) -> String {
+    match encoding {
+        TextEncoding::Base58 => encode_compact(bytes),
+        TextEncoding::Hex => hex::encode(bytes),
+        TextEncoding::Base64Url => URL_SAFE_NO_PAD.encode(bytes),
+        TextEncoding::Base58Check => encode_checked(bytes),
+    }
+}
+
+pub fn decode_text(encoding: TextEncoding, encoded: &str) -> Result<Vec<u8>, Error> {
+    match encoding {
+        TextEncoding::Base58 => decode_compact(encoded),
+        TextEncoding::Hex => Ok(hex::decode(encoded)?),
+        TextEncoding::Base64Url => Ok(URL_SAFE_NO_PAD.decode(encoded)?),
+        TextEncoding::Base58Check => decode_checked(encoded),
+    }
+}
+
+/// First bytes of the double SHA-256 of the prefix and the bytes, so neither a mistyped character
+/// nor the value of another prefix passes the check
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let digest = Sha256::digest(
+        Sha256::new()
+            .chain_update(CHECKED_PREFIX)
+            .chain_update(bytes)
+            .finalize(),
+    );
+
+    let mut checksum = [0u8; CHECKSUM_SIZE];
+    checksum.copy_from_slice(&digest[..CHECKSUM_SIZE]);
+    checksum
+}
+
+/// `fp1<base58(bytes | checksum)>`
+pub fn encode_checked(bytes: &[u8]) -> String {
+    let mut checked = bytes.to_vec();
+    checked.extend_from_slice(&checksum(bytes));
+
+    format!("{}{}", CHECKED_PREFIX, encode_compact(&checked))
+}
+
+pub fn decode_checked(encoded: &str) -> Result<Vec<u8>, Error> {
+    let checked = encoded.strip_prefix(CHECKED_PREFIX).ok_or(anyhow!(
+        "Checked value should start with {}",
+        CHECKED_PREFIX
+    ))?;
+    let mut bytes = decode_compact(checked)?;
+    if bytes.len() < CHECKSUM_SIZE {
+        return Err(anyhow!("Checked value is shorter than its checksum"));
+    }
+
+    let claimed = bytes.split_off(bytes.len() - CHECKSUM_SIZE);
+    if claimed != checksum(&bytes) {
+        return Err(anyhow!(
+            "Checksum of the checked value does not match, the value is corrupted"
+        ));
+    }
+
+    Ok(bytes)
+}
+
+/// Share of the agent as `<agent>:<compacted share>`
+pub fn encode_share(agent: usize, share: &Fr) -> String {
+    format!("{}:{}", agent, encode_compact(&encode_scalar(share)))
+}
+
+pub fn decode_share(encoded: &str) -> Result<(usize, Fr), Error> {
+    let (agent, share) = encoded
+        .split_once(':')
+        .ok_or(anyhow!("Share {} should be <agent>:<share>", encoded))?;
+
+    Ok((agent.parse()?, decode_scalar(&decode_compact(share)?)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves_axiom::ff::Field;
+    use halo2curves_axiom::group::Group;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_round_trips() -> Result<(), Error> {
+        let scalar = Fr::random(OsRng);
+        assert_eq!(decode_scalar(&encode_scalar(&scalar))?, scalar);
+        assert!(decode_scalar(&[0xFF; SCALAR_SIZE]).is_err());
+
+        let point = G1::random(OsRng);
+        assert_eq!(decode_point(&encode_point(&point))?, point);
+        assert!(decode_point(&[0u8; 31]).is_err());
+
+        let bytes = [0u8, 1, 2, 255];
+        assert_eq!(decode_compact(&encode_compact(&bytes))?, bytes);
+        for encoding in TextEncoding::ALL {
+            assert_eq!(
+                decode_text(encoding, &encode_text(encoding, &bytes))?,
+                bytes
+            );
+            assert_eq!(encoding.name().parse::<TextEncoding>()?, encoding);
+        }
+        assert_eq!(encode_text(TextEncoding::Hex, &bytes), "000102ff");
+        assert_eq!(encode_text(TextEncoding::Base64Url, &bytes), "AAEC_w");
+
+        // Checked value is refused with any character changed or without the prefix
+        let checked = encode_checked(&encode_scalar(&scalar));
+        assert!(checked.starts_with(CHECKED_PREFIX));
+        assert_eq!(decode_checked(&checked)?, encode_scalar(&scalar));
+        let corrupted = checked
+            .char_indices()
+            .map(|(i, c)| match i {
+                10 if c == '2' => '3',
+                10 => '2',
+                _ => c,
+            })
+            .collect::<String>();
+        assert!(decode_checked(&corrupted).is_err());
+        assert!(decode_checked(&checked[CHECKED_PREFIX.len()..]).is_err());
+        assert!(decode_checked("fp1").is_err());
+
+        assert_eq!(decode_share(&encode_share(3, &scalar))?, (3, scalar));
+        assert!(decode_share("3").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_v1_encodings() -> Result<(), Error> {
+        // Changing any of the values breaks the fingerprints, see `WireVersion`
+        assert_eq!(&encode_bic("BCEELU21XXX")?, b"BCEELU");
+        assert!(encode_bic("bceelu21").is_err());
+        assert_eq!(encode_currency(978), [0x03, 0xd2]);
+        assert_eq!(
+            hex::encode(encode_amount((1000, 550_000_000_000_000_000))?),
+            "00000000000000000000000000000000000000000000000007a1fe1602775dc0"
+        );
+
+        let preimage_of = |version| {
+            Ok::<_, Error>(preimage(
+                version,
+                &encode_bic("BCEELU21")?,
+                &encode_amount((1000, 0))?,
+                &encode_currency(978),
+                &encode_scalar(&Fr::from(42)),
+            ))
+        };
+        let preimage = preimage_of(WireVersion::V1)?;
+        assert_eq!(preimage.len(), PREIMAGE_SIZE);
+        assert_eq!(preimage[..8], PREIMAGE_PREFIX);
+        assert_eq!(schema_preimage(&preimage, 0), preimage);
+        assert_eq!(schema_preimage(&preimage, 2)[PREIMAGE_SIZE..], [0, 0, 0, 2]);
+
+        // Version follows the prefix, the components are encoded the same way
+        let versioned = preimage_of(WireVersion::V2)?;
+        assert_eq!(versioned.len(), preimage_size(WireVersion::V2));
+        assert_eq!(versioned[..8], PREIMAGE_PREFIX);
+        assert_eq!(versioned[8..12], [0, 0, 0, 2]);
+        assert_eq!(versioned[12..], preimage[8..]);
+
+        // Amount in the atto units since V4, the atto part of the whole unit is refused
+        assert_eq!(
+            hex::encode(encode_versioned_amount(
+                WireVersion::V4,
+                (1000, 550_000_000_000_000_000)
+            )?),
+            "0000000000000000000000000000000000000000000000363d6babdbe1170000"
+        );
+        assert_eq!(
+            encode_versioned_amount(WireVersion::V3, (1, 0))?,
+            encode_amount((0, 24))?
+        );
+        assert!(encode_versioned_amount(WireVersion::V4, (0, 1_000_000_000_000_000_000)).is_err());
+
+        assert_eq!(WireVersion::negotiate(&[1, 7])?, WireVersion::V1);
+        assert_eq!(WireVersion::negotiate(&[1, 2])?, WireVersion::V2);
+        assert_eq!(WireVersion::negotiate(&[3, 2, 1])?, WireVersion::V3);
+        assert!(WireVersion::negotiate(&[7]).is_err());
+        assert!(WireVersion::try_from(0).is_err());
+
+        Ok(())
+    }
+}